@@ -0,0 +1,65 @@
+//! Queries over the graph of links between objects.
+//!
+//! These follow the links of each object, as listed by its
+//! [`Links`](crate::Links) implementation, across all the tables of a
+//! context. The tables to follow are listed by an implementation of
+//! [`Collect`], which the [`persian_rug`](crate::persian_rug) macro
+//! generates with its `gc` option.
+//!
+//! ```rust
+//! use persian_rug::{algo, contextual, persian_rug, AnyProxy, Context, Links, Proxy};
+//!
+//! #[derive(Links)]
+//! #[contextual(Rug)]
+//! struct Config {
+//!   key: &'static str,
+//! }
+//!
+//! #[derive(Links)]
+//! #[contextual(Rug)]
+//! struct Service {
+//!   name: &'static str,
+//!   config: Vec<Proxy<Config>>,
+//!   depends: Vec<Proxy<Service>>,
+//! }
+//!
+//! #[persian_rug(gc)]
+//! struct Rug(#[table] Config, #[table] Service);
+//!
+//! let mut r = Rug(Default::default(), Default::default());
+//! let port = r.add(Config { key: "port" });
+//! let path = r.add(Config { key: "path" });
+//! let db = r.add(Service { name: "db", config: vec![port], depends: vec![] });
+//! let web = r.add(Service { name: "web", config: vec![port], depends: vec![db] });
+//! r.add(Service { name: "backup", config: vec![path], depends: vec![db] });
+//!
+//! let reached = algo::reachable(&r, [AnyProxy::from(web)]);
+//! let configs = reached
+//!     .iter()
+//!     .filter_map(|p| p.downcast::<Config>())
+//!     .map(|p| r.get(&p).key)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(configs, vec!["port"]);
+//! assert_eq!(reached.len(), 3);
+//! ```
+
+use std::collections::BTreeSet;
+
+use crate::gc::{self, Collect};
+use crate::AnyProxy;
+
+/// Find every object reachable from `roots`.
+///
+/// The result holds the roots themselves, and every object reached
+/// by following links from them, whatever its type. Links are only
+/// followed out of objects whose table is listed by the [`Collect`]
+/// implementation of the context, but objects of other types are
+/// still included when they are linked to. A link to an object which
+/// has been deleted is included, but leads nowhere, which makes it
+/// easy to spot dangling references.
+pub fn reachable<C: Collect>(
+    context: &C,
+    roots: impl IntoIterator<Item = AnyProxy>,
+) -> BTreeSet<AnyProxy> {
+    gc::reachable(context, roots)
+}
@@ -0,0 +1,373 @@
+//! Zero-copy archives of contexts, using [`rkyv`].
+//!
+//! This module is available with the `rkyv` feature. It makes
+//! [`Proxy`] and [`Table`] archivable, so that a context whose
+//! participating types are all archivable can itself be archived.
+//! An archived context can be accessed directly from the bytes that
+//! hold it (for example, a memory-mapped file) without deserializing
+//! anything.
+//!
+//! Passing `rkyv` to the [`persian_rug`](crate::persian_rug) attribute
+//! derives [`Archive`], [`Serialize`] and [`Deserialize`] for the
+//! context, and implements [`ArchivedContext`] for its archived form,
+//! which gives read-only access to the archived objects through the
+//! same proxies as the original context:
+//!
+//! ```rust
+//! use persian_rug::archive::{ArchivedContext, ArchivedProxy};
+//! use persian_rug::rkyv::{self, rancor::Error, Archive, Deserialize, Serialize};
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[derive(Archive, Serialize, Deserialize)]
+//! #[rkyv(crate = persian_rug::rkyv)]
+//! #[contextual(Rug)]
+//! struct Foo {
+//!   a: i32,
+//!   next: Option<Proxy<Foo>>,
+//! }
+//!
+//! #[persian_rug(rkyv)]
+//! struct Rug(#[table] Foo);
+//!
+//! let mut r = Rug(Default::default());
+//! let f1 = r.add(Foo { a: 1, next: None });
+//! let f2 = r.add(Foo { a: 2, next: Some(f1) });
+//!
+//! let bytes = rkyv::to_bytes::<Error>(&r).unwrap();
+//! let archived = rkyv::access::<ArchivedRug, Error>(&bytes).unwrap();
+//!
+//! let foo = archived.get(&f2);
+//! assert_eq!(foo.a, 2);
+//! let next = foo.next.as_ref().map(ArchivedProxy::proxy).unwrap();
+//! assert_eq!(archived.get(&next).a, 1);
+//! ```
+//!
+//! Archived contexts can also be deserialized back into an ordinary
+//! context, in which all the original proxies remain valid.
+//!
+//! [`rkyv::access`] checks that the bytes hold a valid archive before
+//! returning it, which means reading all of them. For large archives
+//! that the program wrote itself, such as save files, the check can
+//! be skipped with `rkyv::access_unchecked`, so that loading takes
+//! the same time whatever the size of the archive, and objects are
+//! only read from disk when they are used.
+
+use std::marker::PhantomData;
+
+use rkyv::bytecheck::CheckBytes;
+use rkyv::munge::munge;
+use rkyv::rancor::Fallible;
+use rkyv::ser::{Allocator, Writer};
+use rkyv::tuple::ArchivedTuple2;
+use rkyv::vec::{ArchivedVec, VecResolver};
+use rkyv::{Archive, Archived, Deserialize, Place, Portable, Serialize};
+
+use crate::storage::Storage;
+use crate::{Contextual, Proxy, Table};
+
+/// The archived form of a [`Proxy`].
+///
+/// Use [`proxy`](ArchivedProxy::proxy) to recover the original
+/// [`Proxy`], which can then be used to look up objects in either an
+/// archived or an ordinary context.
+#[repr(transparent)]
+pub struct ArchivedProxy<T> {
+    index: Archived<u64>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> ArchivedProxy<T> {
+    /// The proxy this was archived from.
+    pub fn proxy(&self) -> Proxy<T> {
+        Proxy::from_handle(self.index.to_native())
+    }
+}
+
+impl<T> From<&ArchivedProxy<T>> for Proxy<T> {
+    fn from(p: &ArchivedProxy<T>) -> Self {
+        p.proxy()
+    }
+}
+
+impl<T> PartialEq for ArchivedProxy<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for ArchivedProxy<T> {}
+
+impl<T> PartialEq<Proxy<T>> for ArchivedProxy<T> {
+    fn eq(&self, other: &Proxy<T>) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> std::fmt::Debug for ArchivedProxy<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.proxy().fmt(f)
+    }
+}
+
+// Safety: ArchivedProxy is a transparent wrapper around a portable
+// integer.
+unsafe impl<T> Portable for ArchivedProxy<T> {}
+
+// Safety: every bit pattern is a valid archived integer.
+unsafe impl<T, C: Fallible + ?Sized> CheckBytes<C> for ArchivedProxy<T> {
+    unsafe fn check_bytes(_value: *const Self, _context: &mut C) -> Result<(), C::Error> {
+        Ok(())
+    }
+}
+
+impl<T> Archive for Proxy<T> {
+    type Archived = ArchivedProxy<T>;
+    type Resolver = ();
+
+    fn resolve(&self, _resolver: Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let ArchivedProxy { index, _marker } = out);
+        self.index.resolve((), index);
+    }
+}
+
+impl<T, S: Fallible + ?Sized> Serialize<S> for Proxy<T> {
+    fn serialize(&self, _serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        Ok(())
+    }
+}
+
+impl<T, D: Fallible + ?Sized> Deserialize<Proxy<T>, D> for ArchivedProxy<T> {
+    fn deserialize(&self, _deserializer: &mut D) -> Result<Proxy<T>, D::Error> {
+        Ok(self.proxy())
+    }
+}
+
+type ArchivedEntry<T> = ArchivedTuple2<ArchivedProxy<T>, Archived<T>>;
+
+/// The archived form of a [`Table`].
+///
+/// Objects are held in handle order, and looked up by binary search.
+/// The storage used by the original table is not recorded, so tables
+/// can be deserialized with any storage.
+#[derive(Portable, CheckBytes)]
+#[bytecheck(crate = rkyv::bytecheck)]
+#[rkyv(crate = rkyv)]
+#[repr(C)]
+pub struct ArchivedTable<T: Archive> {
+    entries: ArchivedVec<ArchivedEntry<T>>,
+    next_index: Archived<u64>,
+}
+
+impl<T: Archive> ArchivedTable<T> {
+    /// Retrieve an archived object.
+    pub fn get(&self, p: &Proxy<T>) -> Option<&T::Archived> {
+        self.entries
+            .binary_search_by_key(&p.index, |entry| entry.0.index.to_native())
+            .ok()
+            .map(|ix| &self.entries[ix].1)
+    }
+
+    /// The number of archived objects.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if there are no archived objects.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over the archived objects, in handle order.
+    pub fn iter(&self) -> ArchivedTableIterator<'_, T> {
+        ArchivedTableIterator {
+            iter: self.entries.iter(),
+        }
+    }
+
+    /// Iterate over the proxies of the archived objects, in handle
+    /// order.
+    pub fn iter_proxies(&self) -> ArchivedTableProxyIterator<'_, T> {
+        ArchivedTableProxyIterator {
+            iter: self.entries.iter(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for ArchivedTable<T>
+where
+    T: Archive,
+    T::Archived: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.entries.iter().map(|e| (e.0.index.to_native(), &e.1)))
+            .finish()
+    }
+}
+
+/// An [`Iterator`] over the objects in an [`ArchivedTable`].
+pub struct ArchivedTableIterator<'a, T: Archive> {
+    iter: std::slice::Iter<'a, ArchivedEntry<T>>,
+}
+
+impl<'a, T: Archive> Iterator for ArchivedTableIterator<'a, T> {
+    type Item = &'a T::Archived;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|entry| &entry.1)
+    }
+}
+
+/// An [`Iterator`] over the proxies of an [`ArchivedTable`].
+pub struct ArchivedTableProxyIterator<'a, T: Archive> {
+    iter: std::slice::Iter<'a, ArchivedEntry<T>>,
+}
+
+impl<T: Archive> Iterator for ArchivedTableProxyIterator<'_, T> {
+    type Item = Proxy<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|entry| entry.0.proxy())
+    }
+}
+
+/// A borrowed table entry, archived as an [`ArchivedEntry`].
+struct EntryRef<'a, T>(&'a Proxy<T>, &'a T);
+
+impl<T: Archive> Archive for EntryRef<'_, T> {
+    type Archived = ArchivedEntry<T>;
+    type Resolver = T::Resolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let ArchivedTuple2(proxy, value) = out);
+        self.0.resolve((), proxy);
+        self.1.resolve(resolver, value);
+    }
+}
+
+impl<T, S> Serialize<S> for EntryRef<'_, T>
+where
+    T: Serialize<S>,
+    S: Fallible + ?Sized,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        self.1.serialize(serializer)
+    }
+}
+
+impl<T: Archive, St: Storage<T>> Archive for Table<T, St> {
+    type Archived = ArchivedTable<T>;
+    type Resolver = VecResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let ArchivedTable { entries, next_index } = out);
+        ArchivedVec::resolve_from_len(self.storage.len(), resolver, entries);
+        self.handles.next.resolve((), next_index);
+    }
+}
+
+impl<T, St, S> Serialize<S> for Table<T, St>
+where
+    T: Serialize<S>,
+    St: Storage<T>,
+    S: Fallible + Allocator + Writer + ?Sized,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        let mut entries = self
+            .storage
+            .entries()
+            .map(|(p, v)| EntryRef(p, v))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| entry.0.index);
+        ArchivedVec::serialize_from_slice(&entries, serializer)
+    }
+}
+
+impl<T, St, D> Deserialize<Table<T, St>, D> for ArchivedTable<T>
+where
+    T: Archive,
+    T::Archived: Deserialize<T, D>,
+    St: Storage<T> + Default,
+    D: Fallible + ?Sized,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<Table<T, St>, D::Error> {
+        let mut storage = St::default();
+        for entry in self.entries.iter() {
+            storage.insert(entry.0.proxy(), entry.1.deserialize(deserializer)?);
+        }
+        Ok(Table {
+            _marker: Default::default(),
+            storage,
+            handles: crate::handles::Sequential {
+                next: self.next_index.to_native(),
+            },
+            #[cfg(feature = "profiling")]
+            counters: Default::default(),
+            #[cfg(feature = "provenance")]
+            provenance: Default::default(),
+            #[cfg(feature = "observe")]
+            observers: Default::default(),
+            deleted: Default::default(),
+            generations: Default::default(),
+        })
+    }
+}
+
+/// An archived context which holds a table for `T`.
+///
+/// Implementations are generated by the
+/// [`persian_rug`](crate::persian_rug) attribute when given the
+/// `rkyv` option.
+pub trait ArchivedOwner<T: Archive> {
+    /// The archived table of objects of type `T`.
+    fn archived_table(&self) -> &ArchivedTable<T>;
+}
+
+/// Read-only access to an archived context.
+///
+/// This mirrors the read-only part of [`Context`](crate::Context),
+/// returning archived objects. Implementations are generated by the
+/// [`persian_rug`](crate::persian_rug) attribute when given the
+/// `rkyv` option.
+pub trait ArchivedContext {
+    /// The context this is an archive of.
+    type Context: crate::Context;
+
+    /// Get an archived object by its proxy.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the object is not in the archive.
+    fn get<'a, T>(&'a self, what: &Proxy<T>) -> &'a T::Archived
+    where
+        Self: ArchivedOwner<T>,
+        T: Archive + Contextual<Context = Self::Context> + 'a,
+    {
+        self.archived_table().get(what).unwrap()
+    }
+
+    /// Get an archived object by its proxy, if it is in the archive.
+    fn try_get<'a, T>(&'a self, what: &Proxy<T>) -> Option<&'a T::Archived>
+    where
+        Self: ArchivedOwner<T>,
+        T: Archive + Contextual<Context = Self::Context> + 'a,
+    {
+        self.archived_table().get(what)
+    }
+
+    /// Iterate over the archived objects of type `T`.
+    fn get_iter<T>(&self) -> ArchivedTableIterator<'_, T>
+    where
+        Self: ArchivedOwner<T>,
+        T: Archive + Contextual<Context = Self::Context>,
+    {
+        self.archived_table().iter()
+    }
+
+    /// Iterate over the proxies of the archived objects of type `T`.
+    fn get_proxy_iter<T>(&self) -> ArchivedTableProxyIterator<'_, T>
+    where
+        Self: ArchivedOwner<T>,
+        T: Archive + Contextual<Context = Self::Context>,
+    {
+        self.archived_table().iter_proxies()
+    }
+}
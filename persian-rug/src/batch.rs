@@ -0,0 +1,215 @@
+//! Describing changes to a context as data.
+//!
+//! An [`Op`] is one change to a context, such as adding an object or
+//! updating one, held as a value rather than carried out straight
+//! away. A list of them can be built up in one place, and applied in
+//! another with [`Context::apply_batch`] or [`Mutator::apply_batch`].
+//! This suits undo systems, which keep the changes they can reverse,
+//! network protocols, which receive changes to apply, and tests,
+//! which can script the changes they make.
+//!
+//! A batch is checked before any of it is applied: if an operation
+//! refers to an object which is not stored, the whole batch is
+//! rejected with a [`BatchError`] naming that operation, and the
+//! context is left unchanged.
+//!
+//! ```rust
+//! use persian_rug::batch::Op;
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Task {
+//!   title: &'static str,
+//!   done: bool,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Task);
+//!
+//! let mut r = Rug(Default::default());
+//! let write = r.add(Task { title: "write", done: false });
+//!
+//! let added = r.apply_batch(vec![
+//!     Op::update(&write, |task: &mut Task| task.done = true),
+//!     Op::add(Task { title: "review", done: false }),
+//! ]).unwrap();
+//! let review = added[0].downcast::<Task>().unwrap();
+//! assert!(r.get(&write).done);
+//! assert_eq!(r.get(&review).title, "review");
+//!
+//! let stale = Proxy::<Task>::from_handle(9);
+//! let err = r.apply_batch(vec![
+//!     Op::set(&review, Task { title: "review", done: true }),
+//!     Op::set(&stale, Task { title: "lost", done: true }),
+//! ]).unwrap_err();
+//! assert_eq!(err.index(), 1);
+//! assert!(!r.get(&review).done);
+//! ```
+//!
+//! Operations are checked against the context as it was before the
+//! batch, so an operation cannot refer to an object added earlier in
+//! the same batch. If the closure of an update panics, the operations
+//! before it have already been applied.
+//!
+//! [`Mutator::apply_batch`]: crate::Mutator::apply_batch
+
+use crate::checked::Missing;
+use crate::{AnyProxy, Context, Contextual, Owner, Proxy};
+
+/// One change to a context.
+///
+/// See the [module documentation](self) for details.
+#[non_exhaustive]
+pub enum Op<C> {
+    /// Insert a new object.
+    Add(AddOp<C>),
+    /// Change an object which is already stored.
+    Update(UpdateOp<C>),
+}
+
+/// The insertion of an object, made by [`Op::add`].
+pub struct AddOp<C> {
+    type_name: &'static str,
+    apply: Box<dyn FnOnce(&mut C) -> AnyProxy>,
+}
+
+/// The change of a stored object, made by [`Op::update`] or
+/// [`Op::set`].
+pub struct UpdateOp<C> {
+    target: AnyProxy,
+    missing: Missing,
+    contains: fn(&C, &AnyProxy) -> bool,
+    apply: Box<dyn FnOnce(&mut C)>,
+}
+
+impl<C: Context> Op<C> {
+    /// Insert `value`.
+    pub fn add<T>(value: T) -> Self
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + 'static,
+    {
+        Op::Add(AddOp {
+            type_name: std::any::type_name::<T>(),
+            apply: Box::new(move |context| AnyProxy::new(Owner::add(context, value))),
+        })
+    }
+
+    /// Change the object `what` with `f`.
+    pub fn update<T>(what: &Proxy<T>, f: impl FnOnce(&mut T) + 'static) -> Self
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + 'static,
+    {
+        let what = *what;
+        Op::Update(UpdateOp {
+            target: AnyProxy::new(what),
+            missing: Missing::new(&what),
+            contains: |context, target| {
+                target
+                    .downcast()
+                    .is_some_and(|p| Owner::<T>::contains(context, &p))
+            },
+            apply: Box::new(move |context| f(Owner::get_mut(context, &what))),
+        })
+    }
+
+    /// Replace the object `what` with `value`.
+    pub fn set<T>(what: &Proxy<T>, value: T) -> Self
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + 'static,
+    {
+        Self::update(what, move |object| *object = value)
+    }
+
+    /// The full name of the type of object this changes.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Op::Add(op) => op.type_name,
+            Op::Update(op) => op.target.type_name(),
+        }
+    }
+
+    /// The object this changes, if it was already stored.
+    pub fn target(&self) -> Option<AnyProxy> {
+        match self {
+            Op::Add(_) => None,
+            Op::Update(op) => Some(op.target),
+        }
+    }
+
+    fn check(&self, context: &C) -> Result<(), Missing> {
+        match self {
+            Op::Add(_) => Ok(()),
+            Op::Update(op) => {
+                if (op.contains)(context, &op.target) {
+                    Ok(())
+                } else {
+                    Err(op.missing)
+                }
+            }
+        }
+    }
+
+    fn apply(self, context: &mut C) -> Option<AnyProxy> {
+        match self {
+            Op::Add(op) => Some((op.apply)(context)),
+            Op::Update(op) => {
+                (op.apply)(context);
+                None
+            }
+        }
+    }
+}
+
+impl<C> std::fmt::Debug for Op<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Op::Add(op) => write!(f, "Add({})", op.type_name),
+            Op::Update(op) => write!(f, "Update({:?})", op.target),
+        }
+    }
+}
+
+/// The error returned when a batch is rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchError {
+    index: usize,
+    missing: Missing,
+}
+
+impl BatchError {
+    /// The position in the batch of the operation which was rejected.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The object the rejected operation referred to.
+    pub fn missing(&self) -> &Missing {
+        &self.missing
+    }
+}
+
+impl std::fmt::Display for BatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "operation {} of the batch: {}", self.index, self.missing)
+    }
+}
+
+impl std::error::Error for BatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.missing)
+    }
+}
+
+pub(crate) fn apply<C: Context>(
+    context: &mut C,
+    ops: Vec<Op<C>>,
+) -> Result<Vec<AnyProxy>, BatchError> {
+    for (index, op) in ops.iter().enumerate() {
+        op.check(context)
+            .map_err(|missing| BatchError { index, missing })?;
+    }
+    Ok(ops.into_iter().filter_map(|op| op.apply(context)).collect())
+}
@@ -0,0 +1,86 @@
+//! Implementations of the [`borsh`] traits.
+//!
+//! A [`Proxy`] is encoded as its `u64` handle. A [`Table`] is encoded
+//! as the next handle it will issue, followed by its entries as a
+//! `Vec` of `(Proxy<T>, T)` pairs in handle order. The encoding does
+//! not depend on the storage of the table, so the same bytes always
+//! result from the same contents.
+
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::storage::Storage;
+use crate::{Proxy, Table};
+
+impl<T> BorshSerialize for Proxy<T> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        self.index.serialize(writer)
+    }
+}
+
+impl<T> BorshDeserialize for Proxy<T> {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        Ok(Proxy::from_handle(u64::deserialize_reader(reader)?))
+    }
+}
+
+impl<T, S> BorshSerialize for Table<T, S>
+where
+    T: BorshSerialize,
+    S: Storage<T>,
+{
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        self.handles.next.serialize(writer)?;
+
+        let mut entries = self.storage.entries().collect::<Vec<_>>();
+        entries.sort_by_key(|(p, _)| p.index);
+        let len = u32::try_from(entries.len())
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "table too large for borsh"))?;
+        len.serialize(writer)?;
+        for (p, v) in entries {
+            p.serialize(writer)?;
+            v.serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, S> BorshDeserialize for Table<T, S>
+where
+    T: BorshDeserialize,
+    S: Storage<T> + Default,
+{
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        let next_index = u64::deserialize_reader(reader)?;
+        let len = u32::deserialize_reader(reader)?;
+
+        let mut storage = S::default();
+        let mut last = None;
+        for _ in 0..len {
+            let p = Proxy::<T>::deserialize_reader(reader)?;
+            if p.index >= next_index || last.is_some_and(|last| p.index <= last) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unexpected table handle {}", p.index),
+                ));
+            }
+            last = Some(p.index);
+            storage.insert(p, T::deserialize_reader(reader)?);
+        }
+
+        Ok(Table {
+            _marker: Default::default(),
+            storage,
+            handles: crate::handles::Sequential { next: next_index },
+            #[cfg(feature = "profiling")]
+            counters: Default::default(),
+            #[cfg(feature = "provenance")]
+            provenance: Default::default(),
+            #[cfg(feature = "observe")]
+            observers: Default::default(),
+            deleted: Default::default(),
+            generations: Default::default(),
+        })
+    }
+}
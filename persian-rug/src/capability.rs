@@ -0,0 +1,278 @@
+//! Restricting code to the tables it declares.
+//!
+//! A function which takes a whole context can read and change every
+//! object in it, so its signature says nothing about what it actually
+//! touches. The types here let a signature say exactly that. A
+//! [`ReadCap<T>`] is a zero-sized token granting read access to the
+//! table of `T`, and a [`WriteCap<T>`] grants write access. Code
+//! given a [`Restricted`] or [`RestrictedMut`] view of a context
+//! instead of the context itself can only reach a table by presenting
+//! the matching token, and has no way to create tokens of its own:
+//!
+//! ```rust
+//! use persian_rug::capability::{ReadCap, RestrictedMut, WriteCap};
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Account {
+//!   balance: i64,
+//! }
+//!
+//! #[contextual(Rug)]
+//! struct Transfer {
+//!   from: Proxy<Account>,
+//!   to: Proxy<Account>,
+//!   amount: i64,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Account, #[table] Transfer);
+//!
+//! // This can read transfers and change accounts, and nothing else.
+//! fn apply(
+//!     mut r: RestrictedMut<'_, Rug>,
+//!     transfers: ReadCap<Transfer>,
+//!     accounts: WriteCap<Account>,
+//!     transfer: &Proxy<Transfer>,
+//! ) {
+//!     let &Transfer { from, to, amount } = r.get(transfers, transfer);
+//!     r.get_mut(accounts, &from).balance -= amount;
+//!     r.get_mut(accounts, &to).balance += amount;
+//! }
+//!
+//! let mut r = Rug(Default::default(), Default::default());
+//! let a = r.add(Account { balance: 10 });
+//! let b = r.add(Account { balance: 0 });
+//! let t = r.add(Transfer { from: a, to: b, amount: 4 });
+//!
+//! let transfers = ReadCap::grant(&r);
+//! let accounts = WriteCap::grant(&mut r);
+//! apply(RestrictedMut::new(&mut r), transfers, accounts, &t);
+//! assert_eq!(r.get(&a).balance, 6);
+//! assert_eq!(r.get(&b).balance, 4);
+//! ```
+//!
+//! Tokens are granted by whoever holds the context itself: a
+//! [`ReadCap`] needs a shared reference to it, and a [`WriteCap`] an
+//! exclusive one. They can be copied and passed on freely, and a
+//! [`WriteCap`] can stand in wherever a [`ReadCap`] of the same type
+//! is needed. Tokens are checked only by type, so a token granted by
+//! one context also works with another context of the same type.
+
+use crate::{
+    Context, Contextual, Owner, Proxy, TableIterator, TableMutIterator, TableProxyIterator,
+};
+
+/// A token granting read access to the table of `T`.
+///
+/// See the [module documentation](self) for details.
+pub struct ReadCap<T> {
+    _marker: core::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> ReadCap<T> {
+    /// Grant read access to the table of `T` in contexts of the same
+    /// type as `context`.
+    pub fn grant<C>(_context: &C) -> Self
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        Self {
+            _marker: Default::default(),
+        }
+    }
+}
+
+impl<T> Clone for ReadCap<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ReadCap<T> {}
+
+impl<T> std::fmt::Debug for ReadCap<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ReadCap<{}>", std::any::type_name::<T>())
+    }
+}
+
+/// A token granting read and write access to the table of `T`.
+///
+/// See the [module documentation](self) for details.
+pub struct WriteCap<T> {
+    _marker: core::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> WriteCap<T> {
+    /// Grant write access to the table of `T` in contexts of the same
+    /// type as `context`.
+    pub fn grant<C>(_context: &mut C) -> Self
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        Self {
+            _marker: Default::default(),
+        }
+    }
+
+    /// The read access included in this token.
+    pub fn read(&self) -> ReadCap<T> {
+        ReadCap {
+            _marker: Default::default(),
+        }
+    }
+}
+
+impl<T> Clone for WriteCap<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for WriteCap<T> {}
+
+impl<T> std::fmt::Debug for WriteCap<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WriteCap<{}>", std::any::type_name::<T>())
+    }
+}
+
+impl<T> From<WriteCap<T>> for ReadCap<T> {
+    fn from(cap: WriteCap<T>) -> Self {
+        cap.read()
+    }
+}
+
+/// A shared view of a context, whose tables can only be read with a
+/// [`ReadCap`].
+pub struct Restricted<'a, C> {
+    context: &'a C,
+}
+
+impl<'a, C: Context> Restricted<'a, C> {
+    /// Restrict access to `context`.
+    pub fn new(context: &'a C) -> Self {
+        Self { context }
+    }
+
+    /// Retrieve a reference to a value from a [`Proxy`].
+    pub fn get<T>(&self, _cap: impl Into<ReadCap<T>>, what: &Proxy<T>) -> &'a T
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        Owner::get(self.context, what)
+    }
+
+    /// Iterate over the values of type `T`.
+    pub fn get_iter<T>(&self, _cap: impl Into<ReadCap<T>>) -> TableIterator<'a, T>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        Owner::get_iter(self.context)
+    }
+
+    /// Iterate over proxies for the values of type `T`.
+    pub fn get_proxy_iter<T>(&self, _cap: impl Into<ReadCap<T>>) -> TableProxyIterator<'a, T>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        Owner::get_proxy_iter(self.context)
+    }
+}
+
+impl<C> Clone for Restricted<'_, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for Restricted<'_, C> {}
+
+/// An exclusive view of a context, whose tables can only be read
+/// with a [`ReadCap`], and changed with a [`WriteCap`].
+pub struct RestrictedMut<'a, C> {
+    context: &'a mut C,
+}
+
+impl<'a, C: Context> RestrictedMut<'a, C> {
+    /// Restrict access to `context`.
+    pub fn new(context: &'a mut C) -> Self {
+        Self { context }
+    }
+
+    /// A shorter-lived view of the same context, to pass on while
+    /// keeping this one.
+    pub fn reborrow(&mut self) -> RestrictedMut<'_, C> {
+        RestrictedMut {
+            context: self.context,
+        }
+    }
+
+    /// A shared view of the same context.
+    pub fn shared(&self) -> Restricted<'_, C> {
+        Restricted {
+            context: self.context,
+        }
+    }
+
+    /// Insert the given value, returning a [`Proxy`] for it.
+    #[track_caller]
+    pub fn add<T>(&mut self, _cap: WriteCap<T>, value: T) -> Proxy<T>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        Owner::add(self.context, value)
+    }
+
+    /// Retrieve a reference to a value from a [`Proxy`].
+    pub fn get<T>(&self, _cap: impl Into<ReadCap<T>>, what: &Proxy<T>) -> &T
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        Owner::get(self.context, what)
+    }
+
+    /// Retrieve a mutable reference to a value from a [`Proxy`].
+    pub fn get_mut<T>(&mut self, _cap: WriteCap<T>, what: &Proxy<T>) -> &mut T
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        Owner::get_mut(self.context, what)
+    }
+
+    /// Iterate over the values of type `T`.
+    pub fn get_iter<T>(&self, _cap: impl Into<ReadCap<T>>) -> TableIterator<'_, T>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        Owner::get_iter(self.context)
+    }
+
+    /// Mutably iterate over the values of type `T`.
+    pub fn get_iter_mut<T>(&mut self, _cap: WriteCap<T>) -> TableMutIterator<'_, T>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        Owner::get_iter_mut(self.context)
+    }
+
+    /// Iterate over proxies for the values of type `T`.
+    pub fn get_proxy_iter<T>(&self, _cap: impl Into<ReadCap<T>>) -> TableProxyIterator<'_, T>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        Owner::get_proxy_iter(self.context)
+    }
+}
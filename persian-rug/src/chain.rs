@@ -0,0 +1,208 @@
+//! Reading from two contexts through one accessor.
+//!
+//! Some programs keep their objects in several cooperating contexts,
+//! for example a large, rarely changing catalogue alongside a small
+//! context of the current session's objects. Functions which read
+//! from both would need an [`Accessor`] for each. A [`Chain`] instead
+//! holds both accessors, and answers for the objects of either
+//! context, choosing the accessor from the context the object
+//! belongs to:
+//!
+//! ```rust
+//! use persian_rug::chain::Chain;
+//! use persian_rug::{contextual, persian_rug, Accessor, Context, Proxy};
+//!
+//! #[contextual(Catalogue)]
+//! struct Product {
+//!   name: &'static str,
+//! }
+//!
+//! #[persian_rug]
+//! struct Catalogue(#[table] Product);
+//!
+//! #[contextual(Session)]
+//! struct Order {
+//!   product: Proxy<Product>,
+//!   quantity: u32,
+//! }
+//!
+//! #[persian_rug]
+//! struct Session(#[table] Order);
+//!
+//! fn describe(order: &Proxy<Order>, access: Chain<&Catalogue, &Session>) -> String {
+//!     let order = access.get(order);
+//!     format!("{} x {}", order.quantity, access.get(&order.product).name)
+//! }
+//!
+//! let mut catalogue = Catalogue(Default::default());
+//! let mut session = Session(Default::default());
+//! let product = catalogue.add(Product { name: "tea" });
+//! let order = session.add(Order { product, quantity: 2 });
+//!
+//! assert_eq!(describe(&order, (&catalogue).chain(&session)), "2 x tea");
+//! ```
+//!
+//! The two contexts must be different types, since otherwise there
+//! would be no way to choose between them.
+
+use crate::{Accessor, Contextual, Owner, Proxy, TableIterator, TableProxyIterator};
+
+/// Selects the first accessor of a [`Chain`].
+pub enum First {}
+
+/// Selects the second accessor of a [`Chain`].
+pub enum Second {}
+
+/// An accessor for the objects of two contexts.
+///
+/// This is created with [`Accessor::chain`] or [`Chain::new`]. It
+/// does not implement [`Accessor`], which is for a single context,
+/// but it has the same methods for reading objects, which work for
+/// objects belonging to either context. Chains can be nested, as in
+/// `Chain<A, Chain<B, C>>`, to read from more contexts.
+#[derive(Clone, Copy, Debug)]
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> Chain<A, B> {
+    /// Combine accessors for two different contexts.
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+
+    /// The accessor for the first context.
+    pub fn first(&self) -> &A {
+        &self.first
+    }
+
+    /// The accessor for the second context.
+    pub fn second(&self) -> &B {
+        &self.second
+    }
+
+    /// Recover the two accessors.
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+
+    /// Get an object from whichever context it belongs to.
+    ///
+    /// The `W` parameter, which says which context that is, is always
+    /// inferred.
+    pub fn get<T, W>(&self, what: &Proxy<T>) -> &T
+    where
+        Self: Select<T, W>,
+    {
+        Select::get(self, what)
+    }
+
+    /// Iterate over the objects of type `T`, from whichever context
+    /// holds them.
+    pub fn get_iter<T, W>(&self) -> TableIterator<'_, T>
+    where
+        Self: Select<T, W>,
+    {
+        Select::get_iter(self)
+    }
+
+    /// Iterate over the proxies for objects of type `T`, from
+    /// whichever context holds them.
+    pub fn get_proxy_iter<T, W>(&self) -> TableProxyIterator<'_, T>
+    where
+        Self: Select<T, W>,
+    {
+        Select::get_proxy_iter(self)
+    }
+}
+
+/// Read objects of type `T` through a [`Chain`].
+///
+/// `W` is [`First`] or [`Second`], naming the accessor in the chain
+/// whose context holds `T`, or for nested chains, a pair of these
+/// giving the path to it. This trait is implemented by [`Chain`], and
+/// is only useful in bounds.
+pub trait Select<T, W> {
+    /// Get an object from the selected context.
+    fn get(&self, what: &Proxy<T>) -> &T;
+
+    /// Iterate over the objects of type `T` in the selected context.
+    fn get_iter(&self) -> TableIterator<'_, T>;
+
+    /// Iterate over the proxies for objects of type `T` in the
+    /// selected context.
+    fn get_proxy_iter(&self) -> TableProxyIterator<'_, T>;
+}
+
+impl<A, B, T> Select<T, First> for Chain<A, B>
+where
+    A: Accessor,
+    A::Context: Owner<T>,
+    T: Contextual<Context = A::Context>,
+{
+    fn get(&self, what: &Proxy<T>) -> &T {
+        self.first.get(what)
+    }
+
+    fn get_iter(&self) -> TableIterator<'_, T> {
+        self.first.get_iter()
+    }
+
+    fn get_proxy_iter(&self) -> TableProxyIterator<'_, T> {
+        self.first.get_proxy_iter()
+    }
+}
+
+impl<A, B, T> Select<T, Second> for Chain<A, B>
+where
+    B: Accessor,
+    B::Context: Owner<T>,
+    T: Contextual<Context = B::Context>,
+{
+    fn get(&self, what: &Proxy<T>) -> &T {
+        self.second.get(what)
+    }
+
+    fn get_iter(&self) -> TableIterator<'_, T> {
+        self.second.get_iter()
+    }
+
+    fn get_proxy_iter(&self) -> TableProxyIterator<'_, T> {
+        self.second.get_proxy_iter()
+    }
+}
+
+impl<A, B, T, W> Select<T, (First, W)> for Chain<A, B>
+where
+    A: Select<T, W>,
+{
+    fn get(&self, what: &Proxy<T>) -> &T {
+        self.first.get(what)
+    }
+
+    fn get_iter(&self) -> TableIterator<'_, T> {
+        self.first.get_iter()
+    }
+
+    fn get_proxy_iter(&self) -> TableProxyIterator<'_, T> {
+        self.first.get_proxy_iter()
+    }
+}
+
+impl<A, B, T, W> Select<T, (Second, W)> for Chain<A, B>
+where
+    B: Select<T, W>,
+{
+    fn get(&self, what: &Proxy<T>) -> &T {
+        self.second.get(what)
+    }
+
+    fn get_iter(&self) -> TableIterator<'_, T> {
+        self.second.get_iter()
+    }
+
+    fn get_proxy_iter(&self) -> TableProxyIterator<'_, T> {
+        self.second.get_proxy_iter()
+    }
+}
@@ -0,0 +1,118 @@
+//! Resolving proxies without panicking.
+//!
+//! [`Context::get`](crate::Context::get) and its relatives panic if
+//! asked for an object which is not stored. That is the right
+//! behaviour for most code, where a missing object is a bug, but some
+//! services have a policy of never panicking, and must handle a stale
+//! or forged [`Proxy`] as an ordinary error. For them, every way of
+//! resolving a proxy has a `try_` counterpart, which returns a
+//! [`Missing`] error instead:
+//!
+//! - [`Owner::try_get`](crate::Owner::try_get) and
+//!   [`Owner::try_get_mut`](crate::Owner::try_get_mut),
+//! - [`Context::try_get`](crate::Context::try_get) and
+//!   [`Context::try_get_mut`](crate::Context::try_get_mut),
+//! - [`Accessor::try_get`](crate::Accessor::try_get),
+//! - [`Mutator::try_get`](crate::Mutator::try_get) and
+//!   [`Mutator::try_get_mut`](crate::Mutator::try_get_mut).
+//!
+//! ```rust
+//! use persian_rug::checked::Missing;
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Session {
+//!   user: String,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Session);
+//!
+//! fn user_of<'a>(r: &'a Rug, session: &Proxy<Session>) -> Result<&'a str, Missing> {
+//!     Ok(&r.try_get(session)?.user)
+//! }
+//!
+//! let mut r = Rug(Default::default());
+//! let s = r.add(Session { user: "alice".to_string() });
+//! assert_eq!(user_of(&r, &s), Ok("alice"));
+//!
+//! let forged = Proxy::<Session>::from_handle(7);
+//! let err = user_of(&r, &forged).unwrap_err();
+//! assert_eq!(err.handle(), 7);
+//! ```
+//!
+//! The contexts generated by the [`persian_rug`](crate::persian_rug)
+//! macro look objects up once, rather than checking for them and then
+//! fetching them. To make sure the panicking methods are not used by
+//! accident, they can be forbidden with Clippy's
+//! [`disallowed_methods`][disallowed] lint, by listing them in
+//! `clippy.toml`:
+//!
+//! ```toml
+//! disallowed-methods = [
+//!     "persian_rug::Context::get",
+//!     "persian_rug::Context::get_mut",
+//!     "persian_rug::Accessor::get",
+//!     "persian_rug::Mutator::get",
+//!     "persian_rug::Mutator::get_mut",
+//! ]
+//! ```
+//!
+//! [disallowed]: https://rust-lang.github.io/rust-clippy/master/index.html#disallowed_methods
+//!
+//! A context declared with `#[persian_rug(checked)]` routes every
+//! lookup for its tables through the `try_` methods, so the generated
+//! code contains no `unwrap` at all: its
+//! [`Owner::get`](crate::Owner::get) and
+//! [`Owner::get_mut`](crate::Owner::get_mut) call
+//! [`Owner::try_get`](crate::Owner::try_get) and
+//! [`Owner::try_get_mut`](crate::Owner::try_get_mut), and panic with
+//! the [`Missing`] error if they fail. That is all the option
+//! guarantees. Every [`Context`](crate::Context) has to provide the
+//! panicking methods, so a `checked` context still has them, and only
+//! the `try_` methods are free of panics. A service which must never
+//! panic should use only those, and forbid the others with the lint
+//! above.
+
+use crate::Proxy;
+
+/// The error returned when a [`Proxy`] refers to an object which is
+/// not stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Missing {
+    type_name: &'static str,
+    handle: u64,
+}
+
+impl Missing {
+    /// The error for a lookup of `what`.
+    pub fn new<T>(what: &Proxy<T>) -> Self {
+        Self {
+            type_name: std::any::type_name::<T>(),
+            handle: what.index,
+        }
+    }
+
+    /// The name of the type of the missing object, as given by
+    /// [`std::any::type_name`].
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// The handle of the missing object.
+    pub fn handle(&self) -> u64 {
+        self.handle
+    }
+}
+
+impl std::fmt::Display for Missing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "no {} is stored with handle {}",
+            self.type_name, self.handle
+        )
+    }
+}
+
+impl std::error::Error for Missing {}
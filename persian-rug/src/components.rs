@@ -0,0 +1,188 @@
+//! Splitting a context into connected components.
+//!
+//! Two objects are in the same component when one can be reached
+//! from the other by following links, in either direction. A context
+//! which should describe one connected structure, such as a document
+//! or a scene, can be checked for stray islands of objects, and one
+//! which holds many independent structures can be split into them, to
+//! be processed one at a time or in parallel.
+//!
+//! Links are found through the [`Links`] trait, so the tables to
+//! consider are given one at a time to a [`ComponentBuilder`]:
+//!
+//! ```rust
+//! use persian_rug::components::Components;
+//! use persian_rug::{contextual, persian_rug, Context, Links, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Station {
+//!   name: &'static str,
+//! }
+//!
+//! impl Links for Station {}
+//!
+//! #[contextual(Rug)]
+//! struct Track {
+//!   #[link]
+//!   ends: [Proxy<Station>; 2],
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Station, #[table] Track);
+//!
+//! let mut r = Rug(Default::default(), Default::default());
+//! let a = r.add(Station { name: "a" });
+//! let b = r.add(Station { name: "b" });
+//! let c = r.add(Station { name: "c" });
+//! let island = r.add(Station { name: "island" });
+//! r.add(Track { ends: [a, b] });
+//! r.add(Track { ends: [c, b] });
+//!
+//! let components = Components::builder(&r)
+//!     .table::<Station>()
+//!     .table::<Track>()
+//!     .build();
+//! assert_eq!(components.len(), 2);
+//! assert_eq!(components.component_of(&a), components.component_of(&c));
+//!
+//! let lonely = components.component_of(&island).unwrap();
+//! let stations = components.proxies::<Station>(lonely);
+//! assert_eq!(stations.len(), 1);
+//! assert!(stations.contains(&island));
+//! ```
+//!
+//! Links to objects outside the tables given to the builder, or to
+//! objects which are not stored, are ignored. Components are numbered
+//! in the order their first objects were visited, which is the order
+//! in which the tables were given, and then the order of each table.
+
+use std::collections::BTreeMap;
+
+use crate::{AnyProxy, Context, Contextual, Links, Owner, Proxy, ProxySet};
+
+/// Collects the objects and links of a context, to find its
+/// components.
+///
+/// This is created by [`Components::builder`].
+pub struct ComponentBuilder<'a, C> {
+    context: &'a C,
+    objects: BTreeMap<AnyProxy, usize>,
+    order: Vec<AnyProxy>,
+    links: Vec<(AnyProxy, AnyProxy)>,
+}
+
+impl<'a, C: Context> ComponentBuilder<'a, C> {
+    /// Include the objects of type `T`, and the links they hold.
+    pub fn table<T>(mut self) -> Self
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + Links + 'static,
+    {
+        for p in Owner::<T>::get_proxy_iter(self.context) {
+            let from = AnyProxy::new(*p);
+            self.objects.insert(from, self.order.len());
+            self.order.push(from);
+            Owner::get(self.context, p).for_each_link(&mut |to| self.links.push((from, to)));
+        }
+        self
+    }
+
+    /// Label every object included with its component.
+    pub fn build(self) -> Components {
+        let mut parents = (0..self.order.len()).collect::<Vec<_>>();
+        for (from, to) in self.links.iter() {
+            let Some(to) = self.objects.get(to) else {
+                continue;
+            };
+            let a = root(&mut parents, self.objects[from]);
+            let b = root(&mut parents, *to);
+            // Keep the earliest object as the root, so that numbering
+            // follows the order of visiting.
+            parents[a.max(b)] = a.min(b);
+        }
+
+        let mut numbers = BTreeMap::new();
+        let mut members = Vec::<Vec<AnyProxy>>::new();
+        let mut labels = BTreeMap::new();
+        for (position, p) in self.order.iter().enumerate() {
+            let r = root(&mut parents, position);
+            let label = *numbers.entry(r).or_insert_with(|| {
+                members.push(Vec::new());
+                members.len() - 1
+            });
+            members[label].push(*p);
+            labels.insert(*p, label);
+        }
+        Components { labels, members }
+    }
+}
+
+fn root(parents: &mut [usize], mut position: usize) -> usize {
+    while parents[position] != position {
+        parents[position] = parents[parents[position]];
+        position = parents[position];
+    }
+    position
+}
+
+/// The connected components of a context.
+///
+/// See the [module documentation](self) for details.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Components {
+    labels: BTreeMap<AnyProxy, usize>,
+    members: Vec<Vec<AnyProxy>>,
+}
+
+impl Components {
+    /// Start finding the components of `context`.
+    pub fn builder<C: Context>(context: &C) -> ComponentBuilder<'_, C> {
+        ComponentBuilder {
+            context,
+            objects: BTreeMap::new(),
+            order: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+
+    /// The number of components.
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Check whether there are no components, because no objects were
+    /// included.
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// The component containing `p`, if it was included.
+    pub fn component_of<T: 'static>(&self, p: &Proxy<T>) -> Option<usize> {
+        self.labels.get(&AnyProxy::new(*p)).copied()
+    }
+
+    /// The objects in a component, in the order they were visited.
+    ///
+    /// Panics if there is no such component.
+    pub fn members(&self, component: usize) -> &[AnyProxy] {
+        &self.members[component]
+    }
+
+    /// The objects of type `T` in a component.
+    ///
+    /// Panics if there is no such component.
+    pub fn proxies<T: 'static>(&self, component: usize) -> ProxySet<T> {
+        let mut res = ProxySet::new();
+        for p in self.members[component].iter() {
+            if let Some(p) = p.downcast() {
+                res.insert(p);
+            }
+        }
+        res
+    }
+
+    /// Iterate over the components, as lists of their objects.
+    pub fn iter(&self) -> impl Iterator<Item = &[AnyProxy]> {
+        self.members.iter().map(Vec::as_slice)
+    }
+}
@@ -0,0 +1,228 @@
+//! Streaming compression for saved contexts.
+//!
+//! Saved contexts tend to be large and highly repetitive, so they
+//! compress well. This module wraps a [`Write`] or [`BufRead`] so that
+//! anything written to or read from a stream is compressed or
+//! decompressed on the fly, without buffering the whole of it in
+//! memory.
+//!
+//! Two formats are available, each behind a crate feature of the
+//! same name:
+//!
+//! - `zstd`, which gives the best ratio and is a good default for
+//!   archives and deploy artifacts.
+//! - `lz4`, which compresses less but is very fast, and is implemented
+//!   in pure Rust.
+//!
+//! Reading does not require the format to be known in advance:
+//! [`Decoder`] recognises compressed streams by their leading magic
+//! bytes, and passes anything else through unchanged, so that
+//! uncompressed data written by older versions can still be read.
+//!
+//! ```rust
+//! # #[cfg(feature = "zstd")]
+//! # {
+//! use std::io::{Read, Write};
+//! use persian_rug::compression::{Compression, Decoder, Encoder};
+//!
+//! let mut enc = Encoder::new(Vec::new(), Compression::zstd()).unwrap();
+//! enc.write_all(b"hello hello hello hello").unwrap();
+//! let data = enc.finish().unwrap();
+//!
+//! let mut dec = Decoder::new(data.as_slice()).unwrap();
+//! assert_eq!(dec.compression(), Compression::zstd());
+//! let mut text = String::new();
+//! dec.read_to_string(&mut text).unwrap();
+//! assert_eq!(text, "hello hello hello hello");
+//! # }
+//! ```
+
+use std::io::{self, BufRead, Read, Write};
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const LZ4_MAGIC: [u8; 4] = [0x04, 0x22, 0x4d, 0x18];
+
+/// The compression applied to a stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// No compression.
+    #[default]
+    None,
+    /// Zstandard compression at the given level (1 to 22).
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+    /// LZ4 frame compression.
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+impl Compression {
+    /// Zstandard compression at its default level.
+    #[cfg(feature = "zstd")]
+    pub fn zstd() -> Self {
+        Compression::Zstd(zstd::DEFAULT_COMPRESSION_LEVEL)
+    }
+}
+
+enum EncoderInner<W: Write> {
+    None(W),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, W>),
+    #[cfg(feature = "lz4")]
+    Lz4(lz4_flex::frame::FrameEncoder<W>),
+}
+
+/// A writer which compresses everything written to it.
+///
+/// You must call [`finish`](Encoder::finish) once all the data has
+/// been written, to complete the compressed stream.
+pub struct Encoder<W: Write> {
+    inner: EncoderInner<W>,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Start a compressed stream, writing to `writer`.
+    pub fn new(writer: W, compression: Compression) -> io::Result<Self> {
+        let inner = match compression {
+            Compression::None => EncoderInner::None(writer),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) => EncoderInner::Zstd(zstd::Encoder::new(writer, level)?),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => EncoderInner::Lz4(lz4_flex::frame::FrameEncoder::new(writer)),
+        };
+        Ok(Self { inner })
+    }
+
+    /// Complete the compressed stream, returning the underlying writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self.inner {
+            EncoderInner::None(mut w) => {
+                w.flush()?;
+                Ok(w)
+            }
+            #[cfg(feature = "zstd")]
+            EncoderInner::Zstd(e) => e.finish(),
+            #[cfg(feature = "lz4")]
+            EncoderInner::Lz4(e) => e.finish().map_err(io::Error::other),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            EncoderInner::None(w) => w.write(buf),
+            #[cfg(feature = "zstd")]
+            EncoderInner::Zstd(e) => e.write(buf),
+            #[cfg(feature = "lz4")]
+            EncoderInner::Lz4(e) => e.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            EncoderInner::None(w) => w.flush(),
+            #[cfg(feature = "zstd")]
+            EncoderInner::Zstd(e) => e.flush(),
+            #[cfg(feature = "lz4")]
+            EncoderInner::Lz4(e) => e.flush(),
+        }
+    }
+}
+
+enum DecoderInner<R: BufRead> {
+    None(R),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Decoder<'static, R>),
+    #[cfg(feature = "lz4")]
+    Lz4(lz4_flex::frame::FrameDecoder<R>),
+}
+
+/// A reader which decompresses a stream written by [`Encoder`].
+pub struct Decoder<R: BufRead> {
+    inner: DecoderInner<R>,
+}
+
+impl<R: BufRead> Decoder<R> {
+    /// Start reading from `reader`, detecting its compression.
+    ///
+    /// Streams which do not start with a recognised magic number are
+    /// read as they are. A stream compressed with a format whose
+    /// feature is not enabled gives an error of kind
+    /// [`Unsupported`](io::ErrorKind::Unsupported).
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let magic = peek_magic(&mut reader)?;
+        let inner = if magic == ZSTD_MAGIC {
+            #[cfg(feature = "zstd")]
+            {
+                DecoderInner::Zstd(zstd::Decoder::with_buffer(reader)?)
+            }
+            #[cfg(not(feature = "zstd"))]
+            {
+                return Err(unsupported("zstd"));
+            }
+        } else if magic == LZ4_MAGIC {
+            #[cfg(feature = "lz4")]
+            {
+                DecoderInner::Lz4(lz4_flex::frame::FrameDecoder::new(reader))
+            }
+            #[cfg(not(feature = "lz4"))]
+            {
+                return Err(unsupported("lz4"));
+            }
+        } else {
+            DecoderInner::None(reader)
+        };
+        Ok(Self { inner })
+    }
+
+    /// The compression detected in the stream.
+    ///
+    /// For zstd streams, the level reported is the default level,
+    /// since the level used is not recorded in the stream.
+    pub fn compression(&self) -> Compression {
+        match &self.inner {
+            DecoderInner::None(_) => Compression::None,
+            #[cfg(feature = "zstd")]
+            DecoderInner::Zstd(_) => Compression::zstd(),
+            #[cfg(feature = "lz4")]
+            DecoderInner::Lz4(_) => Compression::Lz4,
+        }
+    }
+}
+
+impl<R: BufRead> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            DecoderInner::None(r) => r.read(buf),
+            #[cfg(feature = "zstd")]
+            DecoderInner::Zstd(d) => d.read(buf),
+            #[cfg(feature = "lz4")]
+            DecoderInner::Lz4(d) => d.read(buf),
+        }
+    }
+}
+
+/// Look at the first four bytes of a stream without consuming them.
+///
+/// Shorter streams give a magic number that matches no format.
+fn peek_magic<R: BufRead>(reader: &mut R) -> io::Result<[u8; 4]> {
+    let mut magic = [0u8; 4];
+    let buf = reader.fill_buf()?;
+    if buf.len() >= 4 {
+        magic.copy_from_slice(&buf[..4]);
+    }
+    Ok(magic)
+}
+
+#[cfg(not(all(feature = "zstd", feature = "lz4")))]
+fn unsupported(format: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "stream is {}-compressed, but the {} feature is not enabled",
+            format, format
+        ),
+    )
+}
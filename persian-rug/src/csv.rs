@@ -0,0 +1,127 @@
+//! Exporting the structure of a context as CSV.
+//!
+//! Tools such as pandas and Neo4j can load a graph from a pair of
+//! CSV files: one listing its nodes, and one listing its edges. A
+//! context declared with `#[persian_rug(csv)]` implements
+//! [`CsvExport`], which writes the objects of every table as nodes,
+//! and the links between them as edges. The links of an object are
+//! found with [`Links`], so the types in each table must implement
+//! it, usually by marking fields `#[link]` in the
+//! [`contextual`](crate::contextual) macro.
+//!
+//! ```rust
+//! use persian_rug::csv::CsvExport;
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Person {
+//!   name: String,
+//!   #[link]
+//!   manager: Option<Proxy<Person>>,
+//! }
+//!
+//! #[persian_rug(csv)]
+//! struct Rug(#[table] Person);
+//!
+//! let mut r = Rug(Default::default());
+//! let boss = r.add(Person { name: "Alice".to_string(), manager: None });
+//! r.add(Person { name: "Bob".to_string(), manager: Some(boss) });
+//!
+//! let mut nodes = Vec::new();
+//! let mut edges = Vec::new();
+//! r.write_csv(&mut nodes, &mut edges).unwrap();
+//!
+//! let ty = std::any::type_name::<Person>();
+//! assert_eq!(
+//!     String::from_utf8(nodes).unwrap(),
+//!     format!("type,handle\n{ty},0\n{ty},1\n"),
+//! );
+//! assert_eq!(
+//!     String::from_utf8(edges).unwrap(),
+//!     format!("type,handle,field,target_type,target_handle\n{ty},1,manager,{ty},0\n"),
+//! );
+//! ```
+//!
+//! Types are named with [`std::any::type_name`], and handles are the
+//! indices of the objects' proxies, so together they identify each
+//! object. The field is the name of the field holding the link,
+//! as reported by [`Links::for_each_field_link`].
+
+use std::io::{self, Write};
+
+use crate::{Context, Contextual, Links, Owner};
+
+/// A context which can be exported as CSV.
+///
+/// This is normally implemented with the `csv` option of the
+/// [`persian_rug`](crate::persian_rug) macro.
+pub trait CsvExport: Context {
+    /// Export each table of the context to `csv`.
+    fn describe(csv: &mut CsvTables<'_, Self>) -> io::Result<()>
+    where
+        Self: Sized;
+
+    /// Write the objects of this context to `nodes`, and the links
+    /// between them to `edges`.
+    fn write_csv<N: Write, E: Write>(&self, mut nodes: N, mut edges: E) -> io::Result<()>
+    where
+        Self: Sized,
+    {
+        writeln!(nodes, "type,handle")?;
+        writeln!(edges, "type,handle,field,target_type,target_handle")?;
+        let mut csv = CsvTables {
+            context: self,
+            nodes: &mut nodes,
+            edges: &mut edges,
+        };
+        Self::describe(&mut csv)?;
+        nodes.flush()?;
+        edges.flush()
+    }
+}
+
+/// The tables being exported by [`CsvExport::write_csv`].
+pub struct CsvTables<'a, C> {
+    context: &'a C,
+    nodes: &'a mut dyn Write,
+    edges: &'a mut dyn Write,
+}
+
+impl<C: Context> CsvTables<'_, C> {
+    /// Export the table of objects of type `T`.
+    pub fn table<T>(&mut self) -> io::Result<()>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + Links + 'static,
+    {
+        let ty = field(std::any::type_name::<T>());
+        for p in Owner::<T>::get_proxy_iter(self.context) {
+            writeln!(self.nodes, "{},{}", ty, p.index)?;
+            let mut res = Ok(());
+            Owner::get(self.context, p).for_each_field_link(&mut |name, target| {
+                if res.is_ok() {
+                    res = writeln!(
+                        self.edges,
+                        "{},{},{},{},{}",
+                        ty,
+                        p.index,
+                        field(name),
+                        field(target.type_name()),
+                        target.index()
+                    );
+                }
+            });
+            res?;
+        }
+        Ok(())
+    }
+}
+
+/// Quote `value` for use as a CSV field, if it needs it.
+fn field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{Contextual, Owner, Proxy};
+
+/// A position within a table, which survives changes to the context.
+///
+/// Iterators over a table borrow the context, so they cannot be kept
+/// while the context is changed. A `Cursor` instead remembers its
+/// position by handle, and is given the context each time it moves,
+/// so work on a table can be done incrementally: a background task
+/// can process a batch of objects, release the context so that others
+/// can change it, and later continue where it left off. Objects added
+/// in the meantime are visited when the cursor reaches them, and
+/// objects which are no longer stored are skipped.
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, Cursor};
+///
+/// #[contextual(Rug)]
+/// struct Job {
+///   done: bool,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Job);
+///
+/// let mut r = Rug(Default::default());
+/// for _ in 0..3 {
+///     r.add(Job { done: false });
+/// }
+///
+/// let mut cursor = Cursor::<Job>::new();
+/// for p in cursor.next_batch(&r, 2) {
+///     r.get_mut(&p).done = true;
+/// }
+///
+/// // More work arrives while the cursor is idle.
+/// r.add(Job { done: false });
+///
+/// while let Some(p) = cursor.next(&r) {
+///     r.get_mut(&p).done = true;
+/// }
+/// assert!(r.get_iter::<Job>().all(|job| job.done));
+/// assert!(cursor.at_end(&r));
+/// ```
+pub struct Cursor<T> {
+    next: u64,
+    _marker: core::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Cursor<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Cursor<T> {}
+
+impl<T> PartialEq for Cursor<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.next == other.next
+    }
+}
+
+impl<T> Eq for Cursor<T> {}
+
+impl<T> std::fmt::Debug for Cursor<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cursor<{}>({})", std::any::type_name::<T>(), self.next)
+    }
+}
+
+impl<T> Default for Cursor<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Cursor<T> {
+    /// Create a cursor at the start of the table.
+    pub fn new() -> Self {
+        Self {
+            next: 0,
+            _marker: Default::default(),
+        }
+    }
+
+    /// Create a cursor positioned just after `what`.
+    pub fn after(what: &Proxy<T>) -> Self {
+        Self {
+            next: what.index + 1,
+            _marker: Default::default(),
+        }
+    }
+
+    /// Move to the next object stored in `context`, and return its
+    /// proxy.
+    ///
+    /// Returns [`None`] if there are no more objects, in which case
+    /// the cursor stays where it is, so that objects added later will
+    /// still be visited.
+    pub fn next<C>(&mut self, context: &C) -> Option<Proxy<T>>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        let p = Owner::proxy_from(context, self.next)?;
+        self.next = p.index + 1;
+        Some(p)
+    }
+
+    /// Move past up to `count` objects stored in `context`, and return
+    /// their proxies.
+    pub fn next_batch<C>(&mut self, context: &C, count: usize) -> Vec<Proxy<T>>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        std::iter::from_fn(|| self.next(context))
+            .take(count)
+            .collect()
+    }
+
+    /// Check whether there are no more objects in `context` for the
+    /// cursor to visit.
+    pub fn at_end<C>(&self, context: &C) -> bool
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        let mut cursor = *self;
+        cursor.next(context).is_none()
+    }
+}
@@ -0,0 +1,204 @@
+//! Drawing the objects of a context and the links between them.
+//!
+//! A context declared with `#[persian_rug(diagram)]` implements
+//! [`Diagram`], which collects every object of its tables as a node,
+//! and every link between them as an edge. The links of an object are
+//! found with [`Links`], so the types in each table must implement it,
+//! usually by marking fields `#[link]` in the
+//! [`contextual`](crate::contextual) macro. The collected [`Graph`]
+//! can then be rendered by one of the output modules, the `dot`
+//! module with the `dot` feature and the `mermaid` module with the
+//! `mermaid` feature, or walked directly:
+//!
+//! ```rust
+//! use persian_rug::diagram::{Graph, Labels};
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Person {
+//!   name: String,
+//!   #[link]
+//!   manager: Option<Proxy<Person>>,
+//! }
+//!
+//! #[persian_rug(diagram)]
+//! struct Rug(#[table] Person);
+//!
+//! let mut r = Rug(Default::default());
+//! let boss = r.add(Person { name: "Alice".to_string(), manager: None });
+//! r.add(Person { name: "Bob".to_string(), manager: Some(boss) });
+//!
+//! let graph = Graph::new(&r, &Labels::new().label(|p: &Person| p.name.clone()));
+//! for edge in graph.edges.iter() {
+//!     println!("{} -> {}", graph.id(&edge.from), graph.id(&edge.to));
+//! }
+//! assert_eq!(graph.nodes[0].label, "Alice");
+//! assert_eq!(graph.edges[0].field, "manager");
+//! ```
+//!
+//! Each node is labelled with the name of its type and its handle,
+//! unless a label for its type is registered with [`Labels`]. Links
+//! to objects which are not drawn, because their table is not part
+//! of the diagram or because they have been deleted, are left out.
+
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{AnyProxy, Context, Contextual, Links, Owner};
+
+/// A context which can be drawn as a graph.
+///
+/// This is normally implemented with the `diagram` option of the
+/// [`persian_rug`](crate::persian_rug) macro.
+pub trait Diagram: Context {
+    /// Add each table of the context to `diagram`.
+    fn describe(diagram: &mut DiagramTables<'_, Self>)
+    where
+        Self: Sized;
+}
+
+type Label = Box<dyn Fn(&dyn Any) -> String>;
+
+/// The labels to give the nodes of a diagram.
+#[derive(Default)]
+pub struct Labels {
+    labels: BTreeMap<TypeId, Label>,
+}
+
+impl Labels {
+    /// Create a new set of labels, with none registered.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Label each object of type `T` with `f`.
+    pub fn label<T: 'static>(mut self, f: impl Fn(&T) -> String + 'static) -> Self {
+        self.labels.insert(
+            TypeId::of::<T>(),
+            Box::new(move |value| f(value.downcast_ref().unwrap())),
+        );
+        self
+    }
+}
+
+/// A node of a [`Graph`].
+#[derive(Clone, Debug)]
+pub struct Node {
+    /// The object drawn.
+    pub proxy: AnyProxy,
+    /// The label of the object.
+    pub label: String,
+}
+
+/// An edge of a [`Graph`], from an object to an object it links to.
+#[derive(Clone, Debug)]
+pub struct Edge {
+    /// The object holding the link.
+    pub from: AnyProxy,
+    /// The name of the field holding the link.
+    pub field: String,
+    /// The object linked to.
+    pub to: AnyProxy,
+}
+
+/// The objects of a context and the links between them.
+#[derive(Clone, Debug)]
+pub struct Graph {
+    /// The types of the tables drawn, in the order they were
+    /// described.
+    pub tables: Vec<TypeId>,
+    /// The objects drawn, table by table.
+    pub nodes: Vec<Node>,
+    /// The links between the objects drawn.
+    pub edges: Vec<Edge>,
+}
+
+impl Graph {
+    /// Collect the graph of `context`, labelling its nodes with
+    /// `labels`.
+    pub fn new<C: Diagram>(context: &C, labels: &Labels) -> Self {
+        let mut diagram = DiagramTables::new(context, Some(labels));
+        C::describe(&mut diagram);
+        diagram.finish()
+    }
+
+    /// An identifier for an object, unique within the graph.
+    ///
+    /// This is made of the position of its table and its handle, such
+    /// as `n0_3`, so it is usable as is by most graph languages.
+    pub fn id(&self, proxy: &AnyProxy) -> String {
+        let table = self
+            .tables
+            .iter()
+            .position(|ty| *ty == proxy.type_id())
+            .unwrap_or(self.tables.len());
+        format!("n{}_{}", table, proxy.index())
+    }
+}
+
+/// The tables being drawn for a [`Diagram`].
+pub struct DiagramTables<'a, C> {
+    context: &'a C,
+    labels: Option<&'a Labels>,
+    graph: Graph,
+}
+
+impl<'a, C: Context> DiagramTables<'a, C> {
+    pub(crate) fn new(context: &'a C, labels: Option<&'a Labels>) -> Self {
+        Self {
+            context,
+            labels,
+            graph: Graph {
+                tables: Vec::new(),
+                nodes: Vec::new(),
+                edges: Vec::new(),
+            },
+        }
+    }
+
+    /// The graph of the tables drawn, without the links to objects
+    /// which are not part of it.
+    pub(crate) fn finish(self) -> Graph {
+        let mut graph = self.graph;
+        let nodes = graph
+            .nodes
+            .iter()
+            .map(|node| node.proxy)
+            .collect::<BTreeSet<_>>();
+        graph.edges.retain(|edge| nodes.contains(&edge.to));
+        graph
+    }
+
+    /// Draw the table of objects of type `T`.
+    pub fn table<T>(&mut self)
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + Links + 'static,
+    {
+        self.graph.tables.push(TypeId::of::<T>());
+        let label = self
+            .labels
+            .and_then(|labels| labels.labels.get(&TypeId::of::<T>()));
+        let ty = std::any::type_name::<T>();
+        let ty = ty.split('<').next().unwrap_or(ty);
+        let ty = ty.rsplit("::").next().unwrap_or(ty);
+        for p in Owner::<T>::get_proxy_iter(self.context) {
+            let value = Owner::get(self.context, p);
+            let from = AnyProxy::new(*p);
+            self.graph.nodes.push(Node {
+                proxy: from,
+                label: match label {
+                    Some(label) => label(value),
+                    None => format!("{} {}", ty, p.index),
+                },
+            });
+            value.for_each_field_link(&mut |field, to| {
+                self.graph.edges.push(Edge {
+                    from,
+                    field: field.to_string(),
+                    to,
+                });
+            });
+        }
+    }
+}
@@ -0,0 +1,424 @@
+//! Finding the differences between two contexts.
+//!
+//! [`diff`] compares two contexts of the same type, such as two
+//! snapshots of the same state, object by object. Objects are matched
+//! up by their proxies, so an object which is in only one of the two
+//! contexts was added or removed, and an object which is in both but
+//! compares unequal with [`PartialEq`] was changed. Each difference
+//! holds copies of the values involved, so the [`Diff`] can outlive
+//! the contexts it was computed from.
+//!
+//! The tables to compare are listed by an implementation of
+//! [`Diffable`], which the [`persian_rug`](crate::persian_rug) macro
+//! generates with its `diff` option:
+//!
+//! ```rust
+//! use persian_rug::{contextual, diff, persian_rug, Context};
+//!
+//! #[derive(Clone, Debug, PartialEq)]
+//! #[contextual(Rug)]
+//! struct Setting {
+//!   key: &'static str,
+//!   value: i32,
+//! }
+//!
+//! #[derive(Clone)]
+//! #[persian_rug(diff)]
+//! struct Rug(#[table] Setting);
+//!
+//! let mut r = Rug(Default::default());
+//! let width = r.add(Setting { key: "width", value: 80 });
+//! let tabs = r.add(Setting { key: "tabs", value: 4 });
+//! let before = r.clone();
+//!
+//! r.get_mut(&width).value = 100;
+//! r.delete(&tabs);
+//! let wrap = r.add(Setting { key: "wrap", value: 1 });
+//!
+//! let d = diff::diff(&before, &r);
+//! let settings = d.table::<Setting>().unwrap();
+//! assert_eq!(settings.added, vec![(wrap, Setting { key: "wrap", value: 1 })]);
+//! assert_eq!(settings.removed, vec![(tabs, Setting { key: "tabs", value: 4 })]);
+//! assert_eq!(settings.changed[0].proxy, width);
+//! assert_eq!(settings.changed[0].old.value, 80);
+//! assert_eq!(settings.changed[0].new.value, 100);
+//! assert_eq!(d.len(), 3);
+//! ```
+//!
+//! Objects are compared as a whole, so a change to any of the fields
+//! of an object, including its links, is reported as a change to the
+//! object.
+//!
+//! A [`Diff`] can be replayed onto another context of the same type
+//! with [`Context::apply_patch`], for
+//! example to bring a replica up to date. Added objects are stored
+//! under the same proxies they had, so links between them, and links
+//! to them from changed objects, hold in the patched context too. The
+//! patch is checked against the context first, and if the context has
+//! diverged from the one the diff started from, the context is left
+//! unchanged and every [`Conflict`] is reported:
+//!
+//! ```rust
+//! use persian_rug::{contextual, diff, persian_rug, Context};
+//!
+//! #[derive(Clone, Debug, PartialEq)]
+//! #[contextual(Rug)]
+//! struct Setting {
+//!   key: &'static str,
+//!   value: i32,
+//! }
+//!
+//! #[derive(Clone)]
+//! #[persian_rug(diff)]
+//! struct Rug(#[table] Setting);
+//!
+//! let mut primary = Rug(Default::default());
+//! let width = primary.add(Setting { key: "width", value: 80 });
+//! let mut replica = primary.clone();
+//! let mut stale = primary.clone();
+//!
+//! let before = primary.clone();
+//! primary.get_mut(&width).value = 100;
+//! let wrap = primary.add(Setting { key: "wrap", value: 1 });
+//! let patch = diff::diff(&before, &primary);
+//!
+//! replica.apply_patch(&patch).unwrap();
+//! assert_eq!(replica.get(&width).value, 100);
+//! assert_eq!(replica.get(&wrap).key, "wrap");
+//!
+//! stale.get_mut(&width).value = 120;
+//! let err = stale.apply_patch(&patch).unwrap_err();
+//! assert_eq!(err.conflicts(), &[diff::Conflict::Diverged(width.into())]);
+//! assert_eq!(stale.get(&width).value, 120);
+//! assert_eq!(stale.get_iter::<Setting>().count(), 1);
+//! ```
+//!
+//! Changes which the context already has are skipped, so applying
+//! the same patch twice is harmless.
+
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, BTreeSet};
+use std::marker::PhantomData;
+
+use crate::handles::HandleAllocator;
+use crate::storage::Storage;
+use crate::{AnyProxy, Context, Contextual, Owner, Proxy, Table, TableOwner};
+
+/// A context whose tables can be compared with those of another.
+///
+/// This is normally implemented with the `diff` option of the
+/// [`persian_rug`](crate::persian_rug) macro.
+pub trait Diffable: Context {
+    /// Register each table of the context with `tables`.
+    fn describe(tables: &mut DiffTables<'_, Self>)
+    where
+        Self: Sized;
+}
+
+/// An object which is in both contexts, but differs between them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change<T> {
+    /// The proxy for the object in both contexts.
+    pub proxy: Proxy<T>,
+    /// The object in the first context.
+    pub old: T,
+    /// The object in the second context.
+    pub new: T,
+}
+
+/// The differences between the tables of objects of type `T` of two
+/// contexts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableDiff<T> {
+    /// The objects which are only in the second context, in the order
+    /// of their handles.
+    pub added: Vec<(Proxy<T>, T)>,
+    /// The objects which are only in the first context, in the order
+    /// of their handles.
+    pub removed: Vec<(Proxy<T>, T)>,
+    /// The objects which differ between the contexts, in the order of
+    /// their handles.
+    pub changed: Vec<Change<T>>,
+}
+
+impl<T> TableDiff<T> {
+    /// The number of objects which were added, removed or changed.
+    pub fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.changed.len()
+    }
+
+    /// Check whether the tables are the same.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+type Check<C> = fn(&C, &dyn Any, &mut Vec<Conflict>);
+type Apply<C> = fn(&mut C, &dyn Any);
+
+struct Entry<C> {
+    type_id: TypeId,
+    len: usize,
+    diff: Box<dyn Any>,
+    check: Check<C>,
+    apply: Apply<C>,
+}
+
+/// The differences between two contexts of type `C`.
+///
+/// This is returned by [`diff`].
+pub struct Diff<C> {
+    tables: Vec<Entry<C>>,
+    _marker: PhantomData<fn() -> C>,
+}
+
+impl<C> Diff<C> {
+    /// The differences in the table of objects of type `T`, or
+    /// [`None`] if that table was not compared.
+    pub fn table<T: 'static>(&self) -> Option<&TableDiff<T>> {
+        self.tables
+            .iter()
+            .find(|entry| entry.type_id == TypeId::of::<T>())
+            .and_then(|entry| entry.diff.downcast_ref())
+    }
+
+    /// The number of objects which were added, removed or changed,
+    /// across every table.
+    pub fn len(&self) -> usize {
+        self.tables.iter().map(|entry| entry.len).sum()
+    }
+
+    /// Check whether the contexts are the same.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The tables being compared.
+///
+/// This is passed to [`Diffable::describe`].
+pub struct DiffTables<'a, C> {
+    old: &'a C,
+    new: &'a C,
+    diff: Diff<C>,
+}
+
+impl<C: Context> DiffTables<'_, C> {
+    /// Compare the tables of objects of type `T`.
+    pub fn table<T, S, A>(&mut self)
+    where
+        C: TableOwner<T, Table = Table<T, S, A>>,
+        T: Contextual<Context = C> + Clone + PartialEq + 'static,
+        S: Storage<T>,
+        A: HandleAllocator,
+    {
+        let old = Owner::<T>::get_proxy_iter(self.old)
+            .map(|p| (*p, Owner::get(self.old, p)))
+            .collect::<BTreeMap<_, _>>();
+        let mut diff = TableDiff {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        };
+        let mut present = BTreeSet::new();
+        for p in Owner::<T>::get_proxy_iter(self.new) {
+            present.insert(*p);
+            let new = Owner::get(self.new, p);
+            match old.get(p) {
+                None => diff.added.push((*p, new.clone())),
+                Some(old) if *old != new => diff.changed.push(Change {
+                    proxy: *p,
+                    old: (*old).clone(),
+                    new: new.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for (p, value) in old {
+            if !present.contains(&p) {
+                diff.removed.push((p, value.clone()));
+            }
+        }
+        self.diff.tables.push(Entry {
+            type_id: TypeId::of::<T>(),
+            len: diff.len(),
+            diff: Box::new(diff),
+            check: check::<C, T, S, A>,
+            apply: apply::<C, T, S, A>,
+        });
+    }
+}
+
+fn check<C, T, S, A>(context: &C, diff: &dyn Any, conflicts: &mut Vec<Conflict>)
+where
+    C: TableOwner<T, Table = Table<T, S, A>>,
+    T: Contextual<Context = C> + PartialEq + 'static,
+    S: Storage<T>,
+    A: HandleAllocator,
+{
+    let diff: &TableDiff<T> = diff.downcast_ref().unwrap();
+    let table = context.get_table();
+    let mut freed = BTreeMap::new();
+    for (p, old) in diff.removed.iter() {
+        match table.get(p) {
+            Some(value) if value == old => {
+                freed.insert(p.index, p.generation.wrapping_add(1));
+            }
+            Some(_) => conflicts.push(Conflict::Diverged(AnyProxy::new(*p))),
+            None => {}
+        }
+    }
+    for change in diff.changed.iter() {
+        match table.get(&change.proxy) {
+            Some(value) if *value == change.old || *value == change.new => {}
+            Some(_) => conflicts.push(Conflict::Diverged(AnyProxy::new(change.proxy))),
+            None => conflicts.push(Conflict::Missing(AnyProxy::new(change.proxy))),
+        }
+    }
+    for (p, new) in diff.added.iter() {
+        let generation = match freed.get(&p.index) {
+            Some(generation) => *generation,
+            None if table.storage.get(p.index).is_some() => {
+                if table.get(p) != Some(new) {
+                    conflicts.push(Conflict::Occupied(AnyProxy::new(*p)));
+                }
+                continue;
+            }
+            None => table.generations.get(&p.index).copied().unwrap_or(0),
+        };
+        // A proxy from before the handle's current generation would
+        // bring stale proxies in the context back to life.
+        if p.generation < generation {
+            conflicts.push(Conflict::Diverged(AnyProxy::new(*p)));
+        }
+    }
+}
+
+fn apply<C, T, S, A>(context: &mut C, diff: &dyn Any)
+where
+    C: TableOwner<T, Table = Table<T, S, A>>,
+    T: Contextual<Context = C> + Clone + PartialEq + 'static,
+    S: Storage<T>,
+    A: HandleAllocator,
+{
+    let diff: &TableDiff<T> = diff.downcast_ref().unwrap();
+    let table = context.get_table_mut();
+    for (p, _) in diff.removed.iter() {
+        table.delete(p);
+    }
+    for change in diff.changed.iter() {
+        if let Some(value) = table.get_mut(&change.proxy) {
+            *value = change.new.clone();
+        }
+    }
+    for (p, new) in diff.added.iter() {
+        if !table.contains(p) {
+            table
+                .insert_with_proxy(*p, new.clone())
+                .expect("patch was checked before it was applied");
+        }
+    }
+}
+
+/// Find the differences between `old` and `new`.
+pub fn diff<C: Diffable>(old: &C, new: &C) -> Diff<C> {
+    let mut tables = DiffTables {
+        old,
+        new,
+        diff: Diff {
+            tables: Vec::new(),
+            _marker: PhantomData,
+        },
+    };
+    C::describe(&mut tables);
+    tables.diff
+}
+
+/// A part of a [`Diff`] which does not fit the context it is being
+/// applied to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Conflict {
+    /// The object to add has a handle which the context already uses
+    /// for a different object.
+    Occupied(AnyProxy),
+    /// The object to change is not in the context.
+    Missing(AnyProxy),
+    /// The object to change or remove differs from the old value in
+    /// the diff, or the object to add would reuse a proxy the context
+    /// has already made stale.
+    Diverged(AnyProxy),
+}
+
+impl Conflict {
+    /// The object the conflict is about.
+    pub fn proxy(&self) -> &AnyProxy {
+        match self {
+            Self::Occupied(p) | Self::Missing(p) | Self::Diverged(p) => p,
+        }
+    }
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let p = self.proxy();
+        match self {
+            Self::Occupied(_) => write!(f, "handle {} of {} is in use", p.index(), p.type_name()),
+            Self::Missing(_) => write!(
+                f,
+                "no {} is stored with handle {}",
+                p.type_name(),
+                p.index()
+            ),
+            Self::Diverged(_) => write!(
+                f,
+                "the {} with handle {} has diverged",
+                p.type_name(),
+                p.index()
+            ),
+        }
+    }
+}
+
+/// The error returned when a [`Diff`] cannot be applied to a context.
+///
+/// This is returned by
+/// [`Context::apply_patch`], and lists
+/// every conflict found, table by table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatchError {
+    conflicts: Vec<Conflict>,
+}
+
+impl PatchError {
+    /// The parts of the diff which do not fit the context.
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+}
+
+impl std::fmt::Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} conflicts, the first being: {}",
+            self.conflicts.len(),
+            self.conflicts[0]
+        )
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+pub(crate) fn apply_patch<C: Diffable>(context: &mut C, diff: &Diff<C>) -> Result<(), PatchError> {
+    let mut conflicts = Vec::new();
+    for entry in diff.tables.iter() {
+        (entry.check)(context, &*entry.diff, &mut conflicts);
+    }
+    if !conflicts.is_empty() {
+        return Err(PatchError { conflicts });
+    }
+    for entry in diff.tables.iter() {
+        (entry.apply)(context, &*entry.diff);
+    }
+    Ok(())
+}
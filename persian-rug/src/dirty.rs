@@ -0,0 +1,207 @@
+//! Tracking which objects have changed.
+//!
+//! Incremental work, such as redrawing only what changed on screen,
+//! or saving only the objects which changed since the last save,
+//! needs to know which objects have been touched. [`DirtyStorage`] is
+//! a [`Storage`] which records this as a side effect of the table
+//! being used: an object becomes dirty when it is added, and whenever
+//! it is borrowed mutably. The table holding a type is given dirty
+//! tracking with `#[table(dirty)]` in its
+//! [`persian_rug`](crate::persian_rug) context, and the dirty objects
+//! are collected, and the record cleared, with
+//! [`Context::take_dirty`]:
+//!
+//! ```rust
+//! use persian_rug::{contextual, persian_rug, Context};
+//!
+//! #[contextual(Rug)]
+//! struct Widget {
+//!   label: &'static str,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table(dirty)] Widget);
+//!
+//! let mut r = Rug(Default::default());
+//! let ok = r.add(Widget { label: "OK" });
+//! let cancel = r.add(Widget { label: "Cancel" });
+//! assert_eq!(r.take_dirty::<Widget>().len(), 2);
+//!
+//! r.get_mut(&ok).label = "Yes";
+//! let _ = r.get(&cancel);
+//! let dirty = r.take_dirty::<Widget>();
+//! assert!(dirty.contains(&ok));
+//! assert!(!dirty.contains(&cancel));
+//! assert!(r.take_dirty::<Widget>().is_empty());
+//! ```
+//!
+//! Since objects are marked when they are borrowed, an object which
+//! was borrowed mutably but left as it was is still reported. Mutable
+//! iteration over the table marks every object, and so does
+//! [`retain`](crate::Table::retain). Objects which are deleted are
+//! no longer reported, even if they were dirty. The objects are held
+//! in another storage, which is map storage unless another is given,
+//! as in `#[table(dirty(arena))]`.
+//!
+//! The dirty objects are kept in a [`BTreeSet`], so the record only
+//! grows with the number of objects marked, however sparse the
+//! handles of the table are.
+
+use std::collections::BTreeSet;
+
+use crate::referrers::LinkIndex;
+use crate::storage::{sealed, Entries, EntriesMut, MapStorage, Storage};
+use crate::{AnyProxy, Context, Contextual, Proxy, Table};
+
+/// Storage which records which of its objects have changed.
+///
+/// The objects themselves are held in another storage, `S`. Objects
+/// which are inserted or borrowed mutably are marked dirty, until the
+/// marks are collected with [`take_dirty`](DirtyStorage::take_dirty).
+pub struct DirtyStorage<T, S = MapStorage<T>> {
+    inner: S,
+    dirty: BTreeSet<Proxy<T>>,
+}
+
+impl<T, S: Storage<T>> DirtyStorage<T, S> {
+    /// Track changes to the objects held in `inner`.
+    ///
+    /// The objects already in `inner` start out clean.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            dirty: BTreeSet::new(),
+        }
+    }
+
+    /// The objects which have changed since the marks were last
+    /// collected.
+    pub fn dirty(&self) -> &BTreeSet<Proxy<T>> {
+        &self.dirty
+    }
+
+    /// Collect the objects which have changed, leaving every object
+    /// clean.
+    pub fn take_dirty(&mut self) -> BTreeSet<Proxy<T>> {
+        std::mem::take(&mut self.dirty)
+    }
+
+    fn mark(&mut self, index: u64) {
+        if let Some(p) = self.inner.proxy(index) {
+            self.dirty.insert(*p);
+        }
+    }
+}
+
+impl<T, S: Storage<T> + Default> Default for DirtyStorage<T, S> {
+    fn default() -> Self {
+        Self::new(S::default())
+    }
+}
+
+impl<T, S: Clone> Clone for DirtyStorage<T, S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            dirty: self.dirty.clone(),
+        }
+    }
+}
+
+impl<T, S> sealed::Sealed for DirtyStorage<T, S> {}
+
+impl<T, S: Storage<T>> Storage<T> for DirtyStorage<T, S> {
+    fn insert(&mut self, proxy: Proxy<T>, value: T) -> Option<T> {
+        if let Some(p) = self.inner.proxy(proxy.index) {
+            self.dirty.remove(p);
+        }
+        self.dirty.insert(proxy);
+        self.inner.insert(proxy, value)
+    }
+
+    fn get(&self, index: u64) -> Option<&T> {
+        self.inner.get(index)
+    }
+
+    fn get_mut(&mut self, index: u64) -> Option<&mut T> {
+        self.mark(index);
+        self.inner.get_mut(index)
+    }
+
+    fn proxy(&self, index: u64) -> Option<&Proxy<T>> {
+        self.inner.proxy(index)
+    }
+
+    fn proxy_from(&self, index: u64) -> Option<&Proxy<T>> {
+        self.inner.proxy_from(index)
+    }
+
+    fn remove(&mut self, index: u64) -> Option<T> {
+        if let Some(p) = self.inner.proxy(index) {
+            self.dirty.remove(p);
+        }
+        self.inner.remove(index)
+    }
+
+    fn swap(&mut self, a: u64, b: u64) -> bool {
+        let swapped = self.inner.swap(a, b);
+        if swapped {
+            self.mark(a);
+            self.mark(b);
+        }
+        swapped
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn entries(&self) -> Entries<'_, T> {
+        self.inner.entries()
+    }
+
+    fn entries_mut(&mut self) -> EntriesMut<'_, T> {
+        for (p, _) in self.inner.entries() {
+            self.dirty.insert(*p);
+        }
+        self.inner.entries_mut()
+    }
+
+    fn into_entries(self) -> Vec<(Proxy<T>, T)> {
+        self.inner.into_entries()
+    }
+}
+
+impl<T, S: LinkIndex<T>> LinkIndex<T> for DirtyStorage<T, S> {
+    fn referrers_of(&self, target: &AnyProxy, out: &mut Vec<AnyProxy>) {
+        self.inner.referrers_of(target, out)
+    }
+}
+
+impl<T, S: Storage<T>, A> Table<T, DirtyStorage<T, S>, A> {
+    /// The items which have changed since the marks were last
+    /// collected.
+    pub fn dirty(&self) -> &BTreeSet<Proxy<T>> {
+        self.storage.dirty()
+    }
+
+    /// Collect the items which have changed, leaving every item
+    /// clean.
+    pub fn take_dirty(&mut self) -> BTreeSet<Proxy<T>> {
+        self.storage.take_dirty()
+    }
+}
+
+/// A context which tracks changes to its objects of type `T`.
+///
+/// Implementations are generated by the
+/// [`persian_rug`](crate::persian_rug) attribute for tables declared
+/// with `#[table(dirty)]`.
+pub trait DirtyOwner<T>: Context
+where
+    T: Contextual<Context = Self>,
+{
+    /// Collect the objects of type `T` which have changed, leaving
+    /// every object clean.
+    fn take_dirty_objects(&mut self) -> BTreeSet<Proxy<T>>;
+}
@@ -0,0 +1,309 @@
+//! Writing some tables while reading others.
+//!
+//! An exclusive reference to a context covers every table in it, so
+//! while one thread is changing objects of one type, no other thread
+//! can read objects of any type, even those the writer never touches.
+//! A [`Disjoint`] splits a context by type instead. Each call to
+//! [`split`](Disjoint::split) hands out a [`TableMut`] for one table,
+//! and [`reader`](Disjoint::reader) gives a [`Reader`] for all the
+//! others. These borrow separate tables, and so can be used at the
+//! same time, including from different threads:
+//!
+//! ```rust
+//! use persian_rug::disjoint::Disjoint;
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Price {
+//!   cents: u64,
+//! }
+//!
+//! #[contextual(Rug)]
+//! struct Order {
+//!   price: Proxy<Price>,
+//!   quantity: u64,
+//! }
+//!
+//! #[contextual(Rug)]
+//! struct Total {
+//!   cents: u64,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Price, #[table] Order, #[table] Total);
+//!
+//! let mut r = Rug(Default::default(), Default::default(), Default::default());
+//! let price = r.add(Price { cents: 250 });
+//! r.add(Order { price, quantity: 2 });
+//! r.add(Order { price, quantity: 3 });
+//! let total = r.add(Total { cents: 0 });
+//!
+//! let mut split = Disjoint::new(&mut r);
+//! let mut totals = split.split::<Total>();
+//! let reader = split.reader();
+//! std::thread::scope(|scope| {
+//!     scope.spawn(move || {
+//!         totals.get_mut(&total).cents = reader
+//!             .get_iter::<Order>()
+//!             .map(|order| reader.get(&order.price).cents * order.quantity)
+//!             .sum();
+//!     });
+//!     scope.spawn(|| assert_eq!(reader.get(&price).cents, 250));
+//! });
+//! assert_eq!(r.get(&total).cents, 1250);
+//! ```
+//!
+//! Which tables have been split off is checked as the program runs:
+//! splitting the same table twice panics, as does reading a table
+//! through a [`Reader`] while it is split off.
+//!
+//! This is available for contexts created with the
+//! [`persian_rug`](crate::persian_rug) macro, which implements
+//! [`TableField`] for each table, and for types which are `'static`,
+//! since tables are told apart by their [`TypeId`].
+
+use std::any::TypeId;
+
+use crate::{
+    handles, storage, Context, Contextual, Owner, Proxy, Table, TableIterator, TableMutIterator,
+    TableProxyIterator,
+};
+
+/// A context which stores its table for `T` in a field of its own.
+///
+/// This is implemented by the [`persian_rug`](crate::persian_rug)
+/// macro.
+///
+/// # Safety
+///
+/// [`table_ptr`](TableField::table_ptr) must return a pointer to a
+/// field of `this`, which is not the field returned for any other
+/// type, and which holds the table the [`Owner`] implementation for
+/// `T` uses.
+#[doc(hidden)]
+pub unsafe trait TableField<T>: Owner<T>
+where
+    T: Contextual<Context = Self>,
+{
+    type Table: TableAccess<T>;
+
+    /// # Safety
+    ///
+    /// `this` must point to a valid context.
+    unsafe fn table_ptr(this: *mut Self) -> *mut Self::Table;
+}
+
+/// The operations on a [`Table`] used by [`TableMut`] and [`Reader`].
+#[doc(hidden)]
+pub trait TableAccess<T> {
+    fn push(&mut self, value: T) -> Proxy<T>;
+    fn get(&self, p: &Proxy<T>) -> Option<&T>;
+    fn get_mut(&mut self, p: &Proxy<T>) -> Option<&mut T>;
+    fn iter(&self) -> TableIterator<'_, T>;
+    fn iter_mut(&mut self) -> TableMutIterator<'_, T>;
+    fn iter_proxies(&self) -> TableProxyIterator<'_, T>;
+}
+
+impl<T, S, A> TableAccess<T> for Table<T, S, A>
+where
+    S: storage::Storage<T>,
+    A: handles::HandleAllocator,
+{
+    fn push(&mut self, value: T) -> Proxy<T> {
+        Table::push(self, value)
+    }
+
+    fn get(&self, p: &Proxy<T>) -> Option<&T> {
+        Table::get(self, p)
+    }
+
+    fn get_mut(&mut self, p: &Proxy<T>) -> Option<&mut T> {
+        Table::get_mut(self, p)
+    }
+
+    fn iter(&self) -> TableIterator<'_, T> {
+        Table::iter(self)
+    }
+
+    fn iter_mut(&mut self) -> TableMutIterator<'_, T> {
+        Table::iter_mut(self)
+    }
+
+    fn iter_proxies(&self) -> TableProxyIterator<'_, T> {
+        Table::iter_proxies(self)
+    }
+}
+
+/// A context split into separately borrowed tables.
+///
+/// See the [module documentation](self) for details.
+pub struct Disjoint<'a, C> {
+    context: *mut C,
+    writing: Vec<TypeId>,
+    _marker: core::marker::PhantomData<&'a mut C>,
+}
+
+impl<'a, C: Context> Disjoint<'a, C> {
+    /// Split up `context`.
+    pub fn new(context: &'a mut C) -> Self {
+        Self {
+            context,
+            writing: Vec::new(),
+            _marker: Default::default(),
+        }
+    }
+
+    /// Take exclusive access to the table for `T`.
+    ///
+    /// Panics if the table has already been split off.
+    #[track_caller]
+    pub fn split<T>(&mut self) -> TableMut<'a, C, T>
+    where
+        C: TableField<T>,
+        T: Contextual<Context = C> + 'static,
+    {
+        let id = TypeId::of::<T>();
+        assert!(
+            !self.writing.contains(&id),
+            "the table for {} has already been split off",
+            std::any::type_name::<T>()
+        );
+        self.writing.push(id);
+        // The table is a field of its own, which is not reachable
+        // through any other TableMut, or through a Reader while it is
+        // in `writing`.
+        let table = unsafe { &mut *C::table_ptr(self.context) };
+        TableMut { table }
+    }
+
+    /// Shared access to every table which has not been split off.
+    pub fn reader(&self) -> Reader<'_, C> {
+        Reader {
+            context: self.context,
+            writing: &self.writing,
+        }
+    }
+}
+
+/// Exclusive access to the table for `T`, split from a [`Disjoint`].
+pub struct TableMut<'a, C, T>
+where
+    C: TableField<T>,
+    T: Contextual<Context = C>,
+{
+    table: &'a mut C::Table,
+}
+
+impl<C, T> TableMut<'_, C, T>
+where
+    C: TableField<T>,
+    T: Contextual<Context = C>,
+{
+    /// Insert the given value, returning a [`Proxy`] for it.
+    #[track_caller]
+    pub fn add(&mut self, value: T) -> Proxy<T> {
+        self.table.push(value)
+    }
+
+    /// Retrieve a reference to a value from a [`Proxy`].
+    pub fn get(&self, what: &Proxy<T>) -> &T {
+        self.table.get(what).unwrap()
+    }
+
+    /// Retrieve a mutable reference to a value from a [`Proxy`].
+    pub fn get_mut(&mut self, what: &Proxy<T>) -> &mut T {
+        self.table.get_mut(what).unwrap()
+    }
+
+    /// Iterate over the values currently stored.
+    pub fn get_iter(&self) -> TableIterator<'_, T> {
+        self.table.iter()
+    }
+
+    /// Mutably iterate over the values currently stored.
+    pub fn get_iter_mut(&mut self) -> TableMutIterator<'_, T> {
+        self.table.iter_mut()
+    }
+
+    /// Iterate over proxies for the values currently stored.
+    pub fn get_proxy_iter(&self) -> TableProxyIterator<'_, T> {
+        self.table.iter_proxies()
+    }
+}
+
+/// Shared access to the tables of a [`Disjoint`] which have not been
+/// split off.
+pub struct Reader<'r, C> {
+    context: *mut C,
+    writing: &'r [TypeId],
+}
+
+// A Reader only ever makes shared references into the context.
+unsafe impl<C: Sync> Send for Reader<'_, C> {}
+unsafe impl<C: Sync> Sync for Reader<'_, C> {}
+
+impl<C> Clone for Reader<'_, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for Reader<'_, C> {}
+
+impl<'r, C: Context> Reader<'r, C> {
+    #[track_caller]
+    fn table<T>(&self) -> &'r C::Table
+    where
+        C: TableField<T>,
+        C::Table: 'r,
+        T: Contextual<Context = C> + 'static,
+    {
+        assert!(
+            !self.writing.contains(&TypeId::of::<T>()),
+            "the table for {} has been split off for writing",
+            std::any::type_name::<T>()
+        );
+        // No TableMut exists for this table, and none can be made
+        // while `writing` is borrowed.
+        unsafe { &*C::table_ptr(self.context) }
+    }
+
+    /// Retrieve a reference to a value from a [`Proxy`].
+    ///
+    /// Panics if the table for `T` has been split off.
+    #[track_caller]
+    pub fn get<T>(&self, what: &Proxy<T>) -> &'r T
+    where
+        C: TableField<T>,
+        C::Table: 'r,
+        T: Contextual<Context = C> + 'static,
+    {
+        self.table().get(what).unwrap()
+    }
+
+    /// Iterate over the values of type `T`.
+    ///
+    /// Panics if the table for `T` has been split off.
+    #[track_caller]
+    pub fn get_iter<T>(&self) -> TableIterator<'r, T>
+    where
+        C: TableField<T>,
+        C::Table: 'r,
+        T: Contextual<Context = C> + 'static,
+    {
+        self.table().iter()
+    }
+
+    /// Iterate over proxies for the values of type `T`.
+    ///
+    /// Panics if the table for `T` has been split off.
+    #[track_caller]
+    pub fn get_proxy_iter<T>(&self) -> TableProxyIterator<'r, T>
+    where
+        C: TableField<T>,
+        C::Table: 'r,
+        T: Contextual<Context = C> + 'static,
+    {
+        self.table().iter_proxies()
+    }
+}
@@ -0,0 +1,93 @@
+//! Rendering a context as a [Graphviz](https://graphviz.org) graph.
+//!
+//! This module is available with the `dot` feature. It writes the
+//! graph collected by a [`Diagram`] in the DOT language, with a node
+//! for each object and an edge, labelled with the name of the field,
+//! for each link between them. This is mostly useful for checking the
+//! shape of a graph while debugging.
+//!
+//! ```rust
+//! use persian_rug::diagram::Labels;
+//! use persian_rug::{contextual, dot, persian_rug, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Person {
+//!   name: String,
+//!   #[link]
+//!   manager: Option<Proxy<Person>>,
+//! }
+//!
+//! #[persian_rug(diagram)]
+//! struct Rug(#[table] Person);
+//!
+//! let mut r = Rug(Default::default());
+//! let boss = r.add(Person { name: "Alice".to_string(), manager: None });
+//! r.add(Person { name: "Bob".to_string(), manager: Some(boss) });
+//!
+//! let labels = Labels::new().label(|p: &Person| p.name.clone());
+//! assert_eq!(
+//!     dot::to_string(&r, &labels),
+//!     concat!(
+//!         "digraph {\n",
+//!         "  n0_0 [label=\"Alice\"];\n",
+//!         "  n0_1 [label=\"Bob\"];\n",
+//!         "  n0_1 -> n0_0 [label=\"manager\"];\n",
+//!         "}\n",
+//!     )
+//! );
+//! ```
+//!
+//! The output can be turned into an image with Graphviz, for example
+//! with `dot -Tsvg context.dot -o context.svg`.
+
+use std::io::{self, Write};
+
+use crate::diagram::{Diagram, Graph, Labels};
+
+/// Write `context` to `out` as a DOT graph.
+pub fn write<C: Diagram, W: Write>(context: &C, labels: &Labels, mut out: W) -> io::Result<()> {
+    let graph = Graph::new(context, labels);
+    writeln!(out, "digraph {{")?;
+    for node in graph.nodes.iter() {
+        writeln!(
+            out,
+            "  {} [label={}];",
+            graph.id(&node.proxy),
+            quote(&node.label)
+        )?;
+    }
+    for edge in graph.edges.iter() {
+        writeln!(
+            out,
+            "  {} -> {} [label={}];",
+            graph.id(&edge.from),
+            graph.id(&edge.to),
+            quote(&edge.field)
+        )?;
+    }
+    writeln!(out, "}}")?;
+    out.flush()
+}
+
+/// Render `context` as a DOT graph.
+pub fn to_string<C: Diagram>(context: &C, labels: &Labels) -> String {
+    let mut out = Vec::new();
+    write(context, labels, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+/// Quote `value` as a DOT string.
+fn quote(value: &str) -> String {
+    let mut res = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => {}
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
@@ -0,0 +1,338 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{Accessor, Contextual, Owner, Proxy, TableIterator};
+
+/// A link between two objects which carries data of its own.
+///
+/// Plain [`Proxy`] fields are enough to link objects together, but
+/// some relationships have properties of their own: the weight of a
+/// road between two towns, or the date one user started following
+/// another. An edge holds the proxies of the objects it links, and a
+/// payload for such properties.
+///
+/// An edge belongs to the same context as its source, so it can be
+/// stored in a table like any other object, and found again with the
+/// methods of [`EdgeAccessor`]:
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Accessor, Context, Edge, EdgeAccessor};
+///
+/// #[contextual(Rug)]
+/// struct Town {
+///   name: &'static str,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Town, #[table] Edge<Town, Town, u32>);
+///
+/// let mut r = Rug(Default::default(), Default::default());
+/// let a = r.add(Town { name: "Aston" });
+/// let b = r.add(Town { name: "Brill" });
+/// let c = r.add(Town { name: "Cole" });
+/// r.add(Edge::new(a, b, 5));
+/// r.add(Edge::new(a, c, 8));
+/// r.add(Edge::new(b, c, 2));
+///
+/// let access = &r;
+/// let from_a = access
+///     .edges_from::<Town, Town, u32>(&a)
+///     .map(|e| e.payload)
+///     .collect::<Vec<_>>();
+/// assert_eq!(from_a, vec![5, 8]);
+/// let into_c = access
+///     .edges_to::<Town, Town, u32>(&c)
+///     .map(|e| access.get(&e.from).name)
+///     .collect::<Vec<_>>();
+/// assert_eq!(into_c, vec!["Aston", "Brill"]);
+/// ```
+///
+/// The methods of [`EdgeAccessor`] scan the whole edge table. For
+/// large graphs, an [`EdgeIndex`] gives direct access to the edges of
+/// each object.
+pub struct Edge<A, B, P = ()> {
+    /// The object this edge leads from.
+    pub from: Proxy<A>,
+    /// The object this edge leads to.
+    pub to: Proxy<B>,
+    /// The data carried by this edge.
+    pub payload: P,
+}
+
+impl<A, B, P> Edge<A, B, P> {
+    /// Create an edge from `from` to `to`, carrying `payload`.
+    pub fn new(from: Proxy<A>, to: Proxy<B>, payload: P) -> Self {
+        Self { from, to, payload }
+    }
+}
+
+impl<A, B, P: Clone> Clone for Edge<A, B, P> {
+    fn clone(&self) -> Self {
+        Self {
+            from: self.from,
+            to: self.to,
+            payload: self.payload.clone(),
+        }
+    }
+}
+
+impl<A, B, P: Copy> Copy for Edge<A, B, P> {}
+
+impl<A, B, P: PartialEq> PartialEq for Edge<A, B, P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.from == other.from && self.to == other.to && self.payload == other.payload
+    }
+}
+
+impl<A, B, P: Eq> Eq for Edge<A, B, P> {}
+
+impl<A, B, P: PartialOrd> PartialOrd for Edge<A, B, P> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self.from, self.to).cmp(&(other.from, other.to)) {
+            std::cmp::Ordering::Equal => self.payload.partial_cmp(&other.payload),
+            ord => Some(ord),
+        }
+    }
+}
+
+impl<A, B, P: Ord> Ord for Edge<A, B, P> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.from, self.to)
+            .cmp(&(other.from, other.to))
+            .then_with(|| self.payload.cmp(&other.payload))
+    }
+}
+
+impl<A, B, P: std::fmt::Debug> std::fmt::Debug for Edge<A, B, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Edge")
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .field("payload", &self.payload)
+            .finish()
+    }
+}
+
+impl<A, B, P> Contextual for Edge<A, B, P>
+where
+    A: Contextual,
+    B: Contextual<Context = A::Context>,
+{
+    type Context = A::Context;
+
+    fn schema() -> crate::schema::TypeSchema {
+        use crate::schema::{FieldSchema, TypeSchema};
+        use std::any::type_name;
+
+        TypeSchema {
+            fields: vec![
+                FieldSchema {
+                    name: "from",
+                    ty: "Proxy<A>",
+                    proxies: vec![type_name::<A>()],
+                },
+                FieldSchema {
+                    name: "to",
+                    ty: "Proxy<B>",
+                    proxies: vec![type_name::<B>()],
+                },
+                FieldSchema {
+                    name: "payload",
+                    ty: "P",
+                    proxies: Vec::new(),
+                },
+            ],
+            ..TypeSchema::opaque::<Self>()
+        }
+    }
+}
+
+/// Finding the edges that meet an object.
+///
+/// This is implemented for every [`Accessor`].
+pub trait EdgeAccessor: Accessor {
+    /// Iterate over the edges leading from an object, in the order of
+    /// the edge table.
+    fn edges_from<A, B, P>(&self, from: &Proxy<A>) -> EdgeIterator<'_, A, B, P>
+    where
+        Self::Context: Owner<Edge<A, B, P>>,
+        A: Contextual<Context = Self::Context>,
+        B: Contextual<Context = Self::Context>,
+    {
+        EdgeIterator {
+            iter: self.get_iter(),
+            end: End::From(from.key()),
+        }
+    }
+
+    /// Iterate over the edges leading to an object, in the order of
+    /// the edge table.
+    fn edges_to<A, B, P>(&self, to: &Proxy<B>) -> EdgeIterator<'_, A, B, P>
+    where
+        Self::Context: Owner<Edge<A, B, P>>,
+        A: Contextual<Context = Self::Context>,
+        B: Contextual<Context = Self::Context>,
+    {
+        EdgeIterator {
+            iter: self.get_iter(),
+            end: End::To(to.key()),
+        }
+    }
+}
+
+impl<C: Accessor> EdgeAccessor for C {}
+
+enum End {
+    From((u64, u32)),
+    To((u64, u32)),
+}
+
+/// An [`Iterator`] over the edges meeting an object.
+///
+/// This is created by [`EdgeAccessor::edges_from`] and
+/// [`EdgeAccessor::edges_to`].
+pub struct EdgeIterator<'a, A, B, P> {
+    iter: TableIterator<'a, Edge<A, B, P>>,
+    end: End,
+}
+
+impl<'a, A, B, P> Iterator for EdgeIterator<'a, A, B, P> {
+    type Item = &'a Edge<A, B, P>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = &self.end;
+        self.iter.by_ref().find(|edge| match end {
+            End::From(key) => edge.from.key() == *key,
+            End::To(key) => edge.to.key() == *key,
+        })
+    }
+}
+
+/// An index of the edges meeting each object.
+///
+/// The index records the proxies of edges, keyed by both of their
+/// ends. It is not updated automatically: add each edge to it as it
+/// is created, or rebuild it with [`build`](EdgeIndex::build) after
+/// making changes.
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, Edge, EdgeIndex};
+///
+/// #[contextual(Rug)]
+/// struct Town {
+///   name: &'static str,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Town, #[table] Edge<Town, Town, u32>);
+///
+/// let mut r = Rug(Default::default(), Default::default());
+/// let a = r.add(Town { name: "Aston" });
+/// let b = r.add(Town { name: "Brill" });
+/// let e = r.add(Edge::new(a, b, 5));
+///
+/// let index = EdgeIndex::build(&r);
+/// assert_eq!(index.edges_from(&a).collect::<Vec<_>>(), vec![e]);
+/// assert_eq!(index.edges_to(&a).count(), 0);
+/// ```
+pub struct EdgeIndex<A, B, P = ()> {
+    _marker: core::marker::PhantomData<(A, B, P)>,
+    from: BTreeMap<(u64, u32), BTreeSet<(u64, u32)>>,
+    to: BTreeMap<(u64, u32), BTreeSet<(u64, u32)>>,
+}
+
+impl<A, B, P> EdgeIndex<A, B, P> {
+    /// Create a new, empty index.
+    pub fn new() -> Self {
+        Self {
+            _marker: Default::default(),
+            from: BTreeMap::new(),
+            to: BTreeMap::new(),
+        }
+    }
+
+    /// Index all of the edges in a context.
+    pub fn build<X>(access: X) -> Self
+    where
+        X: Accessor,
+        X::Context: Owner<Edge<A, B, P>>,
+        A: Contextual<Context = X::Context>,
+        B: Contextual<Context = X::Context>,
+    {
+        let mut res = Self::new();
+        for p in access.get_proxy_iter::<Edge<A, B, P>>() {
+            res.insert(*p, access.get(p));
+        }
+        res
+    }
+
+    /// Add an edge to the index.
+    pub fn insert(&mut self, p: Proxy<Edge<A, B, P>>, edge: &Edge<A, B, P>) {
+        self.from
+            .entry(edge.from.key())
+            .or_default()
+            .insert(p.key());
+        self.to.entry(edge.to.key()).or_default().insert(p.key());
+    }
+
+    /// Remove an edge from the index.
+    ///
+    /// The edge must have the same ends as when it was inserted.
+    pub fn remove(&mut self, p: &Proxy<Edge<A, B, P>>, edge: &Edge<A, B, P>) {
+        for (map, end) in [
+            (&mut self.from, edge.from.key()),
+            (&mut self.to, edge.to.key()),
+        ] {
+            if let Some(edges) = map.get_mut(&end) {
+                edges.remove(&p.key());
+                if edges.is_empty() {
+                    map.remove(&end);
+                }
+            }
+        }
+    }
+
+    /// Iterate over the edges leading from an object, in handle
+    /// order.
+    pub fn edges_from(&self, from: &Proxy<A>) -> EdgeIndexIterator<'_, A, B, P> {
+        EdgeIndexIterator {
+            _marker: Default::default(),
+            iter: self.from.get(&from.key()).map(|edges| edges.iter()),
+        }
+    }
+
+    /// Iterate over the edges leading to an object, in handle order.
+    pub fn edges_to(&self, to: &Proxy<B>) -> EdgeIndexIterator<'_, A, B, P> {
+        EdgeIndexIterator {
+            _marker: Default::default(),
+            iter: self.to.get(&to.key()).map(|edges| edges.iter()),
+        }
+    }
+}
+
+impl<A, B, P> Default for EdgeIndex<A, B, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A, B, P> Clone for EdgeIndex<A, B, P> {
+    fn clone(&self) -> Self {
+        Self {
+            _marker: Default::default(),
+            from: self.from.clone(),
+            to: self.to.clone(),
+        }
+    }
+}
+
+/// An [`Iterator`] over the edges recorded in an [`EdgeIndex`].
+pub struct EdgeIndexIterator<'a, A, B, P> {
+    _marker: core::marker::PhantomData<(A, B, P)>,
+    iter: Option<std::collections::btree_set::Iter<'a, (u64, u32)>>,
+}
+
+impl<A, B, P> Iterator for EdgeIndexIterator<'_, A, B, P> {
+    type Item = Proxy<Edge<A, B, P>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.as_mut()?.next().map(|key| Proxy::from_key(*key))
+    }
+}
@@ -0,0 +1,238 @@
+//! Copying part of a context into another.
+//!
+//! A context often holds many independent groups of objects, such as
+//! the documents open in an editor. To save or send just one of them,
+//! [`extract`] copies the objects reachable from some roots into
+//! another context, usually a fresh one, without cloning everything
+//! else. The links of the copies are rewritten with [`Relink`], so
+//! that they point at the other copies rather than at the objects of
+//! the original context.
+//!
+//! The tables to copy from are listed by an implementation of
+//! [`Extract`], which the [`persian_rug`](crate::persian_rug) macro
+//! generates with its `extract` option:
+//!
+//! ```rust
+//! use persian_rug::{contextual, extract, persian_rug, AnyProxy, Context, Links, Proxy};
+//!
+//! #[derive(Clone, Links)]
+//! #[contextual(Rug)]
+//! struct Paragraph {
+//!   text: &'static str,
+//! }
+//!
+//! #[derive(Clone, Links)]
+//! #[contextual(Rug)]
+//! struct Document {
+//!   title: &'static str,
+//!   paragraphs: Vec<Proxy<Paragraph>>,
+//! }
+//!
+//! #[persian_rug(extract)]
+//! struct Rug(#[table] Paragraph, #[table] Document);
+//!
+//! let mut r = Rug(Default::default(), Default::default());
+//! let intro = r.add(Paragraph { text: "Hello" });
+//! let other = r.add(Paragraph { text: "Unrelated" });
+//! let body = r.add(Paragraph { text: "World" });
+//! r.add(Document { title: "Other", paragraphs: vec![other] });
+//! let doc = r.add(Document { title: "Greeting", paragraphs: vec![intro, body] });
+//!
+//! let mut out = Rug(Default::default(), Default::default());
+//! let extracted = extract::extract(&r, &mut out, [AnyProxy::from(doc)]);
+//! let doc = extracted.roots()[0].downcast::<Document>().unwrap();
+//! let text = out
+//!     .get(&doc)
+//!     .paragraphs
+//!     .iter()
+//!     .map(|p| out.get(p).text)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(text, vec!["Hello", "World"]);
+//! assert_eq!(out.get_iter::<Paragraph>().count(), 2);
+//! ```
+//!
+//! Only links reported by [`Links`](crate::Links) are followed and
+//! rewritten, so every field holding a proxy must either be found by
+//! `#[derive(Links)]`, as above, or be marked `#[link]`. Links to objects which have been
+//! deleted cannot be copied, and are left as they are.
+
+use std::any::TypeId;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{AnyProxy, Context, Contextual, Owner, Proxy, Relink};
+
+/// A context whose objects can be copied into another.
+///
+/// This is normally implemented with the `extract` option of the
+/// [`persian_rug`](crate::persian_rug) macro.
+pub trait Extract: Context {
+    /// Register each table of the context with `tables`.
+    fn describe(tables: &mut ExtractTables<Self>)
+    where
+        Self: Sized;
+}
+
+type Trace<C> = fn(&C, &AnyProxy, &mut dyn FnMut(AnyProxy));
+type Duplicate<C> = fn(&C, &mut C, &BTreeSet<AnyProxy>, &mut BTreeMap<AnyProxy, AnyProxy>);
+type Fixup<C> = fn(&mut C, &AnyProxy, &BTreeMap<AnyProxy, AnyProxy>);
+
+struct Entry<C> {
+    trace: Trace<C>,
+    copy: Duplicate<C>,
+    fixup: Fixup<C>,
+}
+
+/// The tables which can be copied from.
+///
+/// This is passed to [`Extract::describe`].
+pub struct ExtractTables<C> {
+    entries: BTreeMap<TypeId, Entry<C>>,
+    order: Vec<TypeId>,
+}
+
+impl<C: Context> ExtractTables<C> {
+    /// Include the table of objects of type `T`.
+    pub fn table<T>(&mut self)
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + Relink + Clone + 'static,
+    {
+        self.order.push(TypeId::of::<T>());
+        self.entries.insert(
+            TypeId::of::<T>(),
+            Entry {
+                trace: trace::<C, T>,
+                copy: copy::<C, T>,
+                fixup: fixup::<C, T>,
+            },
+        );
+    }
+}
+
+fn trace<C, T>(context: &C, p: &AnyProxy, f: &mut dyn FnMut(AnyProxy))
+where
+    C: Owner<T>,
+    T: Contextual<Context = C> + Relink + 'static,
+{
+    if let Some(value) = p.downcast().and_then(|p| Owner::try_get(context, &p).ok()) {
+        value.for_each_link(f);
+    }
+}
+
+fn copy<C, T>(
+    context: &C,
+    into: &mut C,
+    reached: &BTreeSet<AnyProxy>,
+    proxies: &mut BTreeMap<AnyProxy, AnyProxy>,
+) where
+    C: Owner<T>,
+    T: Contextual<Context = C> + Clone + 'static,
+{
+    for p in Owner::<T>::get_proxy_iter(context) {
+        let from = AnyProxy::new(*p);
+        if reached.contains(&from) {
+            let to = Owner::add(into, Owner::get(context, p).clone());
+            proxies.insert(from, AnyProxy::new(to));
+        }
+    }
+}
+
+fn fixup<C, T>(into: &mut C, p: &AnyProxy, proxies: &BTreeMap<AnyProxy, AnyProxy>)
+where
+    C: Owner<T>,
+    T: Contextual<Context = C> + Relink + 'static,
+{
+    let p: Proxy<T> = p.downcast().unwrap();
+    Owner::get_mut(into, &p).relink(&mut |target| proxies.get(&target).copied().unwrap_or(target));
+}
+
+/// The result of an [`extract`].
+///
+/// This maps each object which was copied to its copy.
+#[derive(Clone, Debug, Default)]
+pub struct Extracted {
+    roots: Vec<AnyProxy>,
+    proxies: BTreeMap<AnyProxy, AnyProxy>,
+}
+
+impl Extracted {
+    /// The copies of the roots, in the order they were given.
+    pub fn roots(&self) -> &[AnyProxy] {
+        &self.roots
+    }
+
+    /// The copy of `p`, or [`None`] if it was not copied.
+    pub fn get<T: 'static>(&self, p: &Proxy<T>) -> Option<Proxy<T>> {
+        self.proxies
+            .get(&AnyProxy::new(*p))
+            .and_then(|copy| copy.downcast())
+    }
+
+    /// The number of objects copied.
+    pub fn len(&self) -> usize {
+        self.proxies.len()
+    }
+
+    /// Check whether no objects were copied.
+    pub fn is_empty(&self) -> bool {
+        self.proxies.is_empty()
+    }
+}
+
+/// Copy the objects reachable from `roots` in `context` into `into`.
+///
+/// Each object is copied once, however many paths lead to it, and
+/// the copies are added table by table, in the order of their
+/// handles in `context`. The objects already in `into` are left
+/// alone, so it is usually a freshly created context.
+///
+/// # Panics
+///
+/// Panics if a root has been deleted from `context`.
+pub fn extract<C: Extract>(
+    context: &C,
+    into: &mut C,
+    roots: impl IntoIterator<Item = AnyProxy>,
+) -> Extracted {
+    let mut tables = ExtractTables {
+        entries: BTreeMap::new(),
+        order: Vec::new(),
+    };
+    C::describe(&mut tables);
+
+    let roots = roots.into_iter().collect::<Vec<_>>();
+    let mut reached = BTreeSet::new();
+    let mut work = roots.clone();
+    while let Some(p) = work.pop() {
+        if !reached.insert(p) {
+            continue;
+        }
+        if let Some(entry) = tables.entries.get(&p.type_id()) {
+            (entry.trace)(context, &p, &mut |target| {
+                if !reached.contains(&target) {
+                    work.push(target);
+                }
+            });
+        }
+    }
+
+    let mut proxies = BTreeMap::new();
+    for ty in tables.order.iter() {
+        (tables.entries[ty].copy)(context, into, &reached, &mut proxies);
+    }
+    for copy in proxies.values() {
+        (tables.entries[&copy.type_id()].fixup)(into, copy, &proxies);
+    }
+
+    Extracted {
+        roots: roots
+            .iter()
+            .map(|root| {
+                *proxies
+                    .get(root)
+                    .expect("root of extraction is not in the context")
+            })
+            .collect(),
+        proxies,
+    }
+}
@@ -0,0 +1,163 @@
+//! Deleting the objects which can no longer be reached.
+//!
+//! A context which holds a large graph that changes over time tends
+//! to accumulate objects which nothing refers to any more: nodes cut
+//! out of a tree, or versions replaced by newer ones. Rather than
+//! tracking when each of these can be deleted,
+//! [`Context::collect_garbage`] finds
+//! them all at once. Starting from a set of roots, it follows the
+//! links between objects, as listed by their [`Links`]
+//! implementations, and then deletes every object it did not reach.
+//!
+//! The tables to collect from are listed by an implementation of
+//! [`Collect`], which the [`persian_rug`](crate::persian_rug) macro
+//! generates with its `gc` option:
+//!
+//! ```rust
+//! use persian_rug::{contextual, persian_rug, AnyProxy, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Node {
+//!   name: &'static str,
+//!   #[link]
+//!   children: Vec<Proxy<Node>>,
+//! }
+//!
+//! #[persian_rug(gc)]
+//! struct Rug(#[table] Node);
+//!
+//! let mut r = Rug(Default::default());
+//! let leaf = r.add(Node { name: "leaf", children: Vec::new() });
+//! let root = r.add(Node { name: "root", children: vec![leaf] });
+//! let orphan = r.add(Node { name: "orphan", children: vec![leaf] });
+//!
+//! let deleted = r.collect_garbage([AnyProxy::from(root)]);
+//! assert_eq!(deleted, vec![AnyProxy::from(orphan)]);
+//! assert_eq!(
+//!     r.get_iter::<Node>().map(|n| n.name).collect::<Vec<_>>(),
+//!     vec!["leaf", "root"]
+//! );
+//! ```
+//!
+//! Only links reported by [`Links`] keep objects alive, so every field
+//! holding a proxy to an object which should be kept must be marked
+//! `#[link]`. A [`WeakProxy`](crate::WeakProxy) never keeps its object
+//! alive. Objects [marked as deleted](crate::Table::mark_deleted) are
+//! followed if they are reached, but are never deleted by a
+//! collection.
+
+use std::any::TypeId;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{AnyProxy, Context, Contextual, Links, Owner};
+
+/// A context whose unreachable objects can be deleted.
+///
+/// This is normally implemented with the `gc` option of the
+/// [`persian_rug`](crate::persian_rug) macro.
+pub trait Collect: Context {
+    /// Register each table of the context with `gc`.
+    fn describe(gc: &mut GcTables<Self>)
+    where
+        Self: Sized;
+}
+
+type Trace<C> = fn(&C, &AnyProxy, &mut dyn FnMut(AnyProxy));
+type Sweep<C> = fn(&mut C, &BTreeSet<AnyProxy>, &mut Vec<AnyProxy>);
+
+/// The tables taking part in a collection.
+///
+/// This is passed to [`Collect::describe`].
+pub struct GcTables<C> {
+    trace: BTreeMap<TypeId, Trace<C>>,
+    sweep: Vec<Sweep<C>>,
+}
+
+impl<C: Context> GcTables<C> {
+    /// Include the table of objects of type `T`.
+    pub fn table<T>(&mut self)
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + Links + 'static,
+    {
+        self.trace.insert(TypeId::of::<T>(), trace::<C, T>);
+        self.sweep.push(sweep::<C, T>);
+    }
+}
+
+fn trace<C, T>(context: &C, p: &AnyProxy, f: &mut dyn FnMut(AnyProxy))
+where
+    C: Owner<T>,
+    T: Contextual<Context = C> + Links + 'static,
+{
+    if let Some(value) = p.downcast().and_then(|p| Owner::try_get(context, &p).ok()) {
+        value.for_each_link(f);
+    }
+}
+
+fn sweep<C, T>(context: &mut C, reached: &BTreeSet<AnyProxy>, deleted: &mut Vec<AnyProxy>)
+where
+    C: Owner<T>,
+    T: Contextual<Context = C> + Links + 'static,
+{
+    let unreached = Owner::<T>::get_proxy_iter(context)
+        .copied()
+        .filter(|p| !reached.contains(&AnyProxy::new(*p)))
+        .collect::<Vec<_>>();
+    for p in unreached {
+        Owner::delete(context, &p);
+        deleted.push(AnyProxy::new(p));
+    }
+}
+
+fn tables<C: Collect>() -> GcTables<C> {
+    let mut tables = GcTables {
+        trace: BTreeMap::new(),
+        sweep: Vec::new(),
+    };
+    C::describe(&mut tables);
+    tables
+}
+
+fn mark<C>(
+    context: &C,
+    tables: &GcTables<C>,
+    roots: impl IntoIterator<Item = AnyProxy>,
+) -> BTreeSet<AnyProxy> {
+    let mut reached = BTreeSet::new();
+    let mut work = roots.into_iter().collect::<Vec<_>>();
+    while let Some(p) = work.pop() {
+        if !reached.insert(p) {
+            continue;
+        }
+        if let Some(trace) = tables.trace.get(&p.type_id()) {
+            trace(context, &p, &mut |target| {
+                if !reached.contains(&target) {
+                    work.push(target);
+                }
+            });
+        }
+    }
+    reached
+}
+
+pub(crate) fn reachable<C: Collect>(
+    context: &C,
+    roots: impl IntoIterator<Item = AnyProxy>,
+) -> BTreeSet<AnyProxy> {
+    mark(context, &tables::<C>(), roots)
+}
+
+pub(crate) fn collect<C: Collect>(
+    context: &mut C,
+    roots: impl IntoIterator<Item = AnyProxy>,
+) -> Vec<AnyProxy> {
+    let tables = tables::<C>();
+    let reached = mark(context, &tables, roots);
+
+    let mut deleted = Vec::new();
+    for sweep in tables.sweep.iter() {
+        sweep(context, &reached, &mut deleted);
+    }
+    deleted
+}
@@ -0,0 +1,433 @@
+//! Strategies for choosing the handles of new objects.
+//!
+//! Each [`Proxy`](crate::Proxy) is identified within its table by a
+//! handle. By default a [`Table`](crate::Table) issues handles in
+//! sequence from zero, with [`Sequential`]. When objects are created
+//! on several machines and the contexts later merged, sequential
+//! handles from different machines collide, so a table can instead be
+//! given another [`HandleAllocator`]:
+//!
+//! - [`ShardPrefixed`] issues handles in sequence, with a shard
+//!   number in the top bits, so that each machine can be given its
+//!   own shard.
+//! - [`TimeOrdered`] issues handles built from the current time and
+//!   a node number, so that they sort roughly in creation order.
+//! - [`Random`] issues pseudo-random handles from a seed.
+//! - [`Recycling`] issues handles in sequence, but reuses the handles
+//!   of deleted objects first, so that a table with many objects
+//!   coming and going keeps its handles small and dense.
+//!
+//! The allocator for every table in a context can be chosen with the
+//! `handles` option of the [`persian_rug`](crate::persian_rug)
+//! macro. Tables whose allocator needs configuring are created with
+//! [`Table::with_handles`](crate::Table::with_handles):
+//!
+//! ```rust
+//! use persian_rug::handles::ShardPrefixed;
+//! use persian_rug::{contextual, persian_rug, Context, Table};
+//!
+//! #[contextual(Rug)]
+//! struct Foo {
+//!   a: i32,
+//! }
+//!
+//! #[persian_rug(handles = ShardPrefixed)]
+//! struct Rug(#[table] Foo);
+//!
+//! let mut left = Rug(Table::with_handles(ShardPrefixed::new(1)));
+//! let mut right = Rug(Table::with_handles(ShardPrefixed::new(2)));
+//! let a = left.add(Foo { a: 1 });
+//! let b = right.add(Foo { a: 2 });
+//! assert_ne!(a.handle(), b.handle());
+//! assert_eq!(a.handle() >> 48, 1);
+//! ```
+//!
+//! Whatever the allocator, a table never issues a handle that is
+//! already in use. Features which need to know the proxies of objects
+//! before they are added, such as [`Sandbox`](crate::Sandbox) and
+//! [`import`](crate::import::import), set them aside with
+//! [`Table::reserve_proxy`](crate::Table::reserve_proxy), and
+//! recordings are replayed with
+//! [`Table::insert_with_handle`](crate::Table::insert_with_handle), so
+//! these work with every allocator. Allocators do differ in a few
+//! ways:
+//!
+//! - A [`Cursor`](crate::Cursor) visits objects in handle order. With
+//!   [`Random`], that is not the order in which they were added, and
+//!   with [`Recycling`], an object added under a reused handle which
+//!   the cursor has already passed is not visited.
+//! - A recording made with [`TimeOrdered`] or [`Random`] handles
+//!   replays under the recorded handles, but objects added to the
+//!   replayed context afterwards receive handles from its own
+//!   allocator, which may differ from those the original would have
+//!   issued.
+//! - Only tables using [`Sequential`] can be archived with the `rkyv`
+//!   and `borsh` features.
+
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A strategy for choosing the handles of new objects in a table.
+///
+/// The table asks for the next handle with [`peek`], and once it has
+/// used that handle, moves on with [`advance`]. If the handle is
+/// already in use, the table advances past it without using it.
+///
+/// An allocator which has run out of handles should stay on the last
+/// one, returning it from [`peek`] however many times it advances.
+/// Once that handle is in use, the table refuses to add objects
+/// without a chosen handle.
+///
+/// [`peek`]: HandleAllocator::peek
+/// [`advance`]: HandleAllocator::advance
+pub trait HandleAllocator {
+    /// The handle the next object added will receive.
+    fn peek(&self) -> u64;
+
+    /// Move past the handle returned by [`peek`](HandleAllocator::peek).
+    fn advance(&mut self);
+
+    /// Note that `handle` was used without being issued by this
+    /// allocator, for example by
+    /// [`Table::insert_with_handle`](crate::Table::insert_with_handle).
+    ///
+    /// The default implementation does nothing, which is correct
+    /// since the table skips handles which are in use, but
+    /// implementations which issue handles in order can use this to
+    /// continue after `handle`.
+    fn reserve(&mut self, handle: u64) {
+        let _ = handle;
+    }
+
+    /// Note that `handle` is no longer in use, because its object was
+    /// removed from the table.
+    ///
+    /// The default implementation does nothing, so that the handle is
+    /// not issued again, but implementations can keep it to reuse.
+    /// Proxies for the removed object are not confused with those for
+    /// a later object given the same handle, since the table issues
+    /// the handle with a new [generation](crate::Proxy::generation).
+    fn release(&mut self, handle: u64) {
+        let _ = handle;
+    }
+}
+
+/// Issue handles in sequence, starting from zero.
+///
+/// This is the allocator used by tables unless another is chosen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sequential {
+    pub(crate) next: u64,
+}
+
+impl Sequential {
+    /// Issue handles in sequence, starting from `first`.
+    pub fn starting_at(first: u64) -> Self {
+        Self { next: first }
+    }
+}
+
+impl HandleAllocator for Sequential {
+    fn peek(&self) -> u64 {
+        self.next
+    }
+
+    fn advance(&mut self) {
+        self.next = self.next.saturating_add(1);
+    }
+
+    fn reserve(&mut self, handle: u64) {
+        self.next = self.next.max(handle.saturating_add(1));
+    }
+}
+
+/// The number of bits of a [`ShardPrefixed`] handle which hold the
+/// sequence number.
+pub const SHARD_SHIFT: u32 = 48;
+
+/// Issue handles in sequence, with a shard number in their top 16
+/// bits.
+///
+/// Tables with different shard numbers never issue the same handle,
+/// so objects created in each can be merged into one context.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ShardPrefixed {
+    shard: u16,
+    next: u64,
+}
+
+impl ShardPrefixed {
+    /// Issue handles in sequence within `shard`.
+    pub fn new(shard: u16) -> Self {
+        Self { shard, next: 0 }
+    }
+
+    /// The shard handles are issued in.
+    pub fn shard(&self) -> u16 {
+        self.shard
+    }
+}
+
+const SHARD_LAST: u64 = (1 << SHARD_SHIFT) - 1;
+
+impl HandleAllocator for ShardPrefixed {
+    fn peek(&self) -> u64 {
+        (u64::from(self.shard) << SHARD_SHIFT) | self.next
+    }
+
+    fn advance(&mut self) {
+        self.next = (self.next + 1).min(SHARD_LAST);
+    }
+
+    fn reserve(&mut self, handle: u64) {
+        if handle >> SHARD_SHIFT == u64::from(self.shard) {
+            self.next = self.next.max(((handle & SHARD_LAST) + 1).min(SHARD_LAST));
+        }
+    }
+}
+
+/// Issue handles made from the current time and a node number.
+///
+/// Each handle holds, from the top bit down, the number of
+/// milliseconds since the Unix epoch in 44 bits, the node number in
+/// 8 bits, and a sequence number in 12 bits, which counts objects
+/// created in the same millisecond. Handles are always issued in
+/// increasing order, even if the clock goes backwards, and nodes
+/// with different numbers never issue the same handle.
+///
+/// The time in a handle is the time at which the previous handle was
+/// issued, or at which the allocator was created, since the next
+/// handle must be known before the object it is for is added.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeOrdered {
+    node: u8,
+    time: u64,
+    sequence: u64,
+}
+
+const TIME_NODE_SHIFT: u32 = 12;
+const TIME_SHIFT: u32 = 20;
+const TIME_SEQUENCE_LIMIT: u64 = 1 << TIME_NODE_SHIFT;
+const TIME_LAST: u64 = (1 << (64 - TIME_SHIFT)) - 1;
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl TimeOrdered {
+    /// Issue handles from the current time for `node`.
+    pub fn new(node: u8) -> Self {
+        Self {
+            node,
+            time: now_millis(),
+            sequence: 0,
+        }
+    }
+
+    /// The node handles are issued for.
+    pub fn node(&self) -> u8 {
+        self.node
+    }
+
+    fn next_sequence(&mut self) {
+        if self.sequence + 1 < TIME_SEQUENCE_LIMIT {
+            self.sequence += 1;
+        } else if self.time < TIME_LAST {
+            self.time += 1;
+            self.sequence = 0;
+        }
+    }
+}
+
+impl Default for TimeOrdered {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl HandleAllocator for TimeOrdered {
+    fn peek(&self) -> u64 {
+        (self.time << TIME_SHIFT) | (u64::from(self.node) << TIME_NODE_SHIFT) | self.sequence
+    }
+
+    fn advance(&mut self) {
+        let now = now_millis();
+        if now > self.time {
+            self.time = now;
+            self.sequence = 0;
+        } else {
+            self.next_sequence();
+        }
+    }
+
+    fn reserve(&mut self, handle: u64) {
+        let node = (handle >> TIME_NODE_SHIFT) & 0xFF;
+        if node == u64::from(self.node) && handle >= self.peek() {
+            self.time = handle >> TIME_SHIFT;
+            self.sequence = handle & (TIME_SEQUENCE_LIMIT - 1);
+            self.next_sequence();
+        }
+    }
+}
+
+/// Issue pseudo-random handles, determined by a seed.
+///
+/// Tables with different seeds are very unlikely to issue the same
+/// handle, and tables with the same seed issue the same handles, so
+/// results stay reproducible. Handles are not issued in increasing
+/// order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// Issue handles determined by `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+}
+
+impl HandleAllocator for Random {
+    fn peek(&self) -> u64 {
+        // The finaliser of SplitMix64, applied to a Weyl sequence.
+        let mut z = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn advance(&mut self) {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    }
+}
+
+/// Issue handles in sequence, reusing those of deleted objects.
+///
+/// Handles released by deleting objects are kept in a free list, and
+/// the smallest of them is issued before any new handle. A process
+/// which keeps adding and deleting objects therefore never runs out of
+/// handles, and keeps them close together, which suits a
+/// [`ProxySet`](crate::ProxySet) or a `Vec` indexed by handle. A stale
+/// proxy for a deleted object never resolves to the object which
+/// reuses its handle, since that object's proxy has a later
+/// [generation](crate::Proxy::generation):
+///
+/// ```rust
+/// use persian_rug::handles::Recycling;
+/// use persian_rug::{contextual, persian_rug, Context};
+///
+/// #[contextual(Rug)]
+/// struct Particle {
+///   x: f32,
+/// }
+///
+/// #[persian_rug(handles = Recycling)]
+/// struct Rug(#[table] Particle);
+///
+/// let mut r = Rug(Default::default());
+/// let a = r.add(Particle { x: 0.0 });
+/// let b = r.add(Particle { x: 1.0 });
+/// r.delete(&a);
+///
+/// let c = r.add(Particle { x: 2.0 });
+/// assert_eq!(c.handle(), a.handle());
+/// assert!(r.try_get(&a).is_err());
+/// assert_eq!(r.add(Particle { x: 3.0 }).handle(), 2);
+/// # let _ = b;
+/// ```
+///
+/// Handles skipped over by
+/// [`Table::insert_with_handle`](crate::Table::insert_with_handle)
+/// are added to the free list too, so they are issued before any
+/// handle past the chosen one.
+///
+/// The free list is not kept when a table is archived or encoded, and
+/// nor are the generations of its handles.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recycling {
+    next: u64,
+    // Runs of free handles below `next`, from the first handle of each
+    // to the handle after its last. Runs never overlap or touch.
+    free: BTreeMap<u64, u64>,
+}
+
+impl Recycling {
+    /// Issue handles in sequence, starting from zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of released or skipped handles waiting to be
+    /// reused.
+    pub fn free_len(&self) -> usize {
+        self.free
+            .iter()
+            .map(|(start, end)| usize::try_from(end - start).unwrap_or(usize::MAX))
+            .fold(0, usize::saturating_add)
+    }
+
+    fn free_run(&mut self, start: u64, end: u64) {
+        let mut start = start;
+        let mut end = end;
+        if let Some((&before, &before_end)) = self.free.range(..start).next_back() {
+            if before_end >= start {
+                start = before;
+                end = end.max(before_end);
+            }
+        }
+        while let Some((&after, &after_end)) = self.free.range(start..).next() {
+            if after > end {
+                break;
+            }
+            self.free.remove(&after);
+            end = end.max(after_end);
+        }
+        self.free.insert(start, end);
+    }
+}
+
+impl HandleAllocator for Recycling {
+    fn peek(&self) -> u64 {
+        self.free.keys().next().copied().unwrap_or(self.next)
+    }
+
+    fn advance(&mut self) {
+        match self.free.pop_first() {
+            Some((start, end)) => {
+                if start + 1 < end {
+                    self.free.insert(start + 1, end);
+                }
+            }
+            None => self.next = self.next.saturating_add(1),
+        }
+    }
+
+    fn reserve(&mut self, handle: u64) {
+        if handle >= self.next {
+            if handle > self.next {
+                self.free_run(self.next, handle);
+            }
+            self.next = handle.saturating_add(1);
+        } else if let Some((&start, &end)) = self.free.range(..=handle).next_back() {
+            if handle < end {
+                self.free.remove(&start);
+                if start < handle {
+                    self.free.insert(start, handle);
+                }
+                if handle + 1 < end {
+                    self.free.insert(handle + 1, end);
+                }
+            }
+        }
+    }
+
+    fn release(&mut self, handle: u64) {
+        if handle < self.next {
+            self.free_run(handle, handle + 1);
+        }
+    }
+}
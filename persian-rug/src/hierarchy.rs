@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+
+use crate::{Accessor, Contextual, Mutator, Owner, Proxy, TableIterator, TableProxyIterator};
+
+/// An object which sits in a tree of objects of the same type.
+///
+/// Each object records its parent, and trees are edited by changing
+/// those records. Implementing this for a type gives contexts which
+/// hold it the methods of [`HierarchyAccessor`], for walking the
+/// tree, and of [`HierarchyMutator`], for moving objects around in it
+/// without creating cycles:
+///
+/// ```rust
+/// use persian_rug::{
+///     contextual, persian_rug, Context, HierarchyAccessor, HierarchyMutator, Proxy, TreeNode,
+/// };
+///
+/// #[contextual(Rug)]
+/// struct Shape {
+///   name: &'static str,
+///   parent: Option<Proxy<Shape>>,
+/// }
+///
+/// impl TreeNode for Shape {
+///     fn parent(&self) -> Option<Proxy<Self>> {
+///         self.parent
+///     }
+///     fn set_parent(&mut self, parent: Option<Proxy<Self>>) {
+///         self.parent = parent;
+///     }
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Shape);
+///
+/// let mut r = Rug(Default::default());
+/// let scene = r.add(Shape { name: "scene", parent: None });
+/// let car = r.add(Shape { name: "car", parent: Some(scene) });
+/// let wheel = r.add(Shape { name: "wheel", parent: Some(car) });
+/// let house = r.add(Shape { name: "house", parent: Some(scene) });
+///
+/// let names = |r: &Rug| {
+///     (&r).iter_subtree(&scene).map(|p| r.get(&p).name).collect::<Vec<_>>()
+/// };
+/// assert_eq!(names(&r), vec!["scene", "car", "wheel", "house"]);
+///
+/// (&mut r).reparent(&wheel, Some(house)).unwrap();
+/// assert_eq!(names(&r), vec!["scene", "car", "house", "wheel"]);
+/// assert!((&mut r).reparent(&scene, Some(wheel)).is_err());
+/// ```
+///
+/// Children are listed in the order of the table. Finding them scans
+/// the whole table, as do the other methods which look downwards, so
+/// [`iter_subtree`](HierarchyAccessor::iter_subtree) should be
+/// preferred to repeated calls to
+/// [`children`](HierarchyAccessor::children).
+pub trait TreeNode: Contextual + Sized {
+    /// The parent of this object, or [`None`] if it is a root.
+    fn parent(&self) -> Option<Proxy<Self>>;
+
+    /// Change the parent of this object.
+    ///
+    /// This is used by [`HierarchyMutator::reparent`], which checks
+    /// that the change does not create a cycle.
+    fn set_parent(&mut self, parent: Option<Proxy<Self>>);
+}
+
+/// Walking the trees formed by a [`TreeNode`] type.
+///
+/// This is implemented for every [`Accessor`].
+pub trait HierarchyAccessor: Accessor {
+    /// Iterate over the children of an object.
+    fn children<T>(&self, p: &Proxy<T>) -> ChildIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: TreeNode + Contextual<Context = Self::Context>,
+    {
+        ChildIterator {
+            iter: self.get_proxy_iter().zip(self.get_iter()),
+            parent: Some(*p),
+        }
+    }
+
+    /// Iterate over the objects which have no parent.
+    fn roots<T>(&self) -> ChildIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: TreeNode + Contextual<Context = Self::Context>,
+    {
+        ChildIterator {
+            iter: self.get_proxy_iter().zip(self.get_iter()),
+            parent: None,
+        }
+    }
+
+    /// Iterate over the ancestors of an object, starting with its
+    /// parent.
+    fn ancestors<T>(&self, p: &Proxy<T>) -> Ancestors<'_, Self, T>
+    where
+        Self::Context: Owner<T>,
+        T: TreeNode + Contextual<Context = Self::Context>,
+    {
+        Ancestors {
+            access: self,
+            next: self.get(p).parent(),
+        }
+    }
+
+    /// Iterate over an object and all of its descendants, depth
+    /// first, with each object before its children.
+    fn iter_subtree<T>(&self, root: &Proxy<T>) -> Subtree<T>
+    where
+        Self::Context: Owner<T>,
+        T: TreeNode + Contextual<Context = Self::Context>,
+    {
+        let mut children = BTreeMap::<_, Vec<_>>::new();
+        for p in self.get_proxy_iter() {
+            if let Some(parent) = self.get(p).parent() {
+                children.entry(parent).or_default().push(*p);
+            }
+        }
+        Subtree {
+            children,
+            stack: vec![*root],
+        }
+    }
+}
+
+impl<A: Accessor> HierarchyAccessor for A {}
+
+/// Editing the trees formed by a [`TreeNode`] type.
+///
+/// This is implemented for every [`Mutator`].
+pub trait HierarchyMutator: Mutator {
+    /// Move `child`, with all of its descendants, to be a child of
+    /// `parent`, or a root if `parent` is [`None`]. Returns the
+    /// previous parent of `child`.
+    ///
+    /// Fails, leaving the tree unchanged, if `parent` is `child`
+    /// itself or one of its descendants.
+    fn reparent<T>(
+        &mut self,
+        child: &Proxy<T>,
+        parent: Option<Proxy<T>>,
+    ) -> Result<Option<Proxy<T>>, ReparentError>
+    where
+        Self::Context: Owner<T>,
+        T: TreeNode + Contextual<Context = Self::Context>,
+    {
+        let mut next = parent;
+        while let Some(p) = next {
+            if p == *child {
+                return Err(ReparentError {
+                    child: child.handle(),
+                    parent: parent.unwrap().handle(),
+                });
+            }
+            next = self.get(&p).parent();
+        }
+        let node = self.get_mut(child);
+        let previous = node.parent();
+        node.set_parent(parent);
+        Ok(previous)
+    }
+}
+
+impl<M: Mutator> HierarchyMutator for M {}
+
+/// The error returned when a move would make an object its own
+/// ancestor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReparentError {
+    child: u64,
+    parent: u64,
+}
+
+impl ReparentError {
+    /// The handle of the object being moved.
+    pub fn child(&self) -> u64 {
+        self.child
+    }
+
+    /// The handle of the parent it was being moved to.
+    pub fn parent(&self) -> u64 {
+        self.parent
+    }
+}
+
+impl std::fmt::Display for ReparentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cannot move object {} under object {}, which is in its subtree",
+            self.child, self.parent
+        )
+    }
+}
+
+impl std::error::Error for ReparentError {}
+
+/// An [`Iterator`] over the children of an object, or over the roots
+/// of a table, in the order of the table.
+///
+/// This is created by [`HierarchyAccessor::children`] and
+/// [`HierarchyAccessor::roots`].
+pub struct ChildIterator<'a, T> {
+    iter: std::iter::Zip<TableProxyIterator<'a, T>, TableIterator<'a, T>>,
+    parent: Option<Proxy<T>>,
+}
+
+impl<T: TreeNode> Iterator for ChildIterator<'_, T> {
+    type Item = Proxy<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let parent = self.parent;
+        self.iter
+            .by_ref()
+            .find(|(_, node)| node.parent() == parent)
+            .map(|(p, _)| *p)
+    }
+}
+
+/// An [`Iterator`] over the ancestors of an object, nearest first.
+///
+/// This is created by [`HierarchyAccessor::ancestors`].
+pub struct Ancestors<'a, A, T> {
+    access: &'a A,
+    next: Option<Proxy<T>>,
+}
+
+impl<A, T> Iterator for Ancestors<'_, A, T>
+where
+    A: Accessor,
+    A::Context: Owner<T>,
+    T: TreeNode + Contextual<Context = A::Context>,
+{
+    type Item = Proxy<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let p = self.next?;
+        self.next = self.access.get(&p).parent();
+        Some(p)
+    }
+}
+
+/// An [`Iterator`] over an object and its descendants, depth first.
+///
+/// This is created by [`HierarchyAccessor::iter_subtree`]. It holds
+/// its own record of the tree, so the context can be changed while
+/// it is in use.
+pub struct Subtree<T> {
+    children: BTreeMap<Proxy<T>, Vec<Proxy<T>>>,
+    stack: Vec<Proxy<T>>,
+}
+
+impl<T> Iterator for Subtree<T> {
+    type Item = Proxy<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let p = self.stack.pop()?;
+        if let Some(children) = self.children.remove(&p) {
+            self.stack.extend(children.into_iter().rev());
+        }
+        Some(p)
+    }
+}
@@ -0,0 +1,299 @@
+//! Keeping the earlier values of objects.
+//!
+//! A [`History`] holds copies of objects as they were at each tick of
+//! a clock that the program advances itself, for example once per
+//! frame or once per command. Changes made through the [`Historian`]
+//! returned by [`History::historian`] are recorded automatically, and
+//! any earlier value can be looked up with
+//! [`get_at`](History::get_at). This makes it possible to step back
+//! through the states of an object when debugging, or to show a value
+//! as it was at some point in the past.
+//!
+//! Only the types registered with [`track`](History::track) are
+//! recorded, since each version is a clone of the object:
+//!
+//! ```rust
+//! use persian_rug::history::History;
+//! use persian_rug::{contextual, persian_rug, Context, Mutator};
+//!
+//! #[derive(Clone)]
+//! #[contextual(Rug)]
+//! struct Player {
+//!   x: i32,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Player);
+//!
+//! let mut r = Rug(Default::default());
+//! let mut history = History::new().track::<Player>();
+//!
+//! let p = history.historian(&mut r).add(Player { x: 0 });
+//! for _ in 0..3 {
+//!     history.advance();
+//!     history.historian(&mut r).get_mut(&p).x += 10;
+//! }
+//!
+//! assert_eq!(r.get(&p).x, 30);
+//! assert_eq!(history.get_at(&p, 0).unwrap().x, 0);
+//! assert_eq!(history.get_at(&p, 2).unwrap().x, 20);
+//! ```
+//!
+//! Each object keeps at most one version per tick, holding its value
+//! as of the last change made during that tick. A history made with
+//! [`bounded`](History::bounded) keeps only the most recent versions
+//! of each object, so that long-running programs do not accumulate
+//! them without limit.
+
+use std::any::Any;
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::{
+    Context, Contextual, Mutator, Owner, Proxy, TableIterator, TableMutIterator, TableProxyIterator,
+};
+
+type Snapshot<C> = fn(&C, (u64, u32)) -> Box<dyn Any>;
+type Versions = VecDeque<(u64, Box<dyn Any>)>;
+
+/// The earlier values of the objects in a context of type `C`.
+///
+/// See the [module documentation](self) for details.
+pub struct History<C> {
+    tick: u64,
+    limit: Option<usize>,
+    types: BTreeMap<&'static str, Snapshot<C>>,
+    versions: BTreeMap<(&'static str, (u64, u32)), Versions>,
+}
+
+impl<C: Context> History<C> {
+    /// Create a history which keeps every version of each object.
+    pub fn new() -> Self {
+        Self {
+            tick: 0,
+            limit: None,
+            types: BTreeMap::new(),
+            versions: BTreeMap::new(),
+        }
+    }
+
+    /// Create a history which keeps the last `limit` versions of
+    /// each object.
+    ///
+    /// Panics if `limit` is zero.
+    pub fn bounded(limit: usize) -> Self {
+        assert!(limit > 0, "a history must keep at least one version");
+        Self {
+            limit: Some(limit),
+            ..Self::new()
+        }
+    }
+
+    /// Record the changes made to objects of type `T`.
+    pub fn track<T>(mut self) -> Self
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + Clone + 'static,
+    {
+        self.types
+            .insert(std::any::type_name::<T>(), snapshot::<C, T>);
+        self
+    }
+
+    /// The current tick, which new versions are recorded at.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Move on to the next tick, returning it.
+    pub fn advance(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    /// Start recording the changes made to `context` at the current
+    /// tick.
+    pub fn historian<'a>(&'a mut self, context: &'a mut C) -> Historian<'a, C> {
+        Historian {
+            context,
+            history: self,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Record the current value of `what`, which may have been
+    /// changed without going through a [`Historian`].
+    pub fn record<T>(&mut self, context: &C, what: &Proxy<T>)
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + Clone + 'static,
+    {
+        self.push(
+            std::any::type_name::<T>(),
+            what.key(),
+            Box::new(Owner::get(context, what).clone()),
+        );
+    }
+
+    /// Retrieve the value `what` had at the end of `tick`.
+    ///
+    /// Returns [`None`] if no version was recorded at or before
+    /// `tick`, either because the object had not yet been added, or
+    /// because the versions from that time have been discarded.
+    pub fn get_at<T>(&self, what: &Proxy<T>, tick: u64) -> Option<&T>
+    where
+        T: 'static,
+    {
+        self.versions(what)
+            .take_while(|(at, _)| *at <= tick)
+            .last()
+            .map(|(_, value)| value)
+    }
+
+    /// Iterate over the recorded versions of `what`, oldest first,
+    /// with the tick each was recorded at.
+    pub fn versions<T>(&self, what: &Proxy<T>) -> impl Iterator<Item = (u64, &T)>
+    where
+        T: 'static,
+    {
+        self.versions
+            .get(&(std::any::type_name::<T>(), what.key()))
+            .into_iter()
+            .flatten()
+            .filter_map(|(tick, value)| Some((*tick, value.downcast_ref()?)))
+    }
+
+    /// Discard every recorded version, keeping the current tick.
+    pub fn clear(&mut self) {
+        self.versions.clear();
+    }
+
+    fn push(&mut self, ty: &'static str, key: (u64, u32), value: Box<dyn Any>) {
+        let versions = self.versions.entry((ty, key)).or_default();
+        if matches!(versions.back(), Some((tick, _)) if *tick == self.tick) {
+            versions.pop_back();
+        }
+        versions.push_back((self.tick, value));
+        if let Some(limit) = self.limit {
+            while versions.len() > limit {
+                versions.pop_front();
+            }
+        }
+    }
+}
+
+impl<C: Context> Default for History<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn snapshot<C, T>(context: &C, key: (u64, u32)) -> Box<dyn Any>
+where
+    C: Owner<T>,
+    T: Contextual<Context = C> + Clone + 'static,
+{
+    let p = Proxy::from_key(key);
+    Box::new(Owner::get(context, &p).clone())
+}
+
+/// A [`Mutator`] which records the changes made through it in a
+/// [`History`].
+///
+/// This is created by [`History::historian`]. Objects which are added
+/// are recorded straight away. Objects borrowed mutably are recorded
+/// with their value at the time of the next call to the historian, or
+/// when it is dropped. Objects of types which are not tracked by the
+/// history are passed through without being recorded.
+pub struct Historian<'a, C: Context> {
+    context: &'a mut C,
+    history: &'a mut History<C>,
+    pending: Vec<(&'static str, (u64, u32))>,
+}
+
+impl<C: Context> Historian<'_, C> {
+    fn flush(&mut self) {
+        for (ty, key) in std::mem::take(&mut self.pending) {
+            let value = (self.history.types[ty])(self.context, key);
+            self.history.push(ty, key, value);
+        }
+    }
+
+    fn tracked<T>(&self) -> Option<&'static str> {
+        let ty = std::any::type_name::<T>();
+        self.history.types.contains_key(ty).then_some(ty)
+    }
+}
+
+impl<C: Context> Drop for Historian<'_, C> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+impl<C: Context> Mutator for Historian<'_, C> {
+    type Context = C;
+
+    #[track_caller]
+    fn add<T>(&mut self, value: T) -> Proxy<T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        self.flush();
+        let p = Owner::add(self.context, value);
+        if let Some(ty) = self.tracked::<T>() {
+            self.pending.push((ty, p.key()));
+            self.flush();
+        }
+        p
+    }
+
+    fn get<T>(&self, what: &Proxy<T>) -> &T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        Owner::get(self.context, what)
+    }
+
+    fn get_mut<T>(&mut self, what: &Proxy<T>) -> &mut T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        self.flush();
+        if let Some(ty) = self.tracked::<T>() {
+            self.pending.push((ty, what.key()));
+        }
+        Owner::get_mut(self.context, what)
+    }
+
+    fn get_iter<T>(&self) -> TableIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        Owner::get_iter(self.context)
+    }
+
+    fn get_iter_mut<T>(&mut self) -> TableMutIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        self.flush();
+        if let Some(ty) = self.tracked::<T>() {
+            self.pending
+                .extend(Owner::<T>::get_proxy_iter(self.context).map(|p| (ty, p.key())));
+        }
+        Owner::get_iter_mut(self.context)
+    }
+
+    fn get_proxy_iter<T>(&self) -> TableProxyIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        Owner::get_proxy_iter(self.context)
+    }
+}
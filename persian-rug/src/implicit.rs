@@ -0,0 +1,167 @@
+//! A thread-local current context.
+//!
+//! This module is available with the `implicit` feature. Normally an
+//! [`Accessor`](crate::Accessor) or [`Mutator`](crate::Mutator) is
+//! passed to every function that needs one. For prototypes, and for
+//! scripting layers that call back into Rust, that can be more
+//! ceremony than it is worth. Instead, a context can be made
+//! [current](set_current) for a thread, after which it is available
+//! from the free functions here:
+//!
+//! ```rust
+//! use persian_rug::{contextual, implicit, persian_rug, Accessor, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Foo {
+//!   a: i32,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Foo);
+//!
+//! fn make_foo(a: i32) -> Proxy<Foo> {
+//!     implicit::add(Foo { a })
+//! }
+//!
+//! fn total() -> i32 {
+//!     implicit::with_current(|access: &Rug| access.get_iter::<Foo>().map(|foo| foo.a).sum())
+//! }
+//!
+//! implicit::set_current(Rug(Default::default()));
+//! make_foo(1);
+//! make_foo(2);
+//! assert_eq!(total(), 3);
+//!
+//! assert!(implicit::take_current::<Rug>().is_some());
+//! assert!(!implicit::has_current::<Rug>());
+//! ```
+//!
+//! Each thread has at most one current context of each type. The
+//! context is borrowed for the duration of each call, so adding an
+//! object from inside [`with_current`], for example, panics.
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::{Context, Contextual, Owner, Proxy};
+
+thread_local! {
+    static CURRENT: RefCell<BTreeMap<TypeId, Rc<dyn Any>>> = RefCell::new(BTreeMap::new());
+}
+
+fn current<C: Context + 'static>() -> Option<Rc<RefCell<C>>> {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .get(&TypeId::of::<C>())
+            .cloned()
+            .map(|context| {
+                context
+                    .downcast()
+                    .expect("current context has the wrong type")
+            })
+    })
+}
+
+fn expect_current<C: Context + 'static>() -> Rc<RefCell<C>> {
+    current().unwrap_or_else(|| panic!("no current context of type {}", std::any::type_name::<C>()))
+}
+
+/// Make `context` the current context of its type for this thread.
+///
+/// Returns the previous current context of the same type, if there
+/// was one.
+///
+/// # Panics
+///
+/// Panics if the previous context is still borrowed, for example by
+/// a call to [`with_current`].
+pub fn set_current<C: Context + 'static>(context: C) -> Option<C> {
+    let previous = take_current();
+    CURRENT.with(|current| {
+        current.borrow_mut().insert(
+            TypeId::of::<C>(),
+            Rc::new(RefCell::new(context)) as Rc<dyn Any>,
+        )
+    });
+    previous
+}
+
+/// Remove the current context of type `C` for this thread, and
+/// return it.
+///
+/// # Panics
+///
+/// Panics if the context is still borrowed, for example by a call to
+/// [`with_current`].
+pub fn take_current<C: Context + 'static>() -> Option<C> {
+    let context = CURRENT.with(|current| {
+        let mut current = current.borrow_mut();
+        if Rc::strong_count(current.get(&TypeId::of::<C>())?) > 1 {
+            panic!(
+                "current context of type {} is still borrowed",
+                std::any::type_name::<C>()
+            );
+        }
+        current.remove(&TypeId::of::<C>())
+    })?;
+    let context = context
+        .downcast::<RefCell<C>>()
+        .expect("current context has the wrong type");
+    Rc::try_unwrap(context)
+        .ok()
+        .map(|context| context.into_inner())
+}
+
+/// Check whether this thread has a current context of type `C`.
+pub fn has_current<C: Context + 'static>() -> bool {
+    CURRENT.with(|current| current.borrow().contains_key(&TypeId::of::<C>()))
+}
+
+/// Call `f` with an [`Accessor`](crate::Accessor) for the current
+/// context of type `C`.
+///
+/// # Panics
+///
+/// Panics if there is no current context of type `C`, or if it is
+/// already borrowed mutably.
+pub fn with_current<C, R>(f: impl FnOnce(&C) -> R) -> R
+where
+    C: Context + 'static,
+{
+    let context = expect_current::<C>();
+    let context = context.borrow();
+    f(&context)
+}
+
+/// Call `f` with a [`Mutator`](crate::Mutator) for the current
+/// context of type `C`.
+///
+/// # Panics
+///
+/// Panics if there is no current context of type `C`, or if it is
+/// already borrowed.
+pub fn with_current_mut<C, R>(f: impl FnOnce(&mut C) -> R) -> R
+where
+    C: Context + 'static,
+{
+    let context = expect_current::<C>();
+    let mut context = context.borrow_mut();
+    f(&mut context)
+}
+
+/// Add an object to the current context of its type.
+///
+/// # Panics
+///
+/// Panics if there is no current context for `T`, or if it is already
+/// borrowed.
+pub fn add<T>(value: T) -> Proxy<T>
+where
+    T: Contextual,
+    T::Context: Owner<T> + 'static,
+{
+    with_current_mut(|context: &mut T::Context| Owner::add(context, value))
+}
@@ -0,0 +1,230 @@
+//! Importing object graphs built from shared pointers.
+//!
+//! Code which predates persian-rug often links its objects together
+//! with [`Rc`](std::rc::Rc) or [`Arc`](std::sync::Arc). The
+//! [`import`] function recreates such a graph inside a context: it
+//! walks the graph from some roots, using a closure to find the
+//! children of each node, and then converts each node into an object
+//! of the context. Nodes reachable along more than one path are
+//! identified by their address, so each is added to the context only
+//! once, and cycles are handled.
+//!
+//! ```rust
+//! use std::cell::RefCell;
+//! use std::rc::Rc;
+//!
+//! use persian_rug::{contextual, import, persian_rug, Context, Proxy};
+//!
+//! // The legacy representation.
+//! struct OldNode {
+//!     name: String,
+//!     children: RefCell<Vec<Rc<OldNode>>>,
+//! }
+//!
+//! #[contextual(Rug)]
+//! struct Node {
+//!     name: String,
+//!     children: Vec<Proxy<Node>>,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Node);
+//!
+//! let leaf = Rc::new(OldNode { name: "leaf".to_string(), children: RefCell::new(Vec::new()) });
+//! let root = Rc::new(OldNode {
+//!     name: "root".to_string(),
+//!     children: RefCell::new(vec![leaf.clone(), leaf.clone()]),
+//! });
+//!
+//! let mut r = Rug(Default::default());
+//! let imported = import::import(
+//!     &mut r,
+//!     [root.clone()],
+//!     |node| node.children.borrow().clone(),
+//!     |node, imported| Node {
+//!         name: node.name.clone(),
+//!         children: node
+//!             .children
+//!             .borrow()
+//!             .iter()
+//!             .map(|child| imported.proxy(child).unwrap())
+//!             .collect(),
+//!     },
+//! )
+//! .unwrap();
+//!
+//! // The leaf is shared, so it is only imported once.
+//! assert_eq!(imported.len(), 2);
+//! let new_root = r.get(&imported.roots()[0]);
+//! assert_eq!(new_root.children[0], new_root.children[1]);
+//! assert_eq!(Some(new_root.children[0]), imported.proxy(&leaf));
+//! ```
+
+use std::collections::BTreeMap;
+use std::ops::Deref;
+
+use crate::{Contextual, Owner, Proxy};
+
+/// A failure to [`import`] a graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportError {
+    /// The context cannot set proxies aside for the imported objects,
+    /// because its [`Owner::reserve_proxy`] is not implemented.
+    CannotReserve,
+    /// The context did not store an imported object under the handle
+    /// set aside for it.
+    NotStored(u64),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::CannotReserve => {
+                write!(f, "context cannot set proxies aside for imported objects")
+            }
+            ImportError::NotStored(handle) => write!(
+                f,
+                "context did not store an imported object under handle {}",
+                handle
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// The result of an [`import`].
+///
+/// This maps each node that was imported to the proxy of the object
+/// created for it.
+pub struct Imported<P, T> {
+    nodes: Vec<P>,
+    index: BTreeMap<*const (), usize>,
+    roots: Vec<Proxy<T>>,
+    proxies: Vec<Proxy<T>>,
+}
+
+fn address<P: Deref>(node: &P) -> *const () {
+    &**node as *const P::Target as *const ()
+}
+
+impl<P: Deref + Clone, T> Imported<P, T> {
+    fn proxy_at(&self, index: usize) -> Proxy<T> {
+        self.proxies[index]
+    }
+
+    fn discover(&mut self, node: P) -> usize {
+        let next = self.nodes.len();
+        let index = *self.index.entry(address(&node)).or_insert(next);
+        if index == next {
+            self.nodes.push(node);
+        }
+        index
+    }
+
+    /// The proxy for `node`, if it was imported.
+    pub fn proxy(&self, node: &P) -> Option<Proxy<T>> {
+        self.index
+            .get(&address(node))
+            .map(|index| self.proxy_at(*index))
+    }
+
+    /// The proxies for the roots, in the order they were given.
+    pub fn roots(&self) -> &[Proxy<T>] {
+        &self.roots
+    }
+
+    /// The number of nodes imported.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Check whether no nodes were imported.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Iterate over the imported nodes and their proxies, in the
+    /// order they were added to the context.
+    pub fn iter(&self) -> impl Iterator<Item = (&P, Proxy<T>)> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (node, self.proxy_at(index)))
+    }
+}
+
+/// Import the graph reachable from `roots` into `context`.
+///
+/// The graph is walked breadth first, calling `edges` to find the
+/// children of each node. Once every node has been found, `convert`
+/// is called for each in turn to create its object, and may look up
+/// the proxy of any node in the graph, including ones which have not
+/// been added yet. Nodes are pointers of any kind, typically
+/// [`Rc`](std::rc::Rc) or [`Arc`](std::sync::Arc), and are identified
+/// by the address they point to.
+///
+/// The proxies of the new objects are set aside with
+/// [`Owner::reserve_proxy`] before any of them is added, so this
+/// works whatever [`HandleAllocator`](crate::handles::HandleAllocator)
+/// the context's table uses. Returns an error, leaving the context
+/// unchanged, if the context cannot set proxies aside, which is only
+/// possible for owners written by hand.
+pub fn import<C, T, P, E, I, F>(
+    context: &mut C,
+    roots: impl IntoIterator<Item = P>,
+    mut edges: E,
+    mut convert: F,
+) -> Result<Imported<P, T>, ImportError>
+where
+    C: Owner<T>,
+    T: Contextual<Context = C>,
+    P: Deref + Clone,
+    E: FnMut(&P) -> I,
+    I: IntoIterator<Item = P>,
+    F: FnMut(&P, &Imported<P, T>) -> T,
+{
+    let mut imported = Imported {
+        nodes: Vec::new(),
+        index: BTreeMap::new(),
+        roots: Vec::new(),
+        proxies: Vec::new(),
+    };
+
+    let roots = roots
+        .into_iter()
+        .map(|root| imported.discover(root))
+        .collect::<Vec<_>>();
+    let mut next = 0;
+    while next < imported.nodes.len() {
+        let node = imported.nodes[next].clone();
+        for child in edges(&node) {
+            imported.discover(child);
+        }
+        next += 1;
+    }
+
+    for _ in 0..imported.nodes.len() {
+        match Owner::reserve_proxy(context) {
+            Some(p) => imported.proxies.push(p),
+            None => {
+                for p in &imported.proxies {
+                    Owner::release_proxy(context, p);
+                }
+                return Err(ImportError::CannotReserve);
+            }
+        }
+    }
+    imported.roots = roots.into_iter().map(|ix| imported.proxy_at(ix)).collect();
+
+    for index in 0..imported.nodes.len() {
+        let value = convert(&imported.nodes[index], &imported);
+        let p = imported.proxy_at(index);
+        match Owner::insert_with_handle(context, p.index, value) {
+            Ok(q) if q == p => {}
+            _ => return Err(ImportError::NotStored(p.index)),
+        }
+    }
+
+    Ok(imported)
+}
@@ -0,0 +1,444 @@
+//! Browsing a context from an [`egui`] user interface.
+//!
+//! This module is available with the `egui` feature. An [`Inspector`]
+//! is a widget which shows every table of a context, and the objects
+//! in each. Selecting an object shows its value, with each proxy in
+//! it as a link to the object it refers to, and each object holding
+//! a proxy to it as a link back. The inspector keeps the values it
+//! saw on earlier frames, so that objects which change are
+//! highlighted, and the history of the selected object can be read
+//! back.
+//!
+//! The inspector reads contexts through their [`DebugJson`]
+//! implementation, so it works for any context declared with
+//! `#[persian_rug(json)]`, and uses the [`schema`](crate::schema) of
+//! the context to know which fields hold proxies.
+//!
+//! ```rust
+//! use persian_rug::inspector::Inspector;
+//! use persian_rug::serde::Serialize;
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[derive(Serialize)]
+//! #[serde(crate = "persian_rug::serde")]
+//! #[contextual(Rug)]
+//! struct Person {
+//!   name: String,
+//!   manager: Option<Proxy<Person>>,
+//! }
+//!
+//! #[persian_rug(json)]
+//! struct Rug(#[table] Person);
+//!
+//! let mut r = Rug(Default::default());
+//! let boss = r.add(Person { name: "Alice".to_string(), manager: None });
+//! r.add(Person { name: "Bob".to_string(), manager: Some(boss) });
+//!
+//! let mut inspector = Inspector::new();
+//! inspector.select(&boss);
+//!
+//! // In an application, this is called as each frame is drawn.
+//! let ctx = persian_rug::egui::Context::default();
+//! let _ = ctx.run(Default::default(), |ctx| {
+//!     persian_rug::egui::CentralPanel::default().show(ctx, |ui| {
+//!         inspector.show(ui, &r);
+//!     });
+//! });
+//! assert_eq!(inspector.history(&boss).count(), 1);
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+
+use egui::{Color32, RichText, ScrollArea, Ui};
+use serde_json::Value;
+
+use crate::json::DebugJson;
+use crate::schema::ContextSchema;
+use crate::Proxy;
+
+/// An object in a context, identified by the full name of its type
+/// and its handle.
+type Key = (String, u64);
+
+/// One recorded value of an object.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    frame: u64,
+    value: Option<Value>,
+}
+
+impl Change {
+    /// The number of the observation which saw this value, counting
+    /// from 1.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// The value of the object, as converted by [`DebugJson`], or
+    /// [`None`] if the object was no longer stored.
+    pub fn value(&self) -> Option<&Value> {
+        self.value.as_ref()
+    }
+}
+
+/// A widget for browsing the tables of a context.
+///
+/// See the [module documentation](self) for details.
+pub struct Inspector {
+    frame: u64,
+    history_len: usize,
+    snapshot: BTreeMap<Key, Value>,
+    history: BTreeMap<Key, VecDeque<Change>>,
+    selected: Option<Key>,
+    back: Vec<Key>,
+    filter: String,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inspector {
+    /// Create an inspector which keeps the last 16 values of each
+    /// object.
+    pub fn new() -> Self {
+        Self {
+            frame: 0,
+            history_len: 16,
+            snapshot: BTreeMap::new(),
+            history: BTreeMap::new(),
+            selected: None,
+            back: Vec::new(),
+            filter: String::new(),
+        }
+    }
+
+    /// Keep the last `len` values of each object.
+    pub fn with_history(mut self, len: usize) -> Self {
+        self.history_len = len.max(1);
+        self
+    }
+
+    /// The number of times a context has been observed.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Show the object `what` when the inspector is next drawn.
+    pub fn select<T>(&mut self, what: &Proxy<T>) {
+        self.navigate((std::any::type_name::<T>().to_string(), what.handle()));
+    }
+
+    /// The full type name and handle of the object being shown.
+    pub fn selected(&self) -> Option<(&str, u64)> {
+        self.selected
+            .as_ref()
+            .map(|(ty, handle)| (ty.as_str(), *handle))
+    }
+
+    /// The values recorded for `what`, oldest first.
+    ///
+    /// A value is recorded when the object is first seen, and each
+    /// time it is seen to have changed or been removed.
+    pub fn history<T>(&self, what: &Proxy<T>) -> impl Iterator<Item = &Change> {
+        self.history
+            .get(&(std::any::type_name::<T>().to_string(), what.handle()))
+            .into_iter()
+            .flatten()
+    }
+
+    /// Record the current state of `context`.
+    ///
+    /// This is done by [`show`](Self::show), and only needs to be
+    /// called directly to keep watching a context while the inspector
+    /// is hidden.
+    pub fn observe<C: DebugJson>(&mut self, context: &C) {
+        self.frame += 1;
+        let mut snapshot = BTreeMap::new();
+        if let Value::Object(tables) = context.to_debug_value()["tables"].take() {
+            for (ty, objects) in tables {
+                let Value::Array(objects) = objects else {
+                    continue;
+                };
+                for mut object in objects {
+                    if let Some(handle) = object["handle"].as_u64() {
+                        snapshot.insert((ty.clone(), handle), object["value"].take());
+                    }
+                }
+            }
+        }
+
+        for (key, value) in &snapshot {
+            if self.snapshot.get(key) != Some(value) {
+                self.record(key.clone(), Some(value.clone()));
+            }
+        }
+        let removed = self
+            .snapshot
+            .keys()
+            .filter(|key| !snapshot.contains_key(*key))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in removed {
+            self.record(key, None);
+        }
+        self.snapshot = snapshot;
+    }
+
+    fn record(&mut self, key: Key, value: Option<Value>) {
+        let frame = self.frame;
+        let history = self.history.entry(key).or_default();
+        history.push_back(Change { frame, value });
+        while history.len() > self.history_len {
+            history.pop_front();
+        }
+    }
+
+    fn navigate(&mut self, key: Key) {
+        if let Some(previous) = self.selected.replace(key) {
+            self.back.push(previous);
+        }
+    }
+
+    fn changed_now(&self, key: &Key) -> bool {
+        self.history
+            .get(key)
+            .and_then(|history| history.back())
+            .is_some_and(|change| change.frame == self.frame && self.frame > 1)
+    }
+
+    /// Observe `context`, and draw the inspector into `ui`.
+    pub fn show<C: DebugJson>(&mut self, ui: &mut Ui, context: &C) {
+        self.observe(context);
+        let schema = C::schema();
+        let mut clicked = None;
+
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(schema.name).strong());
+            ui.label(format!("frame {}", self.frame));
+            ui.label("Filter:");
+            ui.text_edit_singleline(&mut self.filter);
+        });
+        ui.separator();
+
+        ui.columns(2, |columns| {
+            ScrollArea::vertical()
+                .id_salt("persian-rug-inspector-tables")
+                .show(&mut columns[0], |ui| {
+                    self.tables_ui(ui, &schema, &mut clicked);
+                });
+            ScrollArea::vertical()
+                .id_salt("persian-rug-inspector-object")
+                .show(&mut columns[1], |ui| {
+                    self.object_ui(ui, &schema, &mut clicked);
+                });
+        });
+
+        if let Some(key) = clicked {
+            self.navigate(key);
+        }
+    }
+
+    fn tables_ui(&self, ui: &mut Ui, schema: &ContextSchema, clicked: &mut Option<Key>) {
+        let filter = self.filter.to_lowercase();
+        for table in &schema.tables {
+            let ty = table.schema.type_name;
+            let objects = self
+                .snapshot
+                .range((ty.to_string(), 0)..=(ty.to_string(), u64::MAX))
+                .filter(|(_, value)| {
+                    filter.is_empty() || value.to_string().to_lowercase().contains(&filter)
+                })
+                .collect::<Vec<_>>();
+            egui::CollapsingHeader::new(format!("{} ({})", table.schema.name, objects.len()))
+                .id_salt(ty)
+                .show(ui, |ui| {
+                    for (key, value) in objects {
+                        let mut text = RichText::new(format!(
+                            "{}({}) {}",
+                            table.schema.name,
+                            key.1,
+                            preview(value)
+                        ));
+                        if self.changed_now(key) {
+                            text = text.color(ui.visuals().warn_fg_color);
+                        }
+                        let selected = self.selected.as_ref() == Some(key);
+                        if ui.selectable_label(selected, text).clicked() {
+                            *clicked = Some(key.clone());
+                        }
+                    }
+                });
+        }
+    }
+
+    fn object_ui(&mut self, ui: &mut Ui, schema: &ContextSchema, clicked: &mut Option<Key>) {
+        let Some(key) = self.selected.clone() else {
+            ui.label("Select an object to inspect it.");
+            return;
+        };
+        let table = schema.table(&key.0);
+        let name = table.map_or(key.0.as_str(), |table| table.schema.name);
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(!self.back.is_empty(), egui::Button::new("Back"))
+                .clicked()
+            {
+                self.selected = self.back.pop();
+            }
+            ui.heading(format!("{}({})", name, key.1));
+        });
+
+        let Some(value) = self.snapshot.get(&key) else {
+            ui.colored_label(ui.visuals().error_fg_color, "not stored");
+            return;
+        };
+
+        // Fields which can only hold proxies to one type link their
+        // handles to objects of that type.
+        let mut links = BTreeMap::new();
+        if let Some(table) = table {
+            for field in table.schema.all_fields() {
+                if let [target] = field.proxies[..] {
+                    links.insert(field.name.to_string(), target);
+                }
+            }
+        }
+        let link = |field: &str| links.get(field).copied();
+        match value {
+            Value::Object(fields) => {
+                for (field, value) in fields {
+                    value_ui(ui, field, value, link(field), schema, clicked);
+                }
+            }
+            Value::Array(fields) => {
+                for (i, value) in fields.iter().enumerate() {
+                    let field = i.to_string();
+                    value_ui(ui, &field, value, link(&field), schema, clicked);
+                }
+            }
+            value => value_ui(ui, "value", value, None, schema, clicked),
+        }
+
+        let referrers = self.referrers(schema, &key);
+        if !referrers.is_empty() {
+            ui.separator();
+            ui.label(RichText::new("Referred to by").strong());
+            for referrer in referrers {
+                if ui.link(object_name(schema, &referrer)).clicked() {
+                    *clicked = Some(referrer);
+                }
+            }
+        }
+
+        if let Some(history) = self.history.get(&key) {
+            ui.separator();
+            egui::CollapsingHeader::new(format!("History ({})", history.len()))
+                .id_salt("persian-rug-inspector-history")
+                .show(ui, |ui| {
+                    for change in history.iter().rev() {
+                        let text = match &change.value {
+                            Some(value) => preview(value),
+                            None => "removed".to_string(),
+                        };
+                        ui.label(format!("frame {}: {}", change.frame, text));
+                    }
+                });
+        }
+    }
+
+    fn referrers(&self, schema: &ContextSchema, key: &Key) -> Vec<Key> {
+        let mut res = Vec::new();
+        for (table, field) in schema.referrers_of(&key.0) {
+            if field.proxies.len() != 1 {
+                continue;
+            }
+            let ty = table.schema.type_name.to_string();
+            for (referrer, value) in self
+                .snapshot
+                .range((ty.clone(), 0)..=(ty.clone(), u64::MAX))
+            {
+                let value = match value {
+                    Value::Array(fields) => {
+                        field.name.parse::<usize>().ok().and_then(|i| fields.get(i))
+                    }
+                    value => value.get(field.name),
+                };
+                if value.is_some_and(|value| holds_handle(value, key.1)) && !res.contains(referrer)
+                {
+                    res.push(referrer.clone());
+                }
+            }
+        }
+        res
+    }
+}
+
+fn value_ui(
+    ui: &mut Ui,
+    label: &str,
+    value: &Value,
+    link: Option<&'static str>,
+    schema: &ContextSchema,
+    clicked: &mut Option<Key>,
+) {
+    match value {
+        Value::Object(fields) if !fields.is_empty() => {
+            ui.collapsing(label, |ui| {
+                for (field, value) in fields {
+                    value_ui(ui, field, value, link, schema, clicked);
+                }
+            });
+        }
+        Value::Array(items) if !items.is_empty() => {
+            ui.collapsing(format!("{} [{}]", label, items.len()), |ui| {
+                for (i, value) in items.iter().enumerate() {
+                    value_ui(ui, &i.to_string(), value, link, schema, clicked);
+                }
+            });
+        }
+        Value::Number(n) if link.is_some() && n.is_u64() => {
+            let key = (link.unwrap().to_string(), n.as_u64().unwrap());
+            ui.horizontal(|ui| {
+                ui.label(format!("{}:", label));
+                if ui.link(object_name(schema, &key)).clicked() {
+                    *clicked = Some(key);
+                }
+            });
+        }
+        value => {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}:", label));
+                ui.label(RichText::new(value.to_string()).color(Color32::GRAY));
+            });
+        }
+    }
+}
+
+fn object_name(schema: &ContextSchema, key: &Key) -> String {
+    let name = schema
+        .table(&key.0)
+        .map_or(key.0.as_str(), |table| table.schema.name);
+    format!("{}({})", name, key.1)
+}
+
+fn holds_handle(value: &Value, handle: u64) -> bool {
+    match value {
+        Value::Number(n) => n.as_u64() == Some(handle),
+        Value::Array(items) => items.iter().any(|item| holds_handle(item, handle)),
+        Value::Object(fields) => fields.values().any(|item| holds_handle(item, handle)),
+        _ => false,
+    }
+}
+
+fn preview(value: &Value) -> String {
+    const LIMIT: usize = 60;
+    let text = value.to_string();
+    match text.char_indices().nth(LIMIT) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
@@ -0,0 +1,1085 @@
+//! Comparing contexts up to the numbering of their objects.
+//!
+//! The handles issued by a context depend on the order in which
+//! objects were added, so two runs of a program can build the same
+//! logical graph with different handles. Comparing such contexts
+//! table by table reports a difference where there is none. The
+//! check here instead looks for a renumbering of the objects under
+//! which the two contexts are identical: objects must have equal
+//! contents apart from their links, and must link to corresponding
+//! objects.
+//!
+//! Passing `isomorphism` to the [`persian_rug`](crate::persian_rug)
+//! attribute implements [`Isomorphic`] for a context. Every type it
+//! stores must implement [`Relink`], usually by marking its link
+//! fields with `#[link]`, as well as [`Clone`] and [`Ord`]:
+//!
+//! ```rust
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//! use persian_rug::isomorphism::Isomorphic;
+//!
+//! #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+//! #[contextual(Rug)]
+//! struct Person {
+//!   name: &'static str,
+//!   #[link]
+//!   friends: Vec<Proxy<Person>>,
+//! }
+//!
+//! #[persian_rug(isomorphism)]
+//! struct Rug(#[table] Person);
+//!
+//! let mut a = Rug(Default::default());
+//! let ann = a.add(Person { name: "Ann", friends: vec![] });
+//! a.add(Person { name: "Bob", friends: vec![ann] });
+//!
+//! let mut b = Rug(Default::default());
+//! let bob = b.add(Person { name: "Bob", friends: vec![] });
+//! let ann = b.add(Person { name: "Ann", friends: vec![] });
+//! b.get_mut(&bob).friends.push(ann);
+//!
+//! assert!(a.is_isomorphic(&b));
+//!
+//! b.get_mut(&ann).friends.push(bob);
+//! assert!(!a.is_isomorphic(&b));
+//! ```
+//!
+//! The contents of objects are compared with their links replaced by
+//! a placeholder, so the [`Ord`] implementation of each type is only
+//! used to sort objects with the same link structure. Links to
+//! objects which are missing from a context are treated as equal to
+//! each other.
+//!
+//! A context can also be brought into a canonical form, in which its
+//! objects are numbered in an order derived from their contents and
+//! links, with [`Isomorphic::canonicalize`]. Isomorphic contexts are
+//! identical once canonicalized, so snapshots of them serialize to the
+//! same bytes, and diff cleanly in version control:
+//!
+//! ```rust
+//! # use persian_rug::{contextual, persian_rug, Context, Proxy};
+//! # use persian_rug::isomorphism::Isomorphic;
+//! #
+//! # #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+//! # #[contextual(Rug)]
+//! # struct Person {
+//! #   name: &'static str,
+//! #   #[link]
+//! #   friends: Vec<Proxy<Person>>,
+//! # }
+//! #
+//! # #[persian_rug(isomorphism)]
+//! # struct Rug(#[table] Person);
+//! #
+//! let mut r = Rug(Default::default());
+//! let zoe = r.add(Person { name: "Zoe", friends: vec![] });
+//! let amy = r.add(Person { name: "Amy", friends: vec![zoe] });
+//!
+//! let renumbering = r.canonicalize();
+//! let amy = renumbering.get(&amy).unwrap();
+//! let zoe = renumbering.get(&zoe).unwrap();
+//! let people = r.get_iter::<Person>().collect::<Vec<_>>();
+//! assert_eq!(people[0], &Person { name: "Amy", friends: vec![zoe] });
+//! assert_eq!(people[1], &Person { name: "Zoe", friends: vec![] });
+//! assert_eq!(r.get(&amy).name, "Amy");
+//! ```
+//!
+//! Two objects, possibly in different contexts, can also be compared
+//! along with everything reachable from them, with [`graph_eq`]. This
+//! is useful for checking that copying a group of objects from one
+//! context to another preserved its meaning:
+//!
+//! ```rust
+//! # use persian_rug::{contextual, persian_rug, Context, Proxy};
+//! # use persian_rug::isomorphism::graph_eq;
+//! #
+//! # #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+//! # #[contextual(Rug)]
+//! # struct Person {
+//! #   name: &'static str,
+//! #   #[link]
+//! #   friends: Vec<Proxy<Person>>,
+//! # }
+//! #
+//! # #[persian_rug(isomorphism)]
+//! # struct Rug(#[table] Person);
+//! #
+//! let mut a = Rug(Default::default());
+//! let ann = a.add(Person { name: "Ann", friends: vec![] });
+//! let bob = a.add(Person { name: "Bob", friends: vec![ann] });
+//! a.get_mut(&ann).friends.push(bob);
+//!
+//! let mut b = Rug(Default::default());
+//! b.add(Person { name: "Cat", friends: vec![] });
+//! let bob2 = b.add(Person { name: "Bob", friends: vec![] });
+//! let ann2 = b.add(Person { name: "Ann", friends: vec![bob2] });
+//! b.get_mut(&bob2).friends.push(ann2);
+//!
+//! assert!(graph_eq(&a, &bob, &b, &bob2));
+//! assert!(!graph_eq(&a, &bob, &b, &ann2));
+//! ```
+//!
+//! When objects or contexts differ, [`graph_difference`] and
+//! [`context_difference`] describe where, for use in test failure
+//! messages; the [`testing`](crate::testing) module has assertions
+//! built on them.
+//!
+//! Both operations on whole contexts work on each connected group of objects
+//! separately. Within a group, they refine a colouring of the objects
+//! by their contents and links until it settles, and only search when
+//! that leaves objects which cannot be told apart. This is fast for
+//! the graphs usually found in practice, but large, highly symmetric
+//! groups of objects can be slow to handle.
+
+use std::any::TypeId;
+use std::collections::{BTreeMap, BTreeSet};
+
+use std::ops::Deref;
+
+use crate::storage::Storage;
+use crate::{Accessor, AnyProxy, Context, Contextual, Owner, Proxy, Relink, Table};
+
+/// A context which can be compared with others up to the numbering
+/// of its objects.
+///
+/// Implementations are generated by the
+/// [`persian_rug`](crate::persian_rug) attribute when given the
+/// `isomorphism` option.
+pub trait Isomorphic: Context {
+    /// Describe each table of this context to `graph`.
+    fn describe(graph: &mut Describe<'_, Self>)
+    where
+        Self: Sized;
+
+    /// Renumber the objects in each table of this context.
+    fn renumber(&mut self, renumbering: &Renumbering);
+
+    /// Check whether this context holds the same objects as `other`,
+    /// linked in the same way, once the objects of one of them have
+    /// been renumbered.
+    fn is_isomorphic(&self, other: &Self) -> bool
+    where
+        Self: Sized,
+    {
+        Graph::build(&[self, other]).isomorphic()
+    }
+
+    /// Renumber the objects in this context in an order derived from
+    /// their contents and links.
+    ///
+    /// Isomorphic contexts are numbered identically once
+    /// canonicalized, so serializing a canonicalized context gives
+    /// the same output however it was built. The returned
+    /// [`Renumbering`] gives the new proxies of the objects.
+    ///
+    /// Objects are ordered first by their contents, with links
+    /// replaced by a placeholder, and then by their place in the
+    /// graph. Where this leaves objects which cannot be told apart,
+    /// each way of telling them apart is tried, and the one giving
+    /// the least numbered graph is kept.
+    fn canonicalize(&mut self) -> Renumbering
+    where
+        Self: Sized,
+    {
+        let renumbering = Graph::build(&[&*self]).canonical();
+        self.renumber(&renumbering);
+        renumbering
+    }
+}
+
+/// Check whether two objects, and everything reachable from them,
+/// are the same.
+///
+/// The objects may be in different contexts. They are the same if
+/// they have equal contents apart from their links, and link, in the
+/// same order, to objects which are themselves the same. Links which
+/// lead back to objects already compared must lead to the
+/// corresponding object on the other side, so cycles are handled, and
+/// two links to the same object are not the same as links to two
+/// equal objects. The handles of the objects do not matter.
+///
+/// The context must implement [`Isomorphic`], which describes its
+/// tables. As with [`Isomorphic::is_isomorphic`], contents are compared with
+/// [`Ord`], and links to objects which are missing from a context are
+/// treated as equal to each other. See the [module
+/// documentation](self) for an example.
+pub fn graph_eq<A, B, C, T>(
+    access_a: A,
+    proxy_a: &Proxy<T>,
+    access_b: B,
+    proxy_b: &Proxy<T>,
+) -> bool
+where
+    A: Accessor<Context = C> + Deref<Target = C>,
+    B: Accessor<Context = C> + Deref<Target = C>,
+    C: Isomorphic,
+    T: 'static,
+{
+    let contexts = [&*access_a, &*access_b];
+    let graph = Graph::build(&contexts);
+    let a = graph.find(0, AnyProxy::new(*proxy_a));
+    let b = graph.find(1, AnyProxy::new(*proxy_b));
+    graph.rooted_difference(a, b).is_none()
+}
+
+/// Find where two objects, and everything reachable from them, first
+/// differ.
+///
+/// This makes the same comparison as [`graph_eq`], returning
+/// [`None`] if the objects are the same, and otherwise the path of
+/// links from the two objects to a pair of objects which differ.
+/// Links are followed breadth first, so the path is as short as
+/// possible.
+pub fn graph_difference<A, B, C, T>(
+    access_a: A,
+    proxy_a: &Proxy<T>,
+    access_b: B,
+    proxy_b: &Proxy<T>,
+) -> Option<GraphDifference>
+where
+    A: Accessor<Context = C> + Deref<Target = C>,
+    B: Accessor<Context = C> + Deref<Target = C>,
+    C: Isomorphic,
+    T: 'static,
+{
+    let contexts = [&*access_a, &*access_b];
+    let graph = Graph::build(&contexts);
+    let a = graph.find(0, AnyProxy::new(*proxy_a));
+    let b = graph.find(1, AnyProxy::new(*proxy_b));
+    graph.rooted_difference(a, b)
+}
+
+/// Find a group of objects in one of two contexts which has no
+/// counterpart in the other.
+///
+/// Returns [`None`] if the contexts are isomorphic (see
+/// [`Isomorphic::is_isomorphic`]).
+pub fn context_difference<C: Isomorphic>(a: &C, b: &C) -> Option<ContextDifference> {
+    Graph::build(&[a, b]).unmatched()
+}
+
+/// The ways in which two objects can differ.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difference {
+    /// The objects have different contents, apart from their links.
+    Contents,
+    /// The objects hold different numbers of links.
+    LinkCount,
+    /// One of the objects is missing from its context.
+    Missing,
+    /// One of the objects was already matched with a different
+    /// object, so that one side reaches a single object along two
+    /// paths where the other reaches two.
+    Shared,
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difference::Contents => write!(f, "objects have different contents"),
+            Difference::LinkCount => write!(f, "objects hold different numbers of links"),
+            Difference::Missing => write!(f, "one object is missing"),
+            Difference::Shared => write!(f, "one object is reached along two different paths"),
+        }
+    }
+}
+
+/// One pair of objects on the path to a [`GraphDifference`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GraphStep {
+    /// The link followed from the previous pair to reach this one, or
+    /// [`None`] for the objects the comparison started from.
+    pub via: Option<String>,
+    /// The object on the left, or [`None`] if it is missing.
+    pub left: Option<AnyProxy>,
+    /// The object on the right, or [`None`] if it is missing.
+    pub right: Option<AnyProxy>,
+}
+
+/// Where two objects compared with [`graph_difference`] first differ.
+///
+/// The [`Display`](std::fmt::Display) form lists the path from the
+/// objects compared to those which differ, one pair per line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GraphDifference {
+    path: Vec<GraphStep>,
+    kind: Difference,
+}
+
+impl GraphDifference {
+    /// The pairs of objects from the starting pair to the pair which
+    /// differs, which is last.
+    pub fn path(&self) -> &[GraphStep] {
+        &self.path
+    }
+
+    /// How the last pair of objects differs.
+    pub fn kind(&self) -> Difference {
+        self.kind
+    }
+}
+
+/// Write an object as its type and handle.
+fn describe_object(f: &mut std::fmt::Formatter<'_>, p: Option<AnyProxy>) -> std::fmt::Result {
+    match p {
+        Some(p) => write!(f, "{}({})", p.type_name(), p.index()),
+        None => write!(f, "<missing>"),
+    }
+}
+
+impl std::fmt::Display for GraphDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        for step in self.path.iter() {
+            match &step.via {
+                Some(via) => write!(f, "\n  via {}: ", via)?,
+                None => write!(f, "\n  from ")?,
+            }
+            describe_object(f, step.left)?;
+            write!(f, " and ")?;
+            describe_object(f, step.right)?;
+        }
+        Ok(())
+    }
+}
+
+/// A group of connected objects in one context with no counterpart
+/// in another.
+///
+/// This is returned by [`context_difference`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContextDifference {
+    in_left: bool,
+    object: AnyProxy,
+    size: usize,
+}
+
+impl ContextDifference {
+    /// Whether the group is in the first (left) context given.
+    pub fn in_left(&self) -> bool {
+        self.in_left
+    }
+
+    /// One of the objects in the group.
+    pub fn object(&self) -> AnyProxy {
+        self.object
+    }
+
+    /// The number of objects in the group.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl std::fmt::Display for ContextDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (here, there) = match self.in_left {
+            true => ("left", "right"),
+            false => ("right", "left"),
+        };
+        let object = Some(self.object);
+        if self.size == 1 {
+            describe_object(f, object)?;
+            write!(f, " in the {} context has", here)?;
+        } else {
+            write!(f, "the {} objects connected to ", self.size)?;
+            describe_object(f, object)?;
+            write!(f, " in the {} context have", here)?;
+        }
+        write!(f, " no counterpart in the {} context", there)
+    }
+}
+
+/// A description of the objects in some contexts, and their links.
+///
+/// This is passed to [`Isomorphic::describe`].
+pub struct Describe<'a, C> {
+    contexts: &'a [&'a C],
+    tables: BTreeMap<TypeId, usize>,
+    nodes: Vec<Node>,
+    links: Vec<Vec<AnyProxy>>,
+    fields: Vec<Vec<usize>>,
+    field_names: BTreeMap<String, usize>,
+}
+
+impl<C: Context> Describe<'_, C> {
+    /// Add the table of objects of type `T` to the description.
+    pub fn table<T>(&mut self)
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + Relink + Clone + Ord + 'static,
+    {
+        let table = self.tables.len();
+        self.tables.insert(TypeId::of::<T>(), table);
+
+        // Objects are labelled by their contents without their links,
+        // ranked so that labels are ordered by content.
+        let mut labels = BTreeMap::<T, Vec<usize>>::new();
+        for (context, c) in self.contexts.iter().enumerate() {
+            for p in Owner::<T>::get_proxy_iter(*c) {
+                let value = Owner::get(*c, p);
+                let mut links = Vec::new();
+                let mut fields = Vec::new();
+                value.for_each_field_link(&mut |name, target| {
+                    links.push(target);
+                    let next = self.field_names.len();
+                    fields.push(*self.field_names.entry(name.to_string()).or_insert(next));
+                });
+                let mut masked = value.clone();
+                masked.relink(&mut |target| target.with_index(0));
+
+                labels.entry(masked).or_default().push(self.nodes.len());
+                self.nodes.push(Node {
+                    context,
+                    table,
+                    label: 0,
+                    proxy: AnyProxy::new(*p),
+                });
+                self.links.push(links);
+                self.fields.push(fields);
+            }
+        }
+        for (label, nodes) in labels.into_values().enumerate() {
+            for node in nodes {
+                self.nodes[node].label = label;
+            }
+        }
+    }
+}
+
+/// A new numbering for the objects in a context.
+///
+/// This is returned by [`Isomorphic::canonicalize`].
+#[derive(Clone, Debug, Default)]
+pub struct Renumbering {
+    proxies: BTreeMap<AnyProxy, u64>,
+    order: BTreeMap<TypeId, Vec<u64>>,
+}
+
+impl Renumbering {
+    /// The new proxy for an object, or [`None`] if the object was not
+    /// renumbered.
+    pub fn get<T: 'static>(&self, p: &Proxy<T>) -> Option<Proxy<T>> {
+        self.proxies
+            .get(&AnyProxy::new(*p))
+            .map(|index| Proxy::from_handle(*index))
+    }
+
+    /// Renumber the objects in a table, and the links they hold.
+    ///
+    /// The table is rebuilt with fresh storage. Tables with no
+    /// renumbered objects are left untouched, as are links to objects
+    /// which were not renumbered.
+    pub fn apply<T, S>(&self, table: &mut Table<T, S>)
+    where
+        T: Relink + Clone + 'static,
+        S: Storage<T> + Default,
+    {
+        let Some(order) = self.order.get(&TypeId::of::<T>()) else {
+            return;
+        };
+        let mut res = Table::<T, S>::default();
+        for index in order {
+            let mut value = table
+                .storage
+                .get(*index)
+                .expect("renumbered object is missing from its table")
+                .clone();
+            value.relink(&mut |target| match self.proxies.get(&target) {
+                Some(index) => target.with_index(*index),
+                None => target,
+            });
+            res.push(value);
+        }
+        *table = res;
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+    context: usize,
+    table: usize,
+    label: usize,
+    proxy: AnyProxy,
+}
+
+/// The objects of some contexts, and the links between them.
+pub(crate) struct Graph {
+    nodes: Vec<Node>,
+    links: Vec<Vec<Option<usize>>>,
+    referenced: Vec<bool>,
+    /// The field holding each link, as an index into `field_names`.
+    ///
+    /// This is only recorded for graphs built from contexts.
+    fields: Vec<Vec<usize>>,
+    field_names: Vec<String>,
+}
+
+impl Graph {
+    pub(crate) fn build<C: Isomorphic>(contexts: &[&C]) -> Self {
+        let mut describe = Describe {
+            contexts,
+            tables: BTreeMap::new(),
+            nodes: Vec::new(),
+            links: Vec::new(),
+            fields: Vec::new(),
+            field_names: BTreeMap::new(),
+        };
+        C::describe(&mut describe);
+
+        let index = describe
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(ix, node)| ((node.context, node.proxy), ix))
+            .collect::<BTreeMap<_, _>>();
+        let links = describe
+            .links
+            .iter()
+            .zip(describe.nodes.iter())
+            .map(|(links, node)| {
+                links
+                    .iter()
+                    .map(|target| index.get(&(node.context, *target)).copied())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let mut field_names = vec![String::new(); describe.field_names.len()];
+        for (name, ix) in describe.field_names {
+            field_names[ix] = name;
+        }
+        Self {
+            fields: describe.fields,
+            field_names,
+            ..Self::new(describe.nodes, links)
+        }
+    }
+
+    fn new(nodes: Vec<Node>, links: Vec<Vec<Option<usize>>>) -> Self {
+        let mut referenced = vec![false; nodes.len()];
+        for target in links.iter().flatten().flatten() {
+            referenced[*target] = true;
+        }
+        Self {
+            nodes,
+            links,
+            referenced,
+            fields: Vec::new(),
+            field_names: Vec::new(),
+        }
+    }
+
+    /// The node for an object in one of the contexts.
+    fn find(&self, context: usize, proxy: AnyProxy) -> Option<usize> {
+        self.nodes
+            .iter()
+            .position(|node| node.context == context && node.proxy == proxy)
+    }
+
+    /// Find where the objects reachable from two nodes first differ,
+    /// following their links in order.
+    ///
+    /// Missing nodes are the same as each other, and different from
+    /// any node that is present. The search is breadth first, so the
+    /// difference found is one of those closest to the roots.
+    fn rooted_difference(&self, a: Option<usize>, b: Option<usize>) -> Option<GraphDifference> {
+        let mut forward = vec![None; self.nodes.len()];
+        let mut backward = vec![None; self.nodes.len()];
+        let mut pairs: Vec<Pair> = vec![(a, b, None)];
+        let mut next = 0;
+        while next < pairs.len() {
+            let current = next;
+            next += 1;
+            let kind = match (pairs[current].0, pairs[current].1) {
+                (None, None) => continue,
+                (Some(a), Some(b)) if forward[a] == Some(b) => continue,
+                (Some(a), Some(b)) if forward[a].is_none() && backward[b].is_none() => {
+                    forward[a] = Some(b);
+                    backward[b] = Some(a);
+                    let (x, y) = (&self.nodes[a], &self.nodes[b]);
+                    if (x.table, x.label) != (y.table, y.label) {
+                        Difference::Contents
+                    } else if self.links[a].len() != self.links[b].len() {
+                        Difference::LinkCount
+                    } else {
+                        for (position, targets) in
+                            self.links[a].iter().zip(self.links[b].iter()).enumerate()
+                        {
+                            pairs.push((*targets.0, *targets.1, Some((current, position))));
+                        }
+                        continue;
+                    }
+                }
+                (Some(_), Some(_)) => Difference::Shared,
+                _ => Difference::Missing,
+            };
+            return Some(self.difference_at(&pairs, current, kind));
+        }
+        None
+    }
+
+    /// Describe the difference found at `pairs[at]`, with the path
+    /// which led to it.
+    fn difference_at(&self, pairs: &[Pair], mut at: usize, kind: Difference) -> GraphDifference {
+        let proxy = |node: Option<usize>| node.map(|node| self.nodes[node].proxy);
+        let mut path = Vec::new();
+        loop {
+            let (a, b, from) = pairs[at];
+            let via = from.map(|(parent, position)| {
+                self.link_name(
+                    pairs[parent]
+                        .0
+                        .expect("links are only followed from objects"),
+                    position,
+                )
+            });
+            path.push(GraphStep {
+                via,
+                left: proxy(a),
+                right: proxy(b),
+            });
+            match from {
+                Some((parent, _)) => at = parent,
+                None => break,
+            }
+        }
+        path.reverse();
+        GraphDifference { path, kind }
+    }
+
+    /// A name for the link at `position` of `node`.
+    ///
+    /// This is the name of the field holding it, followed by its
+    /// position among that field's links if there are several.
+    fn link_name(&self, node: usize, position: usize) -> String {
+        let Some(fields) = self.fields.get(node) else {
+            return format!("[{}]", position);
+        };
+        let field = fields[position];
+        let name = match self.field_names[field].as_str() {
+            "" => "link",
+            name => name,
+        };
+        if fields.iter().filter(|f| **f == field).count() > 1 {
+            let offset = fields[..position].iter().filter(|f| **f == field).count();
+            format!("{}[{}]", name, offset)
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Find a group of connected objects in one of the first two
+    /// contexts with no counterpart in the other.
+    fn unmatched(&self) -> Option<ContextDifference> {
+        let mut forms = [Vec::new(), Vec::new()];
+        for (certificate, members, colours) in self.canonical_components() {
+            // The member coloured first is the same object in
+            // isomorphic components, and so is a stable example.
+            let first = members[colours.iter().position(|c| *c == 0).unwrap()];
+            forms[self.nodes[first].context].push((certificate, members.len(), first));
+        }
+        for form in forms.iter_mut() {
+            form.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        }
+
+        let (mut left, mut right) = (forms[0].iter().peekable(), forms[1].iter().peekable());
+        let unmatched = loop {
+            match (left.peek(), right.peek()) {
+                (None, None) => return None,
+                (Some(l), Some(r)) if l.0 == r.0 => {
+                    left.next();
+                    right.next();
+                }
+                (Some(l), Some(r)) if l.0 < r.0 => break l,
+                (Some(l), None) => break l,
+                (_, Some(r)) => break r,
+            }
+        };
+        let node = &self.nodes[unmatched.2];
+        Some(ContextDifference {
+            in_left: node.context == 0,
+            object: node.proxy,
+            size: unmatched.1,
+        })
+    }
+
+    /// Check whether the first two contexts are isomorphic.
+    ///
+    /// They are if their connected components have the same canonical
+    /// forms.
+    fn isomorphic(&self) -> bool {
+        let mut forms = [Vec::new(), Vec::new()];
+        for (certificate, members, _) in self.canonical_components() {
+            forms[self.nodes[members[0]].context].push(certificate);
+        }
+        for form in forms.iter_mut() {
+            form.sort_unstable();
+        }
+        forms[0] == forms[1]
+    }
+
+    /// Number the objects of the first context canonically.
+    ///
+    /// Objects are ordered by their contents, then by the canonical
+    /// form of their connected component, and then by their place in
+    /// that component.
+    fn canonical(&self) -> Renumbering {
+        let mut components = self.canonical_components();
+        components.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut keys = vec![(0, 0, 0, 0); self.nodes.len()];
+        for (rank, (_, members, colours)) in components.iter().enumerate() {
+            for (member, colour) in members.iter().zip(colours.iter()) {
+                let node = &self.nodes[*member];
+                keys[*member] = (node.table, node.label, rank, *colour);
+            }
+        }
+
+        let mut nodes = (0..self.nodes.len()).collect::<Vec<_>>();
+        nodes.sort_unstable_by_key(|node| keys[*node]);
+        let mut res = Renumbering::default();
+        for node in nodes {
+            let proxy = self.nodes[node].proxy;
+            let order = res.order.entry(proxy.type_id()).or_default();
+            res.proxies.insert(proxy, order.len() as u64);
+            order.push(proxy.index());
+        }
+        res
+    }
+
+    /// The canonical form of each connected component, with its
+    /// members and their colours in that form.
+    fn canonical_components(&self) -> Vec<(Certificate, Vec<usize>, Vec<usize>)> {
+        self.components()
+            .into_iter()
+            .map(|members| {
+                let (certificate, colours) = self.subgraph(&members).canonical_form();
+                (certificate, members, colours)
+            })
+            .collect()
+    }
+
+    /// The connected components of the graph, ignoring the direction
+    /// of links.
+    fn components(&self) -> Vec<Vec<usize>> {
+        fn root(parents: &mut [usize], mut node: usize) -> usize {
+            while parents[node] != node {
+                parents[node] = parents[parents[node]];
+                node = parents[node];
+            }
+            node
+        }
+
+        let mut parents = (0..self.nodes.len()).collect::<Vec<_>>();
+        for (source, links) in self.links.iter().enumerate() {
+            for target in links.iter().flatten() {
+                let (a, b) = (root(&mut parents, source), root(&mut parents, *target));
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+        let mut components = BTreeMap::<usize, Vec<usize>>::new();
+        for node in 0..self.nodes.len() {
+            let root = root(&mut parents, node);
+            components.entry(root).or_default().push(node);
+        }
+        components.into_values().collect()
+    }
+
+    /// The part of the graph made up of `members`, which must be
+    /// closed under links.
+    fn subgraph(&self, members: &[usize]) -> Graph {
+        let position = members
+            .iter()
+            .enumerate()
+            .map(|(ix, node)| (*node, ix))
+            .collect::<BTreeMap<_, _>>();
+        Graph::new(
+            members.iter().map(|node| self.nodes[*node]).collect(),
+            members
+                .iter()
+                .map(|node| {
+                    self.links[*node]
+                        .iter()
+                        .map(|target| target.map(|target| position[&target]))
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    /// Find the numbering of this graph with the least certificate,
+    /// returning the certificate and the colour of each object.
+    fn canonical_form(&self) -> (Certificate, Vec<usize>) {
+        let mut search = CanonicalSearch::default();
+        self.search_canonical(self.initial(), &mut Vec::new(), &mut search);
+        search.best.expect("canonical search found no numbering")
+    }
+
+    /// Search for the numbering with the least certificate, singling
+    /// out each object of an ambiguous class in turn.
+    ///
+    /// Returns the depth to jump back to, when the search finds that
+    /// the rest of a branch will repeat work already done.
+    fn search_canonical(
+        &self,
+        mut colours: Vec<usize>,
+        path: &mut Vec<usize>,
+        search: &mut CanonicalSearch,
+    ) -> Option<usize> {
+        let classes = self.settle(&mut colours);
+        let Some(class) = classes.iter().find(|class| class.len() > 1) else {
+            return search.leaf(self.certificate(&colours), colours, path);
+        };
+
+        let depth = path.len();
+        let mut explored = Vec::new();
+        for node in class.iter().copied() {
+            if search.equivalent(path, &explored, node) {
+                continue;
+            }
+            explored.push(node);
+            path.push(node);
+            let jump = self.search_canonical(split(&colours, |n| n != node), path, search);
+            path.pop();
+            match jump {
+                Some(level) if level < depth => return Some(level),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// The graph, as numbered by a colouring which distinguishes
+    /// every object.
+    fn certificate(&self, colours: &[usize]) -> Certificate {
+        let mut res = vec![Default::default(); self.nodes.len()];
+        for (node, colour) in colours.iter().enumerate() {
+            res[*colour] = (
+                self.nodes[node].table,
+                self.nodes[node].label,
+                self.links[node]
+                    .iter()
+                    .map(|target| target.map(|target| colours[target]))
+                    .collect(),
+            );
+        }
+        res
+    }
+
+    /// Refine a colouring until it settles, returning its classes.
+    fn settle(&self, colours: &mut Vec<usize>) -> Vec<Vec<usize>> {
+        loop {
+            self.refine(colours);
+            let classes = self.classes(colours);
+            if !self.split_interchangeable(&classes, colours) {
+                return classes;
+            }
+        }
+    }
+
+    /// Colour each object by its table and contents.
+    fn initial(&self) -> Vec<usize> {
+        rank(
+            &self
+                .nodes
+                .iter()
+                .map(|node| (node.table, node.label))
+                .collect::<Vec<_>>(),
+        )
+        .0
+    }
+
+    /// Split the colours until objects of the same colour have the
+    /// same colours of links in and out.
+    ///
+    /// Colours are ranked by the colouring they refine, so the result
+    /// only depends on the structure of the graph.
+    fn refine(&self, colours: &mut Vec<usize>) {
+        let mut count = colours.iter().max().map(|c| c + 1).unwrap_or(0);
+        loop {
+            let mut incoming = vec![Vec::new(); self.nodes.len()];
+            for (source, links) in self.links.iter().enumerate() {
+                for (position, target) in links.iter().enumerate() {
+                    if let Some(target) = target {
+                        incoming[*target].push((colours[source], position));
+                    }
+                }
+            }
+            let signatures = incoming
+                .into_iter()
+                .enumerate()
+                .map(|(node, mut incoming)| {
+                    incoming.sort_unstable();
+                    let outgoing = self.links[node]
+                        .iter()
+                        .map(|target| target.map(|target| colours[target]))
+                        .collect::<Vec<_>>();
+                    (colours[node], outgoing, incoming)
+                })
+                .collect::<Vec<_>>();
+            let (next, next_count) = rank(&signatures);
+            *colours = next;
+            if next_count == count {
+                return;
+            }
+            count = next_count;
+        }
+    }
+
+    /// The objects of each colour.
+    fn classes(&self, colours: &[usize]) -> Vec<Vec<usize>> {
+        let count = colours.iter().max().map(|c| c + 1).unwrap_or(0);
+        let mut classes = vec![Vec::new(); count];
+        for (node, colour) in colours.iter().enumerate() {
+            classes[*colour].push(node);
+        }
+        classes
+    }
+
+    /// Give distinct colours to the objects of each class whose
+    /// members can be exchanged freely, returning whether any were
+    /// found.
+    ///
+    /// Objects which nothing links to, and which link to exactly the
+    /// same objects as the rest of their class, can be swapped with
+    /// each other without changing the graph. Any order of such a
+    /// class is then as good as any other, so there is no need to
+    /// search through them.
+    fn split_interchangeable(&self, classes: &[Vec<usize>], colours: &mut Vec<usize>) -> bool {
+        let mut position = vec![0; self.nodes.len()];
+        let mut found = false;
+        for class in classes.iter() {
+            let interchangeable = class.len() > 1
+                && class.iter().all(|node| {
+                    !self.referenced[*node] && self.links[*node] == self.links[class[0]]
+                });
+            if interchangeable {
+                for (offset, node) in class.iter().enumerate() {
+                    position[*node] = offset;
+                }
+                found = true;
+            }
+        }
+        if found {
+            *colours = split(colours, |node| position[node]);
+        }
+        found
+    }
+}
+
+/// A pair of objects compared by [`Graph::rooted_difference`], with
+/// the pair and link it was reached by.
+type Pair = (Option<usize>, Option<usize>, Option<(usize, usize)>);
+
+/// The table, label and links of each object, in order.
+type Certificate = Vec<(usize, usize, Vec<Option<usize>>)>;
+
+/// The state of a search for a canonical numbering.
+///
+/// Two numberings with the same certificate differ by an automorphism
+/// of the graph, which is recorded so that equivalent branches of the
+/// search can be skipped.
+#[derive(Default)]
+struct CanonicalSearch {
+    first: Option<(Certificate, Vec<usize>, Vec<usize>)>,
+    best: Option<(Certificate, Vec<usize>)>,
+    automorphisms: Vec<Vec<usize>>,
+}
+
+impl CanonicalSearch {
+    fn leaf(
+        &mut self,
+        certificate: Certificate,
+        colours: Vec<usize>,
+        path: &[usize],
+    ) -> Option<usize> {
+        let Some((first, first_colours, first_path)) = &self.first else {
+            self.best = Some((certificate.clone(), colours.clone()));
+            self.first = Some((certificate, colours, path.to_vec()));
+            return None;
+        };
+
+        if certificate == *first {
+            // Everything below the point where this branch left the
+            // first one mirrors the first branch.
+            self.automorphisms
+                .push(automorphism(&colours, first_colours));
+            return Some(
+                path.iter()
+                    .zip(first_path.iter())
+                    .take_while(|(a, b)| a == b)
+                    .count(),
+            );
+        }
+
+        let best = self.best.as_mut().unwrap();
+        match certificate.cmp(&best.0) {
+            std::cmp::Ordering::Less => *best = (certificate, colours),
+            std::cmp::Ordering::Equal => {
+                let found = automorphism(&colours, &best.1);
+                self.automorphisms.push(found);
+            }
+            std::cmp::Ordering::Greater => {}
+        }
+        None
+    }
+
+    /// Check whether some automorphism which fixes the objects on
+    /// `path` maps `node` to an object which has been explored.
+    fn equivalent(&self, path: &[usize], explored: &[usize], node: usize) -> bool {
+        let automorphisms = self
+            .automorphisms
+            .iter()
+            .filter(|a| path.iter().all(|p| a[*p] == *p))
+            .collect::<Vec<_>>();
+        let mut seen = BTreeSet::from([node]);
+        let mut queue = vec![node];
+        while let Some(next) = queue.pop() {
+            for a in automorphisms.iter() {
+                let image = a[next];
+                if explored.contains(&image) {
+                    return true;
+                }
+                if seen.insert(image) {
+                    queue.push(image);
+                }
+            }
+        }
+        false
+    }
+}
+
+/// The permutation taking one numbering of a graph to another with
+/// the same certificate.
+fn automorphism(from: &[usize], to: &[usize]) -> Vec<usize> {
+    let mut by_colour = vec![0; to.len()];
+    for (node, colour) in to.iter().enumerate() {
+        by_colour[*colour] = node;
+    }
+    from.iter().map(|colour| by_colour[*colour]).collect()
+}
+
+/// Split each colour by a key computed for its objects, keeping the
+/// colours in the same order.
+fn split<K: Ord>(colours: &[usize], key: impl Fn(usize) -> K) -> Vec<usize> {
+    rank(
+        &colours
+            .iter()
+            .enumerate()
+            .map(|(node, colour)| (*colour, key(node)))
+            .collect::<Vec<_>>(),
+    )
+    .0
+}
+
+/// Number the distinct keys in order, returning each key's number
+/// and the number of distinct keys.
+fn rank<K: Ord>(keys: &[K]) -> (Vec<usize>, usize) {
+    let mut sorted = keys.iter().collect::<Vec<_>>();
+    sorted.sort_unstable();
+    sorted.dedup();
+    let ranks = keys
+        .iter()
+        .map(|key| sorted.binary_search(&key).unwrap())
+        .collect();
+    (ranks, sorted.len())
+}
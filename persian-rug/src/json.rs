@@ -0,0 +1,225 @@
+//! Dumping the contents of a context as JSON.
+//!
+//! This module is available with the `json` feature. A context
+//! declared with `#[persian_rug(json)]` implements [`DebugJson`],
+//! which converts every table into a [`serde_json::Value`], for
+//! attaching to logs, showing in debuggers or loading into other
+//! tools. Objects are converted with their [`Serialize`]
+//! implementations, and proxies within them become the handles of
+//! the objects they refer to, which can be looked up in the table of
+//! the target type. The [`schema`](crate::schema) of the context says
+//! which fields hold proxies, and to which type.
+//!
+//! ```rust
+//! use persian_rug::json::DebugJson;
+//! use persian_rug::serde_json::json;
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//! use persian_rug::serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! #[serde(crate = "persian_rug::serde")]
+//! #[contextual(Rug)]
+//! struct Person {
+//!   name: String,
+//!   manager: Option<Proxy<Person>>,
+//! }
+//!
+//! #[persian_rug(json)]
+//! struct Rug(#[table] Person);
+//!
+//! let mut r = Rug(Default::default());
+//! let boss = r.add(Person { name: "Alice".to_string(), manager: None });
+//! r.add(Person { name: "Bob".to_string(), manager: Some(boss) });
+//!
+//! let ty = std::any::type_name::<Person>();
+//! assert_eq!(
+//!     r.to_debug_value(),
+//!     json!({
+//!         "context": std::any::type_name::<Rug>(),
+//!         "tables": {
+//!             ty: [
+//!                 { "handle": 0, "value": { "name": "Alice", "manager": null } },
+//!                 { "handle": 1, "value": { "name": "Bob", "manager": 0 } },
+//!             ]
+//!         }
+//!     })
+//! );
+//! ```
+//!
+//! Tables are keyed by the [`std::any::type_name`] of their objects.
+//! An object which cannot be converted, for example because it holds
+//! a map whose keys are not strings, appears as an object with a
+//! single `error` field describing the problem, so that the rest of
+//! the context can still be inspected.
+//!
+//! With the `schemars` feature, a context declared with
+//! `#[persian_rug(schemars)]` also implements [`ExportSchema`], which
+//! gives a JSON Schema describing this format for that context, for
+//! validating dumps or generating code to read them in other
+//! languages. The schema of each object is that of its
+//! [`JsonSchema`](schemars::JsonSchema) implementation, and each
+//! proxy is described as an integer, annotated with the type of
+//! object it refers to:
+//!
+//! ```rust
+//! use persian_rug::json::ExportSchema;
+//! use persian_rug::schemars::JsonSchema;
+//! use persian_rug::serde::Serialize;
+//! use persian_rug::{contextual, persian_rug, Proxy};
+//!
+//! #[derive(Serialize, JsonSchema)]
+//! #[serde(crate = "persian_rug::serde")]
+//! #[schemars(crate = "persian_rug::schemars")]
+//! #[contextual(Rug)]
+//! struct Person {
+//!   name: String,
+//!   manager: Option<Proxy<Person>>,
+//! }
+//!
+//! #[persian_rug(json, schemars)]
+//! struct Rug(#[table] Person);
+//!
+//! let schema = Rug::export_schema();
+//! let ty = std::any::type_name::<Person>();
+//! let pointer = format!("/properties/tables/properties/{}/items/properties/value", ty);
+//! assert_eq!(schema.pointer(&pointer).unwrap()["$ref"], "#/$defs/Person");
+//! let manager = schema.pointer("/$defs/Person/properties/manager").unwrap();
+//! assert_eq!(manager["x-persian-rug-proxy"], ty);
+//! ```
+//!
+//! Objects which could not be converted do not match the schema.
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::{Context, Contextual, Owner};
+
+/// A context which can be dumped as JSON.
+///
+/// This is normally implemented with the `json` option of the
+/// [`persian_rug`](crate::persian_rug) macro.
+pub trait DebugJson: Context {
+    /// Convert each table of the context with `json`.
+    fn describe(json: &mut JsonTables<'_, Self>)
+    where
+        Self: Sized;
+
+    /// Convert every table of this context into a JSON value.
+    fn to_debug_value(&self) -> Value
+    where
+        Self: Sized,
+    {
+        let mut json = JsonTables {
+            context: self,
+            tables: Map::new(),
+        };
+        Self::describe(&mut json);
+        json!({
+            "context": std::any::type_name::<Self>(),
+            "tables": json.tables,
+        })
+    }
+}
+
+/// The tables being converted by [`DebugJson::to_debug_value`].
+pub struct JsonTables<'a, C> {
+    context: &'a C,
+    tables: Map<String, Value>,
+}
+
+impl<C: Context> JsonTables<'_, C> {
+    /// Convert the table of objects of type `T`.
+    pub fn table<T>(&mut self)
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + Serialize,
+    {
+        let objects = Owner::<T>::get_proxy_iter(self.context)
+            .map(|p| {
+                let value = serde_json::to_value(Owner::get(self.context, p))
+                    .unwrap_or_else(|e| json!({ "error": e.to_string() }));
+                json!({ "handle": p.index, "value": value })
+            })
+            .collect();
+        self.tables.insert(
+            std::any::type_name::<T>().to_string(),
+            Value::Array(objects),
+        );
+    }
+}
+
+/// A context whose JSON dumps can be described by a JSON Schema.
+///
+/// This is normally implemented with the `schemars` option of the
+/// [`persian_rug`](crate::persian_rug) macro. See the [module
+/// documentation](self) for an example.
+#[cfg(feature = "schemars")]
+pub trait ExportSchema: Context {
+    /// Describe each table of the context with `schema`.
+    fn describe_schema(schema: &mut SchemaTables)
+    where
+        Self: Sized;
+
+    /// A JSON Schema for the values produced by
+    /// [`DebugJson::to_debug_value`] for this context.
+    fn export_schema() -> schemars::Schema
+    where
+        Self: Sized,
+    {
+        let mut schema = SchemaTables {
+            generator: schemars::generate::SchemaSettings::draft2020_12()
+                .for_serialize()
+                .into_generator(),
+            tables: Map::new(),
+        };
+        Self::describe_schema(&mut schema);
+        let required = schema.tables.keys().cloned().collect::<Vec<_>>();
+        let context = std::any::type_name::<Self>();
+        schemars::json_schema!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": context,
+            "type": "object",
+            "properties": {
+                "context": { "const": context },
+                "tables": {
+                    "type": "object",
+                    "properties": schema.tables,
+                    "required": required,
+                    "additionalProperties": false,
+                },
+            },
+            "required": ["context", "tables"],
+            "$defs": schema.generator.take_definitions(true),
+        })
+    }
+}
+
+/// The tables being described by [`ExportSchema::export_schema`].
+#[cfg(feature = "schemars")]
+pub struct SchemaTables {
+    generator: schemars::SchemaGenerator,
+    tables: Map<String, Value>,
+}
+
+#[cfg(feature = "schemars")]
+impl SchemaTables {
+    /// Describe the table of objects of type `T`.
+    pub fn table<T: schemars::JsonSchema>(&mut self) {
+        let value = self.generator.subschema_for::<T>();
+        self.tables.insert(
+            std::any::type_name::<T>().to_string(),
+            json!({
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "handle": { "type": "integer", "format": "uint64", "minimum": 0 },
+                        "value": value,
+                    },
+                    "required": ["handle", "value"],
+                    "additionalProperties": false,
+                },
+            }),
+        );
+    }
+}
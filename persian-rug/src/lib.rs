@@ -86,8 +86,10 @@
 //! over items by type. It can only support one collection of items
 //! per type.
 //!
-//! > Please note:
-//! > **this crate does not support deletion of objects** at present.
+//! Objects can be removed with [`Context::delete`]. Proxies for a
+//! deleted object become stale: looking them up fails, and they never
+//! resolve to an object stored later, even one which is given the
+//! same handle.
 //!
 //! ```rust
 //! use persian_rug::{contextual, persian_rug, Context, Proxy, Table};
@@ -118,26 +120,47 @@
 //!
 //! Context read access is provided to implementations of [`Accessor`]
 //! whose context matches. Shared references to the context are
-//! accessors, as are [`Arc`](std::sync::Arc)s,
-//! [`MutexGuard`](std::sync::MutexGuard)s and
+//! accessors, as are [`Arc`](std::sync::Arc)s and references to
+//! them, [`Box`]es and [`Cow`](std::borrow::Cow)s of cloneable
+//! contexts, [`MutexGuard`](std::sync::MutexGuard)s and
 //! [`RwLockReadGuard`](std::sync::RwLockReadGuard)s.
 //!
 //! Write access is provided to implementations of [`Mutator`] whose
 //! context matches.  Exclusive references to the context are
-//! mutators, as are [`MutexGuard`](std::sync::MutexGuard)s and
+//! mutators, as are [`Box`]es, [`Cow`](std::borrow::Cow)s (which
+//! clone a borrowed context on the first change),
+//! [`MutexGuard`](std::sync::MutexGuard)s and
 //! [`RwLockWriteGuard`](std::sync::RwLockWriteGuard)s. If you enable
 //! the `clone-replace` feature, you can also use
 //! [`MutateGuard`](clone_replace::MutateGuard)s for this.
 //!
+//! The integrations with [`std::sync`] lock types, along with
+//! [`StaticRug`], [`Rcu`] and the [`transaction`] module, are part of
+//! the `sync` feature, which is enabled by default. Programs which
+//! only use their contexts from one thread, such as those built for
+//! `wasm32` to run in a browser, can disable default features to
+//! leave out every lock type. [`Rc`](std::rc::Rc)s are accessors and
+//! [`RefMut`](std::cell::RefMut)s are mutators in any build, and
+//! [`LocalRug`] shares a context within a thread without locking.
+//!
 //! To prevent accidental misuse, each participating type must declare
 //! its context by implementing [`Contextual`], and can only belong to
 //! one context. This apparent restriction is easily lifted by making
 //! the context a generic parameter of the participating type. The
 //! [`constraints`] attribute can help with the boilerplate needed to
 //! use generic parameters in this way.
+//!
+//! Some other crates can work with objects stored in a context. The
+//! [django-query](https://docs.rs/django-query) crate, with its
+//! `persian-rug` feature enabled, provides derive macros
+//! (`FilterableWithPersianRug`, `SortableWithPersianRug` and
+//! `IntoRowWithPersianRug`) which follow [`Proxy`] fields through an
+//! [`Accessor`], so that Django-style filtering and sorting
+//! expressions such as `owner__name=bob` can be applied to objects in
+//! a context.
 
 use std::cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::hash::{Hash, Hasher};
 
 /// A holder for [`Contextual`] types.
@@ -177,6 +200,39 @@ pub trait Context {
         Self: Owner<T>,
         T: Contextual<Context = Self>;
 
+    /// Retrieve a reference to a value from a [`Proxy`], or an error
+    /// if it is not stored.
+    ///
+    /// See the [`checked`] module for details.
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
+    where
+        Self: Owner<T>,
+        T: Contextual<Context = Self>,
+    {
+        Owner::try_get(self, what)
+    }
+
+    /// Retrieve a mutable reference to a value from a [`Proxy`], or an
+    /// error if it is not stored.
+    ///
+    /// See the [`checked`] module for details.
+    fn try_get_mut<T>(&mut self, what: &Proxy<T>) -> Result<&mut T, checked::Missing>
+    where
+        Self: Owner<T>,
+        T: Contextual<Context = Self>,
+    {
+        Owner::try_get_mut(self, what)
+    }
+
+    /// Retrieve a reference to a value from a [`ReadProxy`].
+    fn get_read<T>(&self, what: &ReadProxy<T>) -> &T
+    where
+        Self: Owner<T>,
+        T: Contextual<Context = Self>,
+    {
+        Owner::get(self, &what.proxy)
+    }
+
     /// Iterate over the values currently stored.
     fn get_iter<T>(&self) -> TableIterator<'_, T>
     where
@@ -194,6 +250,460 @@ pub trait Context {
     where
         Self: Owner<T>,
         T: Contextual<Context = Self>;
+
+    /// Modify a value in place with `f`, returning its result.
+    fn update<T, R>(&mut self, what: &Proxy<T>, f: impl FnOnce(&mut T) -> R) -> R
+    where
+        Self: Owner<T>,
+        T: Contextual<Context = Self>,
+    {
+        f(Context::get_mut(self, what))
+    }
+
+    /// Replace a value, returning the one it replaced.
+    fn replace<T>(&mut self, what: &Proxy<T>, value: T) -> T
+    where
+        Self: Owner<T>,
+        T: Contextual<Context = Self>,
+    {
+        Owner::replace(self, what, value)
+    }
+
+    /// Move a value out, leaving its default in its place.
+    ///
+    /// The proxy still refers to the placeholder, so the value can be
+    /// transformed and put back with [`replace`](Context::replace),
+    /// without needing to be cloned:
+    /// ```rust
+    /// use persian_rug::{contextual, persian_rug, Context};
+    ///
+    /// #[derive(Default)]
+    /// #[contextual(Rug)]
+    /// struct Words(Vec<String>);
+    ///
+    /// #[persian_rug]
+    /// struct Rug(#[table] Words);
+    ///
+    /// let mut r = Rug(Default::default());
+    /// let w = r.add(Words(vec!["b".to_string(), "a".to_string()]));
+    ///
+    /// let words = r.take(&w);
+    /// assert!(r.get(&w).0.is_empty());
+    /// let sorted = Words(words.0.into_iter().rev().collect());
+    /// r.replace(&w, sorted);
+    /// assert_eq!(r.get(&w).0, vec!["a", "b"]);
+    /// ```
+    fn take<T>(&mut self, what: &Proxy<T>) -> T
+    where
+        Self: Owner<T>,
+        T: Contextual<Context = Self> + Default,
+    {
+        Owner::replace(self, what, T::default())
+    }
+
+    /// Exchange two values, so that each proxy refers to the value
+    /// the other did.
+    ///
+    /// Returns `false`, leaving the context unchanged, if either
+    /// value is not stored.
+    fn swap<T>(&mut self, a: &Proxy<T>, b: &Proxy<T>) -> bool
+    where
+        Self: Owner<T>,
+        T: Contextual<Context = Self>,
+    {
+        Owner::swap(self, a, b)
+    }
+
+    /// Delete a value, making every proxy for it stale.
+    ///
+    /// Looking up a stale proxy fails, as it would for a proxy from
+    /// another context, even if another value is later stored under
+    /// the same [`handle`](Proxy::handle), because that value's proxy
+    /// has a different [`generation`](Proxy::generation). Returns
+    /// `false`, leaving the context unchanged, if the value is not
+    /// stored.
+    ///
+    /// ```rust
+    /// use persian_rug::{contextual, persian_rug, Context, Proxy};
+    ///
+    /// #[contextual(Rug)]
+    /// struct Session {
+    ///   user: &'static str,
+    /// }
+    ///
+    /// #[persian_rug]
+    /// struct Rug(#[table] Session);
+    ///
+    /// let mut r = Rug(Default::default());
+    /// let alice = r.add(Session { user: "alice" });
+    /// let bob = r.add(Session { user: "bob" });
+    ///
+    /// assert!(r.delete(&alice));
+    /// assert!(r.try_get(&alice).is_err());
+    /// assert_eq!(r.get_iter::<Session>().map(|s| s.user).collect::<Vec<_>>(), vec!["bob"]);
+    /// # let _ = bob;
+    /// ```
+    ///
+    /// Other objects which link to the deleted one are not changed;
+    /// their links become stale too.
+    fn delete<T>(&mut self, what: &Proxy<T>) -> bool
+    where
+        Self: Owner<T>,
+        T: Contextual<Context = Self>,
+    {
+        Owner::delete(self, what)
+    }
+
+    /// Take a value out of the context, making every proxy for it
+    /// stale.
+    ///
+    /// This is [`delete`](Context::delete), but returns the value, so
+    /// that it can be kept or moved elsewhere. Returns [`None`],
+    /// leaving the context unchanged, if the value is not stored.
+    ///
+    /// ```rust
+    /// use persian_rug::{contextual, persian_rug, Context};
+    ///
+    /// #[contextual(Rug)]
+    /// struct Job {
+    ///   name: String,
+    /// }
+    ///
+    /// #[persian_rug]
+    /// struct Rug(#[table] Job);
+    ///
+    /// let mut r = Rug(Default::default());
+    /// let job = r.add(Job { name: "build".to_string() });
+    ///
+    /// let Job { name } = r.remove(&job).unwrap();
+    /// assert_eq!(name, "build");
+    /// assert!(r.remove(&job).is_none());
+    /// ```
+    fn remove<T>(&mut self, what: &Proxy<T>) -> Option<T>
+    where
+        Self: Owner<T>,
+        T: Contextual<Context = Self>,
+    {
+        Owner::remove(self, what)
+    }
+
+    /// Keep only the values of type `T` for which `f` returns `true`,
+    /// deleting the others.
+    ///
+    /// Each value is passed to `f` with its proxy, and can be changed
+    /// as it is checked.
+    ///
+    /// ```rust
+    /// use persian_rug::{contextual, persian_rug, Context};
+    ///
+    /// #[contextual(Rug)]
+    /// struct Entry {
+    ///   age: u32,
+    /// }
+    ///
+    /// #[persian_rug]
+    /// struct Rug(#[table] Entry);
+    ///
+    /// let mut r = Rug(Default::default());
+    /// for age in [1, 5, 2, 9] {
+    ///     r.add(Entry { age });
+    /// }
+    /// r.retain::<Entry, _>(|_, entry| entry.age < 5);
+    /// assert_eq!(r.get_iter::<Entry>().map(|e| e.age).collect::<Vec<_>>(), vec![1, 2]);
+    /// ```
+    fn retain<T, F>(&mut self, mut f: F)
+    where
+        Self: Owner<T>,
+        T: Contextual<Context = Self>,
+        F: FnMut(&Proxy<T>, &mut T) -> bool,
+    {
+        Owner::retain(self, &mut f)
+    }
+
+    /// Take every value of type `T` out of the context, along with
+    /// its proxy, leaving every proxy for them stale.
+    fn drain<T>(&mut self) -> Vec<(Proxy<T>, T)>
+    where
+        Self: Owner<T>,
+        T: Contextual<Context = Self>,
+    {
+        Owner::drain(self)
+    }
+
+    /// Delete every object which cannot be reached from `roots` by
+    /// following links, returning proxies for the objects deleted.
+    ///
+    /// See the [`gc`] module for details.
+    fn collect_garbage(&mut self, roots: impl IntoIterator<Item = AnyProxy>) -> Vec<AnyProxy>
+    where
+        Self: gc::Collect + Sized,
+    {
+        gc::collect(self, roots)
+    }
+
+    /// Copy every object of this context into `into`, which may be a
+    /// context of a different type, returning where each object was
+    /// copied to.
+    ///
+    /// See the [`transplant`] module for details.
+    fn clone_into<D: Context>(&self, into: &mut D) -> transplant::Transplanted
+    where
+        Self: transplant::CloneInto<D> + Sized,
+    {
+        transplant::clone_into(self, into)
+    }
+
+    /// Move every object of `other` into this context, returning
+    /// where each object was moved to.
+    ///
+    /// See the [`merge`] module for details.
+    fn absorb(&mut self, other: Self) -> merge::Merged
+    where
+        Self: merge::Merge + Sized,
+    {
+        merge::absorb(self, other)
+    }
+
+    /// Replay the differences between two contexts onto this one.
+    ///
+    /// Returns an error, leaving the context unchanged, if it has
+    /// diverged from the context the diff started from. See the
+    /// [`diff`] module for details.
+    fn apply_patch(&mut self, diff: &diff::Diff<Self>) -> Result<(), diff::PatchError>
+    where
+        Self: diff::Diffable + Sized,
+    {
+        diff::apply_patch(self, diff)
+    }
+
+    /// Collect the values of type `T` which have been added or
+    /// borrowed mutably since this was last called, leaving every
+    /// value clean.
+    ///
+    /// This needs the table for `T` to be declared with
+    /// `#[table(dirty)]`. See the [`dirty`] module for details.
+    fn take_dirty<T>(&mut self) -> BTreeSet<Proxy<T>>
+    where
+        Self: dirty::DirtyOwner<T> + Sized,
+        T: Contextual<Context = Self>,
+    {
+        dirty::DirtyOwner::take_dirty_objects(self)
+    }
+
+    /// Apply the changes written to `log` by a journal, returning the
+    /// number of changes applied.
+    ///
+    /// This needs the `borsh` feature. See the [`record`] module for
+    /// details.
+    #[cfg(feature = "borsh")]
+    fn replay(
+        &mut self,
+        codec: &record::Codec<Self>,
+        log: impl std::io::Read,
+    ) -> std::io::Result<usize>
+    where
+        Self: Sized,
+    {
+        codec.replay_log(log, self)
+    }
+
+    /// Save the current state of this context, so that it can be
+    /// rolled back to later with [`restore`](Context::restore).
+    ///
+    /// See [`Snapshot`] for details.
+    fn snapshot(&self) -> Snapshot<Self>
+    where
+        Self: Clone + Sized,
+    {
+        Snapshot::new(self.clone())
+    }
+
+    /// Roll this context back to the state saved in `snapshot`.
+    ///
+    /// See [`Snapshot`] for details.
+    fn restore(&mut self, snapshot: Snapshot<Self>)
+    where
+        Self: Clone + Sized,
+    {
+        *self = snapshot.into_context();
+    }
+
+    /// Start buffering speculative changes to this context.
+    ///
+    /// See [`Sandbox`] for details.
+    fn sandbox(&mut self) -> Sandbox<'_, Self>
+    where
+        Self: Sized,
+    {
+        Sandbox::new(self)
+    }
+
+    /// Check a batch of operations, and apply them if they are all
+    /// valid, returning proxies for the objects they added.
+    ///
+    /// See the [`batch`] module for details.
+    fn apply_batch(&mut self, ops: Vec<batch::Op<Self>>) -> Result<Vec<AnyProxy>, batch::BatchError>
+    where
+        Self: Sized,
+    {
+        batch::apply(self, ops)
+    }
+
+    /// Describe this context and the types it stores.
+    ///
+    /// The [`persian_rug`] macro implements this to list every table
+    /// of the context. The default implementation lists no tables.
+    /// See the [`schema`] module for details.
+    fn schema() -> schema::ContextSchema
+    where
+        Self: Sized,
+    {
+        let schema::TypeSchema {
+            name, type_name, ..
+        } = schema::TypeSchema::opaque::<Self>();
+        schema::ContextSchema {
+            name,
+            type_name,
+            tables: Vec::new(),
+        }
+    }
+
+    /// Call `visitor` for every table of this context, and every
+    /// object in each.
+    ///
+    /// The [`persian_rug`] macro implements this to visit each table
+    /// in the order they are declared. The default implementation
+    /// visits nothing. See the [`visit`] module for details.
+    fn visit_all<V>(&self, _visitor: &mut V)
+    where
+        Self: Sized,
+        V: visit::ContextVisitor<Self>,
+    {
+    }
+
+    /// Retrieve a reference to the context's resource of type `R`.
+    ///
+    /// See [`ResourceOwner`] for details.
+    fn resource<R>(&self) -> &R
+    where
+        Self: ResourceOwner<R>,
+    {
+        ResourceOwner::get_resource(self)
+    }
+
+    /// Retrieve a mutable reference to the context's resource of type
+    /// `R`.
+    ///
+    /// See [`ResourceOwner`] for details.
+    fn resource_mut<R>(&mut self) -> &mut R
+    where
+        Self: ResourceOwner<R>,
+    {
+        ResourceOwner::get_resource_mut(self)
+    }
+
+    /// Retrieve a reference to the context's [`Table`] for `T`.
+    ///
+    /// See [`TableOwner`] for details.
+    fn table<T>(&self) -> &<Self as TableOwner<T>>::Table
+    where
+        Self: TableOwner<T>,
+        T: Contextual<Context = Self>,
+    {
+        TableOwner::get_table(self)
+    }
+
+    /// Retrieve a mutable reference to the context's [`Table`] for
+    /// `T`.
+    ///
+    /// See [`TableOwner`] for details.
+    fn table_mut<T>(&mut self) -> &mut <Self as TableOwner<T>>::Table
+    where
+        Self: TableOwner<T>,
+        T: Contextual<Context = Self>,
+    {
+        TableOwner::get_table_mut(self)
+    }
+
+    /// Register an object under a name, in the context's [`Names`]
+    /// resource, returning the object the name referred to before.
+    fn register_name<T>(&mut self, name: impl Into<String>, p: Proxy<T>) -> Option<AnyProxy>
+    where
+        Self: ResourceOwner<Names> + Owner<T>,
+        T: Contextual<Context = Self> + 'static,
+    {
+        ResourceOwner::<Names>::get_resource_mut(self).register(name, p)
+    }
+
+    /// Find the object of type `T` registered under a name, in the
+    /// context's [`Names`] resource.
+    ///
+    /// Returns [`None`] if the name is not registered, or refers to
+    /// an object of another type.
+    fn lookup<T>(&self, name: &str) -> Option<Proxy<T>>
+    where
+        Self: ResourceOwner<Names> + Owner<T>,
+        T: Contextual<Context = Self> + 'static,
+    {
+        ResourceOwner::<Names>::get_resource(self).lookup(name)
+    }
+
+    /// Insert the given value, returning a [`Proxy`] for it, unless
+    /// that would break a limit of the context's [`Quota`](quota::Quota)
+    /// resource.
+    ///
+    /// See the [`quota`] module for details.
+    fn try_add<T>(&mut self, value: T) -> Result<Proxy<T>, quota::QuotaExceeded>
+    where
+        Self: ResourceOwner<quota::Quota> + Owner<T> + Sized,
+        T: Contextual<Context = Self>,
+    {
+        quota::try_add(self, value)
+    }
+
+    /// Find out where the value for a [`Proxy`] came from.
+    ///
+    /// This needs the `provenance` feature, and the `provenance`
+    /// option of the [`persian_rug`] macro. See the [`provenance`]
+    /// module for details.
+    #[cfg(feature = "provenance")]
+    fn provenance<T>(&self, what: &Proxy<T>) -> Option<&provenance::Provenance>
+    where
+        Self: provenance::ProvenanceOwner<T> + Sized,
+        T: Contextual<Context = Self>,
+    {
+        provenance::ProvenanceOwner::provenance(self, what)
+    }
+
+    /// Run `f` on each value of type `T` added to the context from
+    /// now on.
+    ///
+    /// This needs the `observe` feature, and the `observe` option of
+    /// the [`persian_rug`] macro. See the [`observe`] module for
+    /// details.
+    #[cfg(feature = "observe")]
+    fn on_add<T>(&mut self, f: impl FnMut(&Proxy<T>, &T) + Send + Sync + 'static)
+    where
+        Self: observe::ObserverOwner<T> + Sized,
+        T: Contextual<Context = Self>,
+    {
+        observe::ObserverOwner::on_add(self, f)
+    }
+
+    /// Run `f` with the proxy of each value of type `T` borrowed
+    /// mutably from the context from now on.
+    ///
+    /// This needs the `observe` feature, and the `observe` option of
+    /// the [`persian_rug`] macro. See the [`observe`] module for
+    /// details.
+    #[cfg(feature = "observe")]
+    fn on_modify<T>(&mut self, f: impl FnMut(&Proxy<T>) + Send + Sync + 'static)
+    where
+        Self: observe::ObserverOwner<T> + Sized,
+        T: Contextual<Context = Self>,
+    {
+        observe::ObserverOwner::on_modify(self, f)
+    }
 }
 
 /// A convenient way to handle [`Context`] read access.
@@ -222,9 +732,57 @@ pub trait Accessor: Clone {
     where
         Self::Context: Owner<T>,
         T: Contextual<Context = Self::Context>;
+
+    /// Retrieve a reference to a value from a [`Proxy`], or an error
+    /// if it is not stored.
+    ///
+    /// The default implementation searches all the stored proxies
+    /// before looking the value up. See the [`checked`] module for
+    /// details.
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        if self.get_proxy_iter().any(|p| p == what) {
+            Ok(self.get(what))
+        } else {
+            Err(checked::Missing::new(what))
+        }
+    }
+
+    /// Retrieve a reference to a value from a [`ReadProxy`].
+    fn get_read<T>(&self, what: &ReadProxy<T>) -> &T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        self.get(&what.proxy)
+    }
+
+    /// List the objects which hold a link to `what`.
+    ///
+    /// This is available for contexts which maintain a reverse index
+    /// of their links; see the [`referrers`] module.
+    fn referrers<T>(&self, what: &Proxy<T>) -> Vec<AnyProxy>
+    where
+        Self: std::ops::Deref<Target = Self::Context>,
+        Self::Context: referrers::Referrers,
+        T: Contextual<Context = Self::Context> + 'static,
+    {
+        referrers::Referrers::referrers_of(&**self, &AnyProxy::new(*what))
+    }
+
+    /// Combine this with an accessor for another context, to read
+    /// objects from either.
+    ///
+    /// See the [`chain`] module for details.
+    fn chain<B: Accessor>(self, other: B) -> chain::Chain<Self, B> {
+        chain::Chain::new(self, other)
+    }
 }
 
-impl<'a, C> Accessor for &'a C
+impl<C> Accessor for &C
 where
     C: Context,
 {
@@ -253,6 +811,14 @@ where
     {
         <C as Context>::get_proxy_iter(self)
     }
+
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get(self, what)
+    }
 }
 
 impl<C> Accessor for std::sync::Arc<C>
@@ -283,95 +849,152 @@ where
     {
         <C as Context>::get_proxy_iter(self)
     }
-}
-
-/// A convenient way to handle [`Context`] write access.
-///
-/// Rather than plumbing references to a context throughout your code,
-/// especially when you are implementing derive macros that rely on
-/// this crate, it can be more convenient to use this abstraction of
-/// read-write access to a context.
-///
-/// In most hand-written code, it is simplest to use an exclusive
-/// reference to a context as a Mutator.
-pub trait Mutator {
-    type Context: Context;
 
-    fn add<T>(&mut self, proxy: T) -> Proxy<T>
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
     where
         Self::Context: Owner<T>,
-        T: Contextual<Context = Self::Context>;
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get(self, what)
+    }
+}
 
+impl<C> Accessor for std::rc::Rc<C>
+where
+    C: Context,
+{
+    type Context = C;
     fn get<T>(&self, what: &Proxy<T>) -> &T
     where
         Self::Context: Owner<T>,
-        T: Contextual<Context = Self::Context>;
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get(self, what)
+    }
 
-    fn get_mut<T>(&mut self, what: &Proxy<T>) -> &mut T
+    fn get_iter<T>(&self) -> TableIterator<'_, T>
     where
         Self::Context: Owner<T>,
-        T: Contextual<Context = Self::Context>;
-
-    fn get_iter<T>(&self) -> TableIterator<'_, T>
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_iter(self)
+    }
+
+    fn get_proxy_iter<T>(&self) -> TableProxyIterator<'_, T>
     where
         Self::Context: Owner<T>,
-        T: Contextual<Context = Self::Context>;
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_proxy_iter(self)
+    }
 
-    fn get_iter_mut<T>(&mut self) -> TableMutIterator<'_, T>
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
     where
         Self::Context: Owner<T>,
-        T: Contextual<Context = Self::Context>;
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get(self, what)
+    }
+}
+
+impl<C> Accessor for Box<C>
+where
+    C: Context + Clone,
+{
+    type Context = C;
+
+    fn get<T>(&self, what: &Proxy<T>) -> &T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get(self, what)
+    }
+
+    fn get_iter<T>(&self) -> TableIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_iter(self)
+    }
 
     fn get_proxy_iter<T>(&self) -> TableProxyIterator<'_, T>
     where
         Self::Context: Owner<T>,
-        T: Contextual<Context = Self::Context>;
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_proxy_iter(self)
+    }
+
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get(self, what)
+    }
 }
 
-impl<'a, C> Mutator for &'a mut C
+impl<C> Accessor for std::borrow::Cow<'_, C>
 where
-    C: Context,
+    C: Context + Clone,
 {
     type Context = C;
 
-    fn add<T>(&mut self, value: T) -> Proxy<T>
+    fn get<T>(&self, what: &Proxy<T>) -> &T
     where
         Self::Context: Owner<T>,
         T: Contextual<Context = Self::Context>,
     {
-        <C as Context>::add(self, value)
+        <C as Context>::get(self, what)
     }
 
-    fn get<T>(&self, what: &Proxy<T>) -> &T
+    fn get_iter<T>(&self) -> TableIterator<'_, T>
     where
         Self::Context: Owner<T>,
         T: Contextual<Context = Self::Context>,
     {
-        <C as Context>::get(self, what)
+        <C as Context>::get_iter(self)
     }
 
-    fn get_mut<T>(&mut self, what: &Proxy<T>) -> &mut T
+    fn get_proxy_iter<T>(&self) -> TableProxyIterator<'_, T>
     where
         Self::Context: Owner<T>,
         T: Contextual<Context = Self::Context>,
     {
-        <C as Context>::get_mut(self, what)
+        <C as Context>::get_proxy_iter(self)
     }
 
-    fn get_iter<T>(&self) -> TableIterator<'_, T>
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
     where
         Self::Context: Owner<T>,
         T: Contextual<Context = Self::Context>,
     {
-        <C as Context>::get_iter(self)
+        <C as Context>::try_get(self, what)
     }
+}
 
-    fn get_iter_mut<T>(&mut self) -> TableMutIterator<'_, T>
+impl<C> Accessor for &std::sync::Arc<C>
+where
+    C: Context,
+{
+    type Context = C;
+
+    fn get<T>(&self, what: &Proxy<T>) -> &T
     where
         Self::Context: Owner<T>,
         T: Contextual<Context = Self::Context>,
     {
-        <C as Context>::get_iter_mut(self)
+        <C as Context>::get(self, what)
+    }
+
+    fn get_iter<T>(&self) -> TableIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_iter(self)
     }
 
     fn get_proxy_iter<T>(&self) -> TableProxyIterator<'_, T>
@@ -381,69 +1004,283 @@ where
     {
         <C as Context>::get_proxy_iter(self)
     }
+
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get(self, what)
+    }
 }
 
-impl<'a, C> Mutator for std::sync::MutexGuard<'a, C>
+impl<C> Accessor for &std::rc::Rc<C>
 where
     C: Context,
 {
     type Context = C;
 
-    fn add<T>(&mut self, value: T) -> Proxy<T>
+    fn get<T>(&self, what: &Proxy<T>) -> &T
     where
         Self::Context: Owner<T>,
         T: Contextual<Context = Self::Context>,
     {
-        <C as Context>::add(self, value)
+        <C as Context>::get(self, what)
     }
 
-    fn get<T>(&self, what: &Proxy<T>) -> &T
+    fn get_iter<T>(&self) -> TableIterator<'_, T>
     where
         Self::Context: Owner<T>,
         T: Contextual<Context = Self::Context>,
     {
-        <C as Context>::get(self, what)
+        <C as Context>::get_iter(self)
     }
 
-    fn get_mut<T>(&mut self, what: &Proxy<T>) -> &mut T
+    fn get_proxy_iter<T>(&self) -> TableProxyIterator<'_, T>
     where
         Self::Context: Owner<T>,
         T: Contextual<Context = Self::Context>,
     {
-        <C as Context>::get_mut(self, what)
+        <C as Context>::get_proxy_iter(self)
     }
 
-    fn get_iter<T>(&self) -> TableIterator<'_, T>
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
     where
         Self::Context: Owner<T>,
         T: Contextual<Context = Self::Context>,
     {
-        <C as Context>::get_iter(self)
+        <C as Context>::try_get(self, what)
     }
+}
+
+/// A convenient way to handle [`Context`] write access.
+///
+/// Rather than plumbing references to a context throughout your code,
+/// especially when you are implementing derive macros that rely on
+/// this crate, it can be more convenient to use this abstraction of
+/// read-write access to a context.
+///
+/// In most hand-written code, it is simplest to use an exclusive
+/// reference to a context as a Mutator.
+pub trait Mutator {
+    type Context: Context;
+
+    fn add<T>(&mut self, proxy: T) -> Proxy<T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>;
+
+    fn get<T>(&self, what: &Proxy<T>) -> &T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>;
+
+    fn get_mut<T>(&mut self, what: &Proxy<T>) -> &mut T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>;
+
+    fn get_iter<T>(&self) -> TableIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>;
 
     fn get_iter_mut<T>(&mut self) -> TableMutIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>;
+
+    fn get_proxy_iter<T>(&self) -> TableProxyIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>;
+
+    /// Retrieve a reference to a value from a [`Proxy`], or an error
+    /// if it is not stored.
+    ///
+    /// The default implementation searches all the stored proxies
+    /// before looking the value up. See the [`checked`] module for
+    /// details.
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
     where
         Self::Context: Owner<T>,
         T: Contextual<Context = Self::Context>,
     {
-        <C as Context>::get_iter_mut(self)
+        if self.get_proxy_iter().any(|p| p == what) {
+            Ok(self.get(what))
+        } else {
+            Err(checked::Missing::new(what))
+        }
     }
 
-    fn get_proxy_iter<T>(&self) -> TableProxyIterator<'_, T>
+    /// Retrieve a reference to a value from a [`ReadProxy`].
+    fn get_read<T>(&self, what: &ReadProxy<T>) -> &T
     where
         Self::Context: Owner<T>,
         T: Contextual<Context = Self::Context>,
     {
-        <C as Context>::get_proxy_iter(self)
+        self.get(&what.proxy)
+    }
+
+    /// Retrieve a mutable reference to a value from a [`Proxy`], or an
+    /// error if it is not stored.
+    ///
+    /// The default implementation searches all the stored proxies
+    /// before looking the value up. See the [`checked`] module for
+    /// details.
+    fn try_get_mut<T>(&mut self, what: &Proxy<T>) -> Result<&mut T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        if self.get_proxy_iter().any(|p| p == what) {
+            Ok(self.get_mut(what))
+        } else {
+            Err(checked::Missing::new(what))
+        }
+    }
+
+    /// Modify a value in place with `f`, returning its result.
+    ///
+    /// This makes a read-modify-write a single call:
+    /// ```rust
+    /// use persian_rug::{contextual, persian_rug, Context, Mutator, Proxy};
+    ///
+    /// #[contextual(Rug)]
+    /// struct Counter {
+    ///   count: u32,
+    /// }
+    ///
+    /// #[persian_rug]
+    /// struct Rug(#[table] Counter);
+    ///
+    /// fn bump<M: Mutator<Context = Rug>>(mut mutator: M, counter: &Proxy<Counter>) -> u32 {
+    ///     mutator.update(counter, |c| {
+    ///         c.count += 1;
+    ///         c.count
+    ///     })
+    /// }
+    ///
+    /// let mut r = Rug(Default::default());
+    /// let c = r.add(Counter { count: 0 });
+    /// assert_eq!(bump(&mut r, &c), 1);
+    /// assert_eq!(bump(&mut r, &c), 2);
+    /// ```
+    fn update<T, R>(&mut self, what: &Proxy<T>, f: impl FnOnce(&mut T) -> R) -> R
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        f(self.get_mut(what))
+    }
+
+    /// Replace a value, returning the one it replaced.
+    ///
+    /// The old value can be kept, for example to log the change or to
+    /// undo it later.
+    fn replace<T>(&mut self, what: &Proxy<T>, value: T) -> T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        std::mem::replace(self.get_mut(what), value)
+    }
+
+    /// Move a value out, leaving its default in its place.
+    fn take<T>(&mut self, what: &Proxy<T>) -> T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context> + Default,
+    {
+        std::mem::take(self.get_mut(what))
+    }
+
+    /// Check a batch of operations, and apply them if they are all
+    /// valid, returning proxies for the objects they added.
+    ///
+    /// See the [`batch`] module for details.
+    fn apply_batch(
+        &mut self,
+        ops: Vec<batch::Op<Self::Context>>,
+    ) -> Result<Vec<AnyProxy>, batch::BatchError>
+    where
+        Self: std::ops::DerefMut<Target = Self::Context>,
+    {
+        batch::apply(&mut **self, ops)
+    }
+
+    /// Insert the given value, returning a [`Proxy`] for it, unless
+    /// that would break a limit of the context's [`Quota`](quota::Quota)
+    /// resource.
+    ///
+    /// See the [`quota`] module for details.
+    fn try_add<T>(&mut self, value: T) -> Result<Proxy<T>, quota::QuotaExceeded>
+    where
+        Self: std::ops::DerefMut<Target = Self::Context>,
+        Self::Context: ResourceOwner<quota::Quota> + Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        quota::try_add(&mut **self, value)
+    }
+
+    /// Delete a value, making every proxy for it stale.
+    ///
+    /// See [`Context::delete`] for details.
+    fn delete<T>(&mut self, what: &Proxy<T>) -> bool
+    where
+        Self: std::ops::DerefMut<Target = Self::Context>,
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        Owner::delete(&mut **self, what)
+    }
+
+    /// Take a value out of the context, making every proxy for it
+    /// stale.
+    ///
+    /// See [`Context::remove`] for details.
+    fn remove<T>(&mut self, what: &Proxy<T>) -> Option<T>
+    where
+        Self: std::ops::DerefMut<Target = Self::Context>,
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        Owner::remove(&mut **self, what)
+    }
+
+    /// Keep only the values of type `T` for which `f` returns `true`,
+    /// deleting the others.
+    ///
+    /// See [`Context::retain`] for details.
+    fn retain<T, F>(&mut self, mut f: F)
+    where
+        Self: std::ops::DerefMut<Target = Self::Context>,
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+        F: FnMut(&Proxy<T>, &mut T) -> bool,
+    {
+        Owner::retain(&mut **self, &mut f)
+    }
+
+    /// Take every value of type `T` out of the context, along with
+    /// its proxy, leaving every proxy for them stale.
+    fn drain<T>(&mut self) -> Vec<(Proxy<T>, T)>
+    where
+        Self: std::ops::DerefMut<Target = Self::Context>,
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        Owner::drain(&mut **self)
     }
 }
 
-impl<'a, C> Mutator for std::sync::RwLockWriteGuard<'a, C>
+impl<C> Mutator for &mut C
 where
     C: Context,
 {
     type Context = C;
 
+    #[track_caller]
     fn add<T>(&mut self, value: T) -> Proxy<T>
     where
         Self::Context: Owner<T>,
@@ -468,6 +1305,22 @@ where
         <C as Context>::get_mut(self, what)
     }
 
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get(self, what)
+    }
+
+    fn try_get_mut<T>(&mut self, what: &Proxy<T>) -> Result<&mut T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get_mut(self, what)
+    }
+
     fn get_iter<T>(&self) -> TableIterator<'_, T>
     where
         Self::Context: Owner<T>,
@@ -493,13 +1346,13 @@ where
     }
 }
 
-#[cfg(feature = "clone-replace")]
-impl<C> Mutator for clone_replace::MutateGuard<C>
+impl<C> Mutator for Box<C>
 where
     C: Context,
 {
     type Context = C;
 
+    #[track_caller]
     fn add<T>(&mut self, value: T) -> Proxy<T>
     where
         Self::Context: Owner<T>,
@@ -524,6 +1377,22 @@ where
         <C as Context>::get_mut(self, what)
     }
 
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get(self, what)
+    }
+
+    fn try_get_mut<T>(&mut self, what: &Proxy<T>) -> Result<&mut T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get_mut(self, what)
+    }
+
     fn get_iter<T>(&self) -> TableIterator<'_, T>
     where
         Self::Context: Owner<T>,
@@ -549,45 +1418,702 @@ where
     }
 }
 
-/// A type that owns (is the exclusive holder of) a [`Contextual`] type.
-///
-/// Implementations of this trait are normally provided for you by the
-/// [`persian_rug`] attribute macro. You should rarely, if ever, need to
-/// implement it yourself.
-///
-/// Each [`Contextual`] type has a single [`Context`] that owns it. Only
-/// that context may implement this trait for the type, which provides
+/// A [`Cow`](std::borrow::Cow) is copied on write: the borrowed
+/// context is cloned the first time the mutator changes anything.
+impl<C> Mutator for std::borrow::Cow<'_, C>
+where
+    C: Context + Clone,
+{
+    type Context = C;
+
+    #[track_caller]
+    fn add<T>(&mut self, value: T) -> Proxy<T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::add(self.to_mut(), value)
+    }
+
+    fn get<T>(&self, what: &Proxy<T>) -> &T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get(self, what)
+    }
+
+    fn get_mut<T>(&mut self, what: &Proxy<T>) -> &mut T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_mut(self.to_mut(), what)
+    }
+
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get(self, what)
+    }
+
+    fn try_get_mut<T>(&mut self, what: &Proxy<T>) -> Result<&mut T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get_mut(self.to_mut(), what)
+    }
+
+    fn get_iter<T>(&self) -> TableIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_iter(self)
+    }
+
+    fn get_iter_mut<T>(&mut self) -> TableMutIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_iter_mut(self.to_mut())
+    }
+
+    fn get_proxy_iter<T>(&self) -> TableProxyIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_proxy_iter(self)
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<'a, C> Mutator for std::sync::MutexGuard<'a, C>
+where
+    C: Context,
+{
+    type Context = C;
+
+    #[track_caller]
+    fn add<T>(&mut self, value: T) -> Proxy<T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::add(self, value)
+    }
+
+    fn get<T>(&self, what: &Proxy<T>) -> &T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get(self, what)
+    }
+
+    fn get_mut<T>(&mut self, what: &Proxy<T>) -> &mut T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_mut(self, what)
+    }
+
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get(self, what)
+    }
+
+    fn try_get_mut<T>(&mut self, what: &Proxy<T>) -> Result<&mut T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get_mut(self, what)
+    }
+
+    fn get_iter<T>(&self) -> TableIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_iter(self)
+    }
+
+    fn get_iter_mut<T>(&mut self) -> TableMutIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_iter_mut(self)
+    }
+
+    fn get_proxy_iter<T>(&self) -> TableProxyIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_proxy_iter(self)
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<'a, C> Mutator for std::sync::RwLockWriteGuard<'a, C>
+where
+    C: Context,
+{
+    type Context = C;
+
+    #[track_caller]
+    fn add<T>(&mut self, value: T) -> Proxy<T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::add(self, value)
+    }
+
+    fn get<T>(&self, what: &Proxy<T>) -> &T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get(self, what)
+    }
+
+    fn get_mut<T>(&mut self, what: &Proxy<T>) -> &mut T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_mut(self, what)
+    }
+
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get(self, what)
+    }
+
+    fn try_get_mut<T>(&mut self, what: &Proxy<T>) -> Result<&mut T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get_mut(self, what)
+    }
+
+    fn get_iter<T>(&self) -> TableIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_iter(self)
+    }
+
+    fn get_iter_mut<T>(&mut self) -> TableMutIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_iter_mut(self)
+    }
+
+    fn get_proxy_iter<T>(&self) -> TableProxyIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_proxy_iter(self)
+    }
+}
+
+impl<'a, C> Mutator for std::cell::RefMut<'a, C>
+where
+    C: Context,
+{
+    type Context = C;
+
+    #[track_caller]
+    fn add<T>(&mut self, value: T) -> Proxy<T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::add(self, value)
+    }
+
+    fn get<T>(&self, what: &Proxy<T>) -> &T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get(self, what)
+    }
+
+    fn get_mut<T>(&mut self, what: &Proxy<T>) -> &mut T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_mut(self, what)
+    }
+
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get(self, what)
+    }
+
+    fn try_get_mut<T>(&mut self, what: &Proxy<T>) -> Result<&mut T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get_mut(self, what)
+    }
+
+    fn get_iter<T>(&self) -> TableIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_iter(self)
+    }
+
+    fn get_iter_mut<T>(&mut self) -> TableMutIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_iter_mut(self)
+    }
+
+    fn get_proxy_iter<T>(&self) -> TableProxyIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_proxy_iter(self)
+    }
+}
+
+#[cfg(feature = "clone-replace")]
+impl<C> Mutator for clone_replace::MutateGuard<C>
+where
+    C: Context,
+{
+    type Context = C;
+
+    #[track_caller]
+    fn add<T>(&mut self, value: T) -> Proxy<T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::add(self, value)
+    }
+
+    fn get<T>(&self, what: &Proxy<T>) -> &T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get(self, what)
+    }
+
+    fn get_mut<T>(&mut self, what: &Proxy<T>) -> &mut T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_mut(self, what)
+    }
+
+    fn try_get<T>(&self, what: &Proxy<T>) -> Result<&T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get(self, what)
+    }
+
+    fn try_get_mut<T>(&mut self, what: &Proxy<T>) -> Result<&mut T, checked::Missing>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::try_get_mut(self, what)
+    }
+
+    fn get_iter<T>(&self) -> TableIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_iter(self)
+    }
+
+    fn get_iter_mut<T>(&mut self) -> TableMutIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_iter_mut(self)
+    }
+
+    fn get_proxy_iter<T>(&self) -> TableProxyIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        <C as Context>::get_proxy_iter(self)
+    }
+}
+
+/// A type that owns (is the exclusive holder of) a [`Contextual`] type.
+///
+/// Implementations of this trait are normally provided for you by the
+/// [`persian_rug`] attribute macro. You should rarely, if ever, need to
+/// implement it yourself.
+///
+/// Each [`Contextual`] type has a single [`Context`] that owns it. Only
+/// that context may implement this trait for the type, which provides
 /// the standard API for [`Context`] objects, specialised to a single
 /// type. The polymorphic interface for contexts calls through to the
 /// functions defined in this trait, and you should never need to
 /// call them directly; it is preferable to use the [`Context`] interface.
 ///
-/// The main place in which [`Owner`] shows up in code written using
-/// this crate is when specifying the constraints on what contexts are
-/// permitted to call a given generic function. In general, those uses
-/// of [`Owner`] can also be generated for you, using the [`constraints`]
-/// attribute macro, but there are cases where you may need to refer
-/// to it yourself: essentially whenever you need to assert that you
-/// will be able to interact with a type `T` or a proxy for it, via
-/// some context, then you can assert that the context implements
-/// `Owner<T>`.
-pub trait Owner<T>: Context
+/// The main place in which [`Owner`] shows up in code written using
+/// this crate is when specifying the constraints on what contexts are
+/// permitted to call a given generic function. In general, those uses
+/// of [`Owner`] can also be generated for you, using the [`constraints`]
+/// attribute macro, but there are cases where you may need to refer
+/// to it yourself: essentially whenever you need to assert that you
+/// will be able to interact with a type `T` or a proxy for it, via
+/// some context, then you can assert that the context implements
+/// `Owner<T>`.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` does not hold a table of `{T}`",
+    label = "no table of `{T}` in `{Self}`",
+    note = "if `{Self}` is the context of a `#[constraints]` item, add `{T}` to its `access(...)` list"
+)]
+pub trait Owner<T>: Context
+where
+    T: Contextual<Context = Self>,
+{
+    /// Insert the given value, obtaining a [`Proxy`] for it.
+    fn add(&mut self, value: T) -> Proxy<T>;
+    /// Get a shared reference to a value from a [`Proxy`] for it.
+    fn get(&self, proxy: &Proxy<T>) -> &T;
+    /// Get an exclusive reference to a value from a [`Proxy`] for it.
+    fn get_mut(&mut self, proxy: &Proxy<T>) -> &mut T;
+    /// Get a shared reference to a value from a [`Proxy`] for it, or
+    /// an error if it is not stored.
+    ///
+    /// The default implementation checks with
+    /// [`contains`](Owner::contains) before looking the value up with
+    /// [`get`](Owner::get).
+    fn try_get(&self, proxy: &Proxy<T>) -> Result<&T, checked::Missing> {
+        if Owner::contains(self, proxy) {
+            Ok(Owner::get(self, proxy))
+        } else {
+            Err(checked::Missing::new(proxy))
+        }
+    }
+    /// Get an exclusive reference to a value from a [`Proxy`] for it,
+    /// or an error if it is not stored.
+    ///
+    /// The default implementation checks with
+    /// [`contains`](Owner::contains) before looking the value up with
+    /// [`get_mut`](Owner::get_mut).
+    fn try_get_mut(&mut self, proxy: &Proxy<T>) -> Result<&mut T, checked::Missing> {
+        if Owner::contains(self, proxy) {
+            Ok(Owner::get_mut(self, proxy))
+        } else {
+            Err(checked::Missing::new(proxy))
+        }
+    }
+    /// Iterate over shared references to the stored values.
+    fn get_iter(&self) -> TableIterator<'_, T>;
+    /// Iterate over exclusive references to the stored values.
+    fn get_iter_mut(&mut self) -> TableMutIterator<'_, T>;
+    /// Iterate over shared references to [`Proxy`] objects for the
+    /// stored values.
+    fn get_proxy_iter(&self) -> TableProxyIterator<'_, T>;
+    /// The [`Proxy`] that the next value added will receive.
+    ///
+    /// The default implementation assumes that handles are issued
+    /// in order, starting from zero.
+    fn next_proxy(&self) -> Proxy<T> {
+        Proxy::from_handle(
+            Owner::get_proxy_iter(self)
+                .map(|p| p.index + 1)
+                .max()
+                .unwrap_or(0),
+        )
+    }
+    /// Check whether the value a [`Proxy`] refers to is stored.
+    ///
+    /// The default implementation searches all the stored proxies.
+    fn contains(&self, proxy: &Proxy<T>) -> bool {
+        Owner::get_proxy_iter(self).any(|p| p == proxy)
+    }
+    /// Insert the given value under a chosen handle, obtaining a
+    /// [`Proxy`] for it.
+    ///
+    /// Returns an error, leaving the values unchanged, if a value is
+    /// already stored under the handle.
+    ///
+    /// The default implementation can only insert under the handle
+    /// of [`next_proxy`](Owner::next_proxy), using
+    /// [`add`](Owner::add), and reports any other handle as in use.
+    fn insert_with_handle(&mut self, handle: u64, value: T) -> Result<Proxy<T>, HandleInUse> {
+        if Owner::next_proxy(self).index == handle {
+            Ok(Owner::add(self, value))
+        } else {
+            Err(HandleInUse(handle))
+        }
+    }
+    /// Set aside the [`Proxy`] for a value which will be inserted
+    /// later with [`insert_with_handle`](Owner::insert_with_handle),
+    /// so that other values can refer to it first.
+    ///
+    /// Values added in the meantime do not receive the proxy. If it is
+    /// not used, it should be handed back with
+    /// [`release_proxy`](Owner::release_proxy).
+    ///
+    /// The default implementation cannot set proxies aside, and always
+    /// returns [`None`]. It is only there so that owners written
+    /// before reservations were supported still compile; contexts
+    /// generated by the [`persian_rug`] macro override it.
+    fn reserve_proxy(&mut self) -> Option<Proxy<T>> {
+        None
+    }
+    /// Hand back a [`Proxy`] from
+    /// [`reserve_proxy`](Owner::reserve_proxy) which will not be used.
+    ///
+    /// The default implementation does nothing.
+    fn release_proxy(&mut self, _proxy: &Proxy<T>) {}
+    /// The [`Proxy`] for the value stored under a handle, with its
+    /// current [generation](Proxy::generation), if any.
+    ///
+    /// The default implementation searches all the stored proxies.
+    fn proxy_for(&self, handle: u64) -> Option<Proxy<T>> {
+        Owner::get_proxy_iter(self)
+            .find(|p| p.index == handle)
+            .copied()
+    }
+    /// The [`Proxy`] for the value stored under the smallest handle
+    /// which is not less than `handle`, if any.
+    ///
+    /// The default implementation searches all the stored proxies.
+    fn proxy_from(&self, handle: u64) -> Option<Proxy<T>> {
+        Owner::get_proxy_iter(self)
+            .filter(|p| p.index >= handle)
+            .min_by_key(|p| p.index)
+            .copied()
+    }
+    /// The number of values stored.
+    ///
+    /// The default implementation counts all the stored proxies.
+    fn len(&self) -> usize {
+        Owner::get_proxy_iter(self).count()
+    }
+    /// Check whether no values are stored.
+    fn is_empty(&self) -> bool {
+        Owner::len(self) == 0
+    }
+    /// Replace the value a [`Proxy`] refers to, returning the value
+    /// it replaced.
+    fn replace(&mut self, proxy: &Proxy<T>, value: T) -> T {
+        std::mem::replace(Owner::get_mut(self, proxy), value)
+    }
+    /// Exchange the values two [`Proxy`] objects refer to.
+    ///
+    /// Returns `false`, leaving the values unchanged, if either is
+    /// not stored.
+    ///
+    /// The default implementation searches all the stored proxies.
+    fn swap(&mut self, a: &Proxy<T>, b: &Proxy<T>) -> bool {
+        if a == b {
+            return Owner::contains(self, a);
+        }
+        let position = |p| Owner::get_proxy_iter(self).position(|q| q == p);
+        let (Some(a), Some(b)) = (position(a), position(b)) else {
+            return false;
+        };
+        let mut iter = Owner::get_iter_mut(self);
+        let first = iter.nth(a.min(b)).unwrap();
+        let second = iter.nth(a.max(b) - a.min(b) - 1).unwrap();
+        std::mem::swap(first, second);
+        true
+    }
+    /// Take out the value a [`Proxy`] refers to, making every proxy
+    /// for it stale.
+    ///
+    /// Returns [`None`], leaving the values unchanged, if it is not
+    /// stored.
+    ///
+    /// The default implementation cannot take values out, and always
+    /// returns [`None`]. It is only there so that owners written
+    /// before removal was supported still compile; contexts
+    /// generated by the [`persian_rug`] macro override it.
+    fn remove(&mut self, _proxy: &Proxy<T>) -> Option<T> {
+        None
+    }
+    /// Delete the value a [`Proxy`] refers to, making every proxy
+    /// for it stale.
+    ///
+    /// Returns `false`, leaving the values unchanged, if it is not
+    /// stored.
+    fn delete(&mut self, proxy: &Proxy<T>) -> bool {
+        Owner::remove(self, proxy).is_some()
+    }
+    /// Keep only the values for which `f` returns `true`, deleting
+    /// the others.
+    ///
+    /// The default implementation removes the values one at a time.
+    fn retain(&mut self, f: &mut dyn FnMut(&Proxy<T>, &mut T) -> bool) {
+        let proxies = Owner::get_proxy_iter(self).copied().collect::<Vec<_>>();
+        for p in proxies {
+            if !f(&p, Owner::get_mut(self, &p)) {
+                Owner::remove(self, &p);
+            }
+        }
+    }
+    /// Take every value out, along with its proxy.
+    ///
+    /// The default implementation removes the values one at a time,
+    /// leaving any that [`remove`](Owner::remove) cannot take out.
+    fn drain(&mut self) -> Vec<(Proxy<T>, T)> {
+        let proxies = Owner::get_proxy_iter(self).copied().collect::<Vec<_>>();
+        proxies
+            .into_iter()
+            .filter_map(|p| Owner::remove(self, &p).map(|value| (p, value)))
+            .collect()
+    }
+}
+
+/// A context which holds a single value of type `R`, alongside its
+/// tables.
+///
+/// Resources suit state which belongs to the whole context rather
+/// than to any one object, such as configuration, counters or caches.
+/// Keeping them in the context means they are shared, locked and
+/// cloned along with the objects they describe.
+///
+/// Implementations are normally generated by the [`persian_rug`]
+/// attribute macro, for each field of the context marked
+/// `#[resource]`:
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context};
+///
+/// #[contextual(Rug)]
+/// struct Order {
+///   number: u32,
+/// }
+///
+/// #[derive(Default)]
+/// struct NextOrderNumber(u32);
+///
+/// #[persian_rug]
+/// struct Rug {
+///   #[table]
+///   orders: Order,
+///   #[resource]
+///   next: NextOrderNumber,
+/// }
+///
+/// fn place_order<C>(context: &mut C) -> u32
+/// where
+///     C: persian_rug::ResourceOwner<NextOrderNumber>,
+/// {
+///     let next = context.resource_mut::<NextOrderNumber>();
+///     next.0 += 1;
+///     next.0
+/// }
+///
+/// let mut r = Rug { orders: Default::default(), next: Default::default() };
+/// let number = place_order(&mut r);
+/// r.add(Order { number });
+/// assert_eq!(r.resource::<NextOrderNumber>().0, 1);
+/// ```
+///
+/// A context can only hold one resource of each type.
+pub trait ResourceOwner<R>: Context {
+    /// Get a shared reference to the resource.
+    fn get_resource(&self) -> &R;
+    /// Get an exclusive reference to the resource.
+    fn get_resource_mut(&mut self) -> &mut R;
+}
+
+/// A context which holds its values of type `T` in a [`Table`].
+///
+/// [`Context`] and [`Owner`] cover the common operations on stored
+/// objects, but a [`Table`] offers more: handle reservation, deletion
+/// marks, parallel iteration and access to its storage, among others.
+/// Implementations are generated by the [`persian_rug`] attribute
+/// macro for each field of the context marked `#[table]`, so that
+/// these can be reached without naming the field, which may be
+/// private, or in a tuple struct:
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context};
+///
+/// #[contextual(Rug)]
+/// struct Task {
+///   title: &'static str,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Task);
+///
+/// let mut r = Rug(Default::default());
+/// let a = r.add(Task { title: "a" });
+/// let b = r.add(Task { title: "b" });
+///
+/// r.table_mut::<Task>().mark_deleted(&a);
+/// assert_eq!(r.table::<Task>().len(), 1);
+/// assert_eq!(r.get_proxy_iter::<Task>().collect::<Vec<_>>(), vec![&b]);
+/// ```
+///
+/// The exact type of the table depends on the storage and handle
+/// options given to the macro, so it is an associated type.
+pub trait TableOwner<T>: Owner<T>
 where
     T: Contextual<Context = Self>,
 {
-    /// Insert the given value, obtaining a [`Proxy`] for it.
-    fn add(&mut self, value: T) -> Proxy<T>;
-    /// Get a shared reference to a value from a [`Proxy`] for it.
-    fn get(&self, proxy: &Proxy<T>) -> &T;
-    /// Get an exclusive reference to a value from a [`Proxy`] for it.    
-    fn get_mut(&mut self, proxy: &Proxy<T>) -> &mut T;
-    /// Iterate over shared references to the stored values.
-    fn get_iter(&self) -> TableIterator<'_, T>;
-    /// Iterate over exclusive references to the stored values.
-    fn get_iter_mut(&mut self) -> TableMutIterator<'_, T>;
-    /// Iterate over shared references to [`Proxy`] objects for the
-    /// stored values.
-    fn get_proxy_iter(&self) -> TableProxyIterator<'_, T>;
+    /// The type of the table.
+    type Table;
+    /// Get a shared reference to the table.
+    fn get_table(&self) -> &Self::Table;
+    /// Get an exclusive reference to the table.
+    fn get_table_mut(&mut self) -> &mut Self::Table;
+}
+
+/// Support for [`constraints`]: checks that a type in an `access`
+/// list which does not depend on any parameters belongs to the
+/// context.
+#[doc(hidden)]
+pub fn __check_access<C, T>()
+where
+    C: Owner<T>,
+    T: Contextual<Context = C>,
+{
 }
 
 /// Something that is associated to a context
@@ -659,9 +2185,26 @@ where
 /// general, it is better to design your types to be usable in
 /// different contexts if needed, as discussed above, but always
 /// include everything needed in a given scenario in the same context.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be stored in a context",
+    label = "`{Self}` is not `Contextual`",
+    note = "use the `contextual` attribute macro to make it `Contextual`"
+)]
 pub trait Contextual {
     /// The [`Context`] type which owns values of this type.
     type Context: Context;
+
+    /// Describe this type and its fields.
+    ///
+    /// The [`contextual`] macro implements this to list the fields of
+    /// the type. The default implementation describes the type
+    /// without any fields. See the [`schema`] module for details.
+    fn schema() -> schema::TypeSchema
+    where
+        Self: Sized,
+    {
+        schema::TypeSchema::opaque::<Self>()
+    }
 }
 
 /// A handle to an item stored in some context.
@@ -718,94 +2261,440 @@ pub trait Contextual {
 /// #[persian_rug]
 /// struct Rug(#[table] Foo);
 ///
-/// ```
-/// Had we used [`Proxy<Foo>`](Proxy) as our start value here (and given up having
-/// a self parameter), we could've used the proxies themselves to check
-/// for uniqueness, avoiding the need to compare and clone [`String`] fields,
-/// and removing any chance of a name collision.
+/// ```
+/// Had we used [`Proxy<Foo>`](Proxy) as our start value here (and given up having
+/// a self parameter), we could've used the proxies themselves to check
+/// for uniqueness, avoiding the need to compare and clone [`String`] fields,
+/// and removing any chance of a name collision.
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Accessor, Context, Contextual, Proxy};
+/// use std::collections::BTreeSet;
+///
+/// #[contextual(Rug)]
+/// struct Foo {
+///    id: String,
+///    links: Vec<Proxy<Foo>>
+/// }
+///
+/// impl Foo {
+///   pub fn print_graph<A: Accessor<Context=Rug>>(start: Proxy<Foo>, access: A) {
+///     let mut b = BTreeSet::new();
+///     let mut work = Vec::new();
+///     work.push(start);
+///     while let Some(item_proxy) = work.pop() {
+///       let item = access.get(&item_proxy);
+///       println!("{}", item.id);
+///       b.insert(item_proxy);
+///       for link in &item.links {
+///         if !b.contains(link) {
+///           work.push(*link);
+///         }
+///       }
+///     }
+///   }
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Foo);
+///
+/// ```
+///
+/// Note that a [`Proxy`] implements [`Copy`] as well as [`Eq`]. The
+/// implementation of [`Ord`] is guaranteed to be consistent on a given
+/// run of the program, but no other guarantees are made.
+///
+/// Besides its [`handle`](Proxy::handle), a proxy records the
+/// [`generation`](Proxy::generation) of that handle it was issued
+/// for. When an object is [deleted](Context::delete), its table moves
+/// the handle on to a new generation, so that if the handle is given
+/// to another object later, proxies for the deleted object are still
+/// recognised as stale, rather than resolving to the new object.
+pub struct Proxy<T> {
+    _marker: core::marker::PhantomData<T>,
+    index: u64,
+    generation: u32,
+}
+
+impl<T> Proxy<T> {
+    /// The handle identifying this proxy within its table.
+    ///
+    /// A table can be rebuilt with the same proxies by passing each
+    /// handle to [`Table::insert_with_handle`].
+    pub fn handle(&self) -> u64 {
+        self.index
+    }
+
+    /// How many times objects stored under this proxy's handle had
+    /// been deleted when it was issued.
+    ///
+    /// This is zero unless the handle has been used before.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// The proxy with the given handle, in its first generation.
+    ///
+    /// This is for referring to objects whose handles are known before
+    /// they are stored, such as those reserved with
+    /// [`Table::reserve_handles`]. Looking up a proxy for which no
+    /// object has been stored fails, just as for a proxy from another
+    /// context. To find the proxy for an object stored under a handle
+    /// which may have been reused, use [`Owner::proxy_for`] instead.
+    pub const fn from_handle(handle: u64) -> Self {
+        Self::with_generation(handle, 0)
+    }
+
+    /// The proxy with the given handle and generation.
+    pub(crate) const fn with_generation(handle: u64, generation: u32) -> Self {
+        Self {
+            _marker: core::marker::PhantomData,
+            index: handle,
+            generation,
+        }
+    }
+
+    /// The handle and generation of this proxy, which together tell it
+    /// apart from every other proxy of its type.
+    pub(crate) fn key(&self) -> (u64, u32) {
+        (self.index, self.generation)
+    }
+
+    /// The proxy with the given [`key`](Proxy::key).
+    pub(crate) fn from_key((handle, generation): (u64, u32)) -> Self {
+        Self::with_generation(handle, generation)
+    }
+
+    /// The proxy for the `n`th root of a table created with
+    /// [`Table::with_reserved`].
+    ///
+    /// Roots are stored under the first handles of their table, so
+    /// this is the same as [`from_handle`](Proxy::from_handle), but
+    /// says what the proxy is for. Because it can be evaluated at
+    /// compile time, well-known roots can be named by constants, and
+    /// used anywhere without passing their proxies around.
+    pub const fn reserved(n: u64) -> Self {
+        Self::from_handle(n)
+    }
+}
+
+impl<T> Clone for Proxy<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Proxy<T> {}
+
+impl<T> PartialOrd for Proxy<T> {
+    fn partial_cmp(&self, other: &Proxy<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Proxy<T> {
+    fn cmp(&self, other: &Proxy<T>) -> Ordering {
+        (self.index, self.generation).cmp(&(other.index, other.generation))
+    }
+}
+
+impl<T> PartialEq for Proxy<T> {
+    fn eq(&self, other: &Proxy<T>) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Proxy<T> {}
+
+impl<T> Hash for Proxy<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> std::fmt::Debug for Proxy<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "persian_rug::Proxy<{}> {{ handle: {}",
+            std::any::type_name::<T>(),
+            self.index
+        )?;
+        if self.generation != 0 {
+            write!(f, ", generation: {}", self.generation)?;
+        }
+        write!(f, " }}")
+    }
+}
+
+/// A read-only reference to a [`Contextual`] object.
+///
+/// A [`ReadProxy`] is made from a [`Proxy`], and can be resolved with
+/// [`Context::get_read`], [`Accessor::get_read`] or
+/// [`Mutator::get_read`]. It cannot be turned back into a [`Proxy`],
+/// so it is never accepted by [`get_mut`](Context::get_mut) or any
+/// other method that changes an object. An API can hand one out to
+/// let callers refer to an object in the context, knowing that they
+/// cannot use it to modify that object:
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, Proxy, ReadProxy};
+///
+/// #[contextual(Rug)]
+/// struct Config {
+///   verbose: bool,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Config);
+///
+/// struct Service {
+///   config: Proxy<Config>,
+/// }
+///
+/// impl Service {
+///   pub fn config(&self) -> ReadProxy<Config> {
+///     self.config.into()
+///   }
+/// }
+///
+/// let mut r = Rug(Default::default());
+/// let service = Service { config: r.add(Config { verbose: true }) };
+/// let config = service.config();
+/// assert!(r.get_read(&config).verbose);
+/// ```
+///
+/// Passing it to [`get_mut`](Context::get_mut) does not compile:
+///
+/// ```rust,compile_fail
+/// # use persian_rug::{contextual, persian_rug, Context, ReadProxy};
+/// # #[contextual(Rug)]
+/// # struct Config {
+/// #   verbose: bool,
+/// # }
+/// # #[persian_rug]
+/// # struct Rug(#[table] Config);
+/// let mut r = Rug(Default::default());
+/// let config: ReadProxy<Config> = r.add(Config { verbose: true }).into();
+/// r.get_mut(&config).verbose = false;
+/// ```
+///
+/// Like a [`Proxy`], a [`ReadProxy`] implements [`Copy`], [`Eq`],
+/// [`Ord`] and [`Hash`], and compares equal to another exactly when
+/// the proxies they were made from do.
+pub struct ReadProxy<T> {
+    proxy: Proxy<T>,
+}
+
+impl<T> ReadProxy<T> {
+    /// The handle identifying the object within its table.
+    pub fn handle(&self) -> u64 {
+        self.proxy.handle()
+    }
+}
+
+impl<T> From<Proxy<T>> for ReadProxy<T> {
+    fn from(proxy: Proxy<T>) -> Self {
+        Self { proxy }
+    }
+}
+
+impl<T> From<&Proxy<T>> for ReadProxy<T> {
+    fn from(proxy: &Proxy<T>) -> Self {
+        Self { proxy: *proxy }
+    }
+}
+
+impl<T> Clone for ReadProxy<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ReadProxy<T> {}
+
+impl<T> PartialOrd for ReadProxy<T> {
+    fn partial_cmp(&self, other: &ReadProxy<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for ReadProxy<T> {
+    fn cmp(&self, other: &ReadProxy<T>) -> Ordering {
+        self.proxy.cmp(&other.proxy)
+    }
+}
+
+impl<T> PartialEq for ReadProxy<T> {
+    fn eq(&self, other: &ReadProxy<T>) -> bool {
+        self.proxy.eq(&other.proxy)
+    }
+}
+
+impl<T> Eq for ReadProxy<T> {}
+
+impl<T> PartialEq<Proxy<T>> for ReadProxy<T> {
+    fn eq(&self, other: &Proxy<T>) -> bool {
+        self.proxy.eq(other)
+    }
+}
+
+impl<T> Hash for ReadProxy<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.proxy.hash(state);
+    }
+}
+
+impl<T> std::fmt::Debug for ReadProxy<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "persian_rug::ReadProxy<{}> {{ handle: {}",
+            std::any::type_name::<T>(),
+            self.proxy.index
+        )?;
+        if self.proxy.generation != 0 {
+            write!(f, ", generation: {}", self.proxy.generation)?;
+        }
+        write!(f, " }}")
+    }
+}
+
+/// A reference to a [`Contextual`] object which may since have been
+/// deleted.
+///
+/// Proxies never keep their objects alive, but a [`Proxy`] is held
+/// on the understanding that its object is still stored, and looking
+/// it up panics if not. A [`WeakProxy`] is held without that
+/// understanding, which suits back-references and caches: before
+/// it is used, it must be turned back into a [`Proxy`] with
+/// [`upgrade`](WeakProxy::upgrade), which fails once the object has
+/// been [deleted](Context::delete). It is not a [`Links`] value, so
+/// it is not followed by anything which walks the links between
+/// objects.
+///
 /// ```rust
-/// use persian_rug::{contextual, persian_rug, Accessor, Context, Contextual, Proxy};
-/// use std::collections::BTreeSet;
+/// use persian_rug::{contextual, persian_rug, Context, Proxy, WeakProxy};
 ///
 /// #[contextual(Rug)]
-/// struct Foo {
-///    id: String,
-///    links: Vec<Proxy<Foo>>
-/// }
-///
-/// impl Foo {
-///   pub fn print_graph<A: Accessor<Context=Rug>>(start: Proxy<Foo>, access: A) {
-///     let mut b = BTreeSet::new();
-///     let mut work = Vec::new();
-///     work.push(start);
-///     while let Some(item_proxy) = work.pop() {
-///       let item = access.get(&item_proxy);
-///       println!("{}", item.id);
-///       b.insert(item_proxy);
-///       for link in &item.links {
-///         if !b.contains(link) {
-///           work.push(*link);
-///         }
-///       }
-///     }
-///   }
+/// struct Document {
+///   title: &'static str,
 /// }
 ///
 /// #[persian_rug]
-/// struct Rug(#[table] Foo);
+/// struct Rug(#[table] Document);
 ///
+/// let mut r = Rug(Default::default());
+/// let doc = r.add(Document { title: "draft" });
+/// let recent: WeakProxy<Document> = doc.downgrade();
+///
+/// assert_eq!(recent.upgrade(&r), Some(doc));
+/// r.delete(&doc);
+/// assert_eq!(recent.upgrade(&r), None);
 /// ```
 ///
-/// Note that a [`Proxy`] implements [`Copy`] as well as [`Eq`]. The
-/// implementation of [`Ord`] is guaranteed to be consistent on a given
-/// run of the program, but no other guarantees are made.
-pub struct Proxy<T> {
-    _marker: core::marker::PhantomData<T>,
-    index: u64,
+/// Upgrading asks the context whether it still holds the object, with
+/// [`Owner::contains`], which for contexts created with the
+/// [`persian_rug`] attribute macro is a lookup in the object's table.
+/// Objects which are [marked as deleted](Table::mark_deleted) are
+/// still stored, and can still be upgraded to.
+pub struct WeakProxy<T> {
+    proxy: Proxy<T>,
 }
 
-impl<T> Clone for Proxy<T> {
+impl<T> WeakProxy<T> {
+    /// The handle identifying the object within its table.
+    pub fn handle(&self) -> u64 {
+        self.proxy.handle()
+    }
+
+    /// The generation of the handle the object was stored under.
+    pub fn generation(&self) -> u32 {
+        self.proxy.generation()
+    }
+
+    /// Recover a [`Proxy`] for the object, if `context` still holds
+    /// it.
+    pub fn upgrade<C>(&self, context: &C) -> Option<Proxy<T>>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        Owner::contains(context, &self.proxy).then_some(self.proxy)
+    }
+}
+
+impl<T> Proxy<T> {
+    /// Make a [`WeakProxy`] for the same object.
+    pub fn downgrade(&self) -> WeakProxy<T> {
+        WeakProxy { proxy: *self }
+    }
+}
+
+impl<T> From<Proxy<T>> for WeakProxy<T> {
+    fn from(proxy: Proxy<T>) -> Self {
+        Self { proxy }
+    }
+}
+
+impl<T> From<&Proxy<T>> for WeakProxy<T> {
+    fn from(proxy: &Proxy<T>) -> Self {
+        Self { proxy: *proxy }
+    }
+}
+
+impl<T> Clone for WeakProxy<T> {
     fn clone(&self) -> Self {
         *self
     }
 }
 
-impl<T> Copy for Proxy<T> {}
+impl<T> Copy for WeakProxy<T> {}
 
-impl<T> PartialOrd for Proxy<T> {
-    fn partial_cmp(&self, other: &Proxy<T>) -> Option<Ordering> {
+impl<T> PartialOrd for WeakProxy<T> {
+    fn partial_cmp(&self, other: &WeakProxy<T>) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl<T> Ord for Proxy<T> {
-    fn cmp(&self, other: &Proxy<T>) -> Ordering {
-        self.index.cmp(&other.index)
+impl<T> Ord for WeakProxy<T> {
+    fn cmp(&self, other: &WeakProxy<T>) -> Ordering {
+        self.proxy.cmp(&other.proxy)
     }
 }
 
-impl<T> PartialEq for Proxy<T> {
-    fn eq(&self, other: &Proxy<T>) -> bool {
-        self.index.eq(&other.index)
+impl<T> PartialEq for WeakProxy<T> {
+    fn eq(&self, other: &WeakProxy<T>) -> bool {
+        self.proxy.eq(&other.proxy)
     }
 }
 
-impl<T> Eq for Proxy<T> {}
+impl<T> Eq for WeakProxy<T> {}
 
-impl<T> Hash for Proxy<T> {
+impl<T> PartialEq<Proxy<T>> for WeakProxy<T> {
+    fn eq(&self, other: &Proxy<T>) -> bool {
+        self.proxy.eq(other)
+    }
+}
+
+impl<T> Hash for WeakProxy<T> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.index.hash(state);
+        self.proxy.hash(state);
     }
 }
 
-impl<T> std::fmt::Debug for Proxy<T> {
+impl<T> std::fmt::Debug for WeakProxy<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "persian_rug::Proxy<{}> {{ handle: {} }}",
+            "persian_rug::WeakProxy<{}> {{ handle: {}",
             std::any::type_name::<T>(),
-            self.index
-        )
+            self.proxy.index
+        )?;
+        if self.proxy.generation != 0 {
+            write!(f, ", generation: {}", self.proxy.generation)?;
+        }
+        write!(f, " }}")
     }
 }
 
@@ -816,7 +2705,7 @@ impl<T> std::fmt::Debug for Proxy<T> {
 /// makes sense in a given application depends upon how many objects
 /// of type `T` you have created proxies for, and what proportion will
 /// typically belong to the set. In many cases, other set
-/// representations like [`BTreeSet`](std::collections::BTreeSet) and
+/// representations like [`BTreeSet`] and
 /// [`HashSet`](std::collections::HashSet) will be preferable.
 ///
 /// The driver for this type is graph search, where it may be that this
@@ -850,11 +2739,26 @@ impl<T> std::fmt::Debug for Proxy<T> {
 /// assert!(!s.contains(&b));
 /// assert!(s.contains(&c));
 /// ```
+///
+/// A set holds at most one proxy for each handle. Proxies from a
+/// later [generation](Proxy::generation) of a handle are told apart
+/// from those for deleted objects, but inserting one replaces any
+/// proxy with the same handle. Only members whose generation is not
+/// zero have it recorded, in a map beside the bits.
+///
+/// Since the set takes a bit for every handle below its largest
+/// member, it suits tables whose handles are small and close
+/// together, as [`Sequential`](handles::Sequential) and
+/// [`Recycling`](handles::Recycling) issue them. For the far-apart
+/// handles of a [`ShardPrefixed`](handles::ShardPrefixed),
+/// [`TimeOrdered`](handles::TimeOrdered) or
+/// [`Random`](handles::Random) allocator, use a [`BTreeSet`] instead.
 #[derive(Debug)]
 pub struct ProxySet<T> {
     _marker: core::marker::PhantomData<T>,
     marks: Vec<u64>,
     len: usize,
+    generations: BTreeMap<u64, u32>,
 }
 
 impl<T> ProxySet<T> {
@@ -863,6 +2767,19 @@ impl<T> ProxySet<T> {
             _marker: Default::default(),
             marks: Vec::new(),
             len: 0,
+            generations: BTreeMap::new(),
+        }
+    }
+
+    fn generation(&self, index: u64) -> u32 {
+        self.generations.get(&index).copied().unwrap_or(0)
+    }
+
+    fn set_generation(&mut self, p: &Proxy<T>) {
+        if p.generation == 0 {
+            self.generations.remove(&p.index);
+        } else {
+            self.generations.insert(p.index, p.generation);
         }
     }
 
@@ -881,6 +2798,7 @@ impl<T> ProxySet<T> {
             self.marks[Self::word(p.index)] |= Self::bit(p.index);
             self.len += 1;
         }
+        self.set_generation(&p);
     }
 
     pub fn contains(&self, p: &Proxy<T>) -> bool {
@@ -888,14 +2806,14 @@ impl<T> ProxySet<T> {
             false
         } else {
             self.marks[Self::word(p.index)] & (Self::bit(p.index)) != 0
+                && self.generation(p.index) == p.generation
         }
     }
 
     pub fn remove(&mut self, p: &Proxy<T>) -> Option<Proxy<T>> {
-        if self.marks.len() <= Self::word(p.index) {
-            None
-        } else if self.marks[Self::word(p.index)] & (Self::bit(p.index)) != 0 {
+        if self.contains(p) {
             self.marks[Self::word(p.index)] &= !(Self::bit(p.index));
+            self.generations.remove(&p.index);
             self.len -= 1;
             Some(*p)
         } else {
@@ -919,6 +2837,79 @@ impl<T> ProxySet<T> {
             owner: self,
         }
     }
+
+    /// Add every proxy in the table for `T` to this set.
+    ///
+    /// Items which have been [soft deleted](Table::mark_deleted) are
+    /// not added.
+    pub fn fill_from_table<A>(&mut self, access: &A)
+    where
+        A: Accessor,
+        A::Context: Owner<T>,
+        T: Contextual<Context = A::Context>,
+    {
+        for p in access.get_proxy_iter::<T>() {
+            self.insert(*p);
+        }
+    }
+
+    /// The set of proxies in the table for `T` which are not in this
+    /// set.
+    ///
+    /// The result is computed a word at a time from the table's
+    /// handles, rather than by testing each proxy in turn:
+    ///
+    /// ```rust
+    /// use persian_rug::{contextual, persian_rug, Context, ProxySet};
+    ///
+    /// #[contextual(Rug)]
+    /// struct Foo {
+    ///   a: i32,
+    /// }
+    ///
+    /// #[persian_rug]
+    /// struct Rug(#[table] Foo);
+    ///
+    /// let mut r = Rug(Default::default());
+    /// let a = r.add(Foo { a: 1 });
+    /// let b = r.add(Foo { a: 2 });
+    /// let c = r.add(Foo { a: 3 });
+    ///
+    /// let mut visited = ProxySet::new();
+    /// visited.insert(b);
+    ///
+    /// let rest = visited.complement_in(&&r);
+    /// assert_eq!(rest.iter().collect::<Vec<_>>(), vec![a, c]);
+    /// ```
+    ///
+    /// Members of this set which are not in the table are ignored, as
+    /// are items which have been [soft deleted](Table::mark_deleted).
+    pub fn complement_in<A>(&self, access: &A) -> Self
+    where
+        A: Accessor,
+        A::Context: Owner<T>,
+        T: Contextual<Context = A::Context>,
+    {
+        let mut res = Self::new();
+        res.fill_from_table(access);
+        for (word, mark) in res.marks.iter_mut().zip(self.marks.iter()) {
+            *word &= !mark;
+        }
+        res.len = res.marks.iter().map(|w| w.count_ones() as usize).sum();
+        if !self.generations.is_empty() || !res.generations.is_empty() {
+            // Members for deleted objects must not hide the objects
+            // since stored under their handles.
+            for p in access.get_proxy_iter::<T>() {
+                if !self.contains(p) {
+                    res.insert(*p);
+                }
+            }
+            let marks = &res.marks;
+            res.generations
+                .retain(|index, _| marks[Self::word(*index)] & Self::bit(*index) != 0);
+        }
+        res
+    }
 }
 
 impl<T> Default for ProxySet<T> {
@@ -933,13 +2924,14 @@ impl<T> Clone for ProxySet<T> {
             _marker: Default::default(),
             marks: self.marks.clone(),
             len: self.len,
+            generations: self.generations.clone(),
         }
     }
 }
 
 impl<T> PartialEq for ProxySet<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.marks == other.marks
+        self.marks == other.marks && self.generations == other.generations
     }
 }
 
@@ -953,109 +2945,574 @@ impl<T> PartialOrd for ProxySet<T> {
 
 impl<T> Ord for ProxySet<T> {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.marks.cmp(&other.marks)
+        (&self.marks, &self.generations).cmp(&(&other.marks, &other.generations))
     }
 }
 
 impl<T> Hash for ProxySet<T> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.marks.hash(state)
+        self.marks.hash(state);
+        self.generations.hash(state);
+    }
+}
+
+/// An [`Iterator`] over members of a [`ProxySet`].
+///
+/// This is returned by [`ProxySet::iter()`]. Note that the returned
+/// [`Proxy`] objects are not references, since there are no actual
+/// proxy objects stored in the [`ProxySet`].
+pub struct ProxySetIterator<'a, T> {
+    _marker: core::marker::PhantomData<T>,
+    index: u64,
+    mask: u64,
+    owner: &'a ProxySet<T>,
+}
+
+impl<'a, T> Iterator for ProxySetIterator<'a, T> {
+    type Item = Proxy<T>;
+
+    fn next(&mut self) -> Option<Proxy<T>> {
+        while self.owner.marks.len() > ProxySet::<T>::word(self.index) {
+            let w = self.owner.marks[ProxySet::<T>::word(self.index)];
+            if w ^ self.mask == 0 {
+                self.index = ((self.index >> 6) + 1) << 6;
+                self.mask = 0;
+            } else if w & ProxySet::<T>::bit(self.index) != 0 {
+                self.mask |= ProxySet::<T>::bit(self.index);
+                self.index += 1;
+                if self.index & 0x3F == 0 {
+                    self.mask = 0;
+                }
+                let index = self.index - 1;
+                return Some(Proxy::with_generation(index, self.owner.generation(index)));
+            } else {
+                self.index += 1;
+                if self.index & 0x3F == 0 {
+                    self.mask = 0;
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The error returned when a [`Table`] already holds an item under
+/// the requested handle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HandleInUse(pub u64);
+
+impl std::fmt::Display for HandleInUse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "handle {} is already in use", self.0)
+    }
+}
+
+impl std::error::Error for HandleInUse {}
+
+/// A holder for [`Contextual`] objects.
+///
+/// It is unlikely that you will ever need to instantiate this class,
+/// unless for some reason the [`persian_rug`] attribute macro which
+/// creates [`Context`] implementations is not suitable for your use.
+///
+/// A context object will generally have one table per object type,
+/// and that table does the work of storing, retrieving and iterating
+/// over objects of that type, and the [`Proxy`] objects that refer to
+/// them.
+///
+/// How the objects are laid out in memory is determined by the
+/// [`Storage`](storage::Storage) parameter `S`; see the [`storage`]
+/// module for the options. The handles of new items are chosen by
+/// the [`HandleAllocator`](handles::HandleAllocator) parameter `A`;
+/// see the [`handles`] module for those.
+///
+/// The [`Debug`](std::fmt::Debug) representation of a table is a map
+/// from handles to values, in iteration order.
+///
+/// With the `borsh` feature, tables and proxies implement borsh's
+/// `BorshSerialize` and `BorshDeserialize`. Tables are encoded in
+/// handle order whatever their storage, so equal tables give equal
+/// bytes.
+///
+/// With the `serde-diff` feature, tables and proxies implement
+/// `serde_diff::SerdeDiff`, so that the changes between two snapshots
+/// of a table can be sent as the fields which changed in each object,
+/// and the objects which were added. Applying the diff to a copy of
+/// the older snapshot gives the newer one, with the same proxies.
+/// Diffs cannot describe objects being removed.
+pub struct Table<T, S = storage::MapStorage<T>, A = handles::Sequential> {
+    _marker: core::marker::PhantomData<T>,
+    storage: S,
+    handles: A,
+    #[cfg(feature = "profiling")]
+    counters: profiling::Counters,
+    #[cfg(feature = "provenance")]
+    provenance: provenance::Records,
+    #[cfg(feature = "observe")]
+    observers: observe::Observers<T>,
+    deleted: BTreeSet<u64>,
+    generations: BTreeMap<u64, u32>,
+}
+
+impl<T, S, A> Default for Table<T, S, A>
+where
+    S: storage::Storage<T> + Default,
+    A: handles::HandleAllocator + Default,
+{
+    fn default() -> Self {
+        Self {
+            _marker: Default::default(),
+            storage: Default::default(),
+            handles: Default::default(),
+            #[cfg(feature = "profiling")]
+            counters: Default::default(),
+            #[cfg(feature = "provenance")]
+            provenance: Default::default(),
+            #[cfg(feature = "observe")]
+            observers: Default::default(),
+            deleted: Default::default(),
+            generations: Default::default(),
+        }
+    }
+}
+
+impl<T, S, A> Clone for Table<T, S, A>
+where
+    S: Clone,
+    A: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            _marker: Default::default(),
+            storage: self.storage.clone(),
+            handles: self.handles.clone(),
+            #[cfg(feature = "profiling")]
+            counters: Default::default(),
+            #[cfg(feature = "provenance")]
+            provenance: self.provenance.clone(),
+            #[cfg(feature = "observe")]
+            observers: Default::default(),
+            deleted: self.deleted.clone(),
+            generations: self.generations.clone(),
+        }
+    }
+}
+
+impl<T, S, A> std::fmt::Debug for Table<T, S, A>
+where
+    T: std::fmt::Debug,
+    S: storage::Storage<T>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Not every storage keeps its entries in handle order, and the
+        // rendering should not depend on the storage used.
+        let mut entries = self
+            .storage
+            .entries()
+            .map(|(p, v)| (p.index, v))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(index, _)| *index);
+        f.debug_map().entries(entries).finish()
+    }
+}
+
+impl<T, S> Table<T, S>
+where
+    S: storage::Storage<T> + Default,
+{
+    /// Create a new table.
+    ///
+    /// Tables are created empty.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create a new table holding some well-known root objects.
+    ///
+    /// The roots are stored under the first handles, in order, so
+    /// that [`Proxy::reserved`] refers to them without needing to be
+    /// told their proxies:
+    ///
+    /// ```rust
+    /// use persian_rug::{contextual, persian_rug, Context, Proxy, Table};
+    ///
+    /// #[contextual(Rug)]
+    /// struct Folder {
+    ///   name: &'static str,
+    ///   children: Vec<Proxy<Folder>>,
+    /// }
+    ///
+    /// const ROOT: Proxy<Folder> = Proxy::reserved(0);
+    /// const TRASH: Proxy<Folder> = Proxy::reserved(1);
+    ///
+    /// #[persian_rug]
+    /// struct Rug(#[table] Folder);
+    ///
+    /// fn new_rug() -> Rug {
+    ///     Rug(Table::with_reserved([
+    ///         Folder { name: "/", children: vec![] },
+    ///         Folder { name: "trash", children: vec![] },
+    ///     ]))
+    /// }
+    ///
+    /// fn mkdir(r: &mut Rug, name: &'static str) -> Proxy<Folder> {
+    ///     let folder = r.add(Folder { name, children: vec![] });
+    ///     r.get_mut(&ROOT).children.push(folder);
+    ///     folder
+    /// }
+    ///
+    /// let mut r = new_rug();
+    /// let docs = mkdir(&mut r, "docs");
+    /// assert_eq!(docs.handle(), 2);
+    /// assert_eq!(r.get(&ROOT).children, vec![docs]);
+    /// assert_eq!(r.get(&TRASH).name, "trash");
+    /// ```
+    #[track_caller]
+    pub fn with_reserved(roots: impl IntoIterator<Item = T>) -> Self {
+        let mut res = Self::new();
+        for root in roots {
+            res.push(root);
+        }
+        res
+    }
+
+    /// Create a new table holding the given items, returning it along
+    /// with their proxies, in order.
+    ///
+    /// This adopts data prepared elsewhere wholesale, for example
+    /// objects parsed in bulk, or produced on another thread:
+    ///
+    /// ```rust
+    /// use persian_rug::{contextual, persian_rug, Context, Table};
+    ///
+    /// #[contextual(Rug)]
+    /// struct Point {
+    ///   x: i32,
+    /// }
+    ///
+    /// #[persian_rug]
+    /// struct Rug(#[table] Point);
+    ///
+    /// let points = (0..3).map(|x| Point { x }).collect::<Vec<_>>();
+    /// let (table, proxies) = Table::from_vec(points);
+    /// let r = Rug(table);
+    /// assert_eq!(r.get(&proxies[2]).x, 2);
+    /// ```
+    #[track_caller]
+    pub fn from_vec(items: Vec<T>) -> (Self, Vec<Proxy<T>>) {
+        let mut res = Self::new();
+        let proxies = items.into_iter().map(|item| res.push(item)).collect();
+        (res, proxies)
+    }
+}
+
+impl<T, S, A> Table<T, S, A>
+where
+    S: storage::Storage<T> + Default,
+    A: handles::HandleAllocator,
+{
+    /// Create a new table whose handles are chosen by `handles`.
+    ///
+    /// See the [`handles`] module for the options.
+    pub fn with_handles(handles: A) -> Self {
+        Self {
+            _marker: Default::default(),
+            storage: Default::default(),
+            handles,
+            #[cfg(feature = "profiling")]
+            counters: Default::default(),
+            #[cfg(feature = "provenance")]
+            provenance: Default::default(),
+            #[cfg(feature = "observe")]
+            observers: Default::default(),
+            deleted: Default::default(),
+            generations: Default::default(),
+        }
+    }
+}
+
+impl<T, S, A> Table<T, S, A>
+where
+    S: storage::Storage<T>,
+    A: handles::HandleAllocator,
+{
+    /// Create a new table using the given storage, whose handles are
+    /// chosen by `handles`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the storage is not empty.
+    pub fn with_storage_and_handles(storage: S, handles: A) -> Self {
+        assert!(storage.is_empty(), "table storage must be empty");
+        Self {
+            _marker: Default::default(),
+            storage,
+            handles,
+            #[cfg(feature = "profiling")]
+            counters: Default::default(),
+            #[cfg(feature = "provenance")]
+            provenance: Default::default(),
+            #[cfg(feature = "observe")]
+            observers: Default::default(),
+            deleted: Default::default(),
+            generations: Default::default(),
+        }
+    }
+
+    /// The [`Proxy`] that the next item pushed will receive.
+    pub fn next_proxy(&self) -> Proxy<T> {
+        self.issue(self.handles.peek())
+    }
+
+    /// Check whether an item is stored for a [`Proxy`].
+    ///
+    /// This is `false` for a proxy whose item has been
+    /// [deleted](Table::delete), even if another item has since been
+    /// stored under the same handle.
+    pub fn contains(&self, p: &Proxy<T>) -> bool {
+        self.is_current(p) && self.storage.get(p.index).is_some()
+    }
+
+    /// The [`Proxy`] for the item stored under a handle, with its
+    /// current [generation](Proxy::generation).
+    ///
+    /// Unlike [`Proxy::from_handle`], which always gives the first
+    /// generation, this finds the item even if the handle has been
+    /// reused. Returns [`None`] if no item is stored under the handle.
+    pub fn proxy_for(&self, handle: u64) -> Option<Proxy<T>> {
+        self.storage.proxy(handle).copied()
+    }
+
+    /// The [`Proxy`] for the item stored under the smallest handle
+    /// which is not less than `handle`.
+    ///
+    /// This finds the next stored item directly, however sparsely the
+    /// table's allocator issues handles.
+    pub fn proxy_from(&self, handle: u64) -> Option<Proxy<T>> {
+        self.storage.proxy_from(handle).copied()
+    }
+
+    /// Recover a [`Proxy`] from a [`WeakProxy`], if its item is still
+    /// stored.
+    pub fn upgrade(&self, p: &WeakProxy<T>) -> Option<Proxy<T>> {
+        self.contains(&p.proxy).then_some(p.proxy)
+    }
+
+    /// The proxy for the current generation of a handle.
+    fn issue(&self, handle: u64) -> Proxy<T> {
+        Proxy::with_generation(handle, self.generations.get(&handle).copied().unwrap_or(0))
+    }
+
+    /// Check whether a proxy is for the current generation of its
+    /// handle, and so not for an item that has been deleted.
+    fn is_current(&self, p: &Proxy<T>) -> bool {
+        self.generations.get(&p.index).copied().unwrap_or(0) == p.generation
     }
-}
 
-/// An [`Iterator`] over members of a [`ProxySet`].
-///
-/// This is returned by [`ProxySet::iter()`]. Note that the returned
-/// [`Proxy`] objects are not references, since there are no actual
-/// proxy objects stored in the [`ProxySet`].
-pub struct ProxySetIterator<'a, T> {
-    _marker: core::marker::PhantomData<T>,
-    index: u64,
-    mask: u64,
-    owner: &'a ProxySet<T>,
-}
+    /// The number of items stored, except those marked as deleted.
+    pub fn len(&self) -> usize {
+        self.storage.len() - self.deleted.len()
+    }
 
-impl<'a, T> Iterator for ProxySetIterator<'a, T> {
-    type Item = Proxy<T>;
+    /// Check whether no items are stored, except those marked as
+    /// deleted.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 
-    fn next(&mut self) -> Option<Proxy<T>> {
-        while self.owner.marks.len() > ProxySet::<T>::word(self.index) {
-            let w = self.owner.marks[ProxySet::<T>::word(self.index)];
-            if w ^ self.mask == 0 {
-                self.index = ((self.index >> 6) + 1) << 6;
-                self.mask = 0;
-            } else if w & ProxySet::<T>::bit(self.index) != 0 {
-                self.mask |= ProxySet::<T>::bit(self.index);
-                self.index += 1;
-                if self.index & 0x3F == 0 {
-                    self.mask = 0;
-                }
-                return Some(Proxy {
-                    _marker: Default::default(),
-                    index: self.index - 1,
-                });
-            } else {
-                self.index += 1;
-                if self.index & 0x3F == 0 {
-                    self.mask = 0;
+    /// Exchange the items stored for two [`Proxy`] objects.
+    ///
+    /// Returns `false`, leaving the table unchanged, if either is not
+    /// stored.
+    pub fn swap(&mut self, a: &Proxy<T>, b: &Proxy<T>) -> bool {
+        #[cfg(feature = "profiling")]
+        self.counters.get_mut();
+        if !self.is_current(a) || !self.is_current(b) {
+            return false;
+        }
+        let swapped = self.storage.swap(a.index, b.index);
+        #[cfg(feature = "provenance")]
+        if swapped {
+            self.provenance.swap(a.index, b.index);
+        }
+        if swapped && self.deleted.contains(&a.index) != self.deleted.contains(&b.index) {
+            for index in [a.index, b.index] {
+                if !self.deleted.remove(&index) {
+                    self.deleted.insert(index);
                 }
             }
         }
-        None
+        swapped
     }
-}
 
-/// A holder for [`Contextual`] objects.
-///
-/// It is unlikely that you will ever need to instantiate this class,
-/// unless for some reason the [`persian_rug`] attribute macro which
-/// creates [`Context`] implementations is not suitable for your use.
-///
-/// A context object will generally have one table per object type,
-/// and that table does the work of storing, retrieving and iterating
-/// over objects of that type, and the [`Proxy`] objects that refer to
-/// them.
-#[derive(Clone, Debug)]
-pub struct Table<T> {
-    members: BTreeMap<u64, T>,
-    proxies: Vec<Proxy<T>>,
-    next_index: u64,
-}
+    /// Take the items out of this table, along with their proxies,
+    /// in iteration order.
+    ///
+    /// Items marked as deleted are left out. The proxies can be used
+    /// with [`insert_with_handle`](Table::insert_with_handle) to
+    /// rebuild a table with the same handles after the items have
+    /// been processed elsewhere.
+    pub fn into_entries(self) -> Vec<(Proxy<T>, T)> {
+        let deleted = self.deleted;
+        self.storage
+            .into_entries()
+            .into_iter()
+            .filter(|(p, _)| !deleted.contains(&p.index))
+            .collect()
+    }
 
-impl<T> Default for Table<T> {
-    fn default() -> Self {
-        Self {
-            members: Default::default(),
-            proxies: Default::default(),
-            next_index: Default::default(),
-        }
+    /// The storage holding the items of this table.
+    pub fn storage(&self) -> &S {
+        &self.storage
     }
-}
 
-impl<T> Table<T> {
-    /// Create a new table.
-    ///
-    /// Tables are created empty.
-    pub fn new() -> Self {
-        Default::default()
+    /// The allocator choosing the handles of items pushed to this
+    /// table.
+    pub fn handles(&self) -> &A {
+        &self.handles
+    }
+
+    /// Move the allocator on until it offers a handle not in use.
+    fn skip_used_handles(&mut self) {
+        loop {
+            let handle = self.handles.peek();
+            if self.storage.get(handle).is_none() {
+                break;
+            }
+            self.handles.advance();
+            // An allocator which has run out of handles stays on the
+            // last one.
+            if self.handles.peek() == handle {
+                break;
+            }
+        }
     }
 
     /// Insert a new item.
     ///
     /// The return value is a [`Proxy`] that you can store, and later
     /// use to retrieve the stored object from the table.
+    ///
+    /// Panics if the table's [`HandleAllocator`](handles::HandleAllocator)
+    /// has run out of handles.
+    #[track_caller]
     pub fn push(&mut self, value: T) -> Proxy<T> {
-        let ix = self.next_index;
-        self.next_index += 1;
-        self.members.insert(ix, value);
-        let p = Proxy {
-            _marker: Default::default(),
-            index: ix,
-        };
-        self.proxies.push(p);
+        let handle = self.handles.peek();
+        assert!(
+            self.storage.get(handle).is_none(),
+            "table has run out of handles"
+        );
+        let p = self.issue(handle);
+        self.storage.insert(p, value);
+        #[cfg(feature = "provenance")]
+        self.provenance.record(p.index);
+        #[cfg(feature = "observe")]
+        self.observers.added(&p, self.storage.get(p.index).unwrap());
+        self.handles.advance();
+        self.skip_used_handles();
         p
     }
 
+    /// Insert a new item under a chosen handle.
+    ///
+    /// This makes it possible to rebuild a table with exactly the
+    /// proxies it had before, for example when replaying a log of
+    /// changes in another process. The handle is passed to the
+    /// table's [`HandleAllocator::reserve`](handles::HandleAllocator::reserve),
+    /// so that with the default allocator, items pushed afterwards
+    /// receive handles after the largest one used so far.
+    ///
+    /// Returns an error, leaving the table unchanged, if an item is
+    /// already stored under `handle`.
+    #[track_caller]
+    pub fn insert_with_handle(&mut self, handle: u64, value: T) -> Result<Proxy<T>, HandleInUse> {
+        if self.storage.get(handle).is_some() {
+            return Err(HandleInUse(handle));
+        }
+        let p = self.issue(handle);
+        self.storage.insert(p, value);
+        #[cfg(feature = "provenance")]
+        self.provenance.record(p.index);
+        #[cfg(feature = "observe")]
+        self.observers.added(&p, self.storage.get(p.index).unwrap());
+        self.handles.reserve(handle);
+        self.skip_used_handles();
+        Ok(p)
+    }
+
+    /// Insert a new item under the handle and generation of `p`, so
+    /// that `p` itself retrieves it.
+    pub(crate) fn insert_with_proxy(
+        &mut self,
+        p: Proxy<T>,
+        value: T,
+    ) -> Result<Proxy<T>, HandleInUse> {
+        if self.storage.get(p.index).is_some() {
+            return Err(HandleInUse(p.index));
+        }
+        if p.generation == 0 {
+            self.generations.remove(&p.index);
+        } else {
+            self.generations.insert(p.index, p.generation);
+        }
+        self.insert_with_handle(p.index, value)
+    }
+
+    /// Set aside the [`Proxy`] which the next item pushed would
+    /// receive, so that other items can refer to it before it is
+    /// stored.
+    ///
+    /// The handle is not issued to any item pushed afterwards. Store
+    /// the item with [`insert_with_handle`](Table::insert_with_handle),
+    /// which gives it this proxy, or hand the handle back with
+    /// [`release_proxy`](Table::release_proxy) if it is not needed.
+    ///
+    /// Panics if the table's [`HandleAllocator`](handles::HandleAllocator)
+    /// has run out of handles.
+    ///
+    /// ```rust
+    /// use persian_rug::handles::Random;
+    /// use persian_rug::storage::MapStorage;
+    /// use persian_rug::{Proxy, Table};
+    ///
+    /// struct Node {
+    ///     next: Proxy<Node>,
+    /// }
+    ///
+    /// let mut table = Table::<Node, MapStorage<Node>, _>::with_handles(Random::new(7));
+    /// let first = table.reserve_proxy();
+    /// let second = table.push(Node { next: first });
+    /// assert_ne!(second, first);
+    /// assert_eq!(table.insert_with_handle(first.handle(), Node { next: second }), Ok(first));
+    /// assert_eq!(table.get(&table.get(&first).unwrap().next).unwrap().next, first);
+    /// ```
+    #[track_caller]
+    pub fn reserve_proxy(&mut self) -> Proxy<T> {
+        let handle = self.handles.peek();
+        self.handles.advance();
+        // An allocator which has run out of handles stays on the last
+        // one, and would issue it again.
+        assert!(
+            self.storage.get(handle).is_none() && self.handles.peek() != handle,
+            "table has run out of handles"
+        );
+        self.skip_used_handles();
+        self.issue(handle)
+    }
+
+    /// Hand back a [`Proxy`] set aside with
+    /// [`reserve_proxy`](Table::reserve_proxy) which will not be used.
+    ///
+    /// The handle is passed to the table's
+    /// [`HandleAllocator::release`](handles::HandleAllocator::release),
+    /// so an allocator which reuses handles can issue it again. Does
+    /// nothing if an item is stored under the handle.
+    pub fn release_proxy(&mut self, p: &Proxy<T>) {
+        if self.storage.get(p.index).is_none() {
+            self.handles.release(p.index);
+        }
+    }
+
     /// Retrieve a previously stored item.
     ///
     /// Note that the return value is an [`Option`], because not all
@@ -1065,7 +3522,12 @@ impl<T> Table<T> {
     /// attribute macro unwrap this return value, causing a panic on
     /// failure.
     pub fn get(&self, p: &Proxy<T>) -> Option<&T> {
-        self.members.get(&p.index)
+        #[cfg(feature = "profiling")]
+        self.counters.get();
+        if !self.is_current(p) {
+            return None;
+        }
+        self.storage.get(p.index)
     }
 
     /// Retrieve a previously stored item mutably.
@@ -1077,71 +3539,682 @@ impl<T> Table<T> {
     /// attribute macro unwrap this return value, causing a panic on
     /// failure.
     pub fn get_mut(&mut self, p: &Proxy<T>) -> Option<&mut T> {
-        self.members.get_mut(&p.index)
+        #[cfg(feature = "profiling")]
+        self.counters.get_mut();
+        if !self.is_current(p) {
+            return None;
+        }
+        let value = self.storage.get_mut(p.index)?;
+        #[cfg(feature = "observe")]
+        self.observers.modified(p);
+        Some(value)
     }
 
-    /// Iterate over shared references to all stored items.
-    pub fn iter(&self) -> TableIterator<T> {
+    /// Iterate over shared references to all stored items, except
+    /// those marked as deleted.
+    pub fn iter(&self) -> TableIterator<'_, T> {
+        #[cfg(feature = "profiling")]
+        self.counters.iteration();
         TableIterator {
-            iter: self.members.values(),
+            iter: self.storage.entries(),
+            deleted: Some(&self.deleted),
         }
     }
 
-    /// Iterate over mutable references to all stored items.
-    pub fn iter_mut(&mut self) -> TableMutIterator<T> {
+    /// Iterate over mutable references to all stored items, except
+    /// those marked as deleted.
+    pub fn iter_mut(&mut self) -> TableMutIterator<'_, T> {
+        #[cfg(feature = "profiling")]
+        self.counters.iteration();
         TableMutIterator {
-            iter: self.members.values_mut(),
+            iter: self.storage.entries_mut(),
+            deleted: Some(&self.deleted),
+            #[cfg(feature = "observe")]
+            observers: Some(&mut self.observers),
         }
     }
 
-    /// Iterate over proxies for all stored items.
+    /// Mark the item stored for a [`Proxy`] as deleted.
+    ///
+    /// Deleted items are kept, so that proxies to them can still be
+    /// resolved with [`get`](Table::get), but are skipped when
+    /// iterating over the table, and so over its context. This is a
+    /// soft delete: the item can be brought back with
+    /// [`undelete`](Table::undelete), and all items can still be
+    /// iterated over through [`include_deleted`](Table::include_deleted).
+    ///
+    /// Returns `false`, leaving the table unchanged, if the item is not
+    /// stored or is already marked.
+    ///
+    /// ```rust
+    /// use persian_rug::Table;
+    ///
+    /// let mut table = Table::<&str>::new();
+    /// let kept = table.push("kept");
+    /// let dropped = table.push("dropped");
+    ///
+    /// assert!(table.mark_deleted(&dropped));
+    /// assert_eq!(table.iter().collect::<Vec<_>>(), vec![&"kept"]);
+    /// assert_eq!(table.get(&dropped), Some(&"dropped"));
+    /// assert_eq!(table.include_deleted().iter().count(), 2);
+    ///
+    /// assert!(table.undelete(&dropped));
+    /// assert_eq!(table.iter_proxies().collect::<Vec<_>>(), vec![&kept, &dropped]);
+    /// ```
+    ///
+    /// Deletion marks follow their items when they are swapped, and
+    /// are copied when the table is cloned, but they are not kept when
+    /// a table is archived, encoded or diffed.
+    pub fn mark_deleted(&mut self, p: &Proxy<T>) -> bool {
+        self.contains(p) && self.deleted.insert(p.index)
+    }
+
+    /// Clear the deletion mark of the item stored for a [`Proxy`].
+    ///
+    /// Returns `false` if the item was not marked as deleted.
+    pub fn undelete(&mut self, p: &Proxy<T>) -> bool {
+        self.is_current(p) && self.deleted.remove(&p.index)
+    }
+
+    /// Check whether the item stored for a [`Proxy`] is marked as
+    /// deleted.
+    pub fn is_deleted(&self, p: &Proxy<T>) -> bool {
+        self.is_current(p) && self.deleted.contains(&p.index)
+    }
+
+    /// Iterate over proxies for the items marked as deleted.
+    pub fn deleted_proxies(&self) -> impl Iterator<Item = Proxy<T>> + '_ {
+        self.deleted.iter().map(|index| self.issue(*index))
+    }
+
+    /// Delete the item stored for a [`Proxy`].
+    ///
+    /// Unlike [`mark_deleted`](Table::mark_deleted), this drops the
+    /// item, and cannot be undone. Every proxy for it becomes stale:
+    /// looking it up fails, as it would for a proxy from another
+    /// table, and if its handle is ever used again, it is used with a
+    /// new [generation](Proxy::generation), so that the stale proxies
+    /// do not resolve to the new item.
+    ///
+    /// Returns `false`, leaving the table unchanged, if the item is
+    /// not stored.
+    ///
+    /// ```rust
+    /// use persian_rug::handles::Recycling;
+    /// use persian_rug::storage::MapStorage;
+    /// use persian_rug::Table;
+    ///
+    /// let mut table = Table::<&str, MapStorage<&str>, _>::with_handles(Recycling::new());
+    /// let kept = table.push("kept");
+    /// let dropped = table.push("dropped");
+    ///
+    /// assert!(table.delete(&dropped));
+    /// assert_eq!(table.get(&dropped), None);
+    /// assert!(!table.delete(&dropped));
+    ///
+    /// let reused = table.push("reused");
+    /// assert_eq!(reused.handle(), dropped.handle());
+    /// assert_eq!(reused.generation(), 1);
+    /// assert_eq!(table.get(&dropped), None);
+    /// assert_eq!(table.iter().collect::<Vec<_>>(), vec![&"kept", &"reused"]);
+    /// # let _ = kept;
+    /// ```
+    ///
+    /// Links to the item held by other objects are not changed, and
+    /// become stale along with every other proxy for it. Generations
+    /// are copied when the table is cloned, but are not kept when a
+    /// table is archived, encoded or diffed.
+    pub fn delete(&mut self, p: &Proxy<T>) -> bool {
+        self.remove(p).is_some()
+    }
+
+    /// Take the item stored for a [`Proxy`] out of the table.
+    ///
+    /// This is [`delete`](Table::delete), but returns the item
+    /// instead of dropping it, so that it can be moved somewhere else.
+    /// The proxies invalidated are exactly those for the item removed:
+    /// every copy of `p`, and any other proxy with the same handle and
+    /// generation. They stay invalid for good, even if the item is
+    /// stored again, since storing it issues a new proxy. Proxies for
+    /// the other items are unaffected.
+    ///
+    /// Returns [`None`], leaving the table unchanged, if the item is
+    /// not stored.
+    ///
+    /// ```rust
+    /// use persian_rug::Table;
+    ///
+    /// let mut inbox = Table::<String>::new();
+    /// let mut archive = Table::<String>::new();
+    /// let message = inbox.push("hello".to_string());
+    ///
+    /// let moved = archive.push(inbox.remove(&message).unwrap());
+    /// assert!(inbox.is_empty());
+    /// assert_eq!(inbox.remove(&message), None);
+    /// assert_eq!(archive.get(&moved).unwrap(), "hello");
+    /// ```
+    pub fn remove(&mut self, p: &Proxy<T>) -> Option<T> {
+        if !self.is_current(p) {
+            return None;
+        }
+        #[cfg(feature = "profiling")]
+        self.counters.get_mut();
+        let value = self.storage.remove(p.index)?;
+        #[cfg(feature = "provenance")]
+        self.provenance.remove(p.index);
+        self.deleted.remove(&p.index);
+        self.generations
+            .insert(p.index, p.generation.wrapping_add(1));
+        self.handles.release(p.index);
+        Some(value)
+    }
+
+    /// Keep only the items for which `f` returns `true`, deleting the
+    /// others.
+    ///
+    /// Each item is passed to `f` once, in iteration order, along with
+    /// its proxy, and can be changed as it is checked. Items marked as
+    /// deleted are not passed to `f`, and are kept. The items deleted
+    /// become stale, as for [`delete`](Table::delete).
+    ///
+    /// ```rust
+    /// use persian_rug::Table;
+    ///
+    /// let mut table = Table::<i32>::new();
+    /// for i in 0..6 {
+    ///     table.push(i);
+    /// }
+    /// table.retain(|_, value| {
+    ///     *value *= 10;
+    ///     *value % 20 == 0
+    /// });
+    /// assert_eq!(table.iter().collect::<Vec<_>>(), vec![&0, &20, &40]);
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Proxy<T>, &mut T) -> bool,
+    {
+        #[cfg(feature = "profiling")]
+        self.counters.iteration();
+        let deleted = &self.deleted;
+        let unwanted = self
+            .storage
+            .entries_mut()
+            .filter(|(p, _)| !deleted.contains(&p.index))
+            .filter_map(|(p, value)| (!f(p, value)).then_some(*p))
+            .collect::<Vec<_>>();
+        for p in unwanted.iter().rev() {
+            self.remove(p);
+        }
+    }
+
+    /// Take every item out of the table, along with its proxy, in
+    /// iteration order.
+    ///
+    /// The table is left empty, and every proxy it issued becomes
+    /// stale, as for [`remove`](Table::remove). Items marked as
+    /// deleted are dropped rather than returned.
+    pub fn drain(&mut self) -> Vec<(Proxy<T>, T)> {
+        let deleted = std::mem::take(&mut self.deleted);
+        let proxies = self.storage.entries().map(|(p, _)| *p).collect::<Vec<_>>();
+        // Removing from the back leaves arena storage with nothing to
+        // move into the gaps.
+        let mut res = Vec::with_capacity(proxies.len() - deleted.len());
+        for p in proxies.into_iter().rev() {
+            let value = self.remove(&p).unwrap();
+            if !deleted.contains(&p.index) {
+                res.push((p, value));
+            }
+        }
+        res.reverse();
+        res
+    }
+
+    /// View the table including the items marked as deleted.
+    pub fn include_deleted(&self) -> IncludeDeleted<'_, T, S, A> {
+        IncludeDeleted { table: self }
+    }
+
+    /// Update the stored items in parallel.
+    ///
+    /// The items are split into disjoint chunks of at most `size`
+    /// items, each paired with its proxy, and `f` is called on each
+    /// chunk from a worker thread. The chunks are run on rayon's
+    /// thread pool with the `rayon` feature, and otherwise on a scoped
+    /// thread for each available core. Without the `sync` feature, the
+    /// chunks are run in turn on the calling thread. This suits bulk
+    /// updates where each object can be changed on its own.
+    ///
+    /// ```rust
+    /// use persian_rug::Table;
+    ///
+    /// let mut table = Table::<u64>::new();
+    /// for value in 0..1000 {
+    ///     table.push(value);
+    /// }
+    ///
+    /// table.par_chunks(64, |chunk| {
+    ///     for (_, value) in chunk {
+    ///         **value *= 2;
+    ///     }
+    /// });
+    /// assert_eq!(table.iter().sum::<u64>(), 999_000);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero, or if `f` panics.
+    pub fn par_chunks<F>(&mut self, size: usize, f: F)
+    where
+        T: Send,
+        F: Fn(&mut [(Proxy<T>, &mut T)]) + Sync,
+    {
+        assert!(size > 0, "chunk size must not be zero");
+        #[cfg(feature = "profiling")]
+        self.counters.iteration();
+        let deleted = &self.deleted;
+        let mut entries = self
+            .storage
+            .entries_mut()
+            .filter(|(p, _)| !deleted.contains(&p.index))
+            .map(|(p, value)| (*p, value))
+            .collect::<Vec<_>>();
+        parallel::for_each_chunk(&mut entries, size, &f);
+    }
+
+    /// Iterate over proxies for all stored items, except those marked
+    /// as deleted.
     ///
     /// Note that [`Proxy`] implements [`Copy`] so that although this
     /// returns references, you can cheaply convert them to owned
     /// values as required with the [`copied`][Iterator::copied]
     /// method on [`Iterator`].
-    pub fn iter_proxies(&self) -> TableProxyIterator<T> {
+    pub fn iter_proxies(&self) -> TableProxyIterator<'_, T> {
+        #[cfg(feature = "profiling")]
+        self.counters.iteration();
+        TableProxyIterator {
+            iter: self.storage.entries(),
+            deleted: Some(&self.deleted),
+        }
+    }
+}
+
+/// A view of a [`Table`] which includes the items marked as deleted.
+///
+/// This is returned by [`Table::include_deleted`].
+pub struct IncludeDeleted<'a, T, S, A> {
+    table: &'a Table<T, S, A>,
+}
+
+impl<'a, T, S, A> IncludeDeleted<'a, T, S, A>
+where
+    S: storage::Storage<T>,
+{
+    /// Iterate over shared references to all stored items.
+    pub fn iter(&self) -> TableIterator<'a, T> {
+        #[cfg(feature = "profiling")]
+        self.table.counters.iteration();
+        TableIterator {
+            iter: self.table.storage.entries(),
+            deleted: None,
+        }
+    }
+
+    /// Iterate over proxies for all stored items.
+    pub fn iter_proxies(&self) -> TableProxyIterator<'a, T> {
+        #[cfg(feature = "profiling")]
+        self.table.counters.iteration();
         TableProxyIterator {
-            iter: self.proxies.iter(),
+            iter: self.table.storage.entries(),
+            deleted: None,
+        }
+    }
+}
+
+impl<T, S> Table<T, S>
+where
+    S: storage::Storage<T>,
+{
+    /// Create a new table using the given storage.
+    ///
+    /// The storage must be empty. This is useful when the storage
+    /// cannot be created with [`Default`], for example
+    /// [`ArenaStorage`](storage::ArenaStorage) with an allocator that
+    /// needs configuring.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the storage is not empty.
+    pub fn with_storage(storage: S) -> Self {
+        Self::with_storage_and_handles(storage, Default::default())
+    }
+
+    /// Choose the handle that the next item pushed will receive.
+    ///
+    /// This is available for tables using the default
+    /// [`Sequential`](handles::Sequential) allocator.
+    ///
+    /// Handles are normally issued from zero, so two tables built by
+    /// the same steps issue the same proxies. Starting a table from a
+    /// chosen handle instead keeps the proxies issued by different
+    /// tables apart, while staying reproducible.
+    ///
+    /// Returns an error, leaving the table unchanged, if an item is
+    /// stored under `handle` or a later one, since it would otherwise
+    /// be overwritten by a later push.
+    pub fn set_next_handle(&mut self, handle: u64) -> Result<(), HandleInUse> {
+        if let Some(used) = self
+            .storage
+            .entries()
+            .map(|(p, _)| p.index)
+            .filter(|index| *index >= handle)
+            .min()
+        {
+            return Err(HandleInUse(used));
         }
+        self.handles = handles::Sequential::starting_at(handle);
+        Ok(())
+    }
+
+    /// Set aside a block of `count` consecutive handles, which items
+    /// pushed afterwards will not receive.
+    ///
+    /// This is available for tables using the default
+    /// [`Sequential`](handles::Sequential) allocator.
+    ///
+    /// The proxies for the reserved handles, from
+    /// [`Proxy::from_handle`], can be used before their objects exist,
+    /// so a batch of objects which refer to one another can be built
+    /// all at once, for example by several threads, and stored later
+    /// with [`insert_with_handle`](Table::insert_with_handle).
+    ///
+    /// ```rust
+    /// use persian_rug::{Proxy, Table};
+    ///
+    /// struct Node {
+    ///     next: Proxy<Node>,
+    /// }
+    ///
+    /// let mut table = Table::<Node>::new();
+    /// let handles = table.reserve_handles(3);
+    /// let ring = handles
+    ///     .clone()
+    ///     .map(|handle| Node {
+    ///         next: Proxy::from_handle(handles.start + (handle - handles.start + 1) % 3),
+    ///     })
+    ///     .collect::<Vec<_>>();
+    /// let other = table.push(Node { next: Proxy::from_handle(0) });
+    /// assert_eq!(other.handle(), 3);
+    ///
+    /// for (handle, node) in handles.zip(ring) {
+    ///     table.insert_with_handle(handle, node).unwrap();
+    /// }
+    /// let first = Proxy::from_handle(0);
+    /// let second = table.get(&first).unwrap().next;
+    /// let third = table.get(&second).unwrap().next;
+    /// assert_eq!(table.get(&third).unwrap().next, first);
+    /// ```
+    pub fn reserve_handles(&mut self, count: u64) -> std::ops::Range<u64> {
+        let start = self.handles.next;
+        let end = start
+            .checked_add(count)
+            .expect("reserving handles overflowed");
+        self.handles = handles::Sequential::starting_at(end);
+        start..end
     }
 }
 
 /// An [`Iterator`] over references to [`Contextual`] objects.
 pub struct TableIterator<'a, T> {
-    iter: std::collections::btree_map::Values<'a, u64, T>,
+    iter: storage::Entries<'a, T>,
+    deleted: Option<&'a BTreeSet<u64>>,
 }
 
 impl<'a, T> Iterator for TableIterator<'a, T> {
     type Item = &'a T;
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next()
+        let deleted = self.deleted;
+        self.iter
+            .find(|(p, _)| !is_marked(deleted, p.index))
+            .map(|(_, value)| value)
     }
 }
 
+/// Check whether `index` is among the `deleted` handles, if there are
+/// any.
+fn is_marked(deleted: Option<&BTreeSet<u64>>, index: u64) -> bool {
+    deleted.is_some_and(|deleted| deleted.contains(&index))
+}
+
 /// An [`Iterator`] over references to [`Proxy`] objects for [`Contextual`]
 /// objects.
 pub struct TableProxyIterator<'a, T> {
-    iter: std::slice::Iter<'a, Proxy<T>>,
+    iter: storage::Entries<'a, T>,
+    deleted: Option<&'a BTreeSet<u64>>,
 }
 
 impl<'a, T> Iterator for TableProxyIterator<'a, T> {
     type Item = &'a Proxy<T>;
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next()
+        let deleted = self.deleted;
+        self.iter
+            .find(|(p, _)| !is_marked(deleted, p.index))
+            .map(|(proxy, _)| proxy)
     }
 }
 
 /// An [`Iterator`] over exclusive references to [`Contextual`] objects.
 pub struct TableMutIterator<'a, T> {
-    iter: std::collections::btree_map::ValuesMut<'a, u64, T>,
+    iter: storage::EntriesMut<'a, T>,
+    deleted: Option<&'a BTreeSet<u64>>,
+    #[cfg(feature = "observe")]
+    observers: Option<&'a mut observe::Observers<T>>,
 }
 
 impl<'a, T> Iterator for TableMutIterator<'a, T> {
     type Item = &'a mut T;
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next()
+        let deleted = self.deleted;
+        let (_p, value) = self.iter.find(|(p, _)| !is_marked(deleted, p.index))?;
+        #[cfg(feature = "observe")]
+        if let Some(observers) = self.observers.as_mut() {
+            observers.modified(_p);
+        }
+        Some(value)
     }
 }
 
-pub use persian_rug_derive::{constraints, contextual, persian_rug};
+pub mod algo;
+
+#[cfg(feature = "rkyv")]
+pub mod archive;
+#[cfg(feature = "rkyv")]
+pub use rkyv;
+
+pub mod batch;
+
+#[cfg(feature = "borsh")]
+mod borsh_impls;
+#[cfg(feature = "borsh")]
+pub use borsh;
+#[cfg(feature = "borsh")]
+pub mod record;
+#[cfg(feature = "borsh")]
+pub mod versioned;
+
+pub mod capability;
+
+pub mod chain;
+
+pub mod checked;
+
+pub mod components;
+
+pub mod disjoint;
+
+#[cfg(feature = "serde")]
+mod serde_impls;
+#[cfg(feature = "serde")]
+pub use serde;
+
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "proto")]
+pub mod proto;
+#[cfg(feature = "schemars")]
+pub use schemars;
+#[cfg(feature = "json")]
+pub use serde_json;
+#[cfg(feature = "egui")]
+pub mod inspector;
+#[cfg(feature = "egui")]
+pub use egui;
+
+#[cfg(feature = "serde-diff")]
+mod serde_diff_impls;
+#[cfg(feature = "serde-diff")]
+pub use serde_diff;
+
+pub mod compression;
+
+pub mod csv;
+
+pub mod diagram;
+
+pub mod diff;
+
+pub mod dirty;
+
+#[cfg(feature = "dot")]
+pub mod dot;
+
+#[cfg(feature = "mermaid")]
+pub mod mermaid;
+
+#[cfg(feature = "petgraph")]
+pub mod petgraph;
+
+pub mod extract;
+
+pub mod gc;
+
+pub mod handles;
+
+pub mod history;
+
+pub mod import;
+
+pub mod merge;
+
+#[cfg(feature = "implicit")]
+pub mod implicit;
+
+#[cfg(feature = "async")]
+pub mod stream;
+#[cfg(feature = "async")]
+pub use futures_core;
+
+#[cfg(feature = "profiling")]
+pub mod profiling;
+
+#[cfg(feature = "provenance")]
+pub mod provenance;
+
+#[cfg(feature = "observe")]
+pub mod observe;
+
+pub mod quota;
+
+#[cfg(feature = "search")]
+pub mod search;
+
+pub mod storage;
+
+#[cfg(feature = "sync")]
+pub mod transaction;
+
+pub mod transplant;
+
+mod lock;
+
+mod parallel;
+
+mod query;
+#[doc(hidden)]
+pub use query::__query_with;
+
+mod edges;
+pub use edges::{Edge, EdgeAccessor, EdgeIndex, EdgeIndexIterator, EdgeIterator};
+
+mod hierarchy;
+pub use hierarchy::{
+    Ancestors, ChildIterator, HierarchyAccessor, HierarchyMutator, ReparentError, Subtree, TreeNode,
+};
+
+mod links;
+pub use links::{AnyProxy, Links, Relink};
+
+pub mod isomorphism;
+
+pub mod referrers;
+
+pub mod resolve;
+pub use resolve::{Absorb, Resolve};
+
+pub mod rewrite;
+
+pub mod schema;
+
+pub mod visit;
+
+mod cursor;
+pub use cursor::Cursor;
+
+mod owned_iter;
+pub use owned_iter::{OwnedTableIterator, OwnedTableProxyIterator};
+
+mod sandbox;
+pub use sandbox::Sandbox;
+
+mod snapshot;
+pub use snapshot::Snapshot;
+
+mod side_table;
+pub use side_table::{SideTable, SideTableIterator, SideTableMutIterator};
+
+mod tags;
+pub use tags::{TagLookup, TaggedIterator, Tags};
+
+mod names;
+pub use names::Names;
+
+mod seeding;
+pub use seeding::{seed, seed_with_rng, SeedRng};
+
+mod sampling;
+pub use sampling::{sample_weighted, WeightedSampler};
+
+#[cfg(feature = "sync")]
+mod static_rug;
+#[cfg(feature = "sync")]
+pub use static_rug::StaticRug;
+
+mod local_rug;
+pub use local_rug::LocalRug;
+
+#[cfg(feature = "sync")]
+mod rcu;
+#[cfg(feature = "sync")]
+pub use rcu::Rcu;
+
+mod proxy_queue;
+pub use proxy_queue::ProxyQueue;
+
+mod resolve_iter;
+pub use resolve_iter::{ProxyItem, ResolveEntries, ResolveIter, ResolveProxies};
+
+pub mod testing;
+
+pub use persian_rug_derive::{
+    constraints, contextual, persian_rug, Absorb, Links, Resolve, Transplant,
+};
@@ -0,0 +1,331 @@
+use std::any::TypeId;
+use std::collections::{BTreeSet, VecDeque};
+
+use crate::{Edge, Proxy};
+
+/// A [`Proxy`] whose type is only known at runtime.
+///
+/// This is useful where proxies to objects of different types need
+/// to be handled together, for example when listing all the objects
+/// which link to some other object. The original proxy can be
+/// recovered with [`downcast`](AnyProxy::downcast).
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, AnyProxy, Context};
+///
+/// #[contextual(Rug)]
+/// struct Foo {}
+///
+/// #[contextual(Rug)]
+/// struct Bar {}
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Foo, #[table] Bar);
+///
+/// let mut r = Rug(Default::default(), Default::default());
+/// let foo = r.add(Foo {});
+/// let any = AnyProxy::from(foo);
+///
+/// assert!(any.is::<Foo>());
+/// assert_eq!(any.downcast::<Foo>(), Some(foo));
+/// assert_eq!(any.downcast::<Bar>(), None);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AnyProxy {
+    type_id: TypeId,
+    index: u64,
+    generation: u32,
+    type_name: &'static str,
+}
+
+impl AnyProxy {
+    /// Erase the type of a proxy.
+    pub fn new<T: 'static>(p: Proxy<T>) -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            index: p.index,
+            generation: p.generation,
+            type_name: std::any::type_name::<T>(),
+        }
+    }
+
+    /// Check whether this is a proxy for a `T`.
+    pub fn is<T: 'static>(&self) -> bool {
+        self.type_id == TypeId::of::<T>()
+    }
+
+    /// Recover the original proxy, if it was for a `T`.
+    pub fn downcast<T: 'static>(&self) -> Option<Proxy<T>> {
+        self.is::<T>()
+            .then_some(Proxy::with_generation(self.index, self.generation))
+    }
+
+    /// The index of the object this is a proxy for.
+    pub(crate) fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// A proxy of the same type, for a different object.
+    pub(crate) fn with_index(&self, index: u64) -> Self {
+        Self {
+            index,
+            generation: 0,
+            ..*self
+        }
+    }
+
+    /// The [`TypeId`] of the type this is a proxy for.
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// The name of the type this is a proxy for, as given by
+    /// [`std::any::type_name`].
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+impl<T: 'static> From<Proxy<T>> for AnyProxy {
+    fn from(p: Proxy<T>) -> Self {
+        Self::new(p)
+    }
+}
+
+impl<T: 'static> PartialEq<Proxy<T>> for AnyProxy {
+    fn eq(&self, other: &Proxy<T>) -> bool {
+        self.is::<T>() && self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl std::fmt::Debug for AnyProxy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AnyProxy<{}>({})", self.type_name, self.index)
+    }
+}
+
+/// A value which holds links to objects in a context.
+///
+/// This enumerates the proxies held by a value. It is implemented for
+/// [`Proxy`] itself, and for common containers of values that
+/// implement it.
+///
+/// For a type annotated with [`contextual`](crate::contextual), this
+/// can be implemented by marking the fields which hold proxies with
+/// `#[link]`:
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, AnyProxy, Context, Links, Proxy};
+///
+/// #[contextual(Rug)]
+/// struct Foo {
+///   #[link]
+///   parent: Option<Proxy<Foo>>,
+///   #[link]
+///   children: Vec<Proxy<Foo>>,
+///   name: String,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Foo);
+///
+/// let mut r = Rug(Default::default());
+/// let root = r.add(Foo { parent: None, children: Vec::new(), name: "root".to_string() });
+/// let child = r.add(Foo { parent: Some(root), children: Vec::new(), name: "child".to_string() });
+/// r.get_mut(&root).children.push(child);
+///
+/// let mut links = Vec::new();
+/// r.get(&root).for_each_link(&mut |p| links.push(p));
+/// assert_eq!(links, vec![AnyProxy::from(child)]);
+/// ```
+///
+/// Alternatively, `#[derive(Links)]` implements both this and
+/// [`Relink`] from every field whose type holds proxies, without
+/// marking them, and also supports enums; see
+/// [`Links`](macro@crate::Links).
+///
+/// Types which hold no links can implement this trait with an empty
+/// body, since the method does nothing by default.
+pub trait Links {
+    /// Pass each proxy held by this value to `f`.
+    fn for_each_link(&self, f: &mut dyn FnMut(AnyProxy)) {
+        let _ = f;
+    }
+
+    /// Pass each proxy held by this value to `f`, along with the name
+    /// of the field holding it.
+    ///
+    /// By default, every link is reported with an empty name. The
+    /// [`contextual`](crate::contextual) macro reports the name of
+    /// each field marked `#[link]`, or its index for tuple structs.
+    fn for_each_field_link(&self, f: &mut dyn FnMut(&str, AnyProxy)) {
+        self.for_each_link(&mut |p| f("", p))
+    }
+}
+
+impl<T: 'static> Links for Proxy<T> {
+    fn for_each_link(&self, f: &mut dyn FnMut(AnyProxy)) {
+        f(AnyProxy::new(*self))
+    }
+}
+
+impl<L: Links + ?Sized> Links for &L {
+    fn for_each_link(&self, f: &mut dyn FnMut(AnyProxy)) {
+        (**self).for_each_link(f)
+    }
+}
+
+impl<L: Links + ?Sized> Links for Box<L> {
+    fn for_each_link(&self, f: &mut dyn FnMut(AnyProxy)) {
+        (**self).for_each_link(f)
+    }
+}
+
+impl<L: Links> Links for Option<L> {
+    fn for_each_link(&self, f: &mut dyn FnMut(AnyProxy)) {
+        if let Some(l) = self {
+            l.for_each_link(f)
+        }
+    }
+}
+
+impl<L: Links> Links for [L] {
+    fn for_each_link(&self, f: &mut dyn FnMut(AnyProxy)) {
+        for l in self {
+            l.for_each_link(f)
+        }
+    }
+}
+
+impl<L: Links, const N: usize> Links for [L; N] {
+    fn for_each_link(&self, f: &mut dyn FnMut(AnyProxy)) {
+        self.as_slice().for_each_link(f)
+    }
+}
+
+impl<L: Links> Links for Vec<L> {
+    fn for_each_link(&self, f: &mut dyn FnMut(AnyProxy)) {
+        self.as_slice().for_each_link(f)
+    }
+}
+
+impl<L: Links> Links for VecDeque<L> {
+    fn for_each_link(&self, f: &mut dyn FnMut(AnyProxy)) {
+        for l in self {
+            l.for_each_link(f)
+        }
+    }
+}
+
+impl<L: Links> Links for BTreeSet<L> {
+    fn for_each_link(&self, f: &mut dyn FnMut(AnyProxy)) {
+        for l in self {
+            l.for_each_link(f)
+        }
+    }
+}
+
+impl<A: 'static, B: 'static, P> Links for Edge<A, B, P> {
+    fn for_each_link(&self, f: &mut dyn FnMut(AnyProxy)) {
+        self.from.for_each_link(f);
+        self.to.for_each_link(f);
+    }
+
+    fn for_each_field_link(&self, f: &mut dyn FnMut(&str, AnyProxy)) {
+        f("from", AnyProxy::new(self.from));
+        f("to", AnyProxy::new(self.to));
+    }
+}
+
+/// A value whose links can be rewritten.
+///
+/// This is the mutable counterpart of [`Links`]: it passes each proxy
+/// held by a value to a function, and replaces it with the proxy the
+/// function returns. It is used by operations which renumber the
+/// objects in a context, and is implemented for the same types as
+/// [`Links`], except for shared references.
+///
+/// For a type annotated with [`contextual`](crate::contextual), this
+/// is implemented alongside [`Links`] for the fields marked with
+/// `#[link]`. Types which hold no links can implement it with an
+/// empty body.
+pub trait Relink: Links {
+    /// Replace each proxy held by this value with the result of `f`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` returns a proxy of a different type to the one
+    /// it was given.
+    fn relink(&mut self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) {
+        let _ = f;
+    }
+}
+
+impl<T: 'static> Relink for Proxy<T> {
+    fn relink(&mut self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) {
+        *self = f(AnyProxy::new(*self))
+            .downcast()
+            .expect("relinked to a proxy of a different type");
+    }
+}
+
+impl<L: Relink + ?Sized> Relink for Box<L> {
+    fn relink(&mut self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) {
+        (**self).relink(f)
+    }
+}
+
+impl<L: Relink> Relink for Option<L> {
+    fn relink(&mut self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) {
+        if let Some(l) = self {
+            l.relink(f)
+        }
+    }
+}
+
+impl<L: Relink> Relink for [L] {
+    fn relink(&mut self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) {
+        for l in self {
+            l.relink(f)
+        }
+    }
+}
+
+impl<L: Relink, const N: usize> Relink for [L; N] {
+    fn relink(&mut self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) {
+        self.as_mut_slice().relink(f)
+    }
+}
+
+impl<L: Relink> Relink for Vec<L> {
+    fn relink(&mut self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) {
+        self.as_mut_slice().relink(f)
+    }
+}
+
+impl<L: Relink> Relink for VecDeque<L> {
+    fn relink(&mut self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) {
+        for l in self {
+            l.relink(f)
+        }
+    }
+}
+
+impl<L: Relink + Ord> Relink for BTreeSet<L> {
+    fn relink(&mut self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) {
+        *self = std::mem::take(self)
+            .into_iter()
+            .map(|mut l| {
+                l.relink(f);
+                l
+            })
+            .collect();
+    }
+}
+
+impl<A: 'static, B: 'static, P> Relink for Edge<A, B, P> {
+    fn relink(&mut self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) {
+        self.from.relink(f);
+        self.to.relink(f);
+    }
+}
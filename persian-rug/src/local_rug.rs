@@ -0,0 +1,145 @@
+use std::cell::{Ref, RefCell, RefMut};
+use std::rc::Rc;
+
+use crate::{Context, Contextual, Owner, Proxy};
+
+/// A context shared between the parts of a single-threaded program.
+///
+/// This is the counterpart of [`StaticRug`](crate::StaticRug) for
+/// programs that never use their context from more than one thread,
+/// such as those built for `wasm32` to run in a browser. It takes no
+/// locks, and is available without the `sync` feature. Cloning a
+/// `LocalRug` gives another handle to the same context, so it can be
+/// handed to each callback which needs it, or kept in a
+/// [`thread_local!`] for the whole program to reach.
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, LocalRug, Proxy};
+///
+/// #[contextual(Rug)]
+/// struct Task {
+///   title: &'static str,
+///   done: bool,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Task);
+///
+/// let rug = LocalRug::new(Rug(Default::default()));
+/// let on_click = {
+///     let rug = rug.clone();
+///     move |t: &Proxy<Task>| rug.update(t, |task| task.done = true)
+/// };
+///
+/// let t = rug.add(Task { title: "write docs", done: false });
+/// rug.add(Task { title: "write tests", done: false });
+/// on_click(&t);
+///
+/// assert!(rug.get_with(&t, |task| task.done));
+/// let remaining = rug.with(|r| r.get_iter::<Task>().filter(|task| !task.done).count());
+/// assert_eq!(remaining, 1);
+/// ```
+///
+/// For longer sections of code, the context can be borrowed
+/// explicitly with [`read`](LocalRug::read) or
+/// [`write`](LocalRug::write). A reference to the contents of the
+/// read borrow is an [`Accessor`](crate::Accessor), and the write
+/// borrow is itself a [`Mutator`](crate::Mutator). As with any
+/// [`RefCell`], the context cannot be written while it is borrowed
+/// elsewhere.
+pub struct LocalRug<C> {
+    cell: Rc<RefCell<C>>,
+}
+
+impl<C> LocalRug<C> {
+    /// Share `context` within the current thread.
+    pub fn new(context: C) -> Self {
+        Self {
+            cell: Rc::new(RefCell::new(context)),
+        }
+    }
+
+    /// Take the context back, if this is the only handle to it.
+    pub fn into_inner(self) -> Result<C, Self> {
+        Rc::try_unwrap(self.cell)
+            .map(RefCell::into_inner)
+            .map_err(|cell| Self { cell })
+    }
+
+    /// Check whether two handles share the same context.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.cell, &other.cell)
+    }
+}
+
+impl<C> Clone for LocalRug<C> {
+    fn clone(&self) -> Self {
+        Self {
+            cell: self.cell.clone(),
+        }
+    }
+}
+
+impl<C: Default> Default for LocalRug<C> {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<C: Context> LocalRug<C> {
+    /// Borrow the context for reading.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context is borrowed for writing.
+    pub fn read(&self) -> Ref<'_, C> {
+        self.cell.borrow()
+    }
+
+    /// Borrow the context for writing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context is already borrowed.
+    pub fn write(&self) -> RefMut<'_, C> {
+        self.cell.borrow_mut()
+    }
+
+    /// Call `f` with the context borrowed for reading.
+    pub fn with<R>(&self, f: impl FnOnce(&C) -> R) -> R {
+        f(&self.read())
+    }
+
+    /// Call `f` with the context borrowed for writing.
+    pub fn with_mut<R>(&self, f: impl FnOnce(&mut C) -> R) -> R {
+        f(&mut self.write())
+    }
+
+    /// Add an object to the context.
+    #[track_caller]
+    pub fn add<T>(&self, value: T) -> Proxy<T>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        Owner::add(&mut *self.write(), value)
+    }
+
+    /// Call `f` with an object from the context.
+    pub fn get_with<T, R>(&self, what: &Proxy<T>, f: impl FnOnce(&T) -> R) -> R
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        f(Owner::get(&*self.read(), what))
+    }
+
+    /// Call `f` to modify an object in the context.
+    pub fn update<T, R>(&self, what: &Proxy<T>, f: impl FnOnce(&mut T) -> R) -> R
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        f(Owner::get_mut(&mut *self.write(), what))
+    }
+}
@@ -0,0 +1,43 @@
+//! The lock guarding the indexes kept by some storage types.
+//!
+//! With the `sync` feature this is a [`Mutex`](std::sync::Mutex),
+//! whose poisoning is ignored, since the indexes are rebuilt from the
+//! table when stale. Without it, tables are only used from one
+//! thread, and a [`RefCell`](std::cell::RefCell) is enough.
+
+use std::ops::DerefMut;
+
+#[cfg(feature = "sync")]
+pub(crate) struct Lock<T>(std::sync::Mutex<T>);
+
+#[cfg(not(feature = "sync"))]
+pub(crate) struct Lock<T>(std::cell::RefCell<T>);
+
+impl<T> Lock<T> {
+    pub(crate) fn new(value: T) -> Self {
+        #[cfg(feature = "sync")]
+        return Self(std::sync::Mutex::new(value));
+        #[cfg(not(feature = "sync"))]
+        return Self(std::cell::RefCell::new(value));
+    }
+
+    pub(crate) fn lock(&self) -> impl DerefMut<Target = T> + '_ {
+        #[cfg(feature = "sync")]
+        return self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        #[cfg(not(feature = "sync"))]
+        return self.0.borrow_mut();
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        #[cfg(feature = "sync")]
+        return self
+            .0
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        #[cfg(not(feature = "sync"))]
+        return self.0.get_mut();
+    }
+}
@@ -0,0 +1,153 @@
+//! Combining two contexts into one.
+//!
+//! Contexts built independently, for example by separate threads or
+//! by loading separate files, can be combined with
+//! [`Context::absorb`], which moves every
+//! object of one context into another. The moved objects are given
+//! new handles after those of the objects already present, and the
+//! links they hold are rewritten with [`Relink`] to match, so that the
+//! two graphs sit side by side afterwards. The objects are moved
+//! rather than copied, so they do not need to implement [`Clone`].
+//!
+//! The tables to move are listed by an implementation of [`Merge`],
+//! which the [`persian_rug`](crate::persian_rug) macro generates with
+//! its `merge` option:
+//!
+//! ```rust
+//! use persian_rug::{contextual, persian_rug, Context, Links, Proxy};
+//!
+//! #[derive(Links)]
+//! #[contextual(Rug)]
+//! struct Step {
+//!   name: String,
+//!   after: Option<Proxy<Step>>,
+//! }
+//!
+//! #[persian_rug(merge)]
+//! struct Rug(#[table] Step);
+//!
+//! let mut build = Rug(Default::default());
+//! let compile = build.add(Step { name: "compile".to_string(), after: None });
+//! build.add(Step { name: "link".to_string(), after: Some(compile) });
+//!
+//! let mut release = Rug(Default::default());
+//! let tag = release.add(Step { name: "tag".to_string(), after: None });
+//! let upload = release.add(Step { name: "upload".to_string(), after: Some(tag) });
+//!
+//! let moved = build.absorb(release);
+//! let upload = moved.get(&upload).unwrap();
+//! let after = build.get(&upload).after.unwrap();
+//! assert_eq!(build.get(&after).name, "tag");
+//! assert_eq!(build.get_iter::<Step>().count(), 4);
+//! ```
+//!
+//! Only links reported by [`Links`](crate::Links) are rewritten, so
+//! every field holding a proxy must either be found by
+//! `#[derive(Links)]`, as above, or be marked `#[link]`. Links to
+//! objects which had been deleted from the absorbed context are left
+//! as they are.
+
+use std::any::TypeId;
+use std::collections::BTreeMap;
+
+use crate::{AnyProxy, Context, Contextual, Owner, Proxy, Relink};
+
+/// A context which can absorb the objects of another.
+///
+/// This is normally implemented with the `merge` option of the
+/// [`persian_rug`](crate::persian_rug) macro.
+pub trait Merge: Context {
+    /// Register each table of the context with `tables`.
+    fn describe(tables: &mut MergeTables<Self>)
+    where
+        Self: Sized;
+}
+
+type Move<C> = fn(&mut C, &mut C, &mut BTreeMap<AnyProxy, AnyProxy>);
+type Fixup<C> = fn(&mut C, &AnyProxy, &BTreeMap<AnyProxy, AnyProxy>);
+
+/// The tables taking part in a merge.
+///
+/// This is passed to [`Merge::describe`].
+pub struct MergeTables<C> {
+    moves: Vec<Move<C>>,
+    fixup: BTreeMap<TypeId, Fixup<C>>,
+}
+
+impl<C: Context> MergeTables<C> {
+    /// Include the table of objects of type `T`.
+    pub fn table<T>(&mut self)
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + Relink + 'static,
+    {
+        self.moves.push(move_table::<C, T>);
+        self.fixup.insert(TypeId::of::<T>(), fixup::<C, T>);
+    }
+}
+
+fn move_table<C, T>(into: &mut C, from: &mut C, proxies: &mut BTreeMap<AnyProxy, AnyProxy>)
+where
+    C: Owner<T>,
+    T: Contextual<Context = C> + 'static,
+{
+    for (p, value) in Owner::<T>::drain(from) {
+        let to = Owner::add(into, value);
+        proxies.insert(AnyProxy::new(p), AnyProxy::new(to));
+    }
+}
+
+fn fixup<C, T>(into: &mut C, p: &AnyProxy, proxies: &BTreeMap<AnyProxy, AnyProxy>)
+where
+    C: Owner<T>,
+    T: Contextual<Context = C> + Relink + 'static,
+{
+    let p: Proxy<T> = p.downcast().unwrap();
+    Owner::get_mut(into, &p).relink(&mut |target| proxies.get(&target).copied().unwrap_or(target));
+}
+
+/// The result of [`Context::absorb`].
+///
+/// This maps the proxy each object had in the absorbed context to
+/// its proxy in the context which absorbed it.
+#[derive(Clone, Debug, Default)]
+pub struct Merged {
+    proxies: BTreeMap<AnyProxy, AnyProxy>,
+}
+
+impl Merged {
+    /// The new proxy for the object which was `p` in the absorbed
+    /// context, or [`None`] if there was no such object.
+    pub fn get<T: 'static>(&self, p: &Proxy<T>) -> Option<Proxy<T>> {
+        self.proxies
+            .get(&AnyProxy::new(*p))
+            .and_then(|moved| moved.downcast())
+    }
+
+    /// The number of objects moved.
+    pub fn len(&self) -> usize {
+        self.proxies.len()
+    }
+
+    /// Check whether no objects were moved.
+    pub fn is_empty(&self) -> bool {
+        self.proxies.is_empty()
+    }
+}
+
+pub(crate) fn absorb<C: Merge>(context: &mut C, mut other: C) -> Merged {
+    let mut tables = MergeTables {
+        moves: Vec::new(),
+        fixup: BTreeMap::new(),
+    };
+    C::describe(&mut tables);
+
+    let mut proxies = BTreeMap::new();
+    for move_table in tables.moves.iter() {
+        move_table(context, &mut other, &mut proxies);
+    }
+    for moved in proxies.values() {
+        (tables.fixup[&moved.type_id()])(context, moved, &proxies);
+    }
+    Merged { proxies }
+}
@@ -0,0 +1,88 @@
+//! Rendering a context as a [Mermaid](https://mermaid.js.org)
+//! flowchart.
+//!
+//! This module is available with the `mermaid` feature. It writes the
+//! graph collected by a [`Diagram`] as a `graph TD` flowchart, with a
+//! node for each object and an edge, labelled with the name of the
+//! field, for each link between them. Mermaid is rendered by many
+//! documentation tools and issue trackers, so the output can be
+//! pasted straight into a fenced `mermaid` code block.
+//!
+//! ```rust
+//! use persian_rug::diagram::Labels;
+//! use persian_rug::{contextual, mermaid, persian_rug, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Person {
+//!   name: String,
+//!   #[link]
+//!   manager: Option<Proxy<Person>>,
+//! }
+//!
+//! #[persian_rug(diagram)]
+//! struct Rug(#[table] Person);
+//!
+//! let mut r = Rug(Default::default());
+//! let boss = r.add(Person { name: "Alice".to_string(), manager: None });
+//! r.add(Person { name: "Bob".to_string(), manager: Some(boss) });
+//!
+//! let labels = Labels::new().label(|p: &Person| p.name.clone());
+//! assert_eq!(
+//!     mermaid::to_string(&r, &labels),
+//!     concat!(
+//!         "graph TD\n",
+//!         "  n0_0[\"Alice\"]\n",
+//!         "  n0_1[\"Bob\"]\n",
+//!         "  n0_1 -->|\"manager\"| n0_0\n",
+//!     )
+//! );
+//! ```
+
+use std::io::{self, Write};
+
+use crate::diagram::{Diagram, Graph, Labels};
+
+/// Write `context` to `out` as a Mermaid flowchart.
+pub fn write<C: Diagram, W: Write>(context: &C, labels: &Labels, mut out: W) -> io::Result<()> {
+    let graph = Graph::new(context, labels);
+    writeln!(out, "graph TD")?;
+    for node in graph.nodes.iter() {
+        writeln!(out, "  {}[{}]", graph.id(&node.proxy), quote(&node.label))?;
+    }
+    for edge in graph.edges.iter() {
+        writeln!(
+            out,
+            "  {} -->|{}| {}",
+            graph.id(&edge.from),
+            quote(&edge.field),
+            graph.id(&edge.to)
+        )?;
+    }
+    out.flush()
+}
+
+/// Render `context` as a Mermaid flowchart.
+pub fn to_string<C: Diagram>(context: &C, labels: &Labels) -> String {
+    let mut out = Vec::new();
+    write(context, labels, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+/// Quote `value` as a Mermaid string.
+///
+/// Mermaid has no escape character inside strings, so quotes are
+/// written as entity codes, and line breaks as `<br>`.
+fn quote(value: &str) -> String {
+    let mut res = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => res.push_str("#quot;"),
+            '#' => res.push_str("#35;"),
+            '\n' => res.push_str("<br>"),
+            '\r' => {}
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use crate::{AnyProxy, Proxy};
+
+/// A registry of well-known objects, by name.
+///
+/// Most applications have a few objects that many parts of the
+/// program need to find: the root of a tree, the active
+/// configuration, the current user. Rather than passing their proxies
+/// through every constructor, they can be registered under a name
+/// and looked up wherever they are needed. A name refers to one
+/// object, of any type, and lookups say which type they expect.
+///
+/// A registry is usually kept as a `#[resource]` field of the context
+/// it describes, so that [`Context::register_name`] and
+/// [`Context::lookup`] can be used. Because [`Proxy`] values are only
+/// meaningful for the context that issued them, a registry should
+/// only be used with proxies from a single context.
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, Names};
+///
+/// #[contextual(Rug)]
+/// struct Config {
+///   verbose: bool,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug {
+///   #[table]
+///   configs: Config,
+///   #[resource]
+///   names: Names,
+/// }
+///
+/// let mut r = Rug { configs: Default::default(), names: Names::new() };
+/// let config = r.add(Config { verbose: true });
+/// r.register_name("root_config", config);
+///
+/// fn verbose(r: &Rug) -> bool {
+///     let config = r.lookup::<Config>("root_config").unwrap();
+///     r.get(&config).verbose
+/// }
+/// assert!(verbose(&r));
+/// ```
+///
+/// [`Context::register_name`]: crate::Context::register_name
+/// [`Context::lookup`]: crate::Context::lookup
+#[derive(Clone, Default)]
+pub struct Names {
+    names: BTreeMap<String, AnyProxy>,
+}
+
+impl Names {
+    /// Create a new, empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an object under a name, returning the object the name
+    /// referred to before, if any.
+    pub fn register<T: 'static>(
+        &mut self,
+        name: impl Into<String>,
+        p: Proxy<T>,
+    ) -> Option<AnyProxy> {
+        self.names.insert(name.into(), AnyProxy::new(p))
+    }
+
+    /// Remove a name, returning the object it referred to.
+    pub fn unregister(&mut self, name: &str) -> Option<AnyProxy> {
+        self.names.remove(name)
+    }
+
+    /// Find the object of type `T` registered under a name.
+    ///
+    /// Returns [`None`] if the name is not registered, or refers to
+    /// an object of another type.
+    pub fn lookup<T: 'static>(&self, name: &str) -> Option<Proxy<T>> {
+        self.names.get(name)?.downcast()
+    }
+
+    /// Find the object registered under a name, of whatever type.
+    pub fn get(&self, name: &str) -> Option<AnyProxy> {
+        self.names.get(name).copied()
+    }
+
+    /// Iterate over the names an object is registered under, in
+    /// order.
+    pub fn names_of<T: 'static>(&self, p: &Proxy<T>) -> impl Iterator<Item = &str> {
+        let p = *p;
+        self.names
+            .iter()
+            .filter(move |(_, q)| **q == p)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Iterate over the registered names and their objects, in order
+    /// of name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, AnyProxy)> {
+        self.names.iter().map(|(name, p)| (name.as_str(), *p))
+    }
+
+    /// The number of registered names.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Check whether any names are registered.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+impl std::fmt::Debug for Names {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.names.iter()).finish()
+    }
+}
@@ -0,0 +1,156 @@
+//! Running callbacks when objects are added or changed.
+//!
+//! This module is available with the `observe` feature. When it is
+//! enabled, every [`Table`] can hold callbacks, registered with
+//! [`Table::on_add`] and [`Table::on_modify`], which it runs whenever
+//! an object is added to it, or borrowed mutably. This makes it
+//! possible to keep derived state, such as a cache or an index kept
+//! outside the context, up to date without routing every change
+//! through one place.
+//!
+//! Passing `observe` to the [`persian_rug`](crate::persian_rug)
+//! attribute implements [`ObserverOwner`] for each table of the
+//! context, so that callbacks can be registered with
+//! [`Context::on_add`] and
+//! [`Context::on_modify`]:
+//!
+//! ```rust
+//! use std::sync::{Arc, Mutex};
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Sprite {
+//!   x: i32,
+//! }
+//!
+//! #[persian_rug(observe)]
+//! struct Rug(#[table] Sprite);
+//!
+//! let mut r = Rug(Default::default());
+//! let redraw = Arc::new(Mutex::new(Vec::new()));
+//!
+//! let log = redraw.clone();
+//! r.on_add::<Sprite>(move |p, _| log.lock().unwrap().push(*p));
+//! let log = redraw.clone();
+//! r.on_modify::<Sprite>(move |p| log.lock().unwrap().push(*p));
+//!
+//! let a = r.add(Sprite { x: 0 });
+//! let b = r.add(Sprite { x: 5 });
+//! r.get_mut(&a).x += 1;
+//! assert_eq!(*redraw.lock().unwrap(), vec![a, b, a]);
+//! ```
+//!
+//! Modification callbacks run when the mutable borrow is handed out,
+//! before the caller changes anything, so they are told which object
+//! may be changing, rather than how. They run for
+//! [`get_mut`](crate::Context::get_mut) and its relatives, and for
+//! each object visited by a mutable iterator, but not for
+//! [`retain`](Table::retain), nor for objects which are swapped,
+//! moved or deleted.
+//!
+//! Callbacks are not copied when a table is cloned, so a clone of a
+//! context, and a context rolled back with
+//! [`Context::restore`], start with none.
+//! They must be [`Send`] and [`Sync`], so that tables holding them
+//! can still be shared between threads.
+
+use std::marker::PhantomData;
+
+use crate::handles::HandleAllocator;
+use crate::storage::Storage;
+use crate::{Context, Contextual, Proxy, Table};
+
+// The callbacks are stored with `T` erased from their types, so that
+// a table of objects which borrow data can still be dropped in the
+// same scope as that data: a boxed `dyn FnMut(&T)` would make the
+// drop checker assume the table uses the borrowed data when dropped.
+type OnAdd = Box<dyn FnMut(*const (), *const ()) + Send + Sync>;
+type OnModify = Box<dyn FnMut(*const ()) + Send + Sync>;
+
+/// The callbacks registered with a table.
+pub(crate) struct Observers<T> {
+    add: Vec<OnAdd>,
+    modify: Vec<OnModify>,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T> Default for Observers<T> {
+    fn default() -> Self {
+        Self {
+            add: Vec::new(),
+            modify: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Observers<T> {
+    fn on_add(&mut self, mut f: impl FnMut(&Proxy<T>, &T) + Send + Sync + 'static) {
+        self.add.push(Box::new(move |p, value| {
+            // Safety: `added` is the only caller, and passes a proxy
+            // and an item of this table.
+            let (p, value) = unsafe { (&*(p as *const Proxy<T>), &*(value as *const T)) };
+            f(p, value)
+        }));
+    }
+
+    fn on_modify(&mut self, mut f: impl FnMut(&Proxy<T>) + Send + Sync + 'static) {
+        self.modify.push(Box::new(move |p| {
+            // Safety: `modified` is the only caller, and passes a
+            // proxy of this table.
+            f(unsafe { &*(p as *const Proxy<T>) })
+        }));
+    }
+
+    pub(crate) fn added(&mut self, p: &Proxy<T>, value: &T) {
+        for f in self.add.iter_mut() {
+            f(
+                p as *const Proxy<T> as *const (),
+                value as *const T as *const (),
+            );
+        }
+    }
+
+    pub(crate) fn modified(&mut self, p: &Proxy<T>) {
+        for f in self.modify.iter_mut() {
+            f(p as *const Proxy<T> as *const ());
+        }
+    }
+}
+
+impl<T, S: Storage<T>, A: HandleAllocator> Table<T, S, A> {
+    /// Run `f` on each item added to the table from now on, with its
+    /// proxy.
+    pub fn on_add(&mut self, f: impl FnMut(&Proxy<T>, &T) + Send + Sync + 'static) {
+        self.observers.on_add(f);
+    }
+
+    /// Run `f` with the proxy of each item borrowed mutably from now
+    /// on.
+    pub fn on_modify(&mut self, f: impl FnMut(&Proxy<T>) + Send + Sync + 'static) {
+        self.observers.on_modify(f);
+    }
+
+    /// Remove every callback registered with
+    /// [`on_add`](Table::on_add) and [`on_modify`](Table::on_modify).
+    pub fn clear_observers(&mut self) {
+        self.observers = Default::default();
+    }
+}
+
+/// A context which can run callbacks when objects of type `T` are
+/// added or changed.
+///
+/// This is normally implemented with the `observe` option of the
+/// [`persian_rug`](crate::persian_rug) macro.
+pub trait ObserverOwner<T>: Context
+where
+    T: Contextual<Context = Self>,
+{
+    /// Run `f` on each object of type `T` added from now on.
+    fn on_add(&mut self, f: impl FnMut(&Proxy<T>, &T) + Send + Sync + 'static);
+
+    /// Run `f` with the proxy of each object of type `T` borrowed
+    /// mutably from now on.
+    fn on_modify(&mut self, f: impl FnMut(&Proxy<T>) + Send + Sync + 'static);
+}
@@ -0,0 +1,169 @@
+use std::ops::Deref;
+
+use crate::{Contextual, Owner, Proxy};
+
+/// An [`Iterator`] over clones of [`Contextual`] objects, which owns
+/// its access to the context.
+///
+/// [`TableIterator`](crate::TableIterator) borrows from the context it
+/// iterates over, so when the context is behind a lock, the iterator
+/// cannot outlive the guard that was used to create it. This iterator
+/// instead takes ownership of the guard, or of anything else which
+/// dereferences to the context, such as an [`Arc`](std::sync::Arc),
+/// so that it can be returned from a function or stored. The context
+/// stays locked until the iterator is dropped.
+///
+/// ```rust
+/// use std::sync::{Mutex, MutexGuard};
+///
+/// use persian_rug::{contextual, persian_rug, Context, OwnedTableIterator};
+///
+/// #[derive(Clone)]
+/// #[contextual(Rug)]
+/// struct Foo {
+///   a: i32,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Foo);
+///
+/// fn foos(rug: &Mutex<Rug>) -> OwnedTableIterator<MutexGuard<'_, Rug>, Foo> {
+///     OwnedTableIterator::new(rug.lock().unwrap())
+/// }
+///
+/// let rug = Mutex::new(Rug(Default::default()));
+/// rug.lock().unwrap().add(Foo { a: 1 });
+/// rug.lock().unwrap().add(Foo { a: 2 });
+///
+/// assert_eq!(foos(&rug).map(|foo| foo.a).collect::<Vec<_>>(), vec![1, 2]);
+/// ```
+///
+/// The proxies to iterate over are collected when the iterator is
+/// created.
+pub struct OwnedTableIterator<G, T> {
+    inner: OwnedTableProxyIterator<G, T>,
+}
+
+impl<G, T> OwnedTableIterator<G, T>
+where
+    G: Deref,
+    G::Target: Owner<T>,
+    T: Contextual<Context = G::Target>,
+{
+    /// Create an iterator over the objects of type `T` in the context
+    /// that `guard` dereferences to.
+    pub fn new(guard: G) -> Self {
+        Self {
+            inner: OwnedTableProxyIterator::new(guard),
+        }
+    }
+
+    /// The context being iterated over.
+    pub fn context(&self) -> &G::Target {
+        self.inner.context()
+    }
+
+    /// Stop iterating, and recover the guard.
+    pub fn into_guard(self) -> G {
+        self.inner.into_guard()
+    }
+}
+
+impl<G, T> Iterator for OwnedTableIterator<G, T>
+where
+    G: Deref,
+    G::Target: Owner<T>,
+    T: Contextual<Context = G::Target> + Clone,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let p = self.inner.next()?;
+        Some(self.inner.get(&p).clone())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// An [`Iterator`] over [`Proxy`] objects for [`Contextual`] objects,
+/// which owns its access to the context.
+///
+/// This is the counterpart of [`OwnedTableIterator`] for types which
+/// cannot be cloned: it yields the proxies of the objects, and they
+/// can be looked up with [`get`](OwnedTableProxyIterator::get) while
+/// the iterator holds the context.
+///
+/// ```rust
+/// use std::sync::{Arc, RwLock, RwLockReadGuard};
+///
+/// use persian_rug::{contextual, persian_rug, Context, OwnedTableProxyIterator};
+///
+/// #[contextual(Rug)]
+/// struct Foo {
+///   a: i32,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Foo);
+///
+/// let rug = RwLock::new(Rug(Default::default()));
+/// rug.write().unwrap().add(Foo { a: 1 });
+/// rug.write().unwrap().add(Foo { a: 2 });
+///
+/// let mut iter = OwnedTableProxyIterator::<_, Foo>::new(rug.read().unwrap());
+/// let mut total = 0;
+/// while let Some(p) = iter.next() {
+///     total += iter.get(&p).a;
+/// }
+/// assert_eq!(total, 3);
+/// ```
+pub struct OwnedTableProxyIterator<G, T> {
+    guard: G,
+    proxies: std::vec::IntoIter<Proxy<T>>,
+}
+
+impl<G, T> OwnedTableProxyIterator<G, T>
+where
+    G: Deref,
+    G::Target: Owner<T>,
+    T: Contextual<Context = G::Target>,
+{
+    /// Create an iterator over the proxies of the objects of type `T`
+    /// in the context that `guard` dereferences to.
+    pub fn new(guard: G) -> Self {
+        let proxies = Owner::<T>::get_proxy_iter(&*guard)
+            .copied()
+            .collect::<Vec<_>>()
+            .into_iter();
+        Self { guard, proxies }
+    }
+
+    /// Get an object from the context being iterated over.
+    pub fn get(&self, what: &Proxy<T>) -> &T {
+        Owner::get(&*self.guard, what)
+    }
+
+    /// The context being iterated over.
+    pub fn context(&self) -> &G::Target {
+        &self.guard
+    }
+
+    /// Stop iterating, and recover the guard.
+    pub fn into_guard(self) -> G {
+        self.guard
+    }
+}
+
+impl<G, T> Iterator for OwnedTableProxyIterator<G, T> {
+    type Item = Proxy<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.proxies.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.proxies.size_hint()
+    }
+}
@@ -0,0 +1,57 @@
+//! Running work on chunks of a slice in parallel.
+//!
+//! With the `rayon` feature, the chunks are handed to rayon's global
+//! thread pool. Otherwise, a scoped thread is started for each
+//! available core, and the threads take chunks in turn until none are
+//! left. Without the `sync` feature, the chunks are handled in turn
+//! on the calling thread.
+
+#[cfg(feature = "rayon")]
+pub(crate) fn for_each_chunk<E, F>(items: &mut [E], size: usize, f: &F)
+where
+    E: Send,
+    F: Fn(&mut [E]) + Sync,
+{
+    use rayon::prelude::*;
+
+    items.par_chunks_mut(size).for_each(f);
+}
+
+#[cfg(all(feature = "sync", not(feature = "rayon")))]
+pub(crate) fn for_each_chunk<E, F>(items: &mut [E], size: usize, f: &F)
+where
+    E: Send,
+    F: Fn(&mut [E]) + Sync,
+{
+    use std::sync::{Mutex, PoisonError};
+
+    let workers = std::thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(items.len().div_ceil(size));
+    if workers <= 1 {
+        items.chunks_mut(size).for_each(f);
+        return;
+    }
+
+    let chunks = Mutex::new(items.chunks_mut(size));
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let chunk = chunks.lock().unwrap_or_else(PoisonError::into_inner).next();
+                match chunk {
+                    Some(chunk) => f(chunk),
+                    None => break,
+                }
+            });
+        }
+    });
+}
+
+#[cfg(not(feature = "sync"))]
+pub(crate) fn for_each_chunk<E, F>(items: &mut [E], size: usize, f: &F)
+where
+    E: Send,
+    F: Fn(&mut [E]) + Sync,
+{
+    items.chunks_mut(size).for_each(f);
+}
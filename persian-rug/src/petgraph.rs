@@ -0,0 +1,125 @@
+//! Converting contexts into [`petgraph`] graphs.
+//!
+//! This module is available with the `petgraph` feature. A
+//! [`Selection`] gathers the objects of some or all of the tables of a
+//! context, and the links between them, and builds either a [`Graph`]
+//! or a [`StableGraph`] from them. Each node is weighted with the
+//! [`AnyProxy`] of its object, and each edge with the name of the
+//! field holding the link, as found by [`Links`]. This
+//! makes petgraph's algorithms available without writing the
+//! conversion for every context. Its [`algo`] and [`visit`] modules
+//! are re-exported here, so that they can be used without depending on
+//! the same version of petgraph directly.
+//!
+//! ```rust
+//! use persian_rug::petgraph::{algo, Selection};
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Task {
+//!   name: &'static str,
+//!   #[link]
+//!   after: Vec<Proxy<Task>>,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Task);
+//!
+//! let mut r = Rug(Default::default());
+//! let build = r.add(Task { name: "build", after: vec![] });
+//! let test = r.add(Task { name: "test", after: vec![build] });
+//! let ship = r.add(Task { name: "ship", after: vec![test, build] });
+//!
+//! let (graph, _) = Selection::new(&r).table::<Task>().graph();
+//! let order = algo::toposort(&graph, None)
+//!     .unwrap()
+//!     .into_iter()
+//!     .map(|ix| graph[ix].downcast::<Task>().unwrap())
+//!     .collect::<Vec<_>>();
+//! assert_eq!(order, vec![ship, test, build]);
+//! ```
+//!
+//! A context declared with `#[persian_rug(diagram)]` can also be
+//! converted as a whole with [`Selection::all`].
+//!
+//! Links to objects outside the selection are left out, as are links
+//! to objects which have been deleted.
+
+use std::collections::BTreeMap;
+
+pub use ::petgraph::graph::{Graph, NodeIndex};
+pub use ::petgraph::stable_graph::StableGraph;
+pub use ::petgraph::{algo, visit};
+
+use crate::diagram::{Diagram, DiagramTables};
+use crate::{AnyProxy, Context, Contextual, Links, Owner};
+
+/// The tables of a context to convert into a graph.
+pub struct Selection<'a, C> {
+    tables: DiagramTables<'a, C>,
+}
+
+impl<'a, C: Context> Selection<'a, C> {
+    /// Start a selection from `context`, with no tables in it.
+    pub fn new(context: &'a C) -> Self {
+        Self {
+            tables: DiagramTables::new(context, None),
+        }
+    }
+
+    /// Select every table of `context`, as described by its
+    /// [`Diagram`] implementation.
+    pub fn all(context: &'a C) -> Self
+    where
+        C: Diagram,
+    {
+        let mut res = Self::new(context);
+        C::describe(&mut res.tables);
+        res
+    }
+
+    /// Add the table of objects of type `T` to the selection.
+    pub fn table<T>(mut self) -> Self
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + Links + 'static,
+    {
+        self.tables.table::<T>();
+        self
+    }
+
+    /// Build a [`Graph`] of the selected objects.
+    ///
+    /// This also returns the index of the node for each object.
+    pub fn graph(self) -> (Graph<AnyProxy, String>, BTreeMap<AnyProxy, NodeIndex>) {
+        let mut graph = Graph::new();
+        let selected = self.tables.finish();
+        let indices = selected
+            .nodes
+            .into_iter()
+            .map(|node| (node.proxy, graph.add_node(node.proxy)))
+            .collect::<BTreeMap<_, _>>();
+        for edge in selected.edges {
+            graph.add_edge(indices[&edge.from], indices[&edge.to], edge.field);
+        }
+        (graph, indices)
+    }
+
+    /// Build a [`StableGraph`] of the selected objects, whose indices
+    /// stay valid as nodes are removed.
+    ///
+    /// This also returns the index of the node for each object.
+    pub fn stable_graph(self) -> (StableGraph<AnyProxy, String>, BTreeMap<AnyProxy, NodeIndex>) {
+        let mut graph = StableGraph::new();
+        let selected = self.tables.finish();
+        let indices = selected
+            .nodes
+            .into_iter()
+            .map(|node| (node.proxy, graph.add_node(node.proxy)))
+            .collect::<BTreeMap<_, _>>();
+        for edge in selected.edges {
+            graph.add_edge(indices[&edge.from], indices[&edge.to], edge.field);
+        }
+        (graph, indices)
+    }
+}
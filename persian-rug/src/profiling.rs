@@ -0,0 +1,182 @@
+//! Counting accesses to tables.
+//!
+//! This module is available with the `profiling` feature. When it is
+//! enabled, every [`Table`] counts the lookups, mutable lookups and
+//! iterations made on it, which can help to show which types deserve
+//! an index, or a different storage. The counts for a single table
+//! are available from [`Table::access_counts`].
+//!
+//! Passing `profile` to the [`persian_rug`](crate::persian_rug)
+//! attribute implements [`Profiled`] for the context, which gathers
+//! the counts of all of its tables into a [`ProfileReport`]:
+//!
+//! ```rust
+//! use persian_rug::profiling::Profiled;
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Foo {
+//!   a: i32,
+//! }
+//!
+//! #[contextual(Rug)]
+//! struct Bar {
+//!   foo: Proxy<Foo>,
+//! }
+//!
+//! #[persian_rug(profile)]
+//! struct Rug(#[table] Foo, #[table] Bar);
+//!
+//! let mut r = Rug(Default::default(), Default::default());
+//! let foo = r.add(Foo { a: 1 });
+//! let bar = r.add(Bar { foo });
+//! for _ in 0..3 {
+//!     let foo = r.get(&bar).foo;
+//!     r.get_mut(&foo).a += 1;
+//! }
+//!
+//! let report = r.profile();
+//! let (_, foos) = report.iter().find(|(name, _)| name.ends_with("Foo")).unwrap();
+//! assert_eq!(foos.get_muts, 3);
+//! println!("{}", report);
+//! ```
+//!
+//! Counting adds an atomic increment to every access, so the feature
+//! is best enabled only while investigating performance.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::storage::Storage;
+use crate::Table;
+
+/// The number of accesses made to a table.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AccessCounts {
+    /// The number of objects retrieved by shared reference.
+    pub gets: u64,
+    /// The number of objects retrieved by mutable reference.
+    pub get_muts: u64,
+    /// The number of iterations started over the table, of any kind.
+    pub iterations: u64,
+}
+
+impl AccessCounts {
+    /// The total number of accesses of all kinds.
+    pub fn total(&self) -> u64 {
+        self.gets + self.get_muts + self.iterations
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Counters {
+    gets: AtomicU64,
+    get_muts: AtomicU64,
+    iterations: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn get(&self) {
+        self.gets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get_mut(&self) {
+        self.get_muts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn iteration(&self) {
+        self.iterations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn counts(&self) -> AccessCounts {
+        AccessCounts {
+            gets: self.gets.load(Ordering::Relaxed),
+            get_muts: self.get_muts.load(Ordering::Relaxed),
+            iterations: self.iterations.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        self.gets.store(0, Ordering::Relaxed);
+        self.get_muts.store(0, Ordering::Relaxed);
+        self.iterations.store(0, Ordering::Relaxed);
+    }
+}
+
+impl<T, S: Storage<T>, A> Table<T, S, A> {
+    /// The number of accesses made to this table since it was
+    /// created, or since its counts were last reset.
+    ///
+    /// Cloned tables start with no accesses.
+    pub fn access_counts(&self) -> AccessCounts {
+        self.counters.counts()
+    }
+
+    /// Reset the access counts of this table to zero.
+    pub fn reset_access_counts(&self) {
+        self.counters.reset()
+    }
+}
+
+/// The access counts for each table in a context.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProfileReport {
+    tables: Vec<(&'static str, AccessCounts)>,
+}
+
+impl ProfileReport {
+    /// Create a new, empty report.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add the counts for a table holding objects of the type named
+    /// `name`.
+    pub fn add(&mut self, name: &'static str, counts: AccessCounts) {
+        self.tables.push((name, counts));
+    }
+
+    /// Iterate over the tables in the report, busiest first.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &AccessCounts)> {
+        let mut tables = self.tables.iter().collect::<Vec<_>>();
+        tables.sort_by_key(|(_, counts)| std::cmp::Reverse(counts.total()));
+        tables.into_iter().map(|(name, counts)| (*name, counts))
+    }
+}
+
+impl std::fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .tables
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0)
+            .max("table".len());
+        writeln!(
+            f,
+            "{:<width$} {:>12} {:>12} {:>12}",
+            "table", "gets", "get_muts", "iterations"
+        )?;
+        for (name, counts) in self.iter() {
+            writeln!(
+                f,
+                "{:<width$} {:>12} {:>12} {:>12}",
+                name, counts.gets, counts.get_muts, counts.iterations
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A context which can report the accesses made to its tables.
+///
+/// Implementations are generated by the
+/// [`persian_rug`](crate::persian_rug) attribute when given the
+/// `profile` option.
+pub trait Profiled {
+    /// Gather the access counts of every table.
+    fn profile(&self) -> ProfileReport;
+
+    /// Reset the access counts of every table to zero.
+    fn reset_profile(&self);
+}
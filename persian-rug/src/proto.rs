@@ -0,0 +1,935 @@
+//! Exporting contexts as Protocol Buffers.
+//!
+//! This module is available with the `proto` feature. It turns the
+//! [`schema`](crate::schema) of a context into a `.proto` description
+//! with [`describe`], and a context declared with
+//! `#[persian_rug(proto)]` implements [`ExportProto`], which encodes
+//! its objects as a message of that description. Programs in other
+//! languages can then read exported contexts with the code their own
+//! protobuf tooling generates, over whatever RPC infrastructure they
+//! already have.
+//!
+//! Each table becomes a message named after its type, with a field
+//! for each field of the type, numbered in declaration order. The
+//! context becomes a message with a `map` from handles to objects for
+//! each table, and each proxy becomes the `uint64` handle of the
+//! object it refers to, which can be looked up in the map of the
+//! table for the target type:
+//!
+//! ```rust
+//! use persian_rug::proto::ExportProto;
+//! use persian_rug::serde::Serialize;
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[derive(Serialize)]
+//! #[serde(crate = "persian_rug::serde")]
+//! #[contextual(Rug)]
+//! struct Person {
+//!   name: String,
+//!   manager: Option<Proxy<Person>>,
+//! }
+//!
+//! #[persian_rug(proto)]
+//! struct Rug {
+//!   #[table]
+//!   people: Person,
+//! }
+//!
+//! assert_eq!(
+//!     Rug::proto_file("example").unwrap(),
+//!     r#"syntax = "proto3";
+//!
+//! package example;
+//!
+//! message Rug {
+//!   map<uint64, Person> people = 1;
+//! }
+//!
+//! message Person {
+//!   string name = 1;
+//!   optional uint64 manager = 2; // handle of a Person
+//! }
+//! "#
+//! );
+//!
+//! let mut r = Rug { people: Default::default() };
+//! let boss = r.add(Person { name: "Alice".to_string(), manager: None });
+//! r.add(Person { name: "Bob".to_string(), manager: Some(boss) });
+//! let bytes = r.to_proto().unwrap();
+//! ```
+//!
+//! Enums become messages with a nested message for each variant, and
+//! a `oneof` holding whichever is present. The fields of tuple structs
+//! and variants are named `field_0`, `field_1` and so on.
+//!
+//! Only fields whose types map directly onto protobuf are supported:
+//! `bool`, the integer and floating point types up to 64 bits,
+//! `String`, `&str` and `char`, proxies, any of these in an
+//! [`Option`], and any of these in a [`Vec`], [`VecDeque`],
+//! [`BTreeSet`] or [`HashSet`]. [`PhantomData`] fields are left out.
+//! The types of fields are taken from the schema, as they were written
+//! in the source, so type aliases and generic parameters are not
+//! supported either.
+//!
+//! Objects are converted with their [`Serialize`] implementations,
+//! which must not rename or skip fields.
+//!
+//! [`VecDeque`]: std::collections::VecDeque
+//! [`BTreeSet`]: std::collections::BTreeSet
+//! [`HashSet`]: std::collections::HashSet
+//! [`PhantomData`]: std::marker::PhantomData
+
+use std::fmt::Write;
+
+use serde::ser::{self, Impossible, Serialize};
+
+use crate::schema::{ContextSchema, FieldSchema, TypeSchema};
+use crate::{Context, Contextual, Owner};
+
+/// A failure to describe or encode a context as protobuf.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProtoError {
+    /// A field has a type which has no protobuf equivalent.
+    Field {
+        /// The full name of the type with the field.
+        type_name: &'static str,
+        /// The name of the field.
+        field: &'static str,
+        /// The type of the field, as written in the source.
+        ty: &'static str,
+    },
+    /// Two tables store types with the same name, which would give
+    /// two messages the same name.
+    Duplicate(&'static str),
+    /// An object could not be encoded.
+    Encode(String),
+}
+
+impl std::fmt::Display for ProtoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtoError::Field {
+                type_name,
+                field,
+                ty,
+            } => write!(
+                f,
+                "field {} of {} has type {}, which has no protobuf equivalent",
+                field, type_name, ty
+            ),
+            ProtoError::Duplicate(name) => {
+                write!(f, "more than one table stores a type named {}", name)
+            }
+            ProtoError::Encode(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ProtoError {}
+
+impl ser::Error for ProtoError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        ProtoError::Encode(msg.to_string())
+    }
+}
+
+/// A context which can be exported as protobuf.
+///
+/// This is normally implemented with the `proto` option of the
+/// [`persian_rug`](crate::persian_rug) macro. See the [module
+/// documentation](self) for an example.
+pub trait ExportProto: Context {
+    /// Encode each table of the context with `tables`.
+    fn encode_tables(tables: &mut ProtoTables<'_, Self>)
+    where
+        Self: Sized;
+
+    /// A `.proto` description of this context, in the given package.
+    ///
+    /// This is [`describe`] applied to the schema of the context.
+    fn proto_file(package: &str) -> Result<String, ProtoError>
+    where
+        Self: Sized,
+    {
+        describe(&Self::schema(), package)
+    }
+
+    /// Encode every table of this context as a message of the type
+    /// described by [`proto_file`](ExportProto::proto_file).
+    fn to_proto(&self) -> Result<Vec<u8>, ProtoError>
+    where
+        Self: Sized,
+    {
+        let mut tables = ProtoTables {
+            context: self,
+            schema: Self::schema(),
+            out: Vec::new(),
+            error: None,
+        };
+        Self::encode_tables(&mut tables);
+        match tables.error {
+            Some(e) => Err(e),
+            None => Ok(tables.out),
+        }
+    }
+}
+
+/// The tables being encoded by [`ExportProto::to_proto`].
+pub struct ProtoTables<'a, C> {
+    context: &'a C,
+    schema: ContextSchema,
+    out: Vec<u8>,
+    error: Option<ProtoError>,
+}
+
+impl<C: Context> ProtoTables<'_, C> {
+    /// Encode the table of objects of type `T`.
+    ///
+    /// Objects are encoded in handle order.
+    pub fn table<T>(&mut self)
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + Serialize,
+    {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.encode::<T>() {
+            self.error = Some(e);
+        }
+    }
+
+    fn encode<T>(&mut self) -> Result<(), ProtoError>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + Serialize,
+    {
+        let type_name = std::any::type_name::<T>();
+        let number = self
+            .schema
+            .tables
+            .iter()
+            .position(|table| table.schema.type_name == type_name)
+            .ok_or_else(|| {
+                ProtoError::Encode(format!("{} has no table in the schema", type_name))
+            })?
+            + 1;
+        let schema = T::schema();
+
+        let mut proxies = Owner::<T>::get_proxy_iter(self.context).collect::<Vec<_>>();
+        proxies.sort_by_key(|p| p.index);
+        for p in proxies {
+            let mut value = Vec::new();
+            Owner::get(self.context, p).serialize(MessageSerializer {
+                schema: &schema,
+                out: &mut value,
+            })?;
+            let mut entry = Vec::new();
+            write_tag(&mut entry, 1, VARINT);
+            write_varint(&mut entry, p.index);
+            write_bytes(&mut entry, 2, &value);
+            write_bytes(&mut self.out, number as u32, &entry);
+        }
+        Ok(())
+    }
+}
+
+/// Describe a context as a `.proto` file in the given package.
+///
+/// See the [module documentation](self) for the form of the
+/// description, and the types of field which are supported.
+pub fn describe(schema: &ContextSchema, package: &str) -> Result<String, ProtoError> {
+    for (ix, table) in schema.tables.iter().enumerate() {
+        if schema.tables[..ix]
+            .iter()
+            .any(|other| other.schema.name == table.schema.name)
+            || table.schema.name == schema.name
+        {
+            return Err(ProtoError::Duplicate(table.schema.name));
+        }
+    }
+
+    let mut res = String::new();
+    writeln!(res, "syntax = \"proto3\";\n").unwrap();
+    writeln!(res, "package {};\n", package).unwrap();
+    writeln!(res, "message {} {{", schema.name).unwrap();
+    for (ix, table) in schema.tables.iter().enumerate() {
+        writeln!(
+            res,
+            "  map<uint64, {}> {} = {};",
+            table.schema.name,
+            field_name(table.field),
+            ix + 1
+        )
+        .unwrap();
+    }
+    writeln!(res, "}}").unwrap();
+
+    for table in &schema.tables {
+        writeln!(res).unwrap();
+        describe_type(&mut res, schema, &table.schema)?;
+    }
+    Ok(res)
+}
+
+fn describe_type(
+    res: &mut String,
+    schema: &ContextSchema,
+    ty: &TypeSchema,
+) -> Result<(), ProtoError> {
+    writeln!(res, "message {} {{", ty.name).unwrap();
+    describe_fields(res, schema, ty, &ty.fields, "  ")?;
+    if !ty.variants.is_empty() {
+        for variant in &ty.variants {
+            writeln!(res, "  message {} {{", variant.name).unwrap();
+            describe_fields(res, schema, ty, &variant.fields, "    ")?;
+            writeln!(res, "  }}").unwrap();
+        }
+        writeln!(res, "  oneof variant {{").unwrap();
+        for (ix, variant) in ty.variants.iter().enumerate() {
+            writeln!(
+                res,
+                "    {} {} = {};",
+                variant.name,
+                snake_case(variant.name),
+                ix + 1
+            )
+            .unwrap();
+        }
+        writeln!(res, "  }}").unwrap();
+    }
+    writeln!(res, "}}").unwrap();
+    Ok(())
+}
+
+fn describe_fields(
+    res: &mut String,
+    schema: &ContextSchema,
+    ty: &TypeSchema,
+    fields: &[FieldSchema],
+    indent: &str,
+) -> Result<(), ProtoError> {
+    for (ix, field) in fields.iter().enumerate() {
+        let Some(kind) = field_kind(field.ty) else {
+            return Err(ProtoError::Field {
+                type_name: ty.type_name,
+                field: field.name,
+                ty: field.ty,
+            });
+        };
+        let (label, proto_ty) = match kind {
+            FieldKind::Skip => continue,
+            FieldKind::Single(proto_ty) => ("", proto_ty),
+            FieldKind::Optional(proto_ty) => ("optional ", proto_ty),
+            FieldKind::Repeated(proto_ty) => ("repeated ", proto_ty),
+        };
+        write!(
+            res,
+            "{}{}{} {} = {};",
+            indent,
+            label,
+            proto_ty,
+            field_name(field.name),
+            ix + 1
+        )
+        .unwrap();
+        if let [target] = field.proxies.as_slice() {
+            let target = schema
+                .table(target)
+                .map_or(*target, |table| table.schema.name);
+            write!(res, " // handle of a {}", target).unwrap();
+        }
+        writeln!(res).unwrap();
+    }
+    Ok(())
+}
+
+enum FieldKind {
+    Skip,
+    Single(&'static str),
+    Optional(&'static str),
+    Repeated(&'static str),
+}
+
+fn field_kind(ty: &str) -> Option<FieldKind> {
+    let (base, param) = split_type(ty);
+    match (base, param) {
+        ("PhantomData", Some(_)) => Some(FieldKind::Skip),
+        ("Option", Some(param)) => single_type(param).map(FieldKind::Optional),
+        ("Vec" | "VecDeque" | "BTreeSet" | "HashSet", Some(param)) => {
+            single_type(param).map(FieldKind::Repeated)
+        }
+        _ => single_type(ty).map(FieldKind::Single),
+    }
+}
+
+fn single_type(ty: &str) -> Option<&'static str> {
+    match split_type(ty) {
+        ("Proxy", Some(_)) => Some("uint64"),
+        ("bool", None) => Some("bool"),
+        ("i8" | "i16" | "i32", None) => Some("int32"),
+        ("i64" | "isize", None) => Some("int64"),
+        ("u8" | "u16" | "u32", None) => Some("uint32"),
+        ("u64" | "usize", None) => Some("uint64"),
+        ("f32", None) => Some("float"),
+        ("f64", None) => Some("double"),
+        ("String" | "str" | "char", None) => Some("string"),
+        _ => None,
+    }
+}
+
+/// Split a type as written into the last segment of its path, and
+/// its parameter if it has exactly one.
+fn split_type(ty: &str) -> (&str, Option<&str>) {
+    let mut ty = ty.trim();
+    if let Some(rest) = ty.strip_prefix('&') {
+        ty = rest.trim_start();
+        if ty.starts_with('\'') {
+            ty = ty.split_once(' ').map_or("", |(_, rest)| rest.trim_start());
+        }
+        ty = ty.strip_prefix("mut ").unwrap_or(ty);
+    }
+    let (path, param) = match ty.split_once('<') {
+        Some((path, rest)) => match rest.strip_suffix('>') {
+            Some(param) if !has_top_level_comma(param) => (path, Some(param.trim())),
+            _ => return (ty, None),
+        },
+        None => (ty, None),
+    };
+    let path = path.trim();
+    (path.rsplit("::").next().unwrap_or(path), param)
+}
+
+fn has_top_level_comma(params: &str) -> bool {
+    let mut depth = 0;
+    for c in params.chars() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn field_name(name: &str) -> String {
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("field_{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut res = String::new();
+    for (ix, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if ix > 0 {
+                res.push('_');
+            }
+            res.extend(c.to_lowercase());
+        } else {
+            res.push(c);
+        }
+    }
+    res
+}
+
+const VARINT: u8 = 0;
+const I64: u8 = 1;
+const LEN: u8 = 2;
+const I32: u8 = 5;
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_tag(out: &mut Vec<u8>, number: u32, wire_type: u8) {
+    write_varint(out, (u64::from(number) << 3) | u64::from(wire_type));
+}
+
+fn write_bytes(out: &mut Vec<u8>, number: u32, bytes: &[u8]) {
+    write_tag(out, number, LEN);
+    write_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn field_number(fields: &[FieldSchema], name: &str) -> Result<u32, ProtoError> {
+    fields
+        .iter()
+        .position(|field| field.name == name)
+        .map(|ix| ix as u32 + 1)
+        .ok_or_else(|| ProtoError::Encode(format!("field {} is not in the schema", name)))
+}
+
+fn unsupported(what: &str) -> ProtoError {
+    ProtoError::Encode(format!("{} cannot be encoded as protobuf", what))
+}
+
+/// Serializes a contextual type as a message, using its schema to
+/// number its fields.
+struct MessageSerializer<'a> {
+    schema: &'a TypeSchema,
+    out: &'a mut Vec<u8>,
+}
+
+impl<'a> MessageSerializer<'a> {
+    fn fields(self) -> Fields<'a> {
+        Fields {
+            fields: &self.schema.fields,
+            buf: Vec::new(),
+            out: self.out,
+            variant: None,
+            next: 0,
+        }
+    }
+
+    fn variant(self, name: &str) -> Result<Fields<'a>, ProtoError> {
+        let ix = self
+            .schema
+            .variants
+            .iter()
+            .position(|variant| variant.name == name)
+            .ok_or_else(|| ProtoError::Encode(format!("variant {} is not in the schema", name)))?;
+        Ok(Fields {
+            fields: &self.schema.variants[ix].fields,
+            buf: Vec::new(),
+            out: self.out,
+            variant: Some(ix as u32 + 1),
+            next: 0,
+        })
+    }
+}
+
+/// Serializes the fields of a struct or variant.
+struct Fields<'a> {
+    fields: &'a [FieldSchema],
+    buf: Vec<u8>,
+    out: &'a mut Vec<u8>,
+    variant: Option<u32>,
+    next: usize,
+}
+
+impl Fields<'_> {
+    fn field<T: Serialize + ?Sized>(&mut self, name: &str, value: &T) -> Result<(), ProtoError> {
+        let number = field_number(self.fields, name)?;
+        value.serialize(FieldSerializer {
+            number,
+            out: &mut self.buf,
+            repeated: false,
+        })
+    }
+
+    fn positional<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ProtoError> {
+        let name = self.next.to_string();
+        self.next += 1;
+        self.field(&name, value)
+    }
+
+    fn finish(self) -> Result<(), ProtoError> {
+        match self.variant {
+            Some(number) => write_bytes(self.out, number, &self.buf),
+            None => self.out.extend_from_slice(&self.buf),
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for Fields<'_> {
+    type Ok = ();
+    type Error = ProtoError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ProtoError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), ProtoError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for Fields<'_> {
+    type Ok = ();
+    type Error = ProtoError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ProtoError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), ProtoError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for Fields<'_> {
+    type Ok = ();
+    type Error = ProtoError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ProtoError> {
+        self.positional(value)
+    }
+
+    fn end(self) -> Result<(), ProtoError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for Fields<'_> {
+    type Ok = ();
+    type Error = ProtoError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ProtoError> {
+        self.positional(value)
+    }
+
+    fn end(self) -> Result<(), ProtoError> {
+        self.finish()
+    }
+}
+
+macro_rules! not_a_message {
+    ($($method:ident($($ty:ty),*);)*) => {
+        $(
+            fn $method(self, $(_: $ty),*) -> Result<(), ProtoError> {
+                Err(unsupported(self.schema.type_name))
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for MessageSerializer<'a> {
+    type Ok = ();
+    type Error = ProtoError;
+    type SerializeSeq = Impossible<(), ProtoError>;
+    type SerializeTuple = Impossible<(), ProtoError>;
+    type SerializeTupleStruct = Fields<'a>;
+    type SerializeTupleVariant = Fields<'a>;
+    type SerializeMap = Impossible<(), ProtoError>;
+    type SerializeStruct = Fields<'a>;
+    type SerializeStructVariant = Fields<'a>;
+
+    not_a_message! {
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _: &T) -> Result<(), ProtoError> {
+        Err(unsupported(self.schema.type_name))
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), ProtoError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<(), ProtoError> {
+        self.variant(variant)?.finish()
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<(), ProtoError> {
+        let mut fields = self.fields();
+        fields.positional(value)?;
+        fields.finish()
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), ProtoError> {
+        let mut fields = self.variant(variant)?;
+        fields.positional(value)?;
+        fields.finish()
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, ProtoError> {
+        Err(unsupported(self.schema.type_name))
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, ProtoError> {
+        Err(unsupported(self.schema.type_name))
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Fields<'a>, ProtoError> {
+        Ok(self.fields())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<Fields<'a>, ProtoError> {
+        self.variant(variant)
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, ProtoError> {
+        Err(unsupported(self.schema.type_name))
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Fields<'a>, ProtoError> {
+        Ok(self.fields())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<Fields<'a>, ProtoError> {
+        self.variant(variant)
+    }
+}
+
+/// Serializes the value of one field of a message.
+struct FieldSerializer<'a> {
+    number: u32,
+    out: &'a mut Vec<u8>,
+    repeated: bool,
+}
+
+impl FieldSerializer<'_> {
+    fn varint(self, value: u64) -> Result<(), ProtoError> {
+        write_tag(self.out, self.number, VARINT);
+        write_varint(self.out, value);
+        Ok(())
+    }
+}
+
+impl<'a> ser::Serializer for FieldSerializer<'a> {
+    type Ok = ();
+    type Error = ProtoError;
+    type SerializeSeq = Repeated<'a>;
+    type SerializeTuple = Impossible<(), ProtoError>;
+    type SerializeTupleStruct = Impossible<(), ProtoError>;
+    type SerializeTupleVariant = Impossible<(), ProtoError>;
+    type SerializeMap = Impossible<(), ProtoError>;
+    type SerializeStruct = Impossible<(), ProtoError>;
+    type SerializeStructVariant = Impossible<(), ProtoError>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), ProtoError> {
+        self.varint(v.into())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), ProtoError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), ProtoError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), ProtoError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), ProtoError> {
+        // Negative values of int32 and int64 fields are both encoded
+        // as ten byte varints.
+        self.varint(v as u64)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), ProtoError> {
+        self.varint(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), ProtoError> {
+        self.varint(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), ProtoError> {
+        self.varint(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), ProtoError> {
+        self.varint(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), ProtoError> {
+        write_tag(self.out, self.number, I32);
+        self.out.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), ProtoError> {
+        write_tag(self.out, self.number, I64);
+        self.out.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), ProtoError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), ProtoError> {
+        write_bytes(self.out, self.number, v.as_bytes());
+        Ok(())
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<(), ProtoError> {
+        Err(unsupported("a byte string"))
+    }
+
+    fn serialize_none(self) -> Result<(), ProtoError> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), ProtoError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), ProtoError> {
+        Err(unsupported("()"))
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), ProtoError> {
+        // This is how PhantomData fields are serialized.
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        _: u32,
+        _: &'static str,
+    ) -> Result<(), ProtoError> {
+        Err(unsupported(name))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<(), ProtoError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), ProtoError> {
+        Err(unsupported(name))
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Repeated<'a>, ProtoError> {
+        if self.repeated {
+            return Err(unsupported("a sequence of sequences"));
+        }
+        Ok(Repeated {
+            number: self.number,
+            out: self.out,
+        })
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, ProtoError> {
+        Err(unsupported("a tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, ProtoError> {
+        Err(unsupported(name))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, ProtoError> {
+        Err(unsupported(name))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, ProtoError> {
+        Err(unsupported("a map"))
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStruct, ProtoError> {
+        Err(unsupported(name))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, ProtoError> {
+        Err(unsupported(name))
+    }
+}
+
+/// Serializes the elements of a repeated field.
+///
+/// Each element is written as a separate occurrence of the field,
+/// rather than packed, which every protobuf parser accepts.
+struct Repeated<'a> {
+    number: u32,
+    out: &'a mut Vec<u8>,
+}
+
+impl ser::SerializeSeq for Repeated<'_> {
+    type Ok = ();
+    type Error = ProtoError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ProtoError> {
+        value.serialize(FieldSerializer {
+            number: self.number,
+            out: self.out,
+            repeated: true,
+        })
+    }
+
+    fn end(self) -> Result<(), ProtoError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+//! Recording where each object came from.
+//!
+//! This module is available with the `provenance` feature. When it is
+//! enabled, every [`Table`] records, alongside each object added to
+//! it, when it was added, the source location of the call which added
+//! it, and the creator label in effect at the time. In long-lived
+//! contexts which many parts of a program add to, this answers the
+//! question of who created a surprising object. The record for an
+//! object in a single table is available from [`Table::provenance`].
+//!
+//! Passing `provenance` to the [`persian_rug`](crate::persian_rug)
+//! attribute implements [`ProvenanceOwner`] for each table of the
+//! context, so that records can be found with
+//! [`Context::provenance`]:
+//!
+//! ```rust
+//! use persian_rug::provenance::with_creator;
+//! use persian_rug::{contextual, persian_rug, Context};
+//!
+//! #[contextual(Rug)]
+//! struct Foo {
+//!   a: i32,
+//! }
+//!
+//! #[persian_rug(provenance)]
+//! struct Rug(#[table] Foo);
+//!
+//! let mut r = Rug(Default::default());
+//! let plain = r.add(Foo { a: 1 });
+//! let imported = with_creator("importer", || r.add(Foo { a: 2 }));
+//!
+//! assert_eq!(r.provenance(&plain).unwrap().creator(), None);
+//! let record = r.provenance(&imported).unwrap();
+//! assert_eq!(record.creator(), Some("importer"));
+//! assert_eq!(record.location().file(), file!());
+//! ```
+//!
+//! The location is found with [`#[track_caller]`][track_caller], so
+//! it is that of the call to `add` on the context or on a
+//! [`Mutator`](crate::Mutator), or to [`Table::push`]. Objects added
+//! from inside a helper function are attributed to the helper,
+//! unless it is itself marked `#[track_caller]`.
+//!
+//! Records are copied when a table is cloned, follow their objects
+//! when they are swapped, and are not included when a table is
+//! serialized.
+//!
+//! [track_caller]: https://doc.rust-lang.org/reference/attributes/codegen.html#the-track_caller-attribute
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::panic::Location;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::handles::HandleAllocator;
+use crate::storage::Storage;
+use crate::{Context, Contextual, Proxy, Table};
+
+/// Where an object came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Provenance {
+    created: SystemTime,
+    creator: Option<Arc<str>>,
+    location: &'static Location<'static>,
+}
+
+impl Provenance {
+    /// Describe an object being created now, by the caller.
+    #[track_caller]
+    pub(crate) fn here() -> Self {
+        Self {
+            created: SystemTime::now(),
+            creator: CREATOR.with(|creator| creator.borrow().clone()),
+            location: Location::caller(),
+        }
+    }
+
+    /// The time at which the object was added.
+    pub fn created(&self) -> SystemTime {
+        self.created
+    }
+
+    /// The creator label in effect when the object was added, if any.
+    ///
+    /// See [`with_creator`].
+    pub fn creator(&self) -> Option<&str> {
+        self.creator.as_deref()
+    }
+
+    /// The source location of the call which added the object.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+}
+
+thread_local! {
+    static CREATOR: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, labelling the objects it adds with `creator`.
+///
+/// The label applies to objects added on the current thread until
+/// `f` returns, and calls can be nested, in which case the innermost
+/// label applies.
+pub fn with_creator<R>(creator: impl Into<Arc<str>>, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Arc<str>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CREATOR.with(|creator| *creator.borrow_mut() = self.0.take());
+        }
+    }
+
+    let previous = CREATOR.with(|current| current.borrow_mut().replace(creator.into()));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// The creator label in effect on the current thread, if any.
+pub fn current_creator() -> Option<Arc<str>> {
+    CREATOR.with(|creator| creator.borrow().clone())
+}
+
+/// The provenance records of a table, by handle.
+#[derive(Clone, Default)]
+pub(crate) struct Records {
+    records: BTreeMap<u64, Provenance>,
+}
+
+impl Records {
+    #[track_caller]
+    pub(crate) fn record(&mut self, index: u64) {
+        self.records.insert(index, Provenance::here());
+    }
+
+    pub(crate) fn remove(&mut self, index: u64) {
+        self.records.remove(&index);
+    }
+
+    pub(crate) fn swap(&mut self, a: u64, b: u64) {
+        let first = self.records.remove(&a);
+        let second = self.records.remove(&b);
+        if let Some(record) = first {
+            self.records.insert(b, record);
+        }
+        if let Some(record) = second {
+            self.records.insert(a, record);
+        }
+    }
+}
+
+impl<T, S: Storage<T>, A: HandleAllocator> Table<T, S, A> {
+    /// Where the item stored for a [`Proxy`] came from.
+    ///
+    /// Returns [`None`] if the item is not stored, or if it was
+    /// stored without a record, for example by deserializing the
+    /// table.
+    pub fn provenance(&self, p: &Proxy<T>) -> Option<&Provenance> {
+        if !self.contains(p) {
+            return None;
+        }
+        self.provenance.records.get(&p.index)
+    }
+}
+
+/// A context which can report the provenance of objects of type `T`.
+///
+/// This is normally implemented with the `provenance` option of the
+/// [`persian_rug`](crate::persian_rug) macro.
+pub trait ProvenanceOwner<T>: Context
+where
+    T: Contextual<Context = Self>,
+{
+    /// Where the object for `what` came from.
+    fn provenance(&self, what: &Proxy<T>) -> Option<&Provenance>;
+}
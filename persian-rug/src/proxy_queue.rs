@@ -0,0 +1,210 @@
+use std::collections::VecDeque;
+
+use crate::{Proxy, ProxySet};
+
+/// A double-ended queue of [`Proxy`] objects, for use as the worklist
+/// of a traversal.
+///
+/// Walking a graph of objects means keeping a queue of the objects
+/// still to visit, and a set of those already found, so that each is
+/// visited only once. A queue created with
+/// [`deduplicated`](ProxyQueue::deduplicated) keeps both: each proxy
+/// it is given is remembered in a [`ProxySet`], and it is only queued
+/// the first time, even if it has since been taken off the queue.
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, Proxy, ProxyQueue};
+///
+/// #[contextual(Rug)]
+/// struct Node {
+///   name: &'static str,
+///   edges: Vec<Proxy<Node>>,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Node);
+///
+/// let mut r = Rug(Default::default());
+/// let d = r.add(Node { name: "d", edges: vec![] });
+/// let c = r.add(Node { name: "c", edges: vec![d] });
+/// let b = r.add(Node { name: "b", edges: vec![c, d] });
+/// let a = r.add(Node { name: "a", edges: vec![b, c] });
+/// r.get_mut(&d).edges.push(a);
+///
+/// let mut queue = ProxyQueue::deduplicated();
+/// queue.push_back(a);
+/// let mut order = Vec::new();
+/// while let Some(p) = queue.pop_front() {
+///     let node = r.get(&p);
+///     order.push(node.name);
+///     queue.extend(node.edges.iter().copied());
+/// }
+/// assert_eq!(order, vec!["a", "b", "c", "d"]);
+/// ```
+///
+/// Popping from the back instead of the front gives a depth-first
+/// order. A queue created with [`new`](ProxyQueue::new) does not
+/// remember what it has seen, and queues every proxy it is given.
+pub struct ProxyQueue<T> {
+    queue: VecDeque<Proxy<T>>,
+    seen: Option<ProxySet<T>>,
+}
+
+impl<T> ProxyQueue<T> {
+    /// Create an empty queue which queues every proxy it is given.
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            seen: None,
+        }
+    }
+
+    /// Create an empty queue which queues each proxy only the first
+    /// time it is given.
+    pub fn deduplicated() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            seen: Some(ProxySet::new()),
+        }
+    }
+
+    /// Check whether the queue was created with
+    /// [`deduplicated`](ProxyQueue::deduplicated).
+    pub fn is_deduplicated(&self) -> bool {
+        self.seen.is_some()
+    }
+
+    /// Note that `p` has been given to the queue, returning whether
+    /// it should be queued.
+    fn admit(&mut self, p: &Proxy<T>) -> bool {
+        match &mut self.seen {
+            Some(seen) if seen.contains(p) => false,
+            Some(seen) => {
+                seen.insert(*p);
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Add a proxy to the back of the queue.
+    ///
+    /// Returns `false`, leaving the queue unchanged, if the queue is
+    /// deduplicated and has already been given `p`.
+    pub fn push_back(&mut self, p: Proxy<T>) -> bool {
+        let admitted = self.admit(&p);
+        if admitted {
+            self.queue.push_back(p);
+        }
+        admitted
+    }
+
+    /// Add a proxy to the front of the queue.
+    ///
+    /// Returns `false`, leaving the queue unchanged, if the queue is
+    /// deduplicated and has already been given `p`.
+    pub fn push_front(&mut self, p: Proxy<T>) -> bool {
+        let admitted = self.admit(&p);
+        if admitted {
+            self.queue.push_front(p);
+        }
+        admitted
+    }
+
+    /// Take the proxy at the front of the queue.
+    pub fn pop_front(&mut self) -> Option<Proxy<T>> {
+        self.queue.pop_front()
+    }
+
+    /// Take the proxy at the back of the queue.
+    pub fn pop_back(&mut self) -> Option<Proxy<T>> {
+        self.queue.pop_back()
+    }
+
+    /// The number of proxies waiting in the queue.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Check whether no proxies are waiting in the queue.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Check whether the queue has ever been given `p`.
+    ///
+    /// For a queue which is not deduplicated, this only checks the
+    /// proxies waiting in the queue.
+    pub fn has_seen(&self, p: &Proxy<T>) -> bool {
+        match &self.seen {
+            Some(seen) => seen.contains(p),
+            None => self.queue.contains(p),
+        }
+    }
+
+    /// The set of every proxy a deduplicated queue has been given.
+    pub fn seen(&self) -> Option<&ProxySet<T>> {
+        self.seen.as_ref()
+    }
+
+    /// Iterate over the proxies waiting in the queue, from front to
+    /// back.
+    pub fn iter(&self) -> std::collections::vec_deque::Iter<'_, Proxy<T>> {
+        self.queue.iter()
+    }
+
+    /// Empty the queue, and forget which proxies it has seen.
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        if let Some(seen) = &mut self.seen {
+            *seen = ProxySet::new();
+        }
+    }
+}
+
+impl<T> Default for ProxyQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for ProxyQueue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+            seen: self.seen.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for ProxyQueue<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.queue.iter()).finish()
+    }
+}
+
+impl<T> Extend<Proxy<T>> for ProxyQueue<T> {
+    /// Add each proxy to the back of the queue, in order.
+    fn extend<I: IntoIterator<Item = Proxy<T>>>(&mut self, iter: I) {
+        for p in iter {
+            self.push_back(p);
+        }
+    }
+}
+
+impl<T> FromIterator<Proxy<T>> for ProxyQueue<T> {
+    fn from_iter<I: IntoIterator<Item = Proxy<T>>>(iter: I) -> Self {
+        let mut queue = Self::new();
+        queue.extend(iter);
+        queue
+    }
+}
+
+impl<'a, T> IntoIterator for &'a ProxyQueue<T> {
+    type Item = &'a Proxy<T>;
+    type IntoIter = std::collections::vec_deque::Iter<'a, Proxy<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
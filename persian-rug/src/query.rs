@@ -0,0 +1,141 @@
+use crate::Accessor;
+
+/// Iterate over joined objects from a context.
+///
+/// Code that follows links between objects tends to accumulate
+/// nested chains like `access.get(&access.get(&bar).foo)`. This macro
+/// lets you write the traversal declaratively instead, as a list of
+/// bindings, an optional filter, and a result expression:
+///
+/// ```text
+/// query!(access, (bindings...) if condition => result)
+/// ```
+///
+/// Each binding introduces a name for a shared reference to an object
+/// in the context, and takes one of three forms:
+/// - `name: Type` iterates over every `Type` in the context.
+/// - `name = expr` resolves the single [`Proxy`](crate::Proxy) given by
+///   `expr` (for example a field of an earlier binding).
+/// - `name in expr` iterates over the proxies yielded by `&expr` (for
+///   example a `Vec` or an `Option` of proxies held by an earlier
+///   binding), resolving each one.
+///
+/// Any of these forms can also bind the proxy of the object, by
+/// writing `proxy @ name` in place of `name`. This is useful when the
+/// result needs to refer back to the objects that were matched, for
+/// example to modify them afterwards.
+///
+/// Bindings are evaluated in order, so later bindings can refer to
+/// earlier ones. The result is an [`Iterator`] over the values of the
+/// result expression, for every combination of bindings for which the
+/// condition holds.
+///
+/// The first argument must be something implementing [`Accessor`];
+/// the iterator borrows it for as long as it lives.
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, query, Accessor, Context, Proxy};
+///
+/// #[contextual(Rug)]
+/// struct Foo {
+///   a: i32,
+/// }
+///
+/// #[contextual(Rug)]
+/// struct Bar {
+///   name: &'static str,
+///   foo: Proxy<Foo>,
+///   others: Vec<Proxy<Foo>>,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Foo, #[table] Bar);
+///
+/// fn positive_bars<A: Accessor<Context = Rug>>(access: A) -> Vec<&'static str> {
+///   query!(access, (bar: Bar, foo = bar.foo) if foo.a > 0 => bar.name).collect()
+/// }
+///
+/// let mut r = Rug(Default::default(), Default::default());
+/// let f1 = r.add(Foo { a: 1 });
+/// let f2 = r.add(Foo { a: -1 });
+/// r.add(Bar { name: "one", foo: f1, others: vec![f1, f2] });
+/// r.add(Bar { name: "two", foo: f2, others: vec![f2] });
+///
+/// assert_eq!(positive_bars(&r), vec!["one"]);
+///
+/// let pairs = query!(&r, (bar: Bar, other in bar.others) => (bar.name, other.a))
+///     .collect::<Vec<_>>();
+/// assert_eq!(pairs, vec![("one", 1), ("one", -1), ("two", -1)]);
+///
+/// let negative = query!(&r, (p @ foo: Foo) if foo.a < 0 => p).collect::<Vec<_>>();
+/// assert_eq!(negative, vec![f2]);
+/// ```
+///
+/// The condition and result are evaluated inside `move` closures, so
+/// values from the surrounding scope are moved into the query. Take a
+/// reference to anything you need to use again afterwards.
+#[macro_export]
+macro_rules! query {
+    ($access:expr, ($($bindings:tt)*) $(if $cond:expr)? => $body:expr) => {
+        $crate::__query_with(&$access, move |access| {
+            ::core::iter::IntoIterator::into_iter(
+                $crate::query!(@bind access; [$($bindings)*]; [$($cond)?]; $body)
+            )
+        })
+    };
+    (@bind $access:ident; [$name:ident : $ty:ty $(, $($rest:tt)*)?]; [$($cond:expr)?]; $body:expr) => {
+        $crate::Accessor::get_iter::<$ty>($access).flat_map(move |$name| {
+            $crate::query!(@bind $access; [$($($rest)*)?]; [$($cond)?]; $body)
+        })
+    };
+    (@bind $access:ident; [$proxy:ident @ $name:ident : $ty:ty $(, $($rest:tt)*)?]; [$($cond:expr)?]; $body:expr) => {
+        $crate::Accessor::get_proxy_iter::<$ty>($access).flat_map(move |$proxy| {
+            let $proxy = *$proxy;
+            let $name = $crate::Accessor::get($access, &$proxy);
+            $crate::query!(@bind $access; [$($($rest)*)?]; [$($cond)?]; $body)
+        })
+    };
+    (@bind $access:ident; [$proxy:ident @ $name:ident = $link:expr $(, $($rest:tt)*)?]; [$($cond:expr)?]; $body:expr) => {{
+        let $proxy = $link;
+        let $name = $crate::Accessor::get($access, &$proxy);
+        $crate::query!(@bind $access; [$($($rest)*)?]; [$($cond)?]; $body)
+    }};
+    (@bind $access:ident; [$proxy:ident @ $name:ident in $links:expr $(, $($rest:tt)*)?]; [$($cond:expr)?]; $body:expr) => {
+        ::core::iter::IntoIterator::into_iter(&$links).flat_map(move |link| {
+            let $proxy = *link;
+            let $name = $crate::Accessor::get($access, &$proxy);
+            $crate::query!(@bind $access; [$($($rest)*)?]; [$($cond)?]; $body)
+        })
+    };
+    (@bind $access:ident; [$name:ident = $link:expr $(, $($rest:tt)*)?]; [$($cond:expr)?]; $body:expr) => {{
+        let $name = $crate::Accessor::get($access, &$link);
+        $crate::query!(@bind $access; [$($($rest)*)?]; [$($cond)?]; $body)
+    }};
+    (@bind $access:ident; [$name:ident in $links:expr $(, $($rest:tt)*)?]; [$($cond:expr)?]; $body:expr) => {
+        ::core::iter::IntoIterator::into_iter(&$links).flat_map(move |link| {
+            let $name = $crate::Accessor::get($access, link);
+            $crate::query!(@bind $access; [$($($rest)*)?]; [$($cond)?]; $body)
+        })
+    };
+    (@bind $access:ident; []; [$cond:expr]; $body:expr) => {
+        if $cond {
+            ::core::option::Option::Some($body)
+        } else {
+            ::core::option::Option::None
+        }
+    };
+    (@bind $access:ident; []; []; $body:expr) => {
+        ::core::option::Option::Some($body)
+    };
+}
+
+/// Support for [`query!`]: pins down the type of the accessor
+/// reference so the generated closures can be checked.
+#[doc(hidden)]
+pub fn __query_with<'a, A, F, R>(access: &'a A, f: F) -> R
+where
+    A: Accessor,
+    F: FnOnce(&'a A) -> R,
+{
+    f(access)
+}
@@ -0,0 +1,192 @@
+//! Limiting the number of objects a context holds.
+//!
+//! A service which builds a context from untrusted input, such as a
+//! document uploaded by one of many tenants, can be made to grow
+//! without bound by a single malicious or malformed request. A
+//! [`Quota`] sets a ceiling on the objects a context may hold, both
+//! for each type and in total. It is kept as a `#[resource]` field of
+//! the context, and checked by [`Context::try_add`] and
+//! [`Mutator::try_add`], which fail with a [`QuotaExceeded`] error
+//! instead of adding an object which would break a limit:
+//!
+//! ```rust
+//! use persian_rug::quota::{Quota, QuotaExceeded};
+//! use persian_rug::{contextual, persian_rug, Context};
+//!
+//! #[contextual(Rug)]
+//! struct Line {
+//!   text: String,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug {
+//!   #[table]
+//!   lines: Line,
+//!   #[resource]
+//!   quota: Quota,
+//! }
+//!
+//! let mut r = Rug {
+//!     lines: Default::default(),
+//!     quota: Quota::new().with_limit::<Line>(2),
+//! };
+//!
+//! let parsed = "one\ntwo\nthree"
+//!     .lines()
+//!     .map(|text| r.try_add(Line { text: text.to_string() }))
+//!     .collect::<Result<Vec<_>, _>>();
+//! assert!(matches!(parsed, Err(QuotaExceeded::Table { limit: 2, .. })));
+//! assert_eq!(r.get_iter::<Line>().count(), 2);
+//! ```
+//!
+//! Objects added with [`Context::add`] are not checked, but they do
+//! count towards the limits. Objects marked as deleted do not count.
+//! The total is found with [`Context::visit_all`], and so is only
+//! enforced for contexts created with the
+//! [`persian_rug`](crate::persian_rug) macro.
+//!
+//! [`Context::try_add`]: crate::Context::try_add
+//! [`Context::add`]: crate::Context::add
+//! [`Context::visit_all`]: crate::Context::visit_all
+//! [`Mutator::try_add`]: crate::Mutator::try_add
+
+use std::collections::BTreeMap;
+
+use crate::visit::ContextVisitor;
+use crate::{Context, Contextual, Owner, Proxy, ResourceOwner};
+
+/// The limits on the objects a context may hold.
+///
+/// See the [module documentation](self) for details.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Quota {
+    total: Option<usize>,
+    limits: BTreeMap<&'static str, usize>,
+}
+
+impl Quota {
+    /// Create a quota with no limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the number of objects of all types together.
+    pub fn with_total(mut self, limit: usize) -> Self {
+        self.total = Some(limit);
+        self
+    }
+
+    /// Limit the number of objects of type `T`.
+    pub fn with_limit<T>(mut self, limit: usize) -> Self {
+        self.limits.insert(std::any::type_name::<T>(), limit);
+        self
+    }
+
+    /// Change the limit on the number of objects of all types
+    /// together, or remove it.
+    pub fn set_total(&mut self, limit: Option<usize>) {
+        self.total = limit;
+    }
+
+    /// Change the limit on the number of objects of type `T`, or
+    /// remove it.
+    pub fn set_limit<T>(&mut self, limit: Option<usize>) {
+        let name = std::any::type_name::<T>();
+        match limit {
+            Some(limit) => self.limits.insert(name, limit),
+            None => self.limits.remove(name),
+        };
+    }
+
+    /// The limit on the number of objects of all types together.
+    pub fn total(&self) -> Option<usize> {
+        self.total
+    }
+
+    /// The limit on the number of objects of type `T`.
+    pub fn limit<T>(&self) -> Option<usize> {
+        self.limits.get(std::any::type_name::<T>()).copied()
+    }
+
+    /// Check that one more object of type `T` can be added to
+    /// `context`.
+    pub fn check<C, T>(&self, context: &C) -> Result<(), QuotaExceeded>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        if let Some(limit) = self.limit::<T>() {
+            if Owner::<T>::len(context) >= limit {
+                return Err(QuotaExceeded::Table {
+                    type_name: std::any::type_name::<T>(),
+                    limit,
+                });
+            }
+        }
+        if let Some(limit) = self.total {
+            let mut count = Count(0);
+            context.visit_all(&mut count);
+            if count.0 >= limit {
+                return Err(QuotaExceeded::Total { limit });
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Count(usize);
+
+impl<C: Context> ContextVisitor<C> for Count {
+    fn table<T>(&mut self, context: &C)
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        self.0 += Owner::<T>::len(context);
+    }
+}
+
+/// The error returned when adding an object would break a [`Quota`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuotaExceeded {
+    /// The table for one type is full.
+    Table {
+        /// The full name of the type.
+        type_name: &'static str,
+        /// The number of objects of that type allowed.
+        limit: usize,
+    },
+    /// The context as a whole is full.
+    Total {
+        /// The number of objects allowed.
+        limit: usize,
+    },
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuotaExceeded::Table { type_name, limit } => write!(
+                f,
+                "cannot add another {}: the limit is {} objects of that type",
+                type_name, limit
+            ),
+            QuotaExceeded::Total { limit } => write!(
+                f,
+                "cannot add another object: the limit is {} objects in total",
+                limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+pub(crate) fn try_add<C, T>(context: &mut C, value: T) -> Result<Proxy<T>, QuotaExceeded>
+where
+    C: ResourceOwner<Quota> + Owner<T> + Sized,
+    T: Contextual<Context = C>,
+{
+    ResourceOwner::<Quota>::get_resource(context).check(context)?;
+    Ok(Owner::add(context, value))
+}
@@ -0,0 +1,121 @@
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+/// A table or context shared by readers and writers, by read, copy
+/// and update.
+///
+/// Readers take a snapshot with [`load`](Rcu::load), which is an
+/// [`Arc`] of the current version. The lock guarding the current
+/// version is only held while the [`Arc`] is cloned, so readers never
+/// wait for a writer to finish, and can hold their snapshot for as
+/// long as they like. Writers make their changes to a copy of the
+/// current version with [`update`](Rcu::update), and then install the
+/// copy for readers who load afterwards.
+///
+/// This suits contexts which are read far more often than they are
+/// changed. Compared with a single [`RwLock`], reads are never held
+/// up by writes; compared with splitting the objects over several
+/// contexts, nothing about how they are stored has to change. The
+/// cost is that every write copies the whole table or context, so
+/// writes should be few, or batched into a single update.
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, Rcu};
+///
+/// #[derive(Clone)]
+/// #[contextual(Rug)]
+/// struct Setting {
+///   name: &'static str,
+///   value: u32,
+/// }
+///
+/// #[derive(Clone)]
+/// #[persian_rug]
+/// struct Rug(#[table] Setting);
+///
+/// let rug = Rcu::new(Rug(Default::default()));
+/// let limit = rug.update(|r| r.add(Setting { name: "limit", value: 10 }));
+///
+/// let before = rug.load();
+/// rug.update(|r| r.get_mut(&limit).value = 20);
+///
+/// // Snapshots taken before an update are unchanged by it.
+/// assert_eq!(before.get(&limit).value, 10);
+/// assert_eq!(rug.load().get(&limit).value, 20);
+/// ```
+///
+/// Updates are made one at a time: a writer waits for any other
+/// update to finish before copying the current version, so no change
+/// is lost. A writer which panics leaves the current version as it
+/// was.
+pub struct Rcu<C> {
+    current: RwLock<Arc<C>>,
+    writer: Mutex<()>,
+}
+
+impl<C> Rcu<C> {
+    /// Share `value` between readers and writers.
+    pub fn new(value: C) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(value)),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Take a snapshot of the current version.
+    pub fn load(&self) -> Arc<C> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Install `value` as the current version, returning the version
+    /// it replaced.
+    pub fn store(&self, value: C) -> Arc<C> {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        self.install(Arc::new(value))
+    }
+
+    fn install(&self, value: Arc<C>) -> Arc<C> {
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        std::mem::replace(&mut *current, value)
+    }
+}
+
+impl<C: Clone> Rcu<C> {
+    /// Change a copy of the current version with `f`, and install it.
+    ///
+    /// Readers see either none of the changes made by `f`, or all of
+    /// them.
+    pub fn update<R>(&self, f: impl FnOnce(&mut C) -> R) -> R {
+        match self.try_update(|value| Ok::<_, std::convert::Infallible>(f(value))) {
+            Ok(res) => res,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Change a copy of the current version with `f`, and install it
+    /// if `f` succeeds.
+    ///
+    /// If `f` returns an error, the copy is thrown away and the
+    /// current version is left as it was.
+    pub fn try_update<R, E>(&self, f: impl FnOnce(&mut C) -> Result<R, E>) -> Result<R, E> {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut next = C::clone(&self.load());
+        let res = f(&mut next)?;
+        self.install(Arc::new(next));
+        Ok(res)
+    }
+}
+
+impl<C: Default> Default for Rcu<C> {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<C: std::fmt::Debug> std::fmt::Debug for Rcu<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Rcu").field(&self.load()).finish()
+    }
+}
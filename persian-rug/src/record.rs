@@ -0,0 +1,445 @@
+//! Recording the changes made to a context, and replaying them.
+//!
+//! This module is available with the `borsh` feature. A [`Recorder`]
+//! is a [`Mutator`] which passes everything through to a context,
+//! while capturing each object added and each object borrowed mutably
+//! as an [`Operation`] holding the object's [borsh] encoding.
+//! The resulting [`Recording`] can itself be saved with borsh, and
+//! replayed later to reproduce the same context, with the same
+//! handles, whichever [`HandleAllocator`](crate::handles::HandleAllocator)
+//! issued them. This makes it possible to capture a failing scenario in
+//! production and reproduce it exactly in a test.
+//!
+//! The types to record are registered with a [`Codec`], which is
+//! needed both to record and to replay:
+//!
+//! ```rust
+//! use persian_rug::borsh::{BorshDeserialize, BorshSerialize};
+//! use persian_rug::record::Codec;
+//! use persian_rug::{contextual, persian_rug, Context, Mutator, Proxy};
+//!
+//! #[derive(BorshSerialize, BorshDeserialize)]
+//! #[borsh(crate = "persian_rug::borsh")]
+//! #[contextual(Rug)]
+//! struct Foo {
+//!   a: i32,
+//!   next: Option<Proxy<Foo>>,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Foo);
+//!
+//! let codec = Codec::<Rug>::new().register::<Foo>();
+//!
+//! let mut r = Rug(Default::default());
+//! let mut rec = codec.recorder(&mut r);
+//! let f1 = rec.add(Foo { a: 1, next: None });
+//! let f2 = rec.add(Foo { a: 2, next: Some(f1) });
+//! rec.get_mut(&f1).next = Some(f2);
+//! let recording = rec.finish().unwrap();
+//!
+//! let mut replayed = Rug(Default::default());
+//! codec.replay(&recording, &mut replayed).unwrap();
+//! assert_eq!(replayed.get(&f1).next, Some(f2));
+//! assert_eq!(replayed.get(&f2).a, 2);
+//! ```
+//!
+//! An object borrowed mutably is recorded with its value at the time
+//! of the next call to the recorder, or when the recorder is
+//! finished, so that the recording holds the result of each change.
+//! Types are identified by [`std::any::type_name`], so recordings
+//! should be replayed by the same build of a program that made them.
+//!
+//! # Journals
+//!
+//! A recorder made with [`Codec::journal`] writes each operation to a
+//! log as soon as it is recorded, rather than keeping it in memory.
+//! Each operation is written whole, and the log flushed, so a server
+//! which journals every change to a file can rebuild its state after
+//! a crash with [`Context::replay`], and the
+//! file doubles as an audit trail:
+//!
+//! ```rust
+//! use persian_rug::borsh::{BorshDeserialize, BorshSerialize};
+//! use persian_rug::record::Codec;
+//! use persian_rug::{contextual, persian_rug, Context, Mutator};
+//!
+//! #[derive(BorshSerialize, BorshDeserialize)]
+//! #[borsh(crate = "persian_rug::borsh")]
+//! #[contextual(Rug)]
+//! struct Order {
+//!   item: String,
+//!   shipped: bool,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Order);
+//!
+//! let codec = Codec::<Rug>::new().register::<Order>();
+//! let mut log = Vec::new();
+//!
+//! let mut r = Rug(Default::default());
+//! let mut journal = codec.journal(&mut r, &mut log);
+//! let order = journal.add(Order { item: "lamp".to_string(), shipped: false });
+//! journal.get_mut(&order).shipped = true;
+//! journal.finish().unwrap();
+//!
+//! let mut recovered = Rug(Default::default());
+//! assert_eq!(recovered.replay(&codec, log.as_slice()).unwrap(), 2);
+//! assert!(recovered.get(&order).shipped);
+//! ```
+//!
+//! A change made through a mutable borrow is only written at the next
+//! call to the journal, so the last such change can be lost in a
+//! crash. If the program stops partway through writing an operation,
+//! the incomplete operation at the end of the log is ignored when it
+//! is replayed.
+
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{
+    Context, Contextual, Mutator, Owner, Proxy, TableIterator, TableMutIterator, TableProxyIterator,
+};
+
+/// A single recorded change.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum Operation {
+    /// An object was added, and given the handle `index`.
+    Add {
+        /// The name of the type of the object.
+        ty: String,
+        /// The handle issued for the object.
+        index: u64,
+        /// The borsh encoding of the object.
+        value: Vec<u8>,
+    },
+    /// An object was modified, and now has the given value.
+    Set {
+        /// The name of the type of the object.
+        ty: String,
+        /// The handle of the object.
+        index: u64,
+        /// The borsh encoding of the object.
+        value: Vec<u8>,
+    },
+}
+
+/// A sequence of recorded changes to a context.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+    operations: Vec<Operation>,
+}
+
+impl Recording {
+    /// The recorded changes, in the order they were made.
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// The number of recorded changes.
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Check if no changes were recorded.
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+struct CodecEntry<C> {
+    encode: fn(&C, (u64, u32)) -> Result<Vec<u8>>,
+    apply: fn(&mut C, &Operation) -> Result<()>,
+}
+
+/// The types which can be recorded for a context of type `C`.
+pub struct Codec<C> {
+    types: BTreeMap<&'static str, CodecEntry<C>>,
+}
+
+impl<C: Context> Codec<C> {
+    /// Create a codec with no types registered.
+    pub fn new() -> Self {
+        Self {
+            types: BTreeMap::new(),
+        }
+    }
+
+    /// Register a type to be recorded.
+    pub fn register<T>(mut self) -> Self
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + BorshSerialize + BorshDeserialize,
+    {
+        self.types.insert(
+            std::any::type_name::<T>(),
+            CodecEntry {
+                encode: encode::<C, T>,
+                apply: apply::<C, T>,
+            },
+        );
+        self
+    }
+
+    /// Start recording the changes made to `context`.
+    pub fn recorder<'a>(&'a self, context: &'a mut C) -> Recorder<'a, C> {
+        Recorder {
+            context,
+            codec: self,
+            operations: Vec::new(),
+            journal: None,
+            pending: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Start recording the changes made to `context`, writing each
+    /// one to `log` as it is recorded.
+    ///
+    /// The operations are not kept, so the [`Recording`] returned by
+    /// [`Recorder::finish`] is empty. Once writing to the log fails,
+    /// nothing more is written to it, and the error is returned by
+    /// [`Recorder::finish`]. See the [module documentation](self) for
+    /// details.
+    pub fn journal<'a>(&'a self, context: &'a mut C, log: &'a mut dyn Write) -> Recorder<'a, C> {
+        Recorder {
+            journal: Some(log),
+            ..self.recorder(context)
+        }
+    }
+
+    /// Apply the changes in `recording` to `context`.
+    ///
+    /// The context should be in the same state as the one which was
+    /// recorded when recording began. Each added object is stored
+    /// under the handle recorded for it, and it is an error for that
+    /// handle to be in use already.
+    pub fn replay(&self, recording: &Recording, context: &mut C) -> Result<()> {
+        for op in recording.operations.iter() {
+            let (Operation::Add { ty, .. } | Operation::Set { ty, .. }) = op;
+            let entry = self.types.get(ty.as_str()).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("type {} is not registered", ty),
+                )
+            })?;
+            (entry.apply)(context, op)?;
+        }
+        Ok(())
+    }
+
+    /// Apply the changes written to `log` by a [journal](Codec::journal)
+    /// to `context`, returning the number of changes applied.
+    ///
+    /// As for [`replay`](Codec::replay), the context should be in the
+    /// same state as the one which was journaled when journaling
+    /// began. An incomplete operation at the end of the log is
+    /// ignored.
+    pub fn replay_log(&self, mut log: impl Read, context: &mut C) -> Result<usize> {
+        let mut bytes = Vec::new();
+        log.read_to_end(&mut bytes)?;
+        let mut operations = Vec::new();
+        let mut rest = bytes.as_slice();
+        while let Some((len, tail)) = rest.split_first_chunk::<4>() {
+            let Some((op, tail)) = tail.split_at_checked(u32::from_le_bytes(*len) as usize) else {
+                break;
+            };
+            operations.push(Operation::try_from_slice(op)?);
+            rest = tail;
+        }
+        let count = operations.len();
+        self.replay(&Recording { operations }, context)?;
+        Ok(count)
+    }
+
+    fn entry<T>(&self) -> &CodecEntry<C> {
+        let ty = std::any::type_name::<T>();
+        self.types
+            .get(ty)
+            .unwrap_or_else(|| panic!("type {} is not registered for recording", ty))
+    }
+}
+
+impl<C: Context> Default for Codec<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn encode<C, T>(context: &C, key: (u64, u32)) -> Result<Vec<u8>>
+where
+    C: Owner<T>,
+    T: Contextual<Context = C> + BorshSerialize,
+{
+    let p = Proxy::from_key(key);
+    borsh::to_vec(Owner::get(context, &p))
+}
+
+fn apply<C, T>(context: &mut C, op: &Operation) -> Result<()>
+where
+    C: Owner<T>,
+    T: Contextual<Context = C> + BorshDeserialize,
+{
+    match op {
+        Operation::Add { index, value, .. } => {
+            Owner::insert_with_handle(context, *index, T::try_from_slice(value)?)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        }
+        Operation::Set { index, value, .. } => {
+            // A handle may have been reused, so look for its current
+            // generation.
+            let p = Owner::<T>::proxy_for(context, *index).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("no object with handle {} to modify", index),
+                )
+            })?;
+            *Owner::get_mut(context, &p) = T::try_from_slice(value)?;
+        }
+    }
+    Ok(())
+}
+
+/// A [`Mutator`] which records the changes made through it.
+///
+/// This is created by [`Codec::recorder`].
+///
+/// # Panics
+///
+/// Adding or mutably borrowing an object of a type that was not
+/// registered with the codec panics.
+pub struct Recorder<'a, C> {
+    context: &'a mut C,
+    codec: &'a Codec<C>,
+    operations: Vec<Operation>,
+    journal: Option<&'a mut dyn Write>,
+    pending: Vec<(&'static str, (u64, u32))>,
+    error: Option<Error>,
+}
+
+impl<C: Context> Recorder<'_, C> {
+    /// Stop recording, returning the changes made.
+    ///
+    /// Fails if any object could not be encoded.
+    pub fn finish(mut self) -> Result<Recording> {
+        self.flush();
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(Recording {
+                operations: self.operations,
+            }),
+        }
+    }
+
+    /// Record the current values of the objects borrowed mutably.
+    fn flush(&mut self) {
+        for (ty, key) in std::mem::take(&mut self.pending) {
+            let entry = &self.codec.types[ty];
+            self.record((entry.encode)(self.context, key), |value| Operation::Set {
+                ty: ty.to_string(),
+                index: key.0,
+                value,
+            });
+        }
+    }
+
+    fn record(&mut self, value: Result<Vec<u8>>, op: impl FnOnce(Vec<u8>) -> Operation) {
+        // After a failed write, the log may end partway through an
+        // operation, and anything written after it would be misread.
+        if self.journal.is_some() && self.error.is_some() {
+            return;
+        }
+        let res = value.and_then(|value| match self.journal.as_mut() {
+            Some(journal) => write_operation(&mut **journal, &op(value)),
+            None => {
+                self.operations.push(op(value));
+                Ok(())
+            }
+        });
+        if let Err(e) = res {
+            self.error.get_or_insert(e);
+        }
+    }
+}
+
+/// Write one operation to a journal, prefixed with its length, so
+/// that an incomplete operation at the end of the log can be spotted.
+fn write_operation(log: &mut dyn Write, op: &Operation) -> Result<()> {
+    let bytes = borsh::to_vec(op)?;
+    log.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    log.write_all(&bytes)?;
+    log.flush()
+}
+
+impl<C: Context> Mutator for Recorder<'_, C> {
+    type Context = C;
+
+    #[track_caller]
+    fn add<T>(&mut self, value: T) -> Proxy<T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        self.flush();
+        let entry = self.codec.entry::<T>();
+        let p = Owner::add(self.context, value);
+        let value = (entry.encode)(self.context, p.key());
+        self.record(value, |value| Operation::Add {
+            ty: std::any::type_name::<T>().to_string(),
+            index: p.index,
+            value,
+        });
+        p
+    }
+
+    fn get<T>(&self, what: &Proxy<T>) -> &T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        Owner::get(self.context, what)
+    }
+
+    fn get_mut<T>(&mut self, what: &Proxy<T>) -> &mut T
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        self.flush();
+        self.codec.entry::<T>();
+        self.pending.push((std::any::type_name::<T>(), what.key()));
+        Owner::get_mut(self.context, what)
+    }
+
+    fn get_iter<T>(&self) -> TableIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        Owner::get_iter(self.context)
+    }
+
+    fn get_iter_mut<T>(&mut self) -> TableMutIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        self.flush();
+        self.codec.entry::<T>();
+        let ty = std::any::type_name::<T>();
+        self.pending
+            .extend(Owner::<T>::get_proxy_iter(self.context).map(|p| (ty, p.key())));
+        Owner::get_iter_mut(self.context)
+    }
+
+    fn get_proxy_iter<T>(&self) -> TableProxyIterator<'_, T>
+    where
+        Self::Context: Owner<T>,
+        T: Contextual<Context = Self::Context>,
+    {
+        Owner::get_proxy_iter(self.context)
+    }
+}
@@ -0,0 +1,254 @@
+//! Finding the objects which link to an object.
+//!
+//! Proxies only lead one way: an object knows what it links to, but
+//! not what links to it. Passing `referrers` to the
+//! [`persian_rug`](crate::persian_rug) attribute makes a context
+//! maintain a reverse index of the links between its objects, so that
+//! [`Accessor::referrers`](crate::Accessor::referrers) can list every
+//! object which holds a link to a given one. This is the starting
+//! point for working out the impact of a change, or for deciding
+//! whether an object can safely be discarded.
+//!
+//! Every type stored in such a context must implement [`Links`],
+//! usually by marking its link fields with `#[link]`:
+//!
+//! ```rust
+//! use persian_rug::{contextual, persian_rug, Accessor, AnyProxy, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Author {
+//!   name: &'static str,
+//! }
+//!
+//! #[contextual(Rug)]
+//! struct Book {
+//!   #[link]
+//!   authors: Vec<Proxy<Author>>,
+//! }
+//!
+//! #[contextual(Rug)]
+//! struct Review {
+//!   #[link]
+//!   book: Proxy<Book>,
+//!   #[link]
+//!   reviewer: Proxy<Author>,
+//! }
+//!
+//! impl persian_rug::Links for Author {}
+//!
+//! #[persian_rug(referrers)]
+//! struct Rug(#[table] Author, #[table] Book, #[table] Review);
+//!
+//! let mut r = Rug(Default::default(), Default::default(), Default::default());
+//! let ann = r.add(Author { name: "Ann" });
+//! let bob = r.add(Author { name: "Bob" });
+//! let book = r.add(Book { authors: vec![ann] });
+//! let review = r.add(Review { book, reviewer: bob });
+//!
+//! assert_eq!((&r).referrers(&ann), vec![AnyProxy::from(book)]);
+//! assert_eq!((&r).referrers(&bob), vec![AnyProxy::from(review)]);
+//!
+//! r.get_mut(&book).authors.push(bob);
+//! assert_eq!(
+//!     (&r).referrers(&bob),
+//!     vec![AnyProxy::from(book), AnyProxy::from(review)]
+//! );
+//! ```
+//!
+//! The index is held by the storage of each table, which is wrapped
+//! in a [`LinkStorage`]. As with
+//! [full-text search](crate::search), objects which are inserted or
+//! borrowed mutably are reindexed lazily, the next time the index is
+//! consulted.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::lock::Lock;
+use crate::storage::{sealed, Entries, EntriesMut, MapStorage, Storage};
+use crate::{AnyProxy, Context, Links, Proxy};
+
+#[derive(Default)]
+struct Index {
+    outgoing: BTreeMap<u64, BTreeSet<AnyProxy>>,
+    incoming: BTreeMap<AnyProxy, BTreeSet<u64>>,
+    stale: BTreeSet<u64>,
+    rebuild: bool,
+}
+
+impl Index {
+    fn unindex(&mut self, index: u64) {
+        for target in self.outgoing.remove(&index).into_iter().flatten() {
+            if let Some(sources) = self.incoming.get_mut(&target) {
+                sources.remove(&index);
+                if sources.is_empty() {
+                    self.incoming.remove(&target);
+                }
+            }
+        }
+    }
+
+    fn index<T: Links>(&mut self, index: u64, value: &T) {
+        let mut targets = BTreeSet::new();
+        value.for_each_link(&mut |target| {
+            targets.insert(target);
+        });
+        for target in targets.iter() {
+            self.incoming.entry(*target).or_default().insert(index);
+        }
+        self.outgoing.insert(index, targets);
+    }
+
+    fn refresh<T: Links, S: Storage<T>>(&mut self, storage: &S) {
+        if self.rebuild {
+            self.outgoing.clear();
+            self.incoming.clear();
+            for (p, value) in storage.entries() {
+                self.index(p.index, value);
+            }
+            self.rebuild = false;
+        } else {
+            for index in std::mem::take(&mut self.stale) {
+                self.unindex(index);
+                if let Some(value) = storage.get(index) {
+                    self.index(index, value);
+                }
+            }
+        }
+        self.stale.clear();
+    }
+}
+
+/// Storage which maintains an index of the objects linking to each
+/// object.
+///
+/// The objects themselves are held in another storage, `S`. Objects
+/// which are inserted or borrowed mutably are reindexed lazily, when
+/// the index is next consulted.
+pub struct LinkStorage<T, S = MapStorage<T>> {
+    _marker: core::marker::PhantomData<T>,
+    inner: S,
+    index: Lock<Index>,
+}
+
+impl<T, S: Storage<T>> LinkStorage<T, S> {
+    /// Index the objects held in `inner`.
+    pub fn new(inner: S) -> Self {
+        Self {
+            _marker: Default::default(),
+            inner,
+            index: Lock::new(Index {
+                rebuild: true,
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn index_mut(&mut self) -> &mut Index {
+        self.index.get_mut()
+    }
+}
+
+impl<T, S: Storage<T> + Default> Default for LinkStorage<T, S> {
+    fn default() -> Self {
+        Self::new(S::default())
+    }
+}
+
+impl<T, S: Storage<T> + Clone> Clone for LinkStorage<T, S> {
+    fn clone(&self) -> Self {
+        Self::new(self.inner.clone())
+    }
+}
+
+impl<T, S> sealed::Sealed for LinkStorage<T, S> {}
+
+impl<T, S: Storage<T>> Storage<T> for LinkStorage<T, S> {
+    fn insert(&mut self, proxy: Proxy<T>, value: T) -> Option<T> {
+        self.index_mut().stale.insert(proxy.index);
+        self.inner.insert(proxy, value)
+    }
+
+    fn get(&self, index: u64) -> Option<&T> {
+        self.inner.get(index)
+    }
+
+    fn get_mut(&mut self, index: u64) -> Option<&mut T> {
+        self.index_mut().stale.insert(index);
+        self.inner.get_mut(index)
+    }
+
+    fn proxy(&self, index: u64) -> Option<&Proxy<T>> {
+        self.inner.proxy(index)
+    }
+
+    fn proxy_from(&self, index: u64) -> Option<&Proxy<T>> {
+        self.inner.proxy_from(index)
+    }
+
+    fn remove(&mut self, index: u64) -> Option<T> {
+        self.index_mut().stale.insert(index);
+        self.inner.remove(index)
+    }
+
+    fn swap(&mut self, a: u64, b: u64) -> bool {
+        let index = self.index_mut();
+        index.stale.insert(a);
+        index.stale.insert(b);
+        self.inner.swap(a, b)
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn entries(&self) -> Entries<'_, T> {
+        self.inner.entries()
+    }
+
+    fn entries_mut(&mut self) -> EntriesMut<'_, T> {
+        self.index_mut().rebuild = true;
+        self.inner.entries_mut()
+    }
+
+    fn into_entries(self) -> Vec<(Proxy<T>, T)> {
+        self.inner.into_entries()
+    }
+}
+
+/// Storage which can list the objects that link to a target.
+///
+/// This is implemented by [`LinkStorage`], and by storage wrapping
+/// it.
+pub trait LinkIndex<T> {
+    /// Append the proxies of the stored objects which link to
+    /// `target` to `out`, in handle order.
+    fn referrers_of(&self, target: &AnyProxy, out: &mut Vec<AnyProxy>);
+}
+
+impl<T: Links + 'static, S: Storage<T>> LinkIndex<T> for LinkStorage<T, S> {
+    fn referrers_of(&self, target: &AnyProxy, out: &mut Vec<AnyProxy>) {
+        let mut index = self.index.lock();
+        index.refresh(&self.inner);
+        out.extend(
+            index
+                .incoming
+                .get(target)
+                .into_iter()
+                .flatten()
+                .filter_map(|index| self.inner.proxy(*index))
+                .map(|p| AnyProxy::new(*p)),
+        );
+    }
+}
+
+/// A context which maintains an index of the links between its
+/// objects.
+///
+/// Implementations are generated by the
+/// [`persian_rug`](crate::persian_rug) attribute when given the
+/// `referrers` option.
+pub trait Referrers: Context {
+    /// The proxies of all the objects which link to `target`, grouped
+    /// by table and in handle order within each table.
+    fn referrers_of(&self, target: &AnyProxy) -> Vec<AnyProxy>;
+}
@@ -0,0 +1,392 @@
+//! Owned snapshots of objects, with their proxies resolved.
+//!
+//! Serializers and template engines generally want a self-contained
+//! value, rather than an object and a context to look its proxies up
+//! in. The [`Resolve`] derive macro generates, for a type `Foo`, a
+//! type `FooResolved` with the same fields, except that fields
+//! marked `#[resolve]` have their proxies replaced by the resolved
+//! objects they point to, recursively. Unmarked fields are cloned.
+//!
+//! ```rust
+//! use persian_rug::{contextual, persian_rug, Context, Proxy, Resolve};
+//!
+//! #[derive(Resolve)]
+//! #[contextual(Rug)]
+//! struct Person {
+//!   name: String,
+//!   #[resolve]
+//!   manager: Option<Proxy<Person>>,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Person);
+//!
+//! let mut r = Rug(Default::default());
+//! let boss = r.add(Person { name: "Alice".to_string(), manager: None });
+//! let worker = r.add(Person { name: "Bob".to_string(), manager: Some(boss) });
+//!
+//! let resolved = r.get(&worker).resolve(&&r);
+//! assert_eq!(resolved.name, "Bob");
+//! assert_eq!(resolved.manager.unwrap().value().unwrap().name, "Alice");
+//! ```
+//!
+//! Fields marked `#[resolve]` must implement [`ResolveField`], which
+//! is provided for [`Proxy`] to any type implementing [`Resolve`], and
+//! for [`Option`], [`Vec`] and [`Box`] of such fields. The resolved
+//! type can be given derives of its own with
+//! `#[resolve(derive(...))]` on the original type.
+//!
+//! An object reachable along more than one path is resolved once for
+//! each path. Objects reachable from themselves cannot be resolved
+//! completely, so a [`Resolver`] has a [`CyclePolicy`] which decides
+//! what happens when a cycle is found; it can also limit the depth
+//! of the resolution.
+//!
+//! The [`Absorb`] derive macro provides the inverse operation: it
+//! takes a resolved value, such as one built from the input to an
+//! API, and adds it to a context, adding the nested objects it
+//! contains along the way. Proxies which were [cut](Resolved::Cut)
+//! are kept as they are.
+//!
+//! ```rust
+//! use persian_rug::resolve::Resolved;
+//! use persian_rug::{contextual, persian_rug, Absorb, Context, Proxy, Resolve};
+//!
+//! #[derive(Absorb, Resolve)]
+//! #[contextual(Rug)]
+//! struct Tag {
+//!   name: String,
+//! }
+//!
+//! #[derive(Absorb, Resolve)]
+//! #[contextual(Rug)]
+//! struct Post {
+//!   title: String,
+//!   #[resolve]
+//!   tags: Vec<Proxy<Tag>>,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Tag, #[table] Post);
+//!
+//! let mut r = Rug(Default::default(), Default::default());
+//! let input = PostResolved {
+//!     title: "Hello".to_string(),
+//!     tags: vec![Resolved::new(TagResolved { name: "news".to_string() })],
+//! };
+//! let post = Post::absorb(input, &mut &mut r);
+//!
+//! assert_eq!(r.get(&r.get(&post).tags[0]).name, "news");
+//! ```
+
+use crate::{Accessor, AnyProxy, Contextual, Mutator, Owner, Proxy};
+
+/// A type which can be resolved into an owned snapshot.
+///
+/// This is normally implemented with the [`Resolve`](macro@crate::Resolve)
+/// derive macro.
+pub trait Resolve: Contextual {
+    /// The resolved form of this type.
+    type Resolved;
+
+    /// Resolve this object, using `resolver` to follow its proxies.
+    fn resolve_with<A>(&self, access: &A, resolver: &mut Resolver) -> Self::Resolved
+    where
+        A: Accessor<Context = Self::Context>;
+
+    /// Resolve this object with a default [`Resolver`], which cuts
+    /// cycles and has no depth limit.
+    fn resolve<A>(&self, access: &A) -> Self::Resolved
+    where
+        A: Accessor<Context = Self::Context>,
+    {
+        self.resolve_with(access, &mut Resolver::default())
+    }
+}
+
+/// A type which can be added to a context from its resolved form.
+///
+/// This is normally implemented with the [`Absorb`](macro@crate::Absorb)
+/// derive macro.
+pub trait Absorb: Resolve {
+    /// Add `resolved` to the context, along with the objects nested
+    /// inside it, and return its proxy.
+    fn absorb<M>(resolved: Self::Resolved, mutator: &mut M) -> Proxy<Self>
+    where
+        M: Mutator<Context = Self::Context>,
+        Self::Context: Owner<Self>,
+        Self: Sized;
+}
+
+/// A field which can be resolved as part of a [`Resolve`] type.
+pub trait ResolveField<C> {
+    /// The resolved form of this field.
+    type Resolved;
+
+    /// Resolve this field, using `resolver` to follow its proxies.
+    fn resolve_field<A>(&self, access: &A, resolver: &mut Resolver) -> Self::Resolved
+    where
+        A: Accessor<Context = C>;
+}
+
+/// A field which can be absorbed as part of an [`Absorb`] type.
+pub trait AbsorbField<C>: ResolveField<C> + Sized {
+    /// Add the objects nested inside `resolved` to the context, and
+    /// return the field.
+    fn absorb_field<M>(resolved: Self::Resolved, mutator: &mut M) -> Self
+    where
+        M: Mutator<Context = C>;
+}
+
+/// A resolved proxy.
+pub enum Resolved<T: Resolve> {
+    /// The resolved object.
+    Value(Box<T::Resolved>),
+    /// The proxy was not followed, because it would have closed a
+    /// cycle, or exceeded the depth limit.
+    Cut(Proxy<T>),
+}
+
+impl<T: Resolve> Resolved<T> {
+    /// Wrap a resolved object.
+    pub fn new(value: T::Resolved) -> Self {
+        Self::Value(Box::new(value))
+    }
+
+    /// The resolved object, unless the proxy was not followed.
+    pub fn value(&self) -> Option<&T::Resolved> {
+        match self {
+            Self::Value(value) => Some(value),
+            Self::Cut(_) => None,
+        }
+    }
+
+    /// The resolved object, unless the proxy was not followed.
+    pub fn into_value(self) -> Option<T::Resolved> {
+        match self {
+            Self::Value(value) => Some(*value),
+            Self::Cut(_) => None,
+        }
+    }
+}
+
+impl<T> Clone for Resolved<T>
+where
+    T: Resolve,
+    T::Resolved: Clone,
+{
+    fn clone(&self) -> Self {
+        match self {
+            Self::Value(value) => Self::Value(value.clone()),
+            Self::Cut(p) => Self::Cut(*p),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Resolved<T>
+where
+    T: Resolve,
+    T::Resolved: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Value(value) => f.debug_tuple("Value").field(value).finish(),
+            Self::Cut(p) => f.debug_tuple("Cut").field(p).finish(),
+        }
+    }
+}
+
+impl<T> PartialEq for Resolved<T>
+where
+    T: Resolve,
+    T::Resolved: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Value(a), Self::Value(b)) => a == b,
+            (Self::Cut(a), Self::Cut(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl<T> Eq for Resolved<T>
+where
+    T: Resolve,
+    T::Resolved: Eq,
+{
+}
+
+/// What to do on finding a cycle during resolution.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CyclePolicy {
+    /// Leave the proxy which closes the cycle as a
+    /// [`Resolved::Cut`].
+    #[default]
+    Cut,
+    /// Panic.
+    Panic,
+}
+
+/// The state of a resolution in progress.
+#[derive(Clone, Debug, Default)]
+pub struct Resolver {
+    policy: CyclePolicy,
+    max_depth: Option<usize>,
+    path: Vec<AnyProxy>,
+}
+
+impl Resolver {
+    /// Create a new resolver with the given cycle policy, and no
+    /// depth limit.
+    pub fn new(policy: CyclePolicy) -> Self {
+        Self {
+            policy,
+            max_depth: None,
+            path: Vec::new(),
+        }
+    }
+
+    /// Stop following proxies at `depth` levels below the object
+    /// being resolved. Proxies which are not followed become
+    /// [`Resolved::Cut`].
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Resolve the object `what` points to.
+    ///
+    /// Unlike [`Resolve::resolve_with`], this knows the proxy of the
+    /// object being resolved, so a cycle back to it is found one
+    /// level sooner.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a cycle is found and the policy is
+    /// [`CyclePolicy::Panic`].
+    pub fn resolve<T, A>(&mut self, access: &A, what: &Proxy<T>) -> Resolved<T>
+    where
+        A: Accessor<Context = T::Context>,
+        T: Resolve + 'static,
+        T::Context: Owner<T>,
+    {
+        let any = AnyProxy::new(*what);
+        if self.path.contains(&any) {
+            match self.policy {
+                CyclePolicy::Cut => return Resolved::Cut(*what),
+                CyclePolicy::Panic => {
+                    panic!("cycle found while resolving {}", std::any::type_name::<T>())
+                }
+            }
+        }
+        if self
+            .max_depth
+            .map(|depth| self.path.len() >= depth)
+            .unwrap_or(false)
+        {
+            return Resolved::Cut(*what);
+        }
+        self.path.push(any);
+        let value = access.get(what).resolve_with(access, self);
+        self.path.pop();
+        Resolved::Value(Box::new(value))
+    }
+}
+
+impl<T> ResolveField<T::Context> for Proxy<T>
+where
+    T: Resolve + 'static,
+    T::Context: Owner<T>,
+{
+    type Resolved = Resolved<T>;
+
+    fn resolve_field<A>(&self, access: &A, resolver: &mut Resolver) -> Self::Resolved
+    where
+        A: Accessor<Context = T::Context>,
+    {
+        resolver.resolve(access, self)
+    }
+}
+
+impl<C, F: ResolveField<C>> ResolveField<C> for Option<F> {
+    type Resolved = Option<F::Resolved>;
+
+    fn resolve_field<A>(&self, access: &A, resolver: &mut Resolver) -> Self::Resolved
+    where
+        A: Accessor<Context = C>,
+    {
+        self.as_ref()
+            .map(|field| field.resolve_field(access, resolver))
+    }
+}
+
+impl<C, F: ResolveField<C>> ResolveField<C> for Vec<F> {
+    type Resolved = Vec<F::Resolved>;
+
+    fn resolve_field<A>(&self, access: &A, resolver: &mut Resolver) -> Self::Resolved
+    where
+        A: Accessor<Context = C>,
+    {
+        self.iter()
+            .map(|field| field.resolve_field(access, resolver))
+            .collect()
+    }
+}
+
+impl<C, F: ResolveField<C>> ResolveField<C> for Box<F> {
+    type Resolved = Box<F::Resolved>;
+
+    fn resolve_field<A>(&self, access: &A, resolver: &mut Resolver) -> Self::Resolved
+    where
+        A: Accessor<Context = C>,
+    {
+        Box::new((**self).resolve_field(access, resolver))
+    }
+}
+
+impl<T> AbsorbField<T::Context> for Proxy<T>
+where
+    T: Absorb + 'static,
+    T::Context: Owner<T>,
+{
+    fn absorb_field<M>(resolved: Self::Resolved, mutator: &mut M) -> Self
+    where
+        M: Mutator<Context = T::Context>,
+    {
+        match resolved {
+            Resolved::Value(value) => T::absorb(*value, mutator),
+            Resolved::Cut(p) => p,
+        }
+    }
+}
+
+impl<C, F: AbsorbField<C>> AbsorbField<C> for Option<F> {
+    fn absorb_field<M>(resolved: Self::Resolved, mutator: &mut M) -> Self
+    where
+        M: Mutator<Context = C>,
+    {
+        resolved.map(|field| F::absorb_field(field, mutator))
+    }
+}
+
+impl<C, F: AbsorbField<C>> AbsorbField<C> for Vec<F> {
+    fn absorb_field<M>(resolved: Self::Resolved, mutator: &mut M) -> Self
+    where
+        M: Mutator<Context = C>,
+    {
+        resolved
+            .into_iter()
+            .map(|field| F::absorb_field(field, mutator))
+            .collect()
+    }
+}
+
+impl<C, F: AbsorbField<C>> AbsorbField<C> for Box<F> {
+    fn absorb_field<M>(resolved: Self::Resolved, mutator: &mut M) -> Self
+    where
+        M: Mutator<Context = C>,
+    {
+        Box::new(F::absorb_field(*resolved, mutator))
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{Accessor, Contextual, Owner, Proxy};
+
+/// An item which names a [`Proxy`]: either a proxy, or a reference
+/// to one.
+///
+/// This lets [`ResolveProxies`] work both with iterators which yield
+/// proxies, and with those which borrow them from a collection.
+pub trait ProxyItem {
+    /// The type of the object the proxy refers to.
+    type Target;
+
+    /// The proxy this item names.
+    fn proxy(&self) -> Proxy<Self::Target>;
+}
+
+impl<T> ProxyItem for Proxy<T> {
+    type Target = T;
+
+    fn proxy(&self) -> Proxy<T> {
+        *self
+    }
+}
+
+impl<T> ProxyItem for &Proxy<T> {
+    type Target = T;
+
+    fn proxy(&self) -> Proxy<T> {
+        **self
+    }
+}
+
+/// Resolve the proxies yielded by an iterator into the objects they
+/// refer to.
+///
+/// This is implemented for every [`Iterator`] whose items are
+/// [`Proxy`] objects, or references to them. It saves writing out
+/// the lookup in the most common iterator pipelines:
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, Proxy, ResolveProxies};
+///
+/// #[contextual(Rug)]
+/// struct Person {
+///   name: &'static str,
+///   friends: Vec<Proxy<Person>>,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Person);
+///
+/// let mut r = Rug(Default::default());
+/// let a = r.add(Person { name: "Alice", friends: vec![] });
+/// let b = r.add(Person { name: "Bob", friends: vec![] });
+/// let c = r.add(Person { name: "Carol", friends: vec![a, b] });
+///
+/// let names = r
+///     .get(&c)
+///     .friends
+///     .iter()
+///     .resolve(&&r)
+///     .map(|p| p.name)
+///     .collect::<Vec<_>>();
+/// assert_eq!(names, vec!["Alice", "Bob"]);
+///
+/// for (p, person) in [a, c].into_iter().resolve_entries(&&r) {
+///     assert_eq!(r.get(&p).name, person.name);
+/// }
+/// ```
+///
+/// As with [`Accessor::get`], resolving a proxy which is not in the
+/// context panics.
+pub trait ResolveProxies: Iterator + Sized
+where
+    Self::Item: ProxyItem,
+{
+    /// Yield the object each proxy refers to.
+    fn resolve<A>(self, access: &A) -> ResolveIter<'_, Self, A>
+    where
+        A: Accessor,
+        A::Context: Owner<<Self::Item as ProxyItem>::Target>,
+        <Self::Item as ProxyItem>::Target: Contextual<Context = A::Context>,
+    {
+        ResolveIter { iter: self, access }
+    }
+
+    /// Yield each proxy together with the object it refers to.
+    fn resolve_entries<A>(self, access: &A) -> ResolveEntries<'_, Self, A>
+    where
+        A: Accessor,
+        A::Context: Owner<<Self::Item as ProxyItem>::Target>,
+        <Self::Item as ProxyItem>::Target: Contextual<Context = A::Context>,
+    {
+        ResolveEntries { iter: self, access }
+    }
+}
+
+impl<I> ResolveProxies for I
+where
+    I: Iterator,
+    I::Item: ProxyItem,
+{
+}
+
+/// An [`Iterator`] over the objects a stream of proxies refers to.
+///
+/// This is returned by [`ResolveProxies::resolve`].
+pub struct ResolveIter<'a, I, A> {
+    iter: I,
+    access: &'a A,
+}
+
+impl<'a, I, A, T> Iterator for ResolveIter<'a, I, A>
+where
+    I: Iterator,
+    I::Item: ProxyItem<Target = T>,
+    A: Accessor,
+    A::Context: Owner<T>,
+    T: Contextual<Context = A::Context> + 'a,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let p = self.iter.next()?.proxy();
+        Some(self.access.get(&p))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// An [`Iterator`] over a stream of proxies, together with the
+/// objects they refer to.
+///
+/// This is returned by [`ResolveProxies::resolve_entries`].
+pub struct ResolveEntries<'a, I, A> {
+    iter: I,
+    access: &'a A,
+}
+
+impl<'a, I, A, T> Iterator for ResolveEntries<'a, I, A>
+where
+    I: Iterator,
+    I::Item: ProxyItem<Target = T>,
+    A: Accessor,
+    A::Context: Owner<T>,
+    T: Contextual<Context = A::Context> + 'a,
+{
+    type Item = (Proxy<T>, &'a T);
+
+    fn next(&mut self) -> Option<(Proxy<T>, &'a T)> {
+        let p = self.iter.next()?.proxy();
+        Some((p, self.access.get(&p)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
@@ -0,0 +1,177 @@
+//! Rewriting the objects in a context by pattern.
+//!
+//! Passes over a graph held in a context, such as the optimisation
+//! passes of a compiler whose intermediate representation is a rug,
+//! often take the form of a set of rules: each rule looks for a small
+//! pattern of objects and links, and replaces it with something
+//! simpler. A [`Rewriter`] holds a set of such rules, and applies
+//! them until none of them matches.
+//!
+//! A rule has two halves. The pattern is a function which looks for
+//! a match in the context, and returns whatever is needed to rewrite
+//! it, usually some proxies. The [`query!`](crate::query) macro is a
+//! convenient way to write patterns, particularly with `proxy @ name`
+//! bindings. The replacement is a function which receives the context
+//! mutably, along with the match, and makes its changes.
+//!
+//! ```rust
+//! use persian_rug::rewrite::Rewriter;
+//! use persian_rug::{contextual, persian_rug, query, Context, Proxy};
+//!
+//! #[derive(Clone)]
+//! #[contextual(Rug)]
+//! enum Expr {
+//!   Const(i64),
+//!   Add(Proxy<Expr>, Proxy<Expr>),
+//! }
+//!
+//! #[derive(Clone)]
+//! #[persian_rug]
+//! struct Rug(#[table] Expr);
+//!
+//! let mut r = Rug(Default::default());
+//! let one = r.add(Expr::Const(1));
+//! let two = r.add(Expr::Const(2));
+//! let three = r.add(Expr::Add(one, two));
+//! let six = r.add(Expr::Add(three, three));
+//!
+//! let mut folding = Rewriter::<Rug>::new().rule(
+//!     |r| {
+//!         query!(r, (p @ e: Expr) => p)
+//!             .find_map(|p| match r.get(&p) {
+//!                 Expr::Add(a, b) => match (r.get(a), r.get(b)) {
+//!                     (Expr::Const(a), Expr::Const(b)) => Some((p, a + b)),
+//!                     _ => None,
+//!                 },
+//!                 _ => None,
+//!             })
+//!     },
+//!     |r, (p, value)| {
+//!         *r.get_mut(&p) = Expr::Const(value);
+//!         Ok(())
+//!     },
+//! );
+//!
+//! assert_eq!(folding.run(&mut r), Ok(2));
+//! assert!(matches!(r.get(&six), Expr::Const(6)));
+//! ```
+//!
+//! Rewriting is transactional: if a replacement fails, or the rules
+//! do not settle within the rewriter's [`limit`](Rewriter::limit),
+//! the context is left exactly as it was. This is achieved by
+//! rewriting a clone of the context, so the context must implement
+//! [`Clone`].
+
+/// The reason a [`Rewriter`] did not complete.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RewriteError<E> {
+    /// A replacement failed with the given error.
+    Failed(E),
+    /// The rules were still matching after this many rewrites.
+    LimitReached(usize),
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for RewriteError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RewriteError::Failed(e) => write!(f, "rewrite failed: {}", e),
+            RewriteError::LimitReached(count) => {
+                write!(f, "rules still matching after {} rewrites", count)
+            }
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for RewriteError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RewriteError::Failed(e) => Some(e),
+            RewriteError::LimitReached(_) => None,
+        }
+    }
+}
+
+type Step<'r, C, E> = Box<dyn FnMut(&mut C) -> Result<bool, E> + 'r>;
+
+/// A set of rewrite rules for contexts of type `C`.
+///
+/// Rules are tried in the order they were added. After each rewrite,
+/// matching starts again from the first rule, so earlier rules take
+/// priority. Replacements may fail with an error of type `E`.
+pub struct Rewriter<'r, C, E = std::convert::Infallible> {
+    rules: Vec<Step<'r, C, E>>,
+    limit: usize,
+}
+
+impl<'r, C, E> Rewriter<'r, C, E> {
+    /// Create a rewriter with no rules.
+    ///
+    /// The default limit is one million rewrites.
+    pub fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            limit: 1_000_000,
+        }
+    }
+
+    /// Add a rule.
+    ///
+    /// The pattern returns a match, or `None` if there are no more
+    /// matches. Each match is passed to the replacement, which must
+    /// change the context so that the same match is not found again.
+    pub fn rule<M, P, R>(mut self, pattern: P, mut replace: R) -> Self
+    where
+        P: Fn(&C) -> Option<M> + 'r,
+        R: FnMut(&mut C, M) -> Result<(), E> + 'r,
+    {
+        self.rules.push(Box::new(move |context: &mut C| {
+            match pattern(context) {
+                Some(m) => replace(context, m)?,
+                None => return Ok(false),
+            }
+            Ok(true)
+        }));
+        self
+    }
+
+    /// Set the maximum number of rewrites for a run.
+    ///
+    /// This guards against rules which never stop matching, for
+    /// example because two rules undo each other's work.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Rewrite a context until no rule matches.
+    ///
+    /// Returns the number of rewrites made. On error, the context is
+    /// unchanged.
+    pub fn run(&mut self, context: &mut C) -> Result<usize, RewriteError<E>>
+    where
+        C: Clone,
+    {
+        let mut work = context.clone();
+        let mut count = 0;
+        'search: loop {
+            for rule in self.rules.iter_mut() {
+                if rule(&mut work).map_err(RewriteError::Failed)? {
+                    if count == self.limit {
+                        return Err(RewriteError::LimitReached(count));
+                    }
+                    count += 1;
+                    continue 'search;
+                }
+            }
+            break;
+        }
+        *context = work;
+        Ok(count)
+    }
+}
+
+impl<C, E> Default for Rewriter<'_, C, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{Accessor, Contextual, Owner, Proxy, SeedRng};
+
+/// Choose an object from a table at random, with probability
+/// proportional to its weight.
+///
+/// The weight of each object is given by `weight`, and objects whose
+/// weight is zero are never chosen. Returns [`None`] if every object
+/// has zero weight, including when the table is empty.
+///
+/// Each call scans the whole table. To choose many objects from the
+/// same weights, build a [`WeightedSampler`] instead.
+///
+/// # Panics
+///
+/// Panics if any weight is negative or not finite.
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, sample_weighted, Context, SeedRng};
+///
+/// #[contextual(Rug)]
+/// struct City {
+///   population: u32,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] City);
+///
+/// let mut r = Rug(Default::default());
+/// let big = r.add(City { population: 900 });
+/// let small = r.add(City { population: 100 });
+///
+/// let mut rng = SeedRng::new(7);
+/// let picks = (0..1000)
+///     .map(|_| sample_weighted(&&r, &mut rng, |city: &City| city.population as f64).unwrap())
+///     .filter(|p| *p == big)
+///     .count();
+/// assert!(picks > 850 && picks < 950);
+/// ```
+pub fn sample_weighted<A, T, F>(access: &A, rng: &mut SeedRng, mut weight: F) -> Option<Proxy<T>>
+where
+    A: Accessor,
+    A::Context: Owner<T>,
+    T: Contextual<Context = A::Context>,
+    F: FnMut(&T) -> f64,
+{
+    let weights = access
+        .get_proxy_iter()
+        .map(|p| (*p, checked(weight(access.get(p)))))
+        .filter(|(_, w)| *w > 0.0)
+        .collect::<Vec<_>>();
+    let total = weights.iter().map(|(_, w)| w).sum::<f64>();
+    let mut target = rng.next_f64() * total;
+    for (p, w) in weights.iter() {
+        if target < *w {
+            return Some(*p);
+        }
+        target -= w;
+    }
+    // Rounding can leave a little of the total unaccounted for.
+    weights.last().map(|(p, _)| *p)
+}
+
+/// Check that `weight` is usable for sampling.
+fn checked(weight: f64) -> f64 {
+    assert!(
+        weight.is_finite() && weight >= 0.0,
+        "sampling weights must be finite and not negative, not {}",
+        weight
+    );
+    weight
+}
+
+/// A precomputed table for choosing objects at random, with
+/// probability proportional to their weights.
+///
+/// Building the table takes time proportional to the number of
+/// objects, after which each choice takes constant time, using
+/// Vose's alias method. This suits simulations which repeatedly pick
+/// objects in proportion to their size or priority.
+///
+/// The table is a snapshot of the weights when it was built: objects
+/// added to the context afterwards are never chosen, and changes to
+/// the weights are not seen until it is rebuilt.
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, SeedRng, WeightedSampler};
+///
+/// #[contextual(Rug)]
+/// struct Task {
+///   priority: u32,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Task);
+///
+/// let mut r = Rug(Default::default());
+/// let urgent = r.add(Task { priority: 3 });
+/// let routine = r.add(Task { priority: 1 });
+/// let never = r.add(Task { priority: 0 });
+///
+/// let sampler = WeightedSampler::new(&&r, |task: &Task| task.priority as f64);
+/// let mut rng = SeedRng::new(1);
+/// let picks = (0..4000)
+///     .map(|_| sampler.sample(&mut rng).unwrap())
+///     .collect::<Vec<_>>();
+/// let urgent_picks = picks.iter().filter(|p| **p == urgent).count();
+/// assert!(urgent_picks > 2800 && urgent_picks < 3200);
+/// assert!(!picks.contains(&never));
+/// ```
+pub struct WeightedSampler<T> {
+    proxies: Vec<Proxy<T>>,
+    /// The probability of keeping each column's own proxy, rather than
+    /// its alias.
+    keep: Vec<f64>,
+    alias: Vec<usize>,
+}
+
+impl<T> Clone for WeightedSampler<T> {
+    fn clone(&self) -> Self {
+        Self {
+            proxies: self.proxies.clone(),
+            keep: self.keep.clone(),
+            alias: self.alias.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for WeightedSampler<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeightedSampler")
+            .field("proxies", &self.proxies)
+            .field("keep", &self.keep)
+            .field("alias", &self.alias)
+            .finish()
+    }
+}
+
+impl<T> WeightedSampler<T> {
+    /// Build a sampler over the objects of a table, weighted by
+    /// `weight`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any weight is negative or not finite.
+    pub fn new<A, F>(access: &A, mut weight: F) -> Self
+    where
+        A: Accessor,
+        A::Context: Owner<T>,
+        T: Contextual<Context = A::Context>,
+        F: FnMut(&T) -> f64,
+    {
+        Self::from_weights(access.get_proxy_iter().map(|p| (*p, weight(access.get(p)))))
+    }
+
+    /// Build a sampler from proxies and their weights.
+    ///
+    /// The proxies need not all come from one table, or be distinct.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any weight is negative or not finite.
+    pub fn from_weights(weights: impl IntoIterator<Item = (Proxy<T>, f64)>) -> Self {
+        let (proxies, weights): (Vec<_>, Vec<_>) = weights
+            .into_iter()
+            .map(|(p, w)| (p, checked(w)))
+            .filter(|(_, w)| *w > 0.0)
+            .unzip();
+
+        let n = proxies.len();
+        let total = weights.iter().sum::<f64>();
+        let mut keep = weights
+            .iter()
+            .map(|w| w * n as f64 / total)
+            .collect::<Vec<_>>();
+        let mut alias = (0..n).collect::<Vec<_>>();
+
+        let (mut small, mut large): (Vec<_>, Vec<_>) = (0..n).partition(|ix| keep[*ix] < 1.0);
+        while let (Some(s), Some(l)) = (small.pop(), large.pop()) {
+            alias[s] = l;
+            keep[l] -= 1.0 - keep[s];
+            if keep[l] < 1.0 {
+                small.push(l);
+            } else {
+                large.push(l);
+            }
+        }
+        // Whatever is left over should have a probability of one,
+        // but for rounding.
+        for ix in small.into_iter().chain(large) {
+            keep[ix] = 1.0;
+        }
+
+        Self {
+            proxies,
+            keep,
+            alias,
+        }
+    }
+
+    /// Choose a proxy at random, or [`None`] if every weight was
+    /// zero.
+    pub fn sample(&self, rng: &mut SeedRng) -> Option<Proxy<T>> {
+        if self.proxies.is_empty() {
+            return None;
+        }
+        let column = rng.below(self.proxies.len());
+        if rng.next_f64() < self.keep[column] {
+            Some(self.proxies[column])
+        } else {
+            Some(self.proxies[self.alias[column]])
+        }
+    }
+
+    /// The number of proxies which can be chosen.
+    pub fn len(&self) -> usize {
+        self.proxies.len()
+    }
+
+    /// Check whether there are no proxies to choose from.
+    pub fn is_empty(&self) -> bool {
+        self.proxies.is_empty()
+    }
+}
@@ -0,0 +1,283 @@
+use std::any::{Any, TypeId};
+use std::collections::BTreeMap;
+
+use crate::{Context, Contextual, Owner, Proxy};
+
+/// A speculative overlay over a context.
+///
+/// A sandbox is created by [`Context::sandbox`]. Reads fall through
+/// to the underlying context, but additions and modifications are
+/// buffered in the sandbox, so that the effect of a change can be
+/// evaluated without cloning the whole context. The buffered changes
+/// can then be written to the context with
+/// [`commit`](Sandbox::commit), or thrown away with
+/// [`discard`](Sandbox::discard) (or simply by dropping the sandbox).
+///
+/// Objects are copied into the sandbox the first time they are
+/// borrowed mutably, so only the objects that are changed take up any
+/// extra space. Objects added to the sandbox receive the same proxies
+/// that they will have once committed, so they can be linked to
+/// freely. Those proxies are set aside in the context with
+/// [`Owner::reserve_proxy`] as the objects are added, and handed back
+/// if the changes are thrown away.
+///
+/// To buffer changes to several contexts and commit them together,
+/// see the [`transaction`](crate::transaction) module.
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, Proxy};
+///
+/// #[derive(Clone)]
+/// #[contextual(Rug)]
+/// struct Account {
+///   balance: i64,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Account);
+///
+/// let mut r = Rug(Default::default());
+/// let a = r.add(Account { balance: 100 });
+/// let b = r.add(Account { balance: 0 });
+///
+/// let mut s = r.sandbox();
+/// s.get_mut(&a).balance -= 150;
+/// s.get_mut(&b).balance += 150;
+/// let overdrawn = s.get_iter::<Account>().any(|(_, acc)| acc.balance < 0);
+/// assert!(overdrawn);
+/// s.discard();
+/// assert_eq!(r.get(&a).balance, 100);
+///
+/// let mut s = r.sandbox();
+/// s.get_mut(&a).balance -= 50;
+/// s.get_mut(&b).balance += 50;
+/// let c = s.add(Account { balance: 1 });
+/// s.commit();
+/// assert_eq!(r.get(&a).balance, 50);
+/// assert_eq!(r.get(&c).balance, 1);
+/// ```
+pub struct Sandbox<'a, C> {
+    base: &'a mut C,
+    layers: Layers<'a, C>,
+}
+
+impl<'a, C: Context> Sandbox<'a, C> {
+    pub(crate) fn new(base: &'a mut C) -> Self {
+        Self {
+            base,
+            layers: BTreeMap::new(),
+        }
+    }
+
+    /// The underlying context, without any of the buffered changes.
+    pub fn base(&self) -> &C {
+        self.base
+    }
+
+    /// Check whether any changes have been buffered.
+    pub fn is_modified(&self) -> bool {
+        !self.layers.is_empty()
+    }
+
+    /// Add an object to the sandbox.
+    ///
+    /// If the context cannot set proxies aside, as for owners written
+    /// by hand which do not implement [`Owner::reserve_proxy`], the
+    /// object is given the proxy that the context would issue next,
+    /// counting the objects already added to the sandbox.
+    pub fn add<T>(&mut self, value: T) -> Proxy<T>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + 'static,
+    {
+        let reserved = Owner::reserve_proxy(&mut *self.base);
+        let layer = layer_mut(&mut self.layers, &*self.base);
+        let p = reserved
+            .unwrap_or_else(|| Proxy::from_handle(layer.next + layer.added.len() as u64));
+        layer.positions.insert(p.key(), layer.added.len());
+        layer.added.push((p, value));
+        p
+    }
+
+    /// Retrieve an object, as modified in the sandbox.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the object is in neither the sandbox nor the
+    /// context.
+    pub fn get<T>(&self, what: &Proxy<T>) -> &T
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + 'static,
+    {
+        match self.layer::<T>() {
+            Some(layer) => match layer.positions.get(&what.key()) {
+                Some(position) => &layer.added[*position].1,
+                None => layer
+                    .modified
+                    .get(&what.key())
+                    .unwrap_or_else(|| Owner::get(self.base, what)),
+            },
+            None => Owner::get(self.base, what),
+        }
+    }
+
+    /// Retrieve an object mutably.
+    ///
+    /// Objects from the context are copied into the sandbox the first
+    /// time this is called for them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the object is in neither the sandbox nor the
+    /// context.
+    pub fn get_mut<T>(&mut self, what: &Proxy<T>) -> &mut T
+    where
+        C: Owner<T>,
+        T: Clone + Contextual<Context = C> + 'static,
+    {
+        let base = &*self.base;
+        let layer = layer_mut(&mut self.layers, base);
+        if let Some(position) = layer.positions.get(&what.key()) {
+            &mut layer.added[*position].1
+        } else {
+            layer
+                .modified
+                .entry(what.key())
+                .or_insert_with(|| Owner::get(base, what).clone())
+        }
+    }
+
+    /// Iterate over the objects of type `T` with their proxies, as
+    /// modified in the sandbox.
+    ///
+    /// Objects from the context come first, in the context's order,
+    /// followed by the objects added to the sandbox.
+    pub fn get_iter<T>(&self) -> impl Iterator<Item = (Proxy<T>, &T)> + '_
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C> + 'static,
+    {
+        let layer = self.layer::<T>();
+        let modified = layer.map(|layer| &layer.modified);
+        let added = layer
+            .into_iter()
+            .flat_map(|layer| layer.added.iter().map(|(p, value)| (*p, value)));
+        Owner::<T>::get_proxy_iter(self.base)
+            .map(move |p| {
+                let value = modified
+                    .and_then(|modified| modified.get(&p.key()))
+                    .unwrap_or_else(|| Owner::get(self.base, p));
+                (*p, value)
+            })
+            .chain(added)
+    }
+
+    /// Write the buffered changes to the context.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context does not store an added object under the
+    /// proxy it was given in the sandbox, which is only possible for
+    /// owners written by hand which cannot set proxies aside.
+    pub fn commit(mut self) {
+        for (_, layer) in std::mem::take(&mut self.layers) {
+            layer.commit(self.base);
+        }
+    }
+
+    /// Throw away the buffered changes.
+    ///
+    /// This is equivalent to dropping the sandbox.
+    pub fn discard(self) {}
+
+    fn layer<T: 'static>(&self) -> Option<&Changes<T>> {
+        self.layers.get(&TypeId::of::<T>()).map(|layer| {
+            layer
+                .as_any()
+                .downcast_ref::<Changes<T>>()
+                .expect("sandbox layer has the wrong type")
+        })
+    }
+}
+
+impl<C> Drop for Sandbox<'_, C> {
+    fn drop(&mut self) {
+        for (_, layer) in std::mem::take(&mut self.layers) {
+            layer.release(self.base);
+        }
+    }
+}
+
+type Layers<'a, C> = BTreeMap<TypeId, Box<dyn Layer<C> + 'a>>;
+
+fn layer_mut<'l, 'a, C, T>(layers: &'l mut Layers<'a, C>, base: &C) -> &'l mut Changes<T>
+where
+    C: Owner<T>,
+    T: Contextual<Context = C> + 'static,
+{
+    layers
+        .entry(TypeId::of::<T>())
+        .or_insert_with(|| {
+            Box::new(Changes::<T> {
+                next: Owner::<T>::next_proxy(base).index,
+                added: Vec::new(),
+                positions: BTreeMap::new(),
+                modified: BTreeMap::new(),
+            })
+        })
+        .as_any_mut()
+        .downcast_mut::<Changes<T>>()
+        .expect("sandbox layer has the wrong type")
+}
+
+/// The buffered changes to the objects of one type.
+struct Changes<T> {
+    /// The index the context would issue next when the layer was
+    /// created, for contexts which cannot set proxies aside.
+    next: u64,
+    added: Vec<(Proxy<T>, T)>,
+    /// The position in `added` of each added object, by proxy.
+    positions: BTreeMap<(u64, u32), usize>,
+    modified: BTreeMap<(u64, u32), T>,
+}
+
+trait Layer<C> {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn commit(self: Box<Self>, base: &mut C);
+    fn release(self: Box<Self>, base: &mut C);
+}
+
+impl<C, T> Layer<C> for Changes<T>
+where
+    C: Owner<T>,
+    T: Contextual<Context = C> + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn commit(self: Box<Self>, base: &mut C) {
+        for (key, value) in self.modified {
+            let p = Proxy::from_key(key);
+            *Owner::get_mut(base, &p) = value;
+        }
+        for (p, value) in self.added {
+            match Owner::insert_with_handle(base, p.index, value) {
+                Ok(q) if q == p => {}
+                _ => panic!("context did not store {:?} under the proxy reserved for it", p),
+            }
+        }
+    }
+
+    fn release(self: Box<Self>, base: &mut C) {
+        for (p, _) in self.added {
+            Owner::release_proxy(base, &p);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Descriptions of the types stored in a context.
+//!
+//! Tools which work with any context, such as exporters, inspectors
+//! and checks that a stored context still matches the program, need
+//! to know what each context holds without being written for it. The
+//! [`contextual`](crate::contextual) macro describes each type it is
+//! applied to, listing its fields and the proxies they hold, in a
+//! [`TypeSchema`], available from
+//! [`Contextual::schema`](crate::Contextual::schema). The
+//! [`persian_rug`](crate::persian_rug) macro gathers these for each
+//! table of a context into a [`ContextSchema`], available from
+//! [`Context::schema`](crate::Context::schema).
+//!
+//! ```rust
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Author {
+//!   name: String,
+//! }
+//!
+//! #[contextual(Rug)]
+//! struct Book {
+//!   title: String,
+//!   authors: Vec<Proxy<Author>>,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Author, #[table] Book);
+//!
+//! let schema = Rug::schema();
+//! assert_eq!(schema.name, "Rug");
+//! assert_eq!(schema.tables.len(), 2);
+//!
+//! let book = schema.table(std::any::type_name::<Book>()).unwrap();
+//! assert_eq!(book.schema.name, "Book");
+//! let authors = &book.schema.fields[1];
+//! assert_eq!(authors.name, "authors");
+//! assert_eq!(authors.ty, "Vec<Proxy<Author>>");
+//! assert_eq!(authors.proxies, vec![std::any::type_name::<Author>()]);
+//! ```
+//!
+//! Types are identified by [`std::any::type_name`], which is not
+//! guaranteed to be stable between compiler versions, and so is best
+//! used to compare schemas produced by the same build. The types of
+//! fields are given as they were written in the source.
+
+use std::any::type_name;
+
+/// A description of a type which can be stored in a context.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeSchema {
+    /// The name of the type, as declared, without its path or
+    /// parameters.
+    pub name: &'static str,
+    /// The full name of the type, from [`std::any::type_name`].
+    pub type_name: &'static str,
+    /// The fields of a struct. This is empty for other types.
+    pub fields: Vec<FieldSchema>,
+    /// The variants of an enum. This is empty for other types.
+    pub variants: Vec<VariantSchema>,
+}
+
+impl TypeSchema {
+    /// Describe `T` without any fields or variants.
+    ///
+    /// This is the description of types whose [`Contextual`]
+    /// implementation was written by hand.
+    ///
+    /// [`Contextual`]: crate::Contextual
+    pub fn opaque<T: ?Sized>() -> Self {
+        let type_name = type_name::<T>();
+        let path = type_name.split('<').next().unwrap_or(type_name);
+        Self {
+            name: path.rsplit("::").next().unwrap_or(path),
+            type_name,
+            fields: Vec::new(),
+            variants: Vec::new(),
+        }
+    }
+
+    /// Iterate over every field of the type, including the fields of
+    /// each variant of an enum.
+    pub fn all_fields(&self) -> impl Iterator<Item = &FieldSchema> {
+        self.fields.iter().chain(
+            self.variants
+                .iter()
+                .flat_map(|variant| variant.fields.iter()),
+        )
+    }
+}
+
+/// A description of one variant of an enum.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VariantSchema {
+    /// The name of the variant.
+    pub name: &'static str,
+    /// The fields of the variant.
+    pub fields: Vec<FieldSchema>,
+}
+
+/// A description of one field of a struct or variant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldSchema {
+    /// The name of the field, or its position for tuple fields.
+    pub name: &'static str,
+    /// The type of the field, as written in the source.
+    pub ty: &'static str,
+    /// The full names of the types of the objects that proxies in the
+    /// field refer to, whether held directly, or in an [`Option`],
+    /// [`Vec`] or other container.
+    pub proxies: Vec<&'static str>,
+}
+
+/// A description of one table of a context.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableSchema {
+    /// The name of the context's field holding the table, or its
+    /// position for tuple structs.
+    pub field: &'static str,
+    /// The type of the objects stored in the table.
+    pub schema: TypeSchema,
+}
+
+/// A description of a context and the types it stores.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContextSchema {
+    /// The name of the context, as declared, without its path or
+    /// parameters.
+    pub name: &'static str,
+    /// The full name of the context, from [`std::any::type_name`].
+    pub type_name: &'static str,
+    /// The tables of the context, in the order they are declared.
+    pub tables: Vec<TableSchema>,
+}
+
+impl ContextSchema {
+    /// Find the table of the type with the given full name.
+    pub fn table(&self, type_name: &str) -> Option<&TableSchema> {
+        self.tables
+            .iter()
+            .find(|table| table.schema.type_name == type_name)
+    }
+
+    /// List the tables whose objects can hold proxies to objects of
+    /// the type with the given full name, along with the fields which
+    /// hold them.
+    pub fn referrers_of(&self, type_name: &str) -> Vec<(&TableSchema, &FieldSchema)> {
+        self.tables
+            .iter()
+            .flat_map(|table| table.schema.all_fields().map(move |field| (table, field)))
+            .filter(|(_, field)| field.proxies.contains(&type_name))
+            .collect()
+    }
+
+    /// List the types which are the targets of proxies in this
+    /// context, but which have no table in it.
+    pub fn missing_tables(&self) -> Vec<&'static str> {
+        let mut res = Vec::new();
+        for table in &self.tables {
+            for field in table.schema.all_fields() {
+                for target in &field.proxies {
+                    if self.table(target).is_none() && !res.contains(target) {
+                        res.push(*target);
+                    }
+                }
+            }
+        }
+        res
+    }
+}
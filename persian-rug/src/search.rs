@@ -0,0 +1,294 @@
+//! Full-text search over the text fields of stored objects.
+//!
+//! This module is available with the `search` feature. It provides
+//! [`SearchStorage`], a [`Storage`] which maintains an inverted index
+//! over the words in some of the fields of the objects it holds, so
+//! that the objects containing a word can be found without scanning
+//! the whole table.
+//!
+//! The fields to index are marked with `#[search]` on a type
+//! annotated with [`contextual`](crate::contextual), which implements
+//! [`Searchable`] for it. The table holding the type is then given
+//! search storage with `#[table(search)]` in its
+//! [`persian_rug`](crate::persian_rug) context, or
+//! `#[table(search(arena))]` to index an arena table. The context can
+//! then be queried with [`Search::search`]:
+//!
+//! ```rust
+//! use persian_rug::search::Search;
+//! use persian_rug::{contextual, persian_rug, Context};
+//!
+//! #[contextual(Rug)]
+//! struct Note {
+//!   #[search]
+//!   title: String,
+//!   #[search]
+//!   body: String,
+//!   stars: u32,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table(search)] Note);
+//!
+//! let mut r = Rug(Default::default());
+//! let a = r.add(Note { title: "Shopping".to_string(), body: "Eggs, milk".to_string(), stars: 0 });
+//! let b = r.add(Note { title: "Recipes".to_string(), body: "Pancakes: eggs and milk, flour".to_string(), stars: 3 });
+//!
+//! assert_eq!(r.search::<Note>("eggs"), vec![a, b]);
+//! assert_eq!(r.search::<Note>("Milk flour"), vec![b]);
+//!
+//! r.get_mut(&a).body = "Bread".to_string();
+//! assert_eq!(r.search::<Note>("eggs"), vec![b]);
+//! ```
+//!
+//! Text is split into words at every character which is not
+//! alphanumeric, and words are compared ignoring case. A query
+//! matches the objects which contain all of its words.
+//!
+//! Since objects can be modified through the mutable references
+//! handed out by a table, the index cannot be kept up to date as
+//! changes are made. Instead, the storage records which objects may
+//! have changed, and reindexes them the next time it is searched.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::handles::HandleAllocator;
+use crate::lock::Lock;
+use crate::referrers::LinkIndex;
+use crate::storage::{sealed, Entries, EntriesMut, MapStorage, Storage};
+use crate::{AnyProxy, Context, Contextual, Proxy, Table};
+
+/// A type with text fields that can be searched.
+///
+/// This is normally implemented by marking fields with `#[search]`
+/// on a type annotated with [`contextual`](crate::contextual). Each
+/// marked field must implement [`AsRef<str>`].
+pub trait Searchable {
+    /// Pass each piece of searchable text in this object to `text`.
+    fn search_text(&self, text: &mut dyn FnMut(&str));
+}
+
+/// Split text into the lowercased words that are indexed.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+#[derive(Default)]
+struct Index {
+    postings: BTreeMap<String, BTreeSet<u64>>,
+    words: BTreeMap<u64, BTreeSet<String>>,
+    stale: BTreeSet<u64>,
+    rebuild: bool,
+}
+
+impl Index {
+    fn unindex(&mut self, index: u64) {
+        for word in self.words.remove(&index).into_iter().flatten() {
+            if let Some(members) = self.postings.get_mut(&word) {
+                members.remove(&index);
+                if members.is_empty() {
+                    self.postings.remove(&word);
+                }
+            }
+        }
+    }
+
+    fn index<T: Searchable>(&mut self, index: u64, value: &T) {
+        let mut found = BTreeSet::new();
+        value.search_text(&mut |text| found.extend(words(text)));
+        for word in found.iter() {
+            self.postings.entry(word.clone()).or_default().insert(index);
+        }
+        self.words.insert(index, found);
+    }
+
+    fn refresh<T: Searchable, S: Storage<T>>(&mut self, storage: &S) {
+        if self.rebuild {
+            self.postings.clear();
+            self.words.clear();
+            for (p, value) in storage.entries() {
+                self.index(p.index, value);
+            }
+            self.rebuild = false;
+        } else {
+            for index in std::mem::take(&mut self.stale) {
+                self.unindex(index);
+                if let Some(value) = storage.get(index) {
+                    self.index(index, value);
+                }
+            }
+        }
+        self.stale.clear();
+    }
+
+    fn search(&self, term: &str) -> BTreeSet<u64> {
+        let mut res: Option<BTreeSet<u64>> = None;
+        for word in words(term) {
+            let members = self.postings.get(&word).cloned().unwrap_or_default();
+            res = Some(match res {
+                Some(res) => res.intersection(&members).copied().collect(),
+                None => members,
+            });
+        }
+        res.unwrap_or_default()
+    }
+}
+
+/// Storage which maintains a full-text index of its objects.
+///
+/// The objects themselves are held in another storage, `S`. Objects
+/// which are inserted or borrowed mutably are reindexed lazily, when
+/// the storage is next searched.
+pub struct SearchStorage<T, S = MapStorage<T>> {
+    _marker: core::marker::PhantomData<T>,
+    inner: S,
+    index: Lock<Index>,
+}
+
+impl<T, S: Storage<T>> SearchStorage<T, S> {
+    /// Index the objects held in `inner`.
+    pub fn new(inner: S) -> Self {
+        Self {
+            _marker: Default::default(),
+            inner,
+            index: Lock::new(Index {
+                rebuild: true,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// The proxies of the objects containing every word of `term`,
+    /// in handle order.
+    pub fn search(&self, term: &str) -> Vec<Proxy<T>>
+    where
+        T: Searchable,
+    {
+        let mut index = self.index.lock();
+        index.refresh(&self.inner);
+        index
+            .search(term)
+            .into_iter()
+            .filter_map(|index| self.inner.proxy(index).copied())
+            .collect()
+    }
+
+    fn index_mut(&mut self) -> &mut Index {
+        self.index.get_mut()
+    }
+}
+
+impl<T, S: Storage<T> + Default> Default for SearchStorage<T, S> {
+    fn default() -> Self {
+        Self::new(S::default())
+    }
+}
+
+impl<T, S: Storage<T> + Clone> Clone for SearchStorage<T, S> {
+    fn clone(&self) -> Self {
+        Self::new(self.inner.clone())
+    }
+}
+
+impl<T, S> sealed::Sealed for SearchStorage<T, S> {}
+
+impl<T, S: Storage<T>> Storage<T> for SearchStorage<T, S> {
+    fn insert(&mut self, proxy: Proxy<T>, value: T) -> Option<T> {
+        self.index_mut().stale.insert(proxy.index);
+        self.inner.insert(proxy, value)
+    }
+
+    fn get(&self, index: u64) -> Option<&T> {
+        self.inner.get(index)
+    }
+
+    fn get_mut(&mut self, index: u64) -> Option<&mut T> {
+        self.index_mut().stale.insert(index);
+        self.inner.get_mut(index)
+    }
+
+    fn proxy(&self, index: u64) -> Option<&Proxy<T>> {
+        self.inner.proxy(index)
+    }
+
+    fn proxy_from(&self, index: u64) -> Option<&Proxy<T>> {
+        self.inner.proxy_from(index)
+    }
+
+    fn remove(&mut self, index: u64) -> Option<T> {
+        self.index_mut().stale.insert(index);
+        self.inner.remove(index)
+    }
+
+    fn swap(&mut self, a: u64, b: u64) -> bool {
+        let index = self.index_mut();
+        index.stale.insert(a);
+        index.stale.insert(b);
+        self.inner.swap(a, b)
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn entries(&self) -> Entries<'_, T> {
+        self.inner.entries()
+    }
+
+    fn entries_mut(&mut self) -> EntriesMut<'_, T> {
+        self.index_mut().rebuild = true;
+        self.inner.entries_mut()
+    }
+
+    fn into_entries(self) -> Vec<(Proxy<T>, T)> {
+        self.inner.into_entries()
+    }
+}
+
+impl<T, S: LinkIndex<T>> LinkIndex<T> for SearchStorage<T, S> {
+    fn referrers_of(&self, target: &AnyProxy, out: &mut Vec<AnyProxy>) {
+        self.inner.referrers_of(target, out)
+    }
+}
+
+impl<T: Searchable, S: Storage<T>, A: HandleAllocator> Table<T, SearchStorage<T, S>, A> {
+    /// The proxies of the objects containing every word of `term`,
+    /// in handle order.
+    ///
+    /// Objects [marked as deleted](Table::mark_deleted) are left out.
+    pub fn search(&self, term: &str) -> Vec<Proxy<T>> {
+        let mut res = self.storage.search(term);
+        res.retain(|p| !self.is_deleted(p));
+        res
+    }
+}
+
+/// A context which holds a searchable table for `T`.
+///
+/// Implementations are generated by the
+/// [`persian_rug`](crate::persian_rug) attribute for tables declared
+/// with `#[table(search)]`.
+pub trait SearchOwner<T: Searchable> {
+    /// The proxies of the objects containing every word of `term`,
+    /// in handle order.
+    fn search_objects(&self, term: &str) -> Vec<Proxy<T>>;
+}
+
+/// Full-text search over a context.
+///
+/// This is implemented for every [`Context`].
+pub trait Search: Context {
+    /// The proxies of the objects of type `T` containing every word
+    /// of `term`, in handle order.
+    fn search<T>(&self, term: &str) -> Vec<Proxy<T>>
+    where
+        Self: SearchOwner<T>,
+        T: Searchable + Contextual<Context = Self>,
+    {
+        self.search_objects(term)
+    }
+}
+
+impl<C: Context> Search for C {}
@@ -0,0 +1,146 @@
+//! Helpers for populating a context with generated objects.
+//!
+//! Tests and benchmarks usually begin by filling a context with many
+//! objects that link to one another. The [`seed`] function runs a
+//! closure to build each object and adds it, returning the proxies
+//! in order. The closure receives the mutator, so it can read objects
+//! that were seeded earlier, or add other objects for the new one to
+//! refer to.
+//!
+//! When the shape of the data should vary, [`seed_with_rng`] also
+//! passes a [`SeedRng`], a small random number generator which always
+//! gives the same sequence for the same seed, so failures are
+//! reproducible.
+//!
+//! ```rust
+//! use persian_rug::{contextual, persian_rug, seed, seed_with_rng, Context, Proxy, SeedRng};
+//!
+//! #[contextual(Rug)]
+//! struct Author {
+//!   id: usize,
+//! }
+//!
+//! #[contextual(Rug)]
+//! struct Book {
+//!   author: Proxy<Author>,
+//!   pages: u64,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Author, #[table] Book);
+//!
+//! let mut r = Rug(Default::default(), Default::default());
+//!
+//! let authors = seed(&mut r, 10, |id, _| Author { id });
+//!
+//! let mut rng = SeedRng::new(42);
+//! let books = seed_with_rng(&mut r, 100, &mut rng, |_, _, rng| Book {
+//!     author: *rng.choose(&authors).unwrap(),
+//!     pages: rng.below(500) as u64 + 1,
+//! });
+//!
+//! assert_eq!(r.get_iter::<Author>().count(), 10);
+//! assert_eq!(books.len(), 100);
+//! assert!(books.iter().all(|b| authors.contains(&r.get(b).author)));
+//! ```
+
+use crate::{Contextual, Mutator, Owner, Proxy};
+
+/// Add `count` objects built by `f` to a context.
+///
+/// The closure is called with the position of the object being built
+/// (counting from zero) and the mutator. The proxies of the new
+/// objects are returned in the order they were built.
+pub fn seed<M, T, F>(mut mutator: M, count: usize, mut f: F) -> Vec<Proxy<T>>
+where
+    M: Mutator,
+    M::Context: Owner<T>,
+    T: Contextual<Context = M::Context>,
+    F: FnMut(usize, &mut M) -> T,
+{
+    (0..count)
+        .map(|ix| {
+            let value = f(ix, &mut mutator);
+            mutator.add(value)
+        })
+        .collect()
+}
+
+/// Add `count` objects built by `f` to a context, using a
+/// deterministic random number generator.
+///
+/// This is [`seed`], except that the closure also receives `rng`.
+/// The same generator can be passed to successive calls, so that a
+/// whole context is generated from a single seed.
+pub fn seed_with_rng<M, T, F>(
+    mutator: M,
+    count: usize,
+    rng: &mut SeedRng,
+    mut f: F,
+) -> Vec<Proxy<T>>
+where
+    M: Mutator,
+    M::Context: Owner<T>,
+    T: Contextual<Context = M::Context>,
+    F: FnMut(usize, &mut M, &mut SeedRng) -> T,
+{
+    seed(mutator, count, |ix, mutator| f(ix, mutator, rng))
+}
+
+/// A small, deterministic random number generator.
+///
+/// This is the SplitMix64 generator: it is fast and has good
+/// statistical behaviour for generating test data, but it is not
+/// suitable for cryptographic use. The sequence produced for a given
+/// seed will not change between releases.
+#[derive(Clone, Debug)]
+pub struct SeedRng {
+    state: u64,
+}
+
+impl SeedRng {
+    /// Create a generator from a seed.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Generate a random `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Generate a random number in `0..n`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "SeedRng::below called with an empty range");
+        // Take the high bits of the product, which avoids most of
+        // the bias of a plain modulus.
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    /// Generate a random number in `0.0..1.0`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Generate `true` with probability `p`.
+    pub fn chance(&mut self, p: f64) -> bool {
+        self.next_f64() < p
+    }
+
+    /// Choose an item from a slice, or `None` if it is empty.
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            None
+        } else {
+            Some(&items[self.below(items.len())])
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Implementations of the [`serde_diff`] traits.
+//!
+//! A [`Proxy`] is diffed as a single value. A [`Table`] is diffed
+//! object by object: each object present in both tables is entered by
+//! its handle and diffed in turn, so that only the fields which
+//! changed are sent, and each object present only in the newer table
+//! is sent whole. Applying a diff inserts new objects under the
+//! handles they had, so that proxies keep referring to the same
+//! objects in both tables.
+
+use serde::de::{self, DeserializeOwned, SeqAccess};
+use serde::ser::{self, SerializeSeq};
+use serde::Serialize;
+use serde_diff::{ApplyContext, DiffContext, DiffPathElementValue, SerdeDiff};
+
+use crate::handles::HandleAllocator;
+use crate::storage::Storage;
+use crate::{Proxy, Table};
+
+impl<T> SerdeDiff for Proxy<T> {
+    fn diff<'a, S: SerializeSeq>(
+        &self,
+        ctx: &mut DiffContext<'a, S>,
+        other: &Self,
+    ) -> Result<bool, S::Error> {
+        if self == other {
+            return Ok(false);
+        }
+        ctx.save_value(other)?;
+        Ok(true)
+    }
+
+    fn apply<'de, A: SeqAccess<'de>>(
+        &mut self,
+        seq: &mut A,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, A::Error> {
+        ctx.read_value(seq, self)
+    }
+}
+
+/// The position in the diff of the object with the given handle.
+fn position<E: ser::Error>(handle: u64) -> Result<usize, E> {
+    usize::try_from(handle).map_err(E::custom)
+}
+
+impl<T, S, A> SerdeDiff for Table<T, S, A>
+where
+    T: SerdeDiff + Serialize + DeserializeOwned,
+    S: Storage<T>,
+    A: HandleAllocator,
+{
+    fn diff<'a, Ser: SerializeSeq>(
+        &self,
+        ctx: &mut DiffContext<'a, Ser>,
+        other: &Self,
+    ) -> Result<bool, Ser::Error> {
+        let mut changed = false;
+        for (p, old) in self.storage.entries() {
+            let new = other.storage.get(p.index).ok_or_else(|| {
+                ser::Error::custom(format!(
+                    "object {} is missing from the newer table, and removals cannot be diffed",
+                    p.index
+                ))
+            })?;
+            ctx.push_collection_index(position(p.index)?);
+            changed |= <T as SerdeDiff>::diff(old, ctx, new)?;
+            ctx.pop_path_element()?;
+        }
+
+        let mut added = other
+            .storage
+            .entries()
+            .filter(|(p, _)| self.storage.get(p.index).is_none())
+            .collect::<Vec<_>>();
+        added.sort_by_key(|(p, _)| p.index);
+        for (p, new) in added {
+            ctx.push_collection_index(position(p.index)?);
+            ctx.push_collection_add();
+            ctx.save_value(new)?;
+            ctx.pop_path_element()?;
+            ctx.pop_path_element()?;
+            changed = true;
+        }
+        Ok(changed)
+    }
+
+    fn apply<'de, D: SeqAccess<'de>>(
+        &mut self,
+        seq: &mut D,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, D::Error> {
+        let mut changed = false;
+        while let Some(element) = ctx.next_path_element(seq)? {
+            let DiffPathElementValue::CollectionIndex(ix) = element else {
+                ctx.skip_value(seq)?;
+                continue;
+            };
+            let handle = ix as u64;
+            if let Some(value) = self.storage.get_mut(handle) {
+                changed |= <T as SerdeDiff>::apply(value, seq, ctx)?;
+                continue;
+            }
+            let mut added = None::<T>;
+            <Option<T> as SerdeDiff>::apply(&mut added, seq, ctx)?;
+            let value = added.ok_or_else(|| {
+                de::Error::custom(format!(
+                    "the diff changes object {}, which is not in the table",
+                    handle
+                ))
+            })?;
+            self.insert_with_handle(handle, value)
+                .map_err(de::Error::custom)?;
+            changed = true;
+        }
+        Ok(changed)
+    }
+}
@@ -0,0 +1,121 @@
+//! Implementations of the [`serde`] traits.
+//!
+//! A [`Proxy`] is serialized as its `u64` handle. With the `schemars`
+//! feature, its JSON Schema is that of an unsigned integer, with an
+//! `x-persian-rug-proxy` keyword naming the type of object it refers
+//! to.
+//!
+//! A [`Table`] is serialized as a structure with two fields: `next`,
+//! the next handle it will issue, and `entries`, a sequence of
+//! `(Proxy<T>, T)` pairs in handle order. As with the `borsh`
+//! encoding, this does not depend on the storage of the table, and
+//! deserializing it restores every object under its original handle.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::storage::Storage;
+use crate::{Proxy, Table};
+
+impl<T> Serialize for Proxy<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.index)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Proxy<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Proxy::from_handle(u64::deserialize(deserializer)?))
+    }
+}
+
+#[derive(Serialize)]
+struct TableRef<'a, T> {
+    next: u64,
+    entries: Vec<(&'a Proxy<T>, &'a T)>,
+}
+
+#[derive(Deserialize)]
+struct TableData<T> {
+    next: u64,
+    entries: Vec<(Proxy<T>, T)>,
+}
+
+impl<T, S> Serialize for Table<T, S>
+where
+    T: Serialize,
+    S: Storage<T>,
+{
+    fn serialize<Sr: Serializer>(&self, serializer: Sr) -> Result<Sr::Ok, Sr::Error> {
+        let mut entries = self.storage.entries().collect::<Vec<_>>();
+        entries.sort_by_key(|(p, _)| p.index);
+        TableRef {
+            next: self.handles.next,
+            entries,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T, S> Deserialize<'de> for Table<T, S>
+where
+    T: Deserialize<'de>,
+    S: Storage<T> + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = TableData::<T>::deserialize(deserializer)?;
+
+        let mut storage = S::default();
+        let mut last = None;
+        for (p, value) in data.entries {
+            if p.index >= data.next || last.is_some_and(|last| p.index <= last) {
+                return Err(D::Error::custom(format!(
+                    "unexpected table handle {}",
+                    p.index
+                )));
+            }
+            last = Some(p.index);
+            storage.insert(p, value);
+        }
+
+        Ok(Table {
+            _marker: Default::default(),
+            storage,
+            handles: crate::handles::Sequential { next: data.next },
+            #[cfg(feature = "profiling")]
+            counters: Default::default(),
+            #[cfg(feature = "provenance")]
+            provenance: Default::default(),
+            #[cfg(feature = "observe")]
+            observers: Default::default(),
+            deleted: Default::default(),
+            generations: Default::default(),
+        })
+    }
+}
+
+#[cfg(feature = "schemars")]
+impl<T> schemars::JsonSchema for Proxy<T> {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Proxy".into()
+    }
+
+    fn schema_id() -> std::borrow::Cow<'static, str> {
+        format!("persian_rug::Proxy<{}>", std::any::type_name::<T>()).into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        let target = std::any::type_name::<T>();
+        schemars::json_schema!({
+            "type": "integer",
+            "format": "uint64",
+            "minimum": 0,
+            "description": format!("The handle of a `{}`.", target),
+            "x-persian-rug-proxy": target,
+        })
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+
+use crate::Proxy;
+
+/// A sparse map of auxiliary data, keyed by [`Proxy`].
+///
+/// A side table lets you associate extra values with objects stored
+/// in a [`Context`](crate::Context) without changing the stored type
+/// itself. This is useful when the data belongs to some other part
+/// of the program: for example, an analysis pass over a tree of
+/// syntax nodes held in a context might want to record a result for
+/// some of the nodes, but the node type has no business knowing
+/// about that analysis.
+///
+/// Only the objects that have been given a value take up any space,
+/// so a side table is cheap to keep for a small subset of a large
+/// table. Because [`Proxy`] values are only meaningful for the
+/// context that issued them, a side table should only be used with
+/// proxies from a single context.
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, SideTable};
+///
+/// #[contextual(Rug)]
+/// struct Node {
+///   name: String,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Node);
+///
+/// let mut r = Rug(Default::default());
+/// let a = r.add(Node { name: "a".to_string() });
+/// let b = r.add(Node { name: "b".to_string() });
+///
+/// let mut depths = SideTable::new();
+/// depths.insert(a, 3);
+///
+/// assert_eq!(depths.get(&a), Some(&3));
+/// assert_eq!(depths.get(&b), None);
+/// ```
+pub struct SideTable<T, V> {
+    _marker: core::marker::PhantomData<T>,
+    values: BTreeMap<(u64, u32), V>,
+}
+
+impl<T, V> SideTable<T, V> {
+    /// Create a new, empty side table.
+    pub fn new() -> Self {
+        Self {
+            _marker: Default::default(),
+            values: BTreeMap::new(),
+        }
+    }
+
+    /// Associate a value with an object.
+    ///
+    /// If the object already had a value, it is replaced and the
+    /// old value is returned.
+    pub fn insert(&mut self, p: Proxy<T>, value: V) -> Option<V> {
+        self.values.insert(p.key(), value)
+    }
+
+    /// Retrieve the value associated with an object, if any.
+    pub fn get(&self, p: &Proxy<T>) -> Option<&V> {
+        self.values.get(&p.key())
+    }
+
+    /// Retrieve the value associated with an object mutably, if any.
+    pub fn get_mut(&mut self, p: &Proxy<T>) -> Option<&mut V> {
+        self.values.get_mut(&p.key())
+    }
+
+    /// Retrieve the value associated with an object, creating it
+    /// with the given function if it is not present.
+    pub fn get_or_insert_with<F>(&mut self, p: Proxy<T>, f: F) -> &mut V
+    where
+        F: FnOnce() -> V,
+    {
+        self.values.entry(p.key()).or_insert_with(f)
+    }
+
+    /// Check whether an object has an associated value.
+    pub fn contains(&self, p: &Proxy<T>) -> bool {
+        self.values.contains_key(&p.key())
+    }
+
+    /// Remove the value associated with an object, returning it.
+    pub fn remove(&mut self, p: &Proxy<T>) -> Option<V> {
+        self.values.remove(&p.key())
+    }
+
+    /// Remove all values.
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// The number of objects with an associated value.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Check whether no objects have an associated value.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Iterate over objects and their associated values.
+    ///
+    /// The iteration order is the same as the order of the
+    /// [`Proxy`] objects.
+    pub fn iter(&self) -> SideTableIterator<'_, T, V> {
+        SideTableIterator {
+            _marker: Default::default(),
+            iter: self.values.iter(),
+        }
+    }
+
+    /// Iterate over objects and mutable references to their
+    /// associated values.
+    pub fn iter_mut(&mut self) -> SideTableMutIterator<'_, T, V> {
+        SideTableMutIterator {
+            _marker: Default::default(),
+            iter: self.values.iter_mut(),
+        }
+    }
+}
+
+impl<T, V> Default for SideTable<T, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, V: Clone> Clone for SideTable<T, V> {
+    fn clone(&self) -> Self {
+        Self {
+            _marker: Default::default(),
+            values: self.values.clone(),
+        }
+    }
+}
+
+impl<T, V: std::fmt::Debug> std::fmt::Debug for SideTable<T, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.values.iter().map(|((index, _), value)| (index, value)))
+            .finish()
+    }
+}
+
+/// An [`Iterator`] over the entries of a [`SideTable`].
+///
+/// This is returned by [`SideTable::iter()`].
+pub struct SideTableIterator<'a, T, V> {
+    _marker: core::marker::PhantomData<T>,
+    iter: std::collections::btree_map::Iter<'a, (u64, u32), V>,
+}
+
+impl<'a, T, V> Iterator for SideTableIterator<'a, T, V> {
+    type Item = (Proxy<T>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter
+            .next()
+            .map(|(key, value)| (Proxy::from_key(*key), value))
+    }
+}
+
+/// An [`Iterator`] over the entries of a [`SideTable`], with mutable
+/// access to the values.
+///
+/// This is returned by [`SideTable::iter_mut()`].
+pub struct SideTableMutIterator<'a, T, V> {
+    _marker: core::marker::PhantomData<T>,
+    iter: std::collections::btree_map::IterMut<'a, (u64, u32), V>,
+}
+
+impl<'a, T, V> Iterator for SideTableMutIterator<'a, T, V> {
+    type Item = (Proxy<T>, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter
+            .next()
+            .map(|(key, value)| (Proxy::from_key(*key), value))
+    }
+}
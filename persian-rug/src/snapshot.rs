@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+/// A saved state of a context, which it can be rolled back to.
+///
+/// A snapshot is taken by [`Context::snapshot`](crate::Context::snapshot),
+/// and put back with [`Context::restore`](crate::Context::restore).
+/// Taking a snapshot clones the context once; the snapshot itself can
+/// then be cloned for free, so that one checkpoint can be restored
+/// many times, for example to run several simulations from the same
+/// starting point. Restoring the last remaining copy of a snapshot
+/// moves the saved state back into the context without copying it
+/// again. With the `im` feature, tables declared
+/// `#[table(persistent)]` share their objects with their clones, so
+/// that taking a snapshot of a large context is cheap too.
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context};
+///
+/// #[derive(Clone)]
+/// #[contextual(Rug)]
+/// struct Particle {
+///   position: i32,
+///   velocity: i32,
+/// }
+///
+/// #[derive(Clone)]
+/// #[persian_rug]
+/// struct Rug(#[table] Particle);
+///
+/// let mut r = Rug(Default::default());
+/// let p = r.add(Particle { position: 0, velocity: 1 });
+/// let start = r.snapshot();
+///
+/// for velocity in [1, 2, 3] {
+///     r.restore(start.clone());
+///     r.get_mut(&p).velocity = velocity;
+///     for _ in 0..10 {
+///         let particle = r.get_mut(&p);
+///         particle.position += particle.velocity;
+///     }
+///     assert_eq!(r.get(&p).position, 10 * velocity);
+/// }
+///
+/// r.restore(start);
+/// assert_eq!(r.get(&p).position, 0);
+/// ```
+///
+/// Proxies stay valid across a restore exactly when they were valid
+/// when the snapshot was taken: objects added since are gone, and
+/// objects deleted since are back.
+#[derive(Debug)]
+pub struct Snapshot<C> {
+    context: Arc<C>,
+}
+
+impl<C> Clone for Snapshot<C> {
+    fn clone(&self) -> Self {
+        Self {
+            context: self.context.clone(),
+        }
+    }
+}
+
+impl<C> Snapshot<C> {
+    pub(crate) fn new(context: C) -> Self {
+        Self {
+            context: Arc::new(context),
+        }
+    }
+
+    /// The context as it was when the snapshot was taken.
+    pub fn context(&self) -> &C {
+        &self.context
+    }
+
+    pub(crate) fn into_context(self) -> C
+    where
+        C: Clone,
+    {
+        Arc::try_unwrap(self.context).unwrap_or_else(|context| (*context).clone())
+    }
+}
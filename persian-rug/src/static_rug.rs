@@ -0,0 +1,146 @@
+use std::sync::{OnceLock, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{Context, Contextual, Owner, Proxy};
+
+/// A context which can be stored in a `static`.
+///
+/// Small programs such as command line tools often want a single
+/// context that every part of the program can reach, without having
+/// to work out who owns it. A `StaticRug` is set once, and then
+/// locked for each operation made through it: reads share the lock
+/// with each other, while changes take it exclusively.
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, Proxy, StaticRug};
+///
+/// #[contextual(Rug)]
+/// struct Task {
+///   title: &'static str,
+///   done: bool,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Task);
+///
+/// static RUG: StaticRug<Rug> = StaticRug::new();
+///
+/// fn add_task(title: &'static str) -> Proxy<Task> {
+///     RUG.add(Task { title, done: false })
+/// }
+///
+/// assert!(RUG.set(Rug(Default::default())).is_ok());
+/// let t = add_task("write docs");
+/// add_task("write tests");
+/// RUG.update(&t, |task| task.done = true);
+///
+/// assert!(RUG.get_with(&t, |task| task.done));
+/// let remaining = RUG.with(|rug| {
+///     rug.get_iter::<Task>().filter(|task| !task.done).count()
+/// });
+/// assert_eq!(remaining, 1);
+/// ```
+///
+/// For longer sections of code, the lock can be held explicitly with
+/// [`read`](StaticRug::read) or [`write`](StaticRug::write). A
+/// reference to the contents of the read guard is an
+/// [`Accessor`](crate::Accessor), and the write guard is itself a
+/// [`Mutator`](crate::Mutator).
+///
+/// A thread which panics while holding the lock does not poison the
+/// context for the rest of the program.
+pub struct StaticRug<C> {
+    cell: OnceLock<RwLock<C>>,
+}
+
+impl<C> StaticRug<C> {
+    /// Create a new, unset context holder.
+    pub const fn new() -> Self {
+        Self {
+            cell: OnceLock::new(),
+        }
+    }
+}
+
+impl<C> Default for StaticRug<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Context> StaticRug<C> {
+    /// Set the context.
+    ///
+    /// Returns the context back if one was already set.
+    pub fn set(&self, context: C) -> Result<(), C> {
+        self.cell
+            .set(RwLock::new(context))
+            .map_err(|lock| lock.into_inner().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Check whether the context has been set.
+    pub fn is_set(&self) -> bool {
+        self.cell.get().is_some()
+    }
+
+    fn lock(&self) -> &RwLock<C> {
+        self.cell
+            .get()
+            .expect("StaticRug used before its context was set")
+    }
+
+    /// Lock the context for reading.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context has not been set.
+    pub fn read(&self) -> RwLockReadGuard<'_, C> {
+        self.lock().read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the context for writing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context has not been set.
+    pub fn write(&self) -> RwLockWriteGuard<'_, C> {
+        self.lock().write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Call `f` with the context locked for reading.
+    pub fn with<R>(&self, f: impl FnOnce(&C) -> R) -> R {
+        f(&self.read())
+    }
+
+    /// Call `f` with the context locked for writing.
+    pub fn with_mut<R>(&self, f: impl FnOnce(&mut C) -> R) -> R {
+        f(&mut self.write())
+    }
+
+    /// Add an object to the context.
+    #[track_caller]
+    pub fn add<T>(&self, value: T) -> Proxy<T>
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        Owner::add(&mut *self.write(), value)
+    }
+
+    /// Call `f` with an object from the context.
+    pub fn get_with<T, R>(&self, what: &Proxy<T>, f: impl FnOnce(&T) -> R) -> R
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        f(Owner::get(&*self.read(), what))
+    }
+
+    /// Call `f` to modify an object in the context.
+    pub fn update<T, R>(&self, what: &Proxy<T>, f: impl FnOnce(&mut T) -> R) -> R
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        f(Owner::get_mut(&mut *self.write(), what))
+    }
+}
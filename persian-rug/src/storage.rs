@@ -0,0 +1,769 @@
+//! Storage strategies for [`Table`](crate::Table).
+//!
+//! A [`Table`](crate::Table) is responsible for assigning handles to
+//! the objects it holds, and delegates keeping the objects themselves
+//! to an implementation of [`Storage`]. The default,
+//! [`MapStorage`], is a good general choice. [`ArenaStorage`] keeps
+//! objects packed together in large blocks, in the order in which
+//! they were inserted, which can make traversal-heavy workloads
+//! faster.
+//!
+//! The storage of a table is selected with its second type
+//! parameter. Within a [`persian_rug`](crate::persian_rug) context,
+//! you can select arena storage for a table with `#[table(arena)]`:
+//!
+//! ```rust
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Node {
+//!   value: i32,
+//!   children: Vec<Proxy<Node>>,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table(arena)] Node);
+//!
+//! let mut r = Rug(Default::default());
+//! let leaf = r.add(Node { value: 1, children: Vec::new() });
+//! let root = r.add(Node { value: 2, children: vec![leaf] });
+//! assert_eq!(r.get(&r.get(&root).children[0]).value, 1);
+//! ```
+//!
+//! Arena storage obtains its blocks from an [`Allocator`], which is
+//! [`Global`] unless another is given as `#[table(arena(MyAlloc))]`.
+//! The allocator type must implement [`Default`] for the context to be
+//! constructed with `Default::default()`; otherwise, build the table
+//! with [`Table::with_storage`](crate::Table::with_storage) and
+//! [`ArenaStorage::new_in`].
+//!
+//! With the `im` feature, `PersistentStorage` keeps objects in
+//! persistent data structures from the `im` crate, so that cloning a
+//! table shares the objects with the clone instead of copying them.
+//! It is selected with `#[table(persistent)]`.
+
+use std::alloc::Layout;
+use std::collections::BTreeMap;
+use std::ptr::NonNull;
+
+use crate::Proxy;
+
+pub(crate) mod sealed {
+    pub trait Sealed {}
+}
+
+/// The way in which a [`Table`](crate::Table) holds its objects.
+///
+/// Each stored object is kept together with the [`Proxy`] that was
+/// issued for it, and is looked up by the index of that proxy. This
+/// trait is sealed: the available implementations are
+/// [`MapStorage`], [`ArenaStorage`] and `PersistentStorage`, together
+/// with wrappers such as `SearchStorage` which add to another
+/// storage.
+pub trait Storage<T>: sealed::Sealed {
+    /// Store a value under the index of its proxy, returning any
+    /// value previously stored there.
+    fn insert(&mut self, proxy: Proxy<T>, value: T) -> Option<T>;
+
+    /// Retrieve the value stored under an index.
+    fn get(&self, index: u64) -> Option<&T>;
+
+    /// Retrieve the value stored under an index mutably.
+    fn get_mut(&mut self, index: u64) -> Option<&mut T>;
+
+    /// Retrieve the proxy stored under an index.
+    fn proxy(&self, index: u64) -> Option<&Proxy<T>>;
+
+    /// Retrieve the proxy stored under the smallest index which is
+    /// not less than `index`.
+    fn proxy_from(&self, index: u64) -> Option<&Proxy<T>>;
+
+    /// Take out the value stored under an index.
+    ///
+    /// Returns [`None`], leaving the storage unchanged, if the index
+    /// holds no value.
+    fn remove(&mut self, index: u64) -> Option<T>;
+
+    /// Exchange the values stored under two indices.
+    ///
+    /// Returns `false`, leaving the storage unchanged, if either index
+    /// holds no value.
+    fn swap(&mut self, a: u64, b: u64) -> bool;
+
+    /// The number of values stored.
+    fn len(&self) -> usize;
+
+    /// Check whether no values are stored.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over the stored proxies and values.
+    fn entries(&self) -> Entries<'_, T>;
+
+    /// Iterate over the stored proxies and mutable values.
+    fn entries_mut(&mut self) -> EntriesMut<'_, T>;
+
+    /// Take the stored proxies and values, in iteration order.
+    fn into_entries(self) -> Vec<(Proxy<T>, T)>
+    where
+        Self: Sized;
+}
+
+/// Storage in an ordered map, keyed by handle.
+///
+/// This is the default storage for a [`Table`](crate::Table). Objects
+/// are iterated in handle order.
+pub struct MapStorage<T> {
+    members: BTreeMap<u64, (Proxy<T>, T)>,
+}
+
+impl<T> Default for MapStorage<T> {
+    fn default() -> Self {
+        Self {
+            members: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: Clone> Clone for MapStorage<T> {
+    fn clone(&self) -> Self {
+        Self {
+            members: self.members.clone(),
+        }
+    }
+}
+
+impl<T> sealed::Sealed for MapStorage<T> {}
+
+impl<T> Storage<T> for MapStorage<T> {
+    fn insert(&mut self, proxy: Proxy<T>, value: T) -> Option<T> {
+        self.members
+            .insert(proxy.index, (proxy, value))
+            .map(|(_, old)| old)
+    }
+
+    fn get(&self, index: u64) -> Option<&T> {
+        self.members.get(&index).map(|(_, value)| value)
+    }
+
+    fn get_mut(&mut self, index: u64) -> Option<&mut T> {
+        self.members.get_mut(&index).map(|(_, value)| value)
+    }
+
+    fn proxy(&self, index: u64) -> Option<&Proxy<T>> {
+        self.members.get(&index).map(|(proxy, _)| proxy)
+    }
+
+    fn proxy_from(&self, index: u64) -> Option<&Proxy<T>> {
+        self.members
+            .range(index..)
+            .next()
+            .map(|(_, (proxy, _))| proxy)
+    }
+
+    fn remove(&mut self, index: u64) -> Option<T> {
+        self.members.remove(&index).map(|(_, value)| value)
+    }
+
+    fn swap(&mut self, a: u64, b: u64) -> bool {
+        if !self.members.contains_key(&a) || !self.members.contains_key(&b) {
+            return false;
+        }
+        if a != b {
+            let (proxy, mut value) = self.members.remove(&a).unwrap();
+            std::mem::swap(&mut value, &mut self.members.get_mut(&b).unwrap().1);
+            self.members.insert(a, (proxy, value));
+        }
+        true
+    }
+
+    fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    fn entries(&self) -> Entries<'_, T> {
+        Entries {
+            iter: EntriesInner::Map(self.members.values()),
+        }
+    }
+
+    fn entries_mut(&mut self) -> EntriesMut<'_, T> {
+        EntriesMut {
+            iter: EntriesMutInner::Map(self.members.values_mut()),
+        }
+    }
+
+    fn into_entries(self) -> Vec<(Proxy<T>, T)> {
+        self.members.into_values().collect()
+    }
+}
+
+/// A source of memory for the blocks of an [`ArenaStorage`].
+///
+/// This is a minimal allocator interface, so that objects stored in
+/// a table can be placed in memory pools, shared memory regions, or
+/// allocators that track their usage. [`Global`] forwards to the
+/// global allocator, and is the default.
+///
+/// Arena storage allocates large blocks (of around
+/// [`ARENA_BLOCK_BYTES`] bytes) for the objects themselves, and
+/// releases them when the storage is dropped. The index from handles
+/// to positions within the blocks is small by comparison, and always
+/// uses the global allocator.
+///
+/// # Safety
+///
+/// A successful call to [`allocate`](Allocator::allocate) must return
+/// a pointer to a region of memory that is valid for reads and writes
+/// of at least `layout.size()` bytes, aligned to `layout.align()`, and
+/// which is not used for anything else until it is passed back to
+/// [`deallocate`](Allocator::deallocate).
+pub unsafe trait Allocator {
+    /// Allocate memory for the given layout, returning `None` on
+    /// failure.
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>>;
+
+    /// Release memory previously obtained from this allocator.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by [`allocate`](Allocator::allocate)
+    /// on this allocator, called with the same `layout`, and must not
+    /// have been deallocated already.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+}
+
+/// The global allocator.
+///
+/// This is the default [`Allocator`] for [`ArenaStorage`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Global;
+
+unsafe impl Allocator for Global {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        // Safety: arena blocks always have a non-zero size.
+        NonNull::new(unsafe { std::alloc::alloc(layout) })
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        std::alloc::dealloc(ptr.as_ptr(), layout)
+    }
+}
+
+unsafe impl<A: Allocator + ?Sized> Allocator for &A {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        (**self).allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        (**self).deallocate(ptr, layout)
+    }
+}
+
+unsafe impl<A: Allocator + ?Sized> Allocator for std::sync::Arc<A> {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        (**self).allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        (**self).deallocate(ptr, layout)
+    }
+}
+
+/// The size in bytes of each block allocated by an [`ArenaStorage`].
+pub const ARENA_BLOCK_BYTES: usize = 16384;
+
+type Entry<T> = (Proxy<T>, T);
+
+/// A fixed-capacity block of entries, allocated by the owning
+/// [`ArenaStorage`]. Only the first `len` entries are initialised.
+struct Block<T> {
+    ptr: NonNull<Entry<T>>,
+    len: usize,
+}
+
+impl<T> Block<T> {
+    fn as_slice(&self) -> &[Entry<T>] {
+        // Safety: the first len entries are initialised.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [Entry<T>] {
+        // Safety: the first len entries are initialised, and we hold
+        // the only reference to the block.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+/// Storage in large blocks, in insertion order.
+///
+/// Objects are placed one after another in blocks of around
+/// [`ARENA_BLOCK_BYTES`] bytes, so objects that are inserted together
+/// end up next to one another in memory. Blocks are never moved or
+/// resized once allocated, so growing the table does not copy the
+/// objects it already holds.
+///
+/// Iteration visits objects in the order in which they were inserted,
+/// which walks straight through memory. Lookup by proxy costs the same
+/// as for [`MapStorage`]. Removing an object moves the most recently
+/// inserted one into its place, to keep the blocks packed, so after a
+/// removal that object is visited earlier than before.
+///
+/// The blocks are obtained from the [`Allocator`] `A`. When that
+/// allocator does not implement [`Default`], create the storage with
+/// [`new_in`](ArenaStorage::new_in) and the table with
+/// [`Table::with_storage`](crate::Table::with_storage).
+pub struct ArenaStorage<T, A: Allocator = Global> {
+    blocks: Vec<Block<T>>,
+    positions: BTreeMap<u64, usize>,
+    alloc: A,
+    _owns: core::marker::PhantomData<Entry<T>>,
+}
+
+// Safety: the storage owns its entries outright, so it can move
+// between threads, or be shared between them, exactly when the
+// entries and the allocator can.
+unsafe impl<T: Send, A: Allocator + Send> Send for ArenaStorage<T, A> {}
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for ArenaStorage<T, A> {}
+
+impl<T, A: Allocator> ArenaStorage<T, A> {
+    /// Create empty storage whose blocks come from the given
+    /// allocator.
+    pub fn new_in(alloc: A) -> Self {
+        Self {
+            blocks: Vec::new(),
+            positions: BTreeMap::new(),
+            alloc,
+            _owns: Default::default(),
+        }
+    }
+
+    /// The allocator used for this storage.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    fn block_capacity() -> usize {
+        (ARENA_BLOCK_BYTES / std::mem::size_of::<Entry<T>>().max(1)).max(1)
+    }
+
+    fn block_layout() -> Layout {
+        Layout::array::<Entry<T>>(Self::block_capacity()).unwrap()
+    }
+
+    fn locate(position: usize) -> (usize, usize) {
+        let cap = Self::block_capacity();
+        (position / cap, position % cap)
+    }
+
+    fn push(&mut self, entry: Entry<T>) -> usize {
+        let cap = Self::block_capacity();
+        if self.blocks.last().is_none_or(|block| block.len == cap) {
+            let layout = Self::block_layout();
+            let ptr = self
+                .alloc
+                .allocate(layout)
+                .unwrap_or_else(|| std::alloc::handle_alloc_error(layout));
+            self.blocks.push(Block {
+                ptr: ptr.cast(),
+                len: 0,
+            });
+        }
+        let position = (self.blocks.len() - 1) * cap;
+        let block = self.blocks.last_mut().unwrap();
+        // Safety: the block has space for cap entries, and len < cap.
+        unsafe { block.ptr.as_ptr().add(block.len).write(entry) };
+        block.len += 1;
+        position + block.len - 1
+    }
+}
+
+impl<T, A: Allocator + Default> Default for ArenaStorage<T, A> {
+    fn default() -> Self {
+        Self::new_in(Default::default())
+    }
+}
+
+impl<T: Clone, A: Allocator + Clone> Clone for ArenaStorage<T, A> {
+    fn clone(&self) -> Self {
+        let mut res = Self::new_in(self.alloc.clone());
+        for block in &self.blocks {
+            for entry in block.as_slice() {
+                res.push(entry.clone());
+            }
+        }
+        res.positions = self.positions.clone();
+        res
+    }
+}
+
+impl<T, A: Allocator> Drop for ArenaStorage<T, A> {
+    fn drop(&mut self) {
+        let layout = Self::block_layout();
+        for block in self.blocks.drain(..) {
+            // Safety: the first len entries are initialised, and the
+            // block was allocated by our allocator with this layout.
+            unsafe {
+                std::ptr::drop_in_place(std::ptr::slice_from_raw_parts_mut(
+                    block.ptr.as_ptr(),
+                    block.len,
+                ));
+                self.alloc.deallocate(block.ptr.cast(), layout);
+            }
+        }
+    }
+}
+
+impl<T, A: Allocator> sealed::Sealed for ArenaStorage<T, A> {}
+
+impl<T, A: Allocator> Storage<T> for ArenaStorage<T, A> {
+    fn insert(&mut self, proxy: Proxy<T>, value: T) -> Option<T> {
+        if let Some(position) = self.positions.get(&proxy.index) {
+            let (block, offset) = Self::locate(*position);
+            return Some(std::mem::replace(
+                &mut self.blocks[block].as_mut_slice()[offset].1,
+                value,
+            ));
+        }
+
+        let position = self.push((proxy, value));
+        self.positions.insert(proxy.index, position);
+        None
+    }
+
+    fn get(&self, index: u64) -> Option<&T> {
+        self.positions.get(&index).map(|position| {
+            let (block, offset) = Self::locate(*position);
+            &self.blocks[block].as_slice()[offset].1
+        })
+    }
+
+    fn get_mut(&mut self, index: u64) -> Option<&mut T> {
+        self.positions.get(&index).map(|position| {
+            let (block, offset) = Self::locate(*position);
+            &mut self.blocks[block].as_mut_slice()[offset].1
+        })
+    }
+
+    fn proxy(&self, index: u64) -> Option<&Proxy<T>> {
+        self.positions.get(&index).map(|position| {
+            let (block, offset) = Self::locate(*position);
+            &self.blocks[block].as_slice()[offset].0
+        })
+    }
+
+    fn proxy_from(&self, index: u64) -> Option<&Proxy<T>> {
+        self.positions.range(index..).next().map(|(_, position)| {
+            let (block, offset) = Self::locate(*position);
+            &self.blocks[block].as_slice()[offset].0
+        })
+    }
+
+    fn remove(&mut self, index: u64) -> Option<T> {
+        let position = self.positions.remove(&index)?;
+        let last = self.positions.len();
+        let (block, offset) = Self::locate(last);
+        // Safety: the entry at the last position is initialised, and
+        // shortening the block means it is read exactly once.
+        let entry = unsafe {
+            self.blocks[block].len -= 1;
+            self.blocks[block].ptr.as_ptr().add(offset).read()
+        };
+        let removed = if position == last {
+            entry
+        } else {
+            self.positions.insert(entry.0.index, position);
+            let (block, offset) = Self::locate(position);
+            // Safety: every position before the last is initialised.
+            unsafe { std::ptr::replace(self.blocks[block].ptr.as_ptr().add(offset), entry) }
+        };
+        if self.blocks[block].len == 0 {
+            let block = self.blocks.pop().unwrap();
+            // Safety: the block holds no entries, and was allocated by
+            // our allocator with this layout.
+            unsafe {
+                self.alloc
+                    .deallocate(block.ptr.cast(), Self::block_layout())
+            };
+        }
+        Some(removed.1)
+    }
+
+    fn swap(&mut self, a: u64, b: u64) -> bool {
+        let (Some(a), Some(b)) = (self.positions.get(&a), self.positions.get(&b)) else {
+            return false;
+        };
+        if a != b {
+            let (block_a, offset_a) = Self::locate(*a);
+            let (block_b, offset_b) = Self::locate(*b);
+            // Safety: both entries are initialised, we hold the only
+            // reference to the blocks, and distinct positions do not
+            // overlap.
+            unsafe {
+                let a = self.blocks[block_a].ptr.as_ptr().add(offset_a);
+                let b = self.blocks[block_b].ptr.as_ptr().add(offset_b);
+                std::ptr::swap(&raw mut (*a).1, &raw mut (*b).1);
+            }
+        }
+        true
+    }
+
+    fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    fn entries(&self) -> Entries<'_, T> {
+        Entries {
+            iter: EntriesInner::Arena(self.blocks.iter().flat_map(Block::as_slice)),
+        }
+    }
+
+    fn entries_mut(&mut self) -> EntriesMut<'_, T> {
+        EntriesMut {
+            iter: EntriesMutInner::Arena(self.blocks.iter_mut().flat_map(Block::as_mut_slice)),
+        }
+    }
+
+    fn into_entries(mut self) -> Vec<(Proxy<T>, T)> {
+        let mut res = Vec::with_capacity(self.len());
+        for block in self.blocks.iter_mut() {
+            // Mark the entries as moved out before reading them, so
+            // that dropping the storage only frees the blocks.
+            let len = std::mem::replace(&mut block.len, 0);
+            for offset in 0..len {
+                // Safety: the first len entries were initialised, and
+                // each is read exactly once.
+                res.push(unsafe { block.ptr.as_ptr().add(offset).read() });
+            }
+        }
+        res
+    }
+}
+
+/// Storage in persistent data structures, which share their contents
+/// with their clones.
+///
+/// Cloning this storage takes constant time, however many objects it
+/// holds: the clone shares the objects with the original, and each
+/// copy only duplicates the parts of its structure that it changes
+/// afterwards. This makes it cheap to keep old versions of a context
+/// around, such as a [`Snapshot`](crate::Snapshot), or the copies made
+/// by the `clone-replace` integration. The price is that each lookup
+/// and change is somewhat slower than with [`MapStorage`], and the
+/// first change to an object after a clone copies it. Objects must
+/// implement [`Clone`].
+///
+/// Iteration visits objects in the order in which they were inserted.
+/// Removing an object moves the most recently inserted one into its
+/// place, as for [`ArenaStorage`].
+///
+/// This requires the `im` feature. Within a
+/// [`persian_rug`](crate::persian_rug) context, select it with
+/// `#[table(persistent)]`:
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context};
+///
+/// #[derive(Clone)]
+/// #[contextual(Rug)]
+/// struct Cell {
+///   alive: bool,
+/// }
+///
+/// #[derive(Clone)]
+/// #[persian_rug]
+/// struct Rug(#[table(persistent)] Cell);
+///
+/// let mut r = Rug(Default::default());
+/// let cells = (0..1000).map(|_| r.add(Cell { alive: false })).collect::<Vec<_>>();
+/// let before = r.clone();
+/// r.get_mut(&cells[7]).alive = true;
+/// assert!(!before.get(&cells[7]).alive);
+/// assert!(r.get(&cells[7]).alive);
+/// ```
+///
+/// A [`Table`](crate::Table) also remembers the generation of each
+/// handle which has been deleted, and copies that record when cloned,
+/// so a table which has seen many deletions is not entirely free to
+/// clone.
+#[cfg(feature = "im")]
+pub struct PersistentStorage<T> {
+    entries: im::Vector<Entry<T>>,
+    positions: im::OrdMap<u64, usize>,
+}
+
+#[cfg(feature = "im")]
+impl<T: Clone> Default for PersistentStorage<T> {
+    fn default() -> Self {
+        Self {
+            entries: im::Vector::new(),
+            positions: im::OrdMap::new(),
+        }
+    }
+}
+
+#[cfg(feature = "im")]
+impl<T: Clone> Clone for PersistentStorage<T> {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            positions: self.positions.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "im")]
+impl<T> sealed::Sealed for PersistentStorage<T> {}
+
+#[cfg(feature = "im")]
+impl<T: Clone> Storage<T> for PersistentStorage<T> {
+    fn insert(&mut self, proxy: Proxy<T>, value: T) -> Option<T> {
+        if let Some(position) = self.positions.get(&proxy.index) {
+            return Some(std::mem::replace(
+                &mut self.entries.get_mut(*position).unwrap().1,
+                value,
+            ));
+        }
+
+        self.positions.insert(proxy.index, self.entries.len());
+        self.entries.push_back((proxy, value));
+        None
+    }
+
+    fn get(&self, index: u64) -> Option<&T> {
+        self.positions
+            .get(&index)
+            .map(|position| &self.entries[*position].1)
+    }
+
+    fn get_mut(&mut self, index: u64) -> Option<&mut T> {
+        let position = *self.positions.get(&index)?;
+        self.entries.get_mut(position).map(|(_, value)| value)
+    }
+
+    fn proxy(&self, index: u64) -> Option<&Proxy<T>> {
+        self.positions
+            .get(&index)
+            .map(|position| &self.entries[*position].0)
+    }
+
+    fn proxy_from(&self, index: u64) -> Option<&Proxy<T>> {
+        self.positions
+            .range(index..)
+            .next()
+            .map(|(_, position)| &self.entries[*position].0)
+    }
+
+    fn remove(&mut self, index: u64) -> Option<T> {
+        let position = self.positions.remove(&index)?;
+        let entry = self.entries.pop_back().unwrap();
+        let removed = if position == self.entries.len() {
+            entry
+        } else {
+            self.positions.insert(entry.0.index, position);
+            self.entries.set(position, entry)
+        };
+        Some(removed.1)
+    }
+
+    fn swap(&mut self, a: u64, b: u64) -> bool {
+        let (Some(a), Some(b)) = (self.positions.get(&a), self.positions.get(&b)) else {
+            return false;
+        };
+        let (a, b) = (*a, *b);
+        if a != b {
+            // Swap the entries whole, then put the proxies back.
+            let (proxy_a, proxy_b) = (self.entries[a].0, self.entries[b].0);
+            self.entries.swap(a, b);
+            self.entries.get_mut(a).unwrap().0 = proxy_a;
+            self.entries.get_mut(b).unwrap().0 = proxy_b;
+        }
+        true
+    }
+
+    fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    fn entries(&self) -> Entries<'_, T> {
+        Entries {
+            iter: EntriesInner::Persistent(Box::new(self.entries.iter())),
+        }
+    }
+
+    fn entries_mut(&mut self) -> EntriesMut<'_, T> {
+        EntriesMut {
+            iter: EntriesMutInner::Persistent(Box::new(self.entries.iter_mut())),
+        }
+    }
+
+    fn into_entries(self) -> Vec<(Proxy<T>, T)> {
+        self.entries.into_iter().collect()
+    }
+}
+
+type BlockEntries<'a, T> = std::iter::FlatMap<
+    std::slice::Iter<'a, Block<T>>,
+    &'a [Entry<T>],
+    fn(&Block<T>) -> &[Entry<T>],
+>;
+
+type BlockEntriesMut<'a, T> = std::iter::FlatMap<
+    std::slice::IterMut<'a, Block<T>>,
+    &'a mut [Entry<T>],
+    fn(&mut Block<T>) -> &mut [Entry<T>],
+>;
+
+enum EntriesInner<'a, T> {
+    Map(std::collections::btree_map::Values<'a, u64, Entry<T>>),
+    Arena(BlockEntries<'a, T>),
+    #[cfg(feature = "im")]
+    Persistent(Box<dyn Iterator<Item = &'a Entry<T>> + 'a>),
+}
+
+/// An [`Iterator`] over the proxies and values held by a [`Storage`].
+pub struct Entries<'a, T> {
+    iter: EntriesInner<'a, T>,
+}
+
+impl<'a, T> Iterator for Entries<'a, T> {
+    type Item = (&'a Proxy<T>, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.iter {
+            EntriesInner::Map(iter) => iter.next(),
+            EntriesInner::Arena(iter) => iter.next(),
+            #[cfg(feature = "im")]
+            EntriesInner::Persistent(iter) => iter.next(),
+        }
+        .map(|(proxy, value)| (proxy, value))
+    }
+}
+
+enum EntriesMutInner<'a, T> {
+    Map(std::collections::btree_map::ValuesMut<'a, u64, Entry<T>>),
+    Arena(BlockEntriesMut<'a, T>),
+    #[cfg(feature = "im")]
+    Persistent(Box<dyn Iterator<Item = &'a mut Entry<T>> + 'a>),
+}
+
+/// An [`Iterator`] over the proxies and mutable values held by a
+/// [`Storage`].
+pub struct EntriesMut<'a, T> {
+    iter: EntriesMutInner<'a, T>,
+}
+
+impl<'a, T> Iterator for EntriesMut<'a, T> {
+    type Item = (&'a Proxy<T>, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.iter {
+            EntriesMutInner::Map(iter) => iter.next(),
+            EntriesMutInner::Arena(iter) => iter.next(),
+            #[cfg(feature = "im")]
+            EntriesMutInner::Persistent(iter) => iter.next(),
+        }
+        .map(|(proxy, value)| (&*proxy, value))
+    }
+}
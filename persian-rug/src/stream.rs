@@ -0,0 +1,303 @@
+//! Iterating over tables as asynchronous streams.
+//!
+//! This module is available with the `async` feature. Iterating over
+//! a large table from an asynchronous task would keep the executor
+//! busy until it finished, so the streams here yield control back to
+//! the executor after each batch of objects.
+//!
+//! There are two kinds of stream. A [`TableStream`] owns its access
+//! to the context, for example a lock guard, for as long as it runs,
+//! just as an [`OwnedTableIterator`](crate::OwnedTableIterator) does.
+//! A [`RelockTableStream`] instead holds the lock itself, and only
+//! locks it while it reads each batch, so that other tasks can use
+//! the context in between. Since standard lock guards cannot be sent
+//! between threads, this is also the kind of stream to use in tasks
+//! which must be [`Send`].
+//!
+//! ```rust
+//! use std::pin::pin;
+//! use std::sync::{Arc, Mutex};
+//! use std::task::{Context, Poll, Waker};
+//!
+//! use persian_rug::futures_core::Stream;
+//! use persian_rug::stream::RelockTableStream;
+//! use persian_rug::{contextual, persian_rug};
+//!
+//! #[derive(Clone)]
+//! #[contextual(Rug)]
+//! struct Foo {
+//!   a: i32,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Foo);
+//!
+//! let rug = Arc::new(Mutex::new(Rug(Default::default())));
+//! for a in 0..10 {
+//!     persian_rug::Context::add(&mut *rug.lock().unwrap(), Foo { a });
+//! }
+//!
+//! let mut stream = pin!(RelockTableStream::<_, Foo>::new(rug.clone()).batch_size(4));
+//! let mut cx = Context::from_waker(Waker::noop());
+//! let mut total = 0;
+//! loop {
+//!     match stream.as_mut().poll_next(&mut cx) {
+//!         Poll::Ready(Some(foo)) => total += foo.a,
+//!         Poll::Ready(None) => break,
+//!         // Between batches, the lock is free.
+//!         Poll::Pending => assert!(rug.try_lock().is_ok()),
+//!     }
+//! }
+//! assert_eq!(total, 45);
+//! ```
+
+use std::collections::VecDeque;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::task::{Context as TaskContext, Poll};
+
+use futures_core::Stream;
+
+use crate::{Contextual, Owner, Proxy, StaticRug};
+
+/// The number of objects a stream produces between yields, unless
+/// another is chosen.
+pub const DEFAULT_BATCH_SIZE: usize = 256;
+
+/// A stream of clones of [`Contextual`] objects, which owns its
+/// access to the context.
+///
+/// This is created from anything which dereferences to the context,
+/// such as a lock guard or an [`Arc`]. The proxies to stream are
+/// collected when the stream is created, and after each batch of
+/// objects the stream yields to the executor.
+pub struct TableStream<G, T> {
+    guard: G,
+    proxies: std::vec::IntoIter<Proxy<T>>,
+    batch_size: usize,
+    remaining: usize,
+}
+
+impl<G, T> TableStream<G, T>
+where
+    G: Deref,
+    G::Target: Owner<T>,
+    T: Contextual<Context = G::Target>,
+{
+    /// Create a stream over the objects of type `T` in the context
+    /// that `guard` dereferences to.
+    pub fn new(guard: G) -> Self {
+        let proxies = Owner::<T>::get_proxy_iter(&*guard)
+            .copied()
+            .collect::<Vec<_>>()
+            .into_iter();
+        Self {
+            guard,
+            proxies,
+            batch_size: DEFAULT_BATCH_SIZE,
+            remaining: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Set the number of objects to produce between yields.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `batch_size` is zero.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must not be zero");
+        self.batch_size = batch_size;
+        self.remaining = batch_size;
+        self
+    }
+
+    /// Stop streaming, and recover the guard.
+    pub fn into_guard(self) -> G {
+        self.guard
+    }
+}
+
+impl<G, T> Unpin for TableStream<G, T> {}
+
+impl<G, T> Stream for TableStream<G, T>
+where
+    G: Deref,
+    G::Target: Owner<T>,
+    T: Contextual<Context = G::Target> + Clone,
+{
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        if self.proxies.len() == 0 {
+            return Poll::Ready(None);
+        }
+        if self.remaining == 0 {
+            self.remaining = self.batch_size;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        self.remaining -= 1;
+        let p = self.proxies.next().unwrap();
+        Poll::Ready(Some(Owner::get(&*self.guard, &p).clone()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.proxies.size_hint()
+    }
+}
+
+/// A lock around a context, which can be taken for a short time.
+///
+/// This is implemented for [`Mutex`], [`RwLock`] and [`StaticRug`],
+/// and for references to and [`Arc`]s of them. Poisoned locks are
+/// used as they are.
+pub trait Lock {
+    /// The context inside the lock.
+    type Context;
+
+    /// Call `f` with the context locked for reading.
+    fn with<R>(&self, f: impl FnOnce(&Self::Context) -> R) -> R;
+}
+
+impl<C> Lock for Mutex<C> {
+    type Context = C;
+
+    fn with<R>(&self, f: impl FnOnce(&C) -> R) -> R {
+        f(&self.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl<C> Lock for RwLock<C> {
+    type Context = C;
+
+    fn with<R>(&self, f: impl FnOnce(&C) -> R) -> R {
+        f(&self.read().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl<C: crate::Context> Lock for StaticRug<C> {
+    type Context = C;
+
+    fn with<R>(&self, f: impl FnOnce(&C) -> R) -> R {
+        StaticRug::with(self, f)
+    }
+}
+
+impl<L: Lock + ?Sized> Lock for &L {
+    type Context = L::Context;
+
+    fn with<R>(&self, f: impl FnOnce(&Self::Context) -> R) -> R {
+        (**self).with(f)
+    }
+}
+
+impl<L: Lock + ?Sized> Lock for Arc<L> {
+    type Context = L::Context;
+
+    fn with<R>(&self, f: impl FnOnce(&Self::Context) -> R) -> R {
+        (**self).with(f)
+    }
+}
+
+/// A stream of clones of [`Contextual`] objects, which locks the
+/// context only while it reads each batch.
+///
+/// The proxies to stream are collected the first time the stream is
+/// polled. Objects added to the table after that are not included.
+pub struct RelockTableStream<L, T> {
+    lock: L,
+    proxies: Option<std::vec::IntoIter<Proxy<T>>>,
+    batch: VecDeque<T>,
+    batch_size: usize,
+    yielded: bool,
+}
+
+impl<L, T> RelockTableStream<L, T>
+where
+    L: Lock,
+    L::Context: Owner<T>,
+    T: Contextual<Context = L::Context>,
+{
+    /// Create a stream over the objects of type `T` in the context
+    /// held by `lock`.
+    pub fn new(lock: L) -> Self {
+        Self {
+            lock,
+            proxies: None,
+            batch: VecDeque::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            yielded: false,
+        }
+    }
+
+    /// Set the number of objects to read each time the context is
+    /// locked.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `batch_size` is zero.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must not be zero");
+        self.batch_size = batch_size;
+        self
+    }
+}
+
+impl<L, T> Unpin for RelockTableStream<L, T> {}
+
+impl<L, T> Stream for RelockTableStream<L, T>
+where
+    L: Lock,
+    L::Context: Owner<T>,
+    T: Contextual<Context = L::Context> + Clone,
+{
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if let Some(value) = this.batch.pop_front() {
+            return Poll::Ready(Some(value));
+        }
+
+        match &this.proxies {
+            Some(proxies) if proxies.len() == 0 => return Poll::Ready(None),
+            Some(_) if !this.yielded => {
+                this.yielded = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            _ => {}
+        }
+        this.yielded = false;
+
+        let batch_size = this.batch_size;
+        let proxies = &mut this.proxies;
+        let batch = &mut this.batch;
+        this.lock.with(|context| {
+            let proxies = proxies.get_or_insert_with(|| {
+                Owner::<T>::get_proxy_iter(context)
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .into_iter()
+            });
+            batch.extend(
+                proxies
+                    .by_ref()
+                    .take(batch_size)
+                    .map(|p| Owner::get(context, &p).clone()),
+            );
+        });
+        Poll::Ready(this.batch.pop_front())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.proxies {
+            Some(proxies) => {
+                let len = self.batch.len() + proxies.len();
+                (len, Some(len))
+            }
+            None => (0, None),
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::any::TypeId;
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::Proxy;
+
+/// A registry of tags attached to proxies.
+///
+/// Tags are a lightweight way to group objects in a context, for
+/// groupings that don't deserve a field in the stored types: objects
+/// a user has pinned, or those touched by the current operation. Any
+/// number of tags can be attached to each object, and all the objects
+/// of a type with a given tag can be listed.
+///
+/// Tags are strings by default, but any ordered type can be used,
+/// such as an enum of the groupings your program knows about.
+///
+/// A registry can hold proxies of any type, and is usually kept as an
+/// ordinary (non-table) field of the context it describes. Because
+/// [`Proxy`] values are only meaningful for the context that issued
+/// them, a registry should only be used with proxies from a single
+/// context.
+///
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, Tags};
+///
+/// #[contextual(Rug)]
+/// struct Note {
+///   text: String,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug {
+///   #[table]
+///   notes: Note,
+///   tags: Tags,
+/// }
+///
+/// let mut r = Rug { notes: Default::default(), tags: Tags::new() };
+/// let a = r.add(Note { text: "a".to_string() });
+/// let b = r.add(Note { text: "b".to_string() });
+/// let c = r.add(Note { text: "c".to_string() });
+///
+/// r.tags.tag(a, "pinned");
+/// r.tags.tag(c, "pinned");
+/// r.tags.tag(c, "draft");
+///
+/// let pinned = r.tags.iter_tagged::<Note>("pinned").collect::<Vec<_>>();
+/// assert_eq!(pinned, vec![a, c]);
+/// assert!(!r.tags.has_tag(&b, "pinned"));
+/// ```
+pub struct Tags<K = String> {
+    tags: BTreeMap<TypeId, Members<K>>,
+}
+
+impl<K: Ord> Tags<K> {
+    /// Create a new, empty registry.
+    pub fn new() -> Self {
+        Self {
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// Attach a tag to an object.
+    ///
+    /// Returns `false` if the object already had the tag.
+    pub fn tag<T: 'static>(&mut self, p: Proxy<T>, tag: impl Into<K>) -> bool {
+        self.tags
+            .entry(TypeId::of::<T>())
+            .or_default()
+            .entry(tag.into())
+            .or_default()
+            .insert(p.key())
+    }
+
+    /// Remove a tag from an object.
+    ///
+    /// Returns `false` if the object did not have the tag.
+    pub fn untag<T: 'static>(&mut self, p: &Proxy<T>, tag: &(impl TagLookup<K> + ?Sized)) -> bool {
+        let Some(by_tag) = self.tags.get_mut(&TypeId::of::<T>()) else {
+            return false;
+        };
+        let Some(members) = tag.lookup_mut(by_tag) else {
+            return false;
+        };
+        let res = members.remove(&p.key());
+        if members.is_empty() {
+            tag.remove_from(by_tag);
+        }
+        res
+    }
+
+    /// Check whether an object has a tag.
+    pub fn has_tag<T: 'static>(&self, p: &Proxy<T>, tag: &(impl TagLookup<K> + ?Sized)) -> bool {
+        self.members::<T>(tag)
+            .is_some_and(|members| members.contains(&p.key()))
+    }
+
+    /// Iterate over the objects of type `T` with a tag, in handle
+    /// order.
+    pub fn iter_tagged<T: 'static>(
+        &self,
+        tag: &(impl TagLookup<K> + ?Sized),
+    ) -> TaggedIterator<'_, T> {
+        TaggedIterator {
+            _marker: Default::default(),
+            iter: self.members::<T>(tag).map(|members| members.iter()),
+        }
+    }
+
+    /// Iterate over the tags attached to an object, in order.
+    pub fn tags_of<T: 'static>(&self, p: &Proxy<T>) -> impl Iterator<Item = &K> {
+        let key = p.key();
+        self.tags
+            .get(&TypeId::of::<T>())
+            .into_iter()
+            .flat_map(move |by_tag| {
+                by_tag
+                    .iter()
+                    .filter(move |(_, members)| members.contains(&key))
+                    .map(|(tag, _)| tag)
+            })
+    }
+
+    /// Remove all tags from an object.
+    pub fn clear<T: 'static>(&mut self, p: &Proxy<T>) {
+        if let Some(by_tag) = self.tags.get_mut(&TypeId::of::<T>()) {
+            by_tag.retain(|_, members| {
+                members.remove(&p.key());
+                !members.is_empty()
+            });
+        }
+    }
+
+    /// Remove a tag from every object, of every type.
+    pub fn remove_tag(&mut self, tag: &(impl TagLookup<K> + ?Sized)) {
+        for by_tag in self.tags.values_mut() {
+            tag.remove_from(by_tag);
+        }
+    }
+
+    fn members<T: 'static>(
+        &self,
+        tag: &(impl TagLookup<K> + ?Sized),
+    ) -> Option<&BTreeSet<(u64, u32)>> {
+        tag.lookup(self.tags.get(&TypeId::of::<T>())?)
+    }
+}
+
+impl<K: Ord> Default for Tags<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Clone> Clone for Tags<K> {
+    fn clone(&self) -> Self {
+        Self {
+            tags: self.tags.clone(),
+        }
+    }
+}
+
+impl<K: std::fmt::Debug> std::fmt::Debug for Tags<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(
+                self.tags
+                    .values()
+                    .flat_map(|by_tag| by_tag.iter())
+                    .map(|(tag, members)| (tag, members.len())),
+            )
+            .finish()
+    }
+}
+
+/// An [`Iterator`] over the objects with a given tag.
+///
+/// This is created by [`Tags::iter_tagged`].
+pub struct TaggedIterator<'a, T> {
+    _marker: core::marker::PhantomData<T>,
+    iter: Option<std::collections::btree_set::Iter<'a, (u64, u32)>>,
+}
+
+impl<T> Iterator for TaggedIterator<'_, T> {
+    type Item = Proxy<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.as_mut()?.next().map(|key| Proxy::from_key(*key))
+    }
+}
+
+type Members<K> = BTreeMap<K, BTreeSet<(u64, u32)>>;
+
+/// A value which can be used to look up tags of type `K`.
+///
+/// This is implemented for every type that `K` can be borrowed as,
+/// so that for example a `Tags<String>` can be queried with a `&str`.
+pub trait TagLookup<K> {
+    #[doc(hidden)]
+    fn lookup<'a>(&self, members: &'a Members<K>) -> Option<&'a BTreeSet<(u64, u32)>>;
+    #[doc(hidden)]
+    fn lookup_mut<'a>(&self, members: &'a mut Members<K>) -> Option<&'a mut BTreeSet<(u64, u32)>>;
+    #[doc(hidden)]
+    fn remove_from(&self, members: &mut Members<K>);
+}
+
+impl<K, Q> TagLookup<K> for Q
+where
+    K: Borrow<Q> + Ord,
+    Q: Ord + ?Sized,
+{
+    fn lookup<'a>(&self, members: &'a Members<K>) -> Option<&'a BTreeSet<(u64, u32)>> {
+        members.get(self)
+    }
+
+    fn lookup_mut<'a>(&self, members: &'a mut Members<K>) -> Option<&'a mut BTreeSet<(u64, u32)>> {
+        members.get_mut(self)
+    }
+
+    fn remove_from(&self, members: &mut Members<K>) {
+        members.remove(self);
+    }
+}
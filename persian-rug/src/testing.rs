@@ -0,0 +1,365 @@
+//! Helpers for testing code that builds contexts.
+//!
+//! Code that constructs graphs of objects is awkward to test with
+//! individual assertions: there are usually a great many objects, and
+//! the interesting property is the overall shape of what was built.
+//! Golden (or snapshot) tests suit this well. The test renders the
+//! whole context to text, and compares it against a rendering that
+//! was checked and recorded earlier.
+//!
+//! The rendering produced by [`render`] is based on the
+//! [`Debug`](std::fmt::Debug) implementation of the context, which
+//! you can derive alongside the [`persian_rug`](crate::persian_rug)
+//! attribute. Tables render as maps in handle order and derived
+//! implementations list fields in declaration order, so as long as
+//! your own types render deterministically (for example, they don't
+//! contain a [`HashMap`](std::collections::HashMap)), the result is
+//! the same on every run.
+//!
+//! The [`assert_context_snapshot!`](crate::assert_context_snapshot)
+//! macro compares a context against a recorded rendering, either
+//! given inline or stored in a file under the `snapshots` directory of
+//! the crate being tested:
+//!
+//! ```rust
+//! use persian_rug::{assert_context_snapshot, contextual, persian_rug, Context};
+//!
+//! #[derive(Debug)]
+//! #[contextual(Rug)]
+//! struct Foo {
+//!   a: i32,
+//!   name: &'static str,
+//! }
+//!
+//! #[derive(Debug)]
+//! #[persian_rug]
+//! struct Rug(#[table] Foo);
+//!
+//! let mut r = Rug(Default::default());
+//! r.add(Foo { a: 1, name: "first" });
+//! r.add(Foo { a: 2, name: "second" });
+//!
+//! assert_context_snapshot!(r, @r#"
+//!     Rug(
+//!         {
+//!             0: Foo {
+//!                 a: 1,
+//!                 name: "first",
+//!             },
+//!             1: Foo {
+//!                 a: 2,
+//!                 name: "second",
+//!             },
+//!         },
+//!     )
+//! "#);
+//! ```
+//!
+//! Note that [`Proxy`] values render with the full path
+//! of the type they refer to, so moving a type to a different module
+//! will change the rendering of any links to it.
+//!
+//! File-based snapshots are written, rather than checked, when the
+//! `PERSIAN_RUG_UPDATE_SNAPSHOTS` environment variable is set to
+//! anything other than `0`. Review the changes to the files before
+//! committing them.
+//!
+//! Where the handles of the objects built are not important, only
+//! the shape of the graph, the [`assert_graph_eq!`](crate::assert_graph_eq)
+//! macro compares two objects and everything reachable from them, or
+//! two whole contexts, up to the numbering of their objects. It needs
+//! the context to implement
+//! [`Isomorphic`]. On failure, it
+//! reports the path of links leading to the first difference, rather
+//! than the contents of both sides:
+//!
+//! ```rust
+//! use persian_rug::{assert_graph_eq, assert_graph_ne, contextual, persian_rug, Context, Proxy};
+//!
+//! #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+//! #[contextual(Rug)]
+//! struct Step {
+//!   name: &'static str,
+//!   #[link]
+//!   next: Option<Proxy<Step>>,
+//! }
+//!
+//! #[persian_rug(isomorphism)]
+//! struct Rug(#[table] Step);
+//!
+//! let mut a = Rug(Default::default());
+//! let end = a.add(Step { name: "end", next: None });
+//! let start = a.add(Step { name: "start", next: Some(end) });
+//!
+//! let mut b = Rug(Default::default());
+//! let start2 = b.add(Step { name: "start", next: None });
+//! let end2 = b.add(Step { name: "end", next: None });
+//! b.get_mut(&start2).next = Some(end2);
+//!
+//! assert_graph_eq!(&a, &start, &b, &start2);
+//! assert_graph_eq!(&a, &b);
+//!
+//! b.get_mut(&end2).name = "finish";
+//! assert_graph_ne!(&a, &start, &b, &start2);
+//! ```
+//!
+//! Here the failure message from `assert_graph_eq!` would name the
+//! differing pair of objects, and the `next` link which led to them.
+
+use std::ops::Deref;
+use std::path::Path;
+
+use crate::isomorphism::{context_difference, graph_difference, Isomorphic};
+use crate::{Accessor, Proxy};
+
+/// The environment variable which causes file snapshots to be
+/// recorded instead of checked.
+pub const UPDATE_SNAPSHOTS_VAR: &str = "PERSIAN_RUG_UPDATE_SNAPSHOTS";
+
+/// Render a context as canonical text.
+///
+/// This is the alternate (`{:#?}`) [`Debug`](std::fmt::Debug)
+/// rendering of the context, with a trailing newline.
+pub fn render<C: std::fmt::Debug>(context: &C) -> String {
+    format!("{:#?}\n", context)
+}
+
+/// Compare a rendering against an expected rendering.
+///
+/// On failure, the returned message describes the first line which
+/// differs, followed by the full actual rendering.
+pub fn compare_snapshot(actual: &str, expected: &str) -> Result<(), String> {
+    if actual == expected {
+        return Ok(());
+    }
+
+    let mut actual_lines = actual.lines();
+    let mut expected_lines = expected.lines();
+    let mut line = 1;
+    loop {
+        match (actual_lines.next(), expected_lines.next()) {
+            (Some(a), Some(e)) if a == e => {
+                line += 1;
+            }
+            (a, e) => {
+                return Err(format!(
+                    "snapshot mismatch at line {}:\n  expected: {}\n    actual: {}\n\nfull rendering:\n{}",
+                    line,
+                    e.unwrap_or("<end of snapshot>"),
+                    a.unwrap_or("<end of rendering>"),
+                    actual
+                ));
+            }
+        }
+    }
+}
+
+/// Assert that a rendering matches an inline snapshot.
+///
+/// The expected text may be indented: the common leading whitespace
+/// of its lines is removed, as are any blank lines at the start and
+/// end, before comparison.
+#[track_caller]
+pub fn assert_inline_snapshot(actual: &str, expected: &str) {
+    if let Err(msg) = compare_snapshot(actual.trim_end(), &dedent(expected)) {
+        panic!("{}", msg);
+    }
+}
+
+/// Assert that a rendering matches the snapshot stored at `path`.
+///
+/// If the `PERSIAN_RUG_UPDATE_SNAPSHOTS` environment variable is set,
+/// the rendering is written to `path` instead (creating any missing
+/// directories) and the assertion passes.
+#[track_caller]
+pub fn assert_file_snapshot(actual: &str, path: &Path) {
+    if updating_snapshots() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap_or_else(|e| {
+                panic!("failed to create {}: {}", parent.display(), e);
+            });
+        }
+        std::fs::write(path, actual).unwrap_or_else(|e| {
+            panic!("failed to write snapshot {}: {}", path.display(), e);
+        });
+        return;
+    }
+
+    let expected = match std::fs::read_to_string(path) {
+        Ok(expected) => expected,
+        Err(e) => panic!(
+            "failed to read snapshot {}: {}\n(set {}=1 to record it)\n\nfull rendering:\n{}",
+            path.display(),
+            e,
+            UPDATE_SNAPSHOTS_VAR,
+            actual
+        ),
+    };
+
+    if let Err(msg) = compare_snapshot(actual, &expected) {
+        panic!(
+            "{}: {}\n(set {}=1 to update it)",
+            path.display(),
+            msg,
+            UPDATE_SNAPSHOTS_VAR
+        );
+    }
+}
+
+/// Assert that two objects, and everything reachable from them, are
+/// the same.
+///
+/// The comparison is that of
+/// [`graph_eq`](crate::isomorphism::graph_eq). On failure, the
+/// message gives the path to the first difference found.
+#[track_caller]
+pub fn assert_graph_eq<A, B, C, T>(access_a: A, proxy_a: &Proxy<T>, access_b: B, proxy_b: &Proxy<T>)
+where
+    A: Accessor<Context = C> + Deref<Target = C>,
+    B: Accessor<Context = C> + Deref<Target = C>,
+    C: Isomorphic,
+    T: 'static,
+{
+    if let Some(difference) = graph_difference(access_a, proxy_a, access_b, proxy_b) {
+        panic!("graphs differ: {}", difference);
+    }
+}
+
+/// Assert that two objects, and everything reachable from them, are
+/// not the same.
+///
+/// This is the opposite of [`assert_graph_eq`].
+#[track_caller]
+pub fn assert_graph_ne<A, B, C, T>(access_a: A, proxy_a: &Proxy<T>, access_b: B, proxy_b: &Proxy<T>)
+where
+    A: Accessor<Context = C> + Deref<Target = C>,
+    B: Accessor<Context = C> + Deref<Target = C>,
+    C: Isomorphic,
+    T: 'static,
+{
+    if graph_difference(access_a, proxy_a, access_b, proxy_b).is_none() {
+        panic!("graphs are the same");
+    }
+}
+
+/// Assert that two contexts hold the same objects, linked in the same
+/// way, up to the numbering of their objects.
+///
+/// The comparison is that of
+/// [`Isomorphic::is_isomorphic`]. On failure, the message names an
+/// object whose connected group has no counterpart in the other
+/// context.
+#[track_caller]
+pub fn assert_isomorphic<C: Isomorphic>(a: &C, b: &C) {
+    if let Some(difference) = context_difference(a, b) {
+        panic!("contexts differ: {}", difference);
+    }
+}
+
+/// Assert that two contexts differ, even up to the numbering of their
+/// objects.
+///
+/// This is the opposite of [`assert_isomorphic`].
+#[track_caller]
+pub fn assert_not_isomorphic<C: Isomorphic>(a: &C, b: &C) {
+    if context_difference(a, b).is_none() {
+        panic!("contexts are the same");
+    }
+}
+
+fn updating_snapshots() -> bool {
+    std::env::var_os(UPDATE_SNAPSHOTS_VAR).is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+fn dedent(text: &str) -> String {
+    let lines = text
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+        .collect::<Vec<_>>();
+    let end = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(0, |ix| ix + 1);
+    let lines = &lines[..end];
+
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or("").trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Assert that a context matches a recorded rendering.
+///
+/// The context must implement [`Debug`](std::fmt::Debug); it is
+/// rendered with [`render`](crate::testing::render). There are two
+/// forms:
+///
+/// - `assert_context_snapshot!(context, @"...")` compares against
+///   the inline string, ignoring common indentation (see
+///   [`assert_inline_snapshot`](crate::testing::assert_inline_snapshot)).
+/// - `assert_context_snapshot!(context, "name")` compares against the
+///   file `snapshots/name.snap` in the directory of the crate being
+///   compiled (see
+///   [`assert_file_snapshot`](crate::testing::assert_file_snapshot)).
+///
+/// See the [`testing`](crate::testing) module for an example.
+#[macro_export]
+macro_rules! assert_context_snapshot {
+    ($context:expr, @$expected:literal $(,)?) => {
+        $crate::testing::assert_inline_snapshot(&$crate::testing::render(&$context), $expected)
+    };
+    ($context:expr, $name:expr $(,)?) => {
+        $crate::testing::assert_file_snapshot(
+            &$crate::testing::render(&$context),
+            &::std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("snapshots")
+                .join(format!("{}.snap", $name)),
+        )
+    };
+}
+
+/// Assert that two graphs of objects are the same, up to the
+/// numbering of their objects.
+///
+/// There are two forms:
+///
+/// - `assert_graph_eq!(access_a, &proxy_a, access_b, &proxy_b)`
+///   compares two objects and everything reachable from them (see
+///   [`assert_graph_eq`](crate::testing::assert_graph_eq)).
+/// - `assert_graph_eq!(&context_a, &context_b)` compares two whole
+///   contexts (see
+///   [`assert_isomorphic`](crate::testing::assert_isomorphic)).
+///
+/// See the [`testing`](crate::testing) module for an example.
+#[macro_export]
+macro_rules! assert_graph_eq {
+    ($a:expr, $proxy_a:expr, $b:expr, $proxy_b:expr $(,)?) => {
+        $crate::testing::assert_graph_eq($a, $proxy_a, $b, $proxy_b)
+    };
+    ($a:expr, $b:expr $(,)?) => {
+        $crate::testing::assert_isomorphic($a, $b)
+    };
+}
+
+/// Assert that two graphs of objects differ, even up to the numbering
+/// of their objects.
+///
+/// This takes the same forms as
+/// [`assert_graph_eq!`](crate::assert_graph_eq).
+#[macro_export]
+macro_rules! assert_graph_ne {
+    ($a:expr, $proxy_a:expr, $b:expr, $proxy_b:expr $(,)?) => {
+        $crate::testing::assert_graph_ne($a, $proxy_a, $b, $proxy_b)
+    };
+    ($a:expr, $b:expr $(,)?) => {
+        $crate::testing::assert_not_isomorphic($a, $b)
+    };
+}
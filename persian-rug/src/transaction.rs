@@ -0,0 +1,261 @@
+//! Changing several contexts together.
+//!
+//! When an application's objects are split across several contexts,
+//! some changes have to be made to more than one of them at once:
+//! moving an object's worth of state from one context to another
+//! should never leave it in both, or in neither. The [`transact`]
+//! function locks each context, gives a [`Sandbox`] for each to a
+//! closure, and then either commits the changes to all of them, if
+//! the closure succeeds, or to none of them, if it fails.
+//!
+//! ```rust
+//! use std::sync::{Mutex, RwLock};
+//!
+//! use persian_rug::transaction::transact;
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! #[derive(Clone)]
+//! #[contextual(Bank)]
+//! struct Account {
+//!   balance: i64,
+//! }
+//!
+//! #[persian_rug]
+//! struct Bank(#[table] Account);
+//!
+//! #[derive(Clone)]
+//! #[contextual(Ledger)]
+//! struct Entry {
+//!   amount: i64,
+//! }
+//!
+//! #[persian_rug]
+//! struct Ledger(#[table] Entry);
+//!
+//! let mut bank = Bank(Default::default());
+//! let account = bank.add(Account { balance: 100 });
+//! let bank = Mutex::new(bank);
+//! let ledger = RwLock::new(Ledger(Default::default()));
+//!
+//! let withdraw = |amount: i64| {
+//!     transact((&bank, &ledger), |(bank, ledger)| {
+//!         ledger.add(Entry { amount: -amount });
+//!         let account = bank.get_mut(&account);
+//!         account.balance -= amount;
+//!         if account.balance < 0 {
+//!             return Err("insufficient funds");
+//!         }
+//!         Ok(account.balance)
+//!     })
+//! };
+//!
+//! assert_eq!(withdraw(30), Ok(70));
+//! assert_eq!(withdraw(100), Err("insufficient funds"));
+//! assert_eq!(bank.lock().unwrap().get(&account).balance, 70);
+//! assert_eq!(ledger.read().unwrap().get_iter::<Entry>().count(), 1);
+//! ```
+//!
+//! The contexts are always locked in the same order, whatever order
+//! they are given in, so transactions over overlapping sets of
+//! contexts cannot deadlock each other. If the closure panics, none
+//! of the changes are committed.
+
+use std::ops::DerefMut;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockWriteGuard};
+
+use crate::{Context, Sandbox};
+
+/// A lock around a context, which can be taken for writing.
+///
+/// This is implemented for [`Mutex`] and [`RwLock`], and for
+/// references to and [`Arc`]s of them. Poisoned locks are used as
+/// they are.
+pub trait ContextLock {
+    /// The context inside the lock.
+    type Context: Context;
+
+    /// The guard which gives access to the context while it is
+    /// locked.
+    type Guard<'a>: DerefMut<Target = Self::Context>
+    where
+        Self: 'a;
+
+    /// Lock the context for writing.
+    fn lock_mut(&self) -> Self::Guard<'_>;
+
+    /// The address of the lock itself, which decides the order in
+    /// which locks are taken.
+    fn address(&self) -> *const ();
+}
+
+impl<C: Context> ContextLock for Mutex<C> {
+    type Context = C;
+    type Guard<'a>
+        = MutexGuard<'a, C>
+    where
+        C: 'a;
+
+    fn lock_mut(&self) -> Self::Guard<'_> {
+        self.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn address(&self) -> *const () {
+        (self as *const Self).cast()
+    }
+}
+
+impl<C: Context> ContextLock for RwLock<C> {
+    type Context = C;
+    type Guard<'a>
+        = RwLockWriteGuard<'a, C>
+    where
+        C: 'a;
+
+    fn lock_mut(&self) -> Self::Guard<'_> {
+        self.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn address(&self) -> *const () {
+        (self as *const Self).cast()
+    }
+}
+
+impl<L: ContextLock + ?Sized> ContextLock for &L {
+    type Context = L::Context;
+    type Guard<'a>
+        = L::Guard<'a>
+    where
+        Self: 'a;
+
+    fn lock_mut(&self) -> Self::Guard<'_> {
+        (**self).lock_mut()
+    }
+
+    fn address(&self) -> *const () {
+        (**self).address()
+    }
+}
+
+impl<L: ContextLock + ?Sized> ContextLock for Arc<L> {
+    type Context = L::Context;
+    type Guard<'a>
+        = L::Guard<'a>
+    where
+        Self: 'a;
+
+    fn lock_mut(&self) -> Self::Guard<'_> {
+        (**self).lock_mut()
+    }
+
+    fn address(&self) -> *const () {
+        (**self).address()
+    }
+}
+
+/// A tuple of [`ContextLock`]s which can take part in a transaction.
+///
+/// This is implemented for tuples of up to eight locks, whose
+/// contexts do not borrow.
+pub trait Locks {
+    /// The guards for all of the locks.
+    type Guards<'a>
+    where
+        Self: 'a;
+
+    /// A tuple of a [`Sandbox`] for each context.
+    type Sandboxes<'g>;
+
+    /// Take all of the locks, in order of their addresses.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the same lock appears more than once.
+    fn lock_all(&self) -> Self::Guards<'_>;
+
+    /// Open a sandbox on each locked context.
+    fn sandboxes<'g>(guards: &'g mut Self::Guards<'_>) -> Self::Sandboxes<'g>;
+
+    /// Commit the changes buffered in every sandbox.
+    fn commit(sandboxes: Self::Sandboxes<'_>);
+}
+
+/// The order in which to take locks at the given addresses.
+fn lock_order<const N: usize>(addresses: [*const (); N]) -> [usize; N] {
+    let mut order = std::array::from_fn(|ix| ix);
+    order.sort_by_key(|ix| addresses[*ix]);
+    for pair in order.windows(2) {
+        assert!(
+            addresses[pair[0]] != addresses[pair[1]],
+            "the same context cannot appear twice in a transaction"
+        );
+    }
+    order
+}
+
+macro_rules! tuple_locks {
+    ($($lock:ident $guard:ident $ix:tt),+) => {
+        impl<$($lock),+> Locks for ($($lock,)+)
+        where
+            $($lock: ContextLock, $lock::Context: 'static,)+
+        {
+            type Guards<'a>
+                = ($($lock::Guard<'a>,)+)
+            where
+                Self: 'a;
+
+            type Sandboxes<'g> = ($(Sandbox<'g, $lock::Context>,)+);
+
+            fn lock_all(&self) -> Self::Guards<'_> {
+                $(let mut $guard = None;)+
+                for ix in lock_order([$(self.$ix.address()),+]) {
+                    match ix {
+                        $($ix => $guard = Some(self.$ix.lock_mut()),)+
+                        _ => unreachable!(),
+                    }
+                }
+                ($($guard.unwrap(),)+)
+            }
+
+            fn sandboxes<'g>(guards: &'g mut Self::Guards<'_>) -> Self::Sandboxes<'g> {
+                ($(guards.$ix.deref_mut().sandbox(),)+)
+            }
+
+            fn commit(sandboxes: Self::Sandboxes<'_>) {
+                $(sandboxes.$ix.commit();)+
+            }
+        }
+    };
+}
+
+tuple_locks!(A a 0);
+tuple_locks!(A a 0, B b 1);
+tuple_locks!(A a 0, B b 1, C c 2);
+tuple_locks!(A a 0, B b 1, C c 2, D d 3);
+tuple_locks!(A a 0, B b 1, C c 2, D d 3, E e 4);
+tuple_locks!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5);
+tuple_locks!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6);
+tuple_locks!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7);
+
+/// Change several contexts together.
+///
+/// Each context in `locks` is locked for writing, and `f` is called
+/// with a [`Sandbox`] for each. If `f` returns `Ok`, the changes made
+/// in every sandbox are committed, and if it returns `Err`, they are
+/// all thrown away. The locks are held until the changes have been
+/// committed, so no other thread sees some of them without the
+/// others. See the [module documentation](self) for an example.
+///
+/// # Panics
+///
+/// Panics if the same lock appears more than once in `locks`.
+pub fn transact<L, R, E, F>(locks: L, f: F) -> Result<R, E>
+where
+    L: Locks,
+    F: for<'g> FnOnce(&mut L::Sandboxes<'g>) -> Result<R, E>,
+{
+    let mut guards = locks.lock_all();
+    let mut sandboxes = L::sandboxes(&mut guards);
+    let res = f(&mut sandboxes)?;
+    L::commit(sandboxes);
+    Ok(res)
+}
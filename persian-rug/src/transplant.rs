@@ -0,0 +1,262 @@
+//! Copying the objects of a context into a context of another type.
+//!
+//! [`Context::clone_into`] copies every
+//! object of a context into another, rewriting the proxies the copies
+//! hold so that they refer to the other copies. The other context can
+//! be of the same type, or of a different type which has a table for
+//! each of the tables being copied, and possibly others. Since an
+//! object belongs to exactly one type of context, copying into a
+//! different type means converting each object: a `Foo<Rug>` becomes
+//! a `Foo<Bigger>`, and each `Proxy<Bar<Rug>>` it holds becomes a
+//! `Proxy<Bar<Bigger>>`. This conversion is provided by
+//! [`Transplant`], which can be derived for types which are generic
+//! over their context.
+//!
+//! The tables to copy are listed by an implementation of
+//! [`CloneInto`], which the [`persian_rug`](crate::persian_rug) macro
+//! generates with its `transplant` option:
+//!
+//! ```rust
+//! use persian_rug::{contextual, persian_rug, Context, Links, Proxy, Transplant};
+//!
+//! #[derive(Links, Transplant)]
+//! #[contextual(C)]
+//! struct Author<C: Context> {
+//!   name: String,
+//!   _marker: std::marker::PhantomData<C>,
+//! }
+//!
+//! #[derive(Links, Transplant)]
+//! #[contextual(C)]
+//! struct Book<C: Context> {
+//!   title: String,
+//!   authors: Vec<Proxy<Author<C>>>,
+//! }
+//!
+//! #[derive(Links, Transplant)]
+//! #[contextual(C)]
+//! struct Shelf<C: Context> {
+//!   books: Vec<Proxy<Book<C>>>,
+//! }
+//!
+//! #[persian_rug(transplant)]
+//! struct Catalogue(#[table] Author<Catalogue>, #[table] Book<Catalogue>);
+//!
+//! #[persian_rug]
+//! struct Library(
+//!   #[table] Shelf<Library>,
+//!   #[table] Author<Library>,
+//!   #[table] Book<Library>,
+//! );
+//!
+//! let mut c = Catalogue(Default::default(), Default::default());
+//! let author = c.add(Author { name: "Mary Shelley".to_string(), _marker: Default::default() });
+//! let book = c.add(Book { title: "Frankenstein".to_string(), authors: vec![author] });
+//!
+//! let mut l = Library(Default::default(), Default::default(), Default::default());
+//! l.add(Author { name: "Jane Austen".to_string(), _marker: Default::default() });
+//! let copies = c.clone_into(&mut l);
+//!
+//! let book: Proxy<Book<Library>> = copies.get(&book).unwrap();
+//! l.add(Shelf { books: vec![book] });
+//! assert_eq!(l.get(&l.get(&book).authors[0]).name, "Mary Shelley");
+//! ```
+//!
+//! The copies are added after whatever the other context already
+//! holds, so their handles may differ from those of the originals.
+//! Only links reported by [`Links`](crate::Links) are rewritten, and
+//! links to objects which have been deleted, or whose tables are not
+//! copied, are left pointing at the same handle.
+
+use std::any::TypeId;
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::{AnyProxy, Context, Contextual, Owner, Proxy, Relink};
+
+/// A value which can be copied as a `T`, converting the proxies it
+/// holds.
+///
+/// Each proxy held by the value is passed to a function, which
+/// returns the proxy to store in the copy in its place. This is
+/// implemented for [`Proxy`], and for common containers of values
+/// that implement it. For a type which is generic over its context,
+/// `#[derive(Transplant)]` implements it for each context the type
+/// can be copied into, see [`Transplant`](macro@crate::Transplant).
+pub trait Transplant<T> {
+    /// Copy this value, replacing each proxy it holds with the result
+    /// of `f`.
+    ///
+    /// If `f` returns the proxy it was given, the copy holds a proxy
+    /// for the same handle, of whatever type the copy needs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` returns some other proxy of a different type to
+    /// the one the copy holds.
+    fn transplant(&self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) -> T;
+}
+
+impl<T: 'static, U: 'static> Transplant<Proxy<U>> for Proxy<T> {
+    fn transplant(&self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) -> Proxy<U> {
+        let p = AnyProxy::new(*self);
+        let res = f(p);
+        match res.downcast() {
+            Some(res) => res,
+            None if res == p => Proxy::with_generation(self.index, self.generation),
+            None => panic!("proxy was transplanted to a different type"),
+        }
+    }
+}
+
+impl<L: Transplant<M>, M> Transplant<Box<M>> for Box<L> {
+    fn transplant(&self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) -> Box<M> {
+        Box::new((**self).transplant(f))
+    }
+}
+
+impl<L: Transplant<M>, M> Transplant<Option<M>> for Option<L> {
+    fn transplant(&self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) -> Option<M> {
+        self.as_ref().map(|value| value.transplant(f))
+    }
+}
+
+impl<L: Transplant<M>, M, const N: usize> Transplant<[M; N]> for [L; N] {
+    fn transplant(&self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) -> [M; N] {
+        std::array::from_fn(|index| self[index].transplant(f))
+    }
+}
+
+impl<L: Transplant<M>, M> Transplant<Vec<M>> for Vec<L> {
+    fn transplant(&self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) -> Vec<M> {
+        self.iter().map(|value| value.transplant(f)).collect()
+    }
+}
+
+impl<L: Transplant<M>, M> Transplant<VecDeque<M>> for VecDeque<L> {
+    fn transplant(&self, f: &mut dyn FnMut(AnyProxy) -> AnyProxy) -> VecDeque<M> {
+        self.iter().map(|value| value.transplant(f)).collect()
+    }
+}
+
+/// A context whose objects can be copied into a context of type `D`.
+///
+/// This is normally implemented with the `transplant` option of the
+/// [`persian_rug`](crate::persian_rug) macro.
+pub trait CloneInto<D>: Context {
+    /// Register each table of the context with `tables`.
+    fn describe(tables: &mut TransplantTables<Self, D>)
+    where
+        Self: Sized;
+}
+
+type Duplicate<C, D> = fn(&C, &mut D, &BTreeMap<TypeId, Retype>, &mut BTreeMap<AnyProxy, AnyProxy>);
+type Fixup<D> = fn(&mut D, &AnyProxy, &BTreeMap<AnyProxy, AnyProxy>);
+type Retype = fn(AnyProxy) -> AnyProxy;
+
+/// The tables being copied by a [`CloneInto`].
+pub struct TransplantTables<C, D> {
+    copy: Vec<Duplicate<C, D>>,
+    fixup: BTreeMap<TypeId, Fixup<D>>,
+    retype: BTreeMap<TypeId, Retype>,
+}
+
+impl<C: Context, D: Context> TransplantTables<C, D> {
+    /// Copy the table of objects of type `T` into the table of
+    /// objects of type `U`.
+    pub fn table<T, U>(&mut self)
+    where
+        C: Owner<T>,
+        D: Owner<U>,
+        T: Contextual<Context = C> + Transplant<U> + 'static,
+        U: Contextual<Context = D> + Relink + 'static,
+    {
+        self.copy.push(copy::<C, D, T, U>);
+        self.fixup.insert(TypeId::of::<U>(), fixup::<D, U>);
+        self.retype.insert(TypeId::of::<T>(), |p| {
+            let p = p.downcast::<T>().unwrap();
+            AnyProxy::new(Proxy::<U>::with_generation(p.index, p.generation))
+        });
+    }
+}
+
+fn copy<C, D, T, U>(
+    context: &C,
+    into: &mut D,
+    retype: &BTreeMap<TypeId, Retype>,
+    proxies: &mut BTreeMap<AnyProxy, AnyProxy>,
+) where
+    C: Owner<T>,
+    D: Owner<U>,
+    T: Contextual<Context = C> + Transplant<U> + 'static,
+    U: Contextual<Context = D> + 'static,
+{
+    for p in Owner::<T>::get_proxy_iter(context) {
+        // Links are given the type they will have in the copy, but
+        // keep their original handles until every object is copied.
+        let value =
+            Owner::get(context, p).transplant(&mut |target| match retype.get(&target.type_id()) {
+                Some(retype) => retype(target),
+                None => target,
+            });
+        proxies.insert(AnyProxy::new(*p), AnyProxy::new(Owner::add(into, value)));
+    }
+}
+
+fn fixup<D, U>(into: &mut D, p: &AnyProxy, proxies: &BTreeMap<AnyProxy, AnyProxy>)
+where
+    D: Owner<U>,
+    U: Contextual<Context = D> + Relink + 'static,
+{
+    let p: Proxy<U> = p.downcast().unwrap();
+    Owner::get_mut(into, &p).relink(&mut |target| proxies.get(&target).copied().unwrap_or(target));
+}
+
+/// The result of [`Context::clone_into`].
+///
+/// This maps each object which was copied to its copy.
+#[derive(Clone, Debug, Default)]
+pub struct Transplanted {
+    proxies: BTreeMap<AnyProxy, AnyProxy>,
+}
+
+impl Transplanted {
+    /// The copy of `p`, or [`None`] if it was not copied, or was
+    /// copied as some type other than `U`.
+    pub fn get<T: 'static, U: 'static>(&self, p: &Proxy<T>) -> Option<Proxy<U>> {
+        self.proxies
+            .get(&AnyProxy::new(*p))
+            .and_then(|copy| copy.downcast())
+    }
+
+    /// The number of objects copied.
+    pub fn len(&self) -> usize {
+        self.proxies.len()
+    }
+
+    /// Check whether no objects were copied.
+    pub fn is_empty(&self) -> bool {
+        self.proxies.is_empty()
+    }
+}
+
+pub(crate) fn clone_into<C: CloneInto<D>, D: Context>(context: &C, into: &mut D) -> Transplanted {
+    let mut tables = TransplantTables {
+        copy: Vec::new(),
+        fixup: BTreeMap::new(),
+        retype: BTreeMap::new(),
+    };
+    C::describe(&mut tables);
+
+    let mut proxies = BTreeMap::new();
+    for copy in tables.copy.iter() {
+        copy(context, into, &tables.retype, &mut proxies);
+    }
+    let retyped = proxies
+        .iter()
+        .map(|(from, to)| ((tables.retype[&from.type_id()])(*from), *to))
+        .collect();
+    for copy in proxies.values() {
+        (tables.fixup[&copy.type_id()])(into, copy, &retyped);
+    }
+    Transplanted { proxies }
+}
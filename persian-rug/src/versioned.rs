@@ -0,0 +1,291 @@
+//! A stable archive format for contexts which change between releases.
+//!
+//! This module is available with the `borsh` feature. The `borsh`
+//! option of the [`persian_rug`](crate::persian_rug) macro encodes a
+//! whole context, but only a context with exactly the same tables can
+//! read it back. An archive written by [`to_vec`] instead stores each
+//! table separately, under the name of its field and with the version
+//! of its type, given by [`Versioned`]. When it is read back with
+//! [`load`]:
+//! - tables in the archive which the context no longer has are
+//!   skipped,
+//! - tables which the context has but the archive does not are left
+//!   as they are,
+//! - tables written with an older version of their type are brought
+//!   up to date by the callbacks registered in [`Migrations`].
+//!
+//! The tables to archive are listed by an implementation of
+//! [`VersionedArchive`], which the macro generates with its
+//! `versioned` option:
+//!
+//! ```rust
+//! use persian_rug::borsh::{BorshDeserialize, BorshSerialize};
+//! use persian_rug::versioned::{self, Migrations, Versioned};
+//! use persian_rug::{contextual, persian_rug, Context, Proxy};
+//!
+//! // The first release stored names as a single string.
+//! #[derive(BorshSerialize, BorshDeserialize)]
+//! #[borsh(crate = "persian_rug::borsh")]
+//! #[contextual(OldRug)]
+//! struct OldUser {
+//!   name: String,
+//! }
+//!
+//! impl Versioned for OldUser {}
+//!
+//! #[persian_rug(versioned)]
+//! struct OldRug {
+//!   #[table]
+//!   users: OldUser,
+//! }
+//!
+//! // The second release splits them up.
+//! #[derive(BorshSerialize, BorshDeserialize)]
+//! #[borsh(crate = "persian_rug::borsh")]
+//! #[contextual(Rug)]
+//! struct User {
+//!   first: String,
+//!   last: String,
+//! }
+//!
+//! impl Versioned for User {
+//!   const VERSION: u32 = 1;
+//! }
+//!
+//! #[persian_rug(versioned)]
+//! struct Rug {
+//!   #[table]
+//!   users: User,
+//! }
+//!
+//! let mut old = OldRug { users: Default::default() };
+//! let ada = old.add(OldUser { name: "Ada Lovelace".to_string() });
+//! let bytes = versioned::to_vec(&old).unwrap();
+//!
+//! let migrations = Migrations::new().map("users", 0, |user: OldUser| {
+//!   let (first, last) = user.name.split_once(' ').unwrap();
+//!   User { first: first.to_string(), last: last.to_string() }
+//! });
+//!
+//! let mut r = Rug { users: Default::default() };
+//! versioned::load(&mut r, &bytes, &migrations).unwrap();
+//! let ada = Proxy::<User>::from_handle(ada.handle());
+//! assert_eq!(r.get(&ada).last, "Lovelace");
+//! ```
+//!
+//! Each table is held in its `borsh` encoding, which is what a
+//! migration receives and returns. [`Migrations::map`] covers the
+//! common case of converting each object in turn.
+
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Result};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::storage::Storage;
+use crate::{Context, Contextual, Table, TableOwner};
+
+const MAGIC: [u8; 4] = *b"PRUG";
+const FORMAT: u32 = 1;
+
+/// The version of a type stored in a versioned archive.
+///
+/// Increase [`VERSION`](Versioned::VERSION) whenever the `borsh`
+/// encoding of the type changes, and register a migration from the
+/// previous version with [`Migrations`].
+pub trait Versioned {
+    /// The current version of the type.
+    const VERSION: u32 = 0;
+}
+
+/// A context which can be written to a versioned archive.
+///
+/// This is normally implemented with the `versioned` option of the
+/// [`persian_rug`](crate::persian_rug) macro.
+pub trait VersionedArchive: Context {
+    /// Register each table of the context with `tables`.
+    fn describe(tables: &mut VersionedTables<Self>)
+    where
+        Self: Sized;
+}
+
+struct Entry<C> {
+    name: &'static str,
+    version: u32,
+    save: fn(&C) -> Result<Vec<u8>>,
+    load: fn(&mut C, &[u8]) -> Result<()>,
+}
+
+/// The tables stored in a versioned archive.
+///
+/// This is passed to [`VersionedArchive::describe`].
+pub struct VersionedTables<C> {
+    entries: Vec<Entry<C>>,
+}
+
+impl<C: Context> VersionedTables<C> {
+    /// Include the table of objects of type `T`, under `name`.
+    ///
+    /// The name identifies the table in the archive, so it must stay
+    /// the same between releases.
+    pub fn table<T, S>(&mut self, name: &'static str)
+    where
+        C: TableOwner<T, Table = Table<T, S>>,
+        T: Contextual<Context = C> + Versioned + BorshSerialize + BorshDeserialize,
+        S: Storage<T> + Default,
+    {
+        self.entries.push(Entry {
+            name,
+            version: T::VERSION,
+            save: |context| borsh::to_vec(TableOwner::<T>::get_table(context)),
+            load: |context, bytes| {
+                *TableOwner::<T>::get_table_mut(context) = Table::try_from_slice(bytes)?;
+                Ok(())
+            },
+        });
+    }
+}
+
+fn tables<C: VersionedArchive>() -> Vec<Entry<C>> {
+    let mut tables = VersionedTables {
+        entries: Vec::new(),
+    };
+    C::describe(&mut tables);
+    tables.entries
+}
+
+type Migration = Box<dyn Fn(&[u8]) -> Result<Vec<u8>>>;
+
+/// The callbacks which bring old tables up to date.
+///
+/// Each migration converts a table from one version of its type to
+/// the next. Loading a table several versions behind runs each of the
+/// migrations in between, in order.
+#[derive(Default)]
+pub struct Migrations {
+    steps: BTreeMap<(String, u32), Migration>,
+}
+
+impl Migrations {
+    /// Create a new set of migrations, with none registered.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `f` to convert the `borsh` encoding of the table
+    /// `name` from version `from` to version `from + 1`.
+    pub fn add(
+        mut self,
+        name: &str,
+        from: u32,
+        f: impl Fn(&[u8]) -> Result<Vec<u8>> + 'static,
+    ) -> Self {
+        self.steps.insert((name.to_string(), from), Box::new(f));
+        self
+    }
+
+    /// Register `f` to convert each object of the table `name` from
+    /// version `from` to version `from + 1`.
+    ///
+    /// The objects keep their handles.
+    pub fn map<Old, New>(self, name: &str, from: u32, f: impl Fn(Old) -> New + 'static) -> Self
+    where
+        Old: BorshDeserialize,
+        New: BorshSerialize,
+    {
+        self.add(name, from, move |bytes| {
+            // This is the encoding of a `Table`, without needing
+            // either type to be stored in one.
+            let (next, entries) = <(u64, Vec<(u64, Old)>)>::try_from_slice(bytes)?;
+            let entries = entries
+                .into_iter()
+                .map(|(index, value)| (index, f(value)))
+                .collect::<Vec<_>>();
+            borsh::to_vec(&(next, entries))
+        })
+    }
+}
+
+/// Write `context` as a versioned archive.
+pub fn to_vec<C: VersionedArchive>(context: &C) -> Result<Vec<u8>> {
+    let tables = tables::<C>()
+        .into_iter()
+        .map(|entry| {
+            Ok((
+                entry.name.to_string(),
+                entry.version,
+                (entry.save)(context)?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    borsh::to_vec(&(MAGIC, FORMAT, tables))
+}
+
+/// Read a versioned archive into `context`.
+///
+/// Each table found in the archive replaces the corresponding table
+/// of `context`, after being migrated to the current version of its
+/// type with `migrations`. Tables the context does not have are
+/// skipped.
+///
+/// An error is returned if the archive is malformed, if a table was
+/// written with a newer version of its type than this build knows, or
+/// if a migration it needs is missing. The archive is checked and
+/// migrated before anything is loaded, but a table which then fails
+/// to decode may leave `context` partly loaded.
+pub fn load<C: VersionedArchive>(
+    context: &mut C,
+    bytes: &[u8],
+    migrations: &Migrations,
+) -> Result<()> {
+    let (magic, format, stored) =
+        <([u8; 4], u32, Vec<(String, u32, Vec<u8>)>)>::try_from_slice(bytes)?;
+    if magic != MAGIC {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "not a persian-rug versioned archive",
+        ));
+    }
+    if format != FORMAT {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("unsupported archive format {}", format),
+        ));
+    }
+
+    let tables = tables::<C>();
+    let mut pending = Vec::new();
+    for (name, mut version, mut data) in stored {
+        let Some(entry) = tables.iter().find(|entry| entry.name == name) else {
+            continue;
+        };
+        if version > entry.version {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "table {} has version {}, but only version {} is known",
+                    name, version, entry.version
+                ),
+            ));
+        }
+        while version < entry.version {
+            let migration = migrations
+                .steps
+                .get(&(name.clone(), version))
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("no migration for table {} from version {}", name, version),
+                    )
+                })?;
+            data = migration(&data)?;
+            version += 1;
+        }
+        pending.push((entry.load, data));
+    }
+
+    for (load, data) in pending {
+        load(context, &data)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+//! Walking every object in a context.
+//!
+//! The [`schema`](crate::schema) of a context describes its tables
+//! without looking at their contents. For one-off passes over
+//! everything a context holds, such as counting objects, checking
+//! invariants or collecting statistics, the
+//! [`persian_rug`](crate::persian_rug) macro also implements
+//! [`Context::visit_all`], which calls a [`ContextVisitor`] for each
+//! table, and each object in it, in the order the tables are
+//! declared.
+//!
+//! ```rust
+//! use std::collections::BTreeMap;
+//!
+//! use persian_rug::visit::ContextVisitor;
+//! use persian_rug::{contextual, persian_rug, Context, Contextual, Proxy};
+//!
+//! #[contextual(Rug)]
+//! struct Foo {
+//!   a: i32,
+//! }
+//!
+//! #[contextual(Rug)]
+//! struct Bar {
+//!   foo: Proxy<Foo>,
+//! }
+//!
+//! #[persian_rug]
+//! struct Rug(#[table] Foo, #[table] Bar);
+//!
+//! #[derive(Default)]
+//! struct Census(BTreeMap<&'static str, usize>);
+//!
+//! impl ContextVisitor<Rug> for Census {
+//!     fn object<T>(&mut self, _proxy: &Proxy<T>, _object: &T)
+//!     where
+//!         Rug: persian_rug::Owner<T>,
+//!         T: Contextual<Context = Rug>,
+//!     {
+//!         *self.0.entry(T::schema().name).or_default() += 1;
+//!     }
+//! }
+//!
+//! let mut r = Rug(Default::default(), Default::default());
+//! let foo = r.add(Foo { a: 1 });
+//! r.add(Foo { a: 2 });
+//! r.add(Bar { foo });
+//!
+//! let mut census = Census::default();
+//! r.visit_all(&mut census);
+//! assert_eq!(census.0["Foo"], 2);
+//! assert_eq!(census.0["Bar"], 1);
+//! ```
+//!
+//! The visitor's methods are generic over the type of the objects,
+//! so one visitor works for every table of a context. They can
+//! identify the type through its [`Contextual::schema`], or with
+//! [`std::any::type_name`].
+
+use crate::{Context, Contextual, Owner, Proxy};
+
+/// A pass over the tables and objects of a context.
+///
+/// See the [module documentation](self) for an example.
+pub trait ContextVisitor<C: Context> {
+    /// Visit the table of objects of type `T`.
+    ///
+    /// The default implementation calls [`object`](Self::object) for
+    /// each object in the table, in iteration order. Override it to
+    /// skip tables, or to work with the whole context at once.
+    fn table<T>(&mut self, context: &C)
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+        for p in Owner::<T>::get_proxy_iter(context) {
+            self.object(p, Owner::get(context, p));
+        }
+    }
+
+    /// Visit one object, along with its proxy.
+    ///
+    /// The default implementation does nothing.
+    fn object<T>(&mut self, _proxy: &Proxy<T>, _object: &T)
+    where
+        C: Owner<T>,
+        T: Contextual<Context = C>,
+    {
+    }
+}
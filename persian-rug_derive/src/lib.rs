@@ -3,7 +3,7 @@ use proc_macro2 as pm2;
 use quote::ToTokens;
 
 enum ConstraintItem {
-    Context(syn::Ident),
+    Context(Box<syn::Type>),
     Access(Vec<syn::Type>),
 }
 
@@ -34,7 +34,7 @@ impl syn::parse::Parse for ConstraintItem {
 }
 
 struct ConstraintArgs {
-    pub context: syn::Ident,
+    pub context: syn::Type,
     pub used_types: Vec<syn::Type>,
 }
 
@@ -48,7 +48,7 @@ impl syn::parse::Parse for ConstraintArgs {
         for item in punc.into_iter() {
             match item {
                 ConstraintItem::Context(id) => {
-                    context = Some(id);
+                    context = Some(*id);
                 }
                 ConstraintItem::Access(tys) => {
                     used_types.extend(tys);
@@ -70,6 +70,17 @@ impl syn::parse::Parse for ConstraintArgs {
     }
 }
 
+/// Write out a type for an error message.
+fn type_name(ty: &syn::Type) -> String {
+    ty.to_token_stream()
+        .to_string()
+        .replace(" <", "<")
+        .replace("< ", "<")
+        .replace(" >", ">")
+        .replace(" ,", ",")
+        .replace(" :: ", "::")
+}
+
 /// Add the type constraints necessary for an impl using persian-rug.
 ///
 /// Rust currently requires all relevant constraints to be written out
@@ -81,14 +92,23 @@ impl syn::parse::Parse for ConstraintArgs {
 /// generates these constraints for you.
 ///
 /// The attribute takes two types of argument:
-/// - `context` specifies the name of the type of the context.
+/// - `context` specifies the type of the context, which may be a
+///   parameter of the item, or a type such as `State<'a>`.
 /// - `access(...)` specifies the types that this impl requires to
 ///   exist within that context. Typically each type requires some
 ///   other types to also exist in its context for it to be
 ///   well-formed.  This argument needs to be given the transitive
 ///   closure of all such types, both direct and indirect dependencies
 ///   of the impl itself. It is unfortunately not possible at present
-///   to find the indirect dependencies automatically.
+///   to find the indirect dependencies automatically, but when one is
+///   missing, the compiler's error names it and suggests adding it to
+///   this list.
+///
+/// Types in the access list which do not depend on any generic
+/// parameter of the item are checked where they are listed, so that
+/// if one is not `Contextual`, or belongs to another context, the
+/// error points at it. When the context is a generic parameter of the
+/// item, such types are rejected, since they cannot belong to it.
 ///
 /// Example:
 /// ```rust
@@ -139,36 +159,505 @@ pub fn constraints(args: TokenStream, input: TokenStream) -> TokenStream {
         used_types,
     } = syn::parse_macro_input!(args);
 
-    let wc = generics.make_where_clause();
+    let params = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<_>>();
+    let params = params.iter().collect::<Vec<_>>();
+    // Lifetimes are matched by their names, which follow a `'` token.
+    let lifetimes = generics
+        .lifetimes()
+        .map(|param| param.lifetime.ident.clone())
+        .collect::<Vec<_>>();
+    let dependencies = params
+        .iter()
+        .copied()
+        .chain(lifetimes.iter())
+        .collect::<Vec<_>>();
 
-    let mut getters = syn::punctuated::Punctuated::<syn::TypeParamBound, syn::token::Add>::new();
-    getters.push(syn::parse_quote! { ::persian_rug::Context });
-    for ty in &used_types {
-        getters.push(syn::parse_quote! { ::persian_rug::Owner<#ty> });
+    // When the context is a parameter of the item, a type which does
+    // not depend on any parameter cannot belong to it.
+    let generic_context = matches!(&context, syn::Type::Path(path)
+        if path.qself.is_none() && params.iter().any(|param| path.path.is_ident(*param)));
+    let context_name = context.to_token_stream().to_string();
+    let mut errors = pm2::TokenStream::new();
+    for (ix, ty) in used_types.iter().enumerate() {
+        if generic_context && !mentions(ty.to_token_stream(), &params) {
+            errors.extend(
+                syn::Error::new_spanned(
+                    ty,
+                    format!(
+                        "`{}` does not depend on any parameter, so it cannot be stored in the generic context `{}`",
+                        type_name(ty),
+                        type_name(&context)
+                    ),
+                )
+                .to_compile_error(),
+            );
+        } else if ty.to_token_stream().to_string() == context_name {
+            errors.extend(
+                syn::Error::new_spanned(ty, "a context cannot be stored in itself")
+                    .to_compile_error(),
+            );
+        } else if used_types[..ix]
+            .iter()
+            .any(|other| other.to_token_stream().to_string() == ty.to_token_stream().to_string())
+        {
+            errors.extend(
+                syn::Error::new_spanned(
+                    ty,
+                    format!("`{}` is listed more than once", type_name(ty)),
+                )
+                .to_compile_error(),
+            );
+        }
     }
+    if !errors.is_empty() {
+        errors.extend(target.into_token_stream());
+        return errors.into();
+    }
+
+    let wc = generics.make_where_clause();
 
     wc.predicates.push(syn::parse_quote! {
-        #context: #getters
+        #context: ::persian_rug::Context
     });
 
+    let mut checks = pm2::TokenStream::new();
     for ty in &used_types {
-        wc.predicates.push(syn::parse_quote! {
-            #ty: ::persian_rug::Contextual<Context = #context>
+        if mentions(quote::quote! { #context #ty }, &dependencies) {
+            wc.predicates.push(syn::parse_quote! {
+                #context: ::persian_rug::Owner<#ty>
+            });
+            wc.predicates.push(syn::parse_quote! {
+                #ty: ::persian_rug::Contextual<Context = #context>
+            });
+        } else {
+            // A bound without parameters either always holds, and so
+            // is not needed, or never does. Checking it separately
+            // lets the error point at the type in the access list.
+            let span = ty.to_token_stream().into_iter().next().unwrap().span();
+            let context = respan(context.to_token_stream(), span);
+            checks.extend(quote::quote_spanned! { span=>
+                ::persian_rug::__check_access::<#context, #ty>();
+            });
+        }
+    }
+
+    let mut res = target.into_token_stream();
+    if !checks.is_empty() {
+        res.extend(quote::quote! {
+            const _: () = {
+                fn check() {
+                    #checks
+                }
+            };
         });
     }
+    res.into()
+}
+
+enum TableStorage {
+    Map,
+    Arena(Option<Box<syn::Type>>),
+    Persistent,
+    Search(Box<TableStorage>),
+    Dirty(Box<TableStorage>),
+}
+
+impl syn::parse::Parse for TableStorage {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let storage: syn::Ident = input.parse()?;
+        match storage.to_string().as_str() {
+            "arena" => {
+                if input.peek(syn::token::Paren) {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    Ok(TableStorage::Arena(Some(Box::new(content.parse()?))))
+                } else {
+                    Ok(TableStorage::Arena(None))
+                }
+            }
+            "persistent" => Ok(TableStorage::Persistent),
+            "search" => {
+                if input.peek(syn::token::Paren) {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    Ok(TableStorage::Search(Box::new(content.parse()?)))
+                } else {
+                    Ok(TableStorage::Search(Box::new(TableStorage::Map)))
+                }
+            }
+            "dirty" => {
+                if input.peek(syn::token::Paren) {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    Ok(TableStorage::Dirty(Box::new(content.parse()?)))
+                } else {
+                    Ok(TableStorage::Dirty(Box::new(TableStorage::Map)))
+                }
+            }
+            _ => Err(syn::Error::new_spanned(
+                storage,
+                "unsupported persian-rug table storage",
+            )),
+        }
+    }
+}
+
+impl TableStorage {
+    fn from_attr(attr: &syn::Attribute) -> syn::Result<Self> {
+        if attr.tokens.is_empty() {
+            return Ok(TableStorage::Map);
+        }
+        attr.parse_args()
+    }
+
+    fn storage_type(&self, field_type: &syn::Type, linked: bool) -> syn::Type {
+        let storage: syn::Type = match self {
+            TableStorage::Map => syn::parse_quote! {
+                ::persian_rug::storage::MapStorage<#field_type>
+            },
+            TableStorage::Arena(None) => syn::parse_quote! {
+                ::persian_rug::storage::ArenaStorage<#field_type>
+            },
+            TableStorage::Arena(Some(alloc)) => syn::parse_quote! {
+                ::persian_rug::storage::ArenaStorage<#field_type, #alloc>
+            },
+            TableStorage::Persistent => syn::parse_quote! {
+                ::persian_rug::storage::PersistentStorage<#field_type>
+            },
+            TableStorage::Search(inner) => {
+                let inner = inner.storage_type(field_type, linked);
+                return syn::parse_quote! {
+                    ::persian_rug::search::SearchStorage<#field_type, #inner>
+                };
+            }
+            TableStorage::Dirty(inner) => {
+                let inner = inner.storage_type(field_type, linked);
+                return syn::parse_quote! {
+                    ::persian_rug::dirty::DirtyStorage<#field_type, #inner>
+                };
+            }
+        };
+        if linked {
+            syn::parse_quote! {
+                ::persian_rug::referrers::LinkStorage<#field_type, #storage>
+            }
+        } else {
+            storage
+        }
+    }
+
+    fn table_type(
+        &self,
+        field_type: &syn::Type,
+        linked: bool,
+        handles: Option<&syn::Type>,
+    ) -> syn::Type {
+        match (self, handles) {
+            (TableStorage::Map, None) if !linked => syn::parse_quote! {
+                ::persian_rug::Table<#field_type>
+            },
+            (_, None) => {
+                let storage = self.storage_type(field_type, linked);
+                syn::parse_quote! {
+                    ::persian_rug::Table<#field_type, #storage>
+                }
+            }
+            (_, Some(handles)) => {
+                let storage = self.storage_type(field_type, linked);
+                syn::parse_quote! {
+                    ::persian_rug::Table<#field_type, #storage, #handles>
+                }
+            }
+        }
+    }
+}
+
+struct RugOptions {
+    rkyv: bool,
+    borsh: bool,
+    serde: bool,
+    versioned: bool,
+    profile: bool,
+    referrers: bool,
+    isomorphism: bool,
+    aliases: bool,
+    csv: bool,
+    gc: bool,
+    diagram: bool,
+    extract: bool,
+    transplant: bool,
+    merge: bool,
+    diff: bool,
+    json: bool,
+    proto: bool,
+    provenance: bool,
+    observe: bool,
+    serde_diff: bool,
+    schemars: bool,
+    checked: bool,
+    handles: Option<syn::Type>,
+}
+
+impl syn::parse::Parse for RugOptions {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut res = RugOptions {
+            rkyv: false,
+            borsh: false,
+            serde: false,
+            versioned: false,
+            profile: false,
+            referrers: false,
+            isomorphism: false,
+            aliases: false,
+            csv: false,
+            gc: false,
+            diagram: false,
+            extract: false,
+            transplant: false,
+            merge: false,
+            diff: false,
+            json: false,
+            proto: false,
+            provenance: false,
+            observe: false,
+            serde_diff: false,
+            schemars: false,
+            checked: false,
+            handles: None,
+        };
+        while !input.is_empty() {
+            let option: syn::Ident = input.parse()?;
+            match option.to_string().as_str() {
+                "rkyv" => res.rkyv = true,
+                "borsh" => res.borsh = true,
+                "serde" => res.serde = true,
+                "versioned" => res.versioned = true,
+                "profile" => res.profile = true,
+                "referrers" => res.referrers = true,
+                "isomorphism" => res.isomorphism = true,
+                "aliases" => res.aliases = true,
+                "csv" => res.csv = true,
+                "gc" => res.gc = true,
+                "diagram" => res.diagram = true,
+                "extract" => res.extract = true,
+                "transplant" => res.transplant = true,
+                "merge" => res.merge = true,
+                "diff" => res.diff = true,
+                "json" => res.json = true,
+                "proto" => res.proto = true,
+                "provenance" => res.provenance = true,
+                "observe" => res.observe = true,
+                "serde_diff" => res.serde_diff = true,
+                "schemars" => res.schemars = true,
+                "checked" => res.checked = true,
+                "handles" => {
+                    input.parse::<syn::Token![=]>()?;
+                    res.handles = Some(input.parse()?);
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        option,
+                        "unsupported persian-rug option",
+                    ))
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<syn::Token![,]>()?;
+            }
+        }
+        if let Some(handles) = &res.handles {
+            if res.rkyv || res.borsh || res.serde || res.versioned || res.isomorphism {
+                return Err(syn::Error::new_spanned(
+                    handles,
+                    "handles cannot be combined with rkyv, borsh, serde, versioned or isomorphism",
+                ));
+            }
+        }
+        Ok(res)
+    }
+}
 
-    target.into_token_stream().into()
+/// Convert a type name in camel case to snake case.
+fn snake_case(name: &str) -> String {
+    let mut res = String::new();
+    for (ix, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if ix > 0 {
+                res.push('_');
+            }
+            res.extend(c.to_lowercase());
+        } else {
+            res.push(c);
+        }
+    }
+    res
 }
 
 /// Convert an annotated struct into a `Context`
 ///
 /// Each field marked with `#[table]` will be converted to be a
 /// `Table` of values of the same type. An implementation of `Context`
-/// will be provided. In addition, an implementation of `Owner` for
-/// each field type will be derived for the overall struct.
+/// will be provided. In addition, implementations of `Owner` and
+/// `TableOwner` for each field type will be derived for the overall
+/// struct, so that each `Table` can be reached with
+/// `Context::table` and `Context::table_mut`.
 ///
 /// Note that a `Context` can only contain one table of each type.
 ///
+/// The implementation of `Context::schema` describes each table, using
+/// the schema of its type; see the `schema` module of `persian-rug`.
+///
+/// Other attributes on a table field, such as doc comments or
+/// attributes for other derives on the struct, are kept on the
+/// generated `Table` field. A table whose field has a `#[cfg(...)]`
+/// attribute, which is only supported for named fields, is left out
+/// of the context along with its `Owner` implementation when the
+/// condition does not hold.
+///
+/// By default each table uses `MapStorage`. Writing `#[table(arena)]`
+/// instead selects `ArenaStorage`, which keeps objects packed together
+/// in insertion order. Its memory comes from the global allocator
+/// unless another `Allocator` is named, as in `#[table(arena(MyAlloc))]`.
+///
+/// Writing `#[table(persistent)]` selects `PersistentStorage`, whose
+/// clones share their objects, so that the context can be cloned
+/// cheaply. This requires the `im` feature of `persian-rug`, and the
+/// objects must implement `Clone`.
+///
+/// Writing `#[table(search)]` selects `SearchStorage`, which maintains
+/// a full-text index of the table, and implements `SearchOwner` for
+/// the context. The objects are held in map storage, unless another
+/// storage is given, as in `#[table(search(arena))]`. This requires
+/// the `search` feature of `persian-rug`.
+///
+/// Writing `#[table(dirty)]` selects `DirtyStorage`, which records the
+/// objects added or borrowed mutably, and implements `DirtyOwner` for
+/// the context, so that they can be collected with
+/// `Context::take_dirty`. Another storage can be given for the objects
+/// themselves, as in `#[table(dirty(arena))]`.
+///
+/// Fields which are not tables are kept as they are. A field marked
+/// `#[resource]` holds a single value of its type for the whole
+/// context, such as configuration or a counter, and the context
+/// implements `ResourceOwner` for it, so that it can be reached
+/// generically with `Context::resource` and `Context::resource_mut`.
+/// A context can only contain one resource of each type.
+///
+/// The attribute accepts the following options:
+/// - `rkyv`: derive rkyv's `Archive`, `Serialize` and `Deserialize`
+///   for the context, and implement `ArchivedContext` for the
+///   archived form. This requires the `rkyv` feature of
+///   `persian-rug`, and all the participating types must be
+///   archivable.
+/// - `borsh`: derive borsh's `BorshSerialize` and `BorshDeserialize`
+///   for the context. This requires the `borsh` feature of
+///   `persian-rug`.
+/// - `serde`: derive serde's `Serialize` and `Deserialize` for the
+///   context. Each table is written with the handles of its objects,
+///   so proxies held by the objects remain valid once the context is
+///   read back. This requires the `serde` feature of `persian-rug`.
+/// - `versioned`: implement `persian_rug::versioned::VersionedArchive`,
+///   so that the context can be written to an archive which later
+///   releases with different tables can still read. Each table is
+///   stored under the name of its field. This requires the `borsh`
+///   feature of `persian-rug`, and every participating type must
+///   implement `Versioned`, along with borsh's `BorshSerialize` and
+///   `BorshDeserialize`.
+/// - `profile`: implement `Profiled` for the context, to report the
+///   accesses made to each of its tables. This requires the
+///   `profiling` feature of `persian-rug`.
+/// - `referrers`: maintain an index of the links between objects, and
+///   implement `Referrers` for the context. Each table's storage is
+///   wrapped in a `LinkStorage`, and every participating type must
+///   implement `Links`.
+/// - `isomorphism`: implement `Isomorphic` for the context, so that it
+///   can be compared with others up to the numbering of its objects,
+///   and canonicalized. Every participating type must implement
+///   `Relink`, `Clone` and `Ord`.
+/// - `aliases`: emit a module of type aliases for the context, named
+///   after it in snake case with `_aliases` appended. For a context
+///   `State` holding a table of `Foo<State>`, the module
+///   `state_aliases` contains `FooProxy` for `Proxy<Foo<State>>`,
+///   along with `StateAccessor<'a>` for `&'a State` and
+///   `StateMutator<'a>` for `&'a mut State`. Table types whose names
+///   would clash are left out. The module has the same visibility as
+///   the context, which must not be generic.
+/// - `csv`: implement `persian_rug::csv::CsvExport`, so that the
+///   objects of the context and the links between them can be written
+///   out as CSV. Every participating type must implement `Links`.
+/// - `gc`: implement `persian_rug::gc::Collect`, so that
+///   `Context::collect_garbage` can delete the objects which cannot be
+///   reached from a set of roots. Every participating type must
+///   implement `Links`.
+/// - `diagram`: implement `persian_rug::diagram::Diagram`, so that the
+///   objects of the context and the links between them can be drawn,
+///   for example as a Graphviz graph with the `dot` feature of
+///   `persian-rug`, or as a Mermaid flowchart with its `mermaid`
+///   feature. Every participating type must implement `Links`.
+/// - `extract`: implement `persian_rug::extract::Extract`, so that the
+///   objects reachable from a set of roots can be copied into another
+///   context. Every participating type must implement `Relink` and
+///   `Clone`.
+/// - `transplant`: implement `persian_rug::transplant::CloneInto` for
+///   every context with a table for each of the types of this one,
+///   once their context parameter is replaced, so that
+///   `Context::clone_into` can copy this context into them. If any of
+///   the types is not generic over its context, this context can only
+///   be copied into another of its own type. Every participating type
+///   must implement `Transplant`, usually by deriving it, and
+///   `Relink`. The context must not be generic.
+/// - `merge`: implement `persian_rug::merge::Merge`, so that
+///   `Context::absorb` can move the objects of another context of the
+///   same type into this one. Every participating type must implement
+///   `Relink`.
+/// - `diff`: implement `persian_rug::diff::Diffable`, so that two
+///   contexts of this type can be compared object by object, and the
+///   differences applied to another with `Context::apply_patch`. Every
+///   participating type must implement `Clone` and `PartialEq`.
+/// - `json`: implement `persian_rug::json::DebugJson`, so that the
+///   objects of the context can be dumped as a JSON value. This
+///   requires the `json` feature of `persian-rug`, and every
+///   participating type must implement serde's `Serialize`.
+/// - `proto`: implement `persian_rug::proto::ExportProto`, so that the
+///   context can be described as a `.proto` file, and its objects
+///   encoded as protobuf. This requires the `proto` feature of
+///   `persian-rug`, and every participating type must implement
+///   serde's `Serialize`.
+/// - `schemars`: implement `persian_rug::json::ExportSchema`, so that
+///   the JSON dumps of the context can be described by a JSON Schema.
+///   This requires the `schemars` feature of `persian-rug`, and every
+///   participating type must implement schemars' `JsonSchema`.
+/// - `provenance`: implement `persian_rug::provenance::ProvenanceOwner`
+///   for each table, so that `Context::provenance` can report where
+///   objects came from. This requires the `provenance` feature of
+///   `persian-rug`.
+/// - `observe`: implement `persian_rug::observe::ObserverOwner` for
+///   each table, so that `Context::on_add` and `Context::on_modify`
+///   can register callbacks. This requires the `observe` feature of
+///   `persian-rug`.
+/// - `serde_diff`: implement `serde_diff::SerdeDiff` for the context,
+///   diffing each of its tables in turn. This requires the
+///   `serde-diff` feature of `persian-rug`, and every participating
+///   type must implement `SerdeDiff`, along with serde's `Serialize`
+///   and `Deserialize`.
+/// - `checked`: generate `Owner::get` and `Owner::get_mut` through
+///   `Owner::try_get` and `Owner::try_get_mut`, so that no `unwrap` is
+///   generated for the context. Only the `try_` lookups are free of
+///   panics; see the `checked` module of `persian-rug`.
+/// - `handles = Type`: choose the handles of new objects in every
+///   table with the given `HandleAllocator`, rather than issuing them
+///   in sequence. Tables which need their allocator configuring are
+///   created with `Table::with_handles`. This cannot be combined with
+///   `rkyv`, `borsh`, `serde`, `versioned` or `isomorphism`.
+///
+/// The context may have lifetime parameters, so that its tables can
+/// hold types which borrow, but then `referrers`, `isomorphism`, `csv`,
+/// `gc`, `diagram`, `extract`, `transplant`, `merge` and `diff` are
+/// not available.
+///
 /// Example:
 /// ```rust
 /// use persian_rug::{contextual, persian_rug, Proxy};
@@ -188,24 +677,58 @@ pub fn constraints(args: TokenStream, input: TokenStream) -> TokenStream {
 /// struct MyRug(#[table] Foo, #[table] Bar);
 /// ```
 #[proc_macro_attribute]
-pub fn persian_rug(_args: TokenStream, input: TokenStream) -> TokenStream {
+pub fn persian_rug(args: TokenStream, input: TokenStream) -> TokenStream {
+    expand_persian_rug(args.into(), input.into()).into()
+}
+
+fn expand_persian_rug(args: pm2::TokenStream, input: pm2::TokenStream) -> pm2::TokenStream {
+    let options: RugOptions = match syn::parse2(args) {
+        Ok(options) => options,
+        Err(e) => return e.to_compile_error(),
+    };
+
     let syn::DeriveInput {
         attrs,
         vis,
         ident: ty_ident,
         data,
-        generics,
-    } = syn::parse_macro_input!(input);
+        generics: ty_generics_decl,
+    } = match syn::parse2(input) {
+        Ok(input) => input,
+        Err(e) => return e.to_compile_error(),
+    };
+
+    // These options identify types with `TypeId`, which only exists
+    // for types that do not borrow.
+    if let Some(lifetime) = ty_generics_decl.lifetimes().next() {
+        if options.referrers
+            || options.isomorphism
+            || options.csv
+            || options.gc
+            || options.diagram
+            || options.extract
+            || options.transplant
+            || options.merge
+            || options.diff
+        {
+            return syn::Error::new_spanned(
+                lifetime,
+                "referrers, isomorphism, csv, gc, diagram, extract, transplant, merge and diff are not supported for contexts with lifetime parameters",
+            )
+            .to_compile_error();
+        }
+    }
 
-    let (generics, ty_generics, wc) = generics.split_for_impl();
+    let (generics, ty_generics, wc) = ty_generics_decl.split_for_impl();
 
     let mut impls = pm2::TokenStream::new();
+    let mut tables = Vec::new();
 
-    let body = if let syn::Data::Struct(s) = data {
-        let mut fields = syn::punctuated::Punctuated::<syn::Field, syn::Token![,]>::new();
+    let mut fields = syn::punctuated::Punctuated::<syn::Field, syn::Token![,]>::new();
 
-        let mut process_field = |field: &syn::Field| {
-            let is_table = field.attrs.iter().any(|attr| attr.path.is_ident("table"));
+    let body = if let syn::Data::Struct(s) = data {
+        let mut process_field = |field: &syn::Field| -> syn::Result<()> {
+            let table_attr = field.attrs.iter().find(|attr| attr.path.is_ident("table"));
 
             let field_type = &field.ty;
             let ident = field
@@ -228,9 +751,34 @@ pub fn persian_rug(_args: TokenStream, input: TokenStream) -> TokenStream {
                 .cloned()
                 .collect::<Vec<_>>();
 
-            if !is_table {
-                fields.push(field.clone());
-            } else {
+            if let Some(table_attr) = table_attr {
+                if let Some(resource) = field
+                    .attrs
+                    .iter()
+                    .find(|attr| attr.path.is_ident("resource"))
+                {
+                    return Err(syn::Error::new_spanned(
+                        resource,
+                        "a field cannot be both a table and a resource",
+                    ));
+                }
+                let storage = TableStorage::from_attr(table_attr)?;
+                // Everything generated for a table is only present
+                // when its field is.
+                let cfgs = attrs
+                    .iter()
+                    .filter(|a| a.path.is_ident("cfg"))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !cfgs.is_empty() && field.ident.is_none() {
+                    return Err(syn::Error::new_spanned(
+                        &cfgs[0],
+                        "cfg is only supported on tables with named fields",
+                    ));
+                }
+                let cfgs = quote::quote! { #(#cfgs)* };
+                let table_type =
+                    storage.table_type(field_type, options.referrers, options.handles.as_ref());
                 fields.push(syn::Field {
                     attrs,
                     vis: vis.clone(),
@@ -240,22 +788,74 @@ pub fn persian_rug(_args: TokenStream, input: TokenStream) -> TokenStream {
                         None
                     },
                     colon_token: field.colon_token,
-                    ty: syn::parse_quote! {
-                        ::persian_rug::Table<#field_type>
-                    },
+                    ty: table_type.clone(),
                 });
+                tables.push((ident.clone(), field_type.clone(), cfgs.clone()));
 
-                impls.extend(quote::quote! {
-                    impl #generics ::persian_rug::Owner<#field_type> for #ty_ident #ty_generics #wc {
-                        fn add(&mut self, what: #field_type) -> ::persian_rug::Proxy<#field_type> {
-                            self.#ident.push(what)
+                if let TableStorage::Search(_) = storage {
+                    impls.extend(quote::quote! {
+                        #cfgs
+                        impl #generics ::persian_rug::search::SearchOwner<#field_type> for #ty_ident #ty_generics #wc {
+                            fn search_objects(&self, term: &str) -> ::std::vec::Vec<::persian_rug::Proxy<#field_type>> {
+                                self.#ident.search(term)
+                            }
+                        }
+                    });
+                }
+
+                if let TableStorage::Dirty(_) = storage {
+                    impls.extend(quote::quote! {
+                        #cfgs
+                        impl #generics ::persian_rug::dirty::DirtyOwner<#field_type> for #ty_ident #ty_generics #wc {
+                            fn take_dirty_objects(&mut self) -> ::std::collections::BTreeSet<::persian_rug::Proxy<#field_type>> {
+                                self.#ident.take_dirty()
+                            }
+                        }
+                    });
+                }
+
+                let unchecked = if options.checked {
+                    quote::quote! {
+                        #[track_caller]
+                        fn get(&self, what: &::persian_rug::Proxy<#field_type>) -> &#field_type {
+                            match ::persian_rug::Owner::try_get(self, what) {
+                                ::std::result::Result::Ok(value) => value,
+                                ::std::result::Result::Err(e) => ::std::panic!("{}", e),
+                            }
+                        }
+                        #[track_caller]
+                        fn get_mut(&mut self, what: &::persian_rug::Proxy<#field_type>) -> &mut #field_type {
+                            match ::persian_rug::Owner::try_get_mut(self, what) {
+                                ::std::result::Result::Ok(value) => value,
+                                ::std::result::Result::Err(e) => ::std::panic!("{}", e),
+                            }
                         }
+                    }
+                } else {
+                    quote::quote! {
                         fn get(&self, what: &::persian_rug::Proxy<#field_type>) -> &#field_type {
                             self.#ident.get(what).unwrap()
                         }
                         fn get_mut(&mut self, what: &::persian_rug::Proxy<#field_type>) -> &mut #field_type {
                             self.#ident.get_mut(what).unwrap()
                         }
+                    }
+                };
+
+                impls.extend(quote::quote! {
+                    #cfgs
+                    impl #generics ::persian_rug::Owner<#field_type> for #ty_ident #ty_generics #wc {
+                        #[track_caller]
+                        fn add(&mut self, what: #field_type) -> ::persian_rug::Proxy<#field_type> {
+                            self.#ident.push(what)
+                        }
+                        #unchecked
+                        fn try_get(&self, what: &::persian_rug::Proxy<#field_type>) -> ::std::result::Result<&#field_type, ::persian_rug::checked::Missing> {
+                            self.#ident.get(what).ok_or_else(|| ::persian_rug::checked::Missing::new(what))
+                        }
+                        fn try_get_mut(&mut self, what: &::persian_rug::Proxy<#field_type>) -> ::std::result::Result<&mut #field_type, ::persian_rug::checked::Missing> {
+                            self.#ident.get_mut(what).ok_or_else(|| ::persian_rug::checked::Missing::new(what))
+                        }
                         fn get_iter(&self) -> ::persian_rug::TableIterator<'_, #field_type> {
                             self.#ident.iter()
                         }
@@ -265,15 +865,121 @@ pub fn persian_rug(_args: TokenStream, input: TokenStream) -> TokenStream {
                         fn get_proxy_iter(&self) -> ::persian_rug::TableProxyIterator<'_, #field_type> {
                             self.#ident.iter_proxies()
                         }
+                        fn next_proxy(&self) -> ::persian_rug::Proxy<#field_type> {
+                            self.#ident.next_proxy()
+                        }
+                        fn contains(&self, what: &::persian_rug::Proxy<#field_type>) -> bool {
+                            self.#ident.contains(what)
+                        }
+                        fn insert_with_handle(&mut self, handle: u64, what: #field_type) -> ::std::result::Result<::persian_rug::Proxy<#field_type>, ::persian_rug::HandleInUse> {
+                            self.#ident.insert_with_handle(handle, what)
+                        }
+                        #[track_caller]
+                        fn reserve_proxy(&mut self) -> ::std::option::Option<::persian_rug::Proxy<#field_type>> {
+                            ::std::option::Option::Some(self.#ident.reserve_proxy())
+                        }
+                        fn release_proxy(&mut self, what: &::persian_rug::Proxy<#field_type>) {
+                            self.#ident.release_proxy(what)
+                        }
+                        fn proxy_for(&self, handle: u64) -> ::std::option::Option<::persian_rug::Proxy<#field_type>> {
+                            self.#ident.proxy_for(handle)
+                        }
+                        fn proxy_from(&self, handle: u64) -> ::std::option::Option<::persian_rug::Proxy<#field_type>> {
+                            self.#ident.proxy_from(handle)
+                        }
+                        fn len(&self) -> usize {
+                            self.#ident.len()
+                        }
+                        fn swap(&mut self, a: &::persian_rug::Proxy<#field_type>, b: &::persian_rug::Proxy<#field_type>) -> bool {
+                            self.#ident.swap(a, b)
+                        }
+                        fn remove(&mut self, what: &::persian_rug::Proxy<#field_type>) -> ::std::option::Option<#field_type> {
+                            self.#ident.remove(what)
+                        }
+                        fn retain(&mut self, f: &mut dyn FnMut(&::persian_rug::Proxy<#field_type>, &mut #field_type) -> bool) {
+                            self.#ident.retain(f)
+                        }
+                        fn drain(&mut self) -> ::std::vec::Vec<(::persian_rug::Proxy<#field_type>, #field_type)> {
+                            self.#ident.drain()
+                        }
+                    }
+
+                    #cfgs
+                    impl #generics ::persian_rug::TableOwner<#field_type> for #ty_ident #ty_generics #wc {
+                        type Table = #table_type;
+                        fn get_table(&self) -> &Self::Table {
+                            &self.#ident
+                        }
+                        fn get_table_mut(&mut self) -> &mut Self::Table {
+                            &mut self.#ident
+                        }
+                    }
+
+                    #cfgs
+                    unsafe impl #generics ::persian_rug::disjoint::TableField<#field_type> for #ty_ident #ty_generics #wc {
+                        type Table = #table_type;
+                        unsafe fn table_ptr(this: *mut Self) -> *mut Self::Table {
+                            unsafe { ::core::ptr::addr_of_mut!((*this).#ident) }
+                        }
+                    }
+                });
+            } else if field
+                .attrs
+                .iter()
+                .any(|attr| attr.path.is_ident("resource"))
+            {
+                if let Some(attr) = field
+                    .attrs
+                    .iter()
+                    .find(|attr| attr.path.is_ident("resource"))
+                {
+                    if !attr.tokens.is_empty() {
+                        return Err(syn::Error::new_spanned(attr, "resource takes no options"));
+                    }
+                }
+                let attrs = field
+                    .attrs
+                    .iter()
+                    .filter(|a| !a.path.is_ident("resource"))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let cfgs = attrs
+                    .iter()
+                    .filter(|a| a.path.is_ident("cfg"))
+                    .collect::<Vec<_>>();
+                if !cfgs.is_empty() && field.ident.is_none() {
+                    return Err(syn::Error::new_spanned(
+                        cfgs[0],
+                        "cfg is only supported on resources with named fields",
+                    ));
+                }
+                impls.extend(quote::quote! {
+                    #(#cfgs)*
+                    impl #generics ::persian_rug::ResourceOwner<#field_type> for #ty_ident #ty_generics #wc {
+                        fn get_resource(&self) -> &#field_type {
+                            &self.#ident
+                        }
+                        fn get_resource_mut(&mut self) -> &mut #field_type {
+                            &mut self.#ident
+                        }
                     }
                 });
+                fields.push(syn::Field {
+                    attrs,
+                    ..field.clone()
+                });
+            } else {
+                fields.push(field.clone());
             }
+            Ok(())
         };
 
         match s.fields {
             syn::Fields::Named(syn::FieldsNamed { named, .. }) => {
                 for field in named.iter() {
-                    (process_field)(field);
+                    if let Err(e) = (process_field)(field) {
+                        return e.to_compile_error();
+                    }
                 }
                 quote::quote! {
                     #vis struct #ty_ident #generics #wc {
@@ -283,7 +989,9 @@ pub fn persian_rug(_args: TokenStream, input: TokenStream) -> TokenStream {
             }
             syn::Fields::Unnamed(syn::FieldsUnnamed { unnamed, .. }) => {
                 for field in unnamed.iter() {
-                    (process_field)(field);
+                    if let Err(e) = (process_field)(field) {
+                        return e.to_compile_error();
+                    }
                 }
                 quote::quote! {
                     #vis struct #ty_ident #generics(
@@ -302,11 +1010,10 @@ pub fn persian_rug(_args: TokenStream, input: TokenStream) -> TokenStream {
             pm2::Span::call_site(),
             "Only structs can be annotated as persian-rugs.",
         )
-        .to_compile_error()
-        .into();
+        .to_compile_error();
     };
 
-    let attrs = {
+    let mut attrs = {
         let mut res = pm2::TokenStream::new();
         for attr in attrs {
             attr.to_tokens(&mut res);
@@ -314,122 +1021,2146 @@ pub fn persian_rug(_args: TokenStream, input: TokenStream) -> TokenStream {
         res
     };
 
-    let res = quote::quote! {
-        #attrs
-        #body
+    if options.borsh {
+        attrs.extend(quote::quote! {
+            #[derive(::persian_rug::borsh::BorshSerialize, ::persian_rug::borsh::BorshDeserialize)]
+            #[borsh(crate = "::persian_rug::borsh")]
+        });
+    }
 
-        impl #generics ::persian_rug::Context for #ty_ident #ty_generics #wc {
-            fn add<T>(&mut self, what: T) -> ::persian_rug::Proxy<T>
-            where
-                #ty_ident #ty_generics: ::persian_rug::Owner<T>,
-                T: ::persian_rug::Contextual<Context=Self>
-            {
-                <Self as ::persian_rug::Owner<T>>::add(self, what)
+    if options.serde {
+        attrs.extend(quote::quote! {
+            #[derive(::persian_rug::serde::Serialize, ::persian_rug::serde::Deserialize)]
+            #[serde(crate = "::persian_rug::serde")]
+        });
+    }
+
+    if options.referrers {
+        let idents = tables.iter().map(|(ident, _, _)| ident).collect::<Vec<_>>();
+        let types = tables.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let cfgs = tables.iter().map(|(_, _, cfgs)| cfgs).collect::<Vec<_>>();
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::referrers::Referrers for #ty_ident #ty_generics #wc {
+                fn referrers_of(&self, target: &::persian_rug::AnyProxy) -> ::std::vec::Vec<::persian_rug::AnyProxy> {
+                    let mut res = ::std::vec::Vec::new();
+                    #(
+                        #cfgs
+                        ::persian_rug::referrers::LinkIndex::<#types>::referrers_of(self.#idents.storage(), target, &mut res);
+                    )*
+                    res
+                }
             }
+        });
+    }
 
-            fn get<T>(&self, what: &::persian_rug::Proxy<T>) -> &T
-            where
-                #ty_ident #ty_generics: ::persian_rug::Owner<T>,
-                T: ::persian_rug::Contextual<Context=Self>
-            {
-                <Self as ::persian_rug::Owner<T>>::get(self, what)
+    if options.isomorphism {
+        let idents = tables.iter().map(|(ident, _, _)| ident).collect::<Vec<_>>();
+        let types = tables.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let cfgs = tables.iter().map(|(_, _, cfgs)| cfgs).collect::<Vec<_>>();
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::isomorphism::Isomorphic for #ty_ident #ty_generics #wc {
+                fn describe(graph: &mut ::persian_rug::isomorphism::Describe<'_, Self>) {
+                    #(
+                        #cfgs
+                        graph.table::<#types>();
+                    )*
+                }
+
+                fn renumber(&mut self, renumbering: &::persian_rug::isomorphism::Renumbering) {
+                    #(
+                        #cfgs
+                        renumbering.apply(&mut self.#idents);
+                    )*
+                }
             }
+        });
+    }
 
-            fn get_mut<T>(&mut self, what: &::persian_rug::Proxy<T>) -> &mut T
-            where
-                #ty_ident #ty_generics: ::persian_rug::Owner<T>,
-                T: ::persian_rug::Contextual<Context=Self>
-            {
-                <Self as ::persian_rug::Owner<T>>::get_mut(self, what)
+    if options.profile {
+        let idents = tables.iter().map(|(ident, _, _)| ident).collect::<Vec<_>>();
+        let types = tables.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let cfgs = tables.iter().map(|(_, _, cfgs)| cfgs).collect::<Vec<_>>();
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::profiling::Profiled for #ty_ident #ty_generics #wc {
+                fn profile(&self) -> ::persian_rug::profiling::ProfileReport {
+                    let mut report = ::persian_rug::profiling::ProfileReport::new();
+                    #(
+                        #cfgs
+                        report.add(::std::any::type_name::<#types>(), self.#idents.access_counts());
+                    )*
+                    report
+                }
+
+                fn reset_profile(&self) {
+                    #(
+                        #cfgs
+                        self.#idents.reset_access_counts();
+                    )*
+                }
             }
+        });
+    }
 
-            fn get_iter<T>(&self) -> ::persian_rug::TableIterator<'_, T>
-            where
-                #ty_ident #ty_generics: ::persian_rug::Owner<T>,
-                T: ::persian_rug::Contextual<Context=Self>
-            {
-                <Self as ::persian_rug::Owner<T>>::get_iter(self)
+    if options.csv {
+        let types = tables.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let cfgs = tables.iter().map(|(_, _, cfgs)| cfgs).collect::<Vec<_>>();
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::csv::CsvExport for #ty_ident #ty_generics #wc {
+                fn describe(csv: &mut ::persian_rug::csv::CsvTables<'_, Self>) -> ::std::io::Result<()> {
+                    #(
+                        #cfgs
+                        csv.table::<#types>()?;
+                    )*
+                    Ok(())
+                }
             }
+        });
+    }
 
-            fn get_iter_mut<T>(&mut self) -> ::persian_rug::TableMutIterator<'_, T>
-            where
-                #ty_ident #ty_generics: ::persian_rug::Owner<T>,
-                T: ::persian_rug::Contextual<Context=Self>
-            {
-                <Self as ::persian_rug::Owner<T>>::get_iter_mut(self)
+    if options.gc {
+        let types = tables.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let cfgs = tables.iter().map(|(_, _, cfgs)| cfgs).collect::<Vec<_>>();
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::gc::Collect for #ty_ident #ty_generics #wc {
+                fn describe(gc: &mut ::persian_rug::gc::GcTables<Self>) {
+                    #(
+                        #cfgs
+                        gc.table::<#types>();
+                    )*
+                }
             }
+        });
+    }
 
-            fn get_proxy_iter<T>(&self) -> ::persian_rug::TableProxyIterator<'_, T>
-            where
-                #ty_ident #ty_generics: ::persian_rug::Owner<T>,
-                T: ::persian_rug::Contextual<Context=Self>
-            {
-                <Self as ::persian_rug::Owner<T>>::get_proxy_iter(self)
+    if options.versioned {
+        let names = tables.iter().map(|(ident, _, _)| match ident {
+            syn::Member::Named(id) => id.to_string(),
+            syn::Member::Unnamed(index) => index.index.to_string(),
+        });
+        let types = tables.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let cfgs = tables.iter().map(|(_, _, cfgs)| cfgs).collect::<Vec<_>>();
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::versioned::VersionedArchive for #ty_ident #ty_generics #wc {
+                fn describe(tables: &mut ::persian_rug::versioned::VersionedTables<Self>) {
+                    #(
+                        #cfgs
+                        tables.table::<#types, _>(#names);
+                    )*
+                }
             }
-        }
+        });
+    }
 
-        #impls
-    };
+    if options.diagram {
+        let types = tables.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let cfgs = tables.iter().map(|(_, _, cfgs)| cfgs).collect::<Vec<_>>();
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::diagram::Diagram for #ty_ident #ty_generics #wc {
+                fn describe(diagram: &mut ::persian_rug::diagram::DiagramTables<'_, Self>) {
+                    #(
+                        #cfgs
+                        diagram.table::<#types>();
+                    )*
+                }
+            }
+        });
+    }
 
-    res.into()
-}
+    if options.extract {
+        let types = tables.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let cfgs = tables.iter().map(|(_, _, cfgs)| cfgs).collect::<Vec<_>>();
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::extract::Extract for #ty_ident #ty_generics #wc {
+                fn describe(tables: &mut ::persian_rug::extract::ExtractTables<Self>) {
+                    #(
+                        #cfgs
+                        tables.table::<#types>();
+                    )*
+                }
+            }
+        });
+    }
 
-/// Provide a implementation of `Contextual` for a type.
-///
-/// This is a very simple derive-style macro, that creates an
-/// impl for `Contextual` for the type it annotates. It takes
-/// one argument, which is the `Context` type that this
-/// type belongs to.
-///
-/// Example:
-/// ```rust
-/// use persian_rug::{contextual, Context};
-///
-/// #[contextual(C)]
-/// struct Foo<C: Context> {
-///    _marker: core::marker::PhantomData<C>
-/// }
-/// ```
-/// which is equivalent to the following:
-/// ```rust
-/// use persian_rug::{Context, Contextual};
-///
-/// struct Foo<C: Context> {
-///    _marker: core::marker::PhantomData<C>
-/// }
+    if options.merge {
+        let types = tables.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let cfgs = tables.iter().map(|(_, _, cfgs)| cfgs).collect::<Vec<_>>();
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::merge::Merge for #ty_ident #ty_generics #wc {
+                fn describe(tables: &mut ::persian_rug::merge::MergeTables<Self>) {
+                    #(
+                        #cfgs
+                        tables.table::<#types>();
+                    )*
+                }
+            }
+        });
+    }
+
+    if options.diff {
+        let types = tables.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let cfgs = tables.iter().map(|(_, _, cfgs)| cfgs).collect::<Vec<_>>();
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::diff::Diffable for #ty_ident #ty_generics #wc {
+                fn describe(tables: &mut ::persian_rug::diff::DiffTables<'_, Self>) {
+                    #(
+                        #cfgs
+                        tables.table::<#types, _, _>();
+                    )*
+                }
+            }
+        });
+    }
+
+    if options.transplant {
+        if !ty_generics_decl.params.is_empty() {
+            return syn::Error::new_spanned(
+                &ty_generics_decl,
+                "transplant is not supported for generic contexts",
+            )
+            .to_compile_error();
+        }
+
+        let types = tables.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let cfgs = tables.iter().map(|(_, _, cfgs)| cfgs).collect::<Vec<_>>();
+        if types
+            .iter()
+            .all(|ty| mentions(ty.to_token_stream(), &[&ty_ident]))
+        {
+            // Each table of the target holds the same type, with this
+            // context replaced by the target.
+            let target = quote::format_ident!("__Target");
+            let targets = types
+                .iter()
+                .map(|ty| substitute(ty.to_token_stream(), &ty_ident, &target))
+                .collect::<Vec<_>>();
+            impls.extend(quote::quote! {
+                impl<#target> ::persian_rug::transplant::CloneInto<#target> for #ty_ident
+                where
+                    #target: ::persian_rug::Context,
+                    #(
+                        #types: ::persian_rug::transplant::Transplant<#targets>,
+                        #targets: ::persian_rug::Contextual<Context = #target> + ::persian_rug::Relink + 'static,
+                        #target: ::persian_rug::Owner<#targets>,
+                    )*
+                {
+                    fn describe(tables: &mut ::persian_rug::transplant::TransplantTables<Self, #target>) {
+                        #(
+                            #cfgs
+                            tables.table::<#types, #targets>();
+                        )*
+                    }
+                }
+            });
+        } else {
+            // A table whose type does not mention this context can
+            // only be copied into another context of the same type.
+            impls.extend(quote::quote! {
+                impl ::persian_rug::transplant::CloneInto<Self> for #ty_ident {
+                    fn describe(tables: &mut ::persian_rug::transplant::TransplantTables<Self, Self>) {
+                        #(
+                            #cfgs
+                            tables.table::<#types, #types>();
+                        )*
+                    }
+                }
+            });
+        }
+    }
+
+    if options.json {
+        let types = tables.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let cfgs = tables.iter().map(|(_, _, cfgs)| cfgs).collect::<Vec<_>>();
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::json::DebugJson for #ty_ident #ty_generics #wc {
+                fn describe(json: &mut ::persian_rug::json::JsonTables<'_, Self>) {
+                    #(
+                        #cfgs
+                        json.table::<#types>();
+                    )*
+                }
+            }
+        });
+    }
+
+    if options.proto {
+        let types = tables.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let cfgs = tables.iter().map(|(_, _, cfgs)| cfgs).collect::<Vec<_>>();
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::proto::ExportProto for #ty_ident #ty_generics #wc {
+                fn encode_tables(tables: &mut ::persian_rug::proto::ProtoTables<'_, Self>) {
+                    #(
+                        #cfgs
+                        tables.table::<#types>();
+                    )*
+                }
+            }
+        });
+    }
+
+    if options.schemars {
+        let types = tables.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let cfgs = tables.iter().map(|(_, _, cfgs)| cfgs).collect::<Vec<_>>();
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::json::ExportSchema for #ty_ident #ty_generics #wc {
+                fn describe_schema(schema: &mut ::persian_rug::json::SchemaTables) {
+                    #(
+                        #cfgs
+                        schema.table::<#types>();
+                    )*
+                }
+            }
+        });
+    }
+
+    if options.provenance {
+        for (ident, field_type, cfgs) in tables.iter() {
+            impls.extend(quote::quote! {
+                #cfgs
+                impl #generics ::persian_rug::provenance::ProvenanceOwner<#field_type> for #ty_ident #ty_generics #wc {
+                    fn provenance(&self, what: &::persian_rug::Proxy<#field_type>) -> ::std::option::Option<&::persian_rug::provenance::Provenance> {
+                        self.#ident.provenance(what)
+                    }
+                }
+            });
+        }
+    }
+
+    if options.observe {
+        for (ident, field_type, cfgs) in tables.iter() {
+            impls.extend(quote::quote! {
+                #cfgs
+                impl #generics ::persian_rug::observe::ObserverOwner<#field_type> for #ty_ident #ty_generics #wc {
+                    fn on_add(&mut self, f: impl ::std::ops::FnMut(&::persian_rug::Proxy<#field_type>, &#field_type) + ::std::marker::Send + ::std::marker::Sync + 'static) {
+                        self.#ident.on_add(f)
+                    }
+                    fn on_modify(&mut self, f: impl ::std::ops::FnMut(&::persian_rug::Proxy<#field_type>) + ::std::marker::Send + ::std::marker::Sync + 'static) {
+                        self.#ident.on_modify(f)
+                    }
+                }
+            });
+        }
+    }
+
+    if options.serde_diff {
+        let mut diffs = pm2::TokenStream::new();
+        let mut applies = pm2::TokenStream::new();
+        for (ident, _, cfgs) in tables.iter() {
+            let (push, path) = match ident {
+                syn::Member::Named(name) => {
+                    let name = name.to_string();
+                    (
+                        quote::quote! { ctx.push_field(#name); },
+                        quote::quote! { ::persian_rug::serde_diff::DiffPathElementValue::Field(field) if field == #name },
+                    )
+                }
+                syn::Member::Unnamed(index) => {
+                    let index = index.index as u16;
+                    (
+                        quote::quote! { ctx.push_field_index(#index); },
+                        quote::quote! { ::persian_rug::serde_diff::DiffPathElementValue::FieldIndex(#index) },
+                    )
+                }
+            };
+            diffs.extend(quote::quote! {
+                #cfgs
+                {
+                    #push
+                    changed |= ::persian_rug::serde_diff::SerdeDiff::diff(&self.#ident, ctx, &other.#ident)?;
+                    ctx.pop_path_element()?;
+                }
+            });
+            applies.extend(quote::quote! {
+                #cfgs
+                #path => {
+                    changed |= ::persian_rug::serde_diff::SerdeDiff::apply(&mut self.#ident, seq, ctx)?;
+                }
+            });
+        }
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::serde_diff::SerdeDiff for #ty_ident #ty_generics #wc {
+                fn diff<'a, S: ::persian_rug::serde::ser::SerializeSeq>(
+                    &self,
+                    ctx: &mut ::persian_rug::serde_diff::DiffContext<'a, S>,
+                    other: &Self,
+                ) -> ::std::result::Result<bool, S::Error> {
+                    let mut changed = false;
+                    #diffs
+                    Ok(changed)
+                }
+
+                fn apply<'de, A: ::persian_rug::serde::de::SeqAccess<'de>>(
+                    &mut self,
+                    seq: &mut A,
+                    ctx: &mut ::persian_rug::serde_diff::ApplyContext,
+                ) -> ::std::result::Result<bool, A::Error> {
+                    let mut changed = false;
+                    while let Some(element) = ctx.next_path_element(seq)? {
+                        match element {
+                            #applies
+                            _ => ctx.skip_value(seq)?,
+                        }
+                    }
+                    Ok(changed)
+                }
+            }
+        });
+    }
+
+    if options.aliases {
+        if !ty_generics_decl.params.is_empty() {
+            return syn::Error::new_spanned(
+                &ty_generics_decl,
+                "aliases are not supported for generic contexts",
+            )
+            .to_compile_error();
+        }
+
+        let mut names =
+            std::collections::BTreeMap::<String, Vec<(&syn::Type, &pm2::TokenStream)>>::new();
+        for (_, field_type, cfgs) in tables.iter() {
+            if let syn::Type::Path(path) = field_type {
+                if let Some(segment) = path.path.segments.last() {
+                    names
+                        .entry(format!("{}Proxy", segment.ident))
+                        .or_default()
+                        .push((field_type, cfgs));
+                }
+            }
+        }
+        let proxies = names.iter().filter_map(|(name, types)| {
+            let name = quote::format_ident!("{}", name);
+            match types.as_slice() {
+                [(ty, cfgs)] => Some(quote::quote! {
+                    #cfgs
+                    pub type #name = ::persian_rug::Proxy<#ty>;
+                }),
+                _ => None,
+            }
+        });
+
+        let module = quote::format_ident!("{}_aliases", snake_case(&ty_ident.to_string()));
+        let accessor = quote::format_ident!("{}Accessor", ty_ident);
+        let mutator = quote::format_ident!("{}Mutator", ty_ident);
+        let doc = format!("Type aliases for [`{}`].", ty_ident);
+        impls.extend(quote::quote! {
+            #[doc = #doc]
+            #vis mod #module {
+                #[allow(unused_imports)]
+                use super::*;
+
+                #(#proxies)*
+
+                pub type #accessor<'a> = &'a #ty_ident;
+                pub type #mutator<'a> = &'a mut #ty_ident;
+            }
+        });
+    }
+
+    if options.rkyv {
+        attrs.extend(quote::quote! {
+            #[derive(
+                ::persian_rug::rkyv::Archive,
+                ::persian_rug::rkyv::Serialize,
+                ::persian_rug::rkyv::Deserialize
+            )]
+            #[rkyv(crate = ::persian_rug::rkyv)]
+        });
+
+        // The archived struct requires all of its fields to be
+        // archivable, so our impls must too.
+        let mut archived_wc = ty_generics_decl
+            .where_clause
+            .clone()
+            .unwrap_or_else(|| syn::parse_quote! { where });
+        for field in fields.iter() {
+            let ty = &field.ty;
+            archived_wc
+                .predicates
+                .push(syn::parse_quote! { #ty: ::persian_rug::rkyv::Archive });
+        }
+
+        let archived_ident = quote::format_ident!("Archived{}", ty_ident);
+        impls.extend(quote::quote! {
+            impl #generics ::persian_rug::archive::ArchivedContext for #archived_ident #ty_generics #archived_wc {
+                type Context = #ty_ident #ty_generics;
+            }
+        });
+        for (ident, field_type, cfgs) in &tables {
+            impls.extend(quote::quote! {
+                #cfgs
+                impl #generics ::persian_rug::archive::ArchivedOwner<#field_type> for #archived_ident #ty_generics #archived_wc {
+                    fn archived_table(&self) -> &::persian_rug::archive::ArchivedTable<#field_type> {
+                        &self.#ident
+                    }
+                }
+            });
+        }
+    }
+
+    let context_name = ty_ident.to_string();
+    let table_schemas = tables.iter().map(|(ident, ty, cfgs)| {
+        let field = match ident {
+            syn::Member::Named(ident) => ident.to_string(),
+            syn::Member::Unnamed(index) => index.index.to_string(),
+        };
+        quote::quote! {
+            #cfgs
+            tables.push(::persian_rug::schema::TableSchema {
+                field: #field,
+                schema: <#ty as ::persian_rug::Contextual>::schema(),
+            });
+        }
+    });
+    let table_schemas = quote::quote! { #(#table_schemas)* };
+    let table_visits = tables.iter().map(|(_, ty, cfgs)| {
+        quote::quote! {
+            #cfgs
+            ::persian_rug::visit::ContextVisitor::<Self>::table::<#ty>(visitor, self);
+        }
+    });
+
+    let res = quote::quote! {
+        #attrs
+        #body
+
+        impl #generics ::persian_rug::Context for #ty_ident #ty_generics #wc {
+            #[allow(unused_mut)]
+            fn schema() -> ::persian_rug::schema::ContextSchema {
+                let mut tables = ::std::vec::Vec::new();
+                #table_schemas
+                ::persian_rug::schema::ContextSchema {
+                    name: #context_name,
+                    type_name: ::std::any::type_name::<Self>(),
+                    tables,
+                }
+            }
+
+            #[allow(unused_variables)]
+            fn visit_all<V>(&self, visitor: &mut V)
+            where
+                V: ::persian_rug::visit::ContextVisitor<Self>,
+            {
+                #(#table_visits)*
+            }
+
+            #[track_caller]
+            fn add<T>(&mut self, what: T) -> ::persian_rug::Proxy<T>
+            where
+                #ty_ident #ty_generics: ::persian_rug::Owner<T>,
+                T: ::persian_rug::Contextual<Context=Self>
+            {
+                <Self as ::persian_rug::Owner<T>>::add(self, what)
+            }
+
+            fn get<T>(&self, what: &::persian_rug::Proxy<T>) -> &T
+            where
+                #ty_ident #ty_generics: ::persian_rug::Owner<T>,
+                T: ::persian_rug::Contextual<Context=Self>
+            {
+                <Self as ::persian_rug::Owner<T>>::get(self, what)
+            }
+
+            fn get_mut<T>(&mut self, what: &::persian_rug::Proxy<T>) -> &mut T
+            where
+                #ty_ident #ty_generics: ::persian_rug::Owner<T>,
+                T: ::persian_rug::Contextual<Context=Self>
+            {
+                <Self as ::persian_rug::Owner<T>>::get_mut(self, what)
+            }
+
+            fn get_iter<T>(&self) -> ::persian_rug::TableIterator<'_, T>
+            where
+                #ty_ident #ty_generics: ::persian_rug::Owner<T>,
+                T: ::persian_rug::Contextual<Context=Self>
+            {
+                <Self as ::persian_rug::Owner<T>>::get_iter(self)
+            }
+
+            fn get_iter_mut<T>(&mut self) -> ::persian_rug::TableMutIterator<'_, T>
+            where
+                #ty_ident #ty_generics: ::persian_rug::Owner<T>,
+                T: ::persian_rug::Contextual<Context=Self>
+            {
+                <Self as ::persian_rug::Owner<T>>::get_iter_mut(self)
+            }
+
+            fn get_proxy_iter<T>(&self) -> ::persian_rug::TableProxyIterator<'_, T>
+            where
+                #ty_ident #ty_generics: ::persian_rug::Owner<T>,
+                T: ::persian_rug::Contextual<Context=Self>
+            {
+                <Self as ::persian_rug::Owner<T>>::get_proxy_iter(self)
+            }
+        }
+
+        #impls
+    };
+
+    res
+}
+
+struct ContextualArgs {
+    context: syn::Type,
+    view: bool,
+}
+
+impl syn::parse::Parse for ContextualArgs {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let context = input.parse()?;
+        let mut res = ContextualArgs {
+            context,
+            view: false,
+        };
+        if input.is_empty() {
+            return Ok(res);
+        }
+        let _: syn::Token![,] = input.parse()?;
+        let options =
+            syn::punctuated::Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated(input)?;
+        for option in options {
+            match option.to_string().as_str() {
+                "view" => res.view = true,
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        option,
+                        "unsupported contextual option",
+                    ))
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// If `ty` is `Wrapper<T>`, for the given wrapper name, return `T`.
+fn wrapped_type<'a>(ty: &'a syn::Type, wrapper: &str) -> Option<&'a syn::Type> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.iter().collect::<Vec<_>>().as_slice() {
+        [syn::GenericArgument::Type(inner)] => Some(inner),
+        _ => None,
+    }
+}
+
+/// Check whether `tokens` mention `ident`, other than in the
+/// arguments of `outer`.
+fn mentions_outside(tokens: pm2::TokenStream, ident: &syn::Ident, outer: &syn::Ident) -> bool {
+    let mut depth = 0usize;
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            pm2::TokenTree::Ident(i) if i == *outer => {
+                if let Some(pm2::TokenTree::Punct(p)) = tokens.peek() {
+                    if p.as_char() == '<' {
+                        tokens.next();
+                        depth += 1;
+                    }
+                }
+            }
+            pm2::TokenTree::Punct(p) if depth > 0 && p.as_char() == '<' => depth += 1,
+            pm2::TokenTree::Punct(p) if depth > 0 && p.as_char() == '>' => depth -= 1,
+            _ if depth > 0 => {}
+            pm2::TokenTree::Ident(i) if i == *ident => return true,
+            pm2::TokenTree::Group(g) if mentions_outside(g.stream(), ident, outer) => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// If `context` is a type parameter of the struct `body` which none
+/// of its fields mention, add a `_marker` field for it, returning a
+/// constructor which fills it in.
+fn add_marker(body: &mut syn::DeriveInput, context: &syn::Type) -> Option<pm2::TokenStream> {
+    let syn::Type::Path(syn::TypePath { qself: None, path }) = context else {
+        return None;
+    };
+    let param = path.get_ident()?;
+    if !body.generics.type_params().any(|p| p.ident == *param) {
+        return None;
+    }
+    let syn::Data::Struct(s) = &mut body.data else {
+        return None;
+    };
+    // Proxies to the type itself, as in `Proxy<Foo<C>>`, do not count
+    // as uses of the parameter.
+    if s.fields
+        .iter()
+        .any(|field| mentions_outside(field.ty.to_token_stream(), param, &body.ident))
+    {
+        return None;
+    }
+
+    let marker: syn::Type = syn::parse_quote! { ::core::marker::PhantomData<#param> };
+    let mut args = Vec::new();
+    let mut inits = Vec::new();
+    for (index, field) in s.fields.iter().enumerate() {
+        let cfgs = field.attrs.iter().filter(|a| a.path.is_ident("cfg"));
+        let ty = &field.ty;
+        match &field.ident {
+            Some(ident) => {
+                args.push(quote::quote! { #(#cfgs)* #ident: #ty });
+                inits.push(quote::quote! { #ident });
+            }
+            None => {
+                let arg = quote::format_ident!("arg{}", index);
+                args.push(quote::quote! { #(#cfgs)* #arg: #ty });
+                inits.push(quote::quote! { #arg });
+            }
+        }
+    }
+
+    let construct = match &mut s.fields {
+        syn::Fields::Named(fields) => {
+            fields.named.push(syn::Field {
+                attrs: Vec::new(),
+                vis: syn::Visibility::Inherited,
+                ident: Some(quote::format_ident!("_marker")),
+                colon_token: Some(Default::default()),
+                ty: marker,
+            });
+            let names = fields.named.iter().filter_map(|f| f.ident.as_ref());
+            let cfgs = fields.named.iter().map(|f| {
+                let cfgs = f.attrs.iter().filter(|a| a.path.is_ident("cfg"));
+                quote::quote! { #(#cfgs)* }
+            });
+            let values = inits.iter().cloned().chain(std::iter::once(
+                quote::quote! { ::core::marker::PhantomData },
+            ));
+            quote::quote! { Self { #( #cfgs #names: #values ),* } }
+        }
+        fields => {
+            let mut unnamed = match fields {
+                syn::Fields::Unnamed(fields) => fields.clone(),
+                _ => syn::parse_quote! { () },
+            };
+            unnamed.unnamed.push(syn::Field {
+                attrs: Vec::new(),
+                vis: syn::Visibility::Inherited,
+                ident: None,
+                colon_token: None,
+                ty: marker,
+            });
+            *fields = syn::Fields::Unnamed(unnamed);
+            quote::quote! { Self( #(#inits,)* ::core::marker::PhantomData ) }
+        }
+    };
+    if let syn::Fields::Unnamed(_) = s.fields {
+        s.semi_token.get_or_insert_with(Default::default);
+    }
+
+    let ident = &body.ident;
+    let vis = &body.vis;
+    let (generics, ty_generics, wc) = body.generics.split_for_impl();
+    Some(quote::quote! {
+        impl #generics #ident #ty_generics #wc {
+            /// Create a new value from its fields.
+            #[allow(clippy::too_many_arguments)]
+            #vis fn new(#(#args),*) -> Self {
+                #construct
+            }
+        }
+    })
+}
+
+/// Build the view of a struct: a struct with the same fields, but
+/// where proxies are resolved to references, and the method which
+/// creates it.
+fn view(body: &syn::DeriveInput, context: &syn::Type) -> syn::Result<pm2::TokenStream> {
+    let syn::Data::Struct(s) = &body.data else {
+        return Err(syn::Error::new_spanned(
+            &body.ident,
+            "views are only supported for structs",
+        ));
+    };
+
+    let ident = &body.ident;
+    let vis = &body.vis;
+    let view_ident = quote::format_ident!("{}View", ident);
+    let (generics, ty_generics, wc) = body.generics.split_for_impl();
+
+    let mut view_generics = body.generics.clone();
+    view_generics.params.insert(0, syn::parse_quote! { 'view });
+    let (view_generics_decl, view_ty_generics, _) = view_generics.split_for_impl();
+
+    let mut fields = Vec::new();
+    let mut values = Vec::new();
+    let mut targets = Vec::new();
+    for (index, field) in s.fields.iter().enumerate() {
+        let member = field
+            .ident
+            .clone()
+            .map(syn::Member::Named)
+            .unwrap_or_else(|| {
+                syn::Member::Unnamed(syn::Index {
+                    index: index as u32,
+                    span: pm2::Span::call_site(),
+                })
+            });
+        let ty = &field.ty;
+        let (view_ty, value) = if let Some(target) = wrapped_type(ty, "Proxy") {
+            targets.push(target);
+            (
+                quote::quote! { &'view #target },
+                quote::quote! { ::persian_rug::Accessor::get(access, &self.#member) },
+            )
+        } else if let Some(target) =
+            wrapped_type(ty, "Option").and_then(|ty| wrapped_type(ty, "Proxy"))
+        {
+            targets.push(target);
+            (
+                quote::quote! { ::core::option::Option<&'view #target> },
+                quote::quote! {
+                    self.#member.as_ref().map(|p| ::persian_rug::Accessor::get(access, p))
+                },
+            )
+        } else if let Some(target) =
+            wrapped_type(ty, "Vec").and_then(|ty| wrapped_type(ty, "Proxy"))
+        {
+            targets.push(target);
+            (
+                quote::quote! { ::std::vec::Vec<&'view #target> },
+                quote::quote! {
+                    self.#member.iter().map(|p| ::persian_rug::Accessor::get(access, p)).collect()
+                },
+            )
+        } else {
+            (
+                quote::quote! { &'view #ty },
+                quote::quote! { &self.#member },
+            )
+        };
+        let field_vis = &field.vis;
+        fields.push(match &field.ident {
+            Some(name) => quote::quote! { #field_vis #name: #view_ty },
+            None => quote::quote! { #field_vis #view_ty },
+        });
+        values.push(quote::quote! { #member: #value });
+    }
+
+    let decl = match &s.fields {
+        syn::Fields::Named(_) => quote::quote! {
+            #vis struct #view_ident #view_generics_decl #wc {
+                #(#fields),*
+            }
+        },
+        syn::Fields::Unnamed(_) => quote::quote! {
+            #vis struct #view_ident #view_generics_decl (
+                #(#fields),*
+            ) #wc;
+        },
+        syn::Fields::Unit => quote::quote! {
+            #vis struct #view_ident #view_generics_decl #wc {
+                _marker: ::core::marker::PhantomData<&'view #ident #ty_generics>,
+            }
+        },
+    };
+    let values = match &s.fields {
+        syn::Fields::Unit => quote::quote! { _marker: ::core::marker::PhantomData },
+        _ => quote::quote! { #(#values),* },
+    };
+
+    let struct_doc = format!(
+        "A view of [`{}`], with its proxies resolved to references.",
+        ident
+    );
+    let fn_doc = format!(
+        "Create a [`{}`] of this object, resolving its proxies through `access`.",
+        view_ident
+    );
+
+    Ok(quote::quote! {
+        #[doc = #struct_doc]
+        #decl
+
+        impl #generics #ident #ty_generics #wc {
+            #[doc = #fn_doc]
+            #vis fn view<'view, A>(&'view self, access: &'view A) -> #view_ident #view_ty_generics
+            where
+                A: ::persian_rug::Accessor<Context = #context>,
+                #(
+                    #context: ::persian_rug::Owner<#targets>,
+                    #targets: ::persian_rug::Contextual<Context = #context>,
+                )*
+            {
+                #view_ident {
+                    #values
+                }
+            }
+        }
+    })
+}
+
+/// Provide a implementation of `Contextual` for a type.
+///
+/// This is a very simple derive-style macro, that creates an
+/// impl for `Contextual` for the type it annotates. It takes
+/// one argument, which is the `Context` type that this
+/// type belongs to.
+///
+/// Example:
+/// ```rust
+/// use persian_rug::{contextual, Context};
+///
+/// #[contextual(C)]
+/// struct Foo<C: Context> {
+///    _marker: core::marker::PhantomData<C>
+/// }
+/// ```
+/// which is equivalent to the following:
+/// ```rust
+/// use persian_rug::{Context, Contextual};
+///
+/// struct Foo<C: Context> {
+///    _marker: core::marker::PhantomData<C>
+/// }
+///
+/// impl<C: Context> Contextual for Foo<C> {
+///    type Context = C;
+/// }
+/// ```
+///
+/// If the context is a type parameter which no field mentions, other
+/// than through proxies to the type itself, the `_marker` field is
+/// added automatically, along with a function `new` which takes the
+/// other fields in order, since the struct can no longer be written
+/// out in full:
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, Proxy};
+///
+/// #[contextual(C)]
+/// struct Node<C: Context> {
+///    value: i32,
+///    next: Option<Proxy<Node<C>>>,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Node<Rug>);
+///
+/// let mut r = Rug(Default::default());
+/// let tail = r.add(Node::new(2, None));
+/// let head = r.add(Node::new(1, Some(tail)));
+/// assert_eq!(r.get(&r.get(&head).next.unwrap()).value, 2);
+/// ```
+/// The `new` function has the same visibility as the struct. Structs
+/// which already mention the parameter are left as they are.
+///
+/// Fields of a struct may be marked with `#[search]`, in which case
+/// `Searchable` is also implemented for the type, with the marked
+/// fields as its searchable text. This requires the `search` feature
+/// of `persian-rug`. Similarly, fields marked with `#[link]` are
+/// used to implement `Links`, which lists the proxies the type holds,
+/// and `Relink`, which rewrites them.
+///
+/// `Contextual::schema` is implemented to describe the fields of the
+/// type, or of each of its variants, and the types of the objects
+/// their proxies refer to.
+///
+/// The type may have lifetime parameters, as in `Foo<'a, C>`, so that
+/// the objects of a context can borrow from data which outlives it.
+/// Such types cannot have `#[link]` fields, since links are only
+/// tracked between types which do not borrow.
+///
+/// If `view` is given after the context, as in
+/// `#[contextual(C, view)]`, a struct `FooView<'view>` is generated
+/// alongside `Foo`, together with a method `Foo::view` which creates
+/// one from an `Accessor`. Fields of type `Proxy<T>`,
+/// `Option<Proxy<T>>` and `Vec<Proxy<T>>` are resolved through the
+/// accessor, becoming `&T`, `Option<&T>` and `Vec<&T>` respectively,
+/// and all other fields are borrowed as they are:
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, Proxy};
+///
+/// #[contextual(Rug)]
+/// struct Author {
+///    name: String,
+/// }
+///
+/// #[contextual(Rug, view)]
+/// struct Book {
+///    title: String,
+///    author: Proxy<Author>,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Author, #[table] Book);
+///
+/// let mut r = Rug(Default::default(), Default::default());
+/// let author = r.add(Author { name: "Jane".to_string() });
+/// let book = r.add(Book { title: "Emma".to_string(), author });
+///
+/// let access = &r;
+/// let view = r.get(&book).view(&access);
+/// assert_eq!(view.author.name, "Jane");
+/// ```
+#[proc_macro_attribute]
+pub fn contextual(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut body: syn::DeriveInput = syn::parse_macro_input!(input);
+
+    if args.is_empty() {
+        return syn::Error::new(
+            pm2::Span::call_site(),
+            "You must specify the associated context when using contextual.",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let ContextualArgs {
+        context,
+        view: make_view,
+    } = syn::parse_macro_input!(args);
+
+    // Fields marked #[search] are the searchable text of the type,
+    // and those marked #[link] hold its links; the markers are
+    // removed since they are not real attributes.
+    let mut search_fields = Vec::new();
+    let mut link_fields = Vec::new();
+    if let syn::Data::Struct(s) = &mut body.data {
+        for (index, field) in s.fields.iter_mut().enumerate() {
+            let member = field
+                .ident
+                .clone()
+                .map(syn::Member::Named)
+                .unwrap_or_else(|| {
+                    syn::Member::Unnamed(syn::Index {
+                        index: index as u32,
+                        span: pm2::Span::call_site(),
+                    })
+                });
+            for (marker, found) in [("search", &mut search_fields), ("link", &mut link_fields)] {
+                let count = field.attrs.len();
+                field.attrs.retain(|attr| !attr.path.is_ident(marker));
+                if field.attrs.len() != count {
+                    found.push(member.clone());
+                }
+            }
+        }
+    }
+
+    let name = body.ident.to_string();
+    let (fields, variants) = match &body.data {
+        syn::Data::Struct(s) => (field_schemas(&s.fields), pm2::TokenStream::new()),
+        syn::Data::Enum(e) => {
+            let variants = e.variants.iter().map(|variant| {
+                let cfgs = variant.attrs.iter().filter(|a| a.path.is_ident("cfg"));
+                let name = variant.ident.to_string();
+                let fields = field_schemas(&variant.fields);
+                quote::quote! {
+                    #(#cfgs)*
+                    variants.push(::persian_rug::schema::VariantSchema {
+                        name: #name,
+                        fields: {
+                            let mut fields = ::std::vec::Vec::new();
+                            #fields
+                            fields
+                        },
+                    });
+                }
+            });
+            (pm2::TokenStream::new(), quote::quote! { #(#variants)* })
+        }
+        syn::Data::Union(_) => (pm2::TokenStream::new(), pm2::TokenStream::new()),
+    };
+
+    // The marker is left out of the schema, which describes the
+    // fields as they were written.
+    let constructor = add_marker(&mut body, &context);
+    let ident = &body.ident;
+    let (generics, ty_generics, wc) = body.generics.split_for_impl();
+
+    let mut res = quote::quote! {
+        #body
+
+        #constructor
+
+        impl #generics ::persian_rug::Contextual for #ident #ty_generics #wc {
+            type Context = #context;
+
+            #[allow(unused_mut)]
+            fn schema() -> ::persian_rug::schema::TypeSchema {
+                let mut fields = ::std::vec::Vec::new();
+                #fields
+                let mut variants = ::std::vec::Vec::new();
+                #variants
+                ::persian_rug::schema::TypeSchema {
+                    name: #name,
+                    type_name: ::std::any::type_name::<Self>(),
+                    fields,
+                    variants,
+                }
+            }
+        }
+    };
+
+    if !search_fields.is_empty() {
+        res.extend(quote::quote! {
+            impl #generics ::persian_rug::search::Searchable for #ident #ty_generics #wc {
+                fn search_text(&self, text: &mut dyn FnMut(&str)) {
+                    #(
+                        text(::core::convert::AsRef::<str>::as_ref(&self.#search_fields));
+                    )*
+                }
+            }
+        });
+    }
+
+    if !link_fields.is_empty() {
+        // Links are reported as `AnyProxy`, which needs a `TypeId`.
+        if let Some(lifetime) = body.generics.lifetimes().next() {
+            return syn::Error::new_spanned(
+                lifetime,
+                "#[link] is not supported on types with lifetime parameters",
+            )
+            .to_compile_error()
+            .into();
+        }
+        let link_names = link_fields.iter().map(|member| match member {
+            syn::Member::Named(ident) => ident.to_string(),
+            syn::Member::Unnamed(index) => index.index.to_string(),
+        });
+        res.extend(quote::quote! {
+            impl #generics ::persian_rug::Links for #ident #ty_generics #wc {
+                fn for_each_link(&self, f: &mut dyn FnMut(::persian_rug::AnyProxy)) {
+                    #(
+                        ::persian_rug::Links::for_each_link(&self.#link_fields, f);
+                    )*
+                }
+
+                fn for_each_field_link(&self, f: &mut dyn FnMut(&str, ::persian_rug::AnyProxy)) {
+                    #(
+                        ::persian_rug::Links::for_each_link(&self.#link_fields, &mut |p| f(#link_names, p));
+                    )*
+                }
+            }
+
+            impl #generics ::persian_rug::Relink for #ident #ty_generics #wc {
+                fn relink(&mut self, f: &mut dyn FnMut(::persian_rug::AnyProxy) -> ::persian_rug::AnyProxy) {
+                    #(
+                        ::persian_rug::Relink::relink(&mut self.#link_fields, f);
+                    )*
+                }
+            }
+        });
+    }
+
+    if make_view {
+        match view(&body, &context) {
+            Ok(tokens) => res.extend(tokens),
+            Err(e) => res.extend(e.to_compile_error()),
+        }
+    }
+
+    res.into()
+}
+
+/// Derive `Resolve` for a struct.
 ///
-/// impl<C: Context> Contextual for Foo<C> {
-///    type Context = C;
+/// This generates a struct `FooResolved` for a struct `Foo`, which
+/// has the same fields, and an implementation of
+/// `persian_rug::resolve::Resolve` which creates one. Fields marked
+/// `#[resolve]` are resolved through the context, and must implement
+/// `persian_rug::resolve::ResolveField`; all other fields are cloned.
+/// If `Foo` is generic, `FooResolved` needs a `_marker` field; this is
+/// added unless `Foo` already has one, in which case it is cloned.
+///
+/// Derives for `FooResolved` can be given with
+/// `#[resolve(derive(...))]` on `Foo`:
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, Context, Proxy, Resolve};
+///
+/// #[derive(Resolve)]
+/// #[resolve(derive(Debug, PartialEq))]
+/// #[contextual(Rug)]
+/// struct Leaf {
+///    value: u32,
+/// }
+///
+/// #[derive(Resolve)]
+/// #[contextual(Rug)]
+/// struct Branch {
+///    #[resolve]
+///    leaves: Vec<Proxy<Leaf>>,
 /// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Leaf, #[table] Branch);
+///
+/// let mut r = Rug(Default::default(), Default::default());
+/// let a = r.add(Leaf { value: 1 });
+/// let b = r.add(Leaf { value: 2 });
+/// let branch = r.add(Branch { leaves: vec![a, b] });
+///
+/// let resolved = r.get(&branch).resolve(&&r);
+/// assert_eq!(resolved.leaves[1].value(), Some(&LeafResolved { value: 2 }));
 /// ```
-#[proc_macro_attribute]
-pub fn contextual(args: TokenStream, input: TokenStream) -> TokenStream {
-    let body = pm2::TokenStream::from(input.clone());
+#[proc_macro_derive(Resolve, attributes(resolve))]
+pub fn derive_resolve(input: TokenStream) -> TokenStream {
+    let body: syn::DeriveInput = syn::parse_macro_input!(input);
+    match resolve(&body) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
 
-    let syn::DeriveInput {
-        ident, generics, ..
-    } = syn::parse_macro_input!(input);
+/// Check whether `tokens` mention any of `idents`.
+fn mentions(tokens: pm2::TokenStream, idents: &[&syn::Ident]) -> bool {
+    tokens.into_iter().any(|token| match token {
+        pm2::TokenTree::Ident(ident) => idents.iter().any(|id| **id == ident),
+        pm2::TokenTree::Group(group) => mentions(group.stream(), idents),
+        _ => false,
+    })
+}
 
-    if args.is_empty() {
-        return syn::Error::new(
-            pm2::Span::call_site(),
-            "You must specify the associated context when using contextual.",
-        )
-        .to_compile_error()
-        .into();
+/// Give every token in `tokens` the same span.
+fn respan(tokens: pm2::TokenStream, span: pm2::Span) -> pm2::TokenStream {
+    tokens
+        .into_iter()
+        .map(|mut token| {
+            if let pm2::TokenTree::Group(group) = &token {
+                let mut respanned =
+                    pm2::Group::new(group.delimiter(), respan(group.stream(), span));
+                respanned.set_span(span);
+                token = pm2::TokenTree::Group(respanned);
+            } else {
+                token.set_span(span);
+            }
+            token
+        })
+        .collect()
+}
+
+/// Statements adding a `FieldSchema` for each of `fields` to a vector
+/// named `fields`.
+fn field_schemas(fields: &syn::Fields) -> pm2::TokenStream {
+    let fields = fields.iter().enumerate().map(|(index, field)| {
+        let cfgs = field.attrs.iter().filter(|a| a.path.is_ident("cfg"));
+        let name = field
+            .ident
+            .as_ref()
+            .map(|ident| ident.to_string())
+            .unwrap_or_else(|| index.to_string());
+        let ty = type_name(&field.ty);
+        let mut targets = Vec::new();
+        proxy_targets(&field.ty, &mut targets);
+        quote::quote! {
+            #(#cfgs)*
+            fields.push(::persian_rug::schema::FieldSchema {
+                name: #name,
+                ty: #ty,
+                proxies: ::std::vec![#(::std::any::type_name::<#targets>()),*],
+            });
+        }
+    });
+    quote::quote! { #(#fields)* }
+}
+
+/// Find the types `T` of every `Proxy<T>` within `ty`.
+fn proxy_targets<'a>(ty: &'a syn::Type, targets: &mut Vec<&'a syn::Type>) {
+    match ty {
+        syn::Type::Path(path) => {
+            if let Some(target) = wrapped_type(ty, "Proxy") {
+                targets.push(target);
+                return;
+            }
+            for segment in path.path.segments.iter() {
+                if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
+                    for arg in args.args.iter() {
+                        if let syn::GenericArgument::Type(ty) = arg {
+                            proxy_targets(ty, targets);
+                        }
+                    }
+                }
+            }
+        }
+        syn::Type::Array(array) => proxy_targets(&array.elem, targets),
+        syn::Type::Tuple(tuple) => {
+            for elem in tuple.elems.iter() {
+                proxy_targets(elem, targets);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The bounds needed to resolve or absorb a field of type `ty` in
+/// the type `ident`, where its type involves the type's parameters.
+///
+/// Proxies to other types require those types to implement
+/// `item_trait`, and other fields must implement `field_trait`.
+fn field_bounds(
+    ident: &syn::Ident,
+    ty: &syn::Type,
+    context: &pm2::TokenStream,
+    params: &[&syn::Ident],
+    field_trait: pm2::TokenStream,
+    item_trait: pm2::TokenStream,
+) -> Vec<pm2::TokenStream> {
+    let mut bounds = Vec::new();
+    let mut targets = Vec::new();
+    proxy_targets(ty, &mut targets);
+    if targets.is_empty() {
+        bounds.push(quote::quote! { #ty: #field_trait });
+    }
+    for target in targets {
+        if !mentions(target.to_token_stream(), params) {
+            continue;
+        }
+        bounds.push(quote::quote! { #context: ::persian_rug::Owner<#target> });
+        bounds.push(quote::quote! { #target: 'static });
+        let recursive = match target {
+            syn::Type::Path(path) => path
+                .path
+                .segments
+                .last()
+                .map(|segment| segment.ident == *ident)
+                .unwrap_or(false),
+            _ => false,
+        };
+        if !recursive {
+            bounds.push(quote::quote! { #target: #item_trait });
+        }
     }
+    bounds
+}
 
-    let context: syn::Type = syn::parse_macro_input!(args);
+fn resolve(body: &syn::DeriveInput) -> syn::Result<pm2::TokenStream> {
+    let syn::Data::Struct(s) = &body.data else {
+        return Err(syn::Error::new_spanned(
+            &body.ident,
+            "Resolve can only be derived for structs",
+        ));
+    };
 
-    let (generics, ty_generics, wc) = generics.split_for_impl();
+    let mut derives = Vec::new();
+    for attr in body
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("resolve"))
+    {
+        attr.parse_args_with(|input: syn::parse::ParseStream| {
+            let option: syn::Ident = input.parse()?;
+            if option != "derive" {
+                return Err(syn::Error::new_spanned(
+                    option,
+                    "unsupported resolve option",
+                ));
+            }
+            let content;
+            syn::parenthesized!(content in input);
+            derives.extend(
+                syn::punctuated::Punctuated::<syn::Path, syn::Token![,]>::parse_terminated(
+                    &content,
+                )?,
+            );
+            Ok(())
+        })?;
+    }
 
-    let res = quote::quote! {
-        #body
+    let ident = &body.ident;
+    let vis = &body.vis;
+    let resolved_ident = quote::format_ident!("{}Resolved", ident);
+    let (generics, ty_generics, wc) = body.generics.split_for_impl();
+    let context = quote::quote! {
+        <#ident #ty_generics as ::persian_rug::Contextual>::Context
+    };
+    let params = body
+        .generics
+        .type_params()
+        .map(|param| &param.ident)
+        .collect::<Vec<_>>();
 
-        impl #generics ::persian_rug::Contextual for #ident #ty_generics #wc {
-            type Context = #context;
+    // Bounds are only needed for fields whose types involve the
+    // type's parameters; others are checked where they are used,
+    // which also allows types which refer to themselves.
+    let mut struct_bounds = Vec::new();
+    let mut impl_bounds = Vec::new();
+    let mut fields = Vec::new();
+    let mut values = Vec::new();
+    let mut has_marker = false;
+    for (index, field) in s.fields.iter().enumerate() {
+        let member = field
+            .ident
+            .clone()
+            .map(syn::Member::Named)
+            .unwrap_or_else(|| {
+                syn::Member::Unnamed(syn::Index {
+                    index: index as u32,
+                    span: pm2::Span::call_site(),
+                })
+            });
+        has_marker |= field
+            .ident
+            .as_ref()
+            .map(|id| id == "_marker")
+            .unwrap_or(false);
+        let ty = &field.ty;
+        let resolved = field.attrs.iter().any(|attr| attr.path.is_ident("resolve"));
+        let (resolved_ty, value) = if resolved {
+            if mentions(ty.to_token_stream(), &params) {
+                struct_bounds.push(quote::quote! {
+                    #ty: ::persian_rug::resolve::ResolveField<#context>
+                });
+                impl_bounds.extend(field_bounds(
+                    ident,
+                    ty,
+                    &context,
+                    &params,
+                    quote::quote! { ::persian_rug::resolve::ResolveField<#context> },
+                    quote::quote! { ::persian_rug::resolve::Resolve<Context = #context> },
+                ));
+            }
+            (
+                quote::quote! {
+                    <#ty as ::persian_rug::resolve::ResolveField<#context>>::Resolved
+                },
+                quote::quote! {
+                    ::persian_rug::resolve::ResolveField::resolve_field(&self.#member, access, resolver)
+                },
+            )
+        } else {
+            (
+                quote::quote! { #ty },
+                quote::quote! { ::core::clone::Clone::clone(&self.#member) },
+            )
+        };
+        let field_vis = &field.vis;
+        fields.push(match &field.ident {
+            Some(name) => quote::quote! { #field_vis #name: #resolved_ty },
+            None => quote::quote! { #field_vis #resolved_ty },
+        });
+        values.push(quote::quote! { #member: #value });
+    }
+
+    // Resolved fields are projections, which do not count as uses of
+    // the type's parameters, so a marker is added if the type does
+    // not already have one.
+    if !params.is_empty() && !has_marker {
+        let marker = quote::quote! { ::core::marker::PhantomData<fn() -> #ident #ty_generics> };
+        let member = match &s.fields {
+            syn::Fields::Named(_) => {
+                fields.push(quote::quote! { pub _marker: #marker });
+                syn::Member::Named(quote::format_ident!("_marker"))
+            }
+            _ => {
+                fields.push(quote::quote! { pub #marker });
+                syn::Member::Unnamed(syn::Index {
+                    index: s.fields.len() as u32,
+                    span: pm2::Span::call_site(),
+                })
+            }
+        };
+        values.push(quote::quote! { #member: ::core::marker::PhantomData });
+    }
+
+    let mut struct_wc = wc.cloned().unwrap_or_else(|| syn::parse_quote! { where });
+    let mut impl_wc = struct_wc.clone();
+    for bound in struct_bounds {
+        struct_wc.predicates.push(syn::parse_quote! { #bound });
+    }
+    for bound in impl_bounds {
+        impl_wc.predicates.push(syn::parse_quote! { #bound });
+    }
+
+    let decl = match &s.fields {
+        syn::Fields::Named(_) => quote::quote! {
+            #vis struct #resolved_ident #generics #struct_wc {
+                #(#fields),*
+            }
+        },
+        _ => quote::quote! {
+            #vis struct #resolved_ident #generics (
+                #(#fields),*
+            ) #struct_wc;
+        },
+    };
+
+    let doc = format!("The resolved form of [`{}`].", ident);
+
+    Ok(quote::quote! {
+        #[doc = #doc]
+        #[derive(#(#derives),*)]
+        #decl
+
+        impl #generics ::persian_rug::resolve::Resolve for #ident #ty_generics #impl_wc {
+            type Resolved = #resolved_ident #ty_generics;
+
+            fn resolve_with<A>(&self, access: &A, resolver: &mut ::persian_rug::resolve::Resolver) -> Self::Resolved
+            where
+                A: ::persian_rug::Accessor<Context = Self::Context>,
+            {
+                #resolved_ident {
+                    #(#values),*
+                }
+            }
         }
+    })
+}
+
+/// Derive `Absorb` for a struct.
+///
+/// This is the inverse of `Resolve`, which must also be derived: it
+/// implements `persian_rug::resolve::Absorb`, which adds a
+/// `FooResolved` to a context as a `Foo`, first adding the objects
+/// nested in the fields marked `#[resolve]`. Those fields must
+/// implement `persian_rug::resolve::AbsorbField`; all other fields
+/// are moved across.
+/// ```rust
+/// use persian_rug::resolve::Resolved;
+/// use persian_rug::{contextual, persian_rug, Absorb, Context, Proxy, Resolve};
+///
+/// #[derive(Absorb, Resolve)]
+/// #[contextual(Rug)]
+/// struct Leaf {
+///    value: u32,
+/// }
+///
+/// #[derive(Absorb, Resolve)]
+/// #[contextual(Rug)]
+/// struct Branch {
+///    #[resolve]
+///    leaf: Option<Proxy<Leaf>>,
+/// }
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Leaf, #[table] Branch);
+///
+/// let mut r = Rug(Default::default(), Default::default());
+/// let branch = Branch::absorb(
+///     BranchResolved { leaf: Some(Resolved::new(LeafResolved { value: 3 })) },
+///     &mut &mut r,
+/// );
+/// let leaf = r.get(&branch).leaf.unwrap();
+/// assert_eq!(r.get(&leaf).value, 3);
+/// ```
+#[proc_macro_derive(Absorb, attributes(resolve))]
+pub fn derive_absorb(input: TokenStream) -> TokenStream {
+    let body: syn::DeriveInput = syn::parse_macro_input!(input);
+    match absorb(&body) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn absorb(body: &syn::DeriveInput) -> syn::Result<pm2::TokenStream> {
+    let syn::Data::Struct(s) = &body.data else {
+        return Err(syn::Error::new_spanned(
+            &body.ident,
+            "Absorb can only be derived for structs",
+        ));
     };
 
-    res.into()
+    let ident = &body.ident;
+    let (generics, ty_generics, wc) = body.generics.split_for_impl();
+    let context = quote::quote! {
+        <#ident #ty_generics as ::persian_rug::Contextual>::Context
+    };
+    let params = body
+        .generics
+        .type_params()
+        .map(|param| &param.ident)
+        .collect::<Vec<_>>();
+
+    let mut bounds = Vec::new();
+    let mut values = Vec::new();
+    for (index, field) in s.fields.iter().enumerate() {
+        let member = field
+            .ident
+            .clone()
+            .map(syn::Member::Named)
+            .unwrap_or_else(|| {
+                syn::Member::Unnamed(syn::Index {
+                    index: index as u32,
+                    span: pm2::Span::call_site(),
+                })
+            });
+        let ty = &field.ty;
+        if field.attrs.iter().any(|attr| attr.path.is_ident("resolve")) {
+            if mentions(ty.to_token_stream(), &params) {
+                bounds.extend(field_bounds(
+                    ident,
+                    ty,
+                    &context,
+                    &params,
+                    quote::quote! { ::persian_rug::resolve::AbsorbField<#context> },
+                    quote::quote! { ::persian_rug::resolve::Absorb<Context = #context> },
+                ));
+            }
+            values.push(quote::quote! {
+                #member: <#ty as ::persian_rug::resolve::AbsorbField<#context>>::absorb_field(resolved.#member, mutator)
+            });
+        } else {
+            values.push(quote::quote! { #member: resolved.#member });
+        }
+    }
+
+    let mut impl_wc = wc.cloned().unwrap_or_else(|| syn::parse_quote! { where });
+    for bound in bounds {
+        impl_wc.predicates.push(syn::parse_quote! { #bound });
+    }
+
+    Ok(quote::quote! {
+        impl #generics ::persian_rug::resolve::Absorb for #ident #ty_generics #impl_wc {
+            fn absorb<M>(resolved: Self::Resolved, mutator: &mut M) -> ::persian_rug::Proxy<Self>
+            where
+                M: ::persian_rug::Mutator<Context = Self::Context>,
+                Self::Context: ::persian_rug::Owner<Self>,
+                Self: Sized,
+            {
+                let value = #ident {
+                    #(#values),*
+                };
+                ::persian_rug::Mutator::add(mutator, value)
+            }
+        }
+    })
+}
+
+/// Implement `Links` and `Relink` for a type, from the proxies held in
+/// its fields.
+///
+/// Every field whose type mentions a `Proxy`, such as `Proxy<T>`,
+/// `Option<Proxy<T>>` or `Vec<Proxy<T>>`, is taken to hold links, so
+/// there is no need to mark fields with `#[link]` as for the
+/// [`contextual`](macro@contextual) macro. Other fields which hold
+/// links, such as structs which themselves implement `Links`, can be
+/// included by marking them `#[links]`, and fields can be left out by
+/// marking them `#[links(skip)]`. Each included field must implement
+/// `Links` and `Relink`, as the common containers of proxies do.
+///
+/// This works for enums as well as structs, in which case the fields
+/// of whichever variant is present are visited. Links are named after
+/// their field, or its index for tuple fields.
+/// ```rust
+/// use persian_rug::{contextual, persian_rug, AnyProxy, Context, Links, Proxy};
+///
+/// #[contextual(Rug)]
+/// struct Var {
+///    name: String,
+/// }
+///
+/// #[derive(Links)]
+/// #[contextual(Rug)]
+/// enum Expr {
+///    Const(i64),
+///    Var(Proxy<Var>),
+///    Add { lhs: Proxy<Expr>, rhs: Proxy<Expr> },
+///    Sum(Vec<Proxy<Expr>>),
+/// }
+///
+/// impl persian_rug::Links for Var {}
+///
+/// #[persian_rug]
+/// struct Rug(#[table] Var, #[table] Expr);
+///
+/// let mut r = Rug(Default::default(), Default::default());
+/// let x = r.add(Var { name: "x".to_string() });
+/// let lhs = r.add(Expr::Var(x));
+/// let rhs = r.add(Expr::Const(1));
+/// let add = r.add(Expr::Add { lhs, rhs });
+///
+/// let mut links = Vec::new();
+/// r.get(&add).for_each_field_link(&mut |name, p| links.push((name.to_string(), p)));
+/// assert_eq!(
+///     links,
+///     vec![("lhs".to_string(), AnyProxy::from(lhs)), ("rhs".to_string(), AnyProxy::from(rhs))]
+/// );
+/// ```
+#[proc_macro_derive(Links, attributes(links))]
+pub fn derive_links(input: TokenStream) -> TokenStream {
+    let body: syn::DeriveInput = syn::parse_macro_input!(input);
+    match links(&body) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Whether a field holds links: either it is marked `#[links]`, or its
+/// type mentions a `Proxy` and it is not marked `#[links(skip)]`.
+fn is_link(field: &syn::Field) -> syn::Result<bool> {
+    let mut marked = false;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("links"))
+    {
+        if attr.tokens.is_empty() {
+            marked = true;
+            continue;
+        }
+        let option: syn::Ident = attr.parse_args()?;
+        if option != "skip" {
+            return Err(syn::Error::new_spanned(option, "expected `skip`"));
+        }
+        return Ok(false);
+    }
+    let mut targets = Vec::new();
+    proxy_targets(&field.ty, &mut targets);
+    Ok(marked || !targets.is_empty())
+}
+
+/// A linked field, with its binding and the name its links are
+/// reported under.
+type BoundField<'a> = (syn::Ident, String, &'a syn::Field);
+
+/// A pattern binding the linked fields of `fields`, and the linked
+/// fields themselves.
+fn link_pattern(
+    path: pm2::TokenStream,
+    fields: &syn::Fields,
+) -> syn::Result<(pm2::TokenStream, Vec<BoundField<'_>>)> {
+    let mut bound = Vec::new();
+    let mut patterns = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let binding = quote::format_ident!("__field_{}", index);
+        let name = field
+            .ident
+            .as_ref()
+            .map(|ident| ident.to_string())
+            .unwrap_or_else(|| index.to_string());
+        let cfgs = field.attrs.iter().filter(|a| a.path.is_ident("cfg"));
+        if is_link(field)? {
+            patterns.push(match &field.ident {
+                Some(ident) => quote::quote! { #(#cfgs)* #ident: #binding },
+                None => quote::quote! { #binding },
+            });
+            bound.push((binding, name, field));
+        } else if field.ident.is_none() {
+            patterns.push(quote::quote! { _ });
+        }
+    }
+    let pattern = match fields {
+        syn::Fields::Named(_) => quote::quote! { #path { #(#patterns,)* .. } },
+        syn::Fields::Unnamed(_) => quote::quote! { #path ( #(#patterns,)* .. ) },
+        syn::Fields::Unit => quote::quote! { #path },
+    };
+    Ok((pattern, bound))
+}
+
+fn links(body: &syn::DeriveInput) -> syn::Result<pm2::TokenStream> {
+    // Links are reported as `AnyProxy`, which needs a `TypeId`.
+    if let Some(lifetime) = body.generics.lifetimes().next() {
+        return Err(syn::Error::new_spanned(
+            lifetime,
+            "Links cannot be derived for types with lifetime parameters",
+        ));
+    }
+
+    let ident = &body.ident;
+    let variants = match &body.data {
+        syn::Data::Struct(s) => vec![(quote::quote! { Self }, &s.fields, Vec::new())],
+        syn::Data::Enum(e) => e
+            .variants
+            .iter()
+            .map(|variant| {
+                let name = &variant.ident;
+                let cfgs = variant
+                    .attrs
+                    .iter()
+                    .filter(|a| a.path.is_ident("cfg"))
+                    .collect::<Vec<_>>();
+                (quote::quote! { Self::#name }, &variant.fields, cfgs)
+            })
+            .collect(),
+        syn::Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "Links cannot be derived for unions",
+            ))
+        }
+    };
+
+    let mut types = Vec::new();
+    let mut for_each = Vec::new();
+    let mut for_each_field = Vec::new();
+    let mut relink = Vec::new();
+    for (path, fields, cfgs) in variants {
+        let (pattern, bound) = link_pattern(path, fields)?;
+        let bindings = bound
+            .iter()
+            .map(|(binding, _, _)| binding)
+            .collect::<Vec<_>>();
+        let names = bound.iter().map(|(_, name, _)| name);
+        let field_cfgs = bound
+            .iter()
+            .map(|(_, _, field)| {
+                let cfgs = field.attrs.iter().filter(|a| a.path.is_ident("cfg"));
+                quote::quote! { #(#cfgs)* }
+            })
+            .collect::<Vec<_>>();
+        types.extend(bound.iter().map(|(_, _, field)| &field.ty));
+        for_each.push(quote::quote! {
+            #(#cfgs)*
+            #pattern => {
+                #(
+                    #field_cfgs
+                    ::persian_rug::Links::for_each_link(#bindings, f);
+                )*
+            }
+        });
+        for_each_field.push(quote::quote! {
+            #(#cfgs)*
+            #pattern => {
+                #(
+                    #field_cfgs
+                    ::persian_rug::Links::for_each_link(#bindings, &mut |p| f(#names, p));
+                )*
+            }
+        });
+        relink.push(quote::quote! {
+            #(#cfgs)*
+            #pattern => {
+                #(
+                    #field_cfgs
+                    ::persian_rug::Relink::relink(#bindings, f);
+                )*
+            }
+        });
+    }
+
+    // Only generic types need to state that their fields hold links;
+    // for the rest, it is checked directly.
+    let (generics, ty_generics, wc) = body.generics.split_for_impl();
+    let mut links_wc = wc.cloned().unwrap_or_else(|| syn::parse_quote! { where });
+    let mut relink_wc = links_wc.clone();
+    if !body.generics.params.is_empty() {
+        for ty in types {
+            links_wc
+                .predicates
+                .push(syn::parse_quote! { #ty: ::persian_rug::Links });
+            relink_wc
+                .predicates
+                .push(syn::parse_quote! { #ty: ::persian_rug::Relink });
+        }
+    }
+
+    Ok(quote::quote! {
+        impl #generics ::persian_rug::Links for #ident #ty_generics #links_wc {
+            #[allow(unused_variables)]
+            fn for_each_link(&self, f: &mut dyn FnMut(::persian_rug::AnyProxy)) {
+                match self {
+                    #(#for_each)*
+                }
+            }
+
+            #[allow(unused_variables)]
+            fn for_each_field_link(&self, f: &mut dyn FnMut(&str, ::persian_rug::AnyProxy)) {
+                match self {
+                    #(#for_each_field)*
+                }
+            }
+        }
+
+        impl #generics ::persian_rug::Relink for #ident #ty_generics #relink_wc {
+            #[allow(unused_variables)]
+            fn relink(&mut self, f: &mut dyn FnMut(::persian_rug::AnyProxy) -> ::persian_rug::AnyProxy) {
+                match self {
+                    #(#relink)*
+                }
+            }
+        }
+    })
+}
+
+/// Implement `Transplant` for a type, so that its objects can be
+/// copied into another type of context.
+///
+/// For a type with a type parameter bounded by `Context`, such as
+/// `Foo<C: Context>`, this implements
+/// `persian_rug::transplant::Transplant<Foo<D>>` for `Foo<C>`, for any
+/// context `D`. The fields whose types mention the context are
+/// converted with `Transplant`, which is implemented for proxies and
+/// the common containers of them, so that a `Proxy<Bar<C>>` becomes a
+/// `Proxy<Bar<D>>`. `PhantomData` fields are recreated, and all other
+/// fields are cloned.
+///
+/// For any other type, this implements `Transplant<Self>`, so that
+/// objects can still be copied into a context of their own type. The
+/// fields which hold proxies are copied with `Transplant`, and all
+/// other fields are cloned.
+///
+/// This works for enums as well as structs.
+/// ```rust
+/// use std::marker::PhantomData;
+///
+/// use persian_rug::transplant::Transplant;
+/// use persian_rug::{contextual, persian_rug, AnyProxy, Context, Proxy, Transplant};
+///
+/// #[derive(Transplant)]
+/// #[contextual(C)]
+/// enum Shape<C: Context> {
+///    Circle(f64, PhantomData<C>),
+///    Group(Vec<Proxy<Shape<C>>>),
+/// }
+///
+/// #[persian_rug]
+/// struct Small(#[table] Shape<Small>);
+///
+/// #[persian_rug]
+/// struct Large(#[table] Shape<Large>);
+///
+/// let mut s = Small(Default::default());
+/// let circle = s.add(Shape::Circle(1.0, PhantomData));
+/// let group = s.add(Shape::Group(vec![circle]));
+///
+/// let copy: Shape<Large> = s.get(&group).transplant(&mut |p| {
+///     let p = p.downcast::<Shape<Small>>().unwrap();
+///     AnyProxy::from(Proxy::<Shape<Large>>::from_handle(p.handle() + 10))
+/// });
+/// let Shape::Group(members) = copy else { panic!() };
+/// assert_eq!(members[0].handle(), 10);
+/// ```
+#[proc_macro_derive(Transplant)]
+pub fn derive_transplant(input: TokenStream) -> TokenStream {
+    let body: syn::DeriveInput = syn::parse_macro_input!(input);
+    match transplant(&body) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Replace every mention of `from` in `tokens` with `to`.
+fn substitute(tokens: pm2::TokenStream, from: &syn::Ident, to: &syn::Ident) -> pm2::TokenStream {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            pm2::TokenTree::Ident(ident) if ident == *from => pm2::TokenTree::Ident(to.clone()),
+            pm2::TokenTree::Group(group) => {
+                let mut substituted =
+                    pm2::Group::new(group.delimiter(), substitute(group.stream(), from, to));
+                substituted.set_span(group.span());
+                pm2::TokenTree::Group(substituted)
+            }
+            token => token,
+        })
+        .collect()
+}
+
+/// The type parameter of `generics` which is bounded by `Context`,
+/// if there is one.
+fn context_param(generics: &syn::Generics) -> syn::Result<Option<&syn::Ident>> {
+    let is_context = |bound: &syn::TypeParamBound| match bound {
+        syn::TypeParamBound::Trait(bound) => bound
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident == "Context")
+            .unwrap_or(false),
+        _ => false,
+    };
+    let mut params = generics
+        .type_params()
+        .filter(|param| {
+            param.bounds.iter().any(is_context)
+                || generics
+                    .where_clause
+                    .iter()
+                    .flat_map(|wc| wc.predicates.iter())
+                    .any(|predicate| match predicate {
+                        syn::WherePredicate::Type(predicate) => {
+                            matches!(&predicate.bounded_ty, syn::Type::Path(ty) if ty.path.is_ident(&param.ident))
+                                && predicate.bounds.iter().any(is_context)
+                        }
+                        _ => false,
+                    })
+        })
+        .map(|param| &param.ident);
+    let res = params.next();
+    if let Some(other) = params.next() {
+        return Err(syn::Error::new_spanned(
+            other,
+            "Transplant needs at most one type parameter bounded by `Context`",
+        ));
+    }
+    Ok(res)
+}
+
+fn transplant(body: &syn::DeriveInput) -> syn::Result<pm2::TokenStream> {
+    let ident = &body.ident;
+    let variants = match &body.data {
+        syn::Data::Struct(s) => vec![(
+            quote::quote! { Self },
+            quote::quote! { #ident },
+            &s.fields,
+            Vec::new(),
+        )],
+        syn::Data::Enum(e) => e
+            .variants
+            .iter()
+            .map(|variant| {
+                let name = &variant.ident;
+                let cfgs = variant
+                    .attrs
+                    .iter()
+                    .filter(|a| a.path.is_ident("cfg"))
+                    .collect::<Vec<_>>();
+                (
+                    quote::quote! { Self::#name },
+                    quote::quote! { #ident::#name },
+                    &variant.fields,
+                    cfgs,
+                )
+            })
+            .collect(),
+        syn::Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "Transplant cannot be derived for unions",
+            ))
+        }
+    };
+
+    let context = context_param(&body.generics)?;
+    let target = quote::format_ident!("__Target");
+    let convert = |tokens: pm2::TokenStream| match context {
+        Some(context) => substitute(tokens, context, &target),
+        None => tokens,
+    };
+    let params = body
+        .generics
+        .type_params()
+        .map(|param| &param.ident)
+        .collect::<Vec<_>>();
+
+    let mut bounds = Vec::new();
+    let mut arms = Vec::new();
+    for (path, constructor, fields, cfgs) in variants {
+        let mut patterns = Vec::new();
+        let mut values = Vec::new();
+        for (index, field) in fields.iter().enumerate() {
+            let binding = quote::format_ident!("__field_{}", index);
+            let field_cfgs = field.attrs.iter().filter(|a| a.path.is_ident("cfg"));
+            let field_cfgs = quote::quote! { #(#field_cfgs)* };
+            let ty = &field.ty;
+            let generic = mentions(ty.to_token_stream(), &params);
+            let mut targets = Vec::new();
+            proxy_targets(ty, &mut targets);
+            let value = if wrapped_type(ty, "PhantomData").is_some() {
+                quote::quote! { ::core::marker::PhantomData }
+            } else if !targets.is_empty()
+                || context
+                    .map(|context| mentions(ty.to_token_stream(), &[context]))
+                    .unwrap_or(false)
+            {
+                let converted = convert(ty.to_token_stream());
+                if generic {
+                    bounds.push(quote::quote! {
+                        #ty: ::persian_rug::transplant::Transplant<#converted>
+                    });
+                }
+                quote::quote! {
+                    ::persian_rug::transplant::Transplant::<#converted>::transplant(#binding, f)
+                }
+            } else {
+                if generic {
+                    bounds.push(quote::quote! { #ty: ::core::clone::Clone });
+                }
+                quote::quote! { ::core::clone::Clone::clone(#binding) }
+            };
+            match &field.ident {
+                Some(name) => {
+                    patterns.push(quote::quote! { #field_cfgs #name: #binding });
+                    values.push(quote::quote! { #field_cfgs #name: #value });
+                }
+                None => {
+                    patterns.push(quote::quote! { #binding });
+                    values.push(value);
+                }
+            }
+        }
+        let (pattern, value) = match fields {
+            syn::Fields::Named(_) => (
+                quote::quote! { #path { #(#patterns),* } },
+                quote::quote! { #constructor { #(#values),* } },
+            ),
+            syn::Fields::Unnamed(_) => (
+                quote::quote! { #path ( #(#patterns),* ) },
+                quote::quote! { #constructor ( #(#values),* ) },
+            ),
+            syn::Fields::Unit => (quote::quote! { #path }, quote::quote! { #constructor }),
+        };
+        arms.push(quote::quote! {
+            #(#cfgs)*
+            #pattern => #value,
+        });
+    }
+
+    // The target context has the same bounds as the context of the
+    // type being copied.
+    let (_, ty_generics, _) = body.generics.split_for_impl();
+    let mut generics = body.generics.clone();
+    let output = match context {
+        Some(context) => {
+            let param = generics
+                .type_params()
+                .find(|param| param.ident == *context)
+                .unwrap();
+            let param_bounds = convert(param.bounds.to_token_stream());
+            generics.params.push(if param.bounds.is_empty() {
+                syn::parse_quote! { #target }
+            } else {
+                syn::parse_quote! { #target: #param_bounds }
+            });
+            let wc = generics.make_where_clause();
+            let predicates = wc
+                .predicates
+                .iter()
+                .filter(|predicate| mentions(predicate.to_token_stream(), &[context]))
+                .map(|predicate| convert(predicate.to_token_stream()))
+                .collect::<Vec<_>>();
+            for predicate in predicates {
+                wc.predicates.push(syn::parse_quote! { #predicate });
+            }
+            let converted = convert(ty_generics.to_token_stream());
+            quote::quote! { #ident #converted }
+        }
+        None => quote::quote! { #ident #ty_generics },
+    };
+    let wc = generics.make_where_clause();
+    for bound in bounds {
+        wc.predicates.push(syn::parse_quote! { #bound });
+    }
+    let (impl_generics, _, wc) = generics.split_for_impl();
+
+    Ok(quote::quote! {
+        impl #impl_generics ::persian_rug::transplant::Transplant<#output> for #ident #ty_generics #wc {
+            #[allow(unused_variables)]
+            fn transplant(&self, f: &mut dyn FnMut(::persian_rug::AnyProxy) -> ::persian_rug::AnyProxy) -> #output {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(args: pm2::TokenStream) -> String {
+        let input = quote::quote! {
+            struct Rug(#[table] Foo, #[table] Bar);
+        };
+        expand_persian_rug(args, input).to_string()
+    }
+
+    #[test]
+    fn test_checked_has_no_unwrap() {
+        let unchecked = expand(quote::quote! {});
+        assert!(unchecked.contains("unwrap"));
+        assert!(unchecked.contains("fn get ("));
+
+        let checked = expand(quote::quote! { checked });
+        assert!(checked.contains("try_get"));
+        assert!(!checked.contains("unwrap"));
+    }
 }
@@ -0,0 +1,102 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::collections::BTreeSet;
+
+use persian_rug::{algo, contextual, persian_rug, AnyProxy, Context, Proxy, WeakProxy};
+
+#[contextual(Rug)]
+struct Module {
+    name: &'static str,
+    #[link]
+    imports: Vec<Proxy<Module>>,
+    #[link]
+    symbols: Vec<Proxy<Symbol>>,
+    owner: Option<WeakProxy<Module>>,
+}
+
+#[contextual(Rug)]
+struct Symbol {
+    name: &'static str,
+}
+
+impl persian_rug::Links for Symbol {}
+
+#[persian_rug(gc)]
+struct Rug(#[table] Module, #[table] Symbol);
+
+fn module(name: &'static str, imports: Vec<Proxy<Module>>, symbols: Vec<Proxy<Symbol>>) -> Module {
+    Module {
+        name,
+        imports,
+        symbols,
+        owner: None,
+    }
+}
+
+fn set(proxies: impl IntoIterator<Item = AnyProxy>) -> BTreeSet<AnyProxy> {
+    proxies.into_iter().collect()
+}
+
+#[test]
+fn test_reachable() {
+    let mut r = Rug(Default::default(), Default::default());
+    let read = r.add(Symbol { name: "read" });
+    let write = r.add(Symbol { name: "write" });
+    let io = r.add(module("io", vec![], vec![read, write]));
+    let fmt = r.add(module("fmt", vec![], vec![]));
+    let app = r.add(module("app", vec![io], vec![]));
+    let mut tool = module("tool", vec![fmt], vec![write]);
+    tool.owner = Some(WeakProxy::from(app));
+    let tool = r.add(tool);
+
+    assert_eq!(
+        algo::reachable(&r, [AnyProxy::from(app)]),
+        set([app.into(), io.into(), read.into(), write.into()])
+    );
+    // Weak proxies are not followed.
+    assert_eq!(
+        algo::reachable(&r, [AnyProxy::from(tool)]),
+        set([tool.into(), fmt.into(), write.into()])
+    );
+    assert_eq!(
+        algo::reachable(&r, [AnyProxy::from(read), AnyProxy::from(fmt)]),
+        set([read.into(), fmt.into()])
+    );
+    assert_eq!(algo::reachable(&r, []), set([]));
+
+    // Nothing is deleted.
+    assert_eq!(persian_rug::Owner::<Module>::len(&r), 4);
+}
+
+#[test]
+fn test_cycles() {
+    let mut r = Rug(Default::default(), Default::default());
+    let a = r.add(module("a", vec![], vec![]));
+    let b = r.add(module("b", vec![a], vec![]));
+    r.get_mut(&a).imports.push(b);
+    let c = r.add(module("c", vec![a], vec![]));
+    r.get_mut(&c).imports.push(c);
+
+    assert_eq!(
+        algo::reachable(&r, [AnyProxy::from(a)]),
+        set([a.into(), b.into()])
+    );
+    assert_eq!(
+        algo::reachable(&r, [AnyProxy::from(c)]),
+        set([a.into(), b.into(), c.into()])
+    );
+}
+
+#[test]
+fn test_dangling() {
+    let mut r = Rug(Default::default(), Default::default());
+    let gone = r.add(Symbol { name: "gone" });
+    let m = r.add(module("m", vec![], vec![gone]));
+    r.delete(&gone);
+
+    assert_eq!(
+        algo::reachable(&r, [AnyProxy::from(m)]),
+        set([m.into(), gone.into()])
+    );
+}
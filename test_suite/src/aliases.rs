@@ -0,0 +1,82 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, Context, Edge, Proxy};
+
+#[contextual(State)]
+struct Foo {
+    a: i32,
+}
+
+#[contextual(State)]
+struct Bar {
+    foo: Proxy<Foo>,
+}
+
+#[contextual(State)]
+struct Baz {}
+
+#[persian_rug(aliases)]
+struct State(
+    #[table] Foo,
+    #[table] Bar,
+    #[table] Edge<Foo, Bar>,
+    #[table] Edge<Bar, Foo>,
+);
+
+mod generic {
+    use persian_rug::{contextual, persian_rug, Context};
+
+    #[contextual(C)]
+    pub struct Item<C: Context> {
+        pub _marker: core::marker::PhantomData<C>,
+        pub n: u32,
+    }
+
+    #[persian_rug(aliases)]
+    pub struct MyRug {
+        #[table]
+        items: Item<MyRug>,
+    }
+
+    impl MyRug {
+        pub fn new() -> Self {
+            Self {
+                items: Default::default(),
+            }
+        }
+    }
+}
+
+use generic::my_rug_aliases::{ItemProxy, MyRugAccessor};
+use state_aliases::{BarProxy, FooProxy, StateAccessor, StateMutator};
+
+fn make_bar(mutator: StateMutator<'_>, a: i32) -> BarProxy {
+    let foo = mutator.add(Foo { a });
+    mutator.add(Bar { foo })
+}
+
+fn foo_of(access: StateAccessor<'_>, bar: &BarProxy) -> FooProxy {
+    access.get(bar).foo
+}
+
+#[test]
+fn test_aliases() {
+    let mut s = State(
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+    let b = make_bar(&mut s, 3);
+    let f = foo_of(&s, &b);
+    assert_eq!(s.get(&f).a, 3);
+
+    let mut r = generic::MyRug::new();
+    let i: ItemProxy = r.add(generic::Item {
+        _marker: Default::default(),
+        n: 2,
+    });
+    let access: MyRugAccessor<'_> = &r;
+    assert_eq!(access.get(&i).n, 2);
+}
@@ -0,0 +1,152 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::archive::{ArchivedContext, ArchivedProxy};
+use persian_rug::rkyv::{self, rancor::Error, Archive, Deserialize, Serialize};
+use persian_rug::storage::ArenaStorage;
+use persian_rug::{contextual, persian_rug, Context, Proxy, Table};
+
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+#[rkyv(crate = persian_rug::rkyv, derive(Debug))]
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+    next: Option<Proxy<Foo>>,
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+#[rkyv(crate = persian_rug::rkyv)]
+#[contextual(Rug)]
+struct Bar {
+    name: String,
+    foos: Vec<Proxy<Foo>>,
+}
+
+#[persian_rug(rkyv)]
+struct Rug {
+    #[table]
+    foos: Foo,
+    #[table(arena)]
+    bars: Bar,
+}
+
+fn make_rug() -> (Rug, Vec<Proxy<Foo>>, Proxy<Bar>) {
+    let mut r = Rug {
+        foos: Default::default(),
+        bars: Default::default(),
+    };
+
+    let mut foos = Vec::new();
+    let mut prev = None;
+    for a in 0..10 {
+        let f = r.add(Foo { a, next: prev });
+        foos.push(f);
+        prev = Some(f);
+    }
+    let b = r.add(Bar {
+        name: "bar".to_string(),
+        foos: vec![foos[3], foos[7]],
+    });
+
+    (r, foos, b)
+}
+
+#[test]
+fn test_access() {
+    let (r, foos, b) = make_rug();
+
+    let bytes = rkyv::to_bytes::<Error>(&r).unwrap();
+    let archived = rkyv::access::<ArchivedRug, Error>(&bytes).unwrap();
+
+    for (a, f) in foos.iter().enumerate() {
+        assert_eq!(archived.get(f).a, a as i32);
+    }
+
+    let bar = archived.get(&b);
+    assert_eq!(bar.name, "bar");
+    let linked = bar
+        .foos
+        .iter()
+        .map(|p| archived.get(&p.proxy()).a.to_native())
+        .collect::<Vec<_>>();
+    assert_eq!(linked, vec![3, 7]);
+
+    // Follow the chain of links back to the start.
+    let mut count = 0;
+    let mut cur = Some(foos[9]);
+    while let Some(p) = cur {
+        count += 1;
+        cur = archived.get(&p).next.as_ref().map(ArchivedProxy::proxy);
+    }
+    assert_eq!(count, 10);
+
+    assert_eq!(archived.get_proxy_iter::<Foo>().collect::<Vec<_>>(), foos);
+    assert_eq!(
+        archived
+            .get_iter::<Foo>()
+            .map(|f| f.a.to_native())
+            .collect::<Vec<_>>(),
+        (0..10).collect::<Vec<_>>()
+    );
+    assert_eq!(archived.foos.len(), 10);
+    assert_eq!(archived.bars.len(), 1);
+}
+
+#[test]
+fn test_round_trip() {
+    let (r, foos, b) = make_rug();
+
+    let bytes = rkyv::to_bytes::<Error>(&r).unwrap();
+    let mut s = rkyv::from_bytes::<Rug, Error>(&bytes).unwrap();
+
+    for f in foos.iter() {
+        assert_eq!(s.get(f), r.get(f));
+    }
+    assert_eq!(s.get(&b), r.get(&b));
+
+    // New objects must not reuse existing handles.
+    let f = s.add(Foo { a: 10, next: None });
+    assert!(!foos.contains(&f));
+}
+
+#[test]
+fn test_table() {
+    let mut t = Table::<Foo, ArenaStorage<Foo>>::new();
+    let f1 = t.push(Foo { a: 1, next: None });
+    let f2 = t.push(Foo {
+        a: 2,
+        next: Some(f1),
+    });
+
+    let bytes = rkyv::to_bytes::<Error>(&t).unwrap();
+    let archived = rkyv::access::<rkyv::Archived<Table<Foo>>, Error>(&bytes).unwrap();
+    assert_eq!(archived.get(&f2).map(|f| f.a.to_native()), Some(2));
+    assert_eq!(archived.iter_proxies().collect::<Vec<_>>(), vec![f1, f2]);
+    assert_eq!(
+        format!("{:?}", archived),
+        "{0: ArchivedFoo { a: 1, next: None }, 1: ArchivedFoo { a: 2, next: Some(persian_rug::Proxy<test_suite::archive::Foo> { handle: 0 }) }}"
+    );
+
+    let u: Table<Foo> = rkyv::deserialize::<_, Error>(archived).unwrap();
+    assert_eq!(u.get(&f2), t.get(&f2));
+}
+
+#[test]
+fn test_invalid() {
+    let (r, _, _) = make_rug();
+    let bytes = rkyv::to_bytes::<Error>(&r).unwrap();
+    assert!(rkyv::access::<ArchivedRug, Error>(&bytes[..bytes.len() / 2]).is_err());
+}
+
+#[test]
+fn test_try_get() {
+    let (mut r, foos, _) = make_rug();
+    let extra = r.add(Foo { a: 10, next: None });
+    r.delete(&extra);
+
+    let bytes = rkyv::to_bytes::<Error>(&r).unwrap();
+    // Safety: the bytes were just written by rkyv.
+    let archived = unsafe { rkyv::access_unchecked::<ArchivedRug>(&bytes) };
+    assert_eq!(archived.try_get(&foos[4]).map(|f| f.a.to_native()), Some(4));
+    assert!(archived.try_get(&extra).is_none());
+}
@@ -0,0 +1,113 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::sync::Mutex;
+
+use persian_rug::batch::Op;
+use persian_rug::checked::Missing;
+use persian_rug::{contextual, persian_rug, AnyProxy, Context, Mutator, Proxy};
+
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[contextual(Rug)]
+struct Bar {
+    name: &'static str,
+}
+
+#[persian_rug]
+struct Rug(#[table] Foo, #[table] Bar);
+
+fn new_rug() -> Rug {
+    Rug(Default::default(), Default::default())
+}
+
+fn script(foo: &Proxy<Foo>) -> Vec<Op<Rug>> {
+    vec![
+        Op::add(Bar { name: "first" }),
+        Op::update(foo, |f: &mut Foo| f.a += 1),
+        Op::add(Foo { a: 10 }),
+        Op::set(foo, Foo { a: 5 }),
+        Op::update(foo, |f: &mut Foo| f.a *= 2),
+    ]
+}
+
+#[test]
+fn test_apply() {
+    let mut r = new_rug();
+    let foo = r.add(Foo { a: 1 });
+
+    let added = r.apply_batch(script(&foo)).unwrap();
+    assert_eq!(added.len(), 2);
+    let bar = added[0].downcast::<Bar>().unwrap();
+    let foo2 = added[1].downcast::<Foo>().unwrap();
+    assert_eq!(r.get(&bar).name, "first");
+    assert_eq!(r.get(&foo2).a, 10);
+    assert_eq!(r.get(&foo).a, 10);
+}
+
+#[test]
+fn test_mutator() {
+    let mut r = new_rug();
+    let foo = r.add(Foo { a: 1 });
+    let m = Mutex::new(r);
+
+    fn run<M>(mut m: M, ops: Vec<Op<Rug>>) -> Vec<AnyProxy>
+    where
+        M: Mutator<Context = Rug> + std::ops::DerefMut<Target = Rug>,
+    {
+        m.apply_batch(ops).unwrap()
+    }
+
+    run(m.lock().unwrap(), script(&foo));
+    let added = run(&mut *m.lock().unwrap(), vec![Op::add(Foo { a: 3 })]);
+    let r = m.into_inner().unwrap();
+    assert_eq!(r.get(&foo).a, 10);
+    assert_eq!(r.get(&added[0].downcast::<Foo>().unwrap()).a, 3);
+    assert_eq!(r.get_iter::<Foo>().count(), 3);
+}
+
+#[test]
+fn test_rejected() {
+    let mut r = new_rug();
+    let foo = r.add(Foo { a: 1 });
+    let missing = Proxy::<Bar>::from_handle(4);
+
+    let mut ops = script(&foo);
+    ops.push(Op::set(&missing, Bar { name: "lost" }));
+    let err = r.apply_batch(ops).unwrap_err();
+    assert_eq!(err.index(), 5);
+    assert_eq!(err.missing(), &Missing::new(&missing));
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "operation 5 of the batch: no {} is stored with handle 4",
+            std::any::type_name::<Bar>()
+        )
+    );
+
+    assert_eq!(r.get(&foo).a, 1);
+    assert_eq!(r.get_iter::<Foo>().count(), 1);
+    assert_eq!(r.get_iter::<Bar>().count(), 0);
+}
+
+#[test]
+fn test_describe() {
+    let mut r = new_rug();
+    let foo = r.add(Foo { a: 1 });
+    let ops = script(&foo);
+
+    assert_eq!(ops[0].type_name(), std::any::type_name::<Bar>());
+    assert_eq!(ops[0].target(), None);
+    assert_eq!(ops[1].target(), Some(AnyProxy::new(foo)));
+    assert_eq!(
+        format!("{:?}", ops[0]),
+        format!("Add({})", std::any::type_name::<Bar>())
+    );
+    assert_eq!(
+        format!("{:?}", ops[1]),
+        format!("Update({:?})", AnyProxy::new(foo))
+    );
+}
@@ -0,0 +1,128 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::borsh::{self, BorshDeserialize, BorshSerialize};
+use persian_rug::storage::ArenaStorage;
+use persian_rug::{contextual, persian_rug, Context, Proxy, Table};
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+#[borsh(crate = "persian_rug::borsh")]
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+    next: Option<Proxy<Foo>>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+#[borsh(crate = "persian_rug::borsh")]
+#[contextual(Rug)]
+struct Bar {
+    name: String,
+    foos: Vec<Proxy<Foo>>,
+}
+
+#[persian_rug(borsh)]
+struct Rug {
+    #[table]
+    foos: Foo,
+    #[table(arena)]
+    bars: Bar,
+}
+
+#[test]
+fn test_proxy() {
+    let mut t = Table::<Foo>::new();
+    t.push(Foo { a: 0, next: None });
+    let p = t.push(Foo { a: 1, next: None });
+
+    let bytes = borsh::to_vec(&p).unwrap();
+    assert_eq!(bytes, 1u64.to_le_bytes());
+    assert_eq!(Proxy::<Foo>::try_from_slice(&bytes).unwrap(), p);
+}
+
+#[test]
+fn test_table() {
+    let mut t = Table::<Foo, ArenaStorage<Foo>>::new();
+    let mut u = Table::<Foo>::new();
+    let f1 = t.push(Foo { a: 1, next: None });
+    u.push(Foo { a: 1, next: None });
+    let f2 = t.push(Foo {
+        a: 2,
+        next: Some(f1),
+    });
+    u.push(Foo {
+        a: 2,
+        next: Some(f1),
+    });
+
+    // The encoding does not depend on the storage.
+    let bytes = borsh::to_vec(&t).unwrap();
+    assert_eq!(bytes, borsh::to_vec(&u).unwrap());
+
+    let mut expected = Vec::new();
+    expected.extend(2u64.to_le_bytes());
+    expected.extend(2u32.to_le_bytes());
+    expected.extend(0u64.to_le_bytes());
+    expected.extend(1i32.to_le_bytes());
+    expected.push(0);
+    expected.extend(1u64.to_le_bytes());
+    expected.extend(2i32.to_le_bytes());
+    expected.push(1);
+    expected.extend(0u64.to_le_bytes());
+    assert_eq!(bytes, expected);
+
+    let v = Table::<Foo>::try_from_slice(&bytes).unwrap();
+    assert_eq!(v.get(&f1), t.get(&f1));
+    assert_eq!(v.get(&f2), t.get(&f2));
+    let mut v = v;
+    let f3 = v.push(Foo { a: 3, next: None });
+    assert_ne!(f3, f1);
+    assert_ne!(f3, f2);
+}
+
+#[test]
+fn test_invalid_table() {
+    // A handle beyond the next index.
+    let mut bytes = Vec::new();
+    bytes.extend(1u64.to_le_bytes());
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend(1u64.to_le_bytes());
+    bytes.extend(1i32.to_le_bytes());
+    bytes.push(0);
+    assert!(Table::<Foo>::try_from_slice(&bytes).is_err());
+
+    // Repeated handles.
+    let mut bytes = Vec::new();
+    bytes.extend(2u64.to_le_bytes());
+    bytes.extend(2u32.to_le_bytes());
+    for _ in 0..2 {
+        bytes.extend(0u64.to_le_bytes());
+        bytes.extend(1i32.to_le_bytes());
+        bytes.push(0);
+    }
+    assert!(Table::<Foo>::try_from_slice(&bytes).is_err());
+}
+
+#[test]
+fn test_context() {
+    let mut r = Rug {
+        foos: Default::default(),
+        bars: Default::default(),
+    };
+    let f1 = r.add(Foo { a: 1, next: None });
+    let f2 = r.add(Foo {
+        a: 2,
+        next: Some(f1),
+    });
+    let b = r.add(Bar {
+        name: "bar".to_string(),
+        foos: vec![f2, f1],
+    });
+
+    let bytes = borsh::to_vec(&r).unwrap();
+    let s = Rug::try_from_slice(&bytes).unwrap();
+    assert_eq!(s.get(&f1), r.get(&f1));
+    assert_eq!(s.get(&f2), r.get(&f2));
+    assert_eq!(s.get(&b), r.get(&b));
+    assert_eq!(borsh::to_vec(&s).unwrap(), bytes);
+}
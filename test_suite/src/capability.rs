@@ -0,0 +1,78 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::capability::{ReadCap, Restricted, RestrictedMut, WriteCap};
+use persian_rug::{contextual, persian_rug, Context, Proxy};
+
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[contextual(Rug)]
+struct Bar {
+    foo: Proxy<Foo>,
+    scale: i32,
+}
+
+#[persian_rug]
+struct Rug(#[table] Foo, #[table] Bar);
+
+fn total(r: Restricted<'_, Rug>, foos: ReadCap<Foo>) -> i32 {
+    r.get_iter(foos).map(|f| f.a).sum()
+}
+
+fn scale(mut r: RestrictedMut<'_, Rug>, bars: ReadCap<Bar>, foos: WriteCap<Foo>) {
+    let work = r
+        .get_proxy_iter(bars)
+        .map(|p| {
+            let bar = r.get(bars, p);
+            (bar.foo, bar.scale)
+        })
+        .collect::<Vec<_>>();
+    for (foo, scale) in work {
+        r.get_mut(foos, &foo).a *= scale;
+    }
+}
+
+#[test]
+fn test_capabilities() {
+    let mut r = Rug(Default::default(), Default::default());
+    let f1 = r.add(Foo { a: 1 });
+    let f2 = r.add(Foo { a: 2 });
+    r.add(Bar { foo: f1, scale: 3 });
+    r.add(Bar { foo: f2, scale: 5 });
+
+    let bars = ReadCap::grant(&r);
+    let foos = WriteCap::<Foo>::grant(&mut r);
+
+    assert_eq!(total(Restricted::new(&r), foos.read()), 3);
+
+    let mut view = RestrictedMut::new(&mut r);
+    scale(view.reborrow(), bars, foos);
+    assert_eq!(total(view.shared(), foos.into()), 13);
+
+    let f3 = view.add(foos, Foo { a: 7 });
+    for foo in view.get_iter_mut(foos) {
+        foo.a += 1;
+    }
+    assert_eq!(view.get(foos, &f3).a, 8);
+    assert_eq!(r.get(&f1).a, 4);
+    assert_eq!(r.get(&f2).a, 11);
+}
+
+#[test]
+fn test_capabilities_are_zero_sized() {
+    assert_eq!(std::mem::size_of::<ReadCap<Foo>>(), 0);
+    assert_eq!(std::mem::size_of::<WriteCap<Foo>>(), 0);
+    assert_eq!(
+        format!(
+            "{:?}",
+            ReadCap::<Foo>::from(WriteCap::grant(&mut Rug(
+                Default::default(),
+                Default::default()
+            )))
+        ),
+        format!("ReadCap<{}>", std::any::type_name::<Foo>())
+    );
+}
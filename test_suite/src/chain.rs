@@ -0,0 +1,99 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::chain::{Chain, Select};
+use persian_rug::{contextual, persian_rug, Accessor, Context, Proxy};
+
+#[contextual(Catalogue)]
+struct Product {
+    name: &'static str,
+    price: u32,
+}
+
+#[persian_rug]
+struct Catalogue(#[table] Product);
+
+#[contextual(Session)]
+struct Order {
+    product: Proxy<Product>,
+    quantity: u32,
+}
+
+#[persian_rug]
+struct Session(#[table] Order);
+
+#[contextual(Audit)]
+struct Note {
+    order: Proxy<Order>,
+    text: &'static str,
+}
+
+#[persian_rug]
+struct Audit(#[table] Note);
+
+fn total<A, W1, W2>(access: &A) -> u32
+where
+    A: Select<Order, W1> + Select<Product, W2>,
+{
+    Select::<Order, W1>::get_iter(access)
+        .map(|order| order.quantity * Select::<Product, W2>::get(access, &order.product).price)
+        .sum()
+}
+
+fn setup() -> (Catalogue, Session) {
+    let mut catalogue = Catalogue(Default::default());
+    let mut session = Session(Default::default());
+    let tea = catalogue.add(Product {
+        name: "tea",
+        price: 3,
+    });
+    let cake = catalogue.add(Product {
+        name: "cake",
+        price: 5,
+    });
+    session.add(Order {
+        product: tea,
+        quantity: 2,
+    });
+    session.add(Order {
+        product: cake,
+        quantity: 1,
+    });
+    (catalogue, session)
+}
+
+#[test]
+fn test_chain() {
+    let (catalogue, session) = setup();
+
+    let access = (&catalogue).chain(&session);
+    let names = access
+        .get_iter::<Order, _>()
+        .map(|order| access.get(&order.product).name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["tea", "cake"]);
+    assert_eq!(access.get_proxy_iter::<Product, _>().count(), 2);
+    assert_eq!(total(&access), 11);
+
+    // The order of the contexts does not matter.
+    let access = Chain::new(&session, &catalogue);
+    assert_eq!(total(&access), 11);
+    assert_eq!(access.first().get_iter::<Order>().count(), 2);
+}
+
+#[test]
+fn test_nested_chain() {
+    let (catalogue, session) = setup();
+    let mut audit = Audit(Default::default());
+    let order = *session.get_proxy_iter::<Order>().next().unwrap();
+    let note = audit.add(Note {
+        order,
+        text: "gift",
+    });
+
+    let access = Chain::new(&catalogue, (&session).chain(&audit));
+    let note = access.get(&note);
+    let order = access.get(&note.order);
+    assert_eq!(access.get(&order.product).name, "tea");
+    assert_eq!(total(&access), 11);
+}
@@ -0,0 +1,179 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+
+use persian_rug::checked::Missing;
+use persian_rug::{contextual, persian_rug, Accessor, Context, Mutator, Proxy};
+
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[contextual(Rug)]
+struct Bar {
+    name: &'static str,
+}
+
+#[persian_rug]
+struct Rug(#[table] Foo, #[table] Bar);
+
+fn new_rug() -> Rug {
+    Rug(Default::default(), Default::default())
+}
+
+#[test]
+fn test_context() {
+    let mut r = new_rug();
+    let foo = r.add(Foo { a: 1 });
+    let missing = Proxy::<Foo>::from_handle(5);
+
+    assert_eq!(r.try_get(&foo).map(|f| f.a), Ok(1));
+    r.try_get_mut(&foo).unwrap().a = 2;
+    assert_eq!(r.get(&foo).a, 2);
+
+    let err = r.try_get(&missing).err().unwrap();
+    assert_eq!(err, Missing::new(&missing));
+    assert_eq!(err.handle(), 5);
+    assert_eq!(err.type_name(), std::any::type_name::<Foo>());
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "no {} is stored with handle 5",
+            std::any::type_name::<Foo>()
+        )
+    );
+    assert!(r.try_get_mut(&missing).is_err());
+
+    // Handles are per table.
+    assert!(r.try_get(&Proxy::<Bar>::from_handle(0)).is_err());
+}
+
+fn read<A: Accessor<Context = Rug>>(access: A, p: &Proxy<Foo>) -> Result<i32, Missing> {
+    access.try_get(p).map(|f| f.a)
+}
+
+fn write<M: Mutator<Context = Rug>>(mut mutator: M, p: &Proxy<Foo>) -> Result<(), Missing> {
+    mutator.try_get_mut(p)?.a += 10;
+    Ok(())
+}
+
+#[test]
+fn test_accessors_and_mutators() {
+    let mut r = new_rug();
+    let foo = r.add(Foo { a: 1 });
+    let missing = Proxy::<Foo>::from_handle(1);
+
+    assert_eq!(write(&mut r, &foo), Ok(()));
+    assert_eq!(write(&mut r, &missing), Err(Missing::new(&missing)));
+    assert_eq!(read(&r, &foo), Ok(11));
+    assert_eq!(read(&r, &missing), Err(Missing::new(&missing)));
+
+    let shared = Arc::new(Mutex::new(r));
+    assert_eq!(write(shared.lock().unwrap(), &foo), Ok(()));
+    let r = Arc::new(Arc::try_unwrap(shared).ok().unwrap().into_inner().unwrap());
+    assert_eq!(read(r.clone(), &foo), Ok(21));
+    assert_eq!(read(r, &missing), Err(Missing::new(&missing)));
+}
+
+#[test]
+fn test_default_mutator() {
+    // The default implementations search the stored proxies.
+    struct Wrapper<'a>(&'a mut Rug);
+
+    impl Mutator for Wrapper<'_> {
+        type Context = Rug;
+
+        fn add<T>(&mut self, value: T) -> Proxy<T>
+        where
+            Rug: persian_rug::Owner<T>,
+            T: persian_rug::Contextual<Context = Rug>,
+        {
+            self.0.add(value)
+        }
+
+        fn get<T>(&self, what: &Proxy<T>) -> &T
+        where
+            Rug: persian_rug::Owner<T>,
+            T: persian_rug::Contextual<Context = Rug>,
+        {
+            self.0.get(what)
+        }
+
+        fn get_mut<T>(&mut self, what: &Proxy<T>) -> &mut T
+        where
+            Rug: persian_rug::Owner<T>,
+            T: persian_rug::Contextual<Context = Rug>,
+        {
+            self.0.get_mut(what)
+        }
+
+        fn get_iter<T>(&self) -> persian_rug::TableIterator<'_, T>
+        where
+            Rug: persian_rug::Owner<T>,
+            T: persian_rug::Contextual<Context = Rug>,
+        {
+            self.0.get_iter()
+        }
+
+        fn get_iter_mut<T>(&mut self) -> persian_rug::TableMutIterator<'_, T>
+        where
+            Rug: persian_rug::Owner<T>,
+            T: persian_rug::Contextual<Context = Rug>,
+        {
+            self.0.get_iter_mut()
+        }
+
+        fn get_proxy_iter<T>(&self) -> persian_rug::TableProxyIterator<'_, T>
+        where
+            Rug: persian_rug::Owner<T>,
+            T: persian_rug::Contextual<Context = Rug>,
+        {
+            self.0.get_proxy_iter()
+        }
+    }
+
+    let mut r = new_rug();
+    let foo = r.add(Foo { a: 1 });
+    let missing = Proxy::<Foo>::from_handle(3);
+
+    assert_eq!(write(Wrapper(&mut r), &foo), Ok(()));
+    assert_eq!(
+        write(Wrapper(&mut r), &missing),
+        Err(Missing::new(&missing))
+    );
+    assert_eq!(Wrapper(&mut r).try_get(&foo).map(|f| f.a), Ok(11));
+}
+
+#[contextual(CheckedRug)]
+struct Baz {
+    a: i32,
+}
+
+#[persian_rug(checked)]
+struct CheckedRug(#[table] Baz);
+
+#[test]
+fn test_checked_context() {
+    let mut r = CheckedRug(Default::default());
+    let baz = r.add(Baz { a: 1 });
+    r.get_mut(&baz).a = 2;
+    assert_eq!(r.get(&baz).a, 2);
+    assert_eq!(r.try_get(&baz).map(|b| b.a), Ok(2));
+    assert!(r.try_get(&Proxy::<Baz>::from_handle(4)).is_err());
+
+    // Only the try_ lookups are free of panics, including for proxies
+    // whose objects have been deleted.
+    assert!(r.delete(&baz));
+    assert_eq!(r.try_get(&baz).err(), Some(Missing::new(&baz)));
+    assert_eq!(r.try_get_mut(&baz).err(), Some(Missing::new(&baz)));
+    assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| r.get(&baz).a)).is_err());
+}
+
+#[test]
+#[should_panic(expected = "is stored with handle 4")]
+fn test_checked_context_missing() {
+    let r = CheckedRug(Default::default());
+    r.get(&Proxy::<Baz>::from_handle(4));
+}
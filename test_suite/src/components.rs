@@ -0,0 +1,120 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::components::Components;
+use persian_rug::{contextual, persian_rug, AnyProxy, Context, Links, Proxy};
+
+#[contextual(Rug)]
+struct Node {
+    #[link]
+    next: Option<Proxy<Node>>,
+}
+
+#[contextual(Rug)]
+struct Label {
+    #[link]
+    node: Proxy<Node>,
+    text: &'static str,
+}
+
+#[contextual(Rug)]
+struct Note {
+    text: &'static str,
+}
+
+impl Links for Note {}
+
+#[persian_rug]
+struct Rug(#[table] Node, #[table] Label, #[table] Note);
+
+fn make_rug() -> Rug {
+    Rug(Default::default(), Default::default(), Default::default())
+}
+
+#[test]
+fn test_components() {
+    let mut r = make_rug();
+    // 0 <- 1, 2 -> 3 <- 4, 5
+    let n0 = r.add(Node { next: None });
+    let n1 = r.add(Node { next: Some(n0) });
+    let n2 = r.add(Node { next: None });
+    let n3 = r.add(Node { next: None });
+    let n4 = r.add(Node { next: Some(n3) });
+    let n5 = r.add(Node { next: None });
+    r.get_mut(&n2).next = Some(n3);
+    let l0 = r.add(Label {
+        node: n5,
+        text: "five",
+    });
+    let l1 = r.add(Label {
+        node: n1,
+        text: "one",
+    });
+
+    let components = Components::builder(&r)
+        .table::<Node>()
+        .table::<Label>()
+        .build();
+    assert_eq!(components.len(), 3);
+    assert!(!components.is_empty());
+    let labels = [n0, n1, n2, n3, n4, n5]
+        .iter()
+        .map(|n| components.component_of(n).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(labels, vec![0, 0, 1, 1, 1, 2]);
+    assert_eq!(components.component_of(&l0), Some(2));
+    assert_eq!(components.component_of(&l1), Some(0));
+
+    assert_eq!(
+        components.members(0),
+        &[AnyProxy::new(n0), AnyProxy::new(n1), AnyProxy::new(l1)]
+    );
+    let nodes = components.proxies::<Node>(1);
+    assert_eq!(nodes.len(), 3);
+    assert!(nodes.contains(&n2) && nodes.contains(&n3) && nodes.contains(&n4));
+    assert_eq!(components.proxies::<Label>(1).len(), 0);
+    assert_eq!(
+        components.iter().map(|c| c.len()).collect::<Vec<_>>(),
+        vec![3, 3, 2]
+    );
+}
+
+#[test]
+fn test_components_partial() {
+    let mut r = make_rug();
+    let n0 = r.add(Node { next: None });
+    let n1 = r.add(Node { next: None });
+    r.add(Label {
+        node: n0,
+        text: "zero",
+    });
+    r.add(Label {
+        node: n1,
+        text: "one",
+    });
+    let note = r.add(Note { text: "alone" });
+
+    // Without the labels, nothing links the nodes together, and the
+    // links from the labels are not followed.
+    let components = Components::builder(&r)
+        .table::<Node>()
+        .table::<Note>()
+        .build();
+    assert_eq!(components.len(), 3);
+    assert_eq!(components.component_of(&note), Some(2));
+
+    let components = Components::builder(&r).build();
+    assert!(components.is_empty());
+    assert_eq!(components.component_of(&n0), None);
+}
+
+#[test]
+fn test_components_dangling() {
+    let mut r = make_rug();
+    let n0 = r.add(Node {
+        next: Some(Proxy::from_handle(7)),
+    });
+    let components = Components::builder(&r).table::<Node>().build();
+    assert_eq!(components.len(), 1);
+    assert_eq!(components.members(0), &[AnyProxy::new(n0)]);
+}
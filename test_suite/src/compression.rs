@@ -0,0 +1,80 @@
+#![cfg(test)]
+
+use std::io::{BufReader, ErrorKind, Read, Write};
+
+use persian_rug::compression::{Compression, Decoder, Encoder};
+
+fn payload() -> Vec<u8> {
+    (0..100_000u32)
+        .flat_map(|ix| format!("object {} links to {}\n", ix, ix / 7).into_bytes())
+        .collect()
+}
+
+fn round_trip(compression: Compression) -> usize {
+    let data = payload();
+
+    let mut enc = Encoder::new(Vec::new(), compression).unwrap();
+    for chunk in data.chunks(1000) {
+        enc.write_all(chunk).unwrap();
+    }
+    let compressed = enc.finish().unwrap();
+
+    // Read back through a small buffer, to check streaming works.
+    let mut dec = Decoder::new(BufReader::with_capacity(64, compressed.as_slice())).unwrap();
+    let detected = match compression {
+        Compression::Zstd(_) => Compression::zstd(),
+        c => c,
+    };
+    assert_eq!(dec.compression(), detected);
+    let mut out = Vec::new();
+    let mut buf = [0u8; 333];
+    loop {
+        let n = dec.read(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        out.extend_from_slice(&buf[..n]);
+    }
+    assert_eq!(out, data);
+
+    compressed.len()
+}
+
+#[test]
+fn test_none() {
+    assert_eq!(round_trip(Compression::None), payload().len());
+}
+
+#[test]
+fn test_zstd() {
+    assert!(round_trip(Compression::zstd()) < payload().len() / 4);
+    assert!(round_trip(Compression::Zstd(19)) < payload().len() / 4);
+}
+
+#[test]
+fn test_lz4() {
+    assert!(round_trip(Compression::Lz4) < payload().len() / 2);
+}
+
+#[test]
+fn test_short_streams() {
+    for data in [&b""[..], &b"ab"[..]] {
+        let mut dec = Decoder::new(data).unwrap();
+        assert_eq!(dec.compression(), Compression::None);
+        let mut out = Vec::new();
+        dec.read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+    }
+}
+
+#[test]
+fn test_corrupt() {
+    let mut enc = Encoder::new(Vec::new(), Compression::zstd()).unwrap();
+    enc.write_all(&payload()).unwrap();
+    let mut compressed = enc.finish().unwrap();
+    compressed.truncate(compressed.len() / 2);
+
+    let mut dec = Decoder::new(compressed.as_slice()).unwrap();
+    let err = dec.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+}
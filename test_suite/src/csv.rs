@@ -0,0 +1,131 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::csv::CsvExport;
+use persian_rug::{contextual, persian_rug, Context, Edge, Links, Proxy};
+
+#[contextual(Rug)]
+struct City {
+    name: String,
+}
+
+impl Links for City {}
+
+#[contextual(Rug)]
+struct Road {
+    #[link]
+    ends: [Proxy<City>; 2],
+    #[link]
+    via: Option<Proxy<City>>,
+}
+
+#[contextual(Rug)]
+struct Route(#[link] Vec<Proxy<Road>>);
+
+#[contextual(Rug)]
+struct Note {
+    text: String,
+}
+
+impl Links for Note {}
+
+#[persian_rug(csv)]
+struct Rug {
+    #[table]
+    cities: City,
+    #[table]
+    roads: Road,
+    #[table]
+    routes: Route,
+    #[table]
+    notes: Note,
+    #[table]
+    edges: Edge<City, Note>,
+}
+
+fn write(r: &Rug) -> (String, String) {
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    r.write_csv(&mut nodes, &mut edges).unwrap();
+    (
+        String::from_utf8(nodes).unwrap(),
+        String::from_utf8(edges).unwrap(),
+    )
+}
+
+#[test]
+fn test_csv() {
+    let mut r = Rug {
+        cities: Default::default(),
+        roads: Default::default(),
+        routes: Default::default(),
+        notes: Default::default(),
+        edges: Default::default(),
+    };
+    assert_eq!(
+        write(&r),
+        (
+            "type,handle\n".to_string(),
+            "type,handle,field,target_type,target_handle\n".to_string()
+        )
+    );
+
+    let a = r.add(City {
+        name: "a".to_string(),
+    });
+    let b = r.add(City {
+        name: "b".to_string(),
+    });
+    let c = r.add(City {
+        name: "c".to_string(),
+    });
+    let ab = r.add(Road {
+        ends: [a, b],
+        via: None,
+    });
+    let bc = r.add(Road {
+        ends: [b, c],
+        via: Some(a),
+    });
+    r.add(Route(vec![ab, bc]));
+    let n = r.add(Note {
+        text: "windy".to_string(),
+    });
+    r.add(Edge::new(c, n, ()));
+
+    let city = std::any::type_name::<City>();
+    let road = std::any::type_name::<Road>();
+    let route = std::any::type_name::<Route>();
+    let note = std::any::type_name::<Note>();
+    // The edge type name contains commas, so it is quoted.
+    let edge = format!("\"{}\"", std::any::type_name::<Edge<City, Note>>());
+    assert!(edge.contains(','));
+
+    let (nodes, edges) = write(&r);
+    assert_eq!(
+        nodes,
+        format!(
+            "type,handle\n\
+             {city},0\n{city},1\n{city},2\n\
+             {road},0\n{road},1\n\
+             {route},0\n\
+             {note},0\n\
+             {edge},0\n"
+        )
+    );
+    assert_eq!(
+        edges,
+        format!(
+            "type,handle,field,target_type,target_handle\n\
+             {road},0,ends,{city},0\n\
+             {road},0,ends,{city},1\n\
+             {road},1,ends,{city},1\n\
+             {road},1,ends,{city},2\n\
+             {road},1,via,{city},0\n\
+             {route},0,0,{road},0\n\
+             {route},0,0,{road},1\n\
+             {edge},0,from,{city},2\n\
+             {edge},0,to,{note},0\n"
+        )
+    );
+}
@@ -0,0 +1,123 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::handles::{Recycling, ShardPrefixed, TimeOrdered};
+use persian_rug::{contextual, persian_rug, Context, Cursor, Proxy, Table};
+
+#[contextual(Rug)]
+struct Foo {
+    a: u32,
+}
+
+#[persian_rug]
+struct Rug(#[table] Foo);
+
+#[test]
+fn test_cursor() {
+    let mut r = Rug(Default::default());
+    let mut cursor = Cursor::<Foo>::default();
+    assert!(cursor.at_end(&r));
+    assert_eq!(cursor.next(&r), None);
+
+    let ps = (0..5).map(|a| r.add(Foo { a })).collect::<Vec<_>>();
+    assert!(!cursor.at_end(&r));
+    assert_eq!(cursor.next(&r), Some(ps[0]));
+    let saved = cursor;
+    assert_eq!(cursor.next_batch(&r, 2), vec![ps[1], ps[2]]);
+    assert_ne!(cursor, saved);
+
+    r.add(Foo { a: 5 });
+    let mut seen = Vec::new();
+    while let Some(p) = cursor.next(&r) {
+        seen.push(r.get(&p).a);
+        if seen.len() == 1 {
+            r.add(Foo { a: 6 });
+        }
+    }
+    assert_eq!(seen, vec![3, 4, 5, 6]);
+    assert!(cursor.at_end(&r));
+    assert_eq!(cursor.next_batch(&r, 10), Vec::<Proxy<Foo>>::new());
+
+    let mut cursor = Cursor::after(&ps[3]);
+    assert_eq!(r.get(&cursor.next(&r).unwrap()).a, 4);
+    assert_eq!(
+        saved.clone().next_batch(&r, 100).len(),
+        6,
+        "a copied cursor moves independently"
+    );
+    assert!(format!("{:?}", saved).starts_with("Cursor<"));
+}
+
+#[test]
+fn test_contains() {
+    let mut r = Rug(Default::default());
+    let p = r.add(Foo { a: 0 });
+    assert!(persian_rug::Owner::contains(&r, &p));
+    let mut other = Rug(Default::default());
+    assert!(!persian_rug::Owner::contains(&other, &p));
+    other.add(Foo { a: 1 });
+    assert!(persian_rug::Owner::contains(&other, &p));
+}
+
+#[contextual(RecyclingRug)]
+struct Bar {
+    b: u32,
+}
+
+#[persian_rug(handles = Recycling)]
+struct RecyclingRug(#[table] Bar);
+
+#[test]
+fn test_reused_handle() {
+    let mut r = RecyclingRug(Default::default());
+    let a = r.add(Bar { b: 0 });
+    let b = r.add(Bar { b: 1 });
+    let c = r.add(Bar { b: 2 });
+    r.delete(&b);
+    let d = r.add(Bar { b: 3 });
+    assert_eq!(d.handle(), b.handle());
+    assert_eq!(d.generation(), 1);
+
+    let mut cursor = Cursor::<Bar>::new();
+    assert_eq!(cursor.next_batch(&r, 10), vec![a, d, c]);
+    assert_eq!(
+        persian_rug::Owner::<Bar>::proxy_for(&r, b.handle()),
+        Some(d)
+    );
+}
+
+#[contextual(ShardRug)]
+struct Baz {
+    c: u32,
+}
+
+#[persian_rug(handles = ShardPrefixed)]
+struct ShardRug(#[table] Baz);
+
+#[contextual(TimeRug)]
+struct Qux {
+    d: u32,
+}
+
+#[persian_rug(handles = TimeOrdered)]
+struct TimeRug(#[table] Qux);
+
+#[test]
+fn test_sparse_handles() {
+    // Handles in a high shard start far from zero, so the cursor must
+    // not walk the handle space one handle at a time.
+    let mut r = ShardRug(Table::with_handles(ShardPrefixed::new(u16::MAX)));
+    let ps = (0..4).map(|c| r.add(Baz { c })).collect::<Vec<_>>();
+    r.delete(&ps[2]);
+
+    let mut cursor = Cursor::<Baz>::new();
+    assert_eq!(cursor.next_batch(&r, 2), vec![ps[0], ps[1]]);
+    assert_eq!(cursor.next(&r), Some(ps[3]));
+    assert!(cursor.at_end(&r));
+
+    let mut r = TimeRug(Default::default());
+    let ps = (0..3).map(|d| r.add(Qux { d })).collect::<Vec<_>>();
+    let mut cursor = Cursor::<Qux>::new();
+    assert_eq!(cursor.next_batch(&r, 10), ps);
+    assert!(cursor.at_end(&r));
+}
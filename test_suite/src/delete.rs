@@ -0,0 +1,376 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::storage::ArenaStorage;
+use persian_rug::{
+    contextual, persian_rug, Context, Mutator, Proxy, ProxySet, SideTable, Table, Tags,
+};
+
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[contextual(Rug)]
+struct Bar {
+    b: i32,
+}
+
+mod private {
+    use super::*;
+
+    #[persian_rug]
+    pub struct Rug {
+        #[table]
+        foos: Foo,
+        #[table(arena)]
+        bars: Bar,
+    }
+
+    impl Rug {
+        pub fn new() -> Self {
+            Self {
+                foos: Default::default(),
+                bars: Default::default(),
+            }
+        }
+    }
+}
+
+use private::Rug;
+
+fn delete_with<M>(mut m: M, p: &Proxy<Bar>) -> bool
+where
+    M: Mutator<Context = Rug> + std::ops::DerefMut<Target = Rug>,
+{
+    m.delete(p)
+}
+
+#[test]
+fn test_delete() {
+    let mut r = Rug::new();
+    let f1 = r.add(Foo { a: 1 });
+    let f2 = r.add(Foo { a: 2 });
+    let bars = (0..5).map(|b| r.add(Bar { b })).collect::<Vec<_>>();
+
+    assert!(r.delete(&f1));
+    assert!(!r.delete(&f1));
+    assert!(r.try_get(&f1).is_err());
+    assert_eq!(r.get_proxy_iter::<Foo>().collect::<Vec<_>>(), vec![&f2]);
+    assert_eq!(persian_rug::Owner::<Foo>::len(&r), 1);
+
+    // Arena storage moves the last object into the gap.
+    assert!(delete_with(&mut r, &bars[1]));
+    assert_eq!(
+        r.get_iter::<Bar>().map(|bar| bar.b).collect::<Vec<_>>(),
+        vec![0, 4, 2, 3]
+    );
+    assert_eq!(r.get(&bars[4]).b, 4);
+    assert!(delete_with(&mut r, &bars[4]));
+    assert_eq!(
+        r.get_iter::<Bar>().map(|bar| bar.b).collect::<Vec<_>>(),
+        vec![0, 3, 2]
+    );
+
+    // Handles are not reused by the default allocator.
+    assert_eq!(r.add(Foo { a: 3 }).handle(), 2);
+}
+
+#[test]
+fn test_stale_proxy() {
+    let mut table = Table::<i32>::new();
+    let first = table.push(1);
+    assert_eq!(first.generation(), 0);
+    assert!(table.delete(&first));
+
+    let second = table.insert_with_handle(first.handle(), 2).unwrap();
+    assert_eq!(second.handle(), first.handle());
+    assert_eq!(second.generation(), 1);
+    assert_ne!(first, second);
+    assert_eq!(table.get(&first), None);
+    assert_eq!(table.get(&second), Some(&2));
+    assert!(!table.contains(&first));
+    assert!(!table.swap(&first, &second));
+    assert!(!table.mark_deleted(&first));
+    assert!(!table.delete(&first));
+    assert_eq!(table.iter_proxies().collect::<Vec<_>>(), vec![&second]);
+
+    assert!(table.mark_deleted(&second));
+    assert_eq!(table.deleted_proxies().collect::<Vec<_>>(), vec![second]);
+    assert!(table.delete(&second));
+    assert_eq!(table.deleted_proxies().count(), 0);
+    assert_eq!(
+        format!("{:?}", table.insert_with_handle(first.handle(), 3).unwrap()),
+        "persian_rug::Proxy<i32> { handle: 0, generation: 2 }"
+    );
+}
+
+#[test]
+fn test_remove_then_reinsert() {
+    let mut table = Table::<i32>::new();
+    let first = table.push(1);
+    assert_eq!(table.remove(&first), Some(1));
+
+    let again = table.insert_with_handle(first.handle(), 2).unwrap();
+    assert_eq!(again.handle(), first.handle());
+    assert_eq!(again.generation(), 1);
+    assert_eq!(table.get(&first), None);
+    assert_eq!(table.get(&again), Some(&2));
+}
+
+#[test]
+fn test_stale_proxy_in_context() {
+    let mut r = Rug::new();
+    let foo = r.add(Foo { a: 1 });
+    let bar = r.add(Bar { b: 1 });
+    assert_eq!(r.remove(&foo).map(|foo| foo.a), Some(1));
+    assert_eq!(r.remove(&bar).map(|bar| bar.b), Some(1));
+
+    let new_foo =
+        persian_rug::Owner::insert_with_handle(&mut r, foo.handle(), Foo { a: 2 }).unwrap();
+    let new_bar =
+        persian_rug::Owner::insert_with_handle(&mut r, bar.handle(), Bar { b: 2 }).unwrap();
+    assert_eq!(new_foo.handle(), foo.handle());
+    assert_eq!(new_bar.handle(), bar.handle());
+    assert!(r.try_get(&foo).is_err());
+    assert!(r.try_get(&bar).is_err());
+    assert_eq!(r.get(&new_foo).a, 2);
+    assert_eq!(r.get(&new_bar).b, 2);
+}
+
+#[test]
+fn test_stale_side_data() {
+    let mut table = Table::<i32>::new();
+    let first = table.push(1);
+
+    let mut set = ProxySet::new();
+    set.insert(first);
+    let mut names = SideTable::new();
+    names.insert(first, "first");
+    let mut tags = Tags::<&str>::new();
+    tags.tag(first, "old");
+
+    assert!(table.delete(&first));
+    let second = table.insert_with_handle(first.handle(), 2).unwrap();
+
+    // Data attached to the deleted object is not seen through a
+    // proxy for the object that took its handle.
+    assert!(set.contains(&first));
+    assert!(!set.contains(&second));
+    assert_eq!(set.iter().collect::<Vec<_>>(), vec![first]);
+    assert_eq!(names.get(&second), None);
+    assert_eq!(names.get(&first), Some(&"first"));
+    assert!(!tags.has_tag(&second, "old"));
+    assert_eq!(
+        tags.iter_tagged::<i32>("old").collect::<Vec<_>>(),
+        vec![first]
+    );
+
+    set.insert(second);
+    assert!(!set.contains(&first));
+    assert_eq!(set.iter().collect::<Vec<_>>(), vec![second]);
+}
+
+#[test]
+fn test_arena_delete_blocks() {
+    let mut table = Table::<u64, ArenaStorage<u64>>::default();
+    let proxies = (0..5000).map(|i| table.push(i)).collect::<Vec<_>>();
+    for p in proxies.iter().rev().step_by(2) {
+        assert!(table.delete(p));
+    }
+    assert_eq!(table.len(), 2500);
+    for p in proxies.iter().rev().skip(1).step_by(2) {
+        assert_eq!(table.get(p), Some(&p.handle()));
+    }
+    for p in proxies.iter().step_by(2) {
+        assert!(table.delete(p));
+    }
+    assert!(table.is_empty());
+    let p = table.push(7);
+    assert_eq!(table.iter().collect::<Vec<_>>(), vec![&7]);
+    assert_eq!(table.get(&p), Some(&7));
+}
+
+#[test]
+fn test_remove() {
+    let mut r = Rug::new();
+    let f1 = r.add(Foo { a: 1 });
+    let f2 = r.add(Foo { a: 2 });
+    let b = r.add(Bar { b: 3 });
+
+    let foo = r.remove(&f1).unwrap();
+    assert_eq!(foo.a, 1);
+    assert!(r.remove(&f1).is_none());
+    assert!(r.try_get(&f1).is_err());
+    assert_eq!(r.get(&f2).a, 2);
+
+    let mut m = &mut r;
+    assert_eq!(Mutator::remove(&mut m, &b).map(|bar| bar.b), Some(3));
+    assert!(Mutator::remove(&mut m, &b).is_none());
+
+    let mut table = Table::<Foo>::new();
+    let moved = table.push(foo);
+    assert!(table.mark_deleted(&moved));
+    assert_eq!(table.remove(&moved).map(|foo| foo.a), Some(1));
+    assert!(!table.is_deleted(&moved));
+    assert_eq!(table.deleted_proxies().count(), 0);
+}
+
+#[test]
+fn test_retain() {
+    let mut r = Rug::new();
+    let foos = (0..6).map(|a| r.add(Foo { a })).collect::<Vec<_>>();
+    let bars = (0..6).map(|b| r.add(Bar { b })).collect::<Vec<_>>();
+
+    r.retain::<Foo, _>(|p, foo| {
+        foo.a += 10;
+        p.handle() % 2 == 0
+    });
+    assert_eq!(
+        r.get_iter::<Foo>().map(|foo| foo.a).collect::<Vec<_>>(),
+        vec![10, 12, 14]
+    );
+    assert!(r.try_get(&foos[1]).is_err());
+
+    // Arena storage moves later items into the gaps.
+    let mut m = &mut r;
+    Mutator::retain::<Bar, _>(&mut m, |_, bar| bar.b % 3 != 1);
+    assert_eq!(
+        r.get_iter::<Bar>().map(|bar| bar.b).collect::<Vec<_>>(),
+        vec![0, 5, 2, 3]
+    );
+    assert!(r.try_get(&bars[4]).is_err());
+
+    let mut table = Table::<i32>::new();
+    let kept = table.push(1);
+    table.push(2);
+    table.mark_deleted(&kept);
+    table.retain(|_, _| false);
+    assert_eq!(table.include_deleted().iter().collect::<Vec<_>>(), vec![&1]);
+}
+
+#[test]
+fn test_drain() {
+    let mut r = Rug::new();
+    let foos = (0..3).map(|a| r.add(Foo { a })).collect::<Vec<_>>();
+    let bars = (0..3).map(|b| r.add(Bar { b })).collect::<Vec<_>>();
+
+    let drained = r.drain::<Foo>();
+    assert_eq!(
+        drained
+            .iter()
+            .map(|(p, foo)| (*p, foo.a))
+            .collect::<Vec<_>>(),
+        vec![(foos[0], 0), (foos[1], 1), (foos[2], 2)]
+    );
+    assert_eq!(persian_rug::Owner::<Foo>::len(&r), 0);
+    assert!(r.try_get(&foos[0]).is_err());
+
+    let mut m = &mut r;
+    let drained = Mutator::drain::<Bar>(&mut m);
+    assert_eq!(
+        drained.into_iter().map(|(p, _)| p).collect::<Vec<_>>(),
+        bars
+    );
+    assert_eq!(r.get_iter::<Bar>().count(), 0);
+
+    let mut table = Table::<i32>::new();
+    let dropped = table.push(1);
+    let kept = table.push(2);
+    table.mark_deleted(&dropped);
+    assert_eq!(table.drain(), vec![(kept, 2)]);
+    assert!(table.is_empty());
+    assert_eq!(table.deleted_proxies().count(), 0);
+    assert_eq!(table.push(3).handle(), 2);
+}
+
+#[contextual(HandRug)]
+struct Baz {
+    c: i32,
+}
+
+// An owner written against the methods `Owner` required before
+// removal was added.
+struct HandRug(Table<Baz>);
+
+impl Context for HandRug {
+    fn add<T>(&mut self, value: T) -> Proxy<T>
+    where
+        Self: persian_rug::Owner<T>,
+        T: persian_rug::Contextual<Context = Self>,
+    {
+        persian_rug::Owner::add(self, value)
+    }
+
+    fn get<T>(&self, what: &Proxy<T>) -> &T
+    where
+        Self: persian_rug::Owner<T>,
+        T: persian_rug::Contextual<Context = Self>,
+    {
+        persian_rug::Owner::get(self, what)
+    }
+
+    fn get_mut<T>(&mut self, what: &Proxy<T>) -> &mut T
+    where
+        Self: persian_rug::Owner<T>,
+        T: persian_rug::Contextual<Context = Self>,
+    {
+        persian_rug::Owner::get_mut(self, what)
+    }
+
+    fn get_iter<T>(&self) -> persian_rug::TableIterator<'_, T>
+    where
+        Self: persian_rug::Owner<T>,
+        T: persian_rug::Contextual<Context = Self>,
+    {
+        persian_rug::Owner::get_iter(self)
+    }
+
+    fn get_iter_mut<T>(&mut self) -> persian_rug::TableMutIterator<'_, T>
+    where
+        Self: persian_rug::Owner<T>,
+        T: persian_rug::Contextual<Context = Self>,
+    {
+        persian_rug::Owner::get_iter_mut(self)
+    }
+
+    fn get_proxy_iter<T>(&self) -> persian_rug::TableProxyIterator<'_, T>
+    where
+        Self: persian_rug::Owner<T>,
+        T: persian_rug::Contextual<Context = Self>,
+    {
+        persian_rug::Owner::get_proxy_iter(self)
+    }
+}
+
+impl persian_rug::Owner<Baz> for HandRug {
+    fn add(&mut self, value: Baz) -> Proxy<Baz> {
+        self.0.push(value)
+    }
+    fn get(&self, proxy: &Proxy<Baz>) -> &Baz {
+        self.0.get(proxy).unwrap()
+    }
+    fn get_mut(&mut self, proxy: &Proxy<Baz>) -> &mut Baz {
+        self.0.get_mut(proxy).unwrap()
+    }
+    fn get_iter(&self) -> persian_rug::TableIterator<'_, Baz> {
+        self.0.iter()
+    }
+    fn get_iter_mut(&mut self) -> persian_rug::TableMutIterator<'_, Baz> {
+        self.0.iter_mut()
+    }
+    fn get_proxy_iter(&self) -> persian_rug::TableProxyIterator<'_, Baz> {
+        self.0.iter_proxies()
+    }
+}
+
+#[test]
+fn test_default_remove() {
+    let mut r = HandRug(Table::new());
+    let baz = r.add(Baz { c: 1 });
+
+    assert!(r.remove(&baz).is_none());
+    assert!(!r.delete(&baz));
+    assert!(r.drain::<Baz>().is_empty());
+    assert_eq!(r.get(&baz).c, 1);
+}
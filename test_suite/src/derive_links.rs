@@ -0,0 +1,157 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, AnyProxy, Context, Links, Proxy, Relink};
+
+#[contextual(Rug)]
+struct Leaf {
+    value: i32,
+}
+
+impl persian_rug::Links for Leaf {}
+
+#[derive(Links)]
+struct Pair {
+    first: Proxy<Leaf>,
+    second: Option<Proxy<Leaf>>,
+}
+
+#[derive(Links)]
+#[contextual(Rug)]
+struct Node {
+    name: &'static str,
+    parent: Option<Proxy<Node>>,
+    leaves: Vec<Proxy<Leaf>>,
+    #[links]
+    pair: Pair,
+    #[links(skip)]
+    hint: Option<Proxy<Leaf>>,
+}
+
+#[derive(Links)]
+#[contextual(Rug)]
+enum Shape {
+    Empty,
+    Point(i32, Proxy<Leaf>),
+    Group {
+        name: &'static str,
+        members: Vec<Proxy<Shape>>,
+    },
+}
+
+#[derive(Links)]
+struct Wrapper<T> {
+    inner: Vec<Proxy<T>>,
+}
+
+#[persian_rug]
+struct Rug(#[table] Leaf, #[table] Node, #[table] Shape);
+
+fn field_links<L: Links>(value: &L) -> Vec<(String, AnyProxy)> {
+    let mut res = Vec::new();
+    value.for_each_field_link(&mut |name, p| res.push((name.to_string(), p)));
+    res
+}
+
+#[test]
+fn test_struct() {
+    let mut r = Rug(Default::default(), Default::default(), Default::default());
+    let leaves = (0..4)
+        .map(|value| r.add(Leaf { value }))
+        .collect::<Vec<_>>();
+    let root = r.add(Node {
+        name: "root",
+        parent: None,
+        leaves: vec![leaves[0]],
+        pair: Pair {
+            first: leaves[1],
+            second: None,
+        },
+        hint: Some(leaves[3]),
+    });
+    let child = r.add(Node {
+        name: "child",
+        parent: Some(root),
+        leaves: vec![leaves[1], leaves[2]],
+        pair: Pair {
+            first: leaves[2],
+            second: Some(leaves[3]),
+        },
+        hint: Some(leaves[0]),
+    });
+
+    assert_eq!(
+        field_links(r.get(&child)),
+        vec![
+            ("parent".to_string(), AnyProxy::from(root)),
+            ("leaves".to_string(), AnyProxy::from(leaves[1])),
+            ("leaves".to_string(), AnyProxy::from(leaves[2])),
+            ("pair".to_string(), AnyProxy::from(leaves[2])),
+            ("pair".to_string(), AnyProxy::from(leaves[3])),
+        ]
+    );
+
+    // Relinking skips the same fields.
+    let mut shifted = Node {
+        name: "copy",
+        parent: None,
+        leaves: vec![leaves[0]],
+        pair: Pair {
+            first: leaves[1],
+            second: None,
+        },
+        hint: Some(leaves[3]),
+    };
+    shifted.relink(&mut |p| {
+        let leaf = p.downcast::<Leaf>().unwrap();
+        AnyProxy::from(Proxy::<Leaf>::from_handle(leaf.handle() + 10))
+    });
+    assert_eq!(shifted.leaves[0].handle(), 10);
+    assert_eq!(shifted.pair.first.handle(), 11);
+    assert_eq!(shifted.hint, Some(leaves[3]));
+}
+
+#[test]
+fn test_enum() {
+    let mut r = Rug(Default::default(), Default::default(), Default::default());
+    let leaf = r.add(Leaf { value: 1 });
+    let empty = r.add(Shape::Empty);
+    let point = r.add(Shape::Point(3, leaf));
+    let group = r.add(Shape::Group {
+        name: "group",
+        members: vec![empty, point],
+    });
+
+    assert_eq!(field_links(r.get(&empty)), vec![]);
+    assert_eq!(
+        field_links(r.get(&point)),
+        vec![("1".to_string(), AnyProxy::from(leaf))]
+    );
+    assert_eq!(
+        field_links(r.get(&group)),
+        vec![
+            ("members".to_string(), AnyProxy::from(empty)),
+            ("members".to_string(), AnyProxy::from(point)),
+        ]
+    );
+
+    let mut shape = Shape::Group {
+        name: "copy",
+        members: vec![group],
+    };
+    shape.relink(&mut |_| AnyProxy::from(point));
+    let Shape::Group { members, .. } = shape else {
+        panic!("relinking changed the variant");
+    };
+    assert_eq!(members, vec![point]);
+}
+
+#[test]
+fn test_generic() {
+    let w = Wrapper {
+        inner: vec![Proxy::<Leaf>::from_handle(2)],
+    };
+    let mut links = Vec::new();
+    w.for_each_link(&mut |p| links.push(p));
+    assert_eq!(links, vec![AnyProxy::from(Proxy::<Leaf>::from_handle(2))]);
+}
@@ -0,0 +1,239 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::diff::{self, Change, Conflict};
+use persian_rug::handles::Recycling;
+use persian_rug::{contextual, persian_rug, Context, Proxy};
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Folder {
+    name: &'static str,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct File {
+    name: &'static str,
+    folder: Proxy<Folder>,
+}
+
+#[derive(Clone)]
+#[persian_rug(diff)]
+struct Rug(#[table] Folder, #[table] File);
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(RecyclingRug)]
+struct Slot(u32);
+
+#[derive(Clone)]
+#[persian_rug(diff, handles = Recycling)]
+struct RecyclingRug(#[table] Slot);
+
+#[test]
+fn test_same() {
+    let mut r = Rug(Default::default(), Default::default());
+    let docs = r.add(Folder { name: "docs" });
+    r.add(File {
+        name: "readme",
+        folder: docs,
+    });
+
+    let d = diff::diff(&r, &r.clone());
+    assert!(d.is_empty());
+    assert!(d.table::<Folder>().unwrap().is_empty());
+    assert!(d.table::<File>().unwrap().is_empty());
+    assert!(d.table::<Slot>().is_none());
+}
+
+#[test]
+fn test_changes() {
+    let mut r = Rug(Default::default(), Default::default());
+    let docs = r.add(Folder { name: "docs" });
+    let src = r.add(Folder { name: "src" });
+    let readme = r.add(File {
+        name: "readme",
+        folder: docs,
+    });
+    let main = r.add(File {
+        name: "main",
+        folder: src,
+    });
+    let before = r.clone();
+
+    // Moving a file only changes its link.
+    r.get_mut(&readme).folder = src;
+    r.delete(&main);
+    r.delete(&docs);
+    let lib = r.add(File {
+        name: "lib",
+        folder: src,
+    });
+
+    let d = diff::diff(&before, &r);
+    assert_eq!(d.len(), 4);
+    let folders = d.table::<Folder>().unwrap();
+    assert_eq!(folders.added, vec![]);
+    assert_eq!(folders.removed, vec![(docs, Folder { name: "docs" })]);
+    assert_eq!(folders.changed, vec![]);
+
+    let files = d.table::<File>().unwrap();
+    assert_eq!(
+        files.added,
+        vec![(
+            lib,
+            File {
+                name: "lib",
+                folder: src
+            }
+        )]
+    );
+    assert_eq!(
+        files.removed,
+        vec![(
+            main,
+            File {
+                name: "main",
+                folder: src
+            }
+        )]
+    );
+    assert_eq!(
+        files.changed,
+        vec![Change {
+            proxy: readme,
+            old: File {
+                name: "readme",
+                folder: docs
+            },
+            new: File {
+                name: "readme",
+                folder: src
+            },
+        }]
+    );
+
+    // Comparing the other way round swaps additions and removals.
+    let d = diff::diff(&r, &before);
+    let files = d.table::<File>().unwrap();
+    assert_eq!(files.added[0].0, main);
+    assert_eq!(files.removed[0].0, lib);
+    assert_eq!(files.changed[0].old.folder, src);
+}
+
+#[test]
+fn test_recycled_handles() {
+    let mut r = RecyclingRug(Default::default());
+    let first = r.add(Slot(1));
+    let before = r.clone();
+
+    r.delete(&first);
+    let second = r.add(Slot(1));
+    assert_eq!(first.handle(), second.handle());
+
+    // The handle was reused for a different object.
+    let d = diff::diff(&before, &r);
+    let slots = d.table::<Slot>().unwrap();
+    assert_eq!(slots.added, vec![(second, Slot(1))]);
+    assert_eq!(slots.removed, vec![(first, Slot(1))]);
+    assert!(slots.changed.is_empty());
+}
+
+#[test]
+fn test_apply_patch() {
+    let mut r = Rug(Default::default(), Default::default());
+    let src = r.add(Folder { name: "src" });
+    let main = r.add(File {
+        name: "main",
+        folder: src,
+    });
+    let lib = r.add(File {
+        name: "lib",
+        folder: src,
+    });
+    let mut replica = r.clone();
+    let before = r.clone();
+
+    let tests = r.add(Folder { name: "tests" });
+    r.get_mut(&main).folder = tests;
+    r.delete(&lib);
+    let check = r.add(File {
+        name: "check",
+        folder: tests,
+    });
+    let d = diff::diff(&before, &r);
+
+    replica.apply_patch(&d).unwrap();
+    assert!(diff::diff(&replica, &r).is_empty());
+    assert_eq!(replica.get(&replica.get(&check).folder).name, "tests");
+    assert!(replica.try_get(&lib).is_err());
+
+    // Everything in the patch is already there.
+    replica.apply_patch(&d).unwrap();
+    assert!(diff::diff(&replica, &r).is_empty());
+}
+
+#[test]
+fn test_apply_patch_conflicts() {
+    let mut r = Rug(Default::default(), Default::default());
+    let src = r.add(Folder { name: "src" });
+    let main = r.add(File {
+        name: "main",
+        folder: src,
+    });
+    let lib = r.add(File {
+        name: "lib",
+        folder: src,
+    });
+    let mut target = r.clone();
+    let before = r.clone();
+
+    r.get_mut(&src).name = "source";
+    r.delete(&lib);
+    let docs = r.add(Folder { name: "docs" });
+    let d = diff::diff(&before, &r);
+
+    target.get_mut(&lib).name = "library";
+    target.delete(&src);
+    let other = target.add(Folder { name: "other" });
+    assert_eq!(other.handle(), docs.handle());
+    let expected = target.clone();
+
+    let err = target.apply_patch(&d).unwrap_err();
+    assert_eq!(
+        err.conflicts(),
+        &[
+            Conflict::Missing(src.into()),
+            Conflict::Occupied(docs.into()),
+            Conflict::Diverged(lib.into()),
+        ]
+    );
+    assert_eq!(*err.conflicts()[2].proxy(), lib);
+    assert!(diff::diff(&expected, &target).is_empty());
+    assert_eq!(target.get(&main).name, "main");
+}
+
+#[test]
+fn test_apply_patch_recycled_handles() {
+    let mut r = RecyclingRug(Default::default());
+    let first = r.add(Slot(1));
+    let mut replica = r.clone();
+    let mut stale = r.clone();
+    let before = r.clone();
+
+    r.delete(&first);
+    let second = r.add(Slot(2));
+    let d = diff::diff(&before, &r);
+
+    replica.apply_patch(&d).unwrap();
+    assert_eq!(replica.get(&second), &Slot(2));
+    assert!(replica.try_get(&first).is_err());
+
+    // The handle has already been through another generation here,
+    // so adding `second` would revive its stale proxies.
+    stale.delete(&first);
+    let third = stale.add(Slot(3));
+    stale.delete(&third);
+    let err = stale.apply_patch(&d).unwrap_err();
+    assert_eq!(err.conflicts(), &[Conflict::Diverged(second.into())]);
+}
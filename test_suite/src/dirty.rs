@@ -0,0 +1,138 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::collections::BTreeSet;
+
+use persian_rug::dirty::DirtyStorage;
+use persian_rug::handles::{ShardPrefixed, SHARD_SHIFT};
+use persian_rug::storage::ArenaStorage;
+use persian_rug::{contextual, persian_rug, Accessor, AnyProxy, Context, Links, Proxy, Table};
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[derive(Clone, Links)]
+#[contextual(Rug)]
+struct Bar {
+    foo: Proxy<Foo>,
+}
+
+#[derive(Clone)]
+#[persian_rug]
+struct Rug(#[table(dirty)] Foo, #[table(dirty(arena))] Bar);
+
+fn proxies<T>(set: &BTreeSet<Proxy<T>>) -> Vec<Proxy<T>> {
+    set.iter().copied().collect()
+}
+
+#[test]
+fn test_take_dirty() {
+    let mut r = Rug(Default::default(), Default::default());
+    let a = r.add(Foo { a: 1 });
+    let b = r.add(Foo { a: 2 });
+    let c = r.add(Foo { a: 3 });
+    let bar = r.add(Bar { foo: a });
+    assert_eq!(proxies(&r.take_dirty::<Foo>()), vec![a, b, c]);
+    assert_eq!(proxies(&r.take_dirty::<Bar>()), vec![bar]);
+    assert!(r.take_dirty::<Foo>().is_empty());
+
+    let _ = r.get(&a);
+    r.get_mut(&c).a = 30;
+    assert!(r.try_get_mut(&b).is_ok());
+    assert_eq!(proxies(&r.take_dirty::<Foo>()), vec![b, c]);
+
+    r.get_mut(&bar).foo = b;
+    r.delete(&bar);
+    assert!(r.take_dirty::<Bar>().is_empty());
+}
+
+#[test]
+fn test_iteration() {
+    let mut r = Rug(Default::default(), Default::default());
+    let a = r.add(Foo { a: 1 });
+    let b = r.add(Foo { a: 2 });
+    r.take_dirty::<Foo>();
+
+    assert_eq!(r.get_iter::<Foo>().count(), 2);
+    assert!(r.take_dirty::<Foo>().is_empty());
+
+    for foo in r.get_iter_mut::<Foo>() {
+        foo.a += 1;
+    }
+    assert_eq!(proxies(&r.take_dirty::<Foo>()), vec![a, b]);
+
+    assert!(r.swap(&a, &b));
+    assert_eq!(proxies(&r.take_dirty::<Foo>()), vec![a, b]);
+}
+
+#[test]
+fn test_clone() {
+    let mut r = Rug(Default::default(), Default::default());
+    let a = r.add(Foo { a: 1 });
+
+    let mut c = r.clone();
+    assert_eq!(proxies(&c.take_dirty::<Foo>()), vec![a]);
+    assert_eq!(proxies(&r.take_dirty::<Foo>()), vec![a]);
+}
+
+#[derive(Clone, Links)]
+#[contextual(LinkedRug)]
+struct Node {
+    next: Option<Proxy<Node>>,
+}
+
+#[persian_rug(referrers)]
+struct LinkedRug(#[table(dirty)] Node);
+
+#[test]
+fn test_referrers() {
+    let mut r = LinkedRug(Default::default());
+    let a = r.add(Node { next: None });
+    let b = r.add(Node { next: Some(a) });
+    assert_eq!((&r).referrers(&a), vec![AnyProxy::from(b)]);
+    assert_eq!(proxies(&r.take_dirty::<Node>()), vec![a, b]);
+}
+
+#[test]
+fn test_table() {
+    let mut t = Table::<i32, DirtyStorage<i32, ArenaStorage<i32>>>::new();
+    let p = t.push(1);
+    let q = t.push(2);
+    assert_eq!(t.dirty().len(), 2);
+    t.take_dirty();
+
+    *t.get_mut(&q).unwrap() += 1;
+    assert!(t.dirty().contains(&q));
+    assert!(!t.dirty().contains(&p));
+
+    t.retain(|_, value| *value > 1);
+    assert_eq!(proxies(&t.take_dirty()), vec![q]);
+    assert!(t.dirty().is_empty());
+}
+
+#[contextual(ShardRug)]
+struct Baz {
+    a: i32,
+}
+
+#[persian_rug(handles = ShardPrefixed)]
+struct ShardRug(#[table(dirty)] Baz);
+
+#[test]
+fn test_sparse_handles() {
+    // The handles are far apart, so the record must not take space
+    // for the handles in between.
+    let mut r = ShardRug(Table::with_handles(ShardPrefixed::new(u16::MAX)));
+    let a = r.add(Baz { a: 1 });
+    let b = r.add(Baz { a: 2 });
+    assert_eq!(a.handle(), u64::from(u16::MAX) << SHARD_SHIFT);
+    assert_eq!(proxies(&r.take_dirty::<Baz>()), vec![a, b]);
+
+    r.get_mut(&b).a = 20;
+    assert_eq!(proxies(&r.take_dirty::<Baz>()), vec![b]);
+    r.delete(&a);
+    assert!(r.take_dirty::<Baz>().is_empty());
+}
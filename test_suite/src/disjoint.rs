@@ -0,0 +1,93 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::disjoint::Disjoint;
+use persian_rug::{contextual, persian_rug, Context, Proxy};
+
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[contextual(Rug)]
+struct Bar {
+    foo: Proxy<Foo>,
+}
+
+#[contextual(Rug)]
+struct Baz {
+    b: i32,
+}
+
+#[persian_rug]
+struct Rug {
+    #[table]
+    foos: Foo,
+    #[table]
+    bars: Bar,
+    #[table]
+    bazs: Baz,
+}
+
+fn new_rug() -> Rug {
+    Rug {
+        foos: Default::default(),
+        bars: Default::default(),
+        bazs: Default::default(),
+    }
+}
+
+#[test]
+fn test_split() {
+    let mut r = new_rug();
+    let f = r.add(Foo { a: 1 });
+    r.add(Bar { foo: f });
+    r.add(Bar { foo: f });
+    let z = r.add(Baz { b: 0 });
+
+    let mut split = Disjoint::new(&mut r);
+    let mut foos = split.split::<Foo>();
+    let mut bazs = split.split::<Baz>();
+    let reader = split.reader();
+
+    let added = std::thread::scope(|scope| {
+        let foos = scope.spawn(move || {
+            let g = foos.add(Foo { a: 10 });
+            foos.get_mut(&f).a += 1;
+            for foo in foos.get_iter_mut() {
+                foo.a *= 2;
+            }
+            assert_eq!(foos.get_proxy_iter().count(), 2);
+            assert_eq!(foos.get_iter().map(|foo| foo.a).sum::<i32>(), 24);
+            assert_eq!(foos.get(&g).a, 20);
+            g
+        });
+        scope.spawn(move || {
+            bazs.get_mut(&z).b = reader.get_proxy_iter::<Bar>().count() as i32;
+        });
+        foos.join().unwrap()
+    });
+
+    assert_eq!(r.get(&f).a, 4);
+    assert_eq!(r.get(&added).a, 20);
+    assert_eq!(r.get(&z).b, 2);
+}
+
+#[test]
+#[should_panic(expected = "has already been split off")]
+fn test_split_twice() {
+    let mut r = new_rug();
+    let mut split = Disjoint::new(&mut r);
+    let _foos = split.split::<Foo>();
+    let _again = split.split::<Foo>();
+}
+
+#[test]
+#[should_panic(expected = "has been split off for writing")]
+fn test_read_split_table() {
+    let mut r = new_rug();
+    let f = r.add(Foo { a: 1 });
+    let mut split = Disjoint::new(&mut r);
+    let _foos = split.split::<Foo>();
+    split.reader().get(&f);
+}
@@ -0,0 +1,93 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use django_query::filtering::{FilterableWithPersianRug, OperatorSetWithContext};
+use django_query::sorting::{OrderingSetWithContext, SortableWithPersianRug};
+use persian_rug::{contextual, persian_rug, Context, Proxy};
+
+#[derive(Clone, FilterableWithPersianRug, SortableWithPersianRug)]
+#[django(persian_rug(context = C, access(Owner<C>)))]
+#[contextual(C)]
+struct Owner<C: Context> {
+    #[django(exclude)]
+    _marker: core::marker::PhantomData<C>,
+    #[django(sort)]
+    name: String,
+    #[django(sort, op(gt))]
+    age: i32,
+}
+
+#[derive(Clone, FilterableWithPersianRug, SortableWithPersianRug)]
+#[django(persian_rug(context = C, access(Owner<C>, Pet<C>)))]
+#[contextual(C)]
+struct Pet<C: Context> {
+    #[django(sort)]
+    name: String,
+    #[django(traverse, sort("age"))]
+    owner: Proxy<Owner<C>>,
+}
+
+#[persian_rug]
+struct Rug(#[table] Owner<Rug>, #[table] Pet<Rug>);
+
+fn make_rug() -> Rug {
+    let mut r = Rug(Default::default(), Default::default());
+    let alice = r.add(Owner {
+        _marker: Default::default(),
+        name: "alice".to_string(),
+        age: 40,
+    });
+    let bob = r.add(Owner {
+        _marker: Default::default(),
+        name: "bob".to_string(),
+        age: 30,
+    });
+    for (name, owner) in [("rex", alice), ("tiddles", bob), ("fido", bob)] {
+        r.add(Pet {
+            name: name.to_string(),
+            owner,
+        });
+    }
+    r
+}
+
+fn names(pets: &[&Pet<Rug>]) -> Vec<String> {
+    pets.iter().map(|p| p.name.clone()).collect()
+}
+
+#[test]
+fn test_filter_through_proxy() {
+    let r = make_rug();
+    let access = &r;
+
+    let ops = OperatorSetWithContext::<Pet<Rug>, _>::new(access);
+    let filter = ops
+        .create_filter_from_query_pair("owner__name", "bob")
+        .unwrap();
+    let mut pets = access.get_iter::<Pet<Rug>>().collect::<Vec<_>>();
+    filter.filter_ref_vec(&mut pets);
+    assert_eq!(names(&pets), vec!["tiddles", "fido"]);
+
+    let filter = ops
+        .create_filter_from_query_pair("owner__age__gt", "35")
+        .unwrap();
+    let mut pets = access.get_iter::<Pet<Rug>>().collect::<Vec<_>>();
+    filter.filter_ref_vec(&mut pets);
+    assert_eq!(names(&pets), vec!["rex"]);
+}
+
+#[test]
+fn test_sort_through_proxy() {
+    let r = make_rug();
+    let access = &r;
+
+    let ordering = OrderingSetWithContext::<Pet<Rug>, _>::new(access);
+    let sort = ordering.create_sort("owner").unwrap();
+    let mut pets = access.get_iter::<Pet<Rug>>().collect::<Vec<_>>();
+    sort.sort_ref_vec(&mut pets);
+    assert_eq!(names(&pets), vec!["tiddles", "fido", "rex"]);
+
+    let sort = ordering.create_sort("-name").unwrap();
+    sort.sort_ref_vec(&mut pets);
+    assert_eq!(names(&pets), vec!["tiddles", "rex", "fido"]);
+}
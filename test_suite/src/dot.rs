@@ -0,0 +1,95 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::diagram::Labels;
+use persian_rug::{contextual, dot, persian_rug, Context, Proxy};
+
+#[contextual(Rug)]
+struct Module {
+    name: &'static str,
+    #[link]
+    imports: Vec<Proxy<Module>>,
+    #[link]
+    owner: Option<Proxy<Team>>,
+}
+
+#[contextual(Rug)]
+struct Team {
+    name: &'static str,
+}
+
+impl persian_rug::Links for Team {}
+
+#[persian_rug(diagram)]
+struct Rug(#[table] Module, #[table] Team);
+
+fn rug() -> (Rug, Vec<Proxy<Module>>) {
+    let mut r = Rug(Default::default(), Default::default());
+    let team = r.add(Team {
+        name: "Core \"team\"",
+    });
+    let base = r.add(Module {
+        name: "base",
+        imports: Vec::new(),
+        owner: Some(team),
+    });
+    let util = r.add(Module {
+        name: "util",
+        imports: vec![base],
+        owner: None,
+    });
+    let app = r.add(Module {
+        name: "app",
+        imports: vec![base, util],
+        owner: Some(team),
+    });
+    (r, vec![base, util, app])
+}
+
+#[test]
+fn test_default_labels() {
+    let (r, _) = rug();
+    assert_eq!(
+        dot::to_string(&r, &Labels::new()),
+        concat!(
+            "digraph {\n",
+            "  n0_0 [label=\"Module 0\"];\n",
+            "  n0_1 [label=\"Module 1\"];\n",
+            "  n0_2 [label=\"Module 2\"];\n",
+            "  n1_0 [label=\"Team 0\"];\n",
+            "  n0_0 -> n1_0 [label=\"owner\"];\n",
+            "  n0_1 -> n0_0 [label=\"imports\"];\n",
+            "  n0_2 -> n0_0 [label=\"imports\"];\n",
+            "  n0_2 -> n0_1 [label=\"imports\"];\n",
+            "  n0_2 -> n1_0 [label=\"owner\"];\n",
+            "}\n",
+        )
+    );
+}
+
+#[test]
+fn test_labels() {
+    let (mut r, modules) = rug();
+    r.delete(&modules[1]);
+
+    let labels = Labels::new()
+        .label(|m: &Module| format!("{}\n{} imports", m.name, m.imports.len()))
+        .label(|t: &Team| t.name.to_string());
+    let mut out = Vec::new();
+    dot::write(&r, &labels, &mut out).unwrap();
+
+    // The link to the deleted module is left out.
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        concat!(
+            "digraph {\n",
+            "  n0_0 [label=\"base\\n0 imports\"];\n",
+            "  n0_2 [label=\"app\\n2 imports\"];\n",
+            "  n1_0 [label=\"Core \\\"team\\\"\"];\n",
+            "  n0_0 -> n1_0 [label=\"owner\"];\n",
+            "  n0_2 -> n0_0 [label=\"imports\"];\n",
+            "  n0_2 -> n1_0 [label=\"owner\"];\n",
+            "}\n",
+        )
+    );
+}
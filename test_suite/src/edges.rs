@@ -0,0 +1,132 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{
+    contextual, persian_rug, seed, Accessor, Context, Edge, EdgeAccessor, EdgeIndex, Proxy,
+};
+
+#[contextual(Rug)]
+struct User {
+    name: &'static str,
+}
+
+#[contextual(Rug)]
+struct Post {
+    title: &'static str,
+}
+
+type Follows = Edge<User, User, u32>;
+type Likes = Edge<User, Post>;
+
+#[persian_rug]
+struct Rug {
+    #[table]
+    users: User,
+    #[table]
+    posts: Post,
+    #[table]
+    follows: Follows,
+    #[table]
+    likes: Likes,
+}
+
+fn make_rug() -> Rug {
+    Rug {
+        users: Default::default(),
+        posts: Default::default(),
+        follows: Default::default(),
+        likes: Default::default(),
+    }
+}
+
+fn names<'a, A: Accessor<Context = Rug>>(
+    access: &'a A,
+    users: impl Iterator<Item = Proxy<User>> + 'a,
+) -> Vec<&'static str> {
+    users.map(|u| access.get(&u).name).collect()
+}
+
+#[test]
+fn test_edges() {
+    let mut r = make_rug();
+    let labels = ["ann", "bob", "cat"];
+    let users = seed(&mut r, 3, |ix, _| User { name: labels[ix] });
+    let posts = seed(&mut r, 2, |_, _| Post { title: "hello" });
+
+    r.add(Follows::new(users[0], users[1], 2020));
+    r.add(Follows::new(users[0], users[2], 2021));
+    r.add(Follows::new(users[2], users[1], 2022));
+    r.add(Likes::new(users[1], posts[0], ()));
+    r.add(Likes::new(users[2], posts[0], ()));
+
+    let access = &r;
+    assert_eq!(
+        names(
+            &access,
+            access
+                .edges_from::<User, User, u32>(&users[0])
+                .map(|e| e.to)
+        ),
+        vec!["bob", "cat"]
+    );
+    assert_eq!(
+        access
+            .edges_to::<User, User, u32>(&users[1])
+            .map(|e| e.payload)
+            .collect::<Vec<_>>(),
+        vec![2020, 2022]
+    );
+    assert_eq!(
+        names(
+            &access,
+            access.edges_to::<User, Post, ()>(&posts[0]).map(|e| e.from)
+        ),
+        vec!["bob", "cat"]
+    );
+    assert_eq!(access.edges_to::<User, Post, ()>(&posts[1]).count(), 0);
+    assert_eq!(access.edges_from::<User, Post, ()>(&users[0]).count(), 0);
+}
+
+#[test]
+fn test_edge_index() {
+    let mut r = make_rug();
+    let users = seed(&mut r, 4, |_, _| User { name: "" });
+    let mut index = EdgeIndex::<User, User, u32>::build(&r);
+    assert_eq!(index.edges_from(&users[0]).count(), 0);
+
+    let mut follows = Vec::new();
+    for (from, to) in [(0, 1), (0, 2), (1, 2), (3, 2)] {
+        let edge = Follows::new(users[from], users[to], 0);
+        let p = r.add(edge);
+        index.insert(p, r.get(&p));
+        follows.push(p);
+    }
+
+    let rebuilt = EdgeIndex::<User, User, u32>::build(&r);
+    for user in users.iter() {
+        let from = index.edges_from(user).collect::<Vec<_>>();
+        let to = index.edges_to(user).collect::<Vec<_>>();
+        assert_eq!(rebuilt.edges_from(user).collect::<Vec<_>>(), from);
+        assert_eq!(rebuilt.edges_to(user).collect::<Vec<_>>(), to);
+        let access = &r;
+        assert_eq!(
+            from.iter().map(|p| *r.get(p)).collect::<Vec<_>>(),
+            access
+                .edges_from::<User, User, u32>(user)
+                .copied()
+                .collect::<Vec<_>>()
+        );
+    }
+    assert_eq!(
+        index.edges_to(&users[2]).collect::<Vec<_>>(),
+        vec![follows[1], follows[2], follows[3]]
+    );
+
+    let edge = *r.get(&follows[2]);
+    index.remove(&follows[2], &edge);
+    assert_eq!(
+        index.edges_to(&users[2]).collect::<Vec<_>>(),
+        vec![follows[1], follows[3]]
+    );
+    assert_eq!(index.edges_from(&users[1]).count(), 0);
+}
@@ -0,0 +1,127 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, extract, persian_rug, AnyProxy, Context, Links, Proxy};
+
+#[derive(Clone, Debug, PartialEq, Links)]
+#[contextual(Rug)]
+struct Style {
+    name: &'static str,
+}
+
+#[derive(Clone, Debug, PartialEq, Links)]
+#[contextual(Rug)]
+struct Block {
+    text: &'static str,
+    style: Option<Proxy<Style>>,
+    children: Vec<Proxy<Block>>,
+    parent: Option<Proxy<Block>>,
+}
+
+#[persian_rug(extract)]
+struct Rug(#[table] Style, #[table] Block);
+
+fn rug() -> Rug {
+    Rug(Default::default(), Default::default())
+}
+
+fn block(text: &'static str, style: Option<Proxy<Style>>, children: Vec<Proxy<Block>>) -> Block {
+    Block {
+        text,
+        style,
+        children,
+        parent: None,
+    }
+}
+
+#[test]
+fn test_extract() {
+    let mut r = rug();
+    let plain = r.add(Style { name: "plain" });
+    let bold = r.add(Style { name: "bold" });
+    let unused = r.add(Style { name: "unused" });
+    let a = r.add(block("a", Some(bold), vec![]));
+    let b = r.add(block("b", Some(plain), vec![a]));
+    let other = r.add(block("other", Some(unused), vec![a]));
+    let doc = r.add(block("doc", None, vec![a, b]));
+    r.get_mut(&a).parent = Some(doc);
+
+    let mut out = rug();
+    let extracted = extract::extract(&r, &mut out, [AnyProxy::from(doc)]);
+    assert_eq!(extracted.len(), 5);
+    assert_eq!(extracted.get(&other), None);
+    assert_eq!(extracted.get(&unused), None);
+
+    let new_doc = extracted.roots()[0].downcast::<Block>().unwrap();
+    assert_eq!(Some(new_doc), extracted.get(&doc));
+    let new_a = extracted.get(&a).unwrap();
+    let new_b = extracted.get(&b).unwrap();
+    assert_eq!(out.get(&new_doc).children, vec![new_a, new_b]);
+    // The shared child is copied once, and the cycle through its
+    // parent is preserved.
+    assert_eq!(out.get(&new_b).children, vec![new_a]);
+    assert_eq!(out.get(&new_a).parent, Some(new_doc));
+    assert_eq!(out.get(&new_a).style, extracted.get(&bold));
+    assert_eq!(
+        out.get(out.get(&new_b).style.as_ref().unwrap()).name,
+        "plain"
+    );
+
+    assert_eq!(
+        out.get_iter::<Style>().map(|s| s.name).collect::<Vec<_>>(),
+        vec!["plain", "bold"]
+    );
+    assert_eq!(
+        out.get_iter::<Block>().map(|b| b.text).collect::<Vec<_>>(),
+        vec!["a", "b", "doc"]
+    );
+
+    // The original is untouched.
+    assert_eq!(persian_rug::Owner::<Block>::len(&r), 4);
+    assert_eq!(r.get(&doc).children, vec![a, b]);
+}
+
+#[test]
+fn test_extract_into_populated() {
+    let mut r = rug();
+    let s = r.add(Style { name: "s" });
+    let root = r.add(block("root", Some(s), vec![]));
+
+    let mut out = rug();
+    let existing = out.add(Style { name: "existing" });
+    let first = extract::extract(&r, &mut out, [AnyProxy::from(root)]);
+    let second = extract::extract(&r, &mut out, [AnyProxy::from(root), AnyProxy::from(s)]);
+
+    assert_eq!(out.get(&existing).name, "existing");
+    let first_root = first.get(&root).unwrap();
+    let second_root = second.get(&root).unwrap();
+    assert_ne!(first_root, second_root);
+    assert_eq!(out.get(&first_root).style, first.get(&s));
+    assert_eq!(out.get(&second_root).style, second.get(&s));
+    assert_eq!(second.roots()[1], AnyProxy::from(second.get(&s).unwrap()));
+    assert_eq!(persian_rug::Owner::<Style>::len(&out), 3);
+}
+
+#[test]
+fn test_extract_dangling() {
+    let mut r = rug();
+    let gone = r.add(Style { name: "gone" });
+    let root = r.add(block("root", Some(gone), vec![]));
+    r.delete(&gone);
+
+    let mut out = rug();
+    let extracted = extract::extract(&r, &mut out, [AnyProxy::from(root)]);
+    assert_eq!(extracted.len(), 1);
+    assert_eq!(out.get(&extracted.get(&root).unwrap()).style, Some(gone));
+}
+
+#[test]
+#[should_panic(expected = "root of extraction is not in the context")]
+fn test_extract_deleted_root() {
+    let mut r = rug();
+    let root = r.add(block("root", None, vec![]));
+    r.delete(&root);
+
+    let mut out = rug();
+    extract::extract(&r, &mut out, [AnyProxy::from(root)]);
+}
@@ -0,0 +1,106 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, AnyProxy, Context, Proxy, WeakProxy};
+
+#[contextual(Rug)]
+struct Scene {
+    #[link]
+    shapes: Vec<Proxy<Shape>>,
+}
+
+#[contextual(Rug)]
+struct Shape {
+    name: &'static str,
+    #[link]
+    material: Option<Proxy<Material>>,
+    #[link]
+    next: Option<Proxy<Shape>>,
+    scene: Option<WeakProxy<Scene>>,
+}
+
+#[contextual(Rug)]
+struct Material {
+    name: &'static str,
+}
+
+impl persian_rug::Links for Material {}
+
+#[persian_rug(gc)]
+struct Rug(#[table] Scene, #[table] Shape, #[table] Material);
+
+fn shape(name: &'static str, material: Option<Proxy<Material>>) -> Shape {
+    Shape {
+        name,
+        material,
+        next: None,
+        scene: None,
+    }
+}
+
+fn names(r: &Rug) -> (Vec<&'static str>, Vec<&'static str>) {
+    (
+        r.get_iter::<Shape>().map(|s| s.name).collect(),
+        r.get_iter::<Material>().map(|m| m.name).collect(),
+    )
+}
+
+#[test]
+fn test_collect_garbage() {
+    let mut r = Rug(Default::default(), Default::default(), Default::default());
+    let red = r.add(Material { name: "red" });
+    let blue = r.add(Material { name: "blue" });
+    let cube = r.add(shape("cube", Some(red)));
+    let sphere = r.add(shape("sphere", Some(blue)));
+    let cone = r.add(shape("cone", None));
+    let scene = r.add(Scene {
+        shapes: vec![cube, cone],
+    });
+
+    // A cycle which is not reachable from the scene.
+    let a = r.add(shape("a", None));
+    let b = r.add(shape("b", Some(red)));
+    r.get_mut(&a).next = Some(b);
+    r.get_mut(&b).next = Some(a);
+
+    // Weak proxies do not keep the scene alive.
+    r.get_mut(&cone).scene = Some(scene.downgrade());
+
+    let deleted = r.collect_garbage([AnyProxy::from(scene)]);
+    assert_eq!(
+        deleted,
+        vec![
+            AnyProxy::from(sphere),
+            AnyProxy::from(a),
+            AnyProxy::from(b),
+            AnyProxy::from(blue),
+        ]
+    );
+    assert_eq!(names(&r), (vec!["cube", "cone"], vec!["red"]));
+    assert!(r.try_get(&sphere).is_err());
+
+    // Without the scene as a root, everything goes, including the
+    // scene itself.
+    let deleted = r.collect_garbage([AnyProxy::from(sphere)]);
+    assert_eq!(deleted.len(), 4);
+    assert_eq!(r.get_iter::<Scene>().count(), 0);
+    assert_eq!(names(&r), (vec![], vec![]));
+    assert_eq!(scene.downgrade().upgrade(&r), None);
+}
+
+#[test]
+fn test_collect_marked_deleted() {
+    let mut r = Rug(Default::default(), Default::default(), Default::default());
+    let red = r.add(Material { name: "red" });
+    let blue = r.add(Material { name: "blue" });
+    let cube = r.add(shape("cube", Some(red)));
+    let scene = r.add(Scene { shapes: vec![cube] });
+
+    r.table_mut::<Shape>().mark_deleted(&cube);
+    r.table_mut::<Material>().mark_deleted(&blue);
+
+    let deleted = r.collect_garbage([AnyProxy::from(scene)]);
+    assert!(deleted.is_empty());
+    assert_eq!(r.get(&red).name, "red");
+    assert!(r.table::<Material>().is_deleted(&blue));
+}
@@ -0,0 +1,158 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{assert_context_snapshot, contextual, persian_rug, testing, Context, Proxy};
+
+#[derive(Debug)]
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+    next: Option<Proxy<Foo>>,
+}
+
+#[derive(Debug)]
+#[contextual(Rug)]
+struct Bar {
+    name: String,
+    foos: Vec<Proxy<Foo>>,
+}
+
+#[derive(Debug)]
+#[persian_rug]
+struct Rug {
+    #[table]
+    foos: Foo,
+    #[table]
+    bars: Bar,
+}
+
+fn make_rug() -> Rug {
+    let mut r = Rug {
+        foos: Default::default(),
+        bars: Default::default(),
+    };
+
+    let f1 = r.add(Foo { a: 1, next: None });
+    let f2 = r.add(Foo {
+        a: 2,
+        next: Some(f1),
+    });
+    r.get_mut(&f1).next = Some(f2);
+    r.add(Bar {
+        name: "bar".to_string(),
+        foos: vec![f2, f1],
+    });
+
+    r
+}
+
+#[test]
+fn test_inline() {
+    let r = make_rug();
+
+    assert_context_snapshot!(r, @r#"
+        Rug {
+            foos: {
+                0: Foo {
+                    a: 1,
+                    next: Some(
+                        persian_rug::Proxy<test_suite::golden::Foo> { handle: 1 },
+                    ),
+                },
+                1: Foo {
+                    a: 2,
+                    next: Some(
+                        persian_rug::Proxy<test_suite::golden::Foo> { handle: 0 },
+                    ),
+                },
+            },
+            bars: {
+                0: Bar {
+                    name: "bar",
+                    foos: [
+                        persian_rug::Proxy<test_suite::golden::Foo> { handle: 1 },
+                        persian_rug::Proxy<test_suite::golden::Foo> { handle: 0 },
+                    ],
+                },
+            },
+        }
+    "#);
+}
+
+#[test]
+fn test_file() {
+    let r = make_rug();
+
+    assert_context_snapshot!(r, "golden_links");
+}
+
+#[test]
+fn test_deterministic() {
+    assert_eq!(testing::render(&make_rug()), testing::render(&make_rug()));
+}
+
+#[test]
+#[should_panic(expected = "snapshot mismatch at line 4")]
+fn test_mismatch() {
+    let mut r = Rug {
+        foos: Default::default(),
+        bars: Default::default(),
+    };
+    r.add(Foo { a: 1, next: None });
+
+    assert_context_snapshot!(r, @"
+        Rug {
+            foos: {
+                0: Foo {
+                    a: 2,
+                    next: None,
+                },
+            },
+            bars: {},
+        }
+    ");
+}
+
+#[test]
+fn test_compare() {
+    assert_eq!(testing::compare_snapshot("a\nb\n", "a\nb\n"), Ok(()));
+
+    let msg = testing::compare_snapshot("a\nb\nc\n", "a\nx\nc\n").unwrap_err();
+    assert!(msg.starts_with("snapshot mismatch at line 2:\n  expected: x\n    actual: b\n"));
+
+    let msg = testing::compare_snapshot("a\n", "a\nb\n").unwrap_err();
+    assert!(msg.contains("expected: b\n    actual: <end of rendering>"));
+}
+
+#[derive(Debug)]
+#[contextual(ArenaRug)]
+struct Baz {
+    a: i32,
+}
+
+#[derive(Debug)]
+#[persian_rug]
+struct ArenaRug(#[table(arena)] Baz);
+
+#[test]
+fn test_arena_handle_order() {
+    let mut r = ArenaRug(Default::default());
+    let first = r.add(Baz { a: 0 });
+    r.add(Baz { a: 1 });
+    r.add(Baz { a: 2 });
+    // The arena moves its last object into the gap this leaves.
+    r.delete(&first);
+
+    assert_context_snapshot!(r, @r#"
+        ArenaRug(
+            {
+                1: Baz {
+                    a: 1,
+                },
+                2: Baz {
+                    a: 2,
+                },
+            },
+        )
+    "#);
+}
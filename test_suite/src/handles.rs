@@ -0,0 +1,324 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::handles::{
+    HandleAllocator, Random, Recycling, ShardPrefixed, TimeOrdered, SHARD_SHIFT,
+};
+use persian_rug::storage::{ArenaStorage, MapStorage};
+use persian_rug::{
+    contextual, persian_rug, Context, HandleInUse, Proxy, ProxySet, SideTable, Table,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[persian_rug]
+struct Rug(#[table] Foo);
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(ShardRug)]
+struct Bar {
+    a: i32,
+}
+
+#[persian_rug(handles = ShardPrefixed)]
+struct ShardRug {
+    #[table(arena)]
+    bars: Bar,
+}
+
+#[test]
+fn test_insert_with_handle() {
+    let mut t = Table::<Foo>::new();
+    let p = t.insert_with_handle(5, Foo { a: 5 }).unwrap();
+    assert_eq!(t.get(&p), Some(&Foo { a: 5 }));
+    assert_eq!(t.next_proxy(), t.push(Foo { a: 6 }));
+    assert_eq!(t.next_proxy().handle(), 7);
+
+    // Earlier, unused handles can still be filled in.
+    let q = t.insert_with_handle(2, Foo { a: 2 }).unwrap();
+    assert_eq!(t.next_proxy().handle(), 7);
+    assert_eq!(t.iter().map(|foo| foo.a).collect::<Vec<_>>(), vec![2, 5, 6]);
+
+    assert_eq!(t.insert_with_handle(2, Foo { a: 3 }), Err(HandleInUse(2)));
+    assert_eq!(t.get(&q), Some(&Foo { a: 2 }));
+    assert_eq!(
+        HandleInUse(2).to_string(),
+        "handle 2 is already in use".to_string()
+    );
+}
+
+#[test]
+fn test_set_next_handle() {
+    let mut t = Table::<Foo, ArenaStorage<Foo>>::new();
+    t.set_next_handle(100).unwrap();
+    let p = t.push(Foo { a: 1 });
+    assert_eq!(p.handle(), 100);
+
+    assert_eq!(t.set_next_handle(50), Err(HandleInUse(100)));
+    assert_eq!(t.set_next_handle(100), Err(HandleInUse(100)));
+    t.set_next_handle(200).unwrap();
+    assert_eq!(t.push(Foo { a: 2 }).handle(), 200);
+}
+
+#[test]
+fn test_reserve_handles() {
+    let mut r = Rug(Default::default());
+    r.add(Foo { a: 0 });
+    let handles = r.0.reserve_handles(4);
+    assert_eq!(handles, 1..5);
+    assert_eq!(r.add(Foo { a: 5 }).handle(), 5);
+
+    // Build the reserved objects on other threads, then store them.
+    let built = std::thread::scope(|scope| {
+        let workers = handles
+            .clone()
+            .map(|handle| scope.spawn(move || (handle, Foo { a: handle as i32 })))
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    for (handle, foo) in built {
+        r.0.insert_with_handle(handle, foo).unwrap();
+    }
+
+    assert_eq!(
+        r.get_iter::<Foo>().map(|foo| foo.a).collect::<Vec<_>>(),
+        vec![0, 1, 2, 3, 4, 5]
+    );
+    assert_eq!(r.get(&Proxy::from_handle(3)).a, 3);
+    assert_eq!(r.0.reserve_handles(0), 6..6);
+    assert_eq!(r.add(Foo { a: 6 }).handle(), 6);
+}
+
+#[test]
+fn test_reproducible_contexts() {
+    fn build(start: u64) -> Rug {
+        let mut r = Rug(Default::default());
+        r.0.set_next_handle(start).unwrap();
+        for a in 0..3 {
+            r.add(Foo { a });
+        }
+        r
+    }
+
+    let a = build(10);
+    let b = build(10);
+    assert_eq!(
+        a.get_proxy_iter::<Foo>().collect::<Vec<_>>(),
+        b.get_proxy_iter::<Foo>().collect::<Vec<_>>()
+    );
+
+    // Rebuilding from the proxies of another context keeps them.
+    let mut c = Rug(Default::default());
+    for p in a.get_proxy_iter::<Foo>() {
+        let q =
+            c.0.insert_with_handle(p.handle(), a.get(p).clone())
+                .unwrap();
+        assert_eq!(&q, p);
+    }
+    assert_eq!(c.add(Foo { a: 3 }).handle(), 13);
+}
+
+#[test]
+fn test_shard_prefixed() {
+    let mut left = ShardRug {
+        bars: Table::with_handles(ShardPrefixed::new(1)),
+    };
+    let mut right = ShardRug {
+        bars: Table::with_handles(ShardPrefixed::new(2)),
+    };
+    let ls = (0..3).map(|a| left.add(Bar { a })).collect::<Vec<_>>();
+    let rs = (3..6).map(|a| right.add(Bar { a })).collect::<Vec<_>>();
+    assert_eq!(ls[2].handle(), (1 << SHARD_SHIFT) | 2);
+    assert_eq!(rs[0].handle(), 2 << SHARD_SHIFT);
+
+    // The two contexts can be merged without renumbering.
+    let mut merged = ShardRug {
+        bars: Table::with_handles(ShardPrefixed::new(1)),
+    };
+    for (from, ps) in [(&left, &ls), (&right, &rs)] {
+        for p in ps.iter() {
+            let q = merged
+                .bars
+                .insert_with_handle(p.handle(), from.get(p).clone())
+                .unwrap();
+            assert_eq!(&q, p);
+        }
+    }
+    assert_eq!(merged.get(&rs[1]), &Bar { a: 4 });
+
+    // Handles reserved in the table's own shard are not reissued.
+    assert_eq!(merged.add(Bar { a: 6 }).handle(), (1 << SHARD_SHIFT) | 3);
+}
+
+#[test]
+fn test_random() {
+    let mut a = Table::<Foo, ArenaStorage<Foo>, Random>::with_handles(Random::new(7));
+    let mut b = Table::<Foo, ArenaStorage<Foo>, Random>::with_handles(Random::new(7));
+    let pa = (0..10).map(|ix| a_push(&mut a, ix)).collect::<Vec<_>>();
+    let pb = (0..10).map(|ix| a_push(&mut b, ix)).collect::<Vec<_>>();
+    assert_eq!(pa, pb);
+    assert!(pa.windows(2).any(|w| w[0] > w[1]));
+
+    // A handle which is already in use is skipped.
+    let mut c = Table::<Foo, ArenaStorage<Foo>, Random>::with_handles(Random::new(7));
+    c.insert_with_handle(pa[1].handle(), Foo { a: -1 }).unwrap();
+    assert_eq!(c.push(Foo { a: 0 }), pa[0]);
+    assert_eq!(c.push(Foo { a: 1 }), pa[2]);
+    assert_eq!(c.next_proxy(), pa[3]);
+}
+
+fn a_push<A: HandleAllocator>(
+    t: &mut Table<Foo, ArenaStorage<Foo>, A>,
+    a: i32,
+) -> persian_rug::Proxy<Foo> {
+    t.push(Foo { a })
+}
+
+#[test]
+fn test_time_ordered() {
+    let mut t = Table::<Foo, ArenaStorage<Foo>, TimeOrdered>::with_handles(TimeOrdered::new(3));
+    let ps = (0..5000).map(|a| t.push(Foo { a })).collect::<Vec<_>>();
+    assert!(ps.windows(2).all(|w| w[0] < w[1]));
+    assert!(ps.iter().all(|p| (p.handle() >> 12) & 0xFF == 3));
+
+    let mut other = TimeOrdered::new(4);
+    assert_ne!(other.peek(), t.next_proxy().handle());
+    let before = other.peek();
+    other.advance();
+    assert!(other.peek() > before);
+
+    // Reserving a later handle for the same node continues after it.
+    let later = t.next_proxy().handle() + (1 << 30);
+    t.insert_with_handle(later, Foo { a: -1 }).unwrap();
+    assert!(t.push(Foo { a: 0 }).handle() > later);
+}
+
+const FIRST: Proxy<Foo> = Proxy::reserved(0);
+const SECOND: Proxy<Foo> = Proxy::reserved(1);
+
+#[test]
+fn test_reserved_roots() {
+    let mut r = Rug(Table::with_reserved([Foo { a: 10 }, Foo { a: 20 }]));
+    assert_eq!(r.get(&FIRST).a, 10);
+    assert_eq!(r.get(&SECOND).a, 20);
+
+    let p = r.add(Foo { a: 30 });
+    assert_eq!(p.handle(), 2);
+    assert_eq!(SECOND, Proxy::from_handle(1));
+
+    // Roots are ordinary objects once stored.
+    r.get_mut(&FIRST).a = 11;
+    assert_eq!(
+        r.get_iter::<Foo>().map(|f| f.a).collect::<Vec<_>>(),
+        vec![11, 20, 30]
+    );
+
+    let empty = Rug(Table::with_reserved([]));
+    assert!(!empty.0.contains(&FIRST));
+}
+
+#[test]
+fn test_reserve_last_handle() {
+    let mut table = Table::<i32>::new();
+    table.insert_with_handle(u64::MAX - 1, 1).unwrap();
+    assert_eq!(table.push(2).handle(), u64::MAX);
+    assert_eq!(table.next_proxy().handle(), u64::MAX);
+    // Handles can still be chosen once the allocator has run out.
+    assert_eq!(table.insert_with_handle(0, 3).unwrap().handle(), 0);
+    assert_eq!(table.len(), 3);
+
+    let mut table = Table::<i32, MapStorage<i32>, _>::with_handles(Recycling::new());
+    let p = table.insert_with_handle(u64::MAX, 1).unwrap();
+    assert!(table.delete(&p));
+    // The skipped handles are issued first.
+    assert_eq!(table.push(2).handle(), 0);
+    assert_eq!(table.push(3).handle(), 1);
+
+    let last = (1 << SHARD_SHIFT) | ((1 << SHARD_SHIFT) - 1);
+    let mut table = Table::<i32, MapStorage<i32>, _>::with_handles(ShardPrefixed::new(1));
+    table.insert_with_handle(last, 1).unwrap();
+    assert_eq!(table.next_proxy().handle(), last);
+}
+
+#[test]
+#[should_panic(expected = "run out of handles")]
+fn test_sequential_exhausted() {
+    let mut table = Table::<i32>::new();
+    table.insert_with_handle(u64::MAX, 1).unwrap();
+    table.push(2);
+}
+
+#[test]
+#[should_panic(expected = "run out of handles")]
+fn test_shard_exhausted() {
+    let mut table = Table::<i32, MapStorage<i32>, _>::with_handles(ShardPrefixed::new(1));
+    table.insert_with_handle((2 << SHARD_SHIFT) - 2, 1).unwrap();
+    table.push(2);
+    table.push(3);
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(RecyclingRug)]
+struct Baz {
+    a: i32,
+}
+
+#[persian_rug(handles = Recycling)]
+struct RecyclingRug(#[table] Baz);
+
+#[test]
+fn test_recycling() {
+    let mut r = RecyclingRug(Default::default());
+    let ps = (0..4).map(|a| r.add(Baz { a })).collect::<Vec<_>>();
+
+    let mut set = ProxySet::new();
+    set.insert(ps[1]);
+    let mut names = SideTable::new();
+    names.insert(ps[1], "one");
+
+    assert!(r.delete(&ps[1]));
+    assert!(r.delete(&ps[3]));
+    assert_eq!(r.0.handles().free_len(), 2);
+
+    // Freed handles are reused smallest first, with a new generation.
+    let a = r.add(Baz { a: 10 });
+    let b = r.add(Baz { a: 11 });
+    let c = r.add(Baz { a: 12 });
+    assert_eq!((a.handle(), a.generation()), (1, 1));
+    assert_eq!((b.handle(), b.generation()), (3, 1));
+    assert_eq!((c.handle(), c.generation()), (4, 0));
+    assert_eq!(r.0.handles().free_len(), 0);
+
+    assert!(r.try_get(&ps[1]).is_err());
+    assert_eq!(r.get(&a).a, 10);
+    assert!(set.contains(&ps[1]));
+    assert!(!set.contains(&a));
+    assert_eq!(names.get(&a), None);
+    assert_eq!(names.get(&ps[1]), Some(&"one"));
+
+    set.insert(a);
+    assert!(!set.contains(&ps[1]));
+    assert_eq!(set.iter().collect::<Vec<_>>(), vec![a]);
+}
+
+#[test]
+fn test_recycling_skipped() {
+    let mut table = Table::<i32, MapStorage<i32>, _>::with_handles(Recycling::new());
+    table.push(0);
+    table.insert_with_handle(5, 5).unwrap();
+    assert_eq!(table.handles().free_len(), 4);
+
+    // Handles skipped by choosing one are not lost.
+    table.insert_with_handle(3, 3).unwrap();
+    assert_eq!(table.handles().free_len(), 3);
+    let issued = (0..4).map(|i| table.push(i).handle()).collect::<Vec<_>>();
+    assert_eq!(issued, vec![1, 2, 4, 6]);
+    assert_eq!(table.handles().free_len(), 0);
+}
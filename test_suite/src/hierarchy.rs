@@ -0,0 +1,115 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{
+    contextual, persian_rug, Accessor, Context, HierarchyAccessor, HierarchyMutator, Proxy,
+    TreeNode,
+};
+
+#[contextual(Rug)]
+struct Section {
+    title: &'static str,
+    parent: Option<Proxy<Section>>,
+}
+
+impl TreeNode for Section {
+    fn parent(&self) -> Option<Proxy<Self>> {
+        self.parent
+    }
+    fn set_parent(&mut self, parent: Option<Proxy<Self>>) {
+        self.parent = parent;
+    }
+}
+
+#[persian_rug]
+struct Rug(#[table] Section);
+
+fn titles<'a, A: Accessor<Context = Rug>>(
+    access: &'a A,
+    sections: impl Iterator<Item = Proxy<Section>> + 'a,
+) -> Vec<&'static str> {
+    sections.map(|s| access.get(&s).title).collect()
+}
+
+fn add(r: &mut Rug, title: &'static str, parent: Option<Proxy<Section>>) -> Proxy<Section> {
+    r.add(Section { title, parent })
+}
+
+#[test]
+fn test_walk() {
+    let mut r = Rug(Default::default());
+    let intro = add(&mut r, "intro", None);
+    let body = add(&mut r, "body", None);
+    let methods = add(&mut r, "methods", Some(body));
+    let results = add(&mut r, "results", Some(body));
+    let tables = add(&mut r, "tables", Some(results));
+    let setup = add(&mut r, "setup", Some(methods));
+
+    let access = &r;
+    assert_eq!(titles(&access, access.roots()), vec!["intro", "body"]);
+    assert_eq!(
+        titles(&access, access.children(&body)),
+        vec!["methods", "results"]
+    );
+    assert_eq!(access.children(&intro).count(), 0);
+    assert_eq!(
+        titles(&access, access.iter_subtree(&body)),
+        vec!["body", "methods", "setup", "results", "tables"]
+    );
+    assert_eq!(titles(&access, access.iter_subtree(&intro)), vec!["intro"]);
+    assert_eq!(
+        titles(&access, access.ancestors(&tables)),
+        vec!["results", "body"]
+    );
+    assert_eq!(access.ancestors(&body).count(), 0);
+    assert_eq!(access.ancestors(&setup).last(), Some(body));
+}
+
+#[test]
+fn test_reparent() {
+    let mut r = Rug(Default::default());
+    let a = add(&mut r, "a", None);
+    let b = add(&mut r, "b", Some(a));
+    let c = add(&mut r, "c", Some(b));
+    let d = add(&mut r, "d", None);
+
+    let mut access = &mut r;
+    assert_eq!(access.reparent(&c, Some(d)), Ok(Some(b)));
+    assert_eq!(access.reparent(&b, None), Ok(Some(a)));
+    assert_eq!(access.reparent(&a, Some(c)), Ok(None));
+
+    let access = &r;
+    assert_eq!(titles(&access, access.roots()), vec!["b", "d"]);
+    assert_eq!(
+        titles(&access, access.iter_subtree(&d)),
+        vec!["d", "c", "a"]
+    );
+}
+
+#[test]
+fn test_reparent_cycle() {
+    let mut r = Rug(Default::default());
+    let a = add(&mut r, "a", None);
+    let b = add(&mut r, "b", Some(a));
+    let c = add(&mut r, "c", Some(b));
+
+    let mut access = &mut r;
+    let err = access.reparent(&a, Some(c)).unwrap_err();
+    assert_eq!(err.child(), a.handle());
+    assert_eq!(err.parent(), c.handle());
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "cannot move object {} under object {}, which is in its subtree",
+            a.handle(),
+            c.handle()
+        )
+    );
+    assert!(access.reparent(&b, Some(b)).is_err());
+
+    let access = &r;
+    assert_eq!(
+        titles(&access, access.iter_subtree(&a)),
+        vec!["a", "b", "c"]
+    );
+}
@@ -0,0 +1,101 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::history::History;
+use persian_rug::{contextual, persian_rug, Context, Mutator, Proxy};
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[contextual(Rug)]
+struct Bar {
+    foo: Proxy<Foo>,
+}
+
+#[persian_rug]
+struct Rug(#[table] Foo, #[table] Bar);
+
+fn values(history: &History<Rug>, p: &Proxy<Foo>) -> Vec<(u64, i32)> {
+    history
+        .versions(p)
+        .map(|(tick, foo)| (tick, foo.a))
+        .collect()
+}
+
+#[test]
+fn test_history() {
+    let mut r = Rug(Default::default(), Default::default());
+    let mut history = History::new().track::<Foo>();
+
+    let (f1, f2) = {
+        let mut h = history.historian(&mut r);
+        let f1 = h.add(Foo { a: 1 });
+        let f2 = h.add(Foo { a: 2 });
+        h.add(Bar { foo: f1 });
+        (f1, f2)
+    };
+    assert_eq!(history.tick(), 0);
+    assert_eq!(history.advance(), 1);
+    {
+        let mut h = history.historian(&mut r);
+        h.get_mut(&f1).a = 10;
+        h.get_mut(&f1).a = 11;
+    }
+    history.advance();
+    history.advance();
+    for foo in history.historian(&mut r).get_iter_mut::<Foo>() {
+        foo.a *= 2;
+    }
+
+    assert_eq!(values(&history, &f1), vec![(0, 1), (1, 11), (3, 22)]);
+    assert_eq!(values(&history, &f2), vec![(0, 2), (3, 4)]);
+    assert_eq!(history.get_at(&f1, 0), Some(&Foo { a: 1 }));
+    assert_eq!(history.get_at(&f1, 2), Some(&Foo { a: 11 }));
+    assert_eq!(history.get_at(&f1, 7), Some(&Foo { a: 22 }));
+    assert_eq!(history.get_at(&f2, 1), Some(&Foo { a: 2 }));
+
+    let bars = r.get_proxy_iter::<Bar>().copied().collect::<Vec<_>>();
+    assert_eq!(history.versions(&bars[0]).count(), 0);
+}
+
+#[test]
+fn test_history_late() {
+    let mut r = Rug(Default::default(), Default::default());
+    let mut history = History::new().track::<Foo>();
+    history.advance();
+    let f = history.historian(&mut r).add(Foo { a: 1 });
+    assert_eq!(history.get_at(&f, 0), None);
+    assert_eq!(history.get_at(&f, 1), Some(&Foo { a: 1 }));
+
+    history.advance();
+    r.get_mut(&f).a = 5;
+    history.record(&r, &f);
+    assert_eq!(values(&history, &f), vec![(1, 1), (2, 5)]);
+
+    history.clear();
+    assert_eq!(history.get_at(&f, 2), None);
+    assert_eq!(history.tick(), 2);
+}
+
+#[test]
+fn test_history_bounded() {
+    let mut r = Rug(Default::default(), Default::default());
+    let mut history = History::bounded(2).track::<Foo>();
+    let f = history.historian(&mut r).add(Foo { a: 0 });
+    for a in 1..5 {
+        history.advance();
+        history.historian(&mut r).get_mut(&f).a = a;
+    }
+    assert_eq!(values(&history, &f), vec![(3, 3), (4, 4)]);
+    assert_eq!(history.get_at(&f, 2), None);
+    assert_eq!(history.get_at(&f, 3), Some(&Foo { a: 3 }));
+}
+
+#[test]
+#[should_panic(expected = "a history must keep at least one version")]
+fn test_history_bounded_zero() {
+    History::<Rug>::bounded(0);
+}
@@ -0,0 +1,117 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, implicit, persian_rug, Context, Proxy};
+
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[contextual(Other)]
+struct Bar {
+    foo: Option<Proxy<Foo>>,
+}
+
+#[persian_rug]
+struct Rug(#[table] Foo);
+
+#[persian_rug]
+struct Other(#[table] Bar);
+
+#[test]
+fn test_current() {
+    assert!(!implicit::has_current::<Rug>());
+    assert!(implicit::set_current(Rug(Default::default())).is_none());
+    assert!(implicit::has_current::<Rug>());
+
+    let f1 = implicit::add(Foo { a: 1 });
+    let f2 = implicit::add(Foo { a: 2 });
+    implicit::with_current_mut(|mutator: &mut Rug| {
+        mutator.get_mut(&f2).a = 3;
+    });
+    let values = implicit::with_current(|access: &Rug| {
+        access
+            .get_iter::<Foo>()
+            .map(|foo| foo.a)
+            .collect::<Vec<_>>()
+    });
+    assert_eq!(values, vec![1, 3]);
+
+    let r = implicit::take_current::<Rug>().unwrap();
+    assert!(!implicit::has_current::<Rug>());
+    assert_eq!(r.get(&f1).a, 1);
+    assert!(implicit::take_current::<Rug>().is_none());
+}
+
+#[test]
+fn test_replace() {
+    let mut first = Rug(Default::default());
+    first.add(Foo { a: 1 });
+    implicit::set_current(first);
+
+    let previous = implicit::set_current(Rug(Default::default())).unwrap();
+    assert_eq!(previous.get_iter::<Foo>().count(), 1);
+    assert_eq!(
+        implicit::with_current(|access: &Rug| access.get_iter::<Foo>().count()),
+        0
+    );
+    implicit::take_current::<Rug>();
+}
+
+#[test]
+fn test_several_types() {
+    implicit::set_current(Rug(Default::default()));
+    implicit::set_current(Other(Default::default()));
+
+    // Contexts of different types can be used at the same time.
+    let b = implicit::with_current(|access: &Rug| {
+        let foos = access.get_proxy_iter::<Foo>().count();
+        assert_eq!(foos, 0);
+        implicit::add(Bar { foo: None })
+    });
+    let f = implicit::add(Foo { a: 5 });
+    implicit::with_current_mut(|mutator: &mut Other| {
+        mutator.get_mut(&b).foo = Some(f);
+    });
+    assert_eq!(
+        implicit::with_current(|access: &Other| access.get(&b).foo),
+        Some(f)
+    );
+}
+
+#[test]
+fn test_per_thread() {
+    implicit::set_current(Rug(Default::default()));
+    implicit::add(Foo { a: 1 });
+    std::thread::spawn(|| {
+        assert!(!implicit::has_current::<Rug>());
+    })
+    .join()
+    .unwrap();
+    assert!(implicit::has_current::<Rug>());
+}
+
+#[test]
+#[should_panic(expected = "no current context")]
+fn test_no_current() {
+    implicit::add(Foo { a: 1 });
+}
+
+#[test]
+#[should_panic]
+fn test_reentrant() {
+    implicit::set_current(Rug(Default::default()));
+    implicit::with_current(|_: &Rug| implicit::add(Foo { a: 1 }));
+}
+
+#[test]
+fn test_take_while_borrowed() {
+    implicit::set_current(Rug(Default::default()));
+    let res = std::panic::catch_unwind(|| {
+        implicit::with_current(|_: &Rug| implicit::take_current::<Rug>());
+    });
+    assert!(res.is_err());
+    // The context is still current after the failed attempt.
+    assert!(implicit::has_current::<Rug>());
+}
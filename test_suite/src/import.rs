@@ -0,0 +1,221 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+
+use persian_rug::handles::Random;
+use persian_rug::{contextual, import, persian_rug, Context, Proxy, Table};
+
+struct OldNode {
+    value: u32,
+    children: Vec<Rc<OldNode>>,
+    parent: RefCell<Weak<OldNode>>,
+}
+
+fn old(value: u32, children: Vec<Rc<OldNode>>) -> Rc<OldNode> {
+    let node = Rc::new(OldNode {
+        value,
+        children,
+        parent: RefCell::new(Weak::new()),
+    });
+    for child in node.children.iter() {
+        *child.parent.borrow_mut() = Rc::downgrade(&node);
+    }
+    node
+}
+
+#[contextual(Rug)]
+struct Node {
+    value: u32,
+    children: Vec<Proxy<Node>>,
+    parent: Option<Proxy<Node>>,
+}
+
+#[contextual(Rug)]
+struct Item {
+    name: &'static str,
+    next: Option<Proxy<Item>>,
+}
+
+#[persian_rug]
+struct Rug(#[table] Node, #[table] Item);
+
+fn new_rug() -> Rug {
+    Rug(Default::default(), Default::default())
+}
+
+fn convert(node: &Rc<OldNode>, imported: &import::Imported<Rc<OldNode>, Node>) -> Node {
+    Node {
+        value: node.value,
+        children: node
+            .children
+            .iter()
+            .map(|child| imported.proxy(child).unwrap())
+            .collect(),
+        parent: node
+            .parent
+            .borrow()
+            .upgrade()
+            .and_then(|parent| imported.proxy(&parent)),
+    }
+}
+
+#[test]
+fn test_import_tree() {
+    let a = old(1, Vec::new());
+    let b = old(2, Vec::new());
+    let root = old(3, vec![a.clone(), b.clone()]);
+
+    let mut r = new_rug();
+    r.add(Node {
+        value: 0,
+        children: Vec::new(),
+        parent: None,
+    });
+    let imported = import::import(
+        &mut r,
+        [root.clone()],
+        |node| node.children.clone(),
+        convert,
+    )
+    .unwrap();
+    assert_eq!(imported.len(), 3);
+    assert!(!imported.is_empty());
+
+    let new_root = imported.roots()[0];
+    assert_eq!(Some(new_root), imported.proxy(&root));
+    assert_eq!(r.get(&new_root).value, 3);
+    let children = r.get(&new_root).children.clone();
+    assert_eq!(
+        children.iter().map(|c| r.get(c).value).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    for child in children {
+        assert_eq!(r.get(&child).parent, Some(new_root));
+    }
+    assert_eq!(
+        imported
+            .iter()
+            .map(|(node, p)| (node.value, r.get(&p).value))
+            .collect::<Vec<_>>(),
+        vec![(3, 3), (1, 1), (2, 2)]
+    );
+    assert_eq!(imported.proxy(&old(1, Vec::new())), None);
+}
+
+#[test]
+fn test_import_shared() {
+    // A diamond, given twice as a root.
+    let bottom = old(1, Vec::new());
+    let left = old(2, vec![bottom.clone()]);
+    let right = old(3, vec![bottom.clone()]);
+    let top = old(4, vec![left, right]);
+
+    let mut r = new_rug();
+    let imported = import::import(
+        &mut r,
+        [top.clone(), bottom.clone(), top.clone()],
+        |node| node.children.clone(),
+        convert,
+    )
+    .unwrap();
+    assert_eq!(imported.len(), 4);
+    assert_eq!(r.get_iter::<Node>().count(), 4);
+    assert_eq!(imported.roots().len(), 3);
+    assert_eq!(imported.roots()[0], imported.roots()[2]);
+    let bottom = imported.roots()[1];
+    for p in r.get(&imported.roots()[0]).children.iter() {
+        assert_eq!(r.get(p).children, vec![bottom]);
+    }
+}
+
+struct OldItem {
+    name: &'static str,
+    next: std::sync::Mutex<Option<Arc<OldItem>>>,
+}
+
+#[test]
+fn test_import_cycle() {
+    let a = Arc::new(OldItem {
+        name: "a",
+        next: Default::default(),
+    });
+    let b = Arc::new(OldItem {
+        name: "b",
+        next: std::sync::Mutex::new(Some(a.clone())),
+    });
+    *a.next.lock().unwrap() = Some(b.clone());
+
+    let mut r = new_rug();
+    let imported = import::import(
+        &mut r,
+        [a.clone()],
+        |item| item.next.lock().unwrap().clone(),
+        |item, imported| Item {
+            name: item.name,
+            next: item
+                .next
+                .lock()
+                .unwrap()
+                .as_ref()
+                .and_then(|next| imported.proxy(next)),
+        },
+    )
+    .unwrap();
+    // Break the cycle, so the items are freed.
+    *a.next.lock().unwrap() = None;
+
+    assert_eq!(imported.len(), 2);
+    let a = imported.roots()[0];
+    let b = r.get(&a).next.unwrap();
+    assert_eq!(r.get(&b).name, "b");
+    assert_eq!(r.get(&b).next, Some(a));
+}
+
+#[contextual(RandomRug)]
+struct Link {
+    name: &'static str,
+    next: Option<Proxy<Link>>,
+}
+
+#[persian_rug(handles = Random)]
+struct RandomRug(#[table] Link);
+
+#[test]
+fn test_import_random_handles() {
+    let a = Arc::new(OldItem {
+        name: "a",
+        next: Default::default(),
+    });
+    let b = Arc::new(OldItem {
+        name: "b",
+        next: std::sync::Mutex::new(Some(a.clone())),
+    });
+    *a.next.lock().unwrap() = Some(b.clone());
+
+    let mut r = RandomRug(Table::with_handles(Random::new(3)));
+    let imported = import::import(
+        &mut r,
+        [a.clone()],
+        |item| item.next.lock().unwrap().clone(),
+        |item, imported| Link {
+            name: item.name,
+            next: item
+                .next
+                .lock()
+                .unwrap()
+                .as_ref()
+                .and_then(|next| imported.proxy(next)),
+        },
+    )
+    .unwrap();
+    *a.next.lock().unwrap() = None;
+
+    let a = imported.roots()[0];
+    let b = r.get(&a).next.unwrap();
+    assert_eq!(r.get(&b).name, "b");
+    assert_eq!(r.get(&b).next, Some(a));
+    assert_eq!(r.get_iter::<Link>().count(), 2);
+}
@@ -0,0 +1,127 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::egui;
+use persian_rug::inspector::Inspector;
+use persian_rug::serde::Serialize;
+use persian_rug::serde_json::json;
+use persian_rug::{contextual, persian_rug, Context, Proxy};
+
+#[derive(Serialize)]
+#[serde(crate = "persian_rug::serde")]
+#[contextual(Rug)]
+struct Person {
+    name: String,
+    manager: Option<Proxy<Person>>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "persian_rug::serde")]
+#[contextual(Rug)]
+struct Team(String, Vec<Proxy<Person>>);
+
+#[persian_rug(json)]
+struct Rug {
+    #[table]
+    people: Person,
+    #[table]
+    teams: Team,
+}
+
+fn new_rug() -> Rug {
+    Rug {
+        people: Default::default(),
+        teams: Default::default(),
+    }
+}
+
+fn draw(inspector: &mut Inspector, r: &Rug) {
+    let ctx = egui::Context::default();
+    let _ = ctx.run(Default::default(), |ctx| {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            inspector.show(ui, r);
+        });
+    });
+}
+
+#[test]
+fn test_history() {
+    let mut r = new_rug();
+    let alice = r.add(Person {
+        name: "Alice".to_string(),
+        manager: None,
+    });
+    let bob = r.add(Person {
+        name: "Bob".to_string(),
+        manager: Some(alice),
+    });
+
+    let mut inspector = Inspector::new().with_history(2);
+    inspector.observe(&r);
+    r.get_mut(&bob).name = "Robert".to_string();
+    inspector.observe(&r);
+    inspector.observe(&r);
+    r.get_mut(&bob).manager = None;
+    inspector.observe(&r);
+    r.people.mark_deleted(&alice);
+    inspector.observe(&r);
+    assert_eq!(inspector.frame(), 5);
+
+    let alice_history = inspector
+        .history(&alice)
+        .map(|change| (change.frame(), change.value().cloned()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        alice_history,
+        vec![
+            (1, Some(json!({ "name": "Alice", "manager": null }))),
+            (5, None),
+        ]
+    );
+
+    let bob_history = inspector
+        .history(&bob)
+        .map(|change| (change.frame(), change.value().cloned()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        bob_history,
+        vec![
+            (2, Some(json!({ "name": "Robert", "manager": 0 }))),
+            (4, Some(json!({ "name": "Robert", "manager": null }))),
+        ]
+    );
+}
+
+#[test]
+fn test_show() {
+    let mut r = new_rug();
+    let alice = r.add(Person {
+        name: "Alice".to_string(),
+        manager: None,
+    });
+    let bob = r.add(Person {
+        name: "Bob".to_string(),
+        manager: Some(alice),
+    });
+    let team = r.add(Team("Core".to_string(), vec![alice, bob]));
+
+    let mut inspector = Inspector::default();
+    assert_eq!(inspector.selected(), None);
+    draw(&mut inspector, &r);
+
+    inspector.select(&bob);
+    draw(&mut inspector, &r);
+    assert_eq!(
+        inspector.selected(),
+        Some((std::any::type_name::<Person>(), bob.handle()))
+    );
+
+    inspector.select(&team);
+    draw(&mut inspector, &r);
+    assert_eq!(
+        inspector.selected(),
+        Some((std::any::type_name::<Team>(), team.handle()))
+    );
+    assert_eq!(inspector.frame(), 3);
+    assert_eq!(inspector.history(&team).count(), 1);
+}
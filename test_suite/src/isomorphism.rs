@@ -0,0 +1,509 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::isomorphism::{
+    context_difference, graph_difference, graph_eq, Difference, Isomorphic,
+};
+use persian_rug::{
+    assert_graph_eq, assert_graph_ne, contextual, persian_rug, AnyProxy, Context, Edge, Proxy,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[contextual(Rug)]
+struct Node {
+    name: String,
+    #[link]
+    next: Option<Proxy<Node>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[contextual(Rug)]
+struct Group {
+    #[link]
+    members: Vec<Proxy<Node>>,
+}
+
+#[persian_rug(isomorphism)]
+struct Rug {
+    #[table]
+    nodes: Node,
+    #[table(arena)]
+    groups: Group,
+    #[table]
+    edges: Edge<Node, Group, u32>,
+}
+
+fn new_rug() -> Rug {
+    Rug {
+        nodes: Default::default(),
+        groups: Default::default(),
+        edges: Default::default(),
+    }
+}
+
+fn node(name: &str) -> Node {
+    Node {
+        name: name.to_string(),
+        next: None,
+    }
+}
+
+/// Add a ring of identical nodes, returning its members.
+fn ring(r: &mut Rug, len: usize) -> Vec<Proxy<Node>> {
+    let members = (0..len).map(|_| r.add(node("ring"))).collect::<Vec<_>>();
+    for (ix, p) in members.iter().enumerate() {
+        r.get_mut(p).next = Some(members[(ix + 1) % len]);
+    }
+    members
+}
+
+#[test]
+fn test_empty() {
+    assert!(new_rug().is_isomorphic(&new_rug()));
+}
+
+#[test]
+fn test_renumbered() {
+    let mut a = new_rug();
+    let x = a.add(node("x"));
+    let y = a.add(Node {
+        name: "y".to_string(),
+        next: Some(x),
+    });
+    let g = a.add(Group {
+        members: vec![x, y],
+    });
+    a.add(Edge::new(y, g, 7));
+
+    let mut b = new_rug();
+    let g = b.add(Group {
+        members: Vec::new(),
+    });
+    let y = b.add(node("y"));
+    let x = b.add(node("x"));
+    b.get_mut(&y).next = Some(x);
+    b.get_mut(&g).members = vec![x, y];
+    b.add(Edge::new(y, g, 7));
+
+    assert!(a.is_isomorphic(&b));
+    assert!(b.is_isomorphic(&a));
+    assert!(a.is_isomorphic(&a));
+}
+
+#[test]
+fn test_contents_differ() {
+    let mut a = new_rug();
+    a.add(node("x"));
+    let mut b = new_rug();
+    b.add(node("y"));
+    assert!(!a.is_isomorphic(&b));
+
+    b.add(node("x"));
+    assert!(!a.is_isomorphic(&b));
+}
+
+#[test]
+fn test_links_differ() {
+    let mut a = new_rug();
+    let x = a.add(node("x"));
+    let y = a.add(node("y"));
+    a.add(Group {
+        members: vec![x, y],
+    });
+
+    let mut b = new_rug();
+    let x = b.add(node("x"));
+    let y = b.add(node("y"));
+    let g = b.add(Group {
+        members: vec![y, x],
+    });
+    assert!(!a.is_isomorphic(&b));
+
+    b.get_mut(&g).members.reverse();
+    assert!(a.is_isomorphic(&b));
+
+    b.get_mut(&x).next = Some(y);
+    assert!(!a.is_isomorphic(&b));
+}
+
+#[test]
+fn test_edge_payload() {
+    let mut a = new_rug();
+    let x = a.add(node("x"));
+    let g = a.add(Group { members: vec![x] });
+    a.add(Edge::new(x, g, 1));
+
+    let mut b = new_rug();
+    let x = b.add(node("x"));
+    let g = b.add(Group { members: vec![x] });
+    let e = b.add(Edge::new(x, g, 2));
+    assert!(!a.is_isomorphic(&b));
+
+    b.get_mut(&e).payload = 1;
+    assert!(a.is_isomorphic(&b));
+}
+
+#[test]
+fn test_symmetric() {
+    // Every node in these graphs looks alike until one is singled
+    // out, so the check has to search.
+    let mut a = new_rug();
+    ring(&mut a, 4);
+    let mut b = new_rug();
+    ring(&mut b, 2);
+    ring(&mut b, 2);
+    assert!(!a.is_isomorphic(&b));
+
+    let mut c = new_rug();
+    ring(&mut c, 2);
+    ring(&mut c, 2);
+    assert!(b.is_isomorphic(&c));
+
+    let mut d = new_rug();
+    let members = ring(&mut d, 4);
+    d.get_mut(&members[0]).name = "start".to_string();
+    let mut e = new_rug();
+    let members = ring(&mut e, 4);
+    e.get_mut(&members[2]).name = "start".to_string();
+    assert!(d.is_isomorphic(&e));
+}
+
+#[test]
+fn test_interchangeable() {
+    let mut a = new_rug();
+    let mut b = new_rug();
+    let x = a.add(node("x"));
+    for _ in 0..2000 {
+        a.add(Node {
+            name: "leaf".to_string(),
+            next: Some(x),
+        });
+    }
+    for _ in 0..2000 {
+        b.add(node("leaf"));
+    }
+    let x = b.add(node("x"));
+    for p in b.get_proxy_iter::<Node>().copied().collect::<Vec<_>>() {
+        if p != x {
+            b.get_mut(&p).next = Some(x);
+        }
+    }
+    assert!(a.is_isomorphic(&b));
+
+    b.add(node("leaf"));
+    assert!(!a.is_isomorphic(&b));
+}
+
+fn assert_identical(a: &Rug, b: &Rug) {
+    assert_eq!(
+        a.get_iter::<Node>().collect::<Vec<_>>(),
+        b.get_iter::<Node>().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        a.get_iter::<Group>().collect::<Vec<_>>(),
+        b.get_iter::<Group>().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        a.get_iter::<Edge<Node, Group, u32>>().collect::<Vec<_>>(),
+        b.get_iter::<Edge<Node, Group, u32>>().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_canonicalize() {
+    let mut a = new_rug();
+    let x = a.add(node("x"));
+    let y = a.add(Node {
+        name: "y".to_string(),
+        next: Some(x),
+    });
+    let g = a.add(Group {
+        members: vec![y, x],
+    });
+    a.add(Edge::new(x, g, 3));
+
+    let mut b = new_rug();
+    let g = b.add(Group {
+        members: Vec::new(),
+    });
+    let y = b.add(node("y"));
+    let x = b.add(node("x"));
+    b.add(Edge::new(x, g, 3));
+    b.get_mut(&y).next = Some(x);
+    b.get_mut(&g).members = vec![y, x];
+
+    let renumbering = b.canonicalize();
+    a.canonicalize();
+    assert_identical(&a, &b);
+    assert!(a.is_isomorphic(&b));
+
+    let x = renumbering.get(&x).unwrap();
+    let y = renumbering.get(&y).unwrap();
+    let g = renumbering.get(&g).unwrap();
+    assert_eq!(b.get(&x).name, "x");
+    assert_eq!(b.get(&y).next, Some(x));
+    assert_eq!(b.get(&g).members, vec![y, x]);
+    assert_eq!(
+        b.get_proxy_iter::<Node>().copied().collect::<Vec<_>>(),
+        vec![x, y]
+    );
+}
+
+#[test]
+fn test_canonicalize_symmetric() {
+    let mut a = new_rug();
+    let members = ring(&mut a, 5);
+    a.get_mut(&members[3]).name = "start".to_string();
+    ring(&mut a, 2);
+
+    let mut b = new_rug();
+    ring(&mut b, 2);
+    let members = ring(&mut b, 5);
+    b.get_mut(&members[1]).name = "start".to_string();
+
+    a.canonicalize();
+    b.canonicalize();
+    assert_identical(&a, &b);
+
+    let mut c = new_rug();
+    ring(&mut c, 3);
+    ring(&mut c, 2);
+    let mut d = new_rug();
+    ring(&mut d, 2);
+    ring(&mut d, 3);
+    c.canonicalize();
+    d.canonicalize();
+    assert_identical(&c, &d);
+
+    let mut e = new_rug();
+    let mut f = new_rug();
+    for _ in 0..50 {
+        ring(&mut e, 2);
+        ring(&mut f, 3);
+    }
+    for _ in 0..50 {
+        ring(&mut e, 3);
+        ring(&mut f, 2);
+    }
+    assert!(e.is_isomorphic(&f));
+    e.canonicalize();
+    f.canonicalize();
+    assert_identical(&e, &f);
+}
+
+#[test]
+fn test_canonicalize_twice() {
+    let mut a = new_rug();
+    let x = a.add(node("x"));
+    a.add(node("a"));
+    a.add(Group {
+        members: vec![x, x],
+    });
+    a.canonicalize();
+    let before = a.get_iter::<Node>().cloned().collect::<Vec<_>>();
+    let renumbering = a.canonicalize();
+    assert_eq!(a.get_iter::<Node>().cloned().collect::<Vec<_>>(), before);
+    for p in a.get_proxy_iter::<Node>() {
+        assert_eq!(renumbering.get(p), Some(*p));
+    }
+}
+
+#[test]
+fn test_graph_eq_rings() {
+    let mut a = new_rug();
+    let three = ring(&mut a, 3);
+    let four = ring(&mut a, 4);
+
+    let mut b = new_rug();
+    b.add(node("unrelated"));
+    let other = ring(&mut b, 3);
+
+    assert!(graph_eq(&a, &three[0], &b, &other[1]));
+    assert!(!graph_eq(&a, &four[0], &b, &other[0]));
+    assert!(graph_eq(&a, &four[0], &a, &four[2]));
+}
+
+#[test]
+fn test_graph_eq_shared_links() {
+    // Two links to one object are not the same as links to two equal
+    // objects.
+    let mut a = new_rug();
+    let x = a.add(node("x"));
+    let shared = a.add(Group {
+        members: vec![x, x],
+    });
+
+    let mut b = new_rug();
+    let x = b.add(node("x"));
+    let y = b.add(node("x"));
+    let separate = b.add(Group {
+        members: vec![x, y],
+    });
+
+    assert!(!graph_eq(&a, &shared, &b, &separate));
+    assert!(graph_eq(&a, &shared, &a, &shared));
+}
+
+#[test]
+fn test_graph_eq_reachable_only() {
+    let mut a = new_rug();
+    let x = a.add(node("x"));
+    let tail = a.add(node("tail"));
+    a.get_mut(&x).next = Some(tail);
+
+    let mut b = new_rug();
+    let tail2 = b.add(node("tail"));
+    let x2 = b.add(node("x"));
+    b.get_mut(&x2).next = Some(tail2);
+    // Objects which cannot be reached from the roots are ignored.
+    b.add(node("y"));
+    assert!(graph_eq(&a, &x, &b, &x2));
+
+    b.get_mut(&tail2).name = "end".to_string();
+    assert!(!graph_eq(&a, &x, &b, &x2));
+
+    b.get_mut(&tail2).name = "tail".to_string();
+    b.get_mut(&x2).next = None;
+    assert!(!graph_eq(&a, &x, &b, &x2));
+}
+
+#[test]
+fn test_graph_difference_path() {
+    let mut a = new_rug();
+    let x = a.add(node("x"));
+    let y = a.add(node("y"));
+    let z = a.add(node("z"));
+    a.get_mut(&y).next = Some(z);
+    let g = a.add(Group {
+        members: vec![x, y],
+    });
+
+    let mut b = new_rug();
+    let z2 = b.add(node("w"));
+    let y2 = b.add(node("y"));
+    let x2 = b.add(node("x"));
+    b.get_mut(&y2).next = Some(z2);
+    let g2 = b.add(Group {
+        members: vec![x2, y2],
+    });
+
+    let difference = graph_difference(&a, &g, &b, &g2).unwrap();
+    assert_eq!(difference.kind(), Difference::Contents);
+    let path = difference.path();
+    assert_eq!(path.len(), 3);
+    assert_eq!(path[0].via, None);
+    assert_eq!(path[0].left, Some(AnyProxy::from(g)));
+    assert_eq!(path[0].right, Some(AnyProxy::from(g2)));
+    assert_eq!(path[1].via.as_deref(), Some("members[1]"));
+    assert_eq!(path[2].via.as_deref(), Some("next"));
+    assert_eq!(path[2].left, Some(AnyProxy::from(z)));
+    assert_eq!(path[2].right, Some(AnyProxy::from(z2)));
+
+    let message = difference.to_string();
+    assert!(message.starts_with("objects have different contents\n"));
+    assert!(message.contains("via members[1]: "));
+    assert!(message.ends_with(
+        "via next: test_suite::isomorphism::Node(2) and test_suite::isomorphism::Node(0)"
+    ));
+
+    b.get_mut(&z2).name = "z".to_string();
+    assert_eq!(graph_difference(&a, &g, &b, &g2), None);
+}
+
+#[test]
+fn test_graph_difference_kinds() {
+    let mut a = new_rug();
+    let x = a.add(node("x"));
+    let shared = a.add(Group {
+        members: vec![x, x],
+    });
+    let dangling = a.add(Group {
+        members: vec![Proxy::from_handle(99)],
+    });
+
+    let mut b = new_rug();
+    let x = b.add(node("x"));
+    let y = b.add(node("x"));
+    let separate = b.add(Group {
+        members: vec![x, y],
+    });
+
+    let difference = graph_difference(&a, &shared, &b, &separate).unwrap();
+    assert_eq!(difference.kind(), Difference::Shared);
+    assert_eq!(
+        difference.path().last().unwrap().via.as_deref(),
+        Some("members[1]")
+    );
+
+    let present = b.add(Group { members: vec![x] });
+    let difference = graph_difference(&a, &dangling, &b, &present).unwrap();
+    assert_eq!(difference.kind(), Difference::Missing);
+    assert_eq!(difference.path().last().unwrap().left, None);
+    assert!(difference
+        .to_string()
+        .contains("via members: <missing> and "));
+}
+
+#[test]
+fn test_context_difference() {
+    let mut a = new_rug();
+    let mut b = new_rug();
+    ring(&mut a, 3);
+    ring(&mut b, 3);
+    assert_eq!(context_difference(&a, &b), None);
+    assert_graph_eq!(&a, &b);
+
+    let extra = ring(&mut b, 2);
+    let difference = context_difference(&a, &b).unwrap();
+    assert!(!difference.in_left());
+    assert_eq!(difference.size(), 2);
+    assert!(extra.iter().any(|p| difference.object() == *p));
+    assert!(difference
+        .to_string()
+        .starts_with("the 2 objects connected to test_suite::isomorphism::Node("));
+    assert_graph_ne!(&a, &b);
+
+    let lone = a.add(node("lone"));
+    let difference = context_difference(&a, &b).unwrap();
+    assert!(difference.in_left());
+    assert_eq!(difference.object(), lone);
+    assert_eq!(
+        difference.to_string(),
+        format!(
+            "test_suite::isomorphism::Node({}) in the left context has no counterpart in the right context",
+            lone.handle()
+        )
+    );
+}
+
+#[test]
+fn test_assert_graph_eq() {
+    let mut a = new_rug();
+    let mut b = new_rug();
+    let x = ring(&mut a, 3);
+    let y = ring(&mut b, 3);
+    assert_graph_eq!(&a, &x[0], &b, &y[2]);
+
+    b.get_mut(&y[1]).name = "changed".to_string();
+    assert_graph_ne!(&a, &x[0], &b, &y[2]);
+}
+
+#[test]
+#[should_panic(expected = "graphs differ: objects have different contents\n  from ")]
+fn test_assert_graph_eq_fails() {
+    let mut a = new_rug();
+    let mut b = new_rug();
+    let x = ring(&mut a, 3);
+    let y = ring(&mut b, 3);
+    b.get_mut(&y[0]).name = "changed".to_string();
+    assert_graph_eq!(&a, &x[0], &b, &y[2]);
+}
+
+#[test]
+#[should_panic(expected = "contexts differ: ")]
+fn test_assert_graph_eq_contexts_fails() {
+    let mut a = new_rug();
+    let b = new_rug();
+    a.add(node("x"));
+    assert_graph_eq!(&a, &b);
+}
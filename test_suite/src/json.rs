@@ -0,0 +1,193 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::any::type_name;
+use std::collections::BTreeMap;
+
+use persian_rug::json::{DebugJson, ExportSchema};
+use persian_rug::schemars::JsonSchema;
+use persian_rug::serde::Serialize;
+use persian_rug::serde_json::{self, json};
+use persian_rug::{contextual, persian_rug, Context, Proxy};
+
+#[derive(Serialize)]
+#[serde(crate = "persian_rug::serde", bound = "")]
+#[contextual(C)]
+struct Tag<C: Context> {
+    name: &'static str,
+    #[serde(skip)]
+    _marker: core::marker::PhantomData<C>,
+}
+
+type TagPair<C> = (Proxy<Tag<C>>, Proxy<Tag<C>>);
+
+#[derive(Serialize)]
+#[serde(crate = "persian_rug::serde", bound = "")]
+#[contextual(C)]
+enum Item<C: Context + 'static> {
+    Note(String),
+    Tagged {
+        tags: Vec<Proxy<Tag<C>>>,
+        weights: BTreeMap<Proxy<Tag<C>>, u32>,
+    },
+    Pairs(BTreeMap<TagPair<C>, u32>),
+}
+
+#[persian_rug(json)]
+struct Rug<U: 'static> {
+    #[table]
+    tags: Tag<Rug<U>>,
+    #[table(arena)]
+    items: Item<Rug<U>>,
+    #[cfg(any())]
+    #[table]
+    unused: U,
+    marker: core::marker::PhantomData<U>,
+}
+
+#[test]
+fn test_debug_value() {
+    let mut r = Rug::<u8> {
+        tags: Default::default(),
+        items: Default::default(),
+        marker: Default::default(),
+    };
+    let red = r.add(Tag {
+        name: "red",
+        _marker: Default::default(),
+    });
+    let blue = r.add(Tag {
+        name: "blue",
+        _marker: Default::default(),
+    });
+    r.add(Item::Note("plain".to_string()));
+    r.add(Item::Tagged {
+        tags: vec![blue, red],
+        weights: [(blue, 3)].into_iter().collect(),
+    });
+
+    let value = r.to_debug_value();
+    assert_eq!(value["context"], type_name::<Rug<u8>>());
+    assert_eq!(
+        value["tables"][type_name::<Tag<Rug<u8>>>()],
+        json!([
+            { "handle": 0, "value": { "name": "red" } },
+            { "handle": 1, "value": { "name": "blue" } },
+        ])
+    );
+    assert_eq!(
+        value["tables"][type_name::<Item<Rug<u8>>>()],
+        json!([
+            { "handle": 0, "value": { "Note": "plain" } },
+            { "handle": 1, "value": { "Tagged": { "tags": [1, 0], "weights": { "1": 3 } } } },
+        ])
+    );
+    assert_eq!(value["tables"].as_object().unwrap().len(), 2);
+}
+
+#[test]
+fn test_unconvertible_object() {
+    let mut r = Rug::<u8> {
+        tags: Default::default(),
+        items: Default::default(),
+        marker: Default::default(),
+    };
+    let red = r.add(Tag {
+        name: "red",
+        _marker: Default::default(),
+    });
+    r.add(Item::Pairs([((red, red), 2)].into_iter().collect()));
+    r.add(Item::Note("after".to_string()));
+
+    // Pairs cannot be the keys of JSON objects, but the rest of the
+    // table is still converted.
+    let items = &r.to_debug_value()["tables"][type_name::<Item<Rug<u8>>>()];
+    assert!(items[0]["value"]["error"].is_string());
+    assert_eq!(items[1]["value"], json!({ "Note": "after" }));
+}
+
+#[derive(Serialize, JsonSchema)]
+#[serde(crate = "persian_rug::serde")]
+#[schemars(crate = "persian_rug::schemars")]
+#[contextual(Library)]
+struct Author {
+    name: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+#[serde(crate = "persian_rug::serde")]
+#[schemars(crate = "persian_rug::schemars")]
+#[contextual(Library)]
+struct Book {
+    title: String,
+    authors: Vec<Proxy<Author>>,
+    sequel: Option<Proxy<Book>>,
+}
+
+#[persian_rug(json, schemars)]
+struct Library {
+    #[table]
+    authors: Author,
+    #[table]
+    books: Book,
+    #[cfg(any())]
+    #[table]
+    unused: u8,
+}
+
+#[test]
+fn test_export_schema() {
+    let schema = Library::export_schema();
+    let author = type_name::<Author>();
+    let book = type_name::<Book>();
+
+    assert_eq!(schema.get("title").unwrap(), type_name::<Library>());
+    assert_eq!(
+        schema.pointer("/properties/context/const").unwrap(),
+        type_name::<Library>()
+    );
+    let tables = schema.pointer("/properties/tables").unwrap();
+    assert_eq!(tables["required"], json!([author, book]));
+    assert_eq!(tables["properties"].as_object().unwrap().len(), 2);
+    assert_eq!(
+        tables["properties"][book]["items"]["properties"]["value"],
+        json!({ "$ref": "#/$defs/Book" })
+    );
+
+    let fields = schema.pointer("/$defs/Book/properties").unwrap();
+    assert_eq!(fields["title"]["type"], "string");
+    assert_eq!(fields["authors"]["items"]["type"], "integer");
+    assert_eq!(fields["authors"]["items"]["x-persian-rug-proxy"], author);
+    let sequel = serde_json::to_string(&fields["sequel"]).unwrap();
+    assert!(sequel.contains(&format!("\"x-persian-rug-proxy\":\"{}\"", book)));
+}
+
+#[test]
+fn test_export_schema_matches_dump() {
+    let mut r = Library {
+        authors: Default::default(),
+        books: Default::default(),
+    };
+    let ann = r.add(Author {
+        name: "Ann".to_string(),
+    });
+    r.add(Book {
+        title: "First".to_string(),
+        authors: vec![ann],
+        sequel: None,
+    });
+
+    let value = r.to_debug_value();
+    let schema = Library::export_schema();
+    let tables = schema.pointer("/properties/tables/properties").unwrap();
+    let dumped = value["tables"].as_object().unwrap();
+    assert_eq!(dumped.len(), tables.as_object().unwrap().len());
+    for (ty, objects) in dumped {
+        let required = &tables[ty]["items"]["required"];
+        for object in objects.as_array().unwrap() {
+            for field in required.as_array().unwrap() {
+                assert!(object.get(field.as_str().unwrap()).is_some());
+            }
+        }
+    }
+}
@@ -1,7 +1,84 @@
 #![cfg(test)]
 #![allow(dead_code)]
 
+mod algo;
+mod aliases;
+mod archive;
+mod batch;
+mod borsh;
+mod capability;
+mod chain;
+mod checked;
+mod components;
+mod compression;
+mod csv;
+mod cursor;
+mod delete;
+mod derive_links;
+mod diff;
+mod dirty;
+mod disjoint;
+mod django;
+mod dot;
+mod edges;
+mod extract;
+mod gc;
+mod golden;
+mod handles;
+mod hierarchy;
+mod history;
+mod implicit;
+mod import;
+mod inspector;
+mod isomorphism;
+mod json;
+mod lifetimes;
+mod local_rug;
+mod marker;
+mod merge;
+mod mermaid;
+mod names;
+mod observe;
+mod owned_iter;
+mod passthrough;
+mod petgraph;
+mod profiling;
+mod proto;
+mod provenance;
+mod proxy_queue;
 mod proxy_set;
+mod query;
+mod quota;
+mod rcu;
+mod read_proxy;
+mod record;
+mod referrers;
+mod resolve;
+mod resolve_iter;
+mod resources;
+mod rewrite;
+mod sampling;
+mod sandbox;
+mod schema;
+mod search;
+mod seeding;
+mod serde;
+mod serde_diff;
+mod side_table;
+mod snapshot;
+mod soft_delete;
+mod static_rug;
+mod storage;
+mod stream;
+mod swap;
+mod table_owner;
+mod tags;
+mod transaction;
+mod transplant;
+mod versioned;
+mod view;
+mod visit;
+mod weak_proxy;
 
 use std::any::Any;
 
@@ -37,6 +114,7 @@ pub struct State {
     baz: Baz<State>,
 }
 
+#[derive(Default)]
 #[persian_rug::contextual(State2)]
 struct Foo2 {
     a: i32,
@@ -668,6 +746,14 @@ mod fn_constraints_tests {
         access.get(&access.get(&access.get(p).bar).foo).a
     }
 
+    #[persian_rug::constraints(context = State, access(Foo<State>, Bar<State>))]
+    fn state_read_proxy_foo_a<A: persian_rug::Accessor<Context = State>>(
+        p: &persian_rug::Proxy<Bar<State>>,
+        access: A,
+    ) -> i32 {
+        access.get(&access.get(p).foo).a
+    }
+
     #[test]
     fn test_fns() {
         use persian_rug::Context;
@@ -699,6 +785,8 @@ mod fn_constraints_tests {
         assert_eq!(baz_read_proxy_a(&z1, &s), 3);
         assert_eq!(baz_read_proxy_bar_a(&z1, &s), 2);
         assert_eq!(baz_read_proxy_bar_foo_a(&z1, &s), 1);
+
+        assert_eq!(state_read_proxy_foo_a(&b1, &s), 1);
     }
 }
 
@@ -1086,8 +1174,10 @@ mod mutator_tests {
     use super::*;
 
     use clone_replace::CloneReplace;
-    use persian_rug::{Mutator, Proxy};
-    use std::sync::{Mutex, RwLock};
+    use persian_rug::{Accessor, Context, Mutator, Proxy};
+    use std::borrow::Cow;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex, RwLock};
 
     fn run_mutation_test<'b, B>(mut mutator: B) -> B
     where
@@ -1165,9 +1255,65 @@ mod mutator_tests {
         assert_eq!(bazs.len(), 1);
         assert_eq!(bazs[0], z1);
 
+        // update
+        let old = mutator.update(&f1, |foo| std::mem::replace(&mut foo.a, 8));
+        assert_eq!(old, 5);
+        assert_eq!(mutator.get(&f1).a, 8);
+
+        // replace
+        let old = mutator.replace(&z1, Baz { a: 9, bar: b1 });
+        assert_eq!(old.a, 7);
+        assert_eq!(mutator.get(&z1).a, 9);
+        assert_eq!(mutator.get(&z1).bar, b1);
+
         mutator
     }
 
+    #[test]
+    fn test_context_update() {
+        let mut s = State {
+            foo: Default::default(),
+            bar: Default::default(),
+            baz: Default::default(),
+        };
+        let f = s.add(Foo {
+            _marker: Default::default(),
+            a: 1,
+        });
+        let b = s.add(Bar { a: 2, foo: f });
+
+        assert_eq!(s.update(&b, |bar| bar.a * 10), 20);
+        s.update(&b, |bar| bar.a += 1);
+        assert_eq!(s.get(&b).a, 3);
+
+        let old = s.replace(&b, Bar { a: 4, foo: f });
+        assert_eq!(old.a, 3);
+        assert_eq!(s.get(&b).a, 4);
+    }
+
+    #[test]
+    fn test_take() {
+        fn take<M: Mutator<Context = State2>>(mut mutator: M, what: &Proxy<Foo2>) -> Foo2 {
+            mutator.take(what)
+        }
+
+        let mut s = State2(
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        let f = s.add(Foo2 { a: 1 });
+
+        let taken = s.take(&f);
+        assert_eq!(taken.a, 1);
+        assert_eq!(s.get(&f).a, 0);
+
+        s.get_mut(&f).a = 2;
+        assert_eq!(take(&mut s, &f).a, 2);
+        assert_eq!(s.get(&f).a, 0);
+    }
+
     #[test]
     fn test_mut_ref() {
         let mut s = State {
@@ -1211,4 +1357,46 @@ mod mutator_tests {
 
         let _unused = run_mutation_test(s.mutate());
     }
+
+    fn empty_state() -> State {
+        State {
+            foo: Default::default(),
+            bar: Default::default(),
+            baz: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_box() {
+        let _unused = run_mutation_test(Box::new(empty_state()));
+    }
+
+    #[test]
+    fn test_cow() {
+        let s = empty_state();
+        let cow = run_mutation_test(Cow::Borrowed(&s));
+        assert!(matches!(cow, Cow::Owned(_)));
+        assert_eq!(count_foos(&*cow), 1);
+        assert_eq!(s.get_iter::<Foo<State>>().count(), 0);
+    }
+
+    fn count_foos<A: Accessor<Context = State>>(access: A) -> usize {
+        access.get_iter::<Foo<State>>().count()
+    }
+
+    #[test]
+    fn test_accessors() {
+        let mut s = empty_state();
+        s.add(Foo {
+            _marker: Default::default(),
+            a: 0,
+        });
+        assert_eq!(count_foos(Box::new(s.clone())), 1);
+        assert_eq!(count_foos(Cow::Borrowed(&s)), 1);
+        assert_eq!(count_foos(Cow::<State>::Owned(s.clone())), 1);
+        let arc = Arc::new(s.clone());
+        assert_eq!(count_foos(&arc), 1);
+        let rc = Rc::new(s);
+        assert_eq!(count_foos(&rc), 1);
+    }
 }
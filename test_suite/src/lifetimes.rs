@@ -0,0 +1,95 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, Accessor, Context, Mutator, Proxy};
+
+#[contextual(C)]
+struct Word<'a, C: Context> {
+    text: &'a str,
+    _marker: core::marker::PhantomData<C>,
+}
+
+#[contextual(Rug<'a>, view)]
+struct Phrase<'a> {
+    text: &'a str,
+    first: Proxy<Word<'a, Rug<'a>>>,
+    rest: Vec<Proxy<Word<'a, Rug<'a>>>>,
+}
+
+#[persian_rug(profile)]
+struct Rug<'a> {
+    #[table]
+    words: Word<'a, Rug<'a>>,
+    #[table(arena)]
+    phrases: Phrase<'a>,
+}
+
+#[persian_rug::constraints(context = C, access(Word<'a, C>))]
+fn add_words<'a, C, M: Mutator<Context = C>>(
+    text: &'a str,
+    mut mutator: M,
+) -> Vec<Proxy<Word<'a, C>>> {
+    text.split(' ')
+        .map(|text| {
+            mutator.add(Word {
+                text,
+                _marker: Default::default(),
+            })
+        })
+        .collect()
+}
+
+#[persian_rug::constraints(context = C, access(Word<'a, C>))]
+struct Sentence<'a, C> {
+    words: Vec<Proxy<Word<'a, C>>>,
+}
+
+#[persian_rug::constraints(context = C, access(Word<'a, C>))]
+impl<'a, C> Sentence<'a, C> {
+    fn text<A: Accessor<Context = C>>(&self, access: A) -> Vec<&'a str> {
+        self.words.iter().map(|p| access.get(p).text).collect()
+    }
+}
+
+#[persian_rug::constraints(context = Rug<'a>, access(Word<'a, Rug<'a>>, Phrase<'a>))]
+fn phrase_text<'a>(phrase: &Proxy<Phrase<'a>>, access: &Rug<'a>) -> &'a str {
+    access.get(phrase).text
+}
+
+#[test]
+fn test_borrowed_objects() {
+    let source = String::from("the quick brown fox");
+
+    let mut r = Rug {
+        words: Default::default(),
+        phrases: Default::default(),
+    };
+    let words = add_words(&source, &mut r);
+    assert_eq!(words.len(), 4);
+
+    let sentence = Sentence {
+        words: words.clone(),
+    };
+    assert_eq!(sentence.text(&r), vec!["the", "quick", "brown", "fox"]);
+
+    let phrase = r.add(Phrase {
+        text: &source[4..],
+        first: words[1],
+        rest: words[2..].to_vec(),
+    });
+    assert_eq!(phrase_text(&phrase, &r), "quick brown fox");
+
+    let access = &r;
+    let view = r.get(&phrase).view(&access);
+    assert_eq!(view.first.text, "quick");
+    assert_eq!(
+        view.rest.iter().map(|word| word.text).collect::<Vec<_>>(),
+        vec!["brown", "fox"]
+    );
+
+    // The text outlives the context, so it can be kept after the
+    // context is dropped.
+    let kept = r.get(&words[3]).text;
+    drop(r);
+    assert_eq!(kept, "fox");
+}
@@ -0,0 +1,70 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use persian_rug::{contextual, persian_rug, Accessor, Context, LocalRug, Mutator, Proxy};
+
+#[contextual(Rug)]
+struct Counter {
+    count: u32,
+}
+
+#[persian_rug]
+struct Rug(#[table] Counter);
+
+fn total<A: Accessor<Context = Rug>>(access: A) -> u32 {
+    access.get_iter::<Counter>().map(|c| c.count).sum()
+}
+
+fn bump<M: Mutator<Context = Rug>>(mut mutator: M, counter: &Proxy<Counter>) -> u32 {
+    mutator.update(counter, |c| {
+        c.count += 1;
+        c.count
+    })
+}
+
+#[test]
+fn test_shared_handles() {
+    let rug = LocalRug::new(Rug(Default::default()));
+    let other = rug.clone();
+    assert!(rug.ptr_eq(&other));
+    assert!(!rug.ptr_eq(&LocalRug::new(Rug(Default::default()))));
+
+    let c = rug.add(Counter { count: 0 });
+    other.update(&c, |c| c.count = 5);
+    assert_eq!(rug.get_with(&c, |c| c.count), 5);
+
+    assert_eq!(bump(rug.write(), &c), 6);
+    assert_eq!(total(&*other.read()), 6);
+    assert_eq!(rug.with(|r| r.get(&c).count), 6);
+    rug.with_mut(|r| r.get_mut(&c).count = 0);
+
+    let rug = rug.into_inner().err().unwrap();
+    drop(other);
+    let r = rug.into_inner().ok().unwrap();
+    assert_eq!(r.get(&c).count, 0);
+}
+
+#[test]
+#[should_panic]
+fn test_write_while_reading() {
+    let rug = LocalRug::new(Rug(Default::default()));
+    let _read = rug.read();
+    rug.write();
+}
+
+#[test]
+fn test_rc_and_refmut() {
+    let mut r = Rug(Default::default());
+    let c = r.add(Counter { count: 1 });
+
+    let cell = RefCell::new(r);
+    assert_eq!(bump(cell.borrow_mut(), &c), 2);
+    assert_eq!(cell.borrow_mut().try_get(&c).map(|c| c.count), Ok(2));
+
+    let shared = Rc::new(cell.into_inner());
+    assert_eq!(total(shared.clone()), 2);
+    assert_eq!(shared.try_get(&c).map(|c| c.count), Ok(2));
+}
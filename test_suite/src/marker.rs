@@ -0,0 +1,79 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, Context, Contextual, Proxy};
+
+#[contextual(C)]
+struct Named<C: Context> {
+    a: i32,
+    #[cfg(test)]
+    b: &'static str,
+    link: Option<Proxy<Named<C>>>,
+}
+
+#[contextual(C)]
+struct Tuple<C: Context>(i32, Vec<Proxy<Tuple<C>>>);
+
+#[contextual(C)]
+struct Unit<C: Context>;
+
+#[contextual(C)]
+struct Explicit<C: Context> {
+    _marker: core::marker::PhantomData<C>,
+    a: i32,
+}
+
+#[contextual(C)]
+struct Mentions<C: Context> {
+    other: Option<Proxy<Named<C>>>,
+}
+
+#[persian_rug]
+struct Rug(
+    #[table] Named<Rug>,
+    #[table] Tuple<Rug>,
+    #[table] Unit<Rug>,
+    #[table] Explicit<Rug>,
+    #[table] Mentions<Rug>,
+);
+
+#[test]
+fn test_markers() {
+    let mut r = Rug(
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+
+    let n1 = r.add(Named::new(1, "one", None));
+    let n2 = r.add(Named::new(2, "two", Some(n1)));
+    assert_eq!(r.get(&n2).link, Some(n1));
+    assert_eq!(r.get(&n2).b, "two");
+
+    let t1 = r.add(Tuple::new(3, Vec::new()));
+    let t2 = r.add(Tuple::new(4, vec![t1]));
+    assert_eq!(r.get(&t2).1, vec![t1]);
+    assert_eq!(r.get(&t1).0, 3);
+
+    r.add(Unit::new());
+    assert_eq!(r.get_iter::<Unit<Rug>>().count(), 1);
+
+    r.add(Explicit {
+        _marker: Default::default(),
+        a: 5,
+    });
+    r.add(Mentions { other: Some(n2) });
+}
+
+#[test]
+fn test_marker_schema() {
+    let names = Named::<Rug>::schema()
+        .fields
+        .iter()
+        .map(|field| field.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["a", "b", "link"]);
+    assert!(Tuple::<Rug>::schema().fields.len() == 2);
+}
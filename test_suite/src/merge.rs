@@ -0,0 +1,140 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, Context, Links, Proxy};
+
+// Neither type is `Clone`, since objects are moved.
+#[derive(Debug, PartialEq, Links)]
+#[contextual(Rug)]
+struct Account {
+    name: String,
+}
+
+#[derive(Debug, PartialEq, Links)]
+#[contextual(Rug)]
+struct Transfer {
+    from: Proxy<Account>,
+    to: Proxy<Account>,
+    amount: u64,
+    refund_of: Option<Proxy<Transfer>>,
+}
+
+#[persian_rug(merge)]
+struct Rug(#[table] Account, #[table] Transfer);
+
+fn rug() -> Rug {
+    Rug(Default::default(), Default::default())
+}
+
+fn account(r: &mut Rug, name: &str) -> Proxy<Account> {
+    r.add(Account {
+        name: name.to_string(),
+    })
+}
+
+#[test]
+fn test_absorb() {
+    let mut a = rug();
+    let alice = account(&mut a, "alice");
+    let bob = account(&mut a, "bob");
+    let first = a.add(Transfer {
+        from: alice,
+        to: bob,
+        amount: 10,
+        refund_of: None,
+    });
+
+    let mut b = rug();
+    let carol = account(&mut b, "carol");
+    let dave = account(&mut b, "dave");
+    let payment = b.add(Transfer {
+        from: carol,
+        to: dave,
+        amount: 5,
+        refund_of: None,
+    });
+    let refund = b.add(Transfer {
+        from: dave,
+        to: carol,
+        amount: 5,
+        refund_of: Some(payment),
+    });
+
+    let moved = a.absorb(b);
+    assert_eq!(moved.len(), 4);
+
+    // The objects already present are untouched.
+    assert_eq!(a.get(&alice).name, "alice");
+    assert_eq!(a.get(&first).to, bob);
+
+    let carol = moved.get(&carol).unwrap();
+    let dave = moved.get(&dave).unwrap();
+    let payment = moved.get(&payment).unwrap();
+    let refund = moved.get(&refund).unwrap();
+    assert_eq!(a.get(&carol).name, "carol");
+    assert_eq!(
+        a.get(&refund),
+        &Transfer {
+            from: dave,
+            to: carol,
+            amount: 5,
+            refund_of: Some(payment),
+        }
+    );
+    assert_eq!(
+        a.get_iter::<Account>()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["alice", "bob", "carol", "dave"]
+    );
+    assert_eq!(
+        a.get_iter::<Transfer>()
+            .map(|t| t.amount)
+            .collect::<Vec<_>>(),
+        vec![10, 5, 5]
+    );
+}
+
+#[test]
+fn test_absorb_with_deletions() {
+    let mut a = rug();
+    account(&mut a, "alice");
+
+    let mut b = rug();
+    let gone = account(&mut b, "gone");
+    let erin = account(&mut b, "erin");
+    let transfer = b.add(Transfer {
+        from: erin,
+        to: gone,
+        amount: 1,
+        refund_of: None,
+    });
+    b.delete(&gone);
+
+    let moved = a.absorb(b);
+    assert_eq!(moved.len(), 2);
+    assert_eq!(moved.get(&gone), None);
+
+    let erin = moved.get(&erin).unwrap();
+    let t = a.get(&moved.get(&transfer).unwrap());
+    assert_eq!(t.from, erin);
+    // The link to the deleted account is left as it was.
+    assert_eq!(t.to, gone);
+}
+
+#[test]
+fn test_absorb_empty() {
+    let mut a = rug();
+    let alice = account(&mut a, "alice");
+
+    let moved = a.absorb(rug());
+    assert!(moved.is_empty());
+    assert_eq!(moved.get(&alice), None);
+    assert_eq!(a.get_iter::<Account>().count(), 1);
+
+    let mut b = rug();
+    let bob = account(&mut b, "bob");
+    let moved = b.absorb(a);
+    assert_eq!(b.get(&bob).name, "bob");
+    assert_eq!(b.get(&moved.get(&alice).unwrap()).name, "alice");
+}
@@ -0,0 +1,62 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::diagram::Labels;
+use persian_rug::{contextual, mermaid, persian_rug, Context, Proxy};
+
+#[contextual(Rug)]
+struct Step {
+    name: &'static str,
+    #[link]
+    next: Vec<Proxy<Step>>,
+}
+
+#[persian_rug(diagram)]
+struct Rug(#[table] Step);
+
+#[test]
+fn test_mermaid() {
+    let mut r = Rug(Default::default());
+    let done = r.add(Step {
+        name: "done",
+        next: Vec::new(),
+    });
+    let retry = r.add(Step {
+        name: "retry #1",
+        next: Vec::new(),
+    });
+    let start = r.add(Step {
+        name: "say \"hi\"\nthen wait",
+        next: vec![retry, done],
+    });
+    r.get_mut(&retry).next.push(start);
+
+    assert_eq!(
+        mermaid::to_string(&r, &Labels::new()),
+        concat!(
+            "graph TD\n",
+            "  n0_0[\"Step 0\"]\n",
+            "  n0_1[\"Step 1\"]\n",
+            "  n0_2[\"Step 2\"]\n",
+            "  n0_1 -->|\"next\"| n0_2\n",
+            "  n0_2 -->|\"next\"| n0_1\n",
+            "  n0_2 -->|\"next\"| n0_0\n",
+        )
+    );
+
+    let labels = Labels::new().label(|s: &Step| s.name.to_string());
+    let mut out = Vec::new();
+    mermaid::write(&r, &labels, &mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        concat!(
+            "graph TD\n",
+            "  n0_0[\"done\"]\n",
+            "  n0_1[\"retry #35;1\"]\n",
+            "  n0_2[\"say #quot;hi#quot;<br>then wait\"]\n",
+            "  n0_1 -->|\"next\"| n0_2\n",
+            "  n0_2 -->|\"next\"| n0_1\n",
+            "  n0_2 -->|\"next\"| n0_0\n",
+        )
+    );
+}
@@ -0,0 +1,84 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, AnyProxy, Context, Names, Proxy};
+
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[contextual(Rug)]
+struct Bar {
+    name: &'static str,
+}
+
+#[persian_rug]
+struct Rug {
+    #[table]
+    foos: Foo,
+    #[table]
+    bars: Bar,
+    #[resource]
+    names: Names,
+}
+
+fn new_rug() -> Rug {
+    Rug {
+        foos: Default::default(),
+        bars: Default::default(),
+        names: Names::new(),
+    }
+}
+
+#[test]
+fn test_context_names() {
+    let mut r = new_rug();
+    let f1 = r.add(Foo { a: 1 });
+    let f2 = r.add(Foo { a: 2 });
+    let b = r.add(Bar { name: "bar" });
+
+    assert_eq!(r.register_name("root", f1), None);
+    assert_eq!(r.register_name("bar", b), None);
+    assert_eq!(r.lookup::<Foo>("root"), Some(f1));
+    assert_eq!(r.lookup::<Bar>("root"), None);
+    assert_eq!(r.lookup::<Foo>("missing"), None);
+
+    assert_eq!(r.register_name("root", f2), Some(AnyProxy::new(f1)));
+    assert_eq!(r.get(&r.lookup::<Foo>("root").unwrap()).a, 2);
+}
+
+#[test]
+fn test_registry() {
+    let mut names = Names::new();
+    let f = Proxy::<Foo>::from_handle(3);
+    let b = Proxy::<Bar>::from_handle(3);
+    assert!(names.is_empty());
+
+    names.register("primary", f);
+    names.register("alias", f);
+    names.register("other", b);
+    assert_eq!(names.len(), 3);
+    assert_eq!(names.get("other"), Some(AnyProxy::new(b)));
+    assert_eq!(
+        names.names_of(&f).collect::<Vec<_>>(),
+        vec!["alias", "primary"]
+    );
+    assert_eq!(names.names_of(&b).collect::<Vec<_>>(), vec!["other"]);
+    assert_eq!(
+        names.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        vec!["alias", "other", "primary"]
+    );
+
+    assert_eq!(names.unregister("alias"), Some(AnyProxy::new(f)));
+    assert_eq!(names.unregister("alias"), None);
+    assert_eq!(names.clone().lookup::<Foo>("primary"), Some(f));
+    assert_eq!(
+        format!("{:?}", names),
+        format!(
+            "{{\"other\": {:?}, \"primary\": {:?}}}",
+            AnyProxy::new(b),
+            AnyProxy::new(f)
+        )
+    );
+}
@@ -0,0 +1,113 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+
+use persian_rug::{contextual, persian_rug, Context, Proxy, Table};
+
+#[derive(Clone)]
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[derive(Clone)]
+#[contextual(Rug)]
+struct Bar {
+    foo: Proxy<Foo>,
+}
+
+#[derive(Clone)]
+#[persian_rug(observe)]
+struct Rug(#[table] Foo, #[table] Bar);
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Added(Proxy<Foo>, i32),
+    Modified(Proxy<Foo>),
+}
+
+fn observed() -> (Rug, Arc<Mutex<Vec<Event>>>) {
+    let mut r = Rug(Default::default(), Default::default());
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let log = events.clone();
+    r.on_add::<Foo>(move |p, foo| log.lock().unwrap().push(Event::Added(*p, foo.a)));
+    let log = events.clone();
+    r.on_modify::<Foo>(move |p| log.lock().unwrap().push(Event::Modified(*p)));
+    (r, events)
+}
+
+#[test]
+fn test_add_and_modify() {
+    let (mut r, events) = observed();
+    let a = r.add(Foo { a: 1 });
+    let b = r.add(Foo { a: 2 });
+    r.add(Bar { foo: a });
+
+    r.get_mut(&b).a = 3;
+    assert!(r.try_get_mut(&b).is_ok());
+    r.get(&a);
+    r.delete(&b);
+    assert!(r.try_get_mut(&b).is_err());
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            Event::Added(a, 1),
+            Event::Added(b, 2),
+            Event::Modified(b),
+            Event::Modified(b),
+        ]
+    );
+}
+
+#[test]
+fn test_iter_mut() {
+    let (mut r, events) = observed();
+    let a = r.add(Foo { a: 1 });
+    let b = r.add(Foo { a: 2 });
+    events.lock().unwrap().clear();
+
+    // Only the items actually visited are reported.
+    r.get_iter_mut::<Foo>().next().unwrap().a = 10;
+    assert_eq!(*events.lock().unwrap(), vec![Event::Modified(a)]);
+
+    for foo in r.get_iter_mut::<Foo>() {
+        foo.a += 1;
+    }
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![Event::Modified(a), Event::Modified(a), Event::Modified(b)]
+    );
+}
+
+#[test]
+fn test_clone_drops_observers() {
+    let (mut r, events) = observed();
+    let a = r.add(Foo { a: 1 });
+
+    let mut c = r.clone();
+    c.get_mut(&a).a = 2;
+    c.add(Foo { a: 3 });
+    assert_eq!(*events.lock().unwrap(), vec![Event::Added(a, 1)]);
+}
+
+#[test]
+fn test_table_observers() {
+    let mut t = Table::<i32>::new();
+    let count = Arc::new(Mutex::new(0));
+    let seen = count.clone();
+    t.on_add(move |_, value| *seen.lock().unwrap() += *value);
+    let seen = count.clone();
+    t.on_modify(move |_| *seen.lock().unwrap() += 100);
+
+    let p = t.push(1);
+    t.insert_with_handle(10, 2).unwrap();
+    *t.get_mut(&p).unwrap() += 1;
+    assert_eq!(*count.lock().unwrap(), 103);
+
+    t.clear_observers();
+    t.push(1000);
+    t.get_mut(&p);
+    assert_eq!(*count.lock().unwrap(), 103);
+}
@@ -0,0 +1,88 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard};
+
+use persian_rug::{
+    contextual, persian_rug, Context, OwnedTableIterator, OwnedTableProxyIterator, Proxy,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[contextual(Rug)]
+struct Bar {
+    foo: Proxy<Foo>,
+}
+
+#[persian_rug]
+struct Rug(#[table] Foo, #[table] Bar);
+
+fn new_rug() -> Rug {
+    let mut r = Rug(Default::default(), Default::default());
+    for a in 0..3 {
+        let foo = r.add(Foo { a });
+        r.add(Bar { foo });
+    }
+    r
+}
+
+struct Holder {
+    foos: OwnedTableIterator<Arc<Rug>, Foo>,
+}
+
+#[test]
+fn test_owned_arc() {
+    let r = Arc::new(new_rug());
+    let mut holder = Holder {
+        foos: OwnedTableIterator::new(r.clone()),
+    };
+    drop(r);
+    assert_eq!(holder.foos.size_hint(), (3, Some(3)));
+    assert_eq!(holder.foos.next(), Some(Foo { a: 0 }));
+    assert_eq!(holder.foos.context().get_iter::<Bar>().count(), 3);
+    assert_eq!(holder.foos.map(|foo| foo.a).collect::<Vec<_>>(), vec![1, 2]);
+}
+
+fn bars(r: &Mutex<Rug>) -> OwnedTableProxyIterator<MutexGuard<'_, Rug>, Bar> {
+    OwnedTableProxyIterator::new(r.lock().unwrap())
+}
+
+#[test]
+fn test_owned_mutex() {
+    let r = Mutex::new(new_rug());
+    let mut iter = bars(&r);
+    assert!(r.try_lock().is_err());
+    let mut values = Vec::new();
+    while let Some(p) = iter.next() {
+        let foo = iter.get(&p).foo;
+        values.push(iter.context().get(&foo).a);
+    }
+    assert_eq!(values, vec![0, 1, 2]);
+
+    let mut guard = iter.into_guard();
+    guard.add(Foo { a: 3 });
+    drop(guard);
+    assert!(r.try_lock().is_ok());
+    assert_eq!(
+        OwnedTableIterator::<_, Foo>::new(r.lock().unwrap()).count(),
+        4
+    );
+}
+
+fn foos(r: &RwLock<Rug>) -> impl Iterator<Item = Foo> + '_ {
+    OwnedTableIterator::<RwLockReadGuard<'_, Rug>, Foo>::new(r.read().unwrap())
+}
+
+#[test]
+fn test_owned_rwlock() {
+    let r = RwLock::new(new_rug());
+    let iter = foos(&r);
+    assert!(r.try_write().is_err());
+    assert!(r.try_read().is_ok());
+    assert_eq!(iter.map(|foo| foo.a).sum::<i32>(), 3);
+    assert!(r.try_write().is_ok());
+}
@@ -0,0 +1,79 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::borsh::{self, BorshDeserialize, BorshSerialize};
+use persian_rug::csv::CsvExport;
+use persian_rug::profiling::Profiled;
+use persian_rug::{contextual, persian_rug, Accessor, AnyProxy, Context, Links, Proxy};
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+#[borsh(crate = "persian_rug::borsh")]
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+    #[link]
+    next: Option<Proxy<Foo>>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+#[borsh(crate = "persian_rug::borsh")]
+#[contextual(Rug)]
+struct Scratch(String);
+
+impl Links for Scratch {}
+
+#[contextual(Rug)]
+struct Missing(i32);
+
+impl Links for Missing {}
+
+#[persian_rug(borsh, referrers, profile, csv, aliases)]
+struct Rug {
+    /// The objects that are kept.
+    #[table]
+    foos: Foo,
+    /// Working space, which is not saved.
+    #[borsh(skip)]
+    #[table(arena)]
+    scratch: Scratch,
+    #[cfg(any())]
+    #[table]
+    missing: Missing,
+}
+
+fn make_rug() -> Rug {
+    Rug {
+        foos: Default::default(),
+        scratch: Default::default(),
+    }
+}
+
+#[test]
+fn test_skipped_table() {
+    let mut r = make_rug();
+    let f = r.add(Foo { a: 1, next: None });
+    r.add(Scratch("temporary".to_string()));
+
+    let s = Rug::try_from_slice(&borsh::to_vec(&r).unwrap()).unwrap();
+    assert_eq!(s.get(&f), &Foo { a: 1, next: None });
+    assert_eq!(s.get_iter::<Scratch>().count(), 0);
+}
+
+#[test]
+fn test_cfg_table() {
+    let mut r = make_rug();
+    let f = r.add(Foo { a: 1, next: None });
+    let g: rug_aliases::FooProxy = r.add(Foo {
+        a: 2,
+        next: Some(f),
+    });
+    r.add(Scratch("x".to_string()));
+
+    assert_eq!((&r).referrers(&f), vec![AnyProxy::from(g)]);
+    assert_eq!(r.profile().iter().count(), 2);
+
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    r.write_csv(&mut nodes, &mut edges).unwrap();
+    assert_eq!(String::from_utf8(nodes).unwrap().lines().count(), 4);
+}
@@ -0,0 +1,88 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::petgraph::{algo, visit::EdgeRef, Selection};
+use persian_rug::{contextual, persian_rug, AnyProxy, Context, Proxy};
+
+#[contextual(Rug)]
+struct City {
+    name: &'static str,
+    #[link]
+    roads: Vec<Proxy<Road>>,
+}
+
+#[contextual(Rug)]
+struct Road {
+    #[link]
+    to: Proxy<City>,
+}
+
+#[persian_rug(diagram)]
+struct Rug(#[table] City, #[table] Road);
+
+fn rug() -> (Rug, Vec<Proxy<City>>) {
+    let mut r = Rug(Default::default(), Default::default());
+    let cities = ["a", "b", "c", "d"]
+        .into_iter()
+        .map(|name| {
+            r.add(City {
+                name,
+                roads: Vec::new(),
+            })
+        })
+        .collect::<Vec<_>>();
+    for (from, to) in [(0, 1), (1, 2), (2, 0)] {
+        let road = r.add(Road { to: cities[to] });
+        r.get_mut(&cities[from]).roads.push(road);
+    }
+    (r, cities)
+}
+
+#[test]
+fn test_all() {
+    let (r, cities) = rug();
+    let (graph, indices) = Selection::all(&r).graph();
+    assert_eq!(graph.node_count(), 7);
+    assert_eq!(graph.edge_count(), 6);
+
+    let a = indices[&AnyProxy::from(cities[0])];
+    let c = indices[&AnyProxy::from(cities[2])];
+    let d = indices[&AnyProxy::from(cities[3])];
+    assert!(algo::has_path_connecting(&graph, a, c, None));
+    assert!(!algo::has_path_connecting(&graph, a, d, None));
+    assert_eq!(algo::tarjan_scc(&graph).len(), 2);
+
+    let fields = graph
+        .edges(a)
+        .map(|e| (graph[e.target()].type_name(), e.weight().as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(fields, vec![(std::any::type_name::<Road>(), "roads")]);
+}
+
+#[test]
+fn test_selection() {
+    let (mut r, cities) = rug();
+    let roads = r.get_proxy_iter::<Road>().copied().collect::<Vec<_>>();
+    r.delete(&roads[2]);
+
+    // Only the cities are selected, so no links are followed.
+    let (graph, _) = Selection::new(&r).table::<City>().graph();
+    assert_eq!(graph.node_count(), 4);
+    assert_eq!(graph.edge_count(), 0);
+
+    let (mut graph, indices) = Selection::new(&r)
+        .table::<Road>()
+        .table::<City>()
+        .stable_graph();
+    assert_eq!(graph.node_count(), 6);
+    assert_eq!(graph.edge_count(), 4);
+    assert!(!algo::is_cyclic_directed(&graph));
+
+    // Indices stay valid as nodes are removed.
+    graph.remove_node(indices[&AnyProxy::from(cities[1])]);
+    assert_eq!(graph.edge_count(), 2);
+    assert_eq!(
+        graph[indices[&AnyProxy::from(cities[2])]].downcast::<City>(),
+        Some(cities[2])
+    );
+}
@@ -0,0 +1,74 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::profiling::{AccessCounts, Profiled};
+use persian_rug::{contextual, persian_rug, seed, Context, Proxy};
+
+#[contextual(Rug)]
+struct Foo {
+    a: usize,
+}
+
+#[contextual(Rug)]
+struct Bar {
+    foo: Proxy<Foo>,
+}
+
+#[persian_rug(profile)]
+struct Rug {
+    #[table]
+    foos: Foo,
+    #[table(arena)]
+    bars: Bar,
+}
+
+#[test]
+fn test_profile() {
+    let mut r = Rug {
+        foos: Default::default(),
+        bars: Default::default(),
+    };
+    let foos = seed(&mut r, 4, |a, _| Foo { a });
+    let bars = seed(&mut r, 4, |ix, _| Bar { foo: foos[ix] });
+
+    for bar in bars.iter() {
+        let foo = r.get(bar).foo;
+        r.get_mut(&foo).a += 1;
+    }
+    assert_eq!(r.get_iter::<Foo>().map(|f| f.a).sum::<usize>(), 10);
+    r.get_proxy_iter::<Bar>().count();
+    r.get_iter_mut::<Bar>().count();
+
+    assert_eq!(
+        r.foos.access_counts(),
+        AccessCounts {
+            gets: 0,
+            get_muts: 4,
+            iterations: 1,
+        }
+    );
+    assert_eq!(
+        r.bars.access_counts(),
+        AccessCounts {
+            gets: 4,
+            get_muts: 0,
+            iterations: 2,
+        }
+    );
+
+    let report = r.profile();
+    let names = report.iter().map(|(name, _)| name).collect::<Vec<_>>();
+    assert_eq!(names.len(), 2);
+    assert!(names[0].ends_with("Bar"));
+    assert!(names[1].ends_with("Foo"));
+
+    let text = report.to_string();
+    let lines = text.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("table"));
+    assert!(lines[1].contains("Bar"));
+    assert!(lines[1].ends_with(" 4            0            2"));
+
+    r.reset_profile();
+    assert!(r.profile().iter().all(|(_, counts)| counts.total() == 0));
+}
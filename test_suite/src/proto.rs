@@ -0,0 +1,243 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, HashMap};
+
+use persian_rug::proto::{ExportProto, ProtoError};
+use persian_rug::serde::Serialize;
+use persian_rug::{contextual, persian_rug, Context, Proxy};
+use prost::Message;
+
+#[derive(Serialize)]
+#[serde(crate = "persian_rug::serde")]
+#[contextual(Rug)]
+struct Person {
+    name: String,
+    score: i32,
+    manager: Option<Proxy<Person>>,
+    friends: Vec<Proxy<Person>>,
+    rating: f64,
+    _marker: core::marker::PhantomData<u8>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "persian_rug::serde")]
+#[contextual(Rug)]
+enum Event {
+    Hired(Proxy<Person>),
+    Moved {
+        who: Proxy<Person>,
+        team: Proxy<Team>,
+    },
+    Closed,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "persian_rug::serde")]
+#[contextual(Rug)]
+struct Team(String);
+
+#[persian_rug(proto)]
+struct Rug {
+    #[table]
+    people: Person,
+    #[table]
+    events: Event,
+    #[table]
+    teams: Team,
+}
+
+const PROTO: &str = r#"syntax = "proto3";
+
+package test.rug;
+
+message Rug {
+  map<uint64, Person> people = 1;
+  map<uint64, Event> events = 2;
+  map<uint64, Team> teams = 3;
+}
+
+message Person {
+  string name = 1;
+  int32 score = 2;
+  optional uint64 manager = 3; // handle of a Person
+  repeated uint64 friends = 4; // handle of a Person
+  double rating = 5;
+}
+
+message Event {
+  message Hired {
+    uint64 field_0 = 1; // handle of a Person
+  }
+  message Moved {
+    uint64 who = 1; // handle of a Person
+    uint64 team = 2; // handle of a Team
+  }
+  message Closed {
+  }
+  oneof variant {
+    Hired hired = 1;
+    Moved moved = 2;
+    Closed closed = 3;
+  }
+}
+
+message Team {
+  string field_0 = 1;
+}
+"#;
+
+// The code prost would generate from PROTO.
+
+#[derive(Clone, PartialEq, Message)]
+struct RugMessage {
+    #[prost(map = "uint64, message", tag = "1")]
+    people: HashMap<u64, PersonMessage>,
+    #[prost(map = "uint64, message", tag = "2")]
+    events: HashMap<u64, EventMessage>,
+    #[prost(map = "uint64, message", tag = "3")]
+    teams: HashMap<u64, TeamMessage>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PersonMessage {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(int32, tag = "2")]
+    score: i32,
+    #[prost(uint64, optional, tag = "3")]
+    manager: Option<u64>,
+    #[prost(uint64, repeated, tag = "4")]
+    friends: Vec<u64>,
+    #[prost(double, tag = "5")]
+    rating: f64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct EventMessage {
+    #[prost(oneof = "EventVariant", tags = "1, 2, 3")]
+    variant: Option<EventVariant>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+enum EventVariant {
+    #[prost(message, tag = "1")]
+    Hired(Hired),
+    #[prost(message, tag = "2")]
+    Moved(Moved),
+    #[prost(message, tag = "3")]
+    Closed(Closed),
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Hired {
+    #[prost(uint64, tag = "1")]
+    field_0: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Moved {
+    #[prost(uint64, tag = "1")]
+    who: u64,
+    #[prost(uint64, tag = "2")]
+    team: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Closed {}
+
+#[derive(Clone, PartialEq, Message)]
+struct TeamMessage {
+    #[prost(string, tag = "1")]
+    field_0: String,
+}
+
+fn person(name: &str, score: i32, manager: Option<Proxy<Person>>) -> Person {
+    Person {
+        name: name.to_string(),
+        score,
+        manager,
+        friends: Vec::new(),
+        rating: 0.5,
+        _marker: Default::default(),
+    }
+}
+
+#[test]
+fn test_proto_file() {
+    assert_eq!(Rug::proto_file("test.rug").unwrap(), PROTO);
+}
+
+#[test]
+fn test_encode() {
+    let mut r = Rug {
+        people: Default::default(),
+        events: Default::default(),
+        teams: Default::default(),
+    };
+    let alice = r.add(person("Alice", -3, None));
+    let bob = r.add(person("Bob", 7, Some(alice)));
+    r.get_mut(&alice).friends = vec![bob, alice];
+    let team = r.add(Team("core".to_string()));
+    r.add(Event::Hired(bob));
+    r.add(Event::Moved { who: bob, team });
+    r.add(Event::Closed);
+
+    let decoded = RugMessage::decode(r.to_proto().unwrap().as_slice()).unwrap();
+
+    assert_eq!(decoded.people.len(), 2);
+    assert_eq!(
+        decoded.people[&alice.handle()],
+        PersonMessage {
+            name: "Alice".to_string(),
+            score: -3,
+            manager: None,
+            friends: vec![bob.handle(), alice.handle()],
+            rating: 0.5,
+        }
+    );
+    assert_eq!(decoded.people[&bob.handle()].manager, Some(alice.handle()));
+    assert_eq!(decoded.teams[&team.handle()].field_0, "core");
+    assert_eq!(decoded.events.len(), 3);
+    assert!(decoded.events.values().any(|event| event.variant
+        == Some(EventVariant::Hired(Hired {
+            field_0: bob.handle()
+        }))));
+    assert!(decoded.events.values().any(|event| event.variant
+        == Some(EventVariant::Moved(Moved {
+            who: bob.handle(),
+            team: team.handle()
+        }))));
+    assert!(decoded
+        .events
+        .values()
+        .any(|event| event.variant == Some(EventVariant::Closed(Closed {}))));
+}
+
+#[derive(Serialize)]
+#[serde(crate = "persian_rug::serde")]
+#[contextual(MapRug)]
+struct Scores {
+    by_name: BTreeMap<String, u32>,
+}
+
+#[persian_rug(proto)]
+struct MapRug(#[table] Scores);
+
+#[test]
+fn test_unsupported() {
+    assert_eq!(
+        MapRug::proto_file("test").unwrap_err(),
+        ProtoError::Field {
+            type_name: std::any::type_name::<Scores>(),
+            field: "by_name",
+            ty: "BTreeMap<String, u32>",
+        }
+    );
+
+    let mut r = MapRug(Default::default());
+    r.add(Scores {
+        by_name: BTreeMap::new(),
+    });
+    assert!(matches!(r.to_proto(), Err(ProtoError::Encode(_))));
+}
@@ -0,0 +1,90 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::time::SystemTime;
+
+use persian_rug::provenance::{current_creator, with_creator};
+use persian_rug::{contextual, persian_rug, Context, Mutator, Proxy, Table};
+
+#[derive(Clone)]
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[derive(Clone)]
+#[contextual(Rug)]
+struct Bar {
+    foo: Proxy<Foo>,
+}
+
+#[derive(Clone)]
+#[persian_rug(provenance)]
+struct Rug(#[table] Foo, #[table] Bar);
+
+fn add_through<M: Mutator<Context = Rug>>(mut m: M, a: i32) -> (Proxy<Foo>, u32) {
+    (m.add(Foo { a }), line!())
+}
+
+#[test]
+fn test_creator() {
+    let mut r = Rug(Default::default(), Default::default());
+    let before = SystemTime::now();
+    let plain = r.add(Foo { a: 1 });
+    let (outer, inner) = with_creator("outer", || {
+        let outer = r.add(Foo { a: 2 });
+        let inner = with_creator("inner", || r.add(Foo { a: 3 }));
+        assert_eq!(current_creator().as_deref(), Some("outer"));
+        (outer, inner)
+    });
+    assert_eq!(current_creator(), None);
+
+    assert_eq!(r.provenance(&plain).unwrap().creator(), None);
+    assert_eq!(r.provenance(&outer).unwrap().creator(), Some("outer"));
+    assert_eq!(r.provenance(&inner).unwrap().creator(), Some("inner"));
+    assert!(r.provenance(&plain).unwrap().created() >= before);
+    assert!(r.provenance(&inner).unwrap().created() >= r.provenance(&plain).unwrap().created());
+}
+
+#[test]
+fn test_creator_restored_after_panic() {
+    let res = std::panic::catch_unwind(|| with_creator("doomed", || panic!("oops")));
+    assert!(res.is_err());
+    assert_eq!(current_creator(), None);
+}
+
+#[test]
+fn test_location() {
+    let mut r = Rug(Default::default(), Default::default());
+    let line = line!() + 1;
+    let foo = r.add(Foo { a: 1 });
+    let record = r.provenance(&foo).unwrap();
+    assert_eq!(record.location().file(), file!());
+    assert_eq!(record.location().line(), line);
+
+    // Helpers which are not marked #[track_caller] are blamed.
+    let (foo, line) = add_through(&mut r, 2);
+    assert_eq!(r.provenance(&foo).unwrap().location().line(), line);
+
+    let mut table = Table::<Foo>::new();
+    let line = line!() + 1;
+    let foo = table.push(Foo { a: 3 });
+    assert_eq!(table.provenance(&foo).unwrap().location().line(), line);
+}
+
+#[test]
+fn test_swap_and_clone() {
+    let mut r = Rug(Default::default(), Default::default());
+    let a = with_creator("a", || r.add(Foo { a: 1 }));
+    let b = with_creator("b", || r.add(Foo { a: 2 }));
+    let bar = r.add(Bar { foo: a });
+
+    assert!(r.swap(&a, &b));
+    assert_eq!(r.get(&a).a, 2);
+    assert_eq!(r.provenance(&a).unwrap().creator(), Some("b"));
+    assert_eq!(r.provenance(&b).unwrap().creator(), Some("a"));
+
+    let copy = r.clone();
+    assert_eq!(copy.provenance(&a), r.provenance(&a));
+    assert_eq!(copy.provenance(&bar).unwrap().creator(), None);
+}
@@ -0,0 +1,76 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{Proxy, ProxyQueue};
+
+struct Node;
+
+fn p(handle: u64) -> Proxy<Node> {
+    Proxy::from_handle(handle)
+}
+
+#[test]
+fn test_plain_queue() {
+    let mut queue = ProxyQueue::new();
+    assert!(!queue.is_deduplicated());
+    assert!(queue.push_back(p(1)));
+    assert!(queue.push_back(p(1)));
+    assert!(queue.push_front(p(0)));
+    assert_eq!(queue.len(), 3);
+    assert_eq!(
+        queue.iter().copied().collect::<Vec<_>>(),
+        vec![p(0), p(1), p(1)]
+    );
+    assert!(queue.seen().is_none());
+
+    assert_eq!(queue.pop_back(), Some(p(1)));
+    assert_eq!(queue.pop_front(), Some(p(0)));
+    assert!(!queue.has_seen(&p(0)));
+    assert!(queue.has_seen(&p(1)));
+    assert_eq!(queue.pop_front(), Some(p(1)));
+    assert!(queue.is_empty());
+    assert_eq!(queue.pop_front(), None);
+}
+
+#[test]
+fn test_deduplicated_queue() {
+    let mut queue = ProxyQueue::deduplicated();
+    assert!(queue.is_deduplicated());
+    assert!(queue.push_back(p(3)));
+    assert!(!queue.push_back(p(3)));
+    assert!(!queue.push_front(p(3)));
+    assert_eq!(queue.pop_front(), Some(p(3)));
+
+    // Proxies which have been taken off the queue are still seen.
+    assert!(!queue.push_back(p(3)));
+    assert!(queue.has_seen(&p(3)));
+    queue.extend([p(4), p(3), p(5), p(4)]);
+    assert_eq!(queue.iter().copied().collect::<Vec<_>>(), vec![p(4), p(5)]);
+    assert_eq!(queue.seen().unwrap().len(), 3);
+
+    queue.clear();
+    assert!(queue.is_empty());
+    assert!(queue.push_back(p(3)));
+}
+
+#[test]
+fn test_depth_first() {
+    // A binary tree, numbered breadth first from the root at 1.
+    let children = |n: u64| [2 * n, 2 * n + 1].into_iter().filter(|c| *c < 8).map(p);
+    let mut queue = ProxyQueue::deduplicated();
+    queue.push_back(p(1));
+    let mut order = Vec::new();
+    while let Some(n) = queue.pop_back() {
+        order.push(n.handle());
+        queue.extend(children(n.handle()).rev());
+    }
+    assert_eq!(order, vec![1, 2, 4, 5, 3, 6, 7]);
+}
+
+#[test]
+fn test_collect() {
+    let queue = [p(2), p(2), p(1)].into_iter().collect::<ProxyQueue<_>>();
+    assert_eq!(queue.len(), 3);
+    assert_eq!((&queue).into_iter().count(), 3);
+    assert_eq!(format!("{:?}", queue.clone()), format!("{:?}", queue));
+}
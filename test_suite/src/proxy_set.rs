@@ -3,7 +3,7 @@
 
 use std::collections::{BTreeSet, HashSet};
 
-use persian_rug::{contextual, persian_rug, Context, Proxy, ProxySet};
+use persian_rug::{contextual, persian_rug, Context, ProxySet};
 use rand::Rng;
 
 #[contextual(Bar)]
@@ -22,14 +22,14 @@ fn test_basic() {
 
     for i in 0..(2 << 16) {
         let mut ps = ProxySet::new();
-        for j in 0..16 {
+        for (j, item) in f.iter().enumerate().take(16) {
             if (i & (1 << j)) != 0 {
-                ps.insert(f[j]);
+                ps.insert(*item);
             }
         }
 
-        for j in 0..16 {
-            assert_eq!(i & (1 << j) != 0, ps.contains(&f[j]));
+        for (j, item) in f.iter().enumerate().take(16) {
+            assert_eq!(i & (1 << j) != 0, ps.contains(item));
         }
     }
 }
@@ -43,14 +43,14 @@ fn test_large() {
 
     for i in 0..(2 << 16) {
         let mut ps = ProxySet::new();
-        for j in 0..16 {
+        for (j, item) in g.iter().enumerate().take(16) {
             if (i & (1 << j)) != 0 {
-                ps.insert(g[j]);
+                ps.insert(*item);
             }
         }
 
-        for j in 0..16 {
-            assert_eq!(i & (1 << j) != 0, ps.contains(&g[j]));
+        for (j, item) in g.iter().enumerate().take(16) {
+            assert_eq!(i & (1 << j) != 0, ps.contains(item));
         }
     }
 }
@@ -74,7 +74,7 @@ fn test_random() {
         }
 
         for item in f.iter() {
-            assert_eq!(hs.contains(&item), ps.contains(&item));
+            assert_eq!(hs.contains(item), ps.contains(item));
         }
     }
 }
@@ -98,7 +98,7 @@ fn test_iterator() {
         }
 
         for item in hs.iter() {
-            assert!(ps.contains(&item));
+            assert!(ps.contains(item));
         }
 
         for item in ps.iter() {
@@ -108,3 +108,38 @@ fn test_iterator() {
         assert!(hs.is_empty());
     }
 }
+
+#[test]
+fn test_complement() {
+    let mut bar = Bar(Default::default());
+
+    let f = (0..200).map(|ix| bar.add(Foo { ix })).collect::<Vec<_>>();
+
+    let mut rng = rand::thread_rng();
+    for _ in 0..100 {
+        let mut ps = ProxySet::new();
+        for item in f.iter() {
+            if rng.gen_bool(0.3) {
+                ps.insert(*item);
+            }
+        }
+
+        let rest = ps.complement_in(&&bar);
+        assert_eq!(rest.len(), f.len() - ps.len());
+        for item in f.iter() {
+            assert_ne!(ps.contains(item), rest.contains(item));
+        }
+        assert_eq!(
+            rest.iter().collect::<Vec<_>>(),
+            f.iter()
+                .filter(|p| !ps.contains(p))
+                .copied()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    let mut all = ProxySet::new();
+    all.fill_from_table(&&bar);
+    assert_eq!(all.len(), f.len());
+    assert!(all.complement_in(&&bar).is_empty());
+}
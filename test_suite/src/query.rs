@@ -0,0 +1,159 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, query, Accessor, Context, Proxy};
+
+#[contextual(C)]
+struct Foo<C: Context> {
+    _marker: core::marker::PhantomData<C>,
+    a: i32,
+}
+
+#[contextual(C)]
+struct Bar<C: Context> {
+    a: i32,
+    foo: Proxy<Foo<C>>,
+    friend: Option<Proxy<Bar<C>>>,
+}
+
+#[contextual(C)]
+struct Baz<C: Context> {
+    a: i32,
+    bars: Vec<Proxy<Bar<C>>>,
+}
+
+#[persian_rug]
+struct State(
+    #[table] Foo<State>,
+    #[table] Bar<State>,
+    #[table] Baz<State>,
+);
+
+fn make_state() -> (State, Vec<Proxy<Bar<State>>>) {
+    let mut s = State(Default::default(), Default::default(), Default::default());
+
+    let f = (0..4)
+        .map(|a| {
+            s.add(Foo {
+                _marker: Default::default(),
+                a,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let b1 = s.add(Bar {
+        a: 10,
+        foo: f[0],
+        friend: None,
+    });
+    let b2 = s.add(Bar {
+        a: 11,
+        foo: f[1],
+        friend: Some(b1),
+    });
+    let b3 = s.add(Bar {
+        a: 12,
+        foo: f[3],
+        friend: Some(b2),
+    });
+
+    s.add(Baz {
+        a: 20,
+        bars: vec![b1, b3],
+    });
+    s.add(Baz {
+        a: 21,
+        bars: vec![],
+    });
+    s.add(Baz {
+        a: 22,
+        bars: vec![b2, b2],
+    });
+
+    (s, vec![b1, b2, b3])
+}
+
+#[persian_rug::constraints(context = C, access(Foo<C>, Bar<C>))]
+fn bar_foo_pairs<A: Accessor<Context = C>, C>(access: A) -> Vec<(i32, i32)> {
+    query!(access, (bar: Bar<C>, foo = bar.foo) => (bar.a, foo.a)).collect()
+}
+
+#[test]
+fn test_single() {
+    let (s, _) = make_state();
+
+    let foos = query!(&s, (foo: Foo<State>) => foo.a).collect::<Vec<_>>();
+    assert_eq!(foos, vec![0, 1, 2, 3]);
+
+    let odd = query!(&s, (foo: Foo<State>) if foo.a % 2 == 1 => foo.a).collect::<Vec<_>>();
+    assert_eq!(odd, vec![1, 3]);
+}
+
+#[test]
+fn test_resolve() {
+    let (s, _) = make_state();
+
+    assert_eq!(bar_foo_pairs(&s), vec![(10, 0), (11, 1), (12, 3)]);
+
+    let filtered =
+        query!(&s, (bar: Bar<State>, foo = bar.foo) if foo.a > 0 => bar.a).collect::<Vec<_>>();
+    assert_eq!(filtered, vec![11, 12]);
+
+    let chained = query!(&s, (baz: Baz<State>, bar in baz.bars, foo = bar.foo) => (baz.a, foo.a))
+        .collect::<Vec<_>>();
+    assert_eq!(chained, vec![(20, 0), (20, 3), (22, 1), (22, 1)]);
+}
+
+#[test]
+fn test_optional() {
+    let (s, _) = make_state();
+
+    let friends = query!(&s, (bar: Bar<State>, friend in bar.friend) => (bar.a, friend.a))
+        .collect::<Vec<_>>();
+    assert_eq!(friends, vec![(11, 10), (12, 11)]);
+}
+
+#[test]
+fn test_from_proxy() {
+    let (s, b) = make_state();
+
+    let start = b[2];
+    let chain = query!(
+        &s,
+        (bar = start, friend in bar.friend, foo = friend.foo) => (bar.a, friend.a, foo.a)
+    )
+    .collect::<Vec<_>>();
+    assert_eq!(chain, vec![(12, 11, 1)]);
+}
+
+#[test]
+fn test_product() {
+    let (s, _) = make_state();
+
+    let threshold = 2;
+    let pairs = query!(
+        &s,
+        (foo: Foo<State>, baz: Baz<State>) if foo.a >= threshold => (foo.a, baz.a)
+    )
+    .collect::<Vec<_>>();
+    assert_eq!(
+        pairs,
+        vec![(2, 20), (2, 21), (2, 22), (3, 20), (3, 21), (3, 22)]
+    );
+}
+
+#[test]
+fn test_proxy_bindings() {
+    let (s, b) = make_state();
+
+    let lonely = query!(&s, (p @ bar: Bar<State>) if bar.friend.is_none() => p).collect::<Vec<_>>();
+    assert_eq!(lonely, vec![b[0]]);
+
+    let start = b[2];
+    let friends = query!(
+        &s,
+        (bar = start, f @ friend in bar.friend, g @ foo = friend.foo) => (f, g, foo.a)
+    )
+    .collect::<Vec<_>>();
+    assert_eq!(friends, vec![(b[1], s.get(&b[1]).foo, 1)]);
+}
@@ -0,0 +1,123 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::quota::{Quota, QuotaExceeded};
+use persian_rug::{contextual, persian_rug, Context, Mutator, Table};
+
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[contextual(Rug)]
+struct Bar {
+    b: i32,
+}
+
+#[persian_rug]
+struct Rug {
+    #[table]
+    foos: Foo,
+    #[table]
+    bars: Bar,
+    #[resource]
+    quota: Quota,
+}
+
+fn make_rug(quota: Quota) -> Rug {
+    Rug {
+        foos: Default::default(),
+        bars: Default::default(),
+        quota,
+    }
+}
+
+#[test]
+fn test_table_limit() {
+    let mut r = make_rug(Quota::new().with_limit::<Foo>(2));
+    assert_eq!(r.resource::<Quota>().limit::<Foo>(), Some(2));
+    assert_eq!(r.resource::<Quota>().limit::<Bar>(), None);
+
+    r.try_add(Foo { a: 1 }).unwrap();
+    r.try_add(Foo { a: 2 }).unwrap();
+    let err = r.try_add(Foo { a: 3 }).unwrap_err();
+    assert_eq!(
+        err,
+        QuotaExceeded::Table {
+            type_name: std::any::type_name::<Foo>(),
+            limit: 2
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "cannot add another {}: the limit is 2 objects of that type",
+            std::any::type_name::<Foo>()
+        )
+    );
+    for b in 0..5 {
+        r.try_add(Bar { b }).unwrap();
+    }
+    assert_eq!(persian_rug::Owner::<Foo>::len(&r), 2);
+    assert_eq!(persian_rug::Owner::<Bar>::len(&r), 5);
+
+    r.resource_mut::<Quota>().set_limit::<Foo>(Some(3));
+    r.try_add(Foo { a: 3 }).unwrap();
+    r.resource_mut::<Quota>().set_limit::<Foo>(None);
+    r.try_add(Foo { a: 4 }).unwrap();
+    assert_eq!(r.get_iter::<Foo>().count(), 4);
+}
+
+#[test]
+fn test_total_limit() {
+    let mut r = make_rug(Quota::new().with_total(3));
+    r.try_add(Foo { a: 1 }).unwrap();
+    r.add(Bar { b: 1 });
+    r.try_add(Bar { b: 2 }).unwrap();
+    let err = r.try_add(Foo { a: 2 }).unwrap_err();
+    assert_eq!(err, QuotaExceeded::Total { limit: 3 });
+    assert_eq!(
+        err.to_string(),
+        "cannot add another object: the limit is 3 objects in total"
+    );
+
+    r.resource_mut::<Quota>().set_total(None);
+    r.try_add(Foo { a: 2 }).unwrap();
+    assert_eq!(r.resource::<Quota>().total(), None);
+}
+
+fn add_bar<M>(m: &mut M, b: i32) -> Result<(), QuotaExceeded>
+where
+    M: Mutator<Context = Rug> + std::ops::DerefMut<Target = Rug>,
+{
+    m.try_add(Bar { b }).map(|_| ())
+}
+
+#[test]
+fn test_quota_mutator() {
+    let mut r = make_rug(Quota::new().with_limit::<Bar>(1));
+    let mut m = &mut r;
+    assert!(add_bar(&mut m, 1).is_ok());
+    assert!(add_bar(&mut m, 2).is_err());
+    assert_eq!(r.get_iter::<Bar>().count(), 1);
+}
+
+#[test]
+fn test_quota_deleted() {
+    let mut r = make_rug(Quota::new().with_limit::<Foo>(1));
+    let f = r.try_add(Foo { a: 1 }).unwrap();
+    assert!(r.try_add(Foo { a: 2 }).is_err());
+    r.foos.mark_deleted(&f);
+    assert_eq!(r.foos.len(), 0);
+    assert!(r.foos.is_empty());
+    assert!(r.try_add(Foo { a: 2 }).is_ok());
+}
+
+#[test]
+fn test_table_len() {
+    let mut table = Table::<Foo>::new();
+    assert!(table.is_empty());
+    table.push(Foo { a: 1 });
+    table.push(Foo { a: 2 });
+    assert_eq!(table.len(), 2);
+}
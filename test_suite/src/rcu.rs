@@ -0,0 +1,114 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use persian_rug::{contextual, persian_rug, Context, Proxy, Rcu, Table};
+
+#[derive(Clone)]
+#[contextual(Bank)]
+struct Account {
+    balance: i64,
+}
+
+#[derive(Clone)]
+#[persian_rug]
+struct Bank(#[table] Account);
+
+fn setup() -> (Rcu<Bank>, Proxy<Account>, Proxy<Account>) {
+    let mut bank = Bank(Default::default());
+    let a = bank.add(Account { balance: 100 });
+    let b = bank.add(Account { balance: 100 });
+    (Rcu::new(bank), a, b)
+}
+
+#[test]
+fn test_readers_see_whole_updates() {
+    let (bank, a, b) = setup();
+    let bank = Arc::new(bank);
+
+    let writer = {
+        let bank = bank.clone();
+        std::thread::spawn(move || {
+            for _ in 0..200 {
+                bank.update(|bank| {
+                    bank.get_mut(&a).balance -= 1;
+                    bank.get_mut(&b).balance += 1;
+                });
+            }
+        })
+    };
+    let readers = (0..4)
+        .map(|_| {
+            let bank = bank.clone();
+            std::thread::spawn(move || {
+                for _ in 0..500 {
+                    let snapshot = bank.load();
+                    assert_eq!(snapshot.get(&a).balance + snapshot.get(&b).balance, 200);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    writer.join().unwrap();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    assert_eq!(bank.load().get(&a).balance, -100);
+    assert_eq!(bank.load().get(&b).balance, 300);
+}
+
+#[test]
+fn test_concurrent_writers() {
+    let (bank, a, _) = setup();
+    let bank = Arc::new(bank);
+    let writers = (0..4)
+        .map(|_| {
+            let bank = bank.clone();
+            std::thread::spawn(move || {
+                for _ in 0..50 {
+                    bank.update(|bank| bank.get_mut(&a).balance += 1);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+    assert_eq!(bank.load().get(&a).balance, 300);
+}
+
+#[test]
+fn test_failed_updates() {
+    let (bank, a, _) = setup();
+
+    let res = bank.try_update(|bank| {
+        bank.get_mut(&a).balance -= 500;
+        if bank.get(&a).balance < 0 {
+            return Err("overdrawn");
+        }
+        Ok(())
+    });
+    assert_eq!(res, Err("overdrawn"));
+    assert_eq!(bank.load().get(&a).balance, 100);
+
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        bank.update(|bank| {
+            bank.get_mut(&a).balance = 0;
+            panic!("oops");
+        })
+    }));
+    assert!(res.is_err());
+    assert_eq!(bank.load().get(&a).balance, 100);
+    bank.update(|bank| bank.get_mut(&a).balance += 1);
+    assert_eq!(bank.load().get(&a).balance, 101);
+}
+
+#[test]
+fn test_table() {
+    let table = Rcu::new(Table::<u32>::new());
+    let p = table.update(|table| table.push(1));
+    let old = table.store(Table::new());
+    assert_eq!(old.get(&p), Some(&1));
+    assert!(table.load().get(&p).is_none());
+}
@@ -0,0 +1,76 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::collections::BTreeSet;
+
+use persian_rug::handles::Recycling;
+use persian_rug::storage::MapStorage;
+use persian_rug::{contextual, persian_rug, Accessor, Context, Mutator, Proxy, ReadProxy, Table};
+
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[persian_rug]
+struct Rug(#[table] Foo);
+
+fn read_with<A: Accessor<Context = Rug>>(access: A, what: &ReadProxy<Foo>) -> i32 {
+    access.get_read(what).a
+}
+
+fn bump<M: Mutator<Context = Rug>>(mut m: M, what: &Proxy<Foo>, seen: &ReadProxy<Foo>) -> i32 {
+    m.get_mut(what).a += 1;
+    m.get_read(seen).a
+}
+
+#[test]
+fn test_read_proxy() {
+    let mut r = Rug(Default::default());
+    let p1 = r.add(Foo { a: 1 });
+    let p2 = r.add(Foo { a: 2 });
+    let q1 = ReadProxy::from(p1);
+    let q2: ReadProxy<Foo> = (&p2).into();
+
+    assert_eq!(r.get_read(&q1).a, 1);
+    assert_eq!(read_with(&r, &q2), 2);
+    assert_eq!(bump(&mut r, &p1, &q1), 2);
+
+    assert_eq!(q1, p1);
+    assert_eq!(q1, ReadProxy::from(p1));
+    assert_ne!(q1, q2);
+    assert_eq!(q2.handle(), p2.handle());
+    assert_eq!(
+        [q2, q1, q2].into_iter().collect::<BTreeSet<_>>(),
+        [p1, p2].into_iter().map(ReadProxy::from).collect()
+    );
+    assert_eq!(
+        format!("{:?}", q2),
+        format!(
+            "persian_rug::ReadProxy<{}> {{ handle: {} }}",
+            std::any::type_name::<Foo>(),
+            p2.handle()
+        )
+    );
+}
+
+#[test]
+fn test_debug_generation() {
+    let mut table = Table::<i32, MapStorage<i32>, _>::with_handles(Recycling::new());
+    let first = table.push(1);
+    assert!(table.delete(&first));
+    let second = table.push(2);
+    assert_eq!(second.handle(), first.handle());
+
+    assert_ne!(
+        format!("{:?}", ReadProxy::from(first)),
+        format!("{:?}", ReadProxy::from(second))
+    );
+    assert_eq!(
+        format!("{:?}", ReadProxy::from(second)),
+        format!(
+            "persian_rug::ReadProxy<i32> {{ handle: {}, generation: 1 }}",
+            second.handle()
+        )
+    );
+}
@@ -0,0 +1,296 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::borsh::{self, BorshDeserialize, BorshSerialize};
+use persian_rug::record::{Codec, Operation, Recording};
+use persian_rug::handles::Random;
+use persian_rug::{contextual, persian_rug, Context, Mutator, Proxy, Table};
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+#[borsh(crate = "persian_rug::borsh")]
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+    next: Option<Proxy<Foo>>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+#[borsh(crate = "persian_rug::borsh")]
+#[contextual(Rug)]
+struct Bar {
+    name: String,
+    foos: Vec<Proxy<Foo>>,
+}
+
+#[persian_rug]
+struct Rug {
+    #[table]
+    foos: Foo,
+    #[table(arena)]
+    bars: Bar,
+}
+
+fn new_rug() -> Rug {
+    Rug {
+        foos: Default::default(),
+        bars: Default::default(),
+    }
+}
+
+fn codec() -> Codec<Rug> {
+    Codec::new().register::<Foo>().register::<Bar>()
+}
+
+fn assert_same(a: &Rug, b: &Rug) {
+    assert_eq!(
+        a.get_iter::<Foo>().collect::<Vec<_>>(),
+        b.get_iter::<Foo>().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        a.get_proxy_iter::<Foo>().collect::<Vec<_>>(),
+        b.get_proxy_iter::<Foo>().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        a.get_iter::<Bar>().collect::<Vec<_>>(),
+        b.get_iter::<Bar>().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        a.get_proxy_iter::<Bar>().collect::<Vec<_>>(),
+        b.get_proxy_iter::<Bar>().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_replay() {
+    let codec = codec();
+    let mut r = new_rug();
+    let mut rec = codec.recorder(&mut r);
+
+    let f1 = rec.add(Foo { a: 1, next: None });
+    let f2 = rec.add(Foo { a: 2, next: None });
+    let b = rec.add(Bar {
+        name: "bar".to_string(),
+        foos: vec![f1],
+    });
+    rec.get_mut(&f1).next = Some(f2);
+    rec.get_mut(&b).foos.push(f2);
+    for foo in rec.get_iter_mut::<Foo>() {
+        foo.a *= 10;
+    }
+    let recording = rec.finish().unwrap();
+
+    assert_eq!(r.get(&f1).a, 10);
+    assert_eq!(r.get(&b).foos, vec![f1, f2]);
+
+    let mut replayed = new_rug();
+    codec.replay(&recording, &mut replayed).unwrap();
+    assert_same(&r, &replayed);
+}
+
+#[test]
+fn test_operations() {
+    let codec = codec();
+    let mut r = new_rug();
+    let mut rec = codec.recorder(&mut r);
+
+    let f = rec.add(Foo { a: 1, next: None });
+    rec.get_mut(&f).a = 2;
+    rec.get_mut(&f).a = 3;
+    assert_eq!(rec.get(&f).a, 3);
+    let recording = rec.finish().unwrap();
+
+    let ty = std::any::type_name::<Foo>().to_string();
+    assert_eq!(
+        recording.operations(),
+        &[
+            Operation::Add {
+                ty: ty.clone(),
+                index: 0,
+                value: borsh::to_vec(&Foo { a: 1, next: None }).unwrap(),
+            },
+            Operation::Set {
+                ty: ty.clone(),
+                index: 0,
+                value: borsh::to_vec(&Foo { a: 2, next: None }).unwrap(),
+            },
+            Operation::Set {
+                ty,
+                index: 0,
+                value: borsh::to_vec(&Foo { a: 3, next: None }).unwrap(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    let codec = codec();
+    let mut r = new_rug();
+    let mut rec = codec.recorder(&mut r);
+
+    let f = rec.add(Foo { a: 1, next: None });
+    rec.add(Bar {
+        name: "bar".to_string(),
+        foos: vec![f],
+    });
+    rec.get_mut(&f).a = 5;
+    let recording = rec.finish().unwrap();
+
+    let bytes = borsh::to_vec(&recording).unwrap();
+    let loaded = Recording::try_from_slice(&bytes).unwrap();
+    assert_eq!(loaded, recording);
+
+    let mut replayed = new_rug();
+    codec.replay(&loaded, &mut replayed).unwrap();
+    assert_same(&r, &replayed);
+}
+
+#[test]
+fn test_replay_onto_existing() {
+    let codec = codec();
+    let mut r = new_rug();
+    r.add(Foo { a: 0, next: None });
+
+    let mut rec = codec.recorder(&mut r);
+    let f = rec.add(Foo { a: 1, next: None });
+    let recording = rec.finish().unwrap();
+    assert_eq!(recording.len(), 1);
+    assert_eq!(r.get(&f).a, 1);
+
+    let mut replayed = new_rug();
+    replayed.add(Foo { a: 0, next: None });
+    codec.replay(&recording, &mut replayed).unwrap();
+    assert_same(&r, &replayed);
+
+    // Objects are replayed under their recorded handles, even where
+    // the context would have issued others.
+    let mut empty = new_rug();
+    codec.replay(&recording, &mut empty).unwrap();
+    assert_eq!(empty.get(&f).a, 1);
+    assert_eq!(empty.get_iter::<Foo>().count(), 1);
+
+    let err = codec.replay(&recording, &mut empty).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+#[borsh(crate = "persian_rug::borsh")]
+#[contextual(RandomRug)]
+struct Baz {
+    a: i32,
+    next: Option<Proxy<Baz>>,
+}
+
+#[persian_rug(handles = Random)]
+struct RandomRug(#[table] Baz);
+
+#[test]
+fn test_replay_random_handles() {
+    let codec = Codec::<RandomRug>::new().register::<Baz>();
+    let mut r = RandomRug(Table::with_handles(Random::new(1)));
+    let mut rec = codec.recorder(&mut r);
+    let b1 = rec.add(Baz { a: 1, next: None });
+    let b2 = rec.add(Baz {
+        a: 2,
+        next: Some(b1),
+    });
+    rec.get_mut(&b1).next = Some(b2);
+    let recording = rec.finish().unwrap();
+
+    // A table with another seed issues other handles.
+    let mut replayed = RandomRug(Table::with_handles(Random::new(2)));
+    codec.replay(&recording, &mut replayed).unwrap();
+    assert_eq!(replayed.get(&b1).next, Some(b2));
+    assert_eq!(replayed.get(&b2).next, Some(b1));
+}
+
+#[test]
+fn test_unregistered() {
+    let codec = codec();
+    let mut r = new_rug();
+    let mut rec = codec.recorder(&mut r);
+    rec.add(Bar {
+        name: "bar".to_string(),
+        foos: Vec::new(),
+    });
+    let recording = rec.finish().unwrap();
+
+    let foos_only = Codec::<Rug>::new().register::<Foo>();
+    let mut replayed = new_rug();
+    let err = foos_only.replay(&recording, &mut replayed).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+#[should_panic]
+fn test_record_unregistered() {
+    let codec = Codec::<Rug>::new().register::<Foo>();
+    let mut r = new_rug();
+    let mut rec = codec.recorder(&mut r);
+    rec.add(Bar {
+        name: "bar".to_string(),
+        foos: Vec::new(),
+    });
+}
+
+#[test]
+fn test_journal() {
+    let codec = codec();
+    let mut log = Vec::new();
+    let mut r = new_rug();
+    let mut journal = codec.journal(&mut r, &mut log);
+
+    let f1 = journal.add(Foo { a: 1, next: None });
+    let f2 = journal.add(Foo { a: 2, next: None });
+    journal.get_mut(&f1).next = Some(f2);
+    journal.add(Bar {
+        name: "bar".to_string(),
+        foos: vec![f1, f2],
+    });
+    for foo in journal.get_iter_mut::<Foo>() {
+        foo.a *= 10;
+    }
+    assert!(journal.finish().unwrap().is_empty());
+
+    let mut replayed = new_rug();
+    assert_eq!(replayed.replay(&codec, log.as_slice()).unwrap(), 6);
+    assert_same(&r, &replayed);
+}
+
+#[test]
+fn test_journal_truncated() {
+    let codec = codec();
+    let mut log = Vec::new();
+    let mut r = new_rug();
+    let mut journal = codec.journal(&mut r, &mut log);
+    let f = journal.add(Foo { a: 1, next: None });
+    journal.finish().unwrap();
+    let complete = log.len();
+
+    let mut journal = codec.journal(&mut r, &mut log);
+    journal.get_mut(&f).a = 2;
+    journal.finish().unwrap();
+
+    for len in complete..log.len() {
+        let mut replayed = new_rug();
+        assert_eq!(replayed.replay(&codec, &log[..len]).unwrap(), 1);
+        assert_eq!(replayed.get(&f).a, 1);
+    }
+    let mut replayed = new_rug();
+    assert_eq!(replayed.replay(&codec, log.as_slice()).unwrap(), 2);
+    assert_eq!(replayed.get(&f).a, 2);
+}
+
+#[test]
+fn test_journal_corrupt() {
+    let codec = codec();
+    let mut log = Vec::new();
+    let mut r = new_rug();
+    let mut journal = codec.journal(&mut r, &mut log);
+    journal.add(Foo { a: 1, next: None });
+    journal.finish().unwrap();
+
+    log[4] = 0xff;
+    let mut replayed = new_rug();
+    assert!(replayed.replay(&codec, log.as_slice()).is_err());
+}
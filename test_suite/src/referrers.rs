@@ -0,0 +1,116 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::search::Search;
+use persian_rug::{contextual, persian_rug, seed, Accessor, AnyProxy, Context, Edge, Links, Proxy};
+
+#[contextual(Rug)]
+struct Node {
+    #[search]
+    name: String,
+    #[link]
+    parent: Option<Proxy<Node>>,
+    #[link]
+    children: Vec<Proxy<Node>>,
+}
+
+#[contextual(Rug)]
+struct Label(#[link] Proxy<Node>, #[search] String);
+
+#[persian_rug(referrers)]
+struct Rug {
+    #[table(search)]
+    nodes: Node,
+    #[table(arena)]
+    labels: Label,
+    #[table]
+    edges: Edge<Node, Label>,
+}
+
+fn make_rug() -> Rug {
+    Rug {
+        nodes: Default::default(),
+        labels: Default::default(),
+        edges: Default::default(),
+    }
+}
+
+fn any<T: 'static>(ps: &[Proxy<T>]) -> Vec<AnyProxy> {
+    ps.iter().copied().map(AnyProxy::from).collect()
+}
+
+#[test]
+fn test_referrers() {
+    let mut r = make_rug();
+    let root = r.add(Node {
+        name: "root".to_string(),
+        parent: None,
+        children: Vec::new(),
+    });
+    let kids = seed(&mut r, 3, |ix, _| Node {
+        name: format!("kid {}", ix),
+        parent: Some(root),
+        children: Vec::new(),
+    });
+    r.get_mut(&root).children = kids.clone();
+    let label = r.add(Label(kids[1], "middle".to_string()));
+    let edge = r.add(Edge::new(kids[2], label, ()));
+
+    let access = &r;
+    assert_eq!(access.referrers(&root), any(&kids));
+    assert_eq!(access.referrers(&kids[0]), vec![AnyProxy::from(root)]);
+    assert_eq!(
+        access.referrers(&kids[1]),
+        vec![AnyProxy::from(root), AnyProxy::from(label)]
+    );
+    assert_eq!(
+        access.referrers(&kids[2]),
+        vec![AnyProxy::from(root), AnyProxy::from(edge)]
+    );
+    assert_eq!(access.referrers(&label), vec![AnyProxy::from(edge)]);
+    assert_eq!(access.referrers(&edge), Vec::<AnyProxy>::new());
+
+    // Changes made through mutable references are picked up.
+    r.get_mut(&root).children.truncate(1);
+    r.get_mut(&label).0 = kids[0];
+    let access = &r;
+    assert_eq!(
+        access.referrers(&kids[0]),
+        vec![AnyProxy::from(root), AnyProxy::from(label)]
+    );
+    assert_eq!(access.referrers(&kids[1]), Vec::<AnyProxy>::new());
+
+    for node in r.get_iter_mut::<Node>() {
+        node.parent = None;
+    }
+    assert_eq!((&r).referrers(&root), Vec::<AnyProxy>::new());
+
+    // Search still works over the wrapped storage.
+    assert_eq!(r.search::<Node>("kid"), kids);
+}
+
+#[test]
+fn test_any_proxy() {
+    let mut r = make_rug();
+    let node = r.add(Node {
+        name: String::new(),
+        parent: None,
+        children: Vec::new(),
+    });
+    let label = r.add(Label(node, String::new()));
+
+    let a = AnyProxy::from(node);
+    let b = AnyProxy::from(label);
+    assert!(a.is::<Node>());
+    assert!(!a.is::<Label>());
+    assert_eq!(a.downcast::<Node>(), Some(node));
+    assert_eq!(b.downcast::<Node>(), None);
+    assert_eq!(a, node);
+    assert_ne!(a, b);
+    assert!(a.type_name().ends_with("Node"));
+    assert!(format!("{:?}", b).contains("Label"));
+
+    let mut links = Vec::new();
+    r.get(&label).for_each_link(&mut |p| links.push(p));
+    assert_eq!(links, vec![a]);
+}
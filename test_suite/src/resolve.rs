@@ -0,0 +1,253 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::resolve::{CyclePolicy, Resolved, Resolver};
+use persian_rug::{contextual, persian_rug, Absorb, Context, Proxy, Resolve};
+
+#[derive(Absorb, Resolve)]
+#[resolve(derive(Clone, Debug, PartialEq))]
+#[contextual(State)]
+struct Person {
+    name: String,
+    #[resolve]
+    manager: Option<Proxy<Person>>,
+    #[resolve]
+    reports: Vec<Proxy<Person>>,
+}
+
+#[derive(Absorb, Resolve)]
+#[resolve(derive(Debug))]
+#[contextual(State)]
+struct Team(String, #[resolve] Box<Proxy<Person>>);
+
+#[persian_rug]
+struct State(#[table] Person, #[table] Team);
+
+fn new_state() -> State {
+    State(Default::default(), Default::default())
+}
+
+fn person(s: &mut State, name: &str, manager: Option<Proxy<Person>>) -> Proxy<Person> {
+    let p = s.add(Person {
+        name: name.to_string(),
+        manager,
+        reports: Vec::new(),
+    });
+    if let Some(m) = manager {
+        s.get_mut(&m).reports.push(p);
+    }
+    p
+}
+
+#[test]
+fn test_resolve_tree() {
+    let mut s = new_state();
+    let alice = s.add(Person {
+        name: "Alice".to_string(),
+        manager: None,
+        reports: Vec::new(),
+    });
+    let bob = s.add(Person {
+        name: "Bob".to_string(),
+        manager: None,
+        reports: Vec::new(),
+    });
+    let carol = s.add(Person {
+        name: "Carol".to_string(),
+        manager: None,
+        reports: vec![alice, bob],
+    });
+    let team = s.add(Team("core".to_string(), Box::new(carol)));
+
+    let access = &s;
+    let resolved = s.get(&team).resolve(&access);
+    assert_eq!(resolved.0, "core");
+    let lead = resolved.1.value().unwrap();
+    assert_eq!(lead.name, "Carol");
+    assert_eq!(
+        lead.reports
+            .iter()
+            .map(|r| r.value().unwrap().name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["Alice", "Bob"]
+    );
+    assert_eq!(
+        lead.reports[0].clone().into_value(),
+        Some(PersonResolved {
+            name: "Alice".to_string(),
+            manager: None,
+            reports: Vec::new(),
+        })
+    );
+}
+
+#[test]
+fn test_resolve_cycle() {
+    let mut s = new_state();
+    let alice = person(&mut s, "Alice", None);
+    let bob = person(&mut s, "Bob", Some(alice));
+
+    let access = &s;
+    let resolved = Resolver::default().resolve(&access, &bob);
+    let bob_resolved = resolved.value().unwrap();
+    let alice_resolved = bob_resolved.manager.as_ref().unwrap().value().unwrap();
+    assert_eq!(alice_resolved.name, "Alice");
+    assert_eq!(alice_resolved.reports, vec![Resolved::Cut(bob)]);
+
+    // Without the starting proxy, the cycle is found one level later.
+    let resolved = s.get(&bob).resolve(&access);
+    let alice_resolved = resolved.manager.as_ref().unwrap().value().unwrap();
+    let bob_again = alice_resolved.reports[0].value().unwrap();
+    assert_eq!(bob_again.name, "Bob");
+    assert_eq!(bob_again.manager, Some(Resolved::Cut(alice)));
+}
+
+#[test]
+#[should_panic(expected = "cycle found")]
+fn test_resolve_cycle_panics() {
+    let mut s = new_state();
+    let alice = person(&mut s, "Alice", None);
+    person(&mut s, "Bob", Some(alice));
+    let access = &s;
+    Resolver::new(CyclePolicy::Panic).resolve(&access, &alice);
+}
+
+#[test]
+fn test_resolve_depth() {
+    let mut s = new_state();
+    let a = person(&mut s, "a", None);
+    let b = person(&mut s, "b", Some(a));
+    let c = person(&mut s, "c", Some(b));
+
+    let access = &s;
+    let mut resolver = Resolver::new(CyclePolicy::Cut).max_depth(2);
+    let resolved = resolver.resolve(&access, &c).into_value().unwrap();
+    let b_resolved = resolved.manager.unwrap().into_value().unwrap();
+    assert_eq!(b_resolved.name, "b");
+    assert_eq!(b_resolved.manager, Some(Resolved::Cut(a)));
+    assert_eq!(b_resolved.reports, vec![Resolved::Cut(c)]);
+
+    let resolved = Resolver::new(CyclePolicy::Cut)
+        .max_depth(0)
+        .resolve(&access, &c);
+    assert_eq!(resolved, Resolved::Cut(c));
+}
+
+mod generic {
+    use persian_rug::{contextual, Absorb, Context, Proxy, Resolve};
+
+    #[derive(Absorb, Resolve)]
+    #[contextual(C)]
+    pub struct Foo<C: Context> {
+        pub _marker: core::marker::PhantomData<C>,
+        pub a: i32,
+    }
+
+    #[derive(Absorb, Resolve)]
+    #[contextual(C)]
+    pub struct Bar<C: Context> {
+        #[resolve]
+        pub foo: Proxy<Foo<C>>,
+        #[resolve]
+        pub next: Option<Proxy<Bar<C>>>,
+    }
+}
+
+#[persian_rug]
+struct Rug(#[table] generic::Foo<Rug>, #[table] generic::Bar<Rug>);
+
+#[test]
+fn test_resolve_generic() {
+    let mut r = Rug(Default::default(), Default::default());
+    let foo = r.add(generic::Foo {
+        _marker: Default::default(),
+        a: 3,
+    });
+    let first = r.add(generic::Bar { foo, next: None });
+    let bar = r.add(generic::Bar {
+        foo,
+        next: Some(first),
+    });
+    let access = &r;
+    let resolved: generic::BarResolved<Rug> = r.get(&bar).resolve(&access);
+    assert_eq!(resolved.foo.value().unwrap().a, 3);
+    let next = resolved.next.unwrap().into_value().unwrap();
+    assert_eq!(next.foo.value().unwrap().a, 3);
+    assert!(next.next.is_none());
+}
+
+#[test]
+fn test_absorb() {
+    let mut s = new_state();
+    let existing = person(&mut s, "Dave", None);
+    let input = TeamResolved(
+        "core".to_string(),
+        Box::new(Resolved::new(PersonResolved {
+            name: "Carol".to_string(),
+            manager: Some(Resolved::Cut(existing)),
+            reports: vec![
+                Resolved::new(PersonResolved {
+                    name: "Alice".to_string(),
+                    manager: None,
+                    reports: Vec::new(),
+                }),
+                Resolved::new(PersonResolved {
+                    name: "Bob".to_string(),
+                    manager: None,
+                    reports: Vec::new(),
+                }),
+            ],
+        })),
+    );
+    let team = Team::absorb(input, &mut &mut s);
+
+    assert_eq!(s.get_iter::<Person>().count(), 4);
+    let carol = *s.get(&team).1;
+    assert_eq!(s.get(&carol).name, "Carol");
+    assert_eq!(s.get(&carol).manager, Some(existing));
+    let names = s
+        .get(&carol)
+        .reports
+        .iter()
+        .map(|p| s.get(p).name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Alice", "Bob"]);
+}
+
+#[test]
+fn test_absorb_round_trip() {
+    // Cut proxies refer to the original context, so only trees can
+    // be copied to another context this way.
+    let mut s = new_state();
+    let bob = person(&mut s, "Bob", None);
+    let carol = person(&mut s, "Carol", None);
+    let alice = s.add(Person {
+        name: "Alice".to_string(),
+        manager: None,
+        reports: vec![bob, carol],
+    });
+
+    let access = &s;
+    let resolved = Resolver::default().resolve(&access, &alice);
+    let mut t = new_state();
+    let copy = Person::absorb(resolved.clone().into_value().unwrap(), &mut &mut t);
+    let access = &t;
+    assert_eq!(Resolver::default().resolve(&access, &copy), resolved);
+}
+
+#[test]
+fn test_absorb_generic() {
+    let mut r = Rug(Default::default(), Default::default());
+    let bar = generic::Bar::absorb(
+        generic::BarResolved {
+            foo: Resolved::new(generic::FooResolved {
+                _marker: Default::default(),
+                a: 1,
+            }),
+            next: None,
+            _marker: Default::default(),
+        },
+        &mut &mut r,
+    );
+    assert_eq!(r.get(&r.get(&bar).foo).a, 1);
+}
@@ -0,0 +1,78 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use persian_rug::{contextual, persian_rug, Context, Proxy, ResolveProxies};
+
+#[contextual(Rug)]
+struct Task {
+    name: &'static str,
+    after: Vec<Proxy<Task>>,
+}
+
+#[persian_rug]
+struct Rug(#[table] Task);
+
+fn setup() -> (Rug, Vec<Proxy<Task>>) {
+    let mut r = Rug(Default::default());
+    let a = r.add(Task {
+        name: "a",
+        after: vec![],
+    });
+    let b = r.add(Task {
+        name: "b",
+        after: vec![a],
+    });
+    let c = r.add(Task {
+        name: "c",
+        after: vec![a, b],
+    });
+    (r, vec![a, b, c])
+}
+
+#[test]
+fn test_resolve_owned_and_borrowed() {
+    let (r, ps) = setup();
+
+    let access = &r;
+    let owned = ps.clone().into_iter().resolve(&access).map(|t| t.name);
+    assert_eq!(owned.collect::<Vec<_>>(), vec!["a", "b", "c"]);
+
+    let borrowed = r.get(&ps[2]).after.iter().resolve(&access).map(|t| t.name);
+    assert_eq!(borrowed.collect::<Vec<_>>(), vec!["a", "b"]);
+
+    assert_eq!(ps.iter().resolve(&&r).size_hint(), (3, Some(3)));
+}
+
+#[test]
+fn test_resolve_entries() {
+    let (r, ps) = setup();
+
+    let entries = ps
+        .iter()
+        .rev()
+        .resolve_entries(&&r)
+        .map(|(p, t)| (p, t.name))
+        .collect::<Vec<_>>();
+    assert_eq!(entries, vec![(ps[2], "c"), (ps[1], "b"), (ps[0], "a")]);
+}
+
+#[test]
+fn test_resolve_through_arc() {
+    let (r, ps) = setup();
+    let r = Arc::new(r);
+
+    let names = ps.iter().resolve(&r).map(|t| t.name).collect::<Vec<_>>();
+    assert_eq!(names, vec!["a", "b", "c"]);
+}
+
+#[test]
+#[should_panic]
+fn test_resolve_missing() {
+    let (r, _) = setup();
+    let _ = [Proxy::<Task>::from_handle(99)]
+        .into_iter()
+        .resolve(&&r)
+        .count();
+}
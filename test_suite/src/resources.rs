@@ -0,0 +1,98 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, Context, Proxy, ResourceOwner};
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Item {
+    name: &'static str,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Config {
+    limit: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Counter(u32);
+
+#[derive(Clone)]
+#[persian_rug]
+struct Rug {
+    #[table]
+    items: Item,
+    #[resource]
+    config: Config,
+    /// Doc comments and other attributes are kept.
+    #[resource]
+    counter: Counter,
+    #[cfg(any())]
+    #[resource]
+    missing: String,
+    plain: Vec<u8>,
+}
+
+fn new_rug(limit: usize) -> Rug {
+    Rug {
+        items: Default::default(),
+        config: Config { limit },
+        counter: Counter(0),
+        plain: Vec::new(),
+    }
+}
+
+/// Take a place within the configured limit, if there is one.
+fn take_place<C>(context: &mut C) -> bool
+where
+    C: ResourceOwner<Config> + ResourceOwner<Counter>,
+{
+    let limit = ResourceOwner::<Config>::get_resource(context).limit;
+    let counter = ResourceOwner::<Counter>::get_resource_mut(context);
+    if counter.0 as usize >= limit {
+        return false;
+    }
+    counter.0 += 1;
+    true
+}
+
+fn add_limited(r: &mut Rug, name: &'static str) -> Option<Proxy<Item>> {
+    take_place(r).then(|| r.add(Item { name }))
+}
+
+#[test]
+fn test_resources() {
+    let mut r = new_rug(2);
+    assert!(add_limited(&mut r, "a").is_some());
+    assert!(add_limited(&mut r, "b").is_some());
+    assert!(add_limited(&mut r, "c").is_none());
+    assert_eq!(r.resource::<Counter>(), &Counter(2));
+    assert_eq!(r.get_iter::<Item>().count(), 2);
+
+    r.resource_mut::<Config>().limit = 3;
+    assert!(add_limited(&mut r, "c").is_some());
+    assert_eq!(r.counter, Counter(3));
+}
+
+#[test]
+fn test_resources_clone_with_context() {
+    let mut r = new_rug(1);
+    let snapshot = r.clone();
+    r.resource_mut::<Counter>().0 = 7;
+    assert_eq!(snapshot.resource::<Counter>(), &Counter(0));
+    assert_eq!(r.resource::<Counter>(), &Counter(7));
+}
+
+#[contextual(TupleRug)]
+struct Other;
+
+#[persian_rug]
+struct TupleRug(#[table] Other, #[resource] Config);
+
+#[test]
+fn test_tuple_resources() {
+    let mut r = TupleRug(Default::default(), Config { limit: 4 });
+    r.add(Other);
+    r.resource_mut::<Config>().limit += 1;
+    assert_eq!(r.1.limit, 5);
+}
@@ -0,0 +1,111 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::rewrite::{RewriteError, Rewriter};
+use persian_rug::{contextual, persian_rug, query, Accessor, Context, Proxy};
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+enum Op {
+    Const(i64),
+    Add(Proxy<Op>, Proxy<Op>),
+    Mul(Proxy<Op>, Proxy<Op>),
+    Div(Proxy<Op>, Proxy<Op>),
+}
+
+#[derive(Clone)]
+#[persian_rug]
+struct Rug(#[table] Op);
+
+fn constant<A: Accessor<Context = Rug>>(access: &A, p: &Proxy<Op>) -> Option<i64> {
+    match access.get(p) {
+        Op::Const(c) => Some(*c),
+        _ => None,
+    }
+}
+
+// Fold an operation on two constants, giving `None` for the result
+// if it is undefined.
+fn fold(r: &Rug) -> Option<(Proxy<Op>, Option<i64>)> {
+    query!(r, (p @ op: Op) => p).find_map(|p| {
+        let (a, b, f): (_, _, fn(i64, i64) -> Option<i64>) = match r.get(&p) {
+            Op::Add(a, b) => (a, b, |a, b| Some(a + b)),
+            Op::Mul(a, b) => (a, b, |a, b| Some(a * b)),
+            Op::Div(a, b) => (a, b, |a, b| a.checked_div(b)),
+            Op::Const(_) => return None,
+        };
+        let a = constant(&r, a)?;
+        let b = constant(&r, b)?;
+        Some((p, f(a, b)))
+    })
+}
+
+fn folding<'r>() -> Rewriter<'r, Rug, String> {
+    Rewriter::new().rule(fold, |r, (p, value)| {
+        *r.get_mut(&p) = Op::Const(value.ok_or("division by zero")?);
+        Ok(())
+    })
+}
+
+fn make_rug() -> (Rug, Proxy<Op>, Proxy<Op>) {
+    let mut r = Rug(Default::default());
+    let two = r.add(Op::Const(2));
+    let three = r.add(Op::Const(3));
+    let zero = r.add(Op::Const(0));
+    let x = r.add(Op::Const(7));
+    let sum = r.add(Op::Add(two, three));
+    let prod = r.add(Op::Mul(sum, zero));
+    let total = r.add(Op::Add(prod, x));
+    (r, total, zero)
+}
+
+#[test]
+fn test_rewrite() {
+    let (mut r, total, _) = make_rug();
+
+    // Multiplying by zero gives zero, whatever the other side is.
+    let mut rw = folding().rule(
+        |r| {
+            query!(r, (p @ op: Op) => p).find(|p| match r.get(p) {
+                Op::Mul(a, b) => {
+                    constant(&r, a) == Some(0) && constant(&r, b).is_none()
+                        || constant(&r, b) == Some(0) && constant(&r, a).is_none()
+                }
+                _ => false,
+            })
+        },
+        |r, p| {
+            *r.get_mut(&p) = Op::Const(0);
+            Ok(())
+        },
+    );
+    assert_eq!(rw.run(&mut r), Ok(3));
+    assert_eq!(r.get(&total), &Op::Const(7));
+
+    // Nothing more to do.
+    assert_eq!(rw.run(&mut r), Ok(0));
+}
+
+#[test]
+fn test_rewrite_rolls_back() {
+    let (mut r, total, zero) = make_rug();
+    let bad = r.add(Op::Div(total, zero));
+
+    let before = query!(&r, (op: Op) => op.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        folding().run(&mut r),
+        Err(RewriteError::Failed("division by zero".to_string()))
+    );
+    let after = query!(&r, (op: Op) => op.clone()).collect::<Vec<_>>();
+    assert_eq!(before, after);
+    assert!(matches!(r.get(&bad), Op::Div(_, _)));
+
+    assert_eq!(
+        folding().limit(2).run(&mut r),
+        Err(RewriteError::LimitReached(2))
+    );
+    assert_eq!(
+        query!(&r, (op: Op) => op.clone()).collect::<Vec<_>>(),
+        before
+    );
+}
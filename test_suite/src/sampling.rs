@@ -0,0 +1,109 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{
+    contextual, persian_rug, sample_weighted, Context, Proxy, SeedRng, WeightedSampler,
+};
+
+#[contextual(Rug)]
+struct Node {
+    size: f64,
+}
+
+#[persian_rug]
+struct Rug(#[table] Node);
+
+fn setup(sizes: &[f64]) -> (Rug, Vec<Proxy<Node>>) {
+    let mut r = Rug(Default::default());
+    let proxies = sizes
+        .iter()
+        .map(|size| r.add(Node { size: *size }))
+        .collect();
+    (r, proxies)
+}
+
+fn counts(proxies: &[Proxy<Node>], mut pick: impl FnMut() -> Proxy<Node>) -> Vec<usize> {
+    let mut res = vec![0; proxies.len()];
+    for _ in 0..10000 {
+        let p = pick();
+        res[proxies.iter().position(|q| *q == p).unwrap()] += 1;
+    }
+    res
+}
+
+fn assert_near(counts: &[usize], expected: &[usize]) {
+    for (count, expected) in counts.iter().zip(expected) {
+        assert!(
+            count.abs_diff(*expected) < 300,
+            "got {:?}, expected about {:?}",
+            counts,
+            expected
+        );
+    }
+}
+
+#[test]
+fn test_sample_weighted() {
+    let (r, proxies) = setup(&[1.0, 0.0, 3.0, 6.0]);
+    let mut rng = SeedRng::new(3);
+    let counts = counts(&proxies, || {
+        sample_weighted(&&r, &mut rng, |node: &Node| node.size).unwrap()
+    });
+    assert_eq!(counts[1], 0);
+    assert_near(&counts, &[1000, 0, 3000, 6000]);
+}
+
+#[test]
+fn test_sampler() {
+    let (r, proxies) = setup(&[5.0, 2.5, 0.0, 2.5]);
+    let sampler = WeightedSampler::new(&&r, |node: &Node| node.size);
+    assert_eq!(sampler.len(), 3);
+    let mut rng = SeedRng::new(11);
+    let counts = counts(&proxies, || sampler.sample(&mut rng).unwrap());
+    assert_eq!(counts[2], 0);
+    assert_near(&counts, &[5000, 2500, 0, 2500]);
+}
+
+#[test]
+fn test_sampler_from_weights() {
+    let (_, proxies) = setup(&[0.0, 0.0]);
+    // Repeated proxies have their weights combined.
+    let sampler =
+        WeightedSampler::from_weights([(proxies[0], 1.0), (proxies[1], 1.0), (proxies[0], 2.0)]);
+    let mut rng = SeedRng::new(5);
+    let counts = counts(&proxies, || sampler.sample(&mut rng).unwrap());
+    assert_near(&counts, &[7500, 2500]);
+}
+
+#[test]
+fn test_zero_weights() {
+    let (r, _) = setup(&[0.0, 0.0]);
+    let mut rng = SeedRng::new(0);
+    assert_eq!(
+        sample_weighted(&&r, &mut rng, |node: &Node| node.size),
+        None
+    );
+    let sampler = WeightedSampler::new(&&r, |node: &Node| node.size);
+    assert!(sampler.is_empty());
+    assert_eq!(sampler.sample(&mut rng), None);
+
+    let (r, _) = setup(&[]);
+    assert_eq!(
+        sample_weighted(&&r, &mut rng, |node: &Node| node.size),
+        None
+    );
+}
+
+#[test]
+#[should_panic(expected = "sampling weights must be finite and not negative")]
+fn test_negative_weight() {
+    let (r, _) = setup(&[1.0, -1.0]);
+    WeightedSampler::new(&&r, |node: &Node| node.size);
+}
+
+#[test]
+#[should_panic(expected = "sampling weights must be finite and not negative")]
+fn test_nan_weight() {
+    let (r, _) = setup(&[f64::NAN]);
+    sample_weighted(&&r, &mut SeedRng::new(0), |node: &Node| node.size);
+}
@@ -0,0 +1,153 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::handles::Recycling;
+use persian_rug::{contextual, persian_rug, seed, Context, Proxy};
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Bar {
+    foo: Proxy<Foo>,
+    next: Option<Proxy<Bar>>,
+}
+
+#[persian_rug]
+struct Rug(#[table] Foo, #[table(arena)] Bar);
+
+fn make_rug() -> (Rug, Vec<Proxy<Foo>>) {
+    let mut r = Rug(Default::default(), Default::default());
+    let foos = seed(&mut r, 3, |ix, _| Foo { a: ix as i32 });
+    (r, foos)
+}
+
+#[test]
+fn test_sandbox_reads() {
+    let (mut r, foos) = make_rug();
+    let mut s = r.sandbox();
+    assert!(!s.is_modified());
+    assert_eq!(s.get(&foos[1]).a, 1);
+
+    s.get_mut(&foos[1]).a = 10;
+    let f = s.add(Foo { a: 3 });
+    assert!(s.is_modified());
+    assert_eq!(s.get(&foos[1]).a, 10);
+    assert_eq!(s.base().get(&foos[1]).a, 1);
+    assert_eq!(s.get(&f).a, 3);
+
+    let seen = s
+        .get_iter::<Foo>()
+        .map(|(p, foo)| (p, foo.a))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        seen,
+        vec![(foos[0], 0), (foos[1], 10), (foos[2], 2), (f, 3)]
+    );
+    assert_eq!(s.get_iter::<Bar>().count(), 0);
+}
+
+#[test]
+fn test_sandbox_commit() {
+    let (mut r, foos) = make_rug();
+    let existing = r.add(Bar {
+        foo: foos[0],
+        next: None,
+    });
+
+    let mut s = r.sandbox();
+    let f = s.add(Foo { a: 7 });
+    let b1 = s.add(Bar { foo: f, next: None });
+    let b2 = s.add(Bar {
+        foo: foos[2],
+        next: Some(b1),
+    });
+    s.get_mut(&b1).next = Some(existing);
+    s.get_mut(&existing).next = Some(b2);
+    s.get_mut(&f).a += 1;
+    s.commit();
+
+    assert_eq!(r.get(&f).a, 8);
+    assert_eq!(r.get(&r.get(&b1).foo).a, 8);
+    assert_eq!(r.get(&b2).next, Some(b1));
+    assert_eq!(r.get(&b1).next, Some(existing));
+    assert_eq!(r.get(&existing).next, Some(b2));
+    assert_eq!(r.get_iter::<Foo>().count(), 4);
+    assert_eq!(r.get_iter::<Bar>().count(), 3);
+
+    // Proxies issued after a commit carry on from the committed ones.
+    let g = r.add(Foo { a: 9 });
+    assert_ne!(g, f);
+    assert_eq!(r.get(&f).a, 8);
+}
+
+#[test]
+fn test_sandbox_discard() {
+    let (mut r, foos) = make_rug();
+
+    let mut s = r.sandbox();
+    s.get_mut(&foos[0]).a = 100;
+    let f = s.add(Foo { a: 5 });
+    s.discard();
+
+    assert_eq!(r.get(&foos[0]).a, 0);
+    assert_eq!(r.get_iter::<Foo>().count(), 3);
+
+    {
+        let mut s = r.sandbox();
+        s.get_mut(&foos[2]).a = 100;
+    }
+    assert_eq!(r.get(&foos[2]).a, 2);
+
+    // The discarded proxy is not handed out again.
+    assert_ne!(r.add(Foo { a: 6 }), f);
+    assert!(r.try_get(&f).is_err());
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(RecyclingRug)]
+struct Baz {
+    a: i32,
+    next: Option<Proxy<Baz>>,
+}
+
+#[persian_rug(handles = Recycling)]
+struct RecyclingRug(#[table] Baz);
+
+#[test]
+fn test_sandbox_recycling() {
+    let mut r = RecyclingRug(Default::default());
+    let ps = (0..3)
+        .map(|a| r.add(Baz { a, next: None }))
+        .collect::<Vec<_>>();
+    r.delete(&ps[0]);
+
+    let mut s = r.sandbox();
+    let a = s.add(Baz { a: 10, next: None });
+    let b = s.add(Baz {
+        a: 11,
+        next: Some(a),
+    });
+    assert_eq!((a.handle(), a.generation()), (0, 1));
+    assert_eq!(b.handle(), 3);
+    // Objects in the context above the first added handle are still
+    // read from the context.
+    assert_eq!(s.get(&ps[2]).a, 2);
+    s.get_mut(&ps[2]).next = Some(b);
+    assert_eq!(s.get(&a).a, 10);
+    s.commit();
+
+    assert!(r.try_get(&ps[0]).is_err());
+    assert_eq!(r.get(&a).a, 10);
+    assert_eq!(r.get(&r.get(&ps[2]).next.unwrap()).next, Some(a));
+
+    let mut s = r.sandbox();
+    let c = s.add(Baz { a: 12, next: None });
+    s.discard();
+    // The handle of a discarded object is reused.
+    assert_eq!(r.add(Baz { a: 13, next: None }).handle(), c.handle());
+}
@@ -0,0 +1,149 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::any::type_name;
+
+use persian_rug::schema::{FieldSchema, TypeSchema};
+use persian_rug::{contextual, persian_rug, Context, Contextual, Edge, Proxy};
+
+#[contextual(C)]
+struct Person<C: Context> {
+    name: String,
+    manager: Option<Proxy<Person<C>>>,
+    #[cfg(any())]
+    hidden: u32,
+    _marker: core::marker::PhantomData<C>,
+}
+
+#[contextual(Rug)]
+enum Event {
+    Hired(Proxy<Person<Rug>>),
+    Moved {
+        who: Proxy<Person<Rug>>,
+        team: Proxy<Team>,
+    },
+    Closed,
+}
+
+#[contextual(Rug)]
+struct Team(String, Vec<Proxy<Person<Rug>>>);
+
+#[persian_rug]
+struct Rug {
+    #[table]
+    people: Person<Rug>,
+    #[table]
+    events: Event,
+    #[table]
+    links: Edge<Person<Rug>, Person<Rug>, u32>,
+    #[cfg(any())]
+    #[table]
+    teams: Team,
+}
+
+#[test]
+fn test_struct_schema() {
+    let schema = Person::<Rug>::schema();
+    assert_eq!(schema.name, "Person");
+    assert_eq!(schema.type_name, type_name::<Person<Rug>>());
+    assert!(schema.variants.is_empty());
+    assert_eq!(
+        schema.fields,
+        vec![
+            FieldSchema {
+                name: "name",
+                ty: "String",
+                proxies: vec![],
+            },
+            FieldSchema {
+                name: "manager",
+                ty: "Option<Proxy<Person<C>>>",
+                proxies: vec![type_name::<Person<Rug>>()],
+            },
+            FieldSchema {
+                name: "_marker",
+                ty: "core::marker::PhantomData<C>",
+                proxies: vec![],
+            },
+        ]
+    );
+
+    let schema = Team::schema();
+    assert_eq!(schema.fields[0].name, "0");
+    assert_eq!(schema.fields[1].name, "1");
+    assert_eq!(schema.fields[1].proxies, vec![type_name::<Person<Rug>>()]);
+}
+
+#[test]
+fn test_enum_schema() {
+    let schema = Event::schema();
+    assert!(schema.fields.is_empty());
+    assert_eq!(
+        schema
+            .variants
+            .iter()
+            .map(|variant| variant.name)
+            .collect::<Vec<_>>(),
+        vec!["Hired", "Moved", "Closed"]
+    );
+    assert_eq!(schema.variants[1].fields[1].name, "team");
+    assert_eq!(
+        schema
+            .all_fields()
+            .flat_map(|field| field.proxies.iter().copied())
+            .collect::<Vec<_>>(),
+        vec![
+            type_name::<Person<Rug>>(),
+            type_name::<Person<Rug>>(),
+            type_name::<Team>()
+        ]
+    );
+}
+
+#[test]
+fn test_context_schema() {
+    let schema = Rug::schema();
+    assert_eq!(schema.name, "Rug");
+    assert_eq!(schema.type_name, type_name::<Rug>());
+    assert_eq!(
+        schema
+            .tables
+            .iter()
+            .map(|table| (table.field, table.schema.name))
+            .collect::<Vec<_>>(),
+        vec![("people", "Person"), ("events", "Event"), ("links", "Edge")]
+    );
+
+    let edge = schema
+        .table(type_name::<Edge<Person<Rug>, Person<Rug>, u32>>())
+        .unwrap();
+    assert_eq!(edge.schema.fields.len(), 3);
+
+    let referrers = schema
+        .referrers_of(type_name::<Person<Rug>>())
+        .into_iter()
+        .map(|(table, field)| (table.schema.name, field.name))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        referrers,
+        vec![
+            ("Person", "manager"),
+            ("Event", "0"),
+            ("Event", "who"),
+            ("Edge", "from"),
+            ("Edge", "to")
+        ]
+    );
+
+    // The table of teams is configured out, but events still refer
+    // to them.
+    assert_eq!(schema.missing_tables(), vec![type_name::<Team>()]);
+}
+
+#[test]
+fn test_opaque_schema() {
+    let schema = TypeSchema::opaque::<Person<Rug>>();
+    assert_eq!(schema.name, "Person");
+    assert!(schema.fields.is_empty());
+    assert_ne!(schema, Person::<Rug>::schema());
+}
@@ -0,0 +1,94 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::search::Search;
+use persian_rug::{contextual, persian_rug, Context, Proxy};
+
+#[derive(Clone)]
+#[contextual(Rug)]
+struct Doc {
+    #[search]
+    title: String,
+    #[search]
+    body: String,
+    author: String,
+}
+
+#[contextual(Rug)]
+struct Tag(#[search] &'static str, usize);
+
+#[persian_rug]
+struct Rug {
+    #[table(search)]
+    docs: Doc,
+    #[table(search(arena))]
+    tags: Tag,
+}
+
+fn doc(title: &str, body: &str) -> Doc {
+    Doc {
+        title: title.to_string(),
+        body: body.to_string(),
+        author: "anonymous".to_string(),
+    }
+}
+
+#[test]
+fn test_search() {
+    let mut r = Rug {
+        docs: Default::default(),
+        tags: Default::default(),
+    };
+    let a = r.add(doc("Rust Ownership", "Every value has an owner."));
+    let b = r.add(doc(
+        "Borrowing",
+        "References borrow a value, without taking ownership.",
+    ));
+    let c = r.add(doc("Lifetimes", "A lifetime names a region of code."));
+
+    assert_eq!(r.search::<Doc>("value"), vec![a, b]);
+    assert_eq!(r.search::<Doc>("OWNERSHIP"), vec![a, b]);
+    assert_eq!(r.search::<Doc>("ownership value borrow"), vec![b]);
+    assert_eq!(r.search::<Doc>("lifetimes"), vec![c]);
+    // Only marked fields are indexed, and only whole words match.
+    assert_eq!(r.search::<Doc>("anonymous"), Vec::<Proxy<Doc>>::new());
+    assert_eq!(r.search::<Doc>("own"), Vec::<Proxy<Doc>>::new());
+    assert_eq!(r.search::<Doc>(""), Vec::<Proxy<Doc>>::new());
+    assert_eq!(r.search::<Doc>("value?!"), vec![a, b]);
+
+    r.get_mut(&c).body = "A lifetime is a value's region.".to_string();
+    assert_eq!(r.search::<Doc>("value"), vec![a, b, c]);
+    assert_eq!(r.search::<Doc>("code"), Vec::<Proxy<Doc>>::new());
+
+    for d in r.get_iter_mut::<Doc>() {
+        d.title = d.title.to_uppercase() + " (draft)";
+    }
+    assert_eq!(r.search::<Doc>("draft"), vec![a, b, c]);
+    assert_eq!(r.docs.search("draft rust"), vec![a]);
+
+    assert!(r.docs.mark_deleted(&b));
+    assert_eq!(r.search::<Doc>("draft"), vec![a, c]);
+    assert_eq!(r.docs.search("borrowing"), Vec::<Proxy<Doc>>::new());
+    assert!(r.docs.undelete(&b));
+    assert_eq!(r.search::<Doc>("borrowing"), vec![b]);
+
+    let t1 = r.add(Tag("Systems programming", 1));
+    let t2 = r.add(Tag("Programming languages", 2));
+    assert_eq!(r.search::<Tag>("programming"), vec![t1, t2]);
+    assert_eq!(r.search::<Tag>("languages"), vec![t2]);
+}
+
+#[test]
+fn test_search_clone() {
+    let mut r = Rug {
+        docs: Default::default(),
+        tags: Default::default(),
+    };
+    let a = r.add(doc("First", "one"));
+    assert_eq!(r.search::<Doc>("one"), vec![a]);
+
+    let mut docs = r.docs.clone();
+    let b = docs.push(doc("Second", "one two"));
+    assert_eq!(docs.search("one"), vec![a, b]);
+    assert_eq!(r.search::<Doc>("one"), vec![a]);
+}
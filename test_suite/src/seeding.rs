@@ -0,0 +1,116 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, seed, seed_with_rng, Context, Mutator, Proxy, SeedRng};
+
+#[contextual(Rug)]
+struct Node {
+    ix: usize,
+    parent: Option<Proxy<Node>>,
+}
+
+#[contextual(Rug)]
+struct Label {
+    node: Proxy<Node>,
+    text: String,
+}
+
+#[persian_rug]
+struct Rug(#[table] Node, #[table] Label);
+
+fn make_tree(seed_value: u64) -> Rug {
+    let mut r = Rug(Default::default(), Default::default());
+    let mut rng = SeedRng::new(seed_value);
+
+    r.add(Node {
+        ix: 0,
+        parent: None,
+    });
+    let nodes = seed_with_rng(&mut r, 50, &mut rng, |ix, m, rng| {
+        let earlier = m.get_proxy_iter::<Node>().copied().collect::<Vec<_>>();
+        Node {
+            ix: ix + 1,
+            parent: Some(*rng.choose(&earlier).unwrap()),
+        }
+    });
+
+    seed_with_rng(&mut r, 20, &mut rng, |ix, _, rng| Label {
+        node: *rng.choose(&nodes).unwrap(),
+        text: format!("label{}", ix),
+    });
+
+    r
+}
+
+fn parents(r: &Rug) -> Vec<Option<usize>> {
+    r.get_iter::<Node>()
+        .map(|n| n.parent.map(|p| r.get(&p).ix))
+        .collect()
+}
+
+#[test]
+fn test_seed() {
+    let mut r = Rug(Default::default(), Default::default());
+
+    let nodes = seed(&mut r, 5, |ix, m| Node {
+        ix,
+        parent: m.get_proxy_iter::<Node>().next().copied(),
+    });
+    assert_eq!(nodes.len(), 5);
+    assert_eq!(
+        nodes.iter().map(|n| r.get(n).ix).collect::<Vec<_>>(),
+        vec![0, 1, 2, 3, 4]
+    );
+    assert!(r.get(&nodes[0]).parent.is_none());
+    assert!(nodes[1..].iter().all(|n| r.get(n).parent == Some(nodes[0])));
+
+    // The closure can add other objects for the new one to refer to.
+    let labels = seed(&mut r, 3, |ix, m| Label {
+        node: m.add(Node { ix, parent: None }),
+        text: ix.to_string(),
+    });
+    assert_eq!(r.get_iter::<Node>().count(), 8);
+    assert_eq!(r.get(&r.get(&labels[2]).node).ix, 2);
+
+    assert!(seed(&mut r, 0, |ix, _| Node { ix, parent: None }).is_empty());
+}
+
+#[test]
+fn test_deterministic() {
+    assert_eq!(parents(&make_tree(7)), parents(&make_tree(7)));
+    assert_ne!(parents(&make_tree(7)), parents(&make_tree(8)));
+
+    // Parents always come before their children.
+    let r = make_tree(7);
+    for n in r.get_iter::<Node>() {
+        if let Some(p) = n.parent {
+            assert!(r.get(&p).ix < n.ix);
+        }
+    }
+}
+
+#[test]
+fn test_rng() {
+    let mut a = SeedRng::new(1);
+    let mut b = a.clone();
+    let xs = (0..100).map(|_| a.next_u64()).collect::<Vec<_>>();
+    assert_eq!(xs, (0..100).map(|_| b.next_u64()).collect::<Vec<_>>());
+
+    // Known output of SplitMix64 for seed 0.
+    let mut r = SeedRng::new(0);
+    assert_eq!(r.next_u64(), 0xe220a8397b1dcdaf);
+
+    let mut counts = [0usize; 4];
+    for _ in 0..4000 {
+        counts[r.below(4)] += 1;
+    }
+    assert!(counts.iter().all(|c| (800..1200).contains(c)));
+
+    let hits = (0..1000).filter(|_| r.chance(0.25)).count();
+    assert!((150..350).contains(&hits));
+    assert!(!(0..100).any(|_| r.chance(0.0)));
+    assert!((0..100).all(|_| r.chance(1.0)));
+
+    assert_eq!(r.choose::<u8>(&[]), None);
+    assert_eq!(r.choose(&[3]), Some(&3));
+}
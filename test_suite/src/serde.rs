@@ -0,0 +1,93 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::serde::{Deserialize, Serialize};
+use persian_rug::serde_json::{self, json};
+use persian_rug::storage::ArenaStorage;
+use persian_rug::{contextual, persian_rug, Context, Proxy, Table};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "persian_rug::serde", bound = "")]
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+    next: Option<Proxy<Foo>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "persian_rug::serde", bound = "")]
+#[contextual(Rug)]
+struct Bar {
+    name: String,
+    foos: Vec<Proxy<Foo>>,
+}
+
+#[persian_rug(serde)]
+struct Rug {
+    #[table]
+    foos: Foo,
+    #[table(arena)]
+    bars: Bar,
+}
+
+#[test]
+fn test_table() {
+    let mut t = Table::<Foo, ArenaStorage<Foo>>::new();
+    let f1 = t.push(Foo { a: 1, next: None });
+    let f2 = t.push(Foo {
+        a: 2,
+        next: Some(f1),
+    });
+    t.delete(&f1);
+
+    let value = serde_json::to_value(&t).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "next": 2,
+            "entries": [[1, {"a": 2, "next": 0}]],
+        })
+    );
+
+    let mut u: Table<Foo> = serde_json::from_value(value).unwrap();
+    assert_eq!(u.get(&f2), t.get(&f2));
+    assert_eq!(u.push(Foo { a: 3, next: None }).handle(), 2);
+}
+
+#[test]
+fn test_invalid_table() {
+    // A handle beyond the next index.
+    let value = json!({"next": 1, "entries": [[1, {"a": 1, "next": null}]]});
+    assert!(serde_json::from_value::<Table<Foo>>(value).is_err());
+
+    // Repeated handles.
+    let value = json!({
+        "next": 2,
+        "entries": [[0, {"a": 1, "next": null}], [0, {"a": 1, "next": null}]],
+    });
+    assert!(serde_json::from_value::<Table<Foo>>(value).is_err());
+}
+
+#[test]
+fn test_context() {
+    let mut r = Rug {
+        foos: Default::default(),
+        bars: Default::default(),
+    };
+    let f1 = r.add(Foo { a: 1, next: None });
+    let f2 = r.add(Foo {
+        a: 2,
+        next: Some(f1),
+    });
+    let b = r.add(Bar {
+        name: "bar".to_string(),
+        foos: vec![f2, f1],
+    });
+
+    let text = serde_json::to_string(&r).unwrap();
+    let s: Rug = serde_json::from_str(&text).unwrap();
+    assert_eq!(s.get(&f1), r.get(&f1));
+    assert_eq!(s.get(&f2), r.get(&f2));
+    assert_eq!(s.get(&b), r.get(&b));
+    assert_eq!(serde_json::to_string(&s).unwrap(), text);
+}
@@ -0,0 +1,144 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::serde::{Deserialize, Serialize};
+use persian_rug::serde_diff::{self, Apply, Diff, SerdeDiff};
+use persian_rug::serde_json;
+use persian_rug::{contextual, persian_rug, Context, Proxy, Table};
+
+#[derive(Clone, Debug, PartialEq, SerdeDiff, Serialize, Deserialize)]
+#[serde(crate = "persian_rug::serde")]
+#[contextual(Rug)]
+struct Person {
+    name: String,
+    age: u32,
+    friends: Vec<Proxy<Person>>,
+}
+
+#[derive(Clone, Debug, PartialEq, SerdeDiff, Serialize, Deserialize)]
+#[serde(crate = "persian_rug::serde")]
+#[contextual(Rug)]
+struct Club {
+    title: String,
+    members: Vec<Proxy<Person>>,
+}
+
+#[derive(Clone)]
+#[persian_rug(serde_diff)]
+struct Rug {
+    #[table]
+    people: Person,
+    #[table(arena)]
+    clubs: Club,
+}
+
+#[derive(Clone, Debug, PartialEq, SerdeDiff, Serialize, Deserialize)]
+#[serde(crate = "persian_rug::serde")]
+#[contextual(Pair)]
+struct Counter(u32);
+
+#[derive(Clone)]
+#[persian_rug(serde_diff)]
+struct Pair(#[table] Counter);
+
+fn diff<T: SerdeDiff>(old: &T, new: &T) -> String {
+    serde_json::to_string(&Diff::serializable(old, new)).unwrap()
+}
+
+fn apply<T: SerdeDiff>(target: &mut T, diff: &str) {
+    let mut de = serde_json::Deserializer::from_str(diff);
+    Apply::apply(&mut de, target).unwrap();
+}
+
+fn person(name: &str, age: u32) -> Person {
+    Person {
+        name: name.to_string(),
+        age,
+        friends: Vec::new(),
+    }
+}
+
+#[test]
+fn test_context_diff() {
+    let mut r = Rug {
+        people: Default::default(),
+        clubs: Default::default(),
+    };
+    let alice = r.add(person("Alice", 30));
+    let bob = r.add(person("Bob", 40));
+    let chess = r.add(Club {
+        title: "Chess".to_string(),
+        members: vec![alice],
+    });
+    let old = r.clone();
+
+    assert!(!Diff::serializable(&old, &r).has_changes());
+
+    r.get_mut(&bob).age += 1;
+    r.get_mut(&chess).members.push(bob);
+    let carol = r.add(Person {
+        name: "Carol".to_string(),
+        age: 25,
+        friends: vec![alice],
+    });
+
+    let patch = diff(&old, &r);
+    // Unchanged fields and objects are not sent.
+    assert!(!patch.contains("Alice"));
+    assert!(!patch.contains("Bob"));
+    assert!(!patch.contains("Chess"));
+    assert!(patch.contains("Carol"));
+
+    let mut replica = old.clone();
+    apply(&mut replica, &patch);
+    for p in [alice, bob, carol] {
+        assert_eq!(replica.get(&p), r.get(&p));
+    }
+    assert_eq!(replica.get(&chess), r.get(&chess));
+    assert_eq!(replica.get(&bob).age, 41);
+
+    // New objects keep their proxies, so later additions agree.
+    let dave = r.add(person("Dave", 50));
+    assert_eq!(replica.add(person("Dave", 50)), dave);
+}
+
+#[test]
+fn test_tuple_context_diff() {
+    let mut p = Pair(Default::default());
+    let a = p.add(Counter(1));
+    let old = p.clone();
+    p.get_mut(&a).0 = 2;
+    let b = p.add(Counter(3));
+
+    let mut replica = old.clone();
+    apply(&mut replica, &diff(&old, &p));
+    assert_eq!(replica.get(&a), &Counter(2));
+    assert_eq!(replica.get(&b), &Counter(3));
+}
+
+#[test]
+fn test_table_diff() {
+    let mut old = Table::<Person>::new();
+    let alice = old.push(person("Alice", 30));
+    let mut new = old.clone();
+    new.get_mut(&alice).unwrap().friends.push(alice);
+
+    let mut replica = old.clone();
+    apply(&mut replica, &diff(&old, &new));
+    assert_eq!(replica.get(&alice).unwrap().friends, vec![alice]);
+
+    // Removals cannot be described.
+    assert!(serde_json::to_string(&Diff::serializable(&new, &Table::<Person>::new())).is_err());
+}
+
+#[test]
+fn test_missing_object() {
+    let mut old = Table::<Person>::new();
+    let alice = old.push(person("Alice", 30));
+    let mut new = old.clone();
+    new.get_mut(&alice).unwrap().age = 31;
+
+    let patch = diff(&old, &new);
+    let mut de = serde_json::Deserializer::from_str(&patch);
+    assert!(Apply::apply(&mut de, &mut Table::<Person>::new()).is_err());
+}
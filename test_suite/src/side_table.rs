@@ -0,0 +1,95 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, Context, Proxy, SideTable};
+
+#[contextual(Bar)]
+struct Foo {
+    ix: u64,
+}
+
+#[persian_rug]
+struct Bar(#[table] Foo);
+
+#[test]
+fn test_basic() {
+    let mut bar = Bar(Default::default());
+
+    let f = (0..16).map(|ix| bar.add(Foo { ix })).collect::<Vec<_>>();
+
+    let mut st = SideTable::new();
+    assert!(st.is_empty());
+
+    for item in f.iter().step_by(2) {
+        assert_eq!(st.insert(*item, bar.get(item).ix * 10), None);
+    }
+    assert_eq!(st.len(), 8);
+
+    for (j, item) in f.iter().enumerate() {
+        assert_eq!(st.contains(item), j % 2 == 0);
+        if j % 2 == 0 {
+            assert_eq!(st.get(item), Some(&(j as u64 * 10)));
+        } else {
+            assert_eq!(st.get(item), None);
+        }
+    }
+
+    assert_eq!(st.insert(f[0], 1), Some(0));
+    assert_eq!(st.get(&f[0]), Some(&1));
+    assert_eq!(st.len(), 8);
+
+    *st.get_mut(&f[2]).unwrap() += 1;
+    assert_eq!(st.get(&f[2]), Some(&21));
+    assert_eq!(st.get_mut(&f[3]), None);
+
+    *st.get_or_insert_with(f[3], || 5) += 1;
+    assert_eq!(st.get(&f[3]), Some(&6));
+    *st.get_or_insert_with(f[3], || 5) += 1;
+    assert_eq!(st.get(&f[3]), Some(&7));
+
+    assert_eq!(st.remove(&f[3]), Some(7));
+    assert_eq!(st.remove(&f[3]), None);
+    assert!(!st.contains(&f[3]));
+    assert_eq!(st.len(), 8);
+
+    st.clear();
+    assert!(st.is_empty());
+    for item in f.iter() {
+        assert!(!st.contains(item));
+    }
+}
+
+#[test]
+fn test_iterator() {
+    let mut bar = Bar(Default::default());
+
+    let f = (0..16).map(|ix| bar.add(Foo { ix })).collect::<Vec<_>>();
+
+    let mut st = SideTable::new();
+    for item in f.iter().rev().step_by(3) {
+        st.insert(*item, bar.get(item).ix);
+    }
+
+    let entries = st
+        .iter()
+        .map(|(p, v)| (p, *v))
+        .collect::<Vec<(Proxy<Foo>, u64)>>();
+    assert_eq!(
+        entries,
+        vec![
+            (f[0], 0),
+            (f[3], 3),
+            (f[6], 6),
+            (f[9], 9),
+            (f[12], 12),
+            (f[15], 15)
+        ]
+    );
+
+    for (p, v) in st.iter_mut() {
+        *v += bar.get(&p).ix;
+    }
+    for (p, v) in st.iter() {
+        assert_eq!(*v, bar.get(&p).ix * 2);
+    }
+}
@@ -0,0 +1,60 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, Context, Proxy};
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Bar {
+    foo: Proxy<Foo>,
+}
+
+#[derive(Clone)]
+#[persian_rug]
+struct Rug(#[table] Foo, #[table(arena)] Bar);
+
+#[test]
+fn test_restore() {
+    let mut r = Rug(Default::default(), Default::default());
+    let kept = r.add(Foo { a: 1 });
+    let deleted = r.add(Foo { a: 2 });
+    let bar = r.add(Bar { foo: kept });
+    let s = r.snapshot();
+
+    r.get_mut(&kept).a = 10;
+    r.delete(&deleted);
+    let added = r.add(Foo { a: 3 });
+    r.get_mut(&bar).foo = added;
+    assert_eq!(s.context().get(&kept).a, 1);
+    assert_eq!(s.context().get_iter::<Foo>().count(), 2);
+
+    r.restore(s);
+    assert_eq!(r.get(&kept).a, 1);
+    assert_eq!(r.get(&deleted).a, 2);
+    assert!(r.try_get(&added).is_err());
+    assert_eq!(r.get(&bar).foo, kept);
+}
+
+#[test]
+fn test_restore_twice() {
+    let mut r = Rug(Default::default(), Default::default());
+    let foo = r.add(Foo { a: 1 });
+    let s = r.snapshot();
+
+    r.get_mut(&foo).a = 2;
+    r.restore(s.clone());
+    assert_eq!(r.get(&foo).a, 1);
+
+    r.get_mut(&foo).a = 3;
+    let later = r.snapshot();
+    r.restore(s);
+    assert_eq!(r.get(&foo).a, 1);
+    r.restore(later);
+    assert_eq!(r.get(&foo).a, 3);
+}
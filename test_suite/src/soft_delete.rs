@@ -0,0 +1,104 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, Context, Proxy, Table};
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Task {
+    title: &'static str,
+    done: bool,
+}
+
+#[derive(Clone)]
+#[persian_rug]
+struct Rug {
+    #[table]
+    tasks: Task,
+}
+
+fn task(title: &'static str) -> Task {
+    Task { title, done: false }
+}
+
+#[test]
+fn test_context_skips_deleted() {
+    let mut r = Rug {
+        tasks: Default::default(),
+    };
+    let write = r.add(task("write"));
+    let test = r.add(task("test"));
+    let ship = r.add(task("ship"));
+
+    assert!(r.tasks.mark_deleted(&test));
+    assert!(!r.tasks.mark_deleted(&test));
+    assert!(r.tasks.is_deleted(&test));
+
+    let titles = r.get_iter::<Task>().map(|t| t.title).collect::<Vec<_>>();
+    assert_eq!(titles, vec!["write", "ship"]);
+    assert_eq!(
+        r.get_proxy_iter::<Task>().copied().collect::<Vec<_>>(),
+        vec![write, ship]
+    );
+    for t in r.get_iter_mut::<Task>() {
+        t.done = true;
+    }
+    // Deleted objects can still be looked up, but were not changed.
+    assert!(!r.get(&test).done);
+    assert!(r.get(&ship).done);
+
+    assert_eq!(r.tasks.include_deleted().iter().count(), 3);
+    assert_eq!(
+        r.tasks
+            .include_deleted()
+            .iter_proxies()
+            .copied()
+            .collect::<Vec<_>>(),
+        vec![write, test, ship]
+    );
+    assert_eq!(r.tasks.deleted_proxies().collect::<Vec<_>>(), vec![test]);
+
+    let copy = r.clone();
+    assert!(copy.tasks.is_deleted(&test));
+
+    assert!(r.tasks.undelete(&test));
+    assert!(!r.tasks.undelete(&test));
+    assert_eq!(r.get_iter::<Task>().count(), 3);
+}
+
+#[test]
+fn test_swap_moves_mark() {
+    let mut table = Table::<Task>::new();
+    let a = table.push(task("a"));
+    let b = table.push(task("b"));
+    table.mark_deleted(&a);
+
+    assert!(table.swap(&a, &b));
+    assert!(!table.is_deleted(&a));
+    assert!(table.is_deleted(&b));
+    assert_eq!(table.get(&b).unwrap().title, "a");
+    assert_eq!(table.iter().map(|t| t.title).collect::<Vec<_>>(), vec!["b"]);
+}
+
+#[test]
+fn test_mark_missing() {
+    let mut table = Table::<Task>::new();
+    assert!(!table.mark_deleted(&Proxy::from_handle(0)));
+    assert_eq!(table.deleted_proxies().count(), 0);
+}
+
+#[test]
+fn test_par_chunks_skips_deleted() {
+    let mut table = Table::<u32>::new();
+    let proxies = (0..100).map(|v| table.push(v)).collect::<Vec<_>>();
+    for p in proxies.iter().step_by(2) {
+        table.mark_deleted(p);
+    }
+    table.par_chunks(8, |chunk| {
+        for (_, value) in chunk {
+            **value += 1000;
+        }
+    });
+    assert!(table.iter().all(|v| *v >= 1000));
+    assert_eq!(table.get(&proxies[0]), Some(&0));
+}
@@ -0,0 +1,101 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, Accessor, Context, Mutator, Proxy, StaticRug};
+
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[contextual(Rug)]
+struct Bar {
+    foo: Proxy<Foo>,
+}
+
+#[persian_rug]
+struct Rug(#[table] Foo, #[table] Bar);
+
+fn new_rug() -> Rug {
+    Rug(Default::default(), Default::default())
+}
+
+fn total<A: Accessor<Context = Rug>>(access: A) -> i32 {
+    access.get_iter::<Foo>().map(|foo| foo.a).sum()
+}
+
+fn add_bar<M: Mutator<Context = Rug>>(mut mutator: M, a: i32) -> Proxy<Bar> {
+    let foo = mutator.add(Foo { a });
+    mutator.add(Bar { foo })
+}
+
+#[test]
+fn test_operations() {
+    static RUG: StaticRug<Rug> = StaticRug::new();
+
+    assert!(!RUG.is_set());
+    assert!(RUG.set(new_rug()).is_ok());
+    assert!(RUG.is_set());
+    assert!(RUG.set(new_rug()).is_err());
+
+    let f = RUG.add(Foo { a: 1 });
+    RUG.update(&f, |foo| foo.a += 1);
+    assert_eq!(RUG.get_with(&f, |foo| foo.a), 2);
+    let b = RUG.with_mut(|rug| rug.add(Bar { foo: f }));
+    assert_eq!(RUG.with(|rug| rug.get(&rug.get(&b).foo).a), 2);
+}
+
+#[test]
+fn test_guards() {
+    static RUG: StaticRug<Rug> = StaticRug::new();
+    RUG.set(new_rug()).ok();
+
+    let b = add_bar(RUG.write(), 3);
+    add_bar(RUG.write(), 4);
+    assert_eq!(total(&*RUG.read()), 7);
+    let guard = RUG.read();
+    let access = &*guard;
+    assert_eq!(access.get(&access.get(&b).foo).a, 3);
+}
+
+#[test]
+fn test_threads() {
+    static RUG: StaticRug<Rug> = StaticRug::new();
+    RUG.set(new_rug()).ok();
+
+    let threads = (0..4)
+        .map(|i| {
+            std::thread::spawn(move || {
+                for _ in 0..100 {
+                    RUG.add(Foo { a: i });
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(RUG.with(|rug| rug.get_iter::<Foo>().count()), 400);
+    assert_eq!(total(&*RUG.read()), 600);
+}
+
+#[test]
+fn test_poison() {
+    static RUG: StaticRug<Rug> = StaticRug::new();
+    RUG.set(new_rug()).ok();
+
+    let f = RUG.add(Foo { a: 1 });
+    std::thread::spawn(move || {
+        RUG.update(&f, |_| panic!("failed while writing"));
+    })
+    .join()
+    .unwrap_err();
+    assert_eq!(RUG.get_with(&f, |foo| foo.a), 1);
+}
+
+#[test]
+#[should_panic(expected = "before its context was set")]
+fn test_unset() {
+    static RUG: StaticRug<Rug> = StaticRug::new();
+    RUG.add(Foo { a: 1 });
+}
@@ -0,0 +1,424 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::alloc::Layout;
+use std::cell::Cell;
+use std::ptr::NonNull;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use persian_rug::storage::{
+    Allocator, ArenaStorage, Global, PersistentStorage, Storage, ARENA_BLOCK_BYTES,
+};
+use persian_rug::{contextual, persian_rug, Context, Proxy, Table};
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Foo {
+    ix: u64,
+    name: String,
+}
+
+#[contextual(Rug)]
+struct Bar {
+    foo: Proxy<Foo>,
+}
+
+#[persian_rug]
+struct Rug {
+    #[table(arena)]
+    foos: Foo,
+    #[table]
+    bars: Bar,
+}
+
+fn foo(ix: u64) -> Foo {
+    Foo {
+        ix,
+        name: format!("foo{}", ix),
+    }
+}
+
+#[test]
+fn test_arena_table() {
+    let mut t = Table::<Foo, ArenaStorage<Foo>>::new();
+
+    let n = 4 * ARENA_BLOCK_BYTES as u64 / std::mem::size_of::<Foo>() as u64;
+    let f = (0..n).map(|ix| t.push(foo(ix))).collect::<Vec<_>>();
+
+    for (ix, p) in f.iter().enumerate() {
+        assert_eq!(t.get(p), Some(&foo(ix as u64)));
+    }
+
+    for p in f.iter() {
+        t.get_mut(p).unwrap().ix *= 2;
+    }
+    for (ix, item) in t.iter().enumerate() {
+        assert_eq!(item.ix, ix as u64 * 2);
+    }
+
+    for item in t.iter_mut() {
+        item.ix += 1;
+    }
+    for (ix, p) in f.iter().enumerate() {
+        assert_eq!(t.get(p).map(|f| f.ix), Some(ix as u64 * 2 + 1));
+    }
+
+    let proxies = t.iter_proxies().copied().collect::<Vec<_>>();
+    assert_eq!(proxies, f);
+}
+
+#[test]
+fn test_arena_locality() {
+    let mut t = Table::<Foo, ArenaStorage<Foo>>::new();
+
+    let f = (0..16).map(|ix| t.push(foo(ix))).collect::<Vec<_>>();
+
+    let stride = std::mem::size_of::<(Proxy<Foo>, Foo)>();
+    for pair in f.windows(2) {
+        let a = t.get(&pair[0]).unwrap() as *const Foo as usize;
+        let b = t.get(&pair[1]).unwrap() as *const Foo as usize;
+        assert_eq!(b - a, stride);
+    }
+}
+
+#[test]
+fn test_arena_storage() {
+    let mut s = ArenaStorage::<Foo>::default();
+    let mut t = Table::<Foo>::new();
+
+    let p1 = t.push(foo(1));
+    let p2 = t.push(foo(2));
+
+    assert!(s.is_empty());
+    assert_eq!(s.insert(p1, foo(1)), None);
+    assert_eq!(s.insert(p2, foo(2)), None);
+    assert_eq!(s.len(), 2);
+
+    assert_eq!(s.insert(p1, foo(3)), Some(foo(1)));
+    assert_eq!(s.len(), 2);
+    assert_eq!(s.get(0), Some(&foo(3)));
+    assert_eq!(s.get(1), Some(&foo(2)));
+    assert_eq!(s.get(2), None);
+
+    let entries = s.entries().map(|(p, v)| (*p, v.ix)).collect::<Vec<_>>();
+    assert_eq!(entries, vec![(p1, 3), (p2, 2)]);
+}
+
+#[test]
+fn test_arena_clone_debug() {
+    let mut t = Table::<Foo, ArenaStorage<Foo>>::new();
+    let mut u = Table::<Foo>::new();
+    for ix in 0..3 {
+        t.push(foo(ix));
+        u.push(foo(ix));
+    }
+
+    let c = t.clone();
+    assert_eq!(c.iter().collect::<Vec<_>>(), t.iter().collect::<Vec<_>>());
+    assert_eq!(format!("{:?}", t), format!("{:?}", u));
+}
+
+#[test]
+fn test_arena_context() {
+    let mut r = Rug {
+        foos: Default::default(),
+        bars: Default::default(),
+    };
+
+    let f1 = r.add(foo(1));
+    let f2 = r.add(foo(2));
+    let b1 = r.add(Bar { foo: f2 });
+
+    assert_eq!(r.get(&r.get(&b1).foo), &foo(2));
+    r.get_mut(&f1).ix = 5;
+    assert_eq!(
+        r.get_iter::<Foo>().map(|f| f.ix).collect::<Vec<_>>(),
+        vec![5, 2]
+    );
+    assert_eq!(
+        r.get_proxy_iter::<Foo>().copied().collect::<Vec<_>>(),
+        vec![f1, f2]
+    );
+}
+
+#[derive(Default)]
+struct Tracker {
+    live_blocks: AtomicUsize,
+    total_blocks: AtomicUsize,
+}
+
+unsafe impl Allocator for Tracker {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        self.live_blocks.fetch_add(1, Ordering::SeqCst);
+        self.total_blocks.fetch_add(1, Ordering::SeqCst);
+        Global.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.live_blocks.fetch_sub(1, Ordering::SeqCst);
+        Global.deallocate(ptr, layout)
+    }
+}
+
+#[test]
+fn test_arena_allocator() {
+    let tracker = Arc::new(Tracker::default());
+    let dropped = Rc::new(Cell::new(0));
+
+    struct Counted(Rc<Cell<usize>>);
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    let mut t = Table::with_storage(ArenaStorage::new_in(tracker.clone()));
+    assert_eq!(tracker.total_blocks.load(Ordering::SeqCst), 0);
+
+    let n = 2 * ARENA_BLOCK_BYTES / std::mem::size_of::<(Proxy<Counted>, Counted)>() + 1;
+    let ps = (0..n)
+        .map(|_| t.push(Counted(dropped.clone())))
+        .collect::<Vec<_>>();
+    assert_eq!(tracker.live_blocks.load(Ordering::SeqCst), 3);
+    assert!(ps.iter().all(|p| t.get(p).is_some()));
+    assert_eq!(dropped.get(), 0);
+
+    drop(t);
+    assert_eq!(dropped.get(), n);
+    assert_eq!(tracker.live_blocks.load(Ordering::SeqCst), 0);
+    assert_eq!(tracker.total_blocks.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn test_arena_into_entries() {
+    let tracker = Arc::new(Tracker::default());
+    let dropped = Rc::new(Cell::new(0));
+
+    #[derive(Debug)]
+    struct Counted(usize, Rc<Cell<usize>>);
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.1.set(self.1.get() + 1);
+        }
+    }
+
+    let mut t = Table::with_storage(ArenaStorage::new_in(tracker.clone()));
+    let n = ARENA_BLOCK_BYTES / std::mem::size_of::<(Proxy<Counted>, Counted)>() + 1;
+    let ps = (0..n)
+        .map(|ix| t.push(Counted(ix, dropped.clone())))
+        .collect::<Vec<_>>();
+    assert!(t.swap(&ps[0], &ps[n - 1]));
+
+    let entries = t.into_entries();
+    assert_eq!(dropped.get(), 0);
+    assert_eq!(tracker.live_blocks.load(Ordering::SeqCst), 0);
+    assert_eq!(entries.len(), n);
+    assert_eq!(entries[0].0, ps[0]);
+    assert_eq!(entries[0].1 .0, n - 1);
+    assert_eq!(entries[n - 1].1 .0, 0);
+    assert!(entries[1..n - 1]
+        .iter()
+        .enumerate()
+        .all(|(ix, (p, c))| *p == ps[ix + 1] && c.0 == ix + 1));
+
+    drop(entries);
+    assert_eq!(dropped.get(), n);
+}
+
+#[test]
+fn test_persistent_table() {
+    let mut t = Table::<Foo, PersistentStorage<Foo>>::new();
+    let f = (0..5).map(|ix| t.push(foo(ix))).collect::<Vec<_>>();
+    for (ix, p) in f.iter().enumerate() {
+        assert_eq!(t.get(p), Some(&foo(ix as u64)));
+    }
+
+    for item in t.iter_mut() {
+        item.ix *= 2;
+    }
+    assert_eq!(
+        t.iter().map(|f| f.ix).collect::<Vec<_>>(),
+        vec![0, 2, 4, 6, 8]
+    );
+
+    // Removal moves the last object into the gap.
+    assert!(t.delete(&f[1]));
+    assert_eq!(t.get(&f[1]), None);
+    assert_eq!(
+        t.iter_proxies().copied().collect::<Vec<_>>(),
+        vec![f[0], f[4], f[2], f[3]]
+    );
+
+    assert!(t.swap(&f[0], &f[3]));
+    assert_eq!(t.get(&f[0]).map(|f| f.ix), Some(6));
+    assert_eq!(t.get(&f[3]).map(|f| f.ix), Some(0));
+    assert_eq!(
+        t.iter_proxies().copied().collect::<Vec<_>>(),
+        vec![f[0], f[4], f[2], f[3]]
+    );
+
+    let entries = t.into_entries();
+    assert_eq!(
+        entries.iter().map(|(p, f)| (*p, f.ix)).collect::<Vec<_>>(),
+        vec![(f[0], 6), (f[4], 8), (f[2], 4), (f[3], 0)]
+    );
+}
+
+#[test]
+fn test_persistent_clone_shares() {
+    let mut t = Table::<Foo, PersistentStorage<Foo>>::new();
+    let f = (0..100).map(|ix| t.push(foo(ix))).collect::<Vec<_>>();
+
+    let mut c = t.clone();
+    assert!(std::ptr::eq(t.get(&f[10]).unwrap(), c.get(&f[10]).unwrap()));
+
+    c.get_mut(&f[10]).unwrap().ix = 1000;
+    c.delete(&f[20]);
+    assert_eq!(t.get(&f[10]), Some(&foo(10)));
+    assert_eq!(t.get(&f[20]), Some(&foo(20)));
+    assert_eq!(c.get(&f[10]).map(|f| f.ix), Some(1000));
+    assert_eq!(c.get(&f[20]), None);
+    assert_eq!(t.len(), 100);
+    assert_eq!(c.len(), 99);
+}
+
+#[test]
+fn test_table_entries() {
+    let (mut t, ps) = Table::<Foo>::from_vec((0..4).map(foo).collect());
+    assert_eq!(
+        ps.iter().map(|p| p.handle()).collect::<Vec<_>>(),
+        vec![0, 1, 2, 3]
+    );
+    assert_eq!(t.get(&ps[2]), Some(&foo(2)));
+    assert_eq!(t.push(foo(4)).handle(), 4);
+
+    assert!(t.mark_deleted(&ps[1]));
+    let entries = t.into_entries();
+    assert_eq!(
+        entries
+            .iter()
+            .map(|(p, f)| (p.handle(), f.ix))
+            .collect::<Vec<_>>(),
+        vec![(0, 0), (2, 2), (3, 3), (4, 4)]
+    );
+
+    let mut rebuilt = Table::<Foo>::new();
+    for (p, f) in entries {
+        rebuilt.insert_with_handle(p.handle(), f).unwrap();
+    }
+    assert_eq!(rebuilt.get(&ps[3]), Some(&foo(3)));
+    assert_eq!(rebuilt.get(&ps[1]), None);
+
+    let (empty, ps) = Table::<Foo>::from_vec(Vec::new());
+    assert!(empty.is_empty());
+    assert!(ps.is_empty());
+}
+
+#[derive(Clone, Default)]
+struct Pool(Arc<Tracker>);
+
+unsafe impl Allocator for Pool {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        self.0.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.0.deallocate(ptr, layout)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[contextual(PooledRug)]
+struct Baz {
+    ix: u64,
+}
+
+#[persian_rug]
+struct PooledRug {
+    #[table(arena(Pool))]
+    bazs: Baz,
+}
+
+#[test]
+fn test_arena_allocator_context() {
+    let mut r: PooledRug = PooledRug {
+        bazs: Default::default(),
+    };
+    let b = r.add(Baz { ix: 1 });
+    assert_eq!(r.get(&b), &Baz { ix: 1 });
+    assert_eq!(r.bazs.iter().count(), 1);
+}
+
+#[test]
+fn test_par_chunks() {
+    let mut r = Rug {
+        foos: Default::default(),
+        bars: Default::default(),
+    };
+    let proxies = (0..1000).map(|ix| r.add(foo(ix))).collect::<Vec<_>>();
+
+    let chunks = AtomicUsize::new(0);
+    r.foos.par_chunks(30, |chunk| {
+        assert!(chunk.len() <= 30);
+        chunks.fetch_add(1, Ordering::Relaxed);
+        for (p, foo) in chunk.iter_mut() {
+            assert_eq!(p.handle(), foo.ix);
+            foo.name = format!("done {}", foo.ix);
+        }
+    });
+    assert_eq!(chunks.into_inner(), 34);
+    for (ix, p) in proxies.iter().enumerate() {
+        assert_eq!(r.get(p).name, format!("done {}", ix));
+    }
+
+    let mut t = Table::<u64>::new();
+    t.par_chunks(5, |_| panic!("there are no chunks"));
+    t.push(1);
+    t.par_chunks(5, |chunk| *chunk[0].1 += 1);
+    assert_eq!(t.iter().copied().collect::<Vec<_>>(), vec![2]);
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(PersistentRug)]
+struct Qux {
+    ix: u64,
+    next: Option<Proxy<Qux>>,
+}
+
+#[derive(Clone)]
+#[persian_rug]
+struct PersistentRug(#[table(persistent)] Qux, #[table] Note);
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(PersistentRug)]
+struct Note {
+    name: String,
+}
+
+#[test]
+fn test_persistent_context() {
+    let mut r = PersistentRug(Default::default(), Default::default());
+    let a = r.add(Qux { ix: 1, next: None });
+    let b = r.add(Qux {
+        ix: 2,
+        next: Some(a),
+    });
+    r.add(Note {
+        name: "hello".to_string(),
+    });
+
+    let s = r.snapshot();
+    r.get_mut(&a).ix = 10;
+    r.delete(&b);
+    assert_eq!(s.context().get(&s.context().get(&b).next.unwrap()).ix, 1);
+
+    r.restore(s);
+    assert_eq!(r.get(&r.get(&b).next.unwrap()).ix, 1);
+    assert_eq!(
+        r.get_iter::<Qux>().map(|q| q.ix).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(r.get_iter::<Note>().count(), 1);
+}
@@ -0,0 +1,115 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::pin::pin;
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context as TaskContext, Poll, Waker};
+
+use persian_rug::futures_core::Stream;
+use persian_rug::stream::{RelockTableStream, TableStream, DEFAULT_BATCH_SIZE};
+use persian_rug::{contextual, persian_rug, Context, StaticRug};
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Foo {
+    a: usize,
+}
+
+#[persian_rug]
+struct Rug(#[table] Foo);
+
+fn new_rug(len: usize) -> Rug {
+    let mut r = Rug(Default::default());
+    for a in 0..len {
+        r.add(Foo { a });
+    }
+    r
+}
+
+/// Drain a stream, returning the values and the number of times it
+/// yielded, and calling `on_yield` each time it does.
+fn drain<S: Stream>(stream: S, mut on_yield: impl FnMut()) -> (Vec<S::Item>, usize) {
+    let mut stream = pin!(stream);
+    let mut cx = TaskContext::from_waker(Waker::noop());
+    let mut values = Vec::new();
+    let mut yields = 0;
+    loop {
+        match stream.as_mut().poll_next(&mut cx) {
+            Poll::Ready(Some(value)) => values.push(value),
+            Poll::Ready(None) => return (values, yields),
+            Poll::Pending => {
+                yields += 1;
+                on_yield();
+            }
+        }
+    }
+}
+
+fn values(len: usize) -> Vec<Foo> {
+    (0..len).map(|a| Foo { a }).collect()
+}
+
+#[test]
+fn test_table_stream() {
+    let r = new_rug(10);
+    let stream = TableStream::<_, Foo>::new(&r).batch_size(3);
+    assert_eq!(stream.size_hint(), (10, Some(10)));
+    assert_eq!(drain(stream, || {}), (values(10), 3));
+
+    let r = Arc::new(new_rug(DEFAULT_BATCH_SIZE * 2));
+    let stream = TableStream::<_, Foo>::new(r.clone());
+    assert_eq!(drain(stream, || {}), (values(DEFAULT_BATCH_SIZE * 2), 1));
+
+    let r = Mutex::new(new_rug(4));
+    let stream = TableStream::<_, Foo>::new(r.lock().unwrap()).batch_size(2);
+    let (found, yields) = drain(stream, || assert!(r.try_lock().is_err()));
+    assert_eq!((found, yields), (values(4), 1));
+    assert!(r.try_lock().is_ok());
+}
+
+#[test]
+fn test_relock_stream() {
+    let r = Arc::new(Mutex::new(new_rug(10)));
+    let stream = RelockTableStream::<_, Foo>::new(r.clone()).batch_size(4);
+    assert_eq!(stream.size_hint(), (0, None));
+    let mut added = false;
+    let (found, yields) = drain(stream, || {
+        // The lock is free between batches, and objects added after
+        // the stream started are not included.
+        let mut guard = r.try_lock().unwrap();
+        if !added {
+            guard.add(Foo { a: 100 });
+            added = true;
+        }
+    });
+    assert_eq!((found, yields), (values(10), 2));
+    assert_eq!(r.lock().unwrap().get_iter::<Foo>().count(), 11);
+
+    let r = RwLock::new(new_rug(3));
+    let stream = RelockTableStream::<_, Foo>::new(&r).batch_size(3);
+    assert_eq!(drain(stream, || {}), (values(3), 0));
+
+    let r = Mutex::new(new_rug(0));
+    let stream = RelockTableStream::<_, Foo>::new(&r);
+    assert_eq!(drain(stream, || {}), (Vec::new(), 0));
+}
+
+static RUG: StaticRug<Rug> = StaticRug::new();
+
+#[test]
+fn test_relock_static() {
+    assert!(RUG.set(new_rug(5)).is_ok());
+    let stream = RelockTableStream::<_, Foo>::new(&RUG).batch_size(2);
+    assert_eq!(drain(stream, || {}), (values(5), 2));
+}
+
+fn assert_send<S: Send>(_: &S) {}
+
+#[test]
+fn test_relock_send() {
+    let r = Arc::new(Mutex::new(new_rug(2)));
+    let stream = RelockTableStream::<_, Foo>::new(r.clone());
+    assert_send(&stream);
+    let handle = std::thread::spawn(move || drain(stream, || {}).0);
+    assert_eq!(handle.join().unwrap(), values(2));
+}
@@ -0,0 +1,115 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::search::Search;
+use persian_rug::storage::{ArenaStorage, ARENA_BLOCK_BYTES};
+use persian_rug::{contextual, persian_rug, Accessor, AnyProxy, Context, Links, Proxy, Table};
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Node {
+    #[search]
+    name: String,
+    #[link]
+    parent: Option<Proxy<Node>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Rug)]
+struct Label(String);
+
+impl Links for Label {}
+
+#[persian_rug(referrers)]
+struct Rug {
+    #[table(search)]
+    nodes: Node,
+    #[table(arena)]
+    labels: Label,
+}
+
+fn make_rug() -> Rug {
+    Rug {
+        nodes: Default::default(),
+        labels: Default::default(),
+    }
+}
+
+fn node(name: &str, parent: Option<Proxy<Node>>) -> Node {
+    Node {
+        name: name.to_string(),
+        parent,
+    }
+}
+
+#[test]
+fn test_replace() {
+    let mut r = make_rug();
+    let a = r.add(node("a", None));
+    let b = r.add(node("b", Some(a)));
+
+    let old = r.replace(&b, node("c", None));
+    assert_eq!(old, node("b", Some(a)));
+    assert_eq!(r.get(&b), &node("c", None));
+    assert_eq!((&r).referrers(&a), Vec::<AnyProxy>::new());
+    assert_eq!(r.search::<Node>("c"), vec![b]);
+    assert_eq!(r.search::<Node>("b"), Vec::new());
+}
+
+#[test]
+fn test_swap() {
+    let mut r = make_rug();
+    let a = r.add(node("a", None));
+    let b = r.add(node("b", Some(a)));
+    let c = r.add(node("c", None));
+
+    assert!(r.swap(&b, &c));
+    assert_eq!(r.get(&b), &node("c", None));
+    assert_eq!(r.get(&c), &node("b", Some(a)));
+    assert_eq!((&r).referrers(&a), vec![AnyProxy::from(c)]);
+    assert_eq!(r.search::<Node>("b"), vec![c]);
+
+    // Swapping a value with itself changes nothing.
+    assert!(r.swap(&a, &a));
+    assert_eq!(r.get(&a), &node("a", None));
+
+    let x = r.add(Label("x".to_string()));
+    let y = r.add(Label("y".to_string()));
+    assert!(r.swap(&x, &y));
+    assert_eq!(r.get(&x), &Label("y".to_string()));
+    assert_eq!(r.get(&y), &Label("x".to_string()));
+    assert_eq!(
+        r.get_iter::<Label>().cloned().collect::<Vec<_>>(),
+        vec![Label("y".to_string()), Label("x".to_string())]
+    );
+}
+
+#[test]
+fn test_swap_missing() {
+    let mut r = make_rug();
+    let a = r.add(node("a", None));
+    let mut other = make_rug();
+    other.add(node("b", None));
+    let b = other.add(node("c", None));
+    assert!(!r.swap(&a, &b));
+    assert_eq!(r.get(&a), &node("a", None));
+}
+
+#[test]
+fn test_table_swap() {
+    let mut t = Table::<Label, ArenaStorage<Label>>::new();
+    let n = 2 * ARENA_BLOCK_BYTES / std::mem::size_of::<Label>();
+    let ps = (0..n)
+        .map(|ix| t.push(Label(ix.to_string())))
+        .collect::<Vec<_>>();
+
+    // The first and last labels are in different blocks.
+    assert!(t.swap(&ps[0], &ps[n - 1]));
+    assert_eq!(t.get(&ps[0]), Some(&Label((n - 1).to_string())));
+    assert_eq!(t.get(&ps[n - 1]), Some(&Label("0".to_string())));
+
+    let mut other = Table::<Label>::new();
+    let missing = (0..=n).map(|_| other.push(Label(String::new()))).last();
+    assert!(!t.swap(&ps[1], &missing.unwrap()));
+    assert_eq!(t.get(&ps[1]), Some(&Label("1".to_string())));
+}
@@ -0,0 +1,66 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::storage::ArenaStorage;
+use persian_rug::{contextual, persian_rug, Context, Contextual, Table, TableOwner};
+
+#[contextual(Rug)]
+struct Foo {
+    a: i32,
+}
+
+#[contextual(Rug)]
+struct Bar {
+    b: i32,
+}
+
+mod private {
+    use super::*;
+
+    #[persian_rug]
+    pub struct Rug {
+        #[table]
+        foos: Foo,
+        #[table(arena)]
+        bars: Bar,
+    }
+
+    impl Rug {
+        pub fn new() -> Self {
+            Self {
+                foos: Default::default(),
+                bars: Default::default(),
+            }
+        }
+    }
+}
+
+use private::Rug;
+
+fn first_reserved<C, T>(context: &mut C) -> u64
+where
+    C: TableOwner<T, Table = Table<T>>,
+    T: Contextual<Context = C>,
+{
+    context.table_mut::<T>().reserve_handles(2).start
+}
+
+#[test]
+fn test_table_owner() {
+    let mut r = Rug::new();
+    let f1 = r.add(Foo { a: 1 });
+    let f2 = r.add(Foo { a: 2 });
+    r.add(Bar { b: 3 });
+
+    let foos: &Table<Foo> = r.table::<Foo>();
+    assert_eq!(foos.len(), 2);
+    let bars: &Table<Bar, ArenaStorage<Bar>> = r.table::<Bar>();
+    assert_eq!(bars.iter().map(|bar| bar.b).collect::<Vec<_>>(), vec![3]);
+
+    assert!(r.table_mut::<Foo>().mark_deleted(&f1));
+    assert_eq!(r.get_proxy_iter::<Foo>().collect::<Vec<_>>(), vec![&f2]);
+    assert!(r.table::<Foo>().is_deleted(&f1));
+
+    assert_eq!(first_reserved::<_, Foo>(&mut r), 2);
+    assert_eq!(r.add(Foo { a: 4 }).handle(), 4);
+}
@@ -0,0 +1,123 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, seed, Context, Tags};
+
+#[contextual(Rug)]
+struct Foo {
+    ix: usize,
+}
+
+#[contextual(Rug)]
+struct Bar {
+    ix: usize,
+}
+
+#[persian_rug]
+struct Rug {
+    #[table]
+    foos: Foo,
+    #[table]
+    bars: Bar,
+    tags: Tags,
+}
+
+fn make_rug() -> Rug {
+    Rug {
+        foos: Default::default(),
+        bars: Default::default(),
+        tags: Tags::new(),
+    }
+}
+
+#[test]
+fn test_tags() {
+    let mut r = make_rug();
+    let foos = seed(&mut r, 8, |ix, _| Foo { ix });
+    let bars = seed(&mut r, 8, |ix, _| Bar { ix });
+
+    for f in foos.iter().step_by(2) {
+        assert!(r.tags.tag(*f, "even"));
+    }
+    assert!(!r.tags.tag(foos[0], "even"));
+    r.tags.tag(foos[3], "pinned");
+    r.tags.tag(foos[4], "pinned");
+    r.tags.tag(bars[1], "pinned");
+
+    assert_eq!(
+        r.tags
+            .iter_tagged::<Foo>("even")
+            .map(|f| r.get(&f).ix)
+            .collect::<Vec<_>>(),
+        vec![0, 2, 4, 6]
+    );
+    assert_eq!(
+        r.tags.iter_tagged::<Foo>("pinned").collect::<Vec<_>>(),
+        vec![foos[3], foos[4]]
+    );
+    assert_eq!(
+        r.tags.iter_tagged::<Bar>("pinned").collect::<Vec<_>>(),
+        vec![bars[1]]
+    );
+    assert_eq!(r.tags.iter_tagged::<Bar>("even").count(), 0);
+    assert_eq!(r.tags.iter_tagged::<Foo>("missing").count(), 0);
+
+    assert!(r.tags.has_tag(&foos[4], "pinned"));
+    assert!(!r.tags.has_tag(&foos[5], "pinned"));
+    // Handles are per-type, so tagging bars[1] says nothing about foos[1].
+    assert!(!r.tags.has_tag(&foos[1], "pinned"));
+
+    assert_eq!(
+        r.tags.tags_of(&foos[4]).collect::<Vec<_>>(),
+        vec!["even", "pinned"]
+    );
+    assert_eq!(r.tags.tags_of(&foos[5]).count(), 0);
+
+    assert!(r.tags.untag(&foos[4], "pinned"));
+    assert!(!r.tags.untag(&foos[4], "pinned"));
+    assert!(!r.tags.untag(&bars[4], "pinned"));
+    assert_eq!(
+        r.tags.iter_tagged::<Foo>("pinned").collect::<Vec<_>>(),
+        vec![foos[3]]
+    );
+
+    r.tags.clear(&foos[2]);
+    assert_eq!(r.tags.tags_of(&foos[2]).count(), 0);
+    assert_eq!(r.tags.iter_tagged::<Foo>("even").count(), 3);
+
+    r.tags.remove_tag("pinned");
+    assert_eq!(r.tags.iter_tagged::<Foo>("pinned").count(), 0);
+    assert_eq!(r.tags.iter_tagged::<Bar>("pinned").count(), 0);
+    assert_eq!(r.tags.iter_tagged::<Foo>("even").count(), 3);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Label {
+    Hot,
+    Cold,
+}
+
+#[test]
+fn test_enum_tags() {
+    let mut r = make_rug();
+    let foos = seed(&mut r, 4, |ix, _| Foo { ix });
+
+    let mut tags = Tags::<Label>::new();
+    tags.tag(foos[0], Label::Hot);
+    tags.tag(foos[1], Label::Cold);
+    tags.tag(foos[2], Label::Hot);
+
+    assert_eq!(
+        tags.iter_tagged::<Foo>(&Label::Hot).collect::<Vec<_>>(),
+        vec![foos[0], foos[2]]
+    );
+    assert_eq!(
+        tags.tags_of(&foos[1]).copied().collect::<Vec<_>>(),
+        vec![Label::Cold]
+    );
+
+    let copy = tags.clone();
+    tags.untag(&foos[0], &Label::Hot);
+    assert!(copy.has_tag(&foos[0], &Label::Hot));
+    assert!(!tags.has_tag(&foos[0], &Label::Hot));
+}
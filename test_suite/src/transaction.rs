@@ -0,0 +1,161 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, RwLock};
+
+use persian_rug::transaction::transact;
+use persian_rug::{contextual, persian_rug, Context, Proxy};
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Warehouse)]
+struct Stock {
+    item: &'static str,
+    count: u32,
+}
+
+#[persian_rug]
+struct Warehouse(#[table] Stock);
+
+#[derive(Clone, Debug, PartialEq)]
+#[contextual(Shop)]
+struct Shelf {
+    item: &'static str,
+    count: u32,
+}
+
+#[persian_rug]
+struct Shop(#[table] Shelf);
+
+fn setup() -> (Mutex<Warehouse>, RwLock<Shop>, Proxy<Stock>, Proxy<Shelf>) {
+    let mut warehouse = Warehouse(Default::default());
+    let stock = warehouse.add(Stock {
+        item: "tea",
+        count: 10,
+    });
+    let mut shop = Shop(Default::default());
+    let shelf = shop.add(Shelf {
+        item: "tea",
+        count: 0,
+    });
+    (Mutex::new(warehouse), RwLock::new(shop), stock, shelf)
+}
+
+fn restock(
+    warehouse: &Mutex<Warehouse>,
+    shop: &RwLock<Shop>,
+    stock: Proxy<Stock>,
+    shelf: Proxy<Shelf>,
+    count: u32,
+) -> Result<(), &'static str> {
+    transact((warehouse, shop), |(warehouse, shop)| {
+        shop.get_mut(&shelf).count += count;
+        let stock = warehouse.get_mut(&stock);
+        stock.count = stock.count.checked_sub(count).ok_or("out of stock")?;
+        Ok(())
+    })
+}
+
+#[test]
+fn test_commit_and_discard() {
+    let (warehouse, shop, stock, shelf) = setup();
+
+    assert_eq!(restock(&warehouse, &shop, stock, shelf, 4), Ok(()));
+    assert_eq!(warehouse.lock().unwrap().get(&stock).count, 6);
+    assert_eq!(shop.read().unwrap().get(&shelf).count, 4);
+
+    assert_eq!(
+        restock(&warehouse, &shop, stock, shelf, 7),
+        Err("out of stock")
+    );
+    assert_eq!(warehouse.lock().unwrap().get(&stock).count, 6);
+    assert_eq!(shop.read().unwrap().get(&shelf).count, 4);
+}
+
+#[test]
+fn test_additions() {
+    let (warehouse, shop, _, _) = setup();
+
+    let added = transact((&shop, &warehouse), |(shop, warehouse)| {
+        let stock = warehouse.add(Stock {
+            item: "cake",
+            count: 1,
+        });
+        let shelf = shop.add(Shelf {
+            item: "cake",
+            count: 0,
+        });
+        Ok::<_, ()>((stock, shelf))
+    })
+    .unwrap();
+
+    assert_eq!(warehouse.lock().unwrap().get(&added.0).item, "cake");
+    assert_eq!(shop.read().unwrap().get(&added.1).item, "cake");
+}
+
+#[test]
+fn test_panic_discards() {
+    let (warehouse, shop, stock, shelf) = setup();
+
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        let _: Result<(), ()> = transact((&warehouse, &shop), |(warehouse, shop)| {
+            warehouse.get_mut(&stock).count = 0;
+            shop.get_mut(&shelf).count = 10;
+            panic!("interrupted");
+        });
+    }));
+    assert!(res.is_err());
+
+    // The locks are poisoned, but the contexts are unchanged.
+    let warehouse = warehouse.lock().unwrap_or_else(|e| e.into_inner());
+    assert_eq!(warehouse.get(&stock).count, 10);
+    let shop = shop.read().unwrap_or_else(|e| e.into_inner());
+    assert_eq!(shop.get(&shelf).count, 0);
+}
+
+#[test]
+#[should_panic(expected = "the same context cannot appear twice in a transaction")]
+fn test_same_lock_twice() {
+    let (warehouse, _, _, _) = setup();
+    let _: Result<(), ()> = transact((&warehouse, &warehouse), |_| Ok(()));
+}
+
+#[test]
+fn test_lock_order() {
+    let (warehouse, shop, stock, shelf) = setup();
+    let warehouse = Arc::new(warehouse);
+    let shop = Arc::new(shop);
+
+    // Transactions naming the locks in opposite orders must not
+    // deadlock each other.
+    let threads = (0..2)
+        .map(|thread| {
+            let warehouse = warehouse.clone();
+            let shop = shop.clone();
+            std::thread::spawn(move || {
+                for _ in 0..100 {
+                    let res: Result<(), ()> = if thread == 0 {
+                        transact((&warehouse, &shop), |(warehouse, shop)| {
+                            warehouse.get_mut(&stock).count += 1;
+                            shop.get_mut(&shelf).count += 1;
+                            Ok(())
+                        })
+                    } else {
+                        transact((shop.clone(), warehouse.clone()), |(shop, warehouse)| {
+                            warehouse.get_mut(&stock).count += 1;
+                            shop.get_mut(&shelf).count += 1;
+                            Ok(())
+                        })
+                    };
+                    res.unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert_eq!(warehouse.lock().unwrap().get(&stock).count, 210);
+    assert_eq!(shop.read().unwrap().get(&shelf).count, 200);
+}
@@ -0,0 +1,207 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::marker::PhantomData;
+
+use persian_rug::transplant::Transplant;
+use persian_rug::{contextual, persian_rug, AnyProxy, Context, Links, Proxy, Transplant};
+
+#[derive(Clone, Debug, PartialEq, Links, Transplant)]
+#[contextual(Rug)]
+struct Item {
+    name: &'static str,
+    next: Option<Proxy<Item>>,
+    tags: Vec<Proxy<Tag>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Links, Transplant)]
+#[contextual(Rug)]
+struct Tag(&'static str);
+
+#[persian_rug(transplant)]
+struct Rug(#[table] Item, #[table] Tag);
+
+#[test]
+fn test_same_type() {
+    let mut r = Rug(Default::default(), Default::default());
+    let red = r.add(Tag("red"));
+    let big = r.add(Tag("big"));
+    let a = r.add(Item {
+        name: "a",
+        next: None,
+        tags: vec![red],
+    });
+    let b = r.add(Item {
+        name: "b",
+        next: Some(a),
+        tags: vec![red, big],
+    });
+    r.get_mut(&a).next = Some(b);
+
+    let mut out = Rug(Default::default(), Default::default());
+    let existing = out.add(Tag("existing"));
+    out.add(Item {
+        name: "existing",
+        next: None,
+        tags: vec![existing],
+    });
+    let copies = r.clone_into(&mut out);
+    assert_eq!(copies.len(), 4);
+
+    let new_a: Proxy<Item> = copies.get(&a).unwrap();
+    let new_b: Proxy<Item> = copies.get(&b).unwrap();
+    let new_red: Proxy<Tag> = copies.get(&red).unwrap();
+    let new_big: Proxy<Tag> = copies.get(&big).unwrap();
+    assert_ne!(new_a, a);
+    assert_eq!(out.get(&new_a).next, Some(new_b));
+    assert_eq!(out.get(&new_b).next, Some(new_a));
+    assert_eq!(out.get(&new_b).tags, vec![new_red, new_big]);
+    assert_eq!(out.get(&new_big).0, "big");
+    assert_eq!(copies.get::<_, Tag>(&a), None);
+
+    assert_eq!(
+        out.get_iter::<Item>().map(|i| i.name).collect::<Vec<_>>(),
+        vec!["existing", "a", "b"]
+    );
+    assert_eq!(
+        out.get_iter::<Tag>().map(|t| t.0).collect::<Vec<_>>(),
+        vec!["existing", "red", "big"]
+    );
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Colour(u8, u8, u8);
+
+#[derive(Links, Transplant)]
+#[contextual(C)]
+struct Layer<C: Context> {
+    name: String,
+    colour: Colour,
+    shapes: Vec<Proxy<Shape<C>>>,
+    _marker: PhantomData<C>,
+}
+
+#[derive(Links, Transplant)]
+#[contextual(C)]
+enum Shape<C>
+where
+    C: Context,
+{
+    Circle { radius: f64, layer: Proxy<Layer<C>> },
+    Group(Box<Option<Proxy<Shape<C>>>>, [Proxy<Layer<C>>; 1]),
+    Empty,
+}
+
+#[derive(Links, Transplant)]
+#[contextual(C)]
+struct Label<C: Context, T> {
+    text: T,
+    shape: Proxy<Shape<C>>,
+}
+
+#[persian_rug(transplant)]
+struct Drawing(#[table] Layer<Drawing>, #[table] Shape<Drawing>);
+
+#[persian_rug]
+struct Document(
+    #[table] Label<Document, String>,
+    #[table] Shape<Document>,
+    #[table] Layer<Document>,
+);
+
+#[test]
+fn test_other_type() {
+    let mut d = Drawing(Default::default(), Default::default());
+    let layer = d.add(Layer {
+        name: "background".to_string(),
+        colour: Colour(1, 2, 3),
+        shapes: Vec::new(),
+        _marker: PhantomData,
+    });
+    let circle = d.add(Shape::Circle { radius: 2.0, layer });
+    let group = d.add(Shape::Group(Box::new(Some(circle)), [layer]));
+    let empty = d.add(Shape::Empty);
+    d.get_mut(&layer).shapes = vec![circle, group, empty];
+
+    let mut doc = Document(Default::default(), Default::default(), Default::default());
+    let first = doc.add(Shape::Empty);
+    let copies = d.clone_into(&mut doc);
+    assert_eq!(copies.len(), 4);
+
+    let new_layer: Proxy<Layer<Document>> = copies.get(&layer).unwrap();
+    let new_circle: Proxy<Shape<Document>> = copies.get(&circle).unwrap();
+    let new_group: Proxy<Shape<Document>> = copies.get(&group).unwrap();
+    let new_empty: Proxy<Shape<Document>> = copies.get(&empty).unwrap();
+    assert_ne!(new_empty, first);
+    assert_eq!(copies.get::<_, Shape<Drawing>>(&circle), None);
+
+    let l = doc.get(&new_layer);
+    assert_eq!(l.name, "background");
+    assert_eq!(l.colour, Colour(1, 2, 3));
+    assert_eq!(l.shapes, vec![new_circle, new_group, new_empty]);
+    match doc.get(&new_circle) {
+        Shape::Circle { radius, layer } => {
+            assert_eq!(*radius, 2.0);
+            assert_eq!(*layer, new_layer);
+        }
+        _ => panic!("the circle was not copied as a circle"),
+    }
+    match doc.get(&new_group) {
+        Shape::Group(member, layers) => {
+            assert_eq!(**member, Some(new_circle));
+            assert_eq!(*layers, [new_layer]);
+        }
+        _ => panic!("the group was not copied as a group"),
+    }
+
+    let label = doc.add(Label {
+        text: "circle".to_string(),
+        shape: new_circle,
+    });
+    let copied: Label<Document, String> = doc.get(&label).transplant(&mut |p| p);
+    assert_eq!(copied.text, "circle");
+    assert_eq!(copied.shape, new_circle);
+}
+
+#[test]
+fn test_dangling() {
+    let mut d = Drawing(Default::default(), Default::default());
+    let layer = d.add(Layer {
+        name: "gone".to_string(),
+        colour: Colour(0, 0, 0),
+        shapes: Vec::new(),
+        _marker: PhantomData,
+    });
+    let circle = d.add(Shape::Circle { radius: 1.0, layer });
+    d.delete(&layer);
+
+    let mut doc = Document(Default::default(), Default::default(), Default::default());
+    let copies = d.clone_into(&mut doc);
+    assert_eq!(copies.len(), 1);
+    let new_circle: Proxy<Shape<Document>> = copies.get(&circle).unwrap();
+    let Shape::Circle {
+        layer: new_layer, ..
+    } = doc.get(&new_circle)
+    else {
+        panic!("the circle was not copied as a circle");
+    };
+    assert_eq!(new_layer.handle(), layer.handle());
+}
+
+#[test]
+#[should_panic(expected = "proxy was transplanted to a different type")]
+fn test_wrong_type() {
+    let mut r = Rug(Default::default(), Default::default());
+    let tag = r.add(Tag("tag"));
+    let item = r.add(Item {
+        name: "item",
+        next: None,
+        tags: vec![tag],
+    });
+    let other = r.add(Item {
+        name: "other",
+        next: None,
+        tags: vec![],
+    });
+    let _: Item = r.get(&item).transplant(&mut |_| AnyProxy::from(other));
+}
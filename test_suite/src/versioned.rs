@@ -0,0 +1,178 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::borsh::{self, BorshDeserialize, BorshSerialize};
+use persian_rug::versioned::{self, Migrations, Versioned};
+use persian_rug::{contextual, persian_rug, Context, Proxy};
+
+mod v1 {
+    use super::*;
+
+    #[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+    #[borsh(crate = "persian_rug::borsh")]
+    #[contextual(Rug)]
+    pub struct Foo {
+        pub a: i32,
+    }
+
+    impl Versioned for Foo {}
+
+    #[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+    #[borsh(crate = "persian_rug::borsh")]
+    #[contextual(Rug)]
+    pub struct Gone {
+        pub name: String,
+    }
+
+    impl Versioned for Gone {}
+
+    #[persian_rug(versioned)]
+    pub struct Rug {
+        #[table]
+        pub foos: Foo,
+        #[table]
+        pub gone: Gone,
+    }
+}
+
+mod v3 {
+    use super::*;
+
+    #[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+    #[borsh(crate = "persian_rug::borsh")]
+    #[contextual(Rug)]
+    pub struct Foo {
+        pub a: i64,
+        pub next: Option<Proxy<Foo>>,
+    }
+
+    impl Versioned for Foo {
+        const VERSION: u32 = 2;
+    }
+
+    #[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+    #[borsh(crate = "persian_rug::borsh")]
+    #[contextual(Rug)]
+    pub struct Added {
+        pub b: bool,
+    }
+
+    impl Versioned for Added {}
+
+    #[persian_rug(versioned)]
+    pub struct Rug {
+        #[table(arena)]
+        pub foos: Foo,
+        #[table]
+        pub added: Added,
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "persian_rug::borsh")]
+struct FooV2 {
+    a: i64,
+}
+
+fn old() -> (v1::Rug, Vec<Proxy<v1::Foo>>) {
+    let mut r = v1::Rug {
+        foos: Default::default(),
+        gone: Default::default(),
+    };
+    let foos = (1..4).map(|a| r.add(v1::Foo { a })).collect::<Vec<_>>();
+    r.add(v1::Gone {
+        name: "gone".to_string(),
+    });
+    r.delete(&foos[1]);
+    (r, foos)
+}
+
+fn migrations() -> Migrations {
+    Migrations::new()
+        .map("foos", 0, |foo: v1::Foo| FooV2 { a: foo.a.into() })
+        .add("foos", 1, |bytes| {
+            let (next, entries) = <(u64, Vec<(u64, FooV2)>)>::try_from_slice(bytes)?;
+            let entries = entries
+                .into_iter()
+                .map(|(index, foo)| {
+                    let foo = v3::Foo {
+                        a: foo.a * 10,
+                        next: None,
+                    };
+                    (Proxy::<v3::Foo>::from_handle(index), foo)
+                })
+                .collect::<Vec<_>>();
+            borsh::to_vec(&(next, entries))
+        })
+}
+
+#[test]
+fn test_migrate() {
+    let (old, foos) = old();
+    let bytes = versioned::to_vec(&old).unwrap();
+
+    let mut r = v3::Rug {
+        foos: Default::default(),
+        added: Default::default(),
+    };
+    let added = r.add(v3::Added { b: true });
+    versioned::load(&mut r, &bytes, &migrations()).unwrap();
+
+    assert_eq!(
+        r.get_proxy_iter::<v3::Foo>()
+            .map(|p| (p.handle(), r.get(p).a))
+            .collect::<Vec<_>>(),
+        vec![(0, 10), (2, 30)]
+    );
+    assert!(r
+        .try_get(&Proxy::<v3::Foo>::from_handle(foos[1].handle()))
+        .is_err());
+    assert_eq!(r.add(v3::Foo { a: 4, next: None }).handle(), 3);
+
+    // The archive had no table of these, so it is left alone.
+    assert!(r.get(&added).b);
+
+    // Archives written by the current version need no migrations.
+    let bytes = versioned::to_vec(&r).unwrap();
+    let mut s = v3::Rug {
+        foos: Default::default(),
+        added: Default::default(),
+    };
+    versioned::load(&mut s, &bytes, &Migrations::new()).unwrap();
+    assert_eq!(versioned::to_vec(&s).unwrap(), bytes);
+}
+
+#[test]
+fn test_errors() {
+    let (old, _) = old();
+    let bytes = versioned::to_vec(&old).unwrap();
+    let mut r = v3::Rug {
+        foos: Default::default(),
+        added: Default::default(),
+    };
+
+    // Only the first of the two steps is known.
+    let partial = Migrations::new().map("foos", 0, |foo: v1::Foo| FooV2 { a: foo.a.into() });
+    let err = versioned::load(&mut r, &bytes, &partial).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "no migration for table foos from version 1"
+    );
+    assert_eq!(r.get_iter::<v3::Foo>().count(), 0);
+
+    // An archive from a later release cannot be read.
+    r.add(v3::Foo { a: 1, next: None });
+    let bytes = versioned::to_vec(&r).unwrap();
+    let mut old = v1::Rug {
+        foos: Default::default(),
+        gone: Default::default(),
+    };
+    let err = versioned::load(&mut old, &bytes, &Migrations::new()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "table foos has version 2, but only version 0 is known"
+    );
+
+    let err = versioned::load(&mut old, b"not an archive", &Migrations::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,106 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use persian_rug::{contextual, persian_rug, Context, Proxy};
+
+#[contextual(State)]
+struct Author {
+    name: String,
+}
+
+#[contextual(State, view)]
+struct Book {
+    title: String,
+    author: Proxy<Author>,
+    editor: Option<Proxy<Author>>,
+    reviewers: Vec<Proxy<Author>>,
+}
+
+#[contextual(State, view)]
+struct Pair(Proxy<Book>, u32);
+
+#[persian_rug]
+struct State(#[table] Author, #[table] Book, #[table] Pair);
+
+mod generic {
+    use persian_rug::{contextual, Context, Proxy};
+
+    #[contextual(C, view)]
+    pub struct Foo<C: Context> {
+        pub _marker: core::marker::PhantomData<C>,
+        pub a: i32,
+    }
+
+    #[contextual(C, view)]
+    pub struct Bar<C: Context> {
+        pub foo: Proxy<Foo<C>>,
+    }
+}
+
+#[persian_rug]
+struct Rug(#[table] generic::Foo<Rug>, #[table] generic::Bar<Rug>);
+
+fn describe(book: BookView<'_>) -> String {
+    format!(
+        "{} by {}, edited by {}, reviewed by {}",
+        book.title,
+        book.author.name,
+        book.editor.map(|a| a.name.as_str()).unwrap_or("nobody"),
+        book.reviewers
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>()
+            .join(" and ")
+    )
+}
+
+#[test]
+fn test_view() {
+    let mut s = State(Default::default(), Default::default(), Default::default());
+    let austen = s.add(Author {
+        name: "Austen".to_string(),
+    });
+    let bronte = s.add(Author {
+        name: "Bronte".to_string(),
+    });
+    let eliot = s.add(Author {
+        name: "Eliot".to_string(),
+    });
+    let book = s.add(Book {
+        title: "Emma".to_string(),
+        author: austen,
+        editor: None,
+        reviewers: vec![bronte, eliot],
+    });
+    let pair = s.add(Pair(book, 2));
+
+    let access = &s;
+    assert_eq!(
+        describe(s.get(&book).view(&access)),
+        "Emma by Austen, edited by nobody, reviewed by Bronte and Eliot"
+    );
+
+    s.get_mut(&book).editor = Some(eliot);
+    let access = &s;
+    let view = s.get(&pair).view(&access);
+    assert_eq!(*view.1, 2);
+    assert_eq!(view.0.title, "Emma");
+    assert_eq!(
+        describe(view.0.view(&access)),
+        "Emma by Austen, edited by Eliot, reviewed by Bronte and Eliot"
+    );
+}
+
+#[test]
+fn test_generic_view() {
+    let mut r = Rug(Default::default(), Default::default());
+    let foo = r.add(generic::Foo {
+        _marker: Default::default(),
+        a: 5,
+    });
+    let bar = r.add(generic::Bar { foo });
+    let access = &r;
+    let view: generic::BarView<'_, Rug> = r.get(&bar).view(&access);
+    assert_eq!(view.foo.a, 5);
+    assert_eq!(*view.foo.view(&access).a, 5);
+}
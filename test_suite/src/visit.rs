@@ -0,0 +1,113 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::any::type_name;
+
+use persian_rug::visit::ContextVisitor;
+use persian_rug::{contextual, persian_rug, Context, Contextual, Proxy};
+
+#[contextual(C)]
+struct Tag<C: Context> {
+    name: &'static str,
+    _marker: core::marker::PhantomData<C>,
+}
+
+#[contextual(C)]
+struct Item<C: Context + 'static> {
+    tags: Vec<Proxy<Tag<C>>>,
+}
+
+#[persian_rug]
+struct Rug<U: 'static> {
+    #[table]
+    tags: Tag<Rug<U>>,
+    #[table(arena)]
+    items: Item<Rug<U>>,
+    #[cfg(any())]
+    #[table]
+    unused: U,
+    marker: core::marker::PhantomData<U>,
+}
+
+type R = Rug<u8>;
+
+fn setup() -> R {
+    let mut r = Rug {
+        tags: Default::default(),
+        items: Default::default(),
+        marker: Default::default(),
+    };
+    let red = r.add(Tag {
+        name: "red",
+        _marker: Default::default(),
+    });
+    let blue = r.add(Tag {
+        name: "blue",
+        _marker: Default::default(),
+    });
+    r.add(Item {
+        tags: vec![red, blue],
+    });
+    r
+}
+
+#[derive(Default)]
+struct Log(Vec<(&'static str, u64)>);
+
+impl ContextVisitor<R> for Log {
+    fn object<T>(&mut self, proxy: &Proxy<T>, _object: &T)
+    where
+        R: persian_rug::Owner<T>,
+        T: Contextual<Context = R>,
+    {
+        self.0.push((type_name::<T>(), proxy.handle()));
+    }
+}
+
+#[test]
+fn test_visit_all() {
+    let r = setup();
+    let mut log = Log::default();
+    r.visit_all(&mut log);
+    assert_eq!(
+        log.0,
+        vec![
+            (type_name::<Tag<R>>(), 0),
+            (type_name::<Tag<R>>(), 1),
+            (type_name::<Item<R>>(), 0),
+        ]
+    );
+}
+
+/// Counts the objects of each table, without visiting them.
+#[derive(Default)]
+struct Sizes(Vec<(&'static str, usize)>);
+
+impl ContextVisitor<R> for Sizes {
+    fn table<T>(&mut self, context: &R)
+    where
+        R: persian_rug::Owner<T>,
+        T: Contextual<Context = R>,
+    {
+        self.0.push((
+            T::schema().name,
+            persian_rug::Owner::<T>::get_proxy_iter(context).count(),
+        ));
+    }
+
+    fn object<T>(&mut self, _proxy: &Proxy<T>, _object: &T)
+    where
+        R: persian_rug::Owner<T>,
+        T: Contextual<Context = R>,
+    {
+        panic!("objects should not be visited");
+    }
+}
+
+#[test]
+fn test_visit_tables() {
+    let r = setup();
+    let mut sizes = Sizes::default();
+    r.visit_all(&mut sizes);
+    assert_eq!(sizes.0, vec![("Tag", 2), ("Item", 1)]);
+}
@@ -0,0 +1,63 @@
+#![cfg(test)]
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+
+use persian_rug::{contextual, persian_rug, Context, Table, WeakProxy};
+
+#[contextual(Rug)]
+struct Page {
+    title: &'static str,
+    parent: Option<WeakProxy<Page>>,
+}
+
+#[persian_rug]
+struct Rug(#[table] Page);
+
+#[test]
+fn test_upgrade() {
+    let mut r = Rug(Default::default());
+    let index = r.add(Page {
+        title: "index",
+        parent: None,
+    });
+    let child = r.add(Page {
+        title: "child",
+        parent: Some(index.downgrade()),
+    });
+
+    let parent = r.get(&child).parent.unwrap();
+    assert_eq!(parent, index);
+    assert_eq!(parent.upgrade(&r).map(|p| r.get(&p).title), Some("index"));
+
+    let mut cache = BTreeMap::new();
+    cache.insert("index", WeakProxy::from(index));
+    cache.insert("child", WeakProxy::from(&child));
+
+    r.delete(&index);
+    assert_eq!(parent.upgrade(&r), None);
+    assert_eq!(cache["index"].upgrade(&r), None);
+    assert_eq!(cache["child"].upgrade(&r), Some(child));
+}
+
+#[test]
+fn test_table_upgrade() {
+    let mut table = Table::<i32>::new();
+    let first = table.push(1);
+    let weak = first.downgrade();
+    assert_eq!(table.upgrade(&weak), Some(first));
+
+    table.mark_deleted(&first);
+    assert_eq!(table.upgrade(&weak), Some(first));
+
+    table.delete(&first);
+    let second = table.insert_with_handle(first.handle(), 2).unwrap();
+    assert_eq!(table.upgrade(&weak), None);
+    assert_eq!(table.upgrade(&second.downgrade()), Some(second));
+    assert_eq!(
+        format!("{:?}", second.downgrade()),
+        "persian_rug::WeakProxy<i32> { handle: 0, generation: 1 }"
+    );
+    assert_eq!(weak.generation(), 0);
+    assert_ne!(weak, second.downgrade());
+}